@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AffixBranch } from "./AffixBranch";
 
-export interface AffixData { id: string, group: string, value: number, stats: Record<string, number>, tags: Array<string>, requirements: Array<string>, is_local: boolean, }
\ No newline at end of file
+export interface AffixData { id: string, group: string, value: number, stats: Record<string, number>, tags: Array<string>, requirements: Array<string>, excluded_tags: Array<string>, condition: string | null, is_local: boolean, tier: number, required_class: string | null, branches: Array<AffixBranch>, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StatDelta } from "./StatDelta";
+
+export interface BuildComparison { summary: Array<StatDelta>, zones: Array<StatDelta>, defenses: Array<StatDelta>, damage_types: Array<StatDelta>, }
\ No newline at end of file
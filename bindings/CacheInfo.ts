@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface CacheInfo { result_hit: boolean, context_hit: boolean, reused_layers: Array<string>, cache_key: string, }
\ No newline at end of file
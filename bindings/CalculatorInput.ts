@@ -1,9 +1,21 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CharacterConfig } from "./CharacterConfig";
+import type { IncreasedFloor } from "./IncreasedFloor";
 import type { ItemData } from "./ItemData";
+import type { ItemSetDefinition } from "./ItemSetDefinition";
 import type { MechanicDefinition } from "./MechanicDefinition";
 import type { MechanicState } from "./MechanicState";
+import type { OutputOptions } from "./OutputOptions";
+import type { OverrideGroup } from "./OverrideGroup";
+import type { OverrideValue } from "./OverrideValue";
 import type { PreviewSlot } from "./PreviewSlot";
+import type { RecentModel } from "./RecentModel";
+import type { SanitizePolicy } from "./SanitizePolicy";
 import type { SkillData } from "./SkillData";
+import type { SlotConflictPolicy } from "./SlotConflictPolicy";
+import type { SlotDefinition } from "./SlotDefinition";
+import type { StackMode } from "./StackMode";
+import type { SustainModel } from "./SustainModel";
 import type { TargetConfig } from "./TargetConfig";
 
-export interface CalculatorInput { context_flags: Record<string, boolean>, context_values: Record<string, number>, target_config: TargetConfig, items: Array<ItemData>, active_skill: SkillData, support_skills: Array<SkillData>, global_overrides: Record<string, number>, preview_slot: PreviewSlot | null, mechanic_states: Array<MechanicState>, mechanic_definitions: Array<MechanicDefinition>, }
\ No newline at end of file
+export interface CalculatorInput { context_flags: Record<string, boolean>, context_values: Record<string, number>, target_config: TargetConfig, items: Array<ItemData>, active_skill: SkillData, support_skills: Array<SkillData>, global_overrides: Record<string, OverrideValue>, preview_slot: PreviewSlot | null, mechanic_states: Array<MechanicState>, mechanic_definitions: Array<MechanicDefinition>, increased_floor: IncreasedFloor, auras: Array<SkillData>, exclude_invalid_supports: boolean, stack_mode: StackMode, recent_model: RecentModel | null, disabled_mechanics: Array<string>, output_options: OutputOptions, sustain_model: SustainModel | null, strict_stat_keys: boolean, sanitize_policy: SanitizePolicy, slot_definitions: Array<SlotDefinition>, slot_conflict_policy: SlotConflictPolicy, item_sets: Array<ItemSetDefinition>, character: CharacterConfig | null, exclude_unusable_items: boolean, override_groups: Array<OverrideGroup>, context_strings: Record<string, string>, }
\ No newline at end of file
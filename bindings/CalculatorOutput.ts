@@ -1,6 +1,10 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CacheInfo } from "./CacheInfo";
 import type { DamageBreakdown } from "./DamageBreakdown";
+import type { DataVersion } from "./DataVersion";
+import type { Diagnostic } from "./Diagnostic";
 import type { EhpSeries } from "./EhpSeries";
 import type { TraceEntry } from "./TraceEntry";
+import type { WeaponSummary } from "./WeaponSummary";
 
-export interface CalculatorOutput { dps_theoretical: number, dps_effective: number, hit_damage: number, rate: number, crit_chance: number, crit_multiplier: number, hit_chance: number, ehp_series: EhpSeries, damage_breakdown: DamageBreakdown, debug_trace: Array<TraceEntry>, }
\ No newline at end of file
+export interface CalculatorOutput { dps_theoretical: number, dps_effective: number, dps_sustained: number, hit_damage: number, rate: number, crit_chance: number, crit_multiplier: number, hit_chance: number, ehp_series: EhpSeries, damage_breakdown: DamageBreakdown, debug_trace: Array<TraceEntry>, data_version: DataVersion, engine_version: string, input_hash: string, mana_cost: number, warnings: Array<Diagnostic>, cache_info: CacheInfo | null, weapon_summaries: Array<WeaponSummary>, }
\ No newline at end of file
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DataVersion } from "./DataVersion";
+import type { ExtendedCacheStats } from "./ExtendedCacheStats";
+import type { StateEntry } from "./StateEntry";
+
+export interface CalculatorState { engine_version: string, data_version: DataVersion, cache_stats: ExtendedCacheStats, entries: Array<StateEntry>, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConditionFactor } from "./ConditionFactor";
+
+export interface ConditionProvenance { condition: string, passed: boolean, decided_by: Array<ConditionFactor>, }
\ No newline at end of file
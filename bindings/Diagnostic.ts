@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DiagnosticCode } from "./DiagnosticCode";
+
+export interface Diagnostic { code: DiagnosticCode, message: string, message_key: string, message_params: Record<string, string>, }
\ No newline at end of file
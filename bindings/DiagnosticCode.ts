@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DiagnosticCode = "invalid_support" | "mechanic_suppressed" | "stacks_not_converged" | "value_out_of_range" | "slot_conflict" | "affix_group_conflict" | "class_restricted" | "requirement_unmet" | "stat_key_aliased" | "unknown_stat_key" | "unit_normalized" | "conversion_scaled" | "crit_capped" | "resistance_capped" | "deprecated_tag" | "unknown_tag" | "unknown_condition_key" | "stat_key_overflow";
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CacheStats } from "./CacheStats";
+
+export interface ExtendedCacheStats { result_cache: CacheStats, context_cache: CacheStats, }
\ No newline at end of file
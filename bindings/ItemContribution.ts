@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EhpSeries } from "./EhpSeries";
+import type { SlotType } from "./SlotType";
+
+export interface ItemContribution { item_id: string, slot: SlotType, dps_without: number, dps_delta: number, dps_delta_percent: number, ehp_delta: EhpSeries, }
\ No newline at end of file
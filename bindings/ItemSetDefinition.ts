@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SetBonus } from "./SetBonus";
+
+export interface ItemSetDefinition { id: string, display_name: string, bonuses: Array<SetBonus>, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface MechanicContribution { mechanic_id: string, stacks: number, dps_without: number, dps_delta: number, dps_delta_percent: number, }
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface MechanicDefinition { id: string, display_name: string, category: string, tag_key: string, default_max_stacks: number, base_effect_per_stack: Record<string, number>, description: string, }
\ No newline at end of file
+export interface MechanicDefinition { id: string, display_name: string, category: string, tag_key: string, default_max_stacks: number, base_effect_per_stack: Record<string, number>, description: string, aura_scalable: boolean, generation_rate: number | null, duration: number | null, exclusive_group: string | null, cost_per_use: number | null, stacks_per_hit: number | null, stacks_per_kill: number | null, }
\ No newline at end of file
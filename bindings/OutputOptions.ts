@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PercentScale } from "./PercentScale";
+import type { RoundingMode } from "./RoundingMode";
+
+export interface OutputOptions { decimal_places: number | null, rounding: RoundingMode, percent_scale: PercentScale, }
\ No newline at end of file
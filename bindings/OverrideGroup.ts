@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OverrideValue } from "./OverrideValue";
+
+export interface OverrideGroup { name: string, enabled: boolean, overrides: Record<string, OverrideValue>, }
\ No newline at end of file
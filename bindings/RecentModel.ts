@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface RecentModel { window: number, rotation_uptime: number, kill_chance_per_hit: number, threshold: number, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TargetConfig } from "./TargetConfig";
+
+export interface ScenarioDelta { name: string, context_flags: Record<string, boolean>, context_values: Record<string, number>, target_config: TargetConfig | null, mechanic_stacks: Record<string, number>, }
\ No newline at end of file
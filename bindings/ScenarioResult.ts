@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EhpSeries } from "./EhpSeries";
+
+export interface ScenarioResult { name: string, dps_theoretical: number, dps_effective: number, hit_damage: number, dps_delta_percent: number, ehp_series: EhpSeries, context_reused: boolean, }
\ No newline at end of file
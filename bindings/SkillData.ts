@@ -1,6 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConditionalTag } from "./ConditionalTag";
+import type { PhaseTag } from "./PhaseTag";
 import type { SkillLevelData } from "./SkillLevelData";
+import type { SkillPart } from "./SkillPart";
 import type { SkillScalingRule } from "./SkillScalingRule";
 import type { SkillType } from "./SkillType";
 
-export interface SkillData { id: string, skill_type: SkillType, damage_type: string | null, is_attack: boolean, level: number, base_damage: Record<string, number>, base_time: number, cooldown: number | null, mana_cost: number, effectiveness: number, tags: Array<string>, stats: Record<string, number>, injected_tags: Array<string>, mana_multiplier: number, level_data: SkillLevelData | null, scaling_rules: Array<SkillScalingRule>, }
\ No newline at end of file
+export interface SkillData { id: string, skill_type: SkillType, damage_type: string | null, is_attack: boolean, level: number, base_damage: Record<string, number>, base_time: number, cooldown: number | null, mana_cost: number, effectiveness: number, tags: Array<string>, stats: Record<string, number>, injected_tags: Array<string>, conditional_tags: Array<ConditionalTag>, support_requirements: Array<string>, support_excluded_tags: Array<string>, phase_tags: Array<PhaseTag>, quality: number, quality_stats: Record<string, number>, parts: Array<SkillPart>, selected_part: number | null, mana_multiplier: number, mana_multiplier_per_level: number, stats_per_level: Record<string, number>, level_data: SkillLevelData | null, scaling_rules: Array<SkillScalingRule>, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface SkillPart { id: string, base_damage: Record<string, number>, effectiveness: number | null, tags: Array<string>, stats: Record<string, number>, weight: number, }
\ No newline at end of file
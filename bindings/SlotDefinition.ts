@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface SlotDefinition { id: string, display_name: string, capacity: number, conflict_group: string | null, blocked_by_two_handed: boolean, }
\ No newline at end of file
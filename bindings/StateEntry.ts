@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CalculatorInput } from "./CalculatorInput";
+import type { CalculatorOutput } from "./CalculatorOutput";
+
+export interface StateEntry { input: CalculatorInput, output: CalculatorOutput, }
\ No newline at end of file
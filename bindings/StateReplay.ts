@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ReplayEntry } from "./ReplayEntry";
+
+export interface StateReplay { engine_version_matches: boolean, data_version_matches: boolean, entries: Array<ReplayEntry>, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface SustainModel { mana_regen: number | null, realistic_buffs: boolean, }
\ No newline at end of file
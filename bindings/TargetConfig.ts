@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TargetDebuff } from "./TargetDebuff";
 
-export interface TargetConfig { level: number, defense_constant: number, resistances: Record<string, number>, generic_dr: number, armor: number, evasion: number, }
\ No newline at end of file
+export interface TargetConfig { level: number, defense_constant: number, resistances: Record<string, number>, generic_dr: number, armor: number, evasion: number, debuffs: Array<TargetDebuff>, }
\ No newline at end of file
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EhpSeries } from "./EhpSeries";
+import type { Toggle } from "./Toggle";
+
+export interface ToggleScenario { flipped: Array<Toggle>, dps_theoretical: number, dps_effective: number, dps_delta_percent: number, ehp_series: EhpSeries, }
\ No newline at end of file
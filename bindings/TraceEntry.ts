@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface TraceEntry { phase: string, description: string, values: Record<string, number>, matched_tags: Array<string>, }
\ No newline at end of file
+export interface TraceEntry { phase: string, description: string, values: Record<string, number>, matched_tags: Array<string>, message_key: string, message_params: Record<string, string>, }
\ No newline at end of file
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DamageRange } from "./DamageRange";
+import type { SlotType } from "./SlotType";
+
+export interface WeaponSummary { item_id: string, slot: SlotType, phys_range: DamageRange, elemental_average: number, attack_speed: number, crit_chance: number, phys_dps: number, elemental_dps: number, total_dps: number, }
\ No newline at end of file
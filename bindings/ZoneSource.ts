@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConditionProvenance } from "./ConditionProvenance";
 
-export interface ZoneSource { source: string, value: number, stat_key: string, }
\ No newline at end of file
+export interface ZoneSource { source: string, value: number, stat_key: string, label_key: string, label_params: Record<string, string>, condition: ConditionProvenance | null, }
\ No newline at end of file
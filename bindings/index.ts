@@ -22,10 +22,113 @@ export interface CalculatorInput {
   active_skill: SkillData;
   /** 辅助技能列表 */
   support_skills: SkillData[];
-  /** 全局属性覆盖 */
-  global_overrides: Record<string, number>;
+  /** 光环技能（受光环效果加成缩放） */
+  auras?: SkillData[];
+  /** 全局属性覆盖（数值或引用其他属性的表达式，如 "fighting_will * 0.002"） */
+  global_overrides: Record<string, OverrideValue>;
   /** 预览槽位 */
   preview_slot?: PreviewSlot;
+  /** 增伤总和下限策略（默认 clamp_zero） */
+  increased_floor?: IncreasedFloor;
+  /** 是否排除无法辅助主技能的辅助技能（默认仅警告） */
+  exclude_invalid_supports?: boolean;
+  /** 机制层数取值模式（默认 manual） */
+  stack_mode?: StackMode;
+  /** 近期事件模型（按速率/命中/暴击推导近期命中、暴击、击杀） */
+  recent_model?: RecentModel;
+  /** 强制关闭的机制 ID（即使由装备/技能授予也不生效） */
+  disabled_mechanics?: string[];
+  /** 输出数值格式（默认不舍入、比例类字段为 0–1） */
+  output_options?: OutputOptions;
+  /** 持续输出模型（未设置时持续 DPS 等于理论 DPS） */
+  sustain_model?: SustainModel;
+  /** 严格模式：未知的 mod.* / conv.* / extra.* 属性键视为错误（默认仅给出诊断） */
+  strict_stat_keys?: boolean;
+  /** 越界输入（负的攻击时间、超过 100% 的格挡率/暴击率等）的处理策略（默认 clamp） */
+  sanitize_policy?: SanitizePolicy;
+  /** 槽位定义（覆盖或新增内置槽位，如赛季槽位） */
+  slot_definitions?: SlotDefinition[];
+  /** 槽位冲突的处理策略（默认 prefer_preview，被移除的装备记入 warnings） */
+  slot_conflict_policy?: SlotConflictPolicy;
+  /** 套装定义（按装备的 set_id 统计件数，加成来源记为 set:<套装 ID>） */
+  item_sets?: ItemSetDefinition[];
+  /** 角色配置（按等级与职业推导基础生命/魔力） */
+  character?: CharacterConfig | null;
+  /** 是否排除需求未满足的装备（默认仅诊断） */
+  exclude_unusable_items?: boolean;
+  /** 命名的全局覆盖分组（启用的分组与 global_overrides 叠加） */
+  override_groups?: OverrideGroup[];
+  /** 字符串上下文（如 weapon_type: "claw"，用于 key == "value" 条件） */
+  context_strings?: Record<string, string>;
+}
+
+/** 角色配置 */
+export interface CharacterConfig {
+  /** 角色等级（默认 1） */
+  level?: number;
+  /** 职业 ID（berserker / mage / ranger，须已登记；未指定时使用通用成长） */
+  class?: string | null;
+}
+
+/** 命名的全局覆盖分组（配置预设，如 "bossing buffs"、"map mods"） */
+export interface OverrideGroup {
+  /** 分组名称（须唯一） */
+  name: string;
+  /** 是否启用（默认 true） */
+  enabled?: boolean;
+  /** 分组内的属性覆盖（语法同 global_overrides） */
+  overrides: Record<string, OverrideValue>;
+}
+
+/** 全局属性覆盖值：数值或表达式（支持 + - * /、括号；标识符取机制层数、上下文数值或聚合后的属性值） */
+export type OverrideValue = number | string;
+
+/** 增伤总和低于 -100% 时的处理策略 */
+export type IncreasedFloor = 'clamp_zero' | 'allow_negative';
+
+/** 机制层数取值模式：手动层数 / 满层 / 稳态平均层数 */
+export type StackMode = 'manual' | 'max' | 'realistic';
+
+/** 越界输入处理策略：钳制到合理范围 / 视为输入错误 / 保留原值（均给出诊断） */
+export type SanitizePolicy = 'clamp' | 'reject' | 'keep';
+
+/** 槽位冲突处理策略：预览与双手武器优先、其余先到先得 / 视为输入错误 / 保留理论 DPS 更高的一方 */
+export type SlotConflictPolicy = 'prefer_preview' | 'error' | 'prefer_higher_dps';
+
+/** 持续输出模型 */
+export interface SustainModel {
+  /** 每秒可用于该技能的魔力回复（不设置则不计魔力限制） */
+  mana_regen?: number;
+  /** 增益是否按稳态平均层数计算（默认 true） */
+  realistic_buffs?: boolean;
+}
+
+/** 输出数值格式 */
+export interface OutputOptions {
+  /** 保留小数位（不设置则不舍入） */
+  decimal_places?: number;
+  /** 舍入方式（默认 half_up） */
+  rounding?: RoundingMode;
+  /** 暴击率、暴击伤害、命中率、增伤总和等比例类字段的刻度（默认 fraction，即 0–1） */
+  percent_scale?: PercentScale;
+}
+
+/** 舍入方式：四舍五入 / 银行家舍入 / 向下 / 向上 / 向零截断 */
+export type RoundingMode = 'half_up' | 'half_even' | 'floor' | 'ceil' | 'truncate';
+
+/** 比例类字段刻度：0–1 / 0–100 */
+export type PercentScale = 'fraction' | 'percent';
+
+/** 近期事件模型 */
+export interface RecentModel {
+  /** "近期"窗口（秒，默认 4） */
+  window?: number;
+  /** 技能循环中实际使用该技能的时间占比（默认 1） */
+  rotation_uptime?: number;
+  /** 每次命中击杀敌人的概率 */
+  kill_chance_per_hit?: number;
+  /** 概率达到该阈值时视为状态成立（默认 0.5） */
+  threshold?: number;
 }
 
 /** 预览槽位 */
@@ -48,6 +151,18 @@ export interface TargetConfig {
   armor: number;
   /** 闪避值 */
   evasion: number;
+  /** 目标减益（诅咒、曝露、感电、地图词缀） */
+  debuffs: TargetDebuff[];
+}
+
+/** 目标减益 */
+export interface TargetDebuff {
+  /** ID */
+  id: string;
+  /** 分类 */
+  category: string;
+  /** 属性效果（敌人侧键） */
+  stats: Record<string, number>;
 }
 
 /** 槽位类型 */
@@ -67,7 +182,23 @@ export type SlotType =
   | 'memory3'
   | 'memory4'
   | 'memory5'
-  | 'memory6';
+  | 'memory6'
+  /** 数据驱动的槽位 ID（如 pact_spirit_1，规则见 SlotDefinition） */
+  | (string & {});
+
+/** 槽位定义（覆盖或新增内置槽位） */
+export interface SlotDefinition {
+  /** 槽位 ID */
+  id: string;
+  /** 显示名称 */
+  display_name?: string;
+  /** 单个槽位可装备的件数（默认 1） */
+  capacity?: number;
+  /** 冲突组：同组槽位同时只能有一个被占用 */
+  conflict_group?: string;
+  /** 装备双手武器时不可用（如副手） */
+  blocked_by_two_handed?: boolean;
+}
 
 // ============================================================
 // 装备类型
@@ -91,13 +222,49 @@ export interface ItemData {
   tags: string[];
   /** 是否侵蚀 */
   is_corrupted: boolean;
+  /** 所属套装 ID */
+  set_id?: string;
+  /** 装备品质（%，提高该装备的护甲/护盾/闪避或武器物理伤害） */
+  quality?: number;
+  /** 装备需求（未提供时按 base_type 从基底表补全） */
+  requirements?: ItemRequirements | null;
+}
+
+/** 装备需求 */
+export interface ItemRequirements {
+  /** 需求等级 */
+  level?: number;
+  /** 力量需求 */
+  strength?: number;
+  /** 敏捷需求 */
+  dexterity?: number;
+  /** 智慧需求 */
+  intelligence?: number;
+}
+
+/** 套装定义 */
+export interface ItemSetDefinition {
+  /** 套装 ID */
+  id: string;
+  /** 显示名称 */
+  display_name?: string;
+  /** 各档套装加成（已装备件数达到要求的各档均生效） */
+  bonuses: SetBonus[];
+}
+
+/** 套装加成档位 */
+export interface SetBonus {
+  /** 所需件数 */
+  pieces: number;
+  /** 加成属性 */
+  stats: Record<string, number>;
 }
 
 /** 词缀数据 */
 export interface AffixData {
   /** ID */
   id: string;
-  /** 词缀组 */
+  /** 词缀组（同组互斥，同一装备上只保留等级最高者） */
   group: string;
   /** 当前数值 */
   value: number;
@@ -107,8 +274,24 @@ export interface AffixData {
   tags: string[];
   /** 生效条件 */
   requirements: string[];
+  /** 排除标签 */
+  excluded_tags: string[];
   /** 是否局部 */
   is_local: boolean;
+  /** 词缀等级（数值越大越高，默认 0） */
+  tier?: number;
+  /** 限定职业（角色职业不符时不生效） */
+  required_class?: string | null;
+  /** 条件分支（按顺序取首个条件满足的分支属性，均不满足时使用 stats） */
+  branches?: AffixBranch[];
+}
+
+/** 词缀条件分支 */
+export interface AffixBranch {
+  /** 分支条件表达式 */
+  condition: string;
+  /** 条件满足时的属性效果 */
+  stats: Record<string, number>;
 }
 
 // ============================================================
@@ -143,8 +326,63 @@ export interface SkillData {
   stats: Record<string, number>;
   /** 注入标签 */
   injected_tags: string[];
+  /** 条件注入标签 */
+  conditional_tags?: ConditionalTag[];
+  /** 辅助技能：主技能须具备的标签 */
+  support_requirements?: string[];
+  /** 辅助技能：主技能不能具备的标签 */
+  support_excluded_tags?: string[];
+  /** 阶段性标签 */
+  phase_tags?: PhaseTag[];
+  /** 品质（%） */
+  quality?: number;
+  /** 每 1% 品质提供的属性 */
+  quality_stats?: Record<string, number>;
+  /** 技能部分 */
+  parts?: SkillPart[];
+  /** 选中的部分索引（为空时按权重合并） */
+  selected_part?: number;
   /** 魔力倍率 */
   mana_multiplier: number;
+  /** 每级魔力倍率变化 */
+  mana_multiplier_per_level?: number;
+  /** 每级额外属性 */
+  stats_per_level?: Record<string, number>;
+}
+
+/** 技能部分 */
+export interface SkillPart {
+  /** 部分 ID */
+  id: string;
+  /** 基础伤害 */
+  base_damage: Record<string, number>;
+  /** 有效系数（覆盖技能） */
+  effectiveness?: number;
+  /** 追加标签 */
+  tags: string[];
+  /** 额外属性 */
+  stats: Record<string, number>;
+  /** 合并权重 */
+  weight: number;
+}
+
+/** 计算阶段 */
+export type CalcPhase = 'conversion' | 'modification';
+
+/** 仅在某一计算阶段生效的标签 */
+export interface PhaseTag {
+  /** 标签名 */
+  tag: string;
+  /** 生效阶段 */
+  phase: CalcPhase;
+}
+
+/** 条件注入标签 */
+export interface ConditionalTag {
+  /** 注入的标签 */
+  tag: string;
+  /** 注入条件（如 has_tag("Tag_Lightning")） */
+  condition: string;
 }
 
 /** 技能类型 */
@@ -160,6 +398,8 @@ export interface CalculatorOutput {
   dps_theoretical: number;
   /** 有效 DPS */
   dps_effective: number;
+  /** 持续 DPS（受魔力、技能循环占比与增益稳态层数限制） */
+  dps_sustained: number;
   /** 单次命中 */
   hit_damage: number;
   /** 攻击速率 */
@@ -176,6 +416,146 @@ export interface CalculatorOutput {
   damage_breakdown: DamageBreakdown;
   /** 调试追踪 */
   debug_trace: TraceEntry[];
+  /** 数据快照 */
+  data_version: DataVersion;
+  /** 计算引擎版本 */
+  engine_version: string;
+  /** 计算输入指纹（规范化输入的 FNV-1a，跨平台稳定） */
+  input_hash: string;
+  /** 魔力消耗（含辅助技能倍率） */
+  mana_cost: number;
+  /** 诊断（非致命问题：无效辅助、未知属性键、转化缩放、暴击率/抗性截断等） */
+  warnings: Diagnostic[];
+  /** 缓存溯源（仅 calculate_cached / calculate_diff 返回） */
+  cache_info: CacheInfo | null;
+  /** 各把武器的面板 DPS（与主技能无关） */
+  weapon_summaries: WeaponSummary[];
+}
+
+/** 武器面板 DPS（仅由该武器自身的局部属性计算） */
+export interface WeaponSummary {
+  /** 装备 ID */
+  item_id: string;
+  /** 装备槽位 */
+  slot: SlotType;
+  /** 物理伤害范围（含局部增伤与品质） */
+  phys_range: DamageRange;
+  /** 元素伤害平均值（火焰 + 冰冷 + 闪电） */
+  elemental_average: number;
+  /** 攻击速度 */
+  attack_speed: number;
+  /** 局部暴击率 */
+  crit_chance: number;
+  /** 物理 DPS */
+  phys_dps: number;
+  /** 元素 DPS */
+  elemental_dps: number;
+  /** 总 DPS */
+  total_dps: number;
+}
+
+/** 诊断类别 */
+export type DiagnosticCode =
+  | 'invalid_support'
+  | 'mechanic_suppressed'
+  | 'stacks_not_converged'
+  | 'value_out_of_range'
+  | 'slot_conflict'
+  | 'affix_group_conflict'
+  | 'class_restricted'
+  | 'requirement_unmet'
+  | 'stat_key_aliased'
+  | 'unknown_stat_key'
+  | 'unit_normalized'
+  | 'conversion_scaled'
+  | 'crit_capped'
+  | 'resistance_capped'
+  | 'deprecated_tag'
+  | 'unknown_tag'
+  | 'unknown_condition_key'
+  | 'stat_key_overflow';
+
+/** 计算诊断（message 按默认语言渲染，可用 renderMessage 按 message_key 本地化） */
+export interface Diagnostic {
+  code: DiagnosticCode;
+  message: string;
+  /** 本地化键（如 `diagnostic.crit_capped`） */
+  message_key: string;
+  /** 本地化参数 */
+  message_params: Record<string, string>;
+}
+
+/** 缓存溯源 */
+export interface CacheInfo {
+  /** 结果缓存是否命中 */
+  result_hit: boolean;
+  /** PreparedContext 缓存是否命中 */
+  context_hit: boolean;
+  /** 复用的缓存层（result / context） */
+  reused_layers: string[];
+  /** 输入缓存键指纹 */
+  cache_key: string;
+}
+
+/** 数据快照标识 */
+export interface DataVersion {
+  /** 标签注册表版本 */
+  version: string;
+  /** 标签注册表校验和 */
+  checksum: string;
+}
+
+/** 缓存统计 */
+export interface CacheStats {
+  capacity: number;
+  size: number;
+  hits: number;
+  misses: number;
+  hit_rate: number;
+}
+
+/** 扩展缓存统计 */
+export interface ExtendedCacheStats {
+  result_cache: CacheStats;
+  context_cache: CacheStats;
+}
+
+/** 一条计算记录 */
+export interface StateEntry {
+  input: CalculatorInput;
+  /** 已按输出选项格式化 */
+  output: CalculatorOutput;
+}
+
+/**
+ * 计算器状态快照（export_state 导出，import_state 重放），用于问题反馈
+ *
+ * 须先调用 set_state_recording(true)；只记录 calculate_cached 的计算
+ */
+export interface CalculatorState {
+  engine_version: string;
+  data_version: DataVersion;
+  cache_stats: ExtendedCacheStats;
+  /** 最近的计算记录（从旧到新） */
+  entries: StateEntry[];
+}
+
+/** 单条记录的重放结果 */
+export interface ReplayEntry {
+  dps_recorded: number;
+  /** 重放失败时为 null */
+  dps_replayed: number | null;
+  matches: boolean;
+  /** 重放失败的错误信息 */
+  error: string | null;
+}
+
+/** 状态重放报告 */
+export interface StateReplay {
+  engine_version_matches: boolean;
+  data_version_matches: boolean;
+  /** 各条记录重放与记录的理论 DPS 对比 */
+  entries: ReplayEntry[];
 }
 
 /** EHP 系列 */
@@ -199,6 +579,14 @@ export interface DamageBreakdown {
   total_more: number;
   /** 转化后分布 */
   after_conversion: Record<string, DamageWithHistory>;
+  /** 各类型单次命中伤害范围（已应用 Inc/More，不含暴击） */
+  ranges: Record<string, DamageRange>;
+}
+
+/** 伤害范围 */
+export interface DamageRange {
+  min: number;
+  max: number;
 }
 
 /** 带历史的伤害 */
@@ -212,7 +600,134 @@ export interface TraceEntry {
   phase: string;
   description: string;
   values: Record<string, number>;
+  /** 标签匹配记录（如 `fire: dmg.phys [hit: Tag_Physical]`） */
   matched_tags: string[];
+  /** 本地化键（如 `trace.critical`） */
+  message_key: string;
+  /** 本地化参数 */
+  message_params: Record<string, string>;
+}
+
+/** 单个机制的 DPS 贡献（关闭该机制后重算的边际差值） */
+export interface MechanicContribution {
+  mechanic_id: string;
+  /** 当前生效层数 */
+  stacks: number;
+  /** 关闭该机制后的理论 DPS */
+  dps_without: number;
+  /** DPS 贡献（当前 DPS - 关闭后 DPS） */
+  dps_delta: number;
+  /** DPS 贡献百分比（如 12.5 表示 +12.5%） */
+  dps_delta_percent: number;
+}
+
+/** 单项数值对比（B 相对 A） */
+export interface StatDelta {
+  /** 数值键（如 dps_theoretical、crit_zone、fire） */
+  key: string;
+  a: number;
+  b: number;
+  /** 差值（B - A） */
+  delta: number;
+  /** 差值百分比（相对 A，如 12.5 表示 +12.5%） */
+  delta_percent: number;
+}
+
+/** 两套构筑的完整对比 */
+export interface BuildComparison {
+  /** 总览（DPS、单次伤害、速率、暴击、命中、魔力消耗） */
+  summary: StatDelta[];
+  /** 各伤害乘区 */
+  zones: StatDelta[];
+  /** 防御（各元素 EHP） */
+  defenses: StatDelta[];
+  /** 各伤害类型的最终伤害 */
+  damage_types: StatDelta[];
+}
+
+/** 单件装备的贡献（移除该装备后重算的边际差值） */
+export interface ItemContribution {
+  item_id: string;
+  slot: SlotType;
+  /** 移除该装备后的理论 DPS */
+  dps_without: number;
+  /** DPS 贡献（当前 DPS - 移除后 DPS） */
+  dps_delta: number;
+  /** DPS 贡献百分比（如 12.5 表示 +12.5%） */
+  dps_delta_percent: number;
+  /** 各元素 EHP 贡献（当前 EHP - 移除后 EHP） */
+  ehp_delta: EhpSeries;
+}
+
+/** 单个辅助技能的贡献（移除该辅助后重算的边际差值） */
+export interface SupportContribution {
+  support_id: string;
+  /** 按等级缩放后的魔力倍率 */
+  mana_multiplier: number;
+  /** 移除该辅助后的理论 DPS */
+  dps_without: number;
+  /** DPS 贡献（当前 DPS - 移除后 DPS） */
+  dps_delta: number;
+  /** DPS 贡献百分比（如 12.5 表示 +12.5%） */
+  dps_delta_percent: number;
+  /** 魔力消耗增量（当前消耗 - 移除后消耗） */
+  mana_cost_delta: number;
+}
+
+/** 假设分析开关类型：上下文标志 / 机制激活状态 / 全局覆盖分组 */
+export type ToggleKind = 'flag' | 'mechanic' | 'override_group';
+
+/** 假设分析开关 */
+export interface Toggle {
+  kind: ToggleKind;
+  /** 标志名、机制 ID 或覆盖分组名称 */
+  key: string;
+}
+
+/** 假设分析的单个场景结果 */
+export interface ToggleScenario {
+  /** 本场景相对当前配置翻转的开关（空表示当前配置） */
+  flipped: Toggle[];
+  dps_theoretical: number;
+  dps_effective: number;
+  /** 相对当前配置的理论 DPS 变化百分比 */
+  dps_delta_percent: number;
+  ehp_series: EhpSeries;
+}
+
+/** 多场景对比的单个场景差异（相对基准输入） */
+export interface ScenarioDelta {
+  /** 场景名称 */
+  name: string;
+  /** 覆盖的上下文标志 */
+  context_flags?: Record<string, boolean>;
+  /** 覆盖的上下文数值 */
+  context_values?: Record<string, number>;
+  /** 替换的目标配置（如 Boss / 普通怪预设） */
+  target_config?: TargetConfig | null;
+  /** 覆盖的机制层数（机制 ID → 层数，0 表示关闭） */
+  mechanic_stacks?: Record<string, number>;
+}
+
+/** 多场景对比的单个场景结果 */
+export interface ScenarioResult {
+  name: string;
+  dps_theoretical: number;
+  dps_effective: number;
+  hit_damage: number;
+  /** 相对基准输入的理论 DPS 变化百分比 */
+  dps_delta_percent: number;
+  ehp_series: EhpSeries;
+  /** 是否复用了基准输入的预处理上下文（仅替换目标配置的场景） */
+  context_reused: boolean;
+}
+
+/** 参数扫描曲线上的一个点 */
+export interface SweepPoint {
+  /** 扫描参数取值 */
+  value: number;
+  dps_theoretical: number;
+  dps_effective: number;
 }
 
 // ============================================================
@@ -231,6 +746,7 @@ export function createDefaultInput(): CalculatorInput {
       generic_dr: 0,
       armor: 0,
       evasion: 0,
+      debuffs: [],
     },
     items: [],
     active_skill: {
@@ -267,9 +783,33 @@ export function createDefaultTarget(): TargetConfig {
     generic_dr: 0,
     armor: 0,
     evasion: 0,
+    debuffs: [],
   };
 }
 
+/**
+ * 按语言表渲染本地化文本（与 Rust 侧 `LocaleTable::render` 规则一致）
+ *
+ * `strings` 来自 `get_locale_strings(locale)`；参数值存在 `<参数名>.<值>` 条目时使用其翻译
+ */
+export function renderMessage(
+  strings: Record<string, string>,
+  key: string,
+  params: Record<string, string>,
+): string {
+  const template = strings[key];
+  if (template === undefined) {
+    return key;
+  }
+  return template.replace(/\{([^{}]*)\}/g, (match, name: string) => {
+    const value = params[name];
+    if (value === undefined) {
+      return match;
+    }
+    return strings[`${name}.${value}`] ?? value;
+  });
+}
+
 /** 伤害类型列表 */
 export const DAMAGE_TYPES = ['physical', 'fire', 'cold', 'lightning', 'chaos'] as const;
 export type DamageType = typeof DAMAGE_TYPES[number];
@@ -68,6 +68,8 @@ CREATE TABLE items_meta (
     display_name VARCHAR(128) NOT NULL,            -- 显示名称
     slot VARCHAR(32) NOT NULL,                     -- 槽位：weapon_main, weapon_off, helmet, chest, gloves, boots, amulet, ring, belt
     is_two_handed BOOLEAN DEFAULT FALSE,           -- 是否为双手武器（用于互斥判断）
+    attack_time DECIMAL(6,3),                      -- 武器基础攻击时间（秒/次），非武器为 NULL
+    crit_chance DECIMAL(6,4),                      -- 武器基础暴击率，非武器为 NULL
     implicit_stats JSONB DEFAULT '{}',             -- 基底固有词缀 JSONB，如 {"dmg.phys.min": 10, "dmg.phys.max": 20}
     base_requirements JSONB DEFAULT '{}',          -- 装备需求，如 {"level": 60, "str": 100}
     tags TEXT[] DEFAULT '{}',                      -- 装备标签，如 ['Tag_Weapon', 'Tag_Sword']
@@ -81,6 +83,7 @@ CREATE INDEX idx_items_meta_two_handed ON items_meta(is_two_handed);
 COMMENT ON TABLE items_meta IS '装备基底数据表';
 COMMENT ON COLUMN items_meta.implicit_stats IS '基底固有词缀，JSONB格式，百分比存为小数';
 COMMENT ON COLUMN items_meta.is_two_handed IS '双手武器标记，用于双持/双手互斥判断';
+COMMENT ON COLUMN items_meta.attack_time IS '武器基础攻击时间，攻速 = 1 / 攻击时间（与 tli-core items_meta.json 一致）';
 
 -- ============================================================
 -- 4. 打造词缀模板表 (affixes)
@@ -201,6 +204,8 @@ CREATE TABLE skills (
     growth_table JSONB DEFAULT '{}',               -- 等级成长表，如 {"1": {"base_damage": 10}, "20": {"base_damage": 100}}
     tags TEXT[] DEFAULT '{}',                      -- 技能标签，如 ['Tag_Spell', 'Tag_Fire', 'Tag_AOE']
     stats JSONB DEFAULT '{}',                      -- 技能自带属性加成
+    quality_stats JSONB DEFAULT '{}',              -- 每 1% 品质提供的属性，如 {"mod.inc.dmg.all": 0.01}
+    parts JSONB DEFAULT '[]',                      -- 技能部分，如 [{"id": "explosion", "base_damage": {...}, "tags": ["Tag_AOE"], "weight": 1}]
     icon VARCHAR(256),
     description TEXT,
     created_at TIMESTAMPTZ DEFAULT NOW()
@@ -278,7 +283,9 @@ CREATE TABLE support_skill_modifiers (
     mana_multiplier DECIMAL(6,4) DEFAULT 1.0,      -- 魔力倍率
     stats JSONB NOT NULL DEFAULT '{}',             -- 提供的属性加成
     injected_tags TEXT[] DEFAULT '{}',             -- 向主技能注入的标签
+    conditional_tags JSONB DEFAULT '[]',           -- 条件注入的标签，如 [{"tag": "Tag_Cold", "condition": "has_tag(\"Tag_Lightning\")"}]
     requirements TEXT[] DEFAULT '{}',              -- 需求的主技能标签
+    excluded_tags TEXT[] DEFAULT '{}',             -- 主技能不能具备的标签（如近战辅助排除 Tag_Spell）
     created_at TIMESTAMPTZ DEFAULT NOW()
 );
 
@@ -391,7 +398,7 @@ COMMENT ON COLUMN conversion_rules.priority IS '转化优先级，数字越小
 CREATE TABLE mechanics_registry (
     id VARCHAR(64) PRIMARY KEY,                    -- 机制ID，如 'focus_blessing', 'fortify_blessing'
     display_name VARCHAR(128) NOT NULL,            -- 显示名称，如 '聚能祝福'
-    category VARCHAR(32) NOT NULL,                 -- 机制分类：blessing, charge, resource
+    category VARCHAR(32) NOT NULL,                 -- 机制分类：blessing, charge, resource, sealed
     tag_key VARCHAR(64),                           -- 关联标签，如 'Mech_Blessing'
     
     -- 层数/资源配置
@@ -405,6 +412,13 @@ CREATE TABLE mechanics_registry (
     -- 特殊规则
     decay_rule VARCHAR(64) DEFAULT 'none',         -- 衰减规则：none, time_based, on_hit
     decay_rate DECIMAL(10,4) DEFAULT 0,            -- 衰减速率
+    aura_scalable BOOLEAN DEFAULT FALSE,           -- 每层效果是否受光环效果加成（祝福类增益）
+    generation_rate DECIMAL(10,4),                 -- 层数获取速率（层/秒），用于估算稳态平均层数
+    duration DECIMAL(10,4),                        -- 每层持续时间（秒）
+    exclusive_group VARCHAR(64),                   -- 互斥组：同组机制同时只有一个生效
+    cost_per_use DECIMAL(10,4),                    -- 消耗型资源每次使用技能的消耗量（如怒火）
+    stacks_per_hit DECIMAL(10,4),                  -- 每次命中获得的层数（命中叠层增益）
+    stacks_per_kill DECIMAL(10,4),                 -- 每次击杀获得的层数（击杀叠层增益）
     
     description TEXT,
     icon VARCHAR(256),
@@ -416,7 +430,7 @@ CREATE INDEX idx_mechanics_tag ON mechanics_registry(tag_key);
 
 COMMENT ON TABLE mechanics_registry IS '机制注册表，定义所有游戏机制（祝福、球类、资源等）的元数据和基础效果';
 COMMENT ON COLUMN mechanics_registry.base_effect_per_stack IS '每层机制提供的基础属性，如聚能祝福每层+4%伤害';
-COMMENT ON COLUMN mechanics_registry.category IS '机制分类: blessing(祝福)/charge(球类)/resource(资源)';
+COMMENT ON COLUMN mechanics_registry.category IS '机制分类: blessing(祝福)/charge(球类)/resource(资源)/sealed(封印魔力)';
 COMMENT ON COLUMN mechanics_registry.tag_key IS '关联的标签键，用于标签系统集成';
 
 -- ============================================================
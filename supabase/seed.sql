@@ -38,6 +38,7 @@ INSERT INTO tags_registry (tag_key, category, display_name, parents, description
 ('Tag_Staff', 'Identity', '长杖', '{"Tag_Weapon"}', '法杖类武器'),
 ('Tag_Bow', 'Identity', '弓', '{"Tag_Weapon"}', '弓类武器'),
 ('Tag_Wand', 'Identity', '法杖', '{"Tag_Weapon"}', '短法杖'),
+('Tag_Claw', 'Identity', '爪', '{"Tag_Weapon"}', '爪类武器'),
 
 -- 装备类型标签
 ('Tag_OneHanded', 'Identity', '单手', '{}', '单手武器'),
@@ -70,6 +71,9 @@ INSERT INTO tags_registry (tag_key, category, display_name, parents, description
 -- 机制标签
 ('Mech_Blessing', 'Mechanic', '祝福', '{}', '祝福层数机制'),
 ('Mech_FightingWill', 'Mechanic', '战意', '{}', '战意值机制，每点提供暴击值，受战意效果影响'),
+('Mech_Charge_Frenzy', 'Mechanic', '狂乱球', '{}', '狂乱球层数'),
+('Mech_Charge_Power', 'Mechanic', '能量球', '{}', '能量球层数'),
+('Mech_Charge_Endurance', 'Mechanic', '耐力球', '{}', '耐力球层数'),
 ('Mech_Rage', 'Mechanic', '怒火', '{}', '怒火值'),
 ('Mech_Fortify', 'Mechanic', '护体', '{}', '护体层数'),
 
@@ -145,6 +149,28 @@ INSERT INTO attributes_meta (attr_key, display_name, value_type, is_local, requi
 ('mod.inc.dmg.projectile', '投射物伤害增加', 'percent', FALSE, '{"Tag_Projectile"}', '投射物伤害增加'),
 ('mod.inc.dmg.aoe', '范围伤害增加', 'percent', FALSE, '{"Tag_AOE"}', '范围伤害增加'),
 ('mod.inc.dmg.dot', '持续伤害增加', 'percent', FALSE, '{"Tag_DOT"}', '持续伤害增加'),
+('mod.inc.aura.effect', '光环效果', 'percent', FALSE, '{}', '光环技能与祝福类增益的效果提高'),
+('mod.max_stacks.focus_blessing', '聚能祝福上限', 'int', FALSE, '{"Mech_Blessing"}', '聚能祝福最大层数提高'),
+('mod.max_stacks.tenacity_blessing', '坚韧祝福上限', 'int', FALSE, '{"Mech_Blessing"}', '坚韧祝福最大层数提高'),
+('mod.max_stacks.agility_blessing', '灵动祝福上限', 'int', FALSE, '{"Mech_Blessing"}', '灵动祝福最大层数提高'),
+('grant.mechanic.focus_blessing', '获得聚能祝福', 'int', FALSE, '{"Mech_Blessing"}', '自动获得指定层数的聚能祝福'),
+('grant.mechanic.tenacity_blessing', '获得坚韧祝福', 'int', FALSE, '{"Mech_Blessing"}', '自动获得指定层数的坚韧祝福'),
+('grant.mechanic.agility_blessing', '获得灵动祝福', 'int', FALSE, '{"Mech_Blessing"}', '自动获得指定层数的灵动祝福'),
+('mod.inc.dmg.spell.per_sealed_10', '每封印 10% 魔力法术伤害增加', 'percent', FALSE, '{"Tag_Spell"}', '每封印 10% 魔力，法术伤害增加'),
+
+-- 武器类别限定（主手武器基底推导标签）
+('mod.inc.dmg.sword', '剑伤害增加', 'percent', FALSE, '{"Tag_Sword"}', '使用剑时伤害增加'),
+('mod.inc.dmg.axe', '斧伤害增加', 'percent', FALSE, '{"Tag_Axe"}', '使用斧时伤害增加'),
+('mod.inc.dmg.mace', '锤伤害增加', 'percent', FALSE, '{"Tag_Mace"}', '使用锤时伤害增加'),
+('mod.inc.dmg.dagger', '匕首伤害增加', 'percent', FALSE, '{"Tag_Dagger"}', '使用匕首时伤害增加'),
+('mod.inc.dmg.claw', '爪伤害增加', 'percent', FALSE, '{"Tag_Claw"}', '使用爪时伤害增加'),
+('mod.inc.dmg.wand', '法杖伤害增加', 'percent', FALSE, '{"Tag_Wand"}', '使用法杖时伤害增加'),
+('mod.inc.dmg.staff', '长杖伤害增加', 'percent', FALSE, '{"Tag_Staff"}', '使用长杖时伤害增加'),
+('mod.inc.dmg.bow', '弓伤害增加', 'percent', FALSE, '{"Tag_Bow"}', '使用弓时伤害增加'),
+('mod.inc.dmg.crossbow', '弩伤害增加', 'percent', FALSE, '{"Tag_Crossbow"}', '使用弩时伤害增加'),
+('mod.inc.dmg.one_handed', '单手武器伤害增加', 'percent', FALSE, '{"Tag_OneHanded"}', '使用单手武器时伤害增加'),
+('mod.inc.dmg.two_handed', '双手武器伤害增加', 'percent', FALSE, '{"Tag_TwoHanded"}', '使用双手武器时伤害增加'),
+('mod.inc.dmg.dual_wield', '双持伤害增加', 'percent', FALSE, '{"Tag_DualWield"}', '双持时伤害增加'),
 
 -- More 修正（相乘）
 ('mod.more.dmg.all', '总伤害提高', 'percent', FALSE, '{}', '总伤害提高（More乘区）'),
@@ -173,12 +199,20 @@ INSERT INTO attributes_meta (attr_key, display_name, value_type, is_local, requi
 ('extra.phys_as_cold', '物理额外冰霜', 'percent', FALSE, '{}', '物理伤害的百分比额外获得为冰霜'),
 ('extra.phys_as_lightning', '物理额外闪电', 'percent', FALSE, '{}', '物理伤害的百分比额外获得为闪电'),
 ('extra.fire_as_chaos', '火焰额外混沌', 'percent', FALSE, '{}', '火焰伤害的百分比额外获得为混沌'),
+('extra.elemental_as_chaos', '元素额外混沌', 'percent', FALSE, '{}', '元素伤害的百分比额外获得为混沌'),
+('extra.all_as_fire', '全部额外火焰', 'percent', FALSE, '{}', '全部伤害的百分比额外获得为火焰'),
+('extra.all_as_cold', '全部额外冰霜', 'percent', FALSE, '{}', '全部伤害的百分比额外获得为冰霜'),
+('extra.all_as_lightning', '全部额外闪电', 'percent', FALSE, '{}', '全部伤害的百分比额外获得为闪电'),
+('extra.all_as_chaos', '全部额外混沌', 'percent', FALSE, '{}', '全部伤害的百分比额外获得为混沌'),
 
 -- 防御
 ('def.armor', '护甲', 'int', FALSE, '{}', '护甲值'),
 ('def.evasion', '闪避', 'int', FALSE, '{}', '闪避值'),
 ('def.block', '格挡率', 'percent', FALSE, '{}', '攻击格挡率'),
 ('def.spell_block', '法术格挡率', 'percent', FALSE, '{}', '法术格挡率'),
+('def.gain.evasion_as_armor', '闪避额外护甲', 'percent', FALSE, '{}', '闪避值的百分比额外获得为护甲'),
+('def.gain.armor_as_evasion', '护甲额外闪避', 'percent', FALSE, '{}', '护甲值的百分比额外获得为闪避'),
+('def.armor_applies.elemental', '护甲适用元素', 'percent', FALSE, '{}', '护甲对元素伤害生效的比例'),
 
 -- 抗性
 ('res.phys', '物理抗性', 'percent', FALSE, '{}', '物理伤害减免'),
@@ -187,6 +221,21 @@ INSERT INTO attributes_meta (attr_key, display_name, value_type, is_local, requi
 ('res.lightning', '闪电抗性', 'percent', FALSE, '{}', '闪电抗性'),
 ('res.chaos', '混沌抗性', 'percent', FALSE, '{}', '混沌抗性'),
 
+-- 技能等级与品质
+('skill.level', '全技能等级', 'int', FALSE, '{}', '所有技能等级提高'),
+('skill.level.attack', '攻击技能等级', 'int', FALSE, '{"Tag_Attack"}', '攻击技能等级提高'),
+('skill.level.spell', '法术技能等级', 'int', FALSE, '{"Tag_Spell"}', '法术技能等级提高'),
+('skill.level.melee', '近战技能等级', 'int', FALSE, '{"Tag_Melee"}', '近战技能等级提高'),
+('skill.level.projectile', '投射物技能等级', 'int', FALSE, '{"Tag_Projectile"}', '投射物技能等级提高'),
+('skill.level.phys', '物理技能等级', 'int', FALSE, '{"Tag_Physical"}', '物理技能等级提高'),
+('skill.level.fire', '火焰技能等级', 'int', FALSE, '{"Tag_Fire"}', '火焰技能等级提高'),
+('skill.level.cold', '冰霜技能等级', 'int', FALSE, '{"Tag_Cold"}', '冰霜技能等级提高'),
+('skill.level.lightning', '闪电技能等级', 'int', FALSE, '{"Tag_Lightning"}', '闪电技能等级提高'),
+('skill.level.chaos', '混沌技能等级', 'int', FALSE, '{"Tag_Chaos"}', '混沌技能等级提高'),
+('skill.level.elemental', '元素技能等级', 'int', FALSE, '{"Tag_Elemental"}', '元素技能等级提高'),
+('skill.level.minion', '召唤技能等级', 'int', FALSE, '{"Tag_Minion"}', '召唤物技能等级提高'),
+('skill.quality', '技能品质', 'int', FALSE, '{}', '技能品质提高（百分比点数）'),
+
 -- 命中
 ('acc.rating', '命中值', 'int', FALSE, '{}', '命中值'),
 ('acc.chance', '命中率', 'percent', FALSE, '{}', '命中率');
@@ -194,81 +243,81 @@ INSERT INTO attributes_meta (attr_key, display_name, value_type, is_local, requi
 -- ============================================================
 -- 3. 装备基底 Seed
 -- ============================================================
-INSERT INTO items_meta (base_type, display_name, slot, is_two_handed, implicit_stats, tags) VALUES
+INSERT INTO items_meta (base_type, display_name, slot, is_two_handed, attack_time, crit_chance, implicit_stats, tags) VALUES
 -- 单手剑
-('sword_1h_phys', '破敌单手剑', 'weapon_main', FALSE, 
- '{"dmg.phys.min": 15, "dmg.phys.max": 30, "crit.chance.local": 0.05, "speed.attack.local": 1.3}',
+('sword_1h_phys', '破敌单手剑', 'weapon_main', FALSE, 0.77, 0.05,
+ '{"dmg.phys.min": 15, "dmg.phys.max": 30}',
  '{"Tag_Weapon", "Tag_Sword", "Tag_OneHanded"}'),
 
 -- 双手剑
-('sword_2h_phys', '斩魂巨剑', 'weapon_main', TRUE,
- '{"dmg.phys.min": 40, "dmg.phys.max": 80, "crit.chance.local": 0.05, "speed.attack.local": 1.0}',
+('sword_2h_phys', '斩魂巨剑', 'weapon_main', TRUE, 1.0, 0.05,
+ '{"dmg.phys.min": 40, "dmg.phys.max": 80}',
  '{"Tag_Weapon", "Tag_Sword", "Tag_TwoHanded"}'),
 
 -- 法杖
-('wand_fire', '烈焰法杖', 'weapon_main', FALSE,
- '{"dmg.fire.min": 10, "dmg.fire.max": 25, "crit.chance.local": 0.07, "speed.attack.local": 1.5}',
+('wand_fire', '烈焰法杖', 'weapon_main', FALSE, 0.67, 0.07,
+ '{"dmg.fire.min": 10, "dmg.fire.max": 25}',
  '{"Tag_Weapon", "Tag_Wand", "Tag_OneHanded"}'),
 
 -- 长杖
-('staff_2h_spell', '奥术长杖', 'weapon_main', TRUE,
- '{"mod.inc.dmg.spell": 0.20, "crit.chance.local": 0.06, "speed.attack.local": 1.1}',
+('staff_2h_spell', '奥术长杖', 'weapon_main', TRUE, 0.91, 0.06,
+ '{"mod.inc.dmg.spell": 0.20}',
  '{"Tag_Weapon", "Tag_Staff", "Tag_TwoHanded"}'),
 
 -- 弓
-('bow_2h', '迅捷长弓', 'weapon_main', TRUE,
- '{"dmg.phys.min": 20, "dmg.phys.max": 50, "crit.chance.local": 0.05, "speed.attack.local": 1.2}',
+('bow_2h', '迅捷长弓', 'weapon_main', TRUE, 0.83, 0.05,
+ '{"dmg.phys.min": 20, "dmg.phys.max": 50}',
  '{"Tag_Weapon", "Tag_Bow", "Tag_TwoHanded"}'),
 
 -- 盾牌
-('shield_str', '铁壁圆盾', 'weapon_off', FALSE,
+('shield_str', '铁壁圆盾', 'weapon_off', FALSE, NULL, NULL,
  '{"def.armor": 100, "def.block": 0.25}',
  '{"Tag_Shield"}'),
 
 -- 头盔
-('helmet_armor', '精钢头盔', 'helmet', FALSE,
+('helmet_armor', '精钢头盔', 'helmet', FALSE, NULL, NULL,
  '{"def.armor": 50}',
  '{"Tag_Armor", "Tag_Helmet"}'),
 
 -- 胸甲
-('chest_armor', '重型铠甲', 'chest', FALSE,
+('chest_armor', '重型铠甲', 'chest', FALSE, NULL, NULL,
  '{"def.armor": 150}',
  '{"Tag_Armor", "Tag_Chest"}'),
 
 -- 手套
-('gloves_armor', '铁手套', 'gloves', FALSE,
+('gloves_armor', '铁手套', 'gloves', FALSE, NULL, NULL,
  '{"def.armor": 30}',
  '{"Tag_Armor", "Tag_Gloves"}'),
 
-('gloves_all_magic_grip', '万法之轻握', 'gloves', FALSE,
+('gloves_all_magic_grip', '万法之轻握', 'gloves', FALSE, NULL, NULL,
  '{"base.es": 120}',
  '{"Tag_Armor", "Tag_Gloves"}'),
 
-('gloves_old_kings_rerebraces', '旧日王者的护臂', 'gloves', FALSE,
+('gloves_old_kings_rerebraces', '旧日王者的护臂', 'gloves', FALSE, NULL, NULL,
  '{"def.armor": 1777}',
  '{"Tag_Armor", "Tag_Gloves"}'),
 
-('gloves_hands_of_calamity', '灾厄之手', 'gloves', FALSE,
+('gloves_hands_of_calamity', '灾厄之手', 'gloves', FALSE, NULL, NULL,
  '{"def.evasion": 1615}',
  '{"Tag_Armor", "Tag_Gloves"}'),
 
 -- 鞋子
-('boots_armor', '铁靴', 'boots', FALSE,
+('boots_armor', '铁靴', 'boots', FALSE, NULL, NULL,
  '{"def.armor": 40}',
  '{"Tag_Armor", "Tag_Boots"}'),
 
 -- 项链
-('amulet_base', '银质项链', 'amulet', FALSE,
+('amulet_base', '银质项链', 'amulet', FALSE, NULL, NULL,
  '{}',
  '{"Tag_Accessory", "Tag_Amulet"}'),
 
 -- 戒指
-('ring_base', '银质戒指', 'ring', FALSE,
+('ring_base', '银质戒指', 'ring', FALSE, NULL, NULL,
  '{}',
  '{"Tag_Accessory", "Tag_Ring"}'),
 
 -- 腰带
-('belt_base', '皮革腰带', 'belt', FALSE,
+('belt_base', '皮革腰带', 'belt', FALSE, NULL, NULL,
  '{}',
  '{"Tag_Accessory", "Tag_Belt"}');
 
@@ -657,13 +706,13 @@ INSERT INTO hero_traits (id, display_name, hero_class, trait_type, position, sta
 INSERT INTO hero_traits (id, display_name, hero_class, trait_type, position, stats, tags, description) VALUES
 ('trait_core_cold_hardship', '苦寒', NULL, 'keystone',
  '{"x": 1, "y": 0}',
- '{"mechanic.focus_blessing.max_stacks": 1, "mechanic.focus_blessing.on_hit_gain": 1}',
+ '{"mod.max_stacks.focus_blessing": 1, "mechanic.focus_blessing.on_hit_gain": 1}',
  '{"Tag_Blessing"}',
  '击中时获得聚能祝福；聚能祝福上限 +1'),
 
 ('trait_core_accumulation', '积聚', NULL, 'keystone',
  '{"x": 2, "y": 0}',
- '{"mechanic.focus_blessing.max_stacks": 1, "mod.inc.dmg.spell.per_focus_blessing": 0.03}',
+ '{"mod.max_stacks.focus_blessing": 1, "mod.inc.dmg.spell.per_focus_blessing": 0.03}',
  '{"Tag_Spell"}',
  '聚能祝福上限 +1；每层聚能祝福 +3% 法术伤害'),
 
@@ -719,20 +768,20 @@ INSERT INTO pacts (id, display_name, pact_type, tier, stats, tags) VALUES
 -- ============================================================
 
 -- 三种祝福机制
-INSERT INTO mechanics_registry (id, display_name, category, tag_key, default_stacks, max_stacks, base_effect_per_stack, description) VALUES
+INSERT INTO mechanics_registry (id, display_name, category, tag_key, default_stacks, max_stacks, base_effect_per_stack, aura_scalable, description) VALUES
 -- 聚能祝福：每层 +4% 伤害
 ('focus_blessing', '聚能祝福', 'blessing', 'Mech_Blessing', 0, 4,
- '{"mod.inc.dmg.all": 0.04}',
+ '{"mod.inc.dmg.all": 0.04}', TRUE,
  '聚能祝福可以通过多种手段获得，初始上限为4层，每层提供额外+4%伤害'),
 
 -- 坚韧祝福：每层 -4% 受到的伤害
 ('tenacity_blessing', '坚韧祝福', 'blessing', 'Mech_Blessing', 0, 4,
- '{"def.damage_taken_reduction": 0.04}',
+ '{"def.damage_taken_reduction": 0.04}', TRUE,
  '坚韧祝福可以通过多种手段获得，初始上限为4层，每层减少4%受到的伤害'),
 
 -- 灵动祝福：每层 +4% 攻击和施法速度, +2% 伤害
 ('agility_blessing', '灵动祝福', 'blessing', 'Mech_Blessing', 0, 4,
- '{"speed.attack": 0.04, "speed.cast": 0.04, "mod.inc.dmg.all": 0.02}',
+ '{"speed.attack": 0.04, "speed.cast": 0.04, "mod.inc.dmg.all": 0.02}', TRUE,
  '灵动祝福可以通过多种手段获得，初始上限为4层，每层提供额外+4%攻击和施法速度，+2%伤害');
 
 -- 战意机制 (Fighting Will)
@@ -742,3 +791,23 @@ INSERT INTO mechanics_registry (id, display_name, category, tag_key, default_sta
  'time_based',
  '战意状态下，击败怪物、击中劲敌时获得1点战意值。每点战意值提供2%攻击和法术暴击值，最多100点。基础持续时间10秒。效果受[战意效果]属性影响。');
 
+-- 三种球类机制：每层效果包含 More 乘区与速度
+INSERT INTO mechanics_registry (id, display_name, category, tag_key, default_stacks, max_stacks, base_effect_per_stack, description) VALUES
+('frenzy_charge', '狂乱球', 'charge', 'Mech_Charge_Frenzy', 0, 3,
+ '{"mod.more.dmg.all": 0.04, "speed.attack": 0.04, "speed.cast": 0.04}',
+ '每个狂乱球提供额外+4%伤害，+4%攻击和施法速度'),
+
+('power_charge', '能量球', 'charge', 'Mech_Charge_Power', 0, 3,
+ '{"mod.inc.crit.chance": 0.4}',
+ '每个能量球提供+40%暴击值'),
+
+('endurance_charge', '耐力球', 'charge', 'Mech_Charge_Endurance', 0, 3,
+ '{"def.damage_taken_reduction": 0.04, "res.fire": 0.04, "res.cold": 0.04, "res.lightning": 0.04}',
+ '每个耐力球减少4%受到的伤害，+4%火焰、冰冷、闪电抗性');
+
+-- 封印魔力：层数为封印的魔力百分比，.per_sealed_<N> 属性按封印量缩放
+INSERT INTO mechanics_registry (id, display_name, category, tag_key, default_stacks, max_stacks, base_effect_per_stack, description) VALUES
+('sealed_mana', '封印魔力', 'sealed', NULL, 0, 100,
+ '{}',
+ '封印部分魔力以强化法术，层数为封印的魔力百分比');
+
@@ -0,0 +1,137 @@
+//! 核心属性模块
+//!
+//! 处理力量、敏捷、智慧三种核心属性的隐性加成
+//!
+//! ## 核心设计
+//!
+//! 1. 属性值本身按普通属性键聚合（`strength` / `mod.inc.strength` 等）
+//! 2. 隐性加成由数据表 `src/data/attributes_table.json` 配置
+//! 3. 每条加成转换为 PerStat 属性键（如 `base.life.per_2_strength`），
+//!    在聚合第二遍按属性总值解析
+
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// 力量
+pub const STRENGTH: &str = "strength";
+/// 敏捷
+pub const DEXTERITY: &str = "dexterity";
+/// 智慧
+pub const INTELLIGENCE: &str = "intelligence";
+
+/// 全部核心属性
+pub const ATTRIBUTE_KEYS: [&str; 3] = [STRENGTH, DEXTERITY, INTELLIGENCE];
+
+/// 属性加成表 JSON 内容（编译时内嵌）
+pub(crate) const ATTRIBUTES_TABLE_JSON: &str = include_str!("data/attributes_table.json");
+
+/// 单条属性隐性加成
+#[derive(Debug, Clone, Deserialize)]
+pub struct AttributeBonus {
+    /// 受加成的属性键
+    pub stat: String,
+    /// 每多少点属性
+    pub per: f64,
+    /// 提供的数值
+    pub value: f64,
+}
+
+/// 属性加成表
+#[derive(Debug, Clone, Default)]
+pub struct AttributeTable {
+    /// 属性 -> 加成列表
+    bonuses: HashMap<String, Vec<AttributeBonus>>,
+}
+
+impl AttributeTable {
+    /// 从 JSON 加载属性加成表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut bonuses = HashMap::new();
+        for (key, value) in raw {
+            // 跳过元数据
+            if key.starts_with('_') {
+                continue;
+            }
+
+            let list: Vec<AttributeBonus> = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse attribute '{}': {}", key, e))?;
+            if let Some(bad) = list.iter().find(|b| b.per <= 0.0) {
+                return Err(format!("Invalid per for '{}' -> '{}': {}", key, bad.stat, bad.per));
+            }
+            bonuses.insert(key, list);
+        }
+
+        Ok(Self { bonuses })
+    }
+
+    /// 加载内嵌的默认属性加成表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不提供隐性加成）
+    pub fn load_default() -> Self {
+        load_embedded("attributes_table", Self::from_json(ATTRIBUTES_TABLE_JSON))
+    }
+
+    /// 获取某属性的加成列表
+    pub fn bonuses(&self, attribute: &str) -> &[AttributeBonus] {
+        self.bonuses.get(attribute).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// 转换为 PerStat 属性键及数值
+    ///
+    /// 例：力量每 2 点 +1 生命 -> `("base.life.per_2_strength", 1.0)`
+    pub fn per_stat_entries(&self) -> Vec<(String, f64)> {
+        let mut entries: Vec<(String, f64)> = self
+            .bonuses
+            .iter()
+            .flat_map(|(attr, list)| {
+                list.iter()
+                    .map(move |b| (format!("{}.per_{}_{}", b.stat, b.per, attr), b.value))
+            })
+            .collect();
+        // 保证聚合顺序稳定
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modifiers::PerStatConfig;
+    use crate::stats::parse_per_stat_key;
+
+    #[test]
+    fn test_default_table_covers_core_attributes() {
+        let table = AttributeTable::load_default();
+        for attr in ATTRIBUTE_KEYS {
+            assert!(!table.bonuses(attr).is_empty(), "missing bonuses for {}", attr);
+        }
+    }
+
+    #[test]
+    fn test_per_stat_entries_round_trip() {
+        let table = AttributeTable::from_json(
+            r#"{ "_meta": {}, "strength": [{ "stat": "base.life", "per": 2, "value": 1 }] }"#,
+        )
+        .unwrap();
+
+        let entries = table.per_stat_entries();
+        assert_eq!(entries, vec![("base.life.per_2_strength".to_string(), 1.0)]);
+        assert_eq!(
+            parse_per_stat_key(&entries[0].0),
+            Some(("base.life", PerStatConfig::new("strength", 2.0)))
+        );
+    }
+
+    #[test]
+    fn test_invalid_per_rejected() {
+        let result = AttributeTable::from_json(
+            r#"{ "strength": [{ "stat": "base.life", "per": 0, "value": 1 }] }"#,
+        );
+        assert!(result.is_err());
+    }
+}
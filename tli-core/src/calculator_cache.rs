@@ -13,12 +13,23 @@
 //! - `calculate_diff_incremental`: 复用 base 的 PreparedContext，仅聚合 preview item 的差异
 //! - 相比两次全量计算，减少约 50% 的聚合开销
 
-use crate::pipeline::{calculate_dps, calculate_from_prepared, prepare_context, CalculationError, PreparedContext};
-use crate::types::{CalculatorInput, CalculatorOutput, ItemData, SlotType};
+use crate::pipeline::{
+    calculate_from_prepared, calculate_raw, default_data_version, embedded_data_checksum, input_digest, input_hash,
+    prepare_context, requires_full_calculation, CalculationError,
+    PreparedContext, ENGINE_VERSION,
+};
+use crate::types::{
+    BuildComparison, CacheInfo, CalculatorInput, CharacterConfig, CalculatorOutput, DataVersion, EhpSeries, IncreasedFloor, ItemContribution, ItemData, OutputOptions,
+    OverrideGroup, OverrideValue, SanitizePolicy, ScenarioDelta, ScenarioResult, SlotConflictPolicy, SlotType, StackMode,
+    SupportContribution,
+};
 use lru::LruCache;
+use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
 use std::hash::{Hash, Hasher};
 use std::num::NonZeroUsize;
+use ts_rs::TS;
 
 /// 缓存键
 /// 
@@ -44,18 +55,58 @@ pub struct CacheKey {
     context_flags_hash: u64,
     /// 上下文数值哈希（如 life_percent, enemy_range 等）
     context_values_hash: u64,
+    /// 字符串上下文哈希（如 weapon_type, stance 等）
+    context_strings_hash: u64,
+    /// 增伤总和下限策略
+    increased_floor: IncreasedFloor,
+    /// 是否排除无效的辅助技能
+    exclude_invalid_supports: bool,
+    /// 是否排除需求未满足的装备
+    exclude_unusable_items: bool,
+    /// 未知属性键是否视为错误
+    strict_stat_keys: bool,
+    /// 越界输入处理策略
+    sanitize_policy: SanitizePolicy,
+    /// 槽位冲突处理策略
+    slot_conflict_policy: SlotConflictPolicy,
+    /// 机制层数取值模式
+    stack_mode: StackMode,
+    /// 近期事件模型哈希
+    recent_model_hash: u64,
+    /// 持续输出模型哈希
+    sustain_model_hash: u64,
+    /// 槽位定义哈希
+    slot_definitions_hash: u64,
+    /// 套装定义哈希
+    item_sets_hash: u64,
+    /// 角色配置
+    character: Option<CharacterConfig>,
+    /// 标签注册表校验和（数据更新后旧缓存自动失效）
+    data_checksum: u64,
 }
 
 impl CacheKey {
+    /// 缓存键指纹（16 位十六进制，用于缓存溯源）
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
     /// 从计算输入生成缓存键
     pub fn from_input(input: &CalculatorInput) -> Self {
         let items_hash = Self::hash_items(&input.items);
-        let skill_hash = Self::hash_skill(&input.active_skill, &input.support_skills);
-        let mechanics_hash = Self::hash_mechanics(&input.mechanic_states);
+        let skill_hash = Self::hash_skill(&input.active_skill, &input.support_skills, &input.auras);
+        let mechanics_hash = Self::hash_mechanics(
+            &input.mechanic_states,
+            &input.mechanic_definitions,
+            &input.disabled_mechanics,
+        );
         let target_hash = Self::hash_target(&input.target_config);
-        let overrides_hash = Self::hash_overrides(&input.global_overrides);
+        let overrides_hash = Self::hash_overrides(&input.global_overrides, &input.override_groups);
         let context_flags_hash = Self::hash_context_flags(&input.context_flags);
         let context_values_hash = Self::hash_context_values(&input.context_values);
+        let context_strings_hash = Self::hash_context_strings(&input.context_strings);
 
         Self {
             items_hash,
@@ -65,6 +116,20 @@ impl CacheKey {
             overrides_hash,
             context_flags_hash,
             context_values_hash,
+            context_strings_hash,
+            increased_floor: input.increased_floor,
+            exclude_invalid_supports: input.exclude_invalid_supports,
+            exclude_unusable_items: input.exclude_unusable_items,
+            strict_stat_keys: input.strict_stat_keys,
+            sanitize_policy: input.sanitize_policy,
+            slot_conflict_policy: input.slot_conflict_policy,
+            stack_mode: input.stack_mode,
+            recent_model_hash: Self::hash_recent_model(input.recent_model.as_ref()),
+            sustain_model_hash: Self::hash_sustain_model(input.sustain_model.as_ref()),
+            slot_definitions_hash: Self::hash_slot_definitions(&input.slot_definitions),
+            item_sets_hash: Self::hash_item_sets(&input.item_sets),
+            character: input.character.clone(),
+            data_checksum: embedded_data_checksum(),
         }
     }
 
@@ -74,13 +139,24 @@ impl CacheKey {
         for item in &input.items {
             if &item.slot != slot {
                 item.id.hash(&mut hasher);
+                item.set_id.hash(&mut hasher);
+                item.quality.hash(&mut hasher);
+                item.requirements.hash(&mut hasher);
                 // 哈希词缀数据
                 for affix in &item.affixes {
                     affix.id.hash(&mut hasher);
+                    affix.group.hash(&mut hasher);
+                    affix.tier.hash(&mut hasher);
+                    affix.required_class.hash(&mut hasher);
+                    affix.condition.hash(&mut hasher);
                     for (k, v) in &affix.stats {
                         k.hash(&mut hasher);
                         v.to_bits().hash(&mut hasher);
                     }
+                    for branch in &affix.branches {
+                        branch.condition.hash(&mut hasher);
+                        Self::hash_stat_map(&branch.stats).hash(&mut hasher);
+                    }
                 }
             }
         }
@@ -88,12 +164,30 @@ impl CacheKey {
 
         Self {
             items_hash,
-            skill_hash: Self::hash_skill(&input.active_skill, &input.support_skills),
-            mechanics_hash: Self::hash_mechanics(&input.mechanic_states),
+            skill_hash: Self::hash_skill(&input.active_skill, &input.support_skills, &input.auras),
+            mechanics_hash: Self::hash_mechanics(
+                &input.mechanic_states,
+                &input.mechanic_definitions,
+                &input.disabled_mechanics,
+            ),
             target_hash: Self::hash_target(&input.target_config),
-            overrides_hash: Self::hash_overrides(&input.global_overrides),
+            overrides_hash: Self::hash_overrides(&input.global_overrides, &input.override_groups),
             context_flags_hash: Self::hash_context_flags(&input.context_flags),
             context_values_hash: Self::hash_context_values(&input.context_values),
+            context_strings_hash: Self::hash_context_strings(&input.context_strings),
+            increased_floor: input.increased_floor,
+            exclude_invalid_supports: input.exclude_invalid_supports,
+            exclude_unusable_items: input.exclude_unusable_items,
+            strict_stat_keys: input.strict_stat_keys,
+            sanitize_policy: input.sanitize_policy,
+            slot_conflict_policy: input.slot_conflict_policy,
+            stack_mode: input.stack_mode,
+            recent_model_hash: Self::hash_recent_model(input.recent_model.as_ref()),
+            sustain_model_hash: Self::hash_sustain_model(input.sustain_model.as_ref()),
+            slot_definitions_hash: Self::hash_slot_definitions(&input.slot_definitions),
+            item_sets_hash: Self::hash_item_sets(&input.item_sets),
+            character: input.character.clone(),
+            data_checksum: embedded_data_checksum(),
         }
     }
 
@@ -101,25 +195,36 @@ impl CacheKey {
         let mut hasher = DefaultHasher::new();
         for item in items {
             item.id.hash(&mut hasher);
+            item.base_type.hash(&mut hasher);
             item.slot.hash(&mut hasher);
+            item.is_two_handed.hash(&mut hasher);
+            item.tags.hash(&mut hasher);
+            item.is_unique.hash(&mut hasher);
+            item.is_corrupted.hash(&mut hasher);
+            item.set_id.hash(&mut hasher);
+            item.quality.hash(&mut hasher);
+            item.requirements.hash(&mut hasher);
             // 哈希词缀数据
             for affix in &item.affixes {
                 affix.id.hash(&mut hasher);
-                for (k, v) in &affix.stats {
-                    k.hash(&mut hasher);
-                    v.to_bits().hash(&mut hasher);
+                affix.group.hash(&mut hasher);
+                affix.value.to_bits().hash(&mut hasher);
+                affix.tags.hash(&mut hasher);
+                affix.requirements.hash(&mut hasher);
+                affix.excluded_tags.hash(&mut hasher);
+                affix.is_local.hash(&mut hasher);
+                affix.tier.hash(&mut hasher);
+                affix.required_class.hash(&mut hasher);
+                affix.condition.hash(&mut hasher);
+                Self::hash_stat_map(&affix.stats).hash(&mut hasher);
+                for branch in &affix.branches {
+                    branch.condition.hash(&mut hasher);
+                    Self::hash_stat_map(&branch.stats).hash(&mut hasher);
                 }
             }
-            // 哈希基底属性
-            for (k, v) in &item.base_implicit_stats {
-                k.hash(&mut hasher);
-                v.to_bits().hash(&mut hasher);
-            }
-            // 哈希暗金词缀属性
-            for (k, v) in &item.implicit_stats {
-                k.hash(&mut hasher);
-                v.to_bits().hash(&mut hasher);
-            }
+            // 哈希基底属性与暗金词缀属性
+            Self::hash_stat_map(&item.base_implicit_stats).hash(&mut hasher);
+            Self::hash_stat_map(&item.implicit_stats).hash(&mut hasher);
         }
         hasher.finish()
     }
@@ -127,6 +232,7 @@ impl CacheKey {
     fn hash_skill(
         active: &crate::types::SkillData,
         supports: &[crate::types::SkillData],
+        auras: &[crate::types::SkillData],
     ) -> u64 {
         let mut hasher = DefaultHasher::new();
         active.id.hash(&mut hasher);
@@ -139,27 +245,94 @@ impl CacheKey {
             k.hash(&mut hasher);
             v.to_bits().hash(&mut hasher);
         }
-        // 排序辅助技能的 stats
-        for support in supports {
-            support.id.hash(&mut hasher);
-            support.level.hash(&mut hasher);
-            let mut support_stats: Vec<_> = support.stats.iter().collect();
-            support_stats.sort_by_key(|(k, _)| *k);
-            for (k, v) in support_stats {
-                k.hash(&mut hasher);
-                v.to_bits().hash(&mut hasher);
+        active.quality.hash(&mut hasher);
+        Self::hash_stat_map(&active.quality_stats).hash(&mut hasher);
+        active.mana_cost.hash(&mut hasher);
+        active.selected_part.hash(&mut hasher);
+        for part in &active.parts {
+            part.id.hash(&mut hasher);
+            part.weight.to_bits().hash(&mut hasher);
+            Self::hash_stat_map(&part.base_damage).hash(&mut hasher);
+            part.effectiveness.map(f64::to_bits).hash(&mut hasher);
+            part.tags.hash(&mut hasher);
+            Self::hash_stat_map(&part.stats).hash(&mut hasher);
+        }
+        // 辅助技能与光环分别带列表标识与长度，技能在两者间移动时缓存键随之变化
+        for (list, skills) in [("supports", supports), ("auras", auras)] {
+            list.hash(&mut hasher);
+            skills.len().hash(&mut hasher);
+            for support in skills {
+                support.id.hash(&mut hasher);
+                support.level.hash(&mut hasher);
+                support.scaled_mana_multiplier().to_bits().hash(&mut hasher);
+                // 排序 stats 以确保哈希一致
+                Self::hash_stat_map(&support.scaled_stats()).hash(&mut hasher);
             }
         }
         hasher.finish()
     }
 
-    fn hash_mechanics(states: &[crate::types::MechanicState]) -> u64 {
+    fn hash_mechanics(
+        states: &[crate::types::MechanicState],
+        definitions: &[crate::types::MechanicDefinition],
+        disabled: &[String],
+    ) -> u64 {
         let mut hasher = DefaultHasher::new();
         for state in states {
             state.id.hash(&mut hasher);
             state.current_stacks.hash(&mut hasher);
+            state.max_stacks.hash(&mut hasher);
             state.is_active.hash(&mut hasher);
         }
+        // 稳态层数依赖获取速率、持续时间与资源消耗
+        for def in definitions {
+            def.id.hash(&mut hasher);
+            def.generation_rate.map(f64::to_bits).hash(&mut hasher);
+            def.duration.map(f64::to_bits).hash(&mut hasher);
+            def.cost_per_use.map(f64::to_bits).hash(&mut hasher);
+            def.stacks_per_hit.map(f64::to_bits).hash(&mut hasher);
+            def.stacks_per_kill.map(f64::to_bits).hash(&mut hasher);
+        }
+        disabled.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_recent_model(model: Option<&crate::types::RecentModel>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        if let Some(model) = model {
+            model.window.to_bits().hash(&mut hasher);
+            model.rotation_uptime.to_bits().hash(&mut hasher);
+            model.kill_chance_per_hit.to_bits().hash(&mut hasher);
+            model.threshold.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    fn hash_sustain_model(model: Option<&crate::types::SustainModel>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        model.is_some().hash(&mut hasher);
+        if let Some(model) = model {
+            model.mana_regen.map(f64::to_bits).hash(&mut hasher);
+            model.realistic_buffs.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    fn hash_slot_definitions(definitions: &[crate::types::SlotDefinition]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        definitions.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_item_sets(sets: &[crate::types::ItemSetDefinition]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for set in sets {
+            set.id.hash(&mut hasher);
+            for bonus in &set.bonuses {
+                bonus.pieces.hash(&mut hasher);
+                Self::hash_stat_map(&bonus.stats).hash(&mut hasher);
+            }
+        }
         hasher.finish()
     }
 
@@ -168,21 +341,52 @@ impl CacheKey {
         target.level.hash(&mut hasher);
         target.armor.hash(&mut hasher);
         target.generic_dr.to_bits().hash(&mut hasher);
-        for (k, v) in &target.resistances {
+        let mut resistances: Vec<_> = target.resistances.iter().collect();
+        resistances.sort_by_key(|(k, _)| *k);
+        for (k, v) in resistances {
+            k.hash(&mut hasher);
+            v.to_bits().hash(&mut hasher);
+        }
+        for debuff in &target.debuffs {
+            debuff.id.hash(&mut hasher);
+            let mut stats: Vec<_> = debuff.stats.iter().collect();
+            stats.sort_by_key(|(k, _)| *k);
+            for (k, v) in stats {
+                k.hash(&mut hasher);
+                v.to_bits().hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// 哈希属性表（键排序后逐项哈希数值位模式）
+    fn hash_stat_map(stats: &std::collections::HashMap<String, f64>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let mut pairs: Vec<_> = stats.iter().collect();
+        pairs.sort_by_key(|(k, _)| *k);
+        for (k, v) in pairs {
             k.hash(&mut hasher);
             v.to_bits().hash(&mut hasher);
         }
         hasher.finish()
     }
 
-    fn hash_overrides(overrides: &std::collections::HashMap<String, f64>) -> u64 {
+    /// 哈希全局覆盖（含已启用的覆盖分组，按录入顺序）
+    fn hash_overrides(overrides: &std::collections::HashMap<String, OverrideValue>, groups: &[OverrideGroup]) -> u64 {
         let mut hasher = DefaultHasher::new();
+        for group in groups.iter().filter(|group| group.enabled) {
+            group.name.hash(&mut hasher);
+            Self::hash_overrides(&group.overrides, &[]).hash(&mut hasher);
+        }
         // 排序以确保一致性
         let mut pairs: Vec<_> = overrides.iter().collect();
         pairs.sort_by_key(|(k, _)| *k);
         for (k, v) in pairs {
             k.hash(&mut hasher);
-            v.to_bits().hash(&mut hasher);
+            match v {
+                OverrideValue::Number(value) => value.to_bits().hash(&mut hasher),
+                OverrideValue::Expression(expr) => expr.hash(&mut hasher),
+            }
         }
         hasher.finish()
     }
@@ -212,6 +416,19 @@ impl CacheKey {
         }
         hasher.finish()
     }
+
+    /// 哈希字符串上下文（影响计算的字符串条件，如 weapon_type, stance）
+    fn hash_context_strings(strings: &std::collections::HashMap<String, String>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        // 排序以确保一致性
+        let mut pairs: Vec<_> = strings.iter().collect();
+        pairs.sort_by_key(|(k, _)| *k);
+        for (k, v) in pairs {
+            k.hash(&mut hasher);
+            v.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
 }
 
 /// 带缓存的计算器
@@ -234,8 +451,15 @@ pub struct CachedCalculator {
     context_hits: u64,
     /// 上下文缓存未命中统计
     context_misses: u64,
+    /// 是否记录计算（默认关闭，见 `set_recording`）
+    recording: bool,
+    /// 最近的计算记录（输入 + 格式化后的结果，最多 `STATE_HISTORY_CAPACITY` 条）
+    history: VecDeque<StateEntry>,
 }
 
+/// 状态快照保留的最近计算记录数
+pub const STATE_HISTORY_CAPACITY: usize = 16;
+
 impl CachedCalculator {
     /// 创建新的缓存计算器
     ///
@@ -251,6 +475,19 @@ impl CachedCalculator {
             cache_misses: 0,
             context_hits: 0,
             context_misses: 0,
+            recording: false,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// 开启或关闭计算记录（用于问题反馈的 `export_state`）
+    ///
+    /// 只记录 `calculate` 的输入与格式化后的结果；差异预览、构筑对比、场景与贡献分析不记录。
+    /// 每条记录深拷贝完整的输入与结果，默认关闭；关闭时清空已有记录
+    pub fn set_recording(&mut self, enabled: bool) {
+        self.recording = enabled;
+        if !enabled {
+            self.history.clear();
         }
     }
 
@@ -266,7 +503,10 @@ impl CachedCalculator {
         // 尝试从缓存获取
         if let Some(cached) = self.context_cache.get(&cache_key) {
             self.context_hits += 1;
-            return Ok(cached.clone());
+            // 缓存键不含输出选项等不影响计算的字段，输入指纹按本次输入重新填写
+            let mut ctx = cached.clone();
+            ctx.input_digest = input_digest(input);
+            return Ok(ctx);
         }
 
         // 缓存未命中，执行准备阶段
@@ -281,23 +521,106 @@ impl CachedCalculator {
 
     /// 带缓存的计算
     ///
-    /// 如果缓存命中，直接返回缓存结果；否则执行完整计算并缓存
+    /// 如果缓存命中，直接返回缓存结果；否则执行完整计算并缓存。
+    /// 缓存保存未格式化的结果，返回前按 `input.output_options` 格式化并附带 `cache_info`
     pub fn calculate(&mut self, input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
+        let mut output = self.calculate_raw(input)?;
+        input.output_options.apply(&mut output);
+        self.record(input, &output);
+        Ok(output)
+    }
+
+    /// 记录一次计算（未开启记录时跳过；超出容量时丢弃最早的记录）
+    fn record(&mut self, input: &CalculatorInput, output: &CalculatorOutput) {
+        if !self.recording {
+            return;
+        }
+        if self.history.len() == STATE_HISTORY_CAPACITY {
+            self.history.pop_front();
+        }
+        self.history.push_back(StateEntry { input: input.clone(), output: output.clone() });
+    }
+
+    /// 导出计算器状态（用于问题反馈）
+    ///
+    /// 打包最近 `limit` 条计算记录（须先经 `set_recording` 开启）、缓存统计、引擎与数据版本
+    pub fn export_state(&self, limit: usize) -> CalculatorState {
+        let skip = self.history.len().saturating_sub(limit);
+        CalculatorState {
+            engine_version: ENGINE_VERSION.to_string(),
+            data_version: default_data_version(),
+            cache_stats: self.get_extended_stats(),
+            entries: self.history.iter().skip(skip).cloned().collect(),
+        }
+    }
+
+    /// 导入计算器状态并逐条重放
+    ///
+    /// 按当前引擎与数据重新计算每条记录的输入（经结果缓存，不写入计算记录），
+    /// 返回重放结果与记录结果的对比；单条记录计算失败时记入该条的错误，不影响其余记录。
+    /// 数据版本不一致时结果差异可能来自数据更新
+    pub fn import_state(&mut self, state: &CalculatorState) -> StateReplay {
+        let recording = std::mem::replace(&mut self.recording, false);
+        let entries = state
+            .entries
+            .iter()
+            .map(|entry| {
+                let recorded = entry.output.dps_theoretical;
+                match self.calculate(&entry.input) {
+                    Ok(replayed) => ReplayEntry {
+                        dps_recorded: recorded,
+                        dps_replayed: Some(replayed.dps_theoretical),
+                        matches: (replayed.dps_theoretical - recorded).abs()
+                            <= REPLAY_TOLERANCE * recorded.abs().max(1.0),
+                        error: None,
+                    },
+                    Err(e) => ReplayEntry {
+                        dps_recorded: recorded,
+                        dps_replayed: None,
+                        matches: false,
+                        error: Some(e.to_string()),
+                    },
+                }
+            })
+            .collect();
+        self.recording = recording;
+        StateReplay {
+            engine_version_matches: state.engine_version == ENGINE_VERSION,
+            data_version_matches: state.data_version == default_data_version(),
+            entries,
+        }
+    }
+
+    /// 带缓存的未格式化计算（差值分析使用），结果附带缓存溯源
+    fn calculate_raw(&mut self, input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
         let cache_key = CacheKey::from_input(input);
+        let fingerprint = cache_key.fingerprint();
 
         // 尝试从缓存获取
         if let Some(cached) = self.result_cache.get(&cache_key) {
             self.cache_hits += 1;
-            return Ok(cached.clone());
+            let mut result = cached.clone();
+            result.input_hash = input_hash(input);
+            result.cache_info = Some(CacheInfo {
+                result_hit: true,
+                context_hit: false,
+                reused_layers: vec!["result".to_string()],
+                cache_key: fingerprint,
+            });
+            return Ok(result);
         }
 
         // 缓存未命中，执行计算
         self.cache_misses += 1;
-        let result = calculate_dps(input)?;
+        let mut result = calculate_raw(input)?;
 
-        // 存入缓存
+        // 存入缓存（不含溯源信息）
         self.result_cache.put(cache_key, result.clone());
 
+        result.cache_info = Some(CacheInfo {
+            cache_key: fingerprint,
+            ..CacheInfo::default()
+        });
         Ok(result)
     }
 
@@ -309,21 +632,10 @@ impl CachedCalculator {
         base_input: &CalculatorInput,
         preview_input: &CalculatorInput,
     ) -> Result<CalculationDiff, CalculationError> {
-        let base_result = self.calculate(base_input)?;
-        let preview_result = self.calculate(preview_input)?;
-
-        Ok(CalculationDiff {
-            base: base_result.clone(),
-            preview: preview_result.clone(),
-            dps_diff: preview_result.dps_theoretical - base_result.dps_theoretical,
-            dps_diff_percent: if base_result.dps_theoretical > 0.0 {
-                (preview_result.dps_theoretical - base_result.dps_theoretical) / base_result.dps_theoretical * 100.0
-            } else {
-                0.0
-            },
-            ehp_physical_diff: preview_result.ehp_series.physical - base_result.ehp_series.physical,
-            crit_chance_diff: preview_result.crit_chance - base_result.crit_chance,
-        })
+        let base_result = self.calculate_raw(base_input)?;
+        let preview_result = self.calculate_raw(preview_input)?;
+
+        Ok(CalculationDiff::new(base_result, preview_result, &base_input.output_options))
     }
 
     /// 增量计算预览差异
@@ -339,47 +651,172 @@ impl CachedCalculator {
     /// * `base_input` - 基准输入（当前装备配置）
     /// * `preview_item` - 预览装备
     /// * `preview_slot` - 预览装备的槽位（将替换该槽位的现有装备）
+    ///
+    /// 需要完整计算的输入（见 `requires_full_calculation`）回退为 `calculate_diff`
     pub fn calculate_diff_incremental(
         &mut self,
         base_input: &CalculatorInput,
         preview_item: &ItemData,
         preview_slot: SlotType,
     ) -> Result<CalculationDiff, CalculationError> {
+        if requires_full_calculation(base_input) {
+            let preview_input = with_preview_item(base_input, preview_item, preview_slot);
+            return self.calculate_diff(base_input, &preview_input);
+        }
+
         // 1. 获取或计算 base 的 PreparedContext
+        let context_hits = self.context_hits;
         let base_ctx = self.get_or_prepare_context(base_input)?;
-        let base_result = calculate_from_prepared(&base_ctx, &base_input.target_config)?;
+        let mut base_result = calculate_from_prepared(&base_ctx, &base_input.target_config)?;
+        let context_hit = self.context_hits > context_hits;
+        base_result.cache_info = Some(CacheInfo {
+            result_hit: false,
+            context_hit,
+            reused_layers: if context_hit { vec!["context".to_string()] } else { vec![] },
+            cache_key: CacheKey::from_input(base_input).fingerprint(),
+        });
 
         // 2. 构建 preview input（替换指定槽位的装备）
-        let mut preview_input = base_input.clone();
-        preview_input.items.retain(|item| item.slot != preview_slot);
-        preview_input.items.push(preview_item.clone());
-        // 设置 preview_slot 为完整的 PreviewSlot 结构
-        preview_input.preview_slot = Some(crate::types::PreviewSlot {
-            slot_type: preview_slot,
-            item: preview_item.clone(),
-        });
+        let preview_input = with_preview_item(base_input, preview_item, preview_slot);
 
         // 3. 计算 preview 结果
         // 注意：当前实现简化处理，直接计算 preview input
         // TODO: 未来可优化为真正的增量合并（移除旧 item + 添加新 item）
         let preview_ctx = prepare_context(&preview_input)?;
-        let preview_result = calculate_from_prepared(&preview_ctx, &preview_input.target_config)?;
+        let mut preview_result = calculate_from_prepared(&preview_ctx, &preview_input.target_config)?;
+        preview_result.cache_info = Some(CacheInfo {
+            cache_key: CacheKey::from_input(&preview_input).fingerprint(),
+            ..CacheInfo::default()
+        });
 
         // 4. 构建差异结果
-        Ok(CalculationDiff {
-            base: base_result.clone(),
-            preview: preview_result.clone(),
-            dps_diff: preview_result.dps_theoretical - base_result.dps_theoretical,
-            dps_diff_percent: if base_result.dps_theoretical > 0.0 {
-                (preview_result.dps_theoretical - base_result.dps_theoretical)
-                    / base_result.dps_theoretical
-                    * 100.0
-            } else {
-                0.0
-            },
-            ehp_physical_diff: preview_result.ehp_series.physical - base_result.ehp_series.physical,
-            crit_chance_diff: preview_result.crit_chance - base_result.crit_chance,
-        })
+        Ok(CalculationDiff::new(base_result, preview_result, &base_input.output_options))
+    }
+
+    /// 多场景对比
+    ///
+    /// 基准输入只预处理一次：仅替换目标配置的场景直接复用基准 PreparedContext；
+    /// 修改上下文标志/数值或机制层数的场景影响聚合阶段，按差异后的输入经结果缓存完整计算（相同差异共享）。
+    /// 需要完整计算的输入（见 `requires_full_calculation`）与多部分技能的各场景均完整计算
+    pub fn evaluate_scenarios(
+        &mut self,
+        base_input: &CalculatorInput,
+        scenarios: &[ScenarioDelta],
+    ) -> Result<Vec<ScenarioResult>, CalculationError> {
+        let base_ctx = if requires_full_calculation(base_input) || !base_input.active_skill.parts.is_empty() {
+            None
+        } else {
+            Some(self.get_or_prepare_context(base_input)?)
+        };
+        let base = match &base_ctx {
+            Some(ctx) => calculate_from_prepared(ctx, &base_input.target_config)?,
+            None => self.calculate_raw(base_input)?,
+        };
+
+        let mut results = Vec::with_capacity(scenarios.len());
+        for scenario in scenarios {
+            let target_config = scenario.target_config.as_ref().unwrap_or(&base_input.target_config);
+            let (output, context_reused) = match &base_ctx {
+                Some(ctx) if scenario.is_target_only() => (calculate_from_prepared(ctx, target_config)?, true),
+                _ => (self.calculate_raw(&scenario.apply(base_input))?, false),
+            };
+            results.push(ScenarioResult {
+                name: scenario.name.clone(),
+                dps_theoretical: output.dps_theoretical,
+                dps_effective: output.dps_effective,
+                hit_damage: output.hit_damage,
+                dps_delta_percent: if base.dps_theoretical > 0.0 {
+                    (output.dps_theoretical - base.dps_theoretical) / base.dps_theoretical * 100.0
+                } else {
+                    0.0
+                },
+                ehp_series: output.ehp_series,
+                context_reused,
+            });
+        }
+        Ok(results)
+    }
+
+    /// 两套构筑的完整对比
+    ///
+    /// 与 `calculate_diff` 共用结果缓存，返回总览、各乘区、防御与各伤害类型的逐项差值（B - A）
+    pub fn compare_builds(
+        &mut self,
+        a_input: &CalculatorInput,
+        b_input: &CalculatorInput,
+    ) -> Result<BuildComparison, CalculationError> {
+        let a = self.calculate_raw(a_input)?;
+        let b = self.calculate_raw(b_input)?;
+        Ok(BuildComparison::from_outputs(&a, &b))
+    }
+
+    /// 装备贡献分析
+    ///
+    /// 逐件移除已装备的物品重算 DPS/EHP，按 DPS 贡献从低到高排序（最弱的槽位在前）。
+    /// 每个移除方案经结果缓存计算，重复分析或与悬停预览交替时直接命中缓存
+    pub fn item_contributions(&mut self, input: &CalculatorInput) -> Result<Vec<ItemContribution>, CalculationError> {
+        let base = self.calculate_raw(input)?;
+        let replaced_slot = input.preview_slot.as_ref().map(|p| &p.slot_type);
+
+        let mut contributions = Vec::with_capacity(input.items.len());
+        for (index, item) in input.items.iter().enumerate() {
+            // 被预览装备替换的物品不参与计算
+            if replaced_slot == Some(&item.slot) {
+                continue;
+            }
+            let mut without_input = input.clone();
+            without_input.items.remove(index);
+            let without = self.calculate_raw(&without_input)?;
+
+            let dps_delta = base.dps_theoretical - without.dps_theoretical;
+            contributions.push(ItemContribution {
+                item_id: item.id.clone(),
+                slot: item.slot.clone(),
+                dps_without: without.dps_theoretical,
+                dps_delta,
+                dps_delta_percent: if without.dps_theoretical > 0.0 {
+                    dps_delta / without.dps_theoretical * 100.0
+                } else {
+                    0.0
+                },
+                ehp_delta: ehp_delta(&base.ehp_series, &without.ehp_series),
+            });
+        }
+        contributions.sort_by(|a, b| a.dps_delta.total_cmp(&b.dps_delta));
+        Ok(contributions)
+    }
+
+    /// 辅助技能贡献分析
+    ///
+    /// 逐个移除辅助技能重算 DPS 与魔力消耗，按 DPS 贡献从低到高排序（最值得替换的在前）
+    pub fn support_contributions(
+        &mut self,
+        input: &CalculatorInput,
+    ) -> Result<Vec<SupportContribution>, CalculationError> {
+        let base = self.calculate_raw(input)?;
+
+        let mut contributions = Vec::with_capacity(input.support_skills.len());
+        for (index, support) in input.support_skills.iter().enumerate() {
+            let mut without_input = input.clone();
+            without_input.support_skills.remove(index);
+            let without = self.calculate_raw(&without_input)?;
+
+            let dps_delta = base.dps_theoretical - without.dps_theoretical;
+            contributions.push(SupportContribution {
+                support_id: support.id.clone(),
+                mana_multiplier: support.scaled_mana_multiplier(),
+                dps_without: without.dps_theoretical,
+                dps_delta,
+                dps_delta_percent: if without.dps_theoretical > 0.0 {
+                    dps_delta / without.dps_theoretical * 100.0
+                } else {
+                    0.0
+                },
+                mana_cost_delta: base.mana_cost - without.mana_cost,
+            });
+        }
+        contributions.sort_by(|a, b| a.dps_delta.total_cmp(&b.dps_delta));
+        Ok(contributions)
     }
 
     /// 清空缓存
@@ -448,6 +885,30 @@ impl Default for CachedCalculator {
     }
 }
 
+/// 用预览装备替换指定槽位的现有装备
+fn with_preview_item(base_input: &CalculatorInput, preview_item: &ItemData, preview_slot: SlotType) -> CalculatorInput {
+    let mut preview_input = base_input.clone();
+    preview_input.items.retain(|item| item.slot != preview_slot);
+    preview_input.items.push(preview_item.clone());
+    // 设置 preview_slot 为完整的 PreviewSlot 结构
+    preview_input.preview_slot = Some(crate::types::PreviewSlot {
+        slot_type: preview_slot,
+        item: preview_item.clone(),
+    });
+    preview_input
+}
+
+/// 两组 EHP 的逐元素差值（a - b）
+fn ehp_delta(a: &EhpSeries, b: &EhpSeries) -> EhpSeries {
+    EhpSeries {
+        physical: a.physical - b.physical,
+        fire: a.fire - b.fire,
+        cold: a.cold - b.cold,
+        lightning: a.lightning - b.lightning,
+        chaos: a.chaos - b.chaos,
+    }
+}
+
 /// 计算差异结果
 ///
 /// 用于悬停预览时显示装备更换的影响
@@ -468,6 +929,29 @@ pub struct CalculationDiff {
 }
 
 impl CalculationDiff {
+    /// 由未格式化的结果构建差异（差值按原始精度计算，再按输出格式处理）
+    pub fn new(mut base: CalculatorOutput, mut preview: CalculatorOutput, options: &OutputOptions) -> Self {
+        let dps_diff = preview.dps_theoretical - base.dps_theoretical;
+        let dps_diff_percent = if base.dps_theoretical > 0.0 {
+            dps_diff / base.dps_theoretical * 100.0
+        } else {
+            0.0
+        };
+        let ehp_physical_diff = preview.ehp_series.physical - base.ehp_series.physical;
+        let crit_chance_diff = options.percent(preview.crit_chance - base.crit_chance);
+
+        options.apply(&mut base);
+        options.apply(&mut preview);
+        Self {
+            base,
+            preview,
+            dps_diff: options.round(dps_diff),
+            dps_diff_percent: options.round(dps_diff_percent),
+            ehp_physical_diff: options.round(ehp_physical_diff),
+            crit_chance_diff: options.round(crit_chance_diff),
+        }
+    }
+
     /// 是否为正收益
     pub fn is_positive(&self) -> bool {
         self.dps_diff > 0.0
@@ -483,23 +967,80 @@ impl CalculationDiff {
     }
 }
 
+/// 重放结果与记录结果视为一致的相对误差
+const REPLAY_TOLERANCE: f64 = 1e-9;
+
+/// 一条计算记录
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct StateEntry {
+    /// 计算输入
+    pub input: CalculatorInput,
+    /// 计算结果（已按输出选项格式化）
+    pub output: CalculatorOutput,
+}
+
+/// 计算器状态快照（`export_state` / `import_state`）
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct CalculatorState {
+    /// 引擎版本
+    pub engine_version: String,
+    /// 数据版本
+    pub data_version: DataVersion,
+    /// 缓存统计
+    pub cache_stats: ExtendedCacheStats,
+    /// 最近的计算记录（从旧到新）
+    pub entries: Vec<StateEntry>,
+}
+
+/// 单条记录的重放结果
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ReplayEntry {
+    /// 记录的理论 DPS
+    pub dps_recorded: f64,
+    /// 重放的理论 DPS（重放失败时为空）
+    pub dps_replayed: Option<f64>,
+    /// 两者是否一致（重放失败时为 false）
+    pub matches: bool,
+    /// 重放失败的错误信息
+    pub error: Option<String>,
+}
+
+/// 状态重放报告
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct StateReplay {
+    /// 引擎版本是否与快照一致
+    pub engine_version_matches: bool,
+    /// 数据版本是否与快照一致
+    pub data_version_matches: bool,
+    /// 各条记录的重放结果（与快照顺序一致）
+    pub entries: Vec<ReplayEntry>,
+}
+
 /// 缓存统计信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
 pub struct CacheStats {
     /// 缓存容量
     pub capacity: usize,
     /// 当前缓存大小
     pub size: usize,
     /// 缓存命中次数
+    #[ts(type = "number")]
     pub hits: u64,
     /// 缓存未命中次数
+    #[ts(type = "number")]
     pub misses: u64,
     /// 命中率
     pub hit_rate: f64,
 }
 
 /// 扩展缓存统计信息（包含结果缓存和上下文缓存）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
 pub struct ExtendedCacheStats {
     /// 结果缓存统计
     pub result_cache: CacheStats,
@@ -511,20 +1052,14 @@ pub struct ExtendedCacheStats {
 mod tests {
     use super::*;
     use crate::types::*;
+    use crate::pipeline::calculate_dps;
     use std::collections::HashMap;
 
     fn create_test_input() -> CalculatorInput {
         CalculatorInput {
-            context_flags: HashMap::new(),
-            context_values: HashMap::new(),
-            target_config: TargetConfig::default(),
-            items: vec![],
             active_skill: SkillData {
                 id: "test_skill".to_string(),
-                skill_type: SkillType::Active,
                 damage_type: Some("fire".to_string()),
-                is_attack: false,
-                level: 1,
                 base_damage: [
                     ("dmg.fire.min".to_string(), 50.0),
                     ("dmg.fire.max".to_string(), 100.0),
@@ -532,21 +1067,11 @@ mod tests {
                 .into_iter()
                 .collect(),
                 base_time: 0.8,
-                cooldown: None,
                 mana_cost: 10,
-                effectiveness: 1.0,
                 tags: vec!["Tag_Spell".to_string(), "Tag_Fire".to_string()],
-                stats: HashMap::new(),
-                injected_tags: vec![],
-                mana_multiplier: 1.0,
-                level_data: None,
-                scaling_rules: vec![],
+                ..Default::default()
             },
-            support_skills: vec![],
-            global_overrides: HashMap::new(),
-            preview_slot: None,
-            mechanic_states: vec![],
-            mechanic_definitions: vec![],
+            ..Default::default()
         }
     }
 
@@ -567,6 +1092,15 @@ mod tests {
 
         // 结果应该相同
         assert_eq!(result1.dps_theoretical, result2.dps_theoretical);
+
+        // 缓存溯源：首次未命中，再次命中结果缓存
+        let info1 = result1.cache_info.unwrap();
+        let info2 = result2.cache_info.unwrap();
+        assert!(!info1.result_hit && info1.reused_layers.is_empty());
+        assert!(info2.result_hit);
+        assert_eq!(info2.reused_layers, vec!["result".to_string()]);
+        assert_eq!(info1.cache_key, info2.cache_key);
+        assert!(calculate_dps(&input).unwrap().cache_info.is_none());
     }
 
     #[test]
@@ -586,23 +1120,319 @@ mod tests {
         assert_eq!(calculator.cache_hits, 0);
     }
 
+    #[test]
+    fn test_cache_key_distinguishes_supports_from_auras() {
+        let mut input = create_test_input();
+        let mut skill = input.active_skill.clone();
+        skill.id = "aura_of_fire".to_string();
+        skill.skill_type = SkillType::Aura;
+        input.support_skills = vec![skill.clone()];
+        let as_support = CacheKey::from_input(&input).fingerprint();
+
+        input.support_skills.clear();
+        input.auras = vec![skill];
+        assert_ne!(CacheKey::from_input(&input).fingerprint(), as_support);
+    }
+
+    #[test]
+    fn test_cache_key_covers_skill_quality_stats() {
+        let mut input = create_test_input();
+        input.active_skill.quality = 20;
+        let base = CacheKey::from_input(&input).fingerprint();
+        input.active_skill.quality_stats = HashMap::from([("mod.inc.dmg.all".to_string(), 0.01)]);
+        assert_ne!(CacheKey::from_input(&input).fingerprint(), base);
+    }
+
+    #[test]
+    fn test_cache_key_covers_skill_part_data() {
+        let mut input = create_test_input();
+        input.active_skill.parts = vec![SkillPart {
+            id: "explosion".to_string(),
+            base_damage: HashMap::from([("dmg.fire.min".to_string(), 50.0)]),
+            effectiveness: None,
+            tags: vec![],
+            stats: HashMap::new(),
+            weight: 1.0,
+        }];
+        let base = CacheKey::from_input(&input).fingerprint();
+
+        let mut changed = input.clone();
+        changed.active_skill.parts[0].base_damage.insert("dmg.fire.min".to_string(), 80.0);
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+
+        let mut changed = input.clone();
+        changed.active_skill.parts[0].effectiveness = Some(1.5);
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+
+        let mut changed = input.clone();
+        changed.active_skill.parts[0].tags.push("Tag_AOE".to_string());
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+
+        let mut changed = input;
+        changed.active_skill.parts[0].stats.insert("mod.inc.dmg".to_string(), 0.2);
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+    }
+
+    #[test]
+    fn test_cache_key_covers_item_data() {
+        let mut input = create_test_input();
+        input.items = vec![serde_json::from_value(serde_json::json!({
+            "id": "sword", "base_type": "sword", "slot": "weapon_main",
+            "affixes": [{ "id": "fire", "group": "fire", "value": 5.0, "stats": { "dmg.fire.min": 5.0 } }],
+        }))
+        .unwrap()];
+        let base = CacheKey::from_input(&input).fingerprint();
+
+        let mut changed = input.clone();
+        changed.items[0].base_type = "axe".to_string();
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+
+        let mut changed = input.clone();
+        changed.items[0].tags.push("Weapon_Sword".to_string());
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+
+        let mut changed = input.clone();
+        changed.items[0].affixes[0].value = 6.0;
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+
+        let mut changed = input.clone();
+        changed.items[0].affixes[0].is_local = true;
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+
+        let mut changed = input;
+        changed.items[0].affixes[0].excluded_tags.push("Tag_Spell".to_string());
+        assert_ne!(CacheKey::from_input(&changed).fingerprint(), base);
+    }
+
     #[test]
     fn test_calculate_diff() {
         let mut calculator = CachedCalculator::new(16);
         let base_input = create_test_input();
         
         let mut preview_input = create_test_input();
-        preview_input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 0.5); // +50% 火焰增伤
+        preview_input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 0.5.into()); // +50% 火焰增伤
 
         let diff = calculator.calculate_diff(&base_input, &preview_input).unwrap();
 
         // 预览应该有更高的 DPS
         assert!(diff.dps_diff > 0.0);
         assert!(diff.is_positive());
+
+        // 再次预览时两侧均命中结果缓存，键指纹不同
+        let diff = calculator.calculate_diff(&base_input, &preview_input).unwrap();
+        let (base, preview) = (diff.base.cache_info.unwrap(), diff.preview.cache_info.unwrap());
+        assert!(base.result_hit && preview.result_hit);
+        assert_ne!(base.cache_key, preview.cache_key);
+    }
+
+    #[test]
+    fn test_item_contributions() {
+        let mut calculator = CachedCalculator::new(16);
+        let mut input = create_test_input();
+        let ring = |id: &str, slot: SlotType, key: &str, value: f64| ItemData {
+            id: id.to_string(),
+            base_type: "ring".to_string(),
+            slot,
+            is_two_handed: false,
+            base_implicit_stats: HashMap::new(),
+            implicit_stats: HashMap::new(),
+            affixes: vec![AffixData {
+                id: format!("{}_affix", id),
+                group: "test".to_string(),
+                value,
+                stats: HashMap::from([(key.to_string(), value)]),
+                ..Default::default()
+            }],
+            tags: vec![],
+            is_unique: false,
+            is_corrupted: false,
+            set_id: None,
+            quality: 0,
+            requirements: None,
+        };
+        input.items = vec![
+            ring("ring_dmg", SlotType::Ring1, "mod.inc.dmg.all", 0.5),
+            ring("ring_armor", SlotType::Ring2, "def.armor", 500.0),
+        ];
+
+        let contributions = calculator.item_contributions(&input).unwrap();
+        assert_eq!(contributions.len(), 2);
+
+        // 最弱（无 DPS 贡献）的护甲戒指在前
+        assert_eq!(contributions[0].item_id, "ring_armor");
+        assert!(contributions[0].dps_delta.abs() < 1e-9);
+        assert!(contributions[0].ehp_delta.physical > 0.0);
+        assert_eq!(contributions[1].item_id, "ring_dmg");
+        assert!((contributions[1].dps_delta_percent - 50.0).abs() < 1e-6);
+
+        // 再次分析全部命中结果缓存
+        let misses = calculator.cache_misses;
+        calculator.item_contributions(&input).unwrap();
+        assert_eq!(calculator.cache_misses, misses);
+    }
+
+    #[test]
+    fn test_support_contributions() {
+        let mut calculator = CachedCalculator::new(16);
+        let mut input = create_test_input();
+        input.active_skill.mana_cost = 10;
+        let support = |id: &str, value: f64, mana_multiplier: f64| {
+            let mut skill = create_test_input().active_skill;
+            skill.id = id.to_string();
+            skill.skill_type = SkillType::Support;
+            skill.stats = HashMap::from([("mod.inc.dmg.all".to_string(), value)]);
+            skill.mana_multiplier = mana_multiplier;
+            skill
+        };
+        input.support_skills = vec![support("support_big", 0.6, 1.5), support("support_small", 0.2, 1.2)];
+
+        let contributions = calculator.support_contributions(&input).unwrap();
+        let ids: Vec<&str> = contributions.iter().map(|c| c.support_id.as_str()).collect();
+        assert_eq!(ids, vec!["support_small", "support_big"]);
+
+        // 移除小辅助：增伤 80% → 60%
+        let small = &contributions[0];
+        assert!((small.dps_delta_percent - 0.2 / 1.6 * 100.0).abs() < 1e-6);
+        assert_eq!(small.mana_multiplier, 1.2);
+        assert!(small.mana_cost_delta > 0.0);
+    }
+
+    #[test]
+    fn test_compare_builds() {
+        let mut calculator = CachedCalculator::new(16);
+        let a = create_test_input();
+        let mut b = create_test_input();
+        b.global_overrides.insert("mod.inc.dmg.fire".to_string(), 0.5.into());
+
+        let comparison = calculator.compare_builds(&a, &b).unwrap();
+        let zone = |key: &str| comparison.zones.iter().find(|d| d.key == key).unwrap();
+        assert!((zone("increased_zone").delta - 0.5).abs() < 1e-6);
+        assert_eq!(zone("crit_zone").delta, 0.0);
+        assert_eq!(comparison.summary[0].key, "dps_theoretical");
+        assert!((comparison.summary[0].delta_percent - 50.0).abs() < 1e-6);
+        assert_eq!(comparison.defenses.len(), 5);
+
+        let fire = comparison.damage_types.iter().find(|d| d.key == "fire").unwrap();
+        assert!(fire.delta > 0.0);
+
+        // 与预览对比共用结果缓存
+        calculator.calculate_diff(&a, &b).unwrap();
+        assert_eq!(calculator.cache_hits, 2);
+    }
+
+    #[test]
+    fn test_evaluate_scenarios() {
+        let mut calculator = CachedCalculator::new(16);
+        let input = create_test_input();
+        let boss = TargetConfig {
+            resistances: HashMap::from([("fire".to_string(), 0.5)]),
+            ..TargetConfig::default()
+        };
+        let lucky = |name: &str| ScenarioDelta {
+            name: name.to_string(),
+            context_flags: HashMap::from([("lucky_damage".to_string(), true)]),
+            ..ScenarioDelta::default()
+        };
+        let scenarios = vec![
+            ScenarioDelta { name: "boss".to_string(), target_config: Some(boss), ..ScenarioDelta::default() },
+            lucky("lucky"),
+            lucky("lucky_again"),
+            ScenarioDelta {
+                name: "focus".to_string(),
+                mechanic_stacks: HashMap::from([("focus_blessing".to_string(), 4)]),
+                ..ScenarioDelta::default()
+            },
+        ];
+
+        let results = calculator.evaluate_scenarios(&input, &scenarios).unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["boss", "lucky", "lucky_again", "focus"]);
+        // 目标抗性只影响有效 DPS
+        let base = calculate_raw(&input).unwrap();
+        assert_eq!(results[0].dps_delta_percent, 0.0);
+        assert!(results[0].dps_effective < base.dps_effective);
+        assert!(results[1].dps_delta_percent > 0.0);
+        assert!(results[3].dps_delta_percent > 0.0);
+
+        // 仅替换目标配置的场景复用基准上下文，其余场景完整计算，相同的聚合差异共享结果缓存
+        let reused: Vec<bool> = results.iter().map(|r| r.context_reused).collect();
+        assert_eq!(reused, vec![true, false, false, false]);
+        assert_eq!(calculator.context_misses, 1);
+        assert_eq!(calculator.cache_hits, 1);
+
+        // 与单次计算一致
+        let direct = calculate_raw(&scenarios[1].apply(&input)).unwrap();
+        assert!((results[1].dps_theoretical - direct.dps_theoretical).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_full_calculation_inputs_skip_prepared_path() {
+        let mut calculator = CachedCalculator::new(16);
+        let mut input = create_test_input();
+        input.active_skill.mana_cost = 10;
+        input.sustain_model = Some(SustainModel { mana_regen: Some(1.0), ..Default::default() });
+        let base = calculate_raw(&input).unwrap();
+        assert!(base.dps_sustained < base.dps_theoretical);
+
+        // 场景对比与增量预览均按完整计算，持续 DPS 与单次计算一致
+        let scenarios = vec![ScenarioDelta { name: "same".to_string(), ..ScenarioDelta::default() }];
+        let results = calculator.evaluate_scenarios(&input, &scenarios).unwrap();
+        assert!((results[0].dps_theoretical - base.dps_theoretical).abs() < 1e-9);
+        assert_eq!(calculator.context_misses, 0);
+
+        let ring = ItemData {
+            implicit_stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.2)]),
+            ..ItemData::new("ring", "ring", SlotType::Ring1)
+        };
+        let diff = calculator.calculate_diff_incremental(&input, &ring, SlotType::Ring1).unwrap();
+        assert!((diff.base.dps_sustained - base.dps_sustained).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_export_and_import_state() {
+        let mut calculator = CachedCalculator::new(16);
+        let input = create_test_input();
+        let mut leveled = create_test_input();
+        leveled.active_skill.level = 10;
+
+        // 默认不记录
+        calculator.calculate(&input).unwrap();
+        assert!(calculator.export_state(STATE_HISTORY_CAPACITY).entries.is_empty());
+
+        calculator.set_recording(true);
+        calculator.calculate(&input).unwrap();
+        calculator.calculate(&leveled).unwrap();
+
+        // 只导出最近 1 条记录，快照可经 JSON 往返
+        let state = calculator.export_state(1);
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.entries[0].input.active_skill.level, 10);
+        assert_eq!(state.cache_stats.result_cache.misses, 2);
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state: CalculatorState = serde_json::from_str(&json).unwrap();
+
+        // 在新的计算器中重放，重放不写入计算记录
+        let mut replayer = CachedCalculator::new(16);
+        replayer.set_recording(true);
+        let replay = replayer.import_state(&state);
+        assert!(replay.engine_version_matches && replay.data_version_matches);
+        assert!(replay.entries[0].matches);
+        assert!(replayer.export_state(STATE_HISTORY_CAPACITY).entries.is_empty());
+
+        // 单条记录失败不影响其余记录
+        state.entries[0].output.dps_theoretical *= 2.0;
+        let mut invalid = state.entries[0].clone();
+        invalid.input.character = Some(CharacterConfig { level: 1, class: Some("unknown".to_string()) });
+        state.entries.insert(0, invalid);
+        let replay = replayer.import_state(&state);
+        assert!(replay.entries[0].error.is_some() && replay.entries[0].dps_replayed.is_none());
+        assert!(replay.entries[1].error.is_none() && !replay.entries[1].matches);
     }
 
     #[test]
     fn test_prepared_context_cache() {
+        use crate::modifiers::ModifierStore;
+
         let mut calculator = CachedCalculator::new(16);
         let input = create_test_input();
 
@@ -617,7 +1447,7 @@ mod tests {
         assert_eq!(calculator.context_hits, 1);
 
         // 验证两次结果一致
-        assert!((ctx1.stat_pool.get_base("dmg.fire.min") - ctx2.stat_pool.get_base("dmg.fire.min")).abs() < 0.001);
+        assert!((ctx1.mod_db.sum_base("dmg.fire.min") - ctx2.mod_db.sum_base("dmg.fire.min")).abs() < 0.001);
     }
 
     #[test]
@@ -0,0 +1,152 @@
+//! 角色成长模块
+//!
+//! 按角色等级与职业推导基础生命与魔力，调用方无需再手动覆盖 `base.life`
+//!
+//! ## 核心设计
+//!
+//! 1. 成长参数由数据表 `src/data/character_table.json` 配置（职业 ID → 成长参数）
+//! 2. 数值 = 1 级基础值 + 每级成长 × (等级 - 1)，等级 0 按 1 级处理
+//! 3. 未指定或未登记的职业使用 `default` 条目
+//! 4. 职业天赋加成（`innate_stats`）按普通属性聚合，来源记为 `class:<职业 ID>`
+//! 5. 不推导命中值：命中率尚未按目标闪避建模，等级成长的命中值会使命中率偏离默认值
+
+use crate::types::CharacterConfig;
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 角色成长表 JSON 内容（编译时内嵌）
+pub(crate) const CHARACTER_TABLE_JSON: &str = include_str!("data/character_table.json");
+
+/// 通用成长条目的职业 ID
+pub const DEFAULT_CLASS: &str = "default";
+
+/// 单个职业的成长参数与天赋加成
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClassGrowth {
+    /// 1 级基础生命
+    #[serde(default)]
+    pub life: f64,
+    /// 每级生命成长
+    #[serde(default)]
+    pub life_per_level: f64,
+    /// 1 级基础魔力
+    #[serde(default)]
+    pub mana: f64,
+    /// 每级魔力成长
+    #[serde(default)]
+    pub mana_per_level: f64,
+    /// 职业天赋加成
+    #[serde(default)]
+    pub innate_stats: HashMap<String, f64>,
+}
+
+/// 角色成长表
+#[derive(Debug, Clone, Default)]
+pub struct CharacterTable {
+    /// 职业 ID -> 成长参数
+    classes: HashMap<String, ClassGrowth>,
+}
+
+impl CharacterTable {
+    /// 从 JSON 加载角色成长表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut classes = HashMap::new();
+        for (class, value) in raw {
+            // 跳过元数据
+            if class.starts_with('_') {
+                continue;
+            }
+
+            let growth: ClassGrowth = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse class '{}': {}", class, e))?;
+            classes.insert(class, growth);
+        }
+
+        Ok(Self { classes })
+    }
+
+    /// 加载内嵌的默认角色成长表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不提供基础属性）
+    pub fn load_default() -> Self {
+        load_embedded("character_table", Self::from_json(CHARACTER_TABLE_JSON))
+    }
+
+    /// 获取共享的默认角色成长表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_CHARACTER: OnceLock<CharacterTable> = OnceLock::new();
+        DEFAULT_CHARACTER.get_or_init(Self::load_default)
+    }
+
+    /// 查询职业成长参数（未登记时回退到 `default`）
+    pub fn growth(&self, class: Option<&str>) -> Option<&ClassGrowth> {
+        class
+            .and_then(|class| self.classes.get(class))
+            .or_else(|| self.classes.get(DEFAULT_CLASS))
+    }
+
+    /// 职业是否已登记
+    pub fn is_registered(&self, class: &str) -> bool {
+        self.classes.contains_key(class)
+    }
+
+    /// 职业天赋加成（仅已登记的职业；通用成长不提供天赋）
+    pub fn innate_stats(&self, class: &str) -> Option<&HashMap<String, f64>> {
+        self.classes.get(class).map(|growth| &growth.innate_stats)
+    }
+
+    /// 推导角色基础属性: (属性键, 数值)
+    pub fn base_stats(&self, character: &CharacterConfig) -> Vec<(&'static str, f64)> {
+        let Some(growth) = self.growth(character.class.as_deref()) else {
+            return Vec::new();
+        };
+        let levels = character.level.saturating_sub(1) as f64;
+        vec![
+            ("base.life", growth.life + growth.life_per_level * levels),
+            ("base.mana", growth.mana + growth.mana_per_level * levels),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(level: u32, class: Option<&str>) -> CharacterConfig {
+        CharacterConfig { level, class: class.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_base_stats_scale_with_level() {
+        let table = CharacterTable::load_default();
+        let stats = table.base_stats(&character(80, Some("berserker")));
+        assert_eq!(stats[0], ("base.life", 70.0 + 14.0 * 79.0));
+        assert_eq!(stats[1], ("base.mana", 35.0 + 5.0 * 79.0));
+
+        // 等级 0 按 1 级处理
+        assert_eq!(table.base_stats(&character(0, Some("mage")))[0], ("base.life", 50.0));
+    }
+
+    #[test]
+    fn test_unknown_class_uses_default() {
+        let table = CharacterTable::load_default();
+        assert_eq!(
+            table.base_stats(&character(10, Some("unknown_hero"))),
+            table.base_stats(&character(10, None))
+        );
+        assert!(CharacterTable::default().base_stats(&character(10, None)).is_empty());
+    }
+
+    #[test]
+    fn test_innate_stats_per_class() {
+        let table = CharacterTable::load_default();
+        assert_eq!(table.innate_stats("mage").unwrap()["mod.inc.dmg.spell"], 0.1);
+        assert!(table.innate_stats(DEFAULT_CLASS).unwrap().is_empty());
+        assert!(table.innate_stats("unknown_hero").is_none());
+    }
+}
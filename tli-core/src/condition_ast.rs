@@ -5,13 +5,18 @@
 //! ## 支持的条件类型
 //!
 //! - **Flag**: 布尔标志检查 (`is_moving`, `cannot_crit`)
-//! - **Compare**: 数值比较 (`life_percent <= 0.35`)
+//! - **StringCompare**: 字符串比较 (`weapon_type == "claw"`)
+//! - **Compare**: 数值比较 (`life_percent <= 0.35`)，两侧可为算术表达式 (`mana - reserved_mana >= 200`)
 //! - **HasTag**: 标签检查 (`has_tag("Tag_Spell")`)
 //! - **MechanicActive**: 机制激活检查 (`mechanic_active("focus_blessing")`)
 //! - **MechanicStacks**: 机制层数检查 (`mechanic_stacks("fighting_will") >= 50`)
 //! - **PerStat**: 每 X 点属性效果 (`per_stat("dexterity", 10)` → 返回倍数)
+//! - **Stat**: 聚合后的属性最终值，可用于比较 (`stat("crit.chance") >= 1.0`)
 //! - **And/Or/Not**: 复合条件
 //!
+//! 另提供数值表达式 [`ValueExpr`]（`fighting_will * 0.002`），支持 `+ - * /`、括号与取负，
+//! 标识符按评估上下文的数值取值（未提供时为 0）。条件与数值表达式共用同一词法分析器与递归下降解析器
+//!
 //! ## 使用示例
 //!
 //! ```ignore
@@ -24,8 +29,12 @@
 //! assert!(cond.evaluate(&ctx));
 //! ```
 
+use crate::mechanics::MechanicsProcessor;
+use crate::tags::ContextTags;
+use crate::types::{CalculatorInput, ConditionFactor};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 
 /// 比较运算符
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -40,6 +49,7 @@ pub enum CompareOp {
 
 impl CompareOp {
     /// 从字符串解析运算符
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "==" => Some(CompareOp::Eq),
@@ -52,6 +62,18 @@ impl CompareOp {
         }
     }
 
+    /// 运算符符号
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+
     /// 评估比较
     pub fn evaluate(&self, lhs: f64, rhs: f64) -> bool {
         match self {
@@ -66,9 +88,10 @@ impl CompareOp {
 }
 
 /// 条件 AST 节点
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum Condition {
     /// 始终为真
+    #[default]
     True,
     /// 始终为假
     False,
@@ -76,6 +99,10 @@ pub enum Condition {
     Flag { key: String, expected: bool },
     /// 数值比较
     Compare { key: String, op: CompareOp, value: f64 },
+    /// 算术表达式比较（`life_percent * 100 <= 35`）
+    CompareExpr { left: ValueExpr, op: CompareOp, right: ValueExpr },
+    /// 字符串比较（`weapon_type == "claw"`，`equal` 为 false 表示 `!=`）
+    StringCompare { key: String, value: String, equal: bool },
     /// 标签检查
     HasTag { tag: String },
     /// 标签集合检查（任一）
@@ -96,12 +123,6 @@ pub enum Condition {
     Not(Box<Condition>),
 }
 
-impl Default for Condition {
-    fn default() -> Self {
-        Condition::True
-    }
-}
-
 impl Condition {
     /// 解析条件表达式字符串
     ///
@@ -109,281 +130,913 @@ impl Condition {
     /// - `true` / `false`
     /// - `key == true` / `key == false`
     /// - `key <= 0.35` / `key >= 100`
+    /// - `key == "claw"` / `key != "claw"`（字符串上下文；字符串须加引号，`key == claw` 报错）
+    /// - 比较两侧的算术表达式：`life_percent * 100 <= 35` / `mana - reserved_mana >= 200`
     /// - `has_tag("Tag_Spell")`
     /// - `mechanic_active("focus_blessing")`
     /// - `mechanic_stacks("fighting_will") >= 50`
     /// - `per_stat("dexterity", 10)`
+    /// - `stat("crit.chance") >= 1.0`（引用聚合后的属性最终值）
     /// - `cond1 && cond2` / `cond1 || cond2`
     /// - `!cond`
-    pub fn parse(expr: &str) -> Result<Self, String> {
-        let expr = expr.trim();
+    ///
+    /// 优先级从高到低：算术（`* /` 高于 `+ -`）→ 比较 → `!` → `&&` → `||`，同级左结合，括号可任意嵌套
+    ///
+    /// 解析失败时返回 [`ConditionParseError`]（出错位置、期望的词法单元与拼写相近的已知标识符）
+    pub fn parse(expr: &str) -> Result<Self, ConditionParseError> {
+        let tokens = tokenize(expr)?;
 
-        // 空字符串或 "true" 返回 True
-        if expr.is_empty() || expr == "true" {
+        // 空字符串视为始终为真
+        if tokens.is_empty() {
             return Ok(Condition::True);
         }
-        if expr == "false" {
-            return Ok(Condition::False);
+
+        let mut parser = TokenParser { tokens, pos: 0, end: expr.len() };
+        let operand = parser.parse_expr(PRECEDENCE_OR)?;
+        let condition = parser.expect_condition(operand)?;
+        parser.expect_end(LOGICAL_OPERATORS)?;
+        Ok(condition)
+    }
+
+    /// 评估条件
+    pub fn evaluate(&self, ctx: &EvalContext) -> bool {
+        match self {
+            Condition::True => true,
+            Condition::False => false,
+            Condition::Flag { key, expected } => {
+                ctx.flags.get(key).copied().unwrap_or(false) == *expected
+            }
+            Condition::Compare { key, op, value } => {
+                let actual = ctx.values.get(key).copied().unwrap_or(0.0);
+                op.evaluate(actual, *value)
+            }
+            Condition::CompareExpr { left, op, right } => op.evaluate(left.evaluate(ctx), right.evaluate(ctx)),
+            Condition::StringCompare { key, value, equal } => (ctx.strings.get(key) == Some(value)) == *equal,
+            Condition::HasTag { tag } => ctx.tags.contains(tag),
+            Condition::HasAnyTag { tags } => tags.iter().any(|t| ctx.tags.contains(t)),
+            Condition::HasAllTags { tags } => tags.iter().all(|t| ctx.tags.contains(t)),
+            Condition::MechanicActive { mechanic_id } => {
+                ctx.mechanic_stacks.get(mechanic_id).copied().unwrap_or(0) > 0
+            }
+            Condition::MechanicStacks { mechanic_id, op, value } => {
+                let stacks = ctx.mechanic_stacks.get(mechanic_id).copied().unwrap_or(0);
+                op.evaluate(stacks as f64, *value as f64)
+            }
+            Condition::PerStat { stat, per } => {
+                // PerStat 不用于布尔评估，用于计算倍数
+                // 这里返回 true 如果有任意层数
+                let value = ctx.values.get(stat).copied().unwrap_or(0.0);
+                value >= *per
+            }
+            Condition::And(left, right) => left.evaluate(ctx) && right.evaluate(ctx),
+            Condition::Or(left, right) => left.evaluate(ctx) || right.evaluate(ctx),
+            Condition::Not(inner) => !inner.evaluate(ctx),
         }
+    }
 
-        // 处理逻辑运算符（优先级：NOT > AND > OR）
-        // 先处理 OR（最低优先级）
-        if let Some(idx) = Self::find_logical_op(expr, "||") {
-            let left = Condition::parse(&expr[..idx])?;
-            let right = Condition::parse(&expr[idx + 2..])?;
-            return Ok(Condition::Or(Box::new(left), Box::new(right)));
+    /// 评估条件并给出决定结果的原子条件
+    ///
+    /// 按短路语义溯源：与/或中仅列出实际决定结果的一侧，两侧共同决定时全部列出；
+    /// 常量条件不产生原子条件
+    pub fn explain(&self, ctx: &EvalContext) -> (bool, Vec<ConditionFactor>) {
+        let factor = |key: &str, actual: String, expected: String, passed: bool| {
+            (passed, vec![ConditionFactor { key: key.to_string(), actual, expected, passed }])
+        };
+
+        match self {
+            Condition::True => (true, vec![]),
+            Condition::False => (false, vec![]),
+            Condition::Flag { key, expected } => {
+                let actual = ctx.flags.get(key).copied().unwrap_or(false);
+                factor(key, actual.to_string(), format!("== {}", expected), actual == *expected)
+            }
+            Condition::Compare { key, op, value } => {
+                let actual = ctx.values.get(key).copied().unwrap_or(0.0);
+                factor(key, actual.to_string(), format!("{} {}", op.symbol(), value), op.evaluate(actual, *value))
+            }
+            Condition::CompareExpr { left, op, right } => {
+                let (actual, threshold) = (left.evaluate(ctx), right.evaluate(ctx));
+                let passed = op.evaluate(actual, threshold);
+                factor(&left.to_string(), actual.to_string(), format!("{} {}", op.symbol(), threshold), passed)
+            }
+            Condition::StringCompare { key, value, equal } => {
+                let actual = ctx.strings.get(key);
+                let op = if *equal { "==" } else { "!=" };
+                let passed = (actual == Some(value)) == *equal;
+                factor(key, format!("{:?}", actual.map_or("", String::as_str)), format!("{} {:?}", op, value), passed)
+            }
+            Condition::HasTag { tag } => {
+                let present = ctx.tags.contains(tag);
+                factor(tag, present.to_string(), "== true".to_string(), present)
+            }
+            Condition::HasAnyTag { tags } | Condition::HasAllTags { tags } => {
+                let require_all = matches!(self, Condition::HasAllTags { .. });
+                let checks: Vec<ConditionFactor> = tags
+                    .iter()
+                    .map(|tag| {
+                        let present = ctx.tags.contains(tag);
+                        ConditionFactor {
+                            key: tag.clone(),
+                            actual: present.to_string(),
+                            expected: "== true".to_string(),
+                            passed: present,
+                        }
+                    })
+                    .collect();
+                let passed = if require_all {
+                    checks.iter().all(|c| c.passed)
+                } else {
+                    checks.iter().any(|c| c.passed)
+                };
+                // 全部要求时由未满足的标签决定，任一要求时由满足的标签决定
+                let deciding: Vec<ConditionFactor> = checks.iter().filter(|c| c.passed == passed).cloned().collect();
+                (passed, if deciding.is_empty() { checks } else { deciding })
+            }
+            Condition::MechanicActive { mechanic_id } => {
+                let stacks = ctx.mechanic_stacks.get(mechanic_id).copied().unwrap_or(0);
+                factor(mechanic_id, stacks.to_string(), "> 0".to_string(), stacks > 0)
+            }
+            Condition::MechanicStacks { mechanic_id, op, value } => {
+                let stacks = ctx.mechanic_stacks.get(mechanic_id).copied().unwrap_or(0);
+                let passed = op.evaluate(stacks as f64, *value as f64);
+                factor(mechanic_id, stacks.to_string(), format!("{} {}", op.symbol(), value), passed)
+            }
+            Condition::PerStat { stat, per } => {
+                let value = ctx.values.get(stat).copied().unwrap_or(0.0);
+                factor(stat, value.to_string(), format!(">= {}", per), value >= *per)
+            }
+            Condition::And(left, right) => {
+                let (left_passed, mut left_factors) = left.explain(ctx);
+                if !left_passed {
+                    return (false, left_factors);
+                }
+                let (right_passed, right_factors) = right.explain(ctx);
+                if !right_passed {
+                    return (false, right_factors);
+                }
+                left_factors.extend(right_factors);
+                (true, left_factors)
+            }
+            Condition::Or(left, right) => {
+                let (left_passed, mut left_factors) = left.explain(ctx);
+                if left_passed {
+                    return (true, left_factors);
+                }
+                let (right_passed, right_factors) = right.explain(ctx);
+                if right_passed {
+                    return (true, right_factors);
+                }
+                left_factors.extend(right_factors);
+                (false, left_factors)
+            }
+            Condition::Not(inner) => {
+                let (passed, factors) = inner.explain(ctx);
+                (!passed, factors)
+            }
         }
+    }
 
-        // 处理 AND
-        if let Some(idx) = Self::find_logical_op(expr, "&&") {
-            let left = Condition::parse(&expr[..idx])?;
-            let right = Condition::parse(&expr[idx + 2..])?;
-            return Ok(Condition::And(Box::new(left), Box::new(right)));
+    /// 条件通过 `stat("...")` 引用的属性键
+    pub fn stat_references(&self) -> Vec<&str> {
+        match self {
+            Condition::CompareExpr { left, right, .. } => {
+                let mut keys = left.stat_references();
+                keys.extend(right.stat_references());
+                keys
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut keys = left.stat_references();
+                keys.extend(right.stat_references());
+                keys
+            }
+            Condition::Not(inner) => inner.stat_references(),
+            _ => vec![],
         }
+    }
 
-        // 处理 NOT
-        if expr.starts_with('!') {
-            let inner = Condition::parse(&expr[1..])?;
-            return Ok(Condition::Not(Box::new(inner)));
+    /// 条件引用的上下文键（布尔标志与数值比较的键，含算术表达式中的标识符）
+    pub fn context_keys(&self) -> Vec<&str> {
+        match self {
+            Condition::Flag { key, .. } | Condition::Compare { key, .. } | Condition::StringCompare { key, .. } => {
+                vec![key.as_str()]
+            }
+            Condition::CompareExpr { left, right, .. } => {
+                let mut keys = left.variables();
+                keys.extend(right.variables());
+                keys
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut keys = left.context_keys();
+                keys.extend(right.context_keys());
+                keys
+            }
+            Condition::Not(inner) => inner.context_keys(),
+            _ => vec![],
         }
+    }
 
-        // 处理括号
-        if expr.starts_with('(') && expr.ends_with(')') {
-            return Condition::parse(&expr[1..expr.len() - 1]);
+    /// 条件引用的机制 ID（`mechanic_active` / `mechanic_stacks`）
+    pub fn mechanic_references(&self) -> Vec<&str> {
+        match self {
+            Condition::MechanicActive { mechanic_id } | Condition::MechanicStacks { mechanic_id, .. } => {
+                vec![mechanic_id.as_str()]
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut ids = left.mechanic_references();
+                ids.extend(right.mechanic_references());
+                ids
+            }
+            Condition::Not(inner) => inner.mechanic_references(),
+            _ => vec![],
         }
+    }
 
-        // 处理函数调用
-        if expr.starts_with("has_tag(") {
-            return Self::parse_has_tag(expr);
+    /// 计算 PerStat 倍数
+    pub fn evaluate_multiplier(&self, ctx: &EvalContext) -> f64 {
+        match self {
+            Condition::PerStat { stat, per } => {
+                let value = ctx.values.get(stat).copied().unwrap_or(0.0);
+                (value / per).floor()
+            }
+            _ => 1.0,
         }
-        if expr.starts_with("has_any_tag(") {
-            return Self::parse_has_any_tag(expr);
+    }
+}
+
+/// 算术运算符
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArithOp {
+    Add, // +
+    Sub, // -
+    Mul, // *
+    Div, // /
+}
+
+impl ArithOp {
+    /// 计算（除数为 0 时结果为 0）
+    pub fn apply(&self, lhs: f64, rhs: f64) -> f64 {
+        match self {
+            ArithOp::Add => lhs + rhs,
+            ArithOp::Sub => lhs - rhs,
+            ArithOp::Mul => lhs * rhs,
+            ArithOp::Div if rhs == 0.0 => 0.0,
+            ArithOp::Div => lhs / rhs,
         }
-        if expr.starts_with("has_all_tags(") {
-            return Self::parse_has_all_tags(expr);
+    }
+
+    /// 运算符符号
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+            ArithOp::Div => "/",
         }
-        if expr.starts_with("mechanic_active(") {
-            return Self::parse_mechanic_active(expr);
+    }
+
+    /// 优先级（乘除高于加减）
+    fn precedence(&self) -> u8 {
+        match self {
+            ArithOp::Add | ArithOp::Sub => 1,
+            ArithOp::Mul | ArithOp::Div => 2,
         }
-        if expr.starts_with("mechanic_stacks(") {
-            return Self::parse_mechanic_stacks(expr);
+    }
+}
+
+/// 数值表达式 AST 节点
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ValueExpr {
+    /// 常量
+    Const(f64),
+    /// 引用上下文数值（属性键、机制层数、上下文数值）
+    Var(String),
+    /// 引用聚合后的属性最终值（`stat("crit.chance")`）
+    Stat(String),
+    /// 取负
+    Neg(Box<ValueExpr>),
+    /// 二元运算
+    Binary { op: ArithOp, left: Box<ValueExpr>, right: Box<ValueExpr> },
+}
+
+impl ValueExpr {
+    /// 解析数值表达式字符串
+    ///
+    /// 支持的格式:
+    /// - 数字 `0.002`、标识符 `fighting_will` / `crit.chance`
+    /// - `a + b` / `a - b` / `a * b` / `a / b`（乘除优先于加减，同级左结合）
+    /// - `(expr)` / `-expr`
+    pub fn parse(expr: &str) -> Result<Self, ConditionParseError> {
+        let mut parser = TokenParser { tokens: tokenize(expr)?, pos: 0, end: expr.len() };
+        let value = parser.parse_value(PRECEDENCE_SUM)?;
+        parser.expect_end(ARITH_OPERATORS)?;
+        Ok(value)
+    }
+
+    /// 评估表达式（未提供的标识符取 0）
+    pub fn evaluate(&self, ctx: &EvalContext) -> f64 {
+        match self {
+            ValueExpr::Const(value) => *value,
+            ValueExpr::Var(key) => ctx.values.get(key).copied().unwrap_or(0.0),
+            ValueExpr::Stat(key) => ctx.stats.get(key).copied().unwrap_or(0.0),
+            ValueExpr::Neg(inner) => -inner.evaluate(ctx),
+            ValueExpr::Binary { op, left, right } => op.apply(left.evaluate(ctx), right.evaluate(ctx)),
         }
-        if expr.starts_with("per_stat(") {
-            return Self::parse_per_stat(expr);
+    }
+
+    /// 表达式引用的标识符
+    pub fn variables(&self) -> Vec<&str> {
+        match self {
+            ValueExpr::Const(_) | ValueExpr::Stat(_) => vec![],
+            ValueExpr::Var(key) => vec![key.as_str()],
+            ValueExpr::Neg(inner) => inner.variables(),
+            ValueExpr::Binary { left, right, .. } => {
+                let mut keys = left.variables();
+                keys.extend(right.variables());
+                keys
+            }
         }
+    }
 
-        // 处理简单比较
-        Self::parse_comparison(expr)
+    /// 表达式是否为常量（不引用上下文键与 `stat("...")`）
+    pub fn is_constant(&self) -> bool {
+        self.variables().is_empty() && self.stat_references().is_empty()
     }
 
-    /// 查找逻辑运算符位置（跳过括号内的内容）
-    fn find_logical_op(expr: &str, op: &str) -> Option<usize> {
-        let mut depth = 0;
-        let bytes = expr.as_bytes();
-        let op_bytes = op.as_bytes();
-        
-        for i in 0..bytes.len() {
-            match bytes[i] {
-                b'(' => depth += 1,
-                b')' => depth -= 1,
-                _ if depth == 0 && i + op.len() <= bytes.len() => {
-                    if &bytes[i..i + op.len()] == op_bytes {
-                        return Some(i);
+    /// 表达式通过 `stat("...")` 引用的属性键
+    pub fn stat_references(&self) -> Vec<&str> {
+        match self {
+            ValueExpr::Const(_) | ValueExpr::Var(_) => vec![],
+            ValueExpr::Stat(key) => vec![key.as_str()],
+            ValueExpr::Neg(inner) => inner.stat_references(),
+            ValueExpr::Binary { left, right, .. } => {
+                let mut keys = left.stat_references();
+                keys.extend(right.stat_references());
+                keys
+            }
+        }
+    }
+}
+
+/// 按最少括号还原表达式文本（用于条件溯源）
+impl fmt::Display for ValueExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueExpr::Const(value) => write!(f, "{}", value),
+            ValueExpr::Var(key) => write!(f, "{}", key),
+            ValueExpr::Stat(key) => write!(f, "stat(\"{}\")", key),
+            ValueExpr::Neg(inner) => match inner.as_ref() {
+                ValueExpr::Binary { .. } => write!(f, "-({})", inner),
+                _ => write!(f, "-{}", inner),
+            },
+            ValueExpr::Binary { op, left, right } => {
+                let wrap = |child: &ValueExpr, right_side: bool| match child {
+                    ValueExpr::Binary { op: child_op, .. } => {
+                        child_op.precedence() < op.precedence()
+                            || (right_side
+                                && child_op.precedence() == op.precedence()
+                                && matches!(op, ArithOp::Sub | ArithOp::Div))
                     }
-                }
-                _ => {}
+                    _ => false,
+                };
+                let side = |child: &ValueExpr, right_side: bool| {
+                    if wrap(child, right_side) {
+                        format!("({})", child)
+                    } else {
+                        child.to_string()
+                    }
+                };
+                write!(f, "{} {} {}", side(left, false), op.symbol(), side(right, true))
             }
         }
-        None
     }
+}
 
-    /// 解析 has_tag("Tag_Spell")
-    fn parse_has_tag(expr: &str) -> Result<Self, String> {
-        let inner = expr
-            .strip_prefix("has_tag(")
-            .and_then(|s| s.strip_suffix(')'))
-            .ok_or_else(|| "Invalid has_tag syntax".to_string())?;
-        
-        let tag = inner.trim().trim_matches('"').trim_matches('\'').to_string();
-        Ok(Condition::HasTag { tag })
+/// 条件与数值表达式解析错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConditionParseError {
+    /// 出错位置（字节偏移；表达式意外结束时为表达式长度）
+    pub position: usize,
+    /// 错误描述
+    pub message: String,
+    /// 该位置期望的词法单元
+    pub expected: Vec<String>,
+    /// 拼写最接近的已知标识符或运算符（如 `has_tags` → `has_tag`，`and` → `&&`）
+    pub suggestion: Option<String>,
+}
+
+impl ConditionParseError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self { position, message: message.into(), expected: vec![], suggestion: None }
     }
 
-    /// 解析 has_any_tag("Tag1", "Tag2")
-    fn parse_has_any_tag(expr: &str) -> Result<Self, String> {
-        let inner = expr
-            .strip_prefix("has_any_tag(")
-            .and_then(|s| s.strip_suffix(')'))
-            .ok_or_else(|| "Invalid has_any_tag syntax".to_string())?;
-        
-        let tags: Vec<String> = inner
-            .split(',')
-            .map(|s| s.trim().trim_matches('"').trim_matches('\'').to_string())
-            .collect();
-        
-        Ok(Condition::HasAnyTag { tags })
+    fn expecting(mut self, expected: &[&str]) -> Self {
+        self.expected = expected.iter().map(|token| token.to_string()).collect();
+        self
     }
 
-    /// 解析 has_all_tags("Tag1", "Tag2")
-    fn parse_has_all_tags(expr: &str) -> Result<Self, String> {
-        let inner = expr
-            .strip_prefix("has_all_tags(")
-            .and_then(|s| s.strip_suffix(')'))
-            .ok_or_else(|| "Invalid has_all_tags syntax".to_string())?;
-        
-        let tags: Vec<String> = inner
-            .split(',')
-            .map(|s| s.trim().trim_matches('"').trim_matches('\'').to_string())
-            .collect();
-        
-        Ok(Condition::HasAllTags { tags })
+    fn suggesting(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
     }
+}
 
-    /// 解析 mechanic_active("focus_blessing")
-    fn parse_mechanic_active(expr: &str) -> Result<Self, String> {
-        let inner = expr
-            .strip_prefix("mechanic_active(")
-            .and_then(|s| s.strip_suffix(')'))
-            .ok_or_else(|| "Invalid mechanic_active syntax".to_string())?;
-        
-        let mechanic_id = inner.trim().trim_matches('"').trim_matches('\'').to_string();
-        Ok(Condition::MechanicActive { mechanic_id })
+impl fmt::Display for ConditionParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)?;
+        if !self.expected.is_empty() {
+            write!(f, " (expected {})", self.expected.join(", "))?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean '{}'?", suggestion)?;
+        }
+        Ok(())
     }
+}
 
-    /// 解析 mechanic_stacks("fighting_will") >= 50
-    fn parse_mechanic_stacks(expr: &str) -> Result<Self, String> {
-        // 找到闭括号
-        let close_paren = expr.find(')').ok_or("Missing closing paren")?;
-        let func_part = &expr[..=close_paren];
-        let compare_part = expr[close_paren + 1..].trim();
-        
-        let inner = func_part
-            .strip_prefix("mechanic_stacks(")
-            .and_then(|s| s.strip_suffix(')'))
-            .ok_or_else(|| "Invalid mechanic_stacks syntax".to_string())?;
-        
-        let mechanic_id = inner.trim().trim_matches('"').trim_matches('\'').to_string();
-        
-        // 解析比较部分
-        let operators = ["<=", ">=", "!=", "==", "<", ">"];
-        for op_str in operators {
-            if let Some(idx) = compare_part.find(op_str) {
-                let op = CompareOp::from_str(op_str).ok_or("Invalid operator")?;
-                let value_str = compare_part[idx + op_str.len()..].trim();
-                let value: u32 = value_str
+impl std::error::Error for ConditionParseError {}
+
+/// 条件与数值表达式的词法单元
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// 标识符（字母或下划线开头，可含数字、下划线与 `.`）
+    Ident(String),
+    /// 数字
+    Number(f64),
+    /// 引号字符串（函数参数）
+    Str(String),
+    /// 算术运算符
+    Arith(ArithOp),
+    /// 比较运算符
+    Compare(CompareOp),
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `!`
+    Not,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `,`
+    Comma,
+}
+
+/// 按源文本还原词法单元（用于错误信息）
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Str(text) => write!(f, "\"{}\"", text),
+            Token::Arith(op) => write!(f, "{}", op.symbol()),
+            Token::Compare(op) => write!(f, "{}", op.symbol()),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Not => write!(f, "!"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+/// 将表达式切分为词法单元（附带起始字节偏移）
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, ConditionParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+
+    // 双字符运算符：下一个字符为 `second` 时一并消费
+    fn pair(chars: &mut std::iter::Peekable<std::str::CharIndices>, second: char) -> bool {
+        chars.next_if(|(_, next)| *next == second).is_some()
+    }
+
+    while let Some((pos, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '+' => Token::Arith(ArithOp::Add),
+            '-' => Token::Arith(ArithOp::Sub),
+            '*' => Token::Arith(ArithOp::Mul),
+            '/' => Token::Arith(ArithOp::Div),
+            '&' if pair(&mut chars, '&') => Token::And,
+            '|' if pair(&mut chars, '|') => Token::Or,
+            '=' if pair(&mut chars, '=') => Token::Compare(CompareOp::Eq),
+            '!' if pair(&mut chars, '=') => Token::Compare(CompareOp::Ne),
+            '!' => Token::Not,
+            '<' if pair(&mut chars, '=') => Token::Compare(CompareOp::Le),
+            '<' => Token::Compare(CompareOp::Lt),
+            '>' if pair(&mut chars, '=') => Token::Compare(CompareOp::Ge),
+            '>' => Token::Compare(CompareOp::Gt),
+            '"' | '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, next)) if next == c => break,
+                        Some((_, next)) => text.push(next),
+                        None => {
+                            let quote = if c == '"' { "\"" } else { "'" };
+                            return Err(ConditionParseError::new(pos, "Unterminated string").expecting(&[quote]));
+                        }
+                    }
+                }
+                Token::Str(text)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut text = c.to_string();
+                while let Some((_, next)) = chars.next_if(|(_, next)| next.is_ascii_digit() || *next == '.') {
+                    text.push(next);
+                }
+                let value = text
                     .parse()
-                    .map_err(|_| format!("Invalid number: {}", value_str))?;
-                
-                return Ok(Condition::MechanicStacks { mechanic_id, op, value });
+                    .map_err(|_| ConditionParseError::new(pos, format!("Invalid number '{}'", text)))?;
+                Token::Number(value)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut text = c.to_string();
+                while let Some((_, next)) =
+                    chars.next_if(|(_, next)| next.is_ascii_alphanumeric() || *next == '_' || *next == '.')
+                {
+                    text.push(next);
+                }
+                Token::Ident(text)
             }
+            c => {
+                // 单字符写法的逻辑/比较运算符
+                let suggestion = match c {
+                    '&' => Some("&&"),
+                    '|' => Some("||"),
+                    '=' => Some("=="),
+                    _ => None,
+                };
+                return Err(ConditionParseError::new(pos, format!("Unexpected '{}'", c))
+                    .suggesting(suggestion.map(str::to_string)));
+            }
+        };
+        tokens.push((token, pos));
+    }
+    Ok(tokens)
+}
+
+/// 可用函数名（`stat` 为数值函数，其余为条件函数）
+const FUNCTION_NAMES: &[&str] =
+    &["has_tag", "has_any_tag", "has_all_tags", "mechanic_active", "mechanic_stacks", "per_stat", "stat"];
+
+/// 布尔字面量（与函数名一同作为拼写提示的候选）
+const BOOLEAN_LITERALS: &[&str] = &["true", "false"];
+
+/// 单词形式的逻辑运算符
+const OPERATOR_WORDS: &[(&str, &str)] = &[("and", "&&"), ("or", "||"), ("not", "!")];
+
+const COMPARE_OPERATORS: &[&str] = &["==", "!=", "<", "<=", ">", ">="];
+const LOGICAL_OPERATORS: &[&str] = &["&&", "||"];
+const ARITH_OPERATORS: &[&str] = &["+", "-", "*", "/"];
+const OPERAND: &[&str] = &["number", "identifier", "(", "-"];
+
+/// 与 `word` 拼写最接近的候选（编辑距离不超过词长的 1/3，至少为 1）
+pub fn nearest_identifier<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let word = word.to_ascii_lowercase();
+    if let Some((_, operator)) = OPERATOR_WORDS.iter().find(|(alias, _)| *alias == word) {
+        return Some(operator.to_string());
+    }
+    let max_distance = (word.len() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+/// 编辑距离（Levenshtein）
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
         }
-        
-        // 默认检查是否 > 0
-        Ok(Condition::MechanicStacks {
-            mechanic_id,
-            op: CompareOp::Gt,
-            value: 0,
-        })
-    }
-
-    /// 解析 per_stat("dexterity", 10)
-    fn parse_per_stat(expr: &str) -> Result<Self, String> {
-        let inner = expr
-            .strip_prefix("per_stat(")
-            .and_then(|s| s.strip_suffix(')'))
-            .ok_or_else(|| "Invalid per_stat syntax".to_string())?;
-        
-        let parts: Vec<&str> = inner.split(',').collect();
-        if parts.len() != 2 {
-            return Err("per_stat requires 2 arguments".to_string());
+    }
+    row[b.len()]
+}
+
+/// 二元运算符优先级（数值越大结合越紧）：`||` < `&&` < 比较 < `+ -` < `* /` < 一元运算
+const PRECEDENCE_OR: u8 = 1;
+const PRECEDENCE_AND: u8 = 2;
+const PRECEDENCE_COMPARE: u8 = 3;
+const PRECEDENCE_SUM: u8 = 4;
+const PRECEDENCE_PRODUCT: u8 = 5;
+const PRECEDENCE_UNARY: u8 = 6;
+
+/// 二元运算符的优先级（非二元运算符返回 None）
+fn binary_precedence(token: &Token) -> Option<u8> {
+    match token {
+        Token::Or => Some(PRECEDENCE_OR),
+        Token::And => Some(PRECEDENCE_AND),
+        Token::Compare(_) => Some(PRECEDENCE_COMPARE),
+        Token::Arith(ArithOp::Add | ArithOp::Sub) => Some(PRECEDENCE_SUM),
+        Token::Arith(ArithOp::Mul | ArithOp::Div) => Some(PRECEDENCE_PRODUCT),
+        _ => None,
+    }
+}
+
+/// 解析中间结果（括号内既可以是条件也可以是数值，由所在位置决定如何使用）
+enum Operand {
+    Cond(Condition),
+    Value(ValueExpr),
+    Str(String),
+    Bool(bool),
+}
+
+/// 词法单元优先级爬升解析器（条件与数值表达式共用）
+struct TokenParser {
+    /// 词法单元与起始字节偏移
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// 表达式长度（意外结束时的出错位置）
+    end: usize,
+}
+
+impl TokenParser {
+    fn peek(&self) -> Option<&Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(token, _)| token)
+    }
+
+    /// 当前词法单元的字节偏移
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, at)| *at)
+    }
+
+    /// 下一个词法单元为 `token` 时消费
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
         }
-        
-        let stat = parts[0].trim().trim_matches('"').trim_matches('\'').to_string();
-        let per: f64 = parts[1]
-            .trim()
-            .parse()
-            .map_err(|_| format!("Invalid number: {}", parts[1]))?;
-        
-        Ok(Condition::PerStat { stat, per })
+        false
     }
 
-    /// 解析比较表达式
-    fn parse_comparison(expr: &str) -> Result<Self, String> {
-        let operators = ["<=", ">=", "!=", "==", "<", ">"];
-        
-        for op_str in operators {
-            if let Some(idx) = expr.find(op_str) {
-                let key = expr[..idx].trim().to_string();
-                let value_str = expr[idx + op_str.len()..].trim();
-                
-                // 尝试解析为布尔值
-                if value_str == "true" || value_str == "false" {
-                    let expected = value_str == "true";
-                    if op_str == "==" {
-                        return Ok(Condition::Flag { key, expected });
-                    } else if op_str == "!=" {
-                        return Ok(Condition::Flag { key, expected: !expected });
-                    }
+    fn expect(&mut self, token: &Token) -> Result<(), ConditionParseError> {
+        if self.eat(token) {
+            return Ok(());
+        }
+        Err(self.unexpected(&[&token.to_string()]))
+    }
+
+    fn expect_end(&self, expected: &[&str]) -> Result<(), ConditionParseError> {
+        match self.peek() {
+            Some(_) => Err(self.unexpected(expected)),
+            None => Ok(()),
+        }
+    }
+
+    /// 当前词法单元不符合语法时的错误（标识符附带拼写提示）
+    fn unexpected(&self, expected: &[&str]) -> ConditionParseError {
+        match self.peek() {
+            Some(token) => {
+                let suggestion = match token {
+                    Token::Ident(word) => nearest_identifier(word, FUNCTION_NAMES.iter().chain(BOOLEAN_LITERALS).copied()),
+                    _ => None,
+                };
+                ConditionParseError::new(self.position(), format!("Unexpected '{}'", token))
+                    .expecting(expected)
+                    .suggesting(suggestion)
+            }
+            None => ConditionParseError::new(self.end, "Unexpected end of expression").expecting(expected),
+        }
+    }
+
+    /// expr := prefix (binop expr)*，按优先级爬升单遍解析（同级左结合，不回溯）
+    ///
+    /// 括号内既可以是条件也可以是算术表达式，解析结果为 [`Operand`]，由所在位置决定按条件还是数值使用
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Operand, ConditionParseError> {
+        let mut left = self.parse_prefix()?;
+        while let Some(precedence) = self.peek().and_then(binary_precedence).filter(|p| *p >= min_precedence) {
+            let op_position = self.position();
+            let token = self.tokens[self.pos].0.clone();
+            left = match token {
+                Token::Or | Token::And => {
+                    let left = self.expect_condition(left)?;
+                    self.pos += 1;
+                    let right = self.parse_expr(precedence + 1)?;
+                    let right = self.expect_condition(right)?;
+                    Operand::Cond(if token == Token::Or {
+                        Condition::Or(Box::new(left), Box::new(right))
+                    } else {
+                        Condition::And(Box::new(left), Box::new(right))
+                    })
                 }
-                
-                // 尝试解析为数值
-                if let Ok(value) = value_str.parse::<f64>() {
-                    let op = CompareOp::from_str(op_str).ok_or("Invalid operator")?;
-                    return Ok(Condition::Compare { key, op, value });
+                Token::Compare(op) => {
+                    let Operand::Value(left) = left else {
+                        return Err(self.unexpected(LOGICAL_OPERATORS));
+                    };
+                    self.pos += 1;
+                    let right_position = self.position();
+                    let right = self.parse_expr(PRECEDENCE_SUM)?;
+                    Operand::Cond(Self::comparison(left, op, right, op_position, right_position)?)
                 }
-                
-                return Err(format!("Cannot parse value: {}", value_str));
+                Token::Arith(op) => {
+                    let Operand::Value(left) = left else {
+                        return Err(self.unexpected(LOGICAL_OPERATORS));
+                    };
+                    self.pos += 1;
+                    let right = self.parse_value(precedence + 1)?;
+                    Operand::Value(ValueExpr::Binary { op, left: Box::new(left), right: Box::new(right) })
+                }
+                _ => unreachable!("binary_precedence only accepts binary operators"),
+            };
+        }
+        Ok(left)
+    }
+
+    /// 解析数值表达式（结果不是数值时报错）
+    fn parse_value(&mut self, min_precedence: u8) -> Result<ValueExpr, ConditionParseError> {
+        let position = self.position();
+        match self.parse_expr(min_precedence)? {
+            Operand::Value(value) => Ok(value),
+            _ => Err(ConditionParseError::new(position, "Expected a numeric value").expecting(OPERAND)),
+        }
+    }
+
+    /// 按条件使用解析结果：单个标识符视为布尔标志检查，其余数值表达式须跟比较运算符
+    fn expect_condition(&self, operand: Operand) -> Result<Condition, ConditionParseError> {
+        match operand {
+            Operand::Cond(condition) => Ok(condition),
+            Operand::Bool(value) => Ok(if value { Condition::True } else { Condition::False }),
+            Operand::Value(ValueExpr::Var(key)) => Ok(Condition::Flag { key, expected: true }),
+            Operand::Value(_) | Operand::Str(_) => Err(self.unexpected(COMPARE_OPERATORS)),
+        }
+    }
+
+    /// comparison := value cmp (value | 'true' | 'false' | string)
+    fn comparison(
+        left: ValueExpr,
+        op: CompareOp,
+        right: Operand,
+        op_position: usize,
+        right_position: usize,
+    ) -> Result<Condition, ConditionParseError> {
+        let invalid = |right: String| {
+            ConditionParseError::new(op_position, format!("Cannot compare with {} using {}", right, op.symbol()))
+                .expecting(&["==", "!="])
+        };
+        match (left, op, right) {
+            (ValueExpr::Var(key), CompareOp::Eq | CompareOp::Ne, Operand::Str(value)) => {
+                Ok(Condition::StringCompare { key, value, equal: op == CompareOp::Eq })
+            }
+            (_, _, Operand::Str(value)) => Err(invalid(format!("\"{}\"", value))),
+            (ValueExpr::Var(key), CompareOp::Eq, Operand::Bool(expected)) => Ok(Condition::Flag { key, expected }),
+            (ValueExpr::Var(key), CompareOp::Ne, Operand::Bool(expected)) => {
+                Ok(Condition::Flag { key, expected: !expected })
             }
+            (_, _, Operand::Bool(expected)) => Err(invalid(expected.to_string())),
+            // `weapon_type == claw`：两个未知键均取 0 会恒为真，要求字符串加引号
+            (ValueExpr::Var(_), CompareOp::Eq | CompareOp::Ne, Operand::Value(ValueExpr::Var(word))) => {
+                Err(ConditionParseError::new(right_position, format!("Unquoted identifier '{}' in equality", word))
+                    .expecting(&["string", "number"])
+                    .suggesting(Some(format!("\"{}\"", word))))
+            }
+            (_, _, Operand::Cond(_)) => {
+                Err(ConditionParseError::new(right_position, "Expected a numeric value").expecting(OPERAND))
+            }
+            // 单个键与常量比较保持简单形式（溯源显示键名与阈值）
+            (ValueExpr::Var(key), op, Operand::Value(right)) if right.is_constant() => {
+                Ok(Condition::Compare { key, op, value: right.evaluate(&EvalContext::new()) })
+            }
+            (left, op, Operand::Value(right)) => Ok(Condition::CompareExpr { left, op, right }),
         }
-        
-        // 单独的标识符视为 Flag 检查（true）
-        Ok(Condition::Flag {
-            key: expr.to_string(),
-            expected: true,
-        })
     }
 
-    /// 评估条件
-    pub fn evaluate(&self, ctx: &EvalContext) -> bool {
-        match self {
-            Condition::True => true,
-            Condition::False => false,
-            Condition::Flag { key, expected } => {
-                ctx.flags.get(key).copied().unwrap_or(false) == *expected
+    /// prefix := '!' expr(比较) | '-' expr(一元) | '(' expr ')' | function | number | string | 'true' | 'false' | identifier
+    fn parse_prefix(&mut self) -> Result<Operand, ConditionParseError> {
+        let position = self.position();
+        match self.peek().cloned() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                let operand = self.parse_expr(PRECEDENCE_COMPARE)?;
+                Ok(Operand::Cond(Condition::Not(Box::new(self.expect_condition(operand)?))))
             }
-            Condition::Compare { key, op, value } => {
-                let actual = ctx.values.get(key).copied().unwrap_or(0.0);
-                op.evaluate(actual, *value)
+            Some(Token::Arith(ArithOp::Sub)) => {
+                self.pos += 1;
+                Ok(Operand::Value(ValueExpr::Neg(Box::new(self.parse_value(PRECEDENCE_UNARY)?))))
             }
-            Condition::HasTag { tag } => ctx.tags.contains(tag),
-            Condition::HasAnyTag { tags } => tags.iter().any(|t| ctx.tags.contains(t)),
-            Condition::HasAllTags { tags } => tags.iter().all(|t| ctx.tags.contains(t)),
-            Condition::MechanicActive { mechanic_id } => {
-                ctx.mechanic_stacks.get(mechanic_id).copied().unwrap_or(0) > 0
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let inner = self.parse_expr(PRECEDENCE_OR)?;
+                self.expect(&Token::RParen)?;
+                Ok(inner)
             }
-            Condition::MechanicStacks { mechanic_id, op, value } => {
-                let stacks = ctx.mechanic_stacks.get(mechanic_id).copied().unwrap_or(0);
-                op.evaluate(stacks as f64, *value as f64)
+            Some(Token::Number(value)) => {
+                self.pos += 1;
+                Ok(Operand::Value(ValueExpr::Const(value)))
             }
-            Condition::PerStat { stat, per } => {
-                // PerStat 不用于布尔评估，用于计算倍数
-                // 这里返回 true 如果有任意层数
-                let value = ctx.values.get(stat).copied().unwrap_or(0.0);
-                value >= *per
+            Some(Token::Str(text)) => {
+                self.pos += 1;
+                Ok(Operand::Str(text))
             }
-            Condition::And(left, right) => left.evaluate(ctx) && right.evaluate(ctx),
-            Condition::Or(left, right) => left.evaluate(ctx) || right.evaluate(ctx),
-            Condition::Not(inner) => !inner.evaluate(ctx),
+            Some(Token::Ident(name)) if self.peek_at(1) == Some(&Token::LParen) => {
+                if !FUNCTION_NAMES.contains(&name.as_str()) {
+                    return Err(ConditionParseError::new(position, format!("Unknown function '{}'", name))
+                        .expecting(FUNCTION_NAMES)
+                        .suggesting(nearest_identifier(&name, FUNCTION_NAMES.iter().copied())));
+                }
+                if name != "stat" {
+                    return self.parse_function().map(Operand::Cond);
+                }
+                self.pos += 2;
+                let key = match self.peek() {
+                    Some(Token::Str(key) | Token::Ident(key)) => key.clone(),
+                    _ => return Err(self.unexpected(&["string"])),
+                };
+                self.pos += 1;
+                self.expect(&Token::RParen)?;
+                Ok(Operand::Value(ValueExpr::Stat(key)))
+            }
+            Some(Token::Ident(name)) if name == "true" || name == "false" => {
+                self.pos += 1;
+                Ok(Operand::Bool(name == "true"))
+            }
+            Some(Token::Ident(key)) => {
+                self.pos += 1;
+                Ok(Operand::Value(ValueExpr::Var(key)))
+            }
+            _ => Err(self.unexpected(OPERAND)),
         }
     }
 
-    /// 计算 PerStat 倍数
-    pub fn evaluate_multiplier(&self, ctx: &EvalContext) -> f64 {
-        match self {
-            Condition::PerStat { stat, per } => {
-                let value = ctx.values.get(stat).copied().unwrap_or(0.0);
-                (value / per).floor()
+    /// function := name '(' args ')' (mechanic_stacks 可跟比较)
+    fn parse_function(&mut self) -> Result<Condition, ConditionParseError> {
+        let name_position = self.position();
+        let Some(Token::Ident(name)) = self.peek().cloned() else {
+            return Err(self.unexpected(&["function name"]));
+        };
+        self.pos += 1;
+        self.expect(&Token::LParen)?;
+        let mut args = Vec::new();
+        while !self.eat(&Token::RParen) {
+            if !args.is_empty() && !self.eat(&Token::Comma) {
+                return Err(self.unexpected(&[",", ")"]));
             }
-            _ => 1.0,
+            args.push(match self.peek() {
+                Some(Token::Str(text) | Token::Ident(text)) => text.clone(),
+                Some(Token::Number(value)) => value.to_string(),
+                _ => return Err(self.unexpected(&["string", "number"])),
+            });
+            self.pos += 1;
+        }
+
+        let single = |args: Vec<String>| -> Result<String, ConditionParseError> {
+            match <[String; 1]>::try_from(args) {
+                Ok([arg]) => Ok(arg),
+                Err(_) => Err(ConditionParseError::new(name_position, format!("{} requires 1 argument", name))),
+            }
+        };
+        match name.as_str() {
+            "has_tag" => Ok(Condition::HasTag { tag: single(args)? }),
+            "has_any_tag" => Ok(Condition::HasAnyTag { tags: args }),
+            "has_all_tags" => Ok(Condition::HasAllTags { tags: args }),
+            "mechanic_active" => Ok(Condition::MechanicActive { mechanic_id: single(args)? }),
+            "mechanic_stacks" => {
+                let mechanic_id = single(args)?;
+                let Some(Token::Compare(op)) = self.peek().cloned() else {
+                    // 默认检查是否 > 0
+                    return Ok(Condition::MechanicStacks { mechanic_id, op: CompareOp::Gt, value: 0 });
+                };
+                self.pos += 1;
+                match self.peek() {
+                    Some(Token::Number(value)) if *value >= 0.0 && value.fract() == 0.0 => {
+                        let value = *value as u32;
+                        self.pos += 1;
+                        Ok(Condition::MechanicStacks { mechanic_id, op, value })
+                    }
+                    _ => {
+                        let mut err = self.unexpected(&["integer"]);
+                        err.message = format!("Invalid stack count: {}", err.message);
+                        Err(err)
+                    }
+                }
+            }
+            "per_stat" => {
+                let [stat, per] = <[String; 2]>::try_from(args)
+                    .map_err(|_| ConditionParseError::new(name_position, "per_stat requires 2 arguments"))?;
+                let per: f64 = per
+                    .parse()
+                    .map_err(|_| ConditionParseError::new(name_position, format!("Invalid number '{}'", per)))?;
+                Ok(Condition::PerStat { stat, per })
+            }
+            _ => Err(ConditionParseError::new(name_position, format!("Unknown function '{}'", name))
+                .expecting(FUNCTION_NAMES)
+                .suggesting(nearest_identifier(&name, FUNCTION_NAMES.iter().copied()))),
         }
     }
 }
@@ -399,6 +1052,10 @@ pub struct EvalContext {
     pub tags: Vec<String>,
     /// 机制层数
     pub mechanic_stacks: HashMap<String, u32>,
+    /// `stat("...")` 引用的属性最终值（由 ModDB 在计算阶段写入）
+    pub stats: HashMap<String, f64>,
+    /// 字符串上下文（如 `weapon_type` = `claw`）
+    pub strings: HashMap<String, String>,
 }
 
 impl EvalContext {
@@ -419,6 +1076,12 @@ impl EvalContext {
         self
     }
 
+    /// 添加字符串上下文
+    pub fn with_string(mut self, key: &str, value: &str) -> Self {
+        self.strings.insert(key.to_string(), value.to_string());
+        self
+    }
+
     /// 添加标签
     pub fn with_tag(mut self, tag: &str) -> Self {
         self.tags.push(tag.to_string());
@@ -437,6 +1100,23 @@ impl EvalContext {
         self
     }
 
+    /// 从计算输入构建（上下文标志/数值/字符串、标签、机制层数）
+    ///
+    /// `calculate_dps` 与 `prepare_context`（`calculate_from_prepared` 的上下文来源）共用，保证两条路径的条件评估一致；
+    /// 标签为技能自带标签与上下文激活标签（武器、辅助注入、派生与机制标签）的并集
+    pub fn from_input(input: &CalculatorInput, mechanics: &MechanicsProcessor, context: &ContextTags) -> Self {
+        let mut ctx = Self::from_context(&input.context_flags, &input.context_values).with_tags(&input.active_skill.tags);
+        for tag in context.active_tag_names() {
+            if !ctx.tags.contains(&tag) {
+                ctx.tags.push(tag);
+            }
+        }
+        ctx.values.extend(mechanics.get_all_stacks());
+        ctx.mechanic_stacks = mechanics.get_stack_counts();
+        ctx.strings = input.context_strings.clone();
+        ctx
+    }
+
     /// 从现有上下文数据构建
     pub fn from_context(
         flags: &HashMap<String, bool>,
@@ -447,6 +1127,8 @@ impl EvalContext {
             values: values.clone(),
             tags: vec![],
             mechanic_stacks: HashMap::new(),
+            stats: HashMap::new(),
+            strings: HashMap::new(),
         }
     }
 }
@@ -622,5 +1304,249 @@ mod tests {
         let cond = Condition::parse("!is_stationary").unwrap();
         assert!(cond.evaluate(&ctx)); // is_stationary 未设置，默认 false，所以 !false = true
     }
-}
 
+    #[test]
+    fn test_explain_reports_deciding_factors() {
+        let ctx = EvalContext::new()
+            .with_flag("is_moving", true)
+            .with_value("life_percent", 0.5);
+
+        // 与：由未满足的一侧决定
+        let cond = Condition::parse("is_moving == true && life_percent <= 0.35").unwrap();
+        let (passed, factors) = cond.explain(&ctx);
+        assert!(!passed);
+        assert_eq!(factors.len(), 1);
+        assert_eq!(factors[0].key, "life_percent");
+        assert_eq!(factors[0].actual, "0.5");
+        assert_eq!(factors[0].expected, "<= 0.35");
+        assert!(!factors[0].passed);
+
+        // 或：由满足的一侧决定
+        let cond = Condition::parse("is_moving == true || life_percent <= 0.35").unwrap();
+        let (passed, factors) = cond.explain(&ctx);
+        assert!(passed);
+        assert_eq!(factors.len(), 1);
+        assert_eq!(factors[0].key, "is_moving");
+
+        // 结果与 evaluate 一致
+        let cond = Condition::parse("!is_stationary && mechanic_stacks(\"fighting_will\") >= 50").unwrap();
+        let (passed, factors) = cond.explain(&ctx);
+        assert_eq!(passed, cond.evaluate(&ctx));
+        assert_eq!(factors[0].key, "fighting_will");
+        assert_eq!(factors[0].actual, "0");
+    }
+
+    #[test]
+    fn test_arithmetic_comparison() {
+        let ctx = EvalContext::new()
+            .with_value("life_percent", 0.3)
+            .with_value("mana", 500.0)
+            .with_value("reserved_mana", 250.0);
+
+        let cond = Condition::parse("life_percent * 100 <= 35").unwrap();
+        assert!(matches!(cond, Condition::CompareExpr { .. }));
+        assert!(cond.evaluate(&ctx));
+        assert!(Condition::parse("mana - reserved_mana >= 200").unwrap().evaluate(&ctx));
+        assert!(!Condition::parse("(mana - reserved_mana) / mana > 0.5 && true").unwrap().evaluate(&ctx));
+
+        // 常量右侧折叠为简单比较
+        let cond = Condition::parse("life_percent <= 70 / 200").unwrap();
+        assert!(matches!(cond, Condition::Compare { value, .. } if (value - 0.35).abs() < 1e-9));
+
+        let (passed, factors) = Condition::parse("mana - reserved_mana >= 300").unwrap().explain(&ctx);
+        assert!(!passed);
+        assert_eq!(factors[0].key, "mana - reserved_mana");
+        assert_eq!(factors[0].actual, "250");
+        assert_eq!(factors[0].expected, ">= 300");
+        assert_eq!(Condition::parse("mana - reserved_mana >= 300").unwrap().context_keys(), vec!["mana", "reserved_mana"]);
+    }
+
+    /// 以紧凑形式还原条件树结构（用于断言分组）
+    fn shape(cond: &Condition) -> String {
+        match cond {
+            Condition::Flag { key, expected: true } => key.clone(),
+            Condition::Flag { key, expected: false } => format!("{}==false", key),
+            Condition::Compare { key, op, value } => format!("{}{}{}", key, op.symbol(), value),
+            Condition::CompareExpr { left, op, right } => format!("{} {} {}", left, op.symbol(), right),
+            Condition::And(left, right) => format!("({} & {})", shape(left), shape(right)),
+            Condition::Or(left, right) => format!("({} | {})", shape(left), shape(right)),
+            Condition::Not(inner) => format!("!{}", shape(inner)),
+            other => format!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_logical_precedence_and_grouping() {
+        let cases = [
+            ("!a && b || c", "((!a & b) | c)"),
+            ("a || b && c", "(a | (b & c))"),
+            ("a || !b && c || d", "((a | (!b & c)) | d)"),
+            ("a && b && c && d", "(((a & b) & c) & d)"),
+            ("!(a || b) && c", "(!(a | b) & c)"),
+            ("a && (b || c) && !d", "((a & (b | c)) & !d)"),
+            ("((a)) || ((b && (c || !d)))", "(a | (b & (c | !d)))"),
+            ("!!a && x > 1 || y <= 2 && !z", "((!!a & x>1) | (y<=2 & !z))"),
+            ("a == false || !(b && (x + 1) * 2 >= 4)", "(a==false | !(b & (x + 1) * 2 >= 4))"),
+        ];
+        for (expr, expected) in cases {
+            let cond = Condition::parse(expr).unwrap();
+            assert_eq!(shape(&cond), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_logical_precedence_evaluation() {
+        let ctx = EvalContext::new().with_flag("a", true).with_flag("c", true);
+
+        // !a && b || c：(false && b) || true
+        assert!(Condition::parse("!a && b || c").unwrap().evaluate(&ctx));
+        // !a && (b || c)：false
+        assert!(!Condition::parse("!a && (b || c)").unwrap().evaluate(&ctx));
+        // 括号内的否定与分组
+        assert!(Condition::parse("!(a && b) && !(b || !c)").unwrap().evaluate(&ctx));
+        assert!(Condition::parse("(a || b) && (c || b) && !(b && c)").unwrap().evaluate(&ctx));
+    }
+
+    #[test]
+    fn test_parenthesized_conditions_and_values() {
+        let ctx = EvalContext::new().with_flag("a", true).with_value("x", 2.0);
+
+        // 括号内可以是条件或算术表达式，可任意混合嵌套
+        assert!(Condition::parse("(((x + 1)) * 2 > 5 && (a)) || !(x < 1)").unwrap().evaluate(&ctx));
+        assert!(Condition::parse("((x - 1) * (x + 1) == 3)").unwrap().evaluate(&ctx));
+        assert!(!Condition::parse("!((x) >= 2 && ((a)))").unwrap().evaluate(&ctx));
+
+        // 条件不能参与算术，算术表达式不能直接作为条件
+        assert!(Condition::parse("(x + 1) && a").is_err());
+        assert!(Condition::parse("(a && x > 1) + 1 > 0").is_err());
+        assert!(Condition::parse("x > (a && a)").is_err());
+        assert!(Condition::parse("x > 1 < 2").is_err());
+        assert!(ValueExpr::parse("(a && a)").is_err());
+        assert!(ValueExpr::parse("x > 1").is_err());
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        let ctx = EvalContext::new().with_string("weapon_type", "claw").with_flag("weapon_type_claw", false);
+
+        let cond = Condition::parse("weapon_type == \"claw\" && stance != 'defensive'").unwrap();
+        assert!(cond.evaluate(&ctx));
+        assert_eq!(cond.context_keys(), vec!["weapon_type", "stance"]);
+        assert!(!Condition::parse("weapon_type == 'sword'").unwrap().evaluate(&ctx));
+
+        let (passed, factors) = Condition::parse("stance == \"offensive\"").unwrap().explain(&ctx);
+        assert!(!passed);
+        assert_eq!(factors[0].actual, "\"\"");
+        assert_eq!(factors[0].expected, "== \"offensive\"");
+
+        assert!(Condition::parse("weapon_type < \"claw\"").is_err());
+        // 未加引号的字符串不能静默按两个数值键比较
+        let err = Condition::parse("weapon_type == claw").unwrap_err();
+        assert_eq!((err.position, err.suggestion.as_deref()), (15, Some("\"claw\"")));
+        assert!(Condition::parse("stance != defensive && true").is_err());
+        assert!(Condition::parse("a - b == 0").is_ok());
+        assert!(Condition::parse("a + 1 == \"claw\"").is_err());
+    }
+
+    #[test]
+    fn test_stat_reference() {
+        let mut ctx = EvalContext::new().with_value("crit.chance", 0.2);
+        ctx.stats.insert("crit.chance".to_string(), 1.0);
+
+        // stat(...) 取聚合后的属性最终值，而非同名上下文数值
+        let cond = Condition::parse("stat(\"crit.chance\") >= 1.0 && crit.chance < 0.5").unwrap();
+        assert!(cond.evaluate(&ctx));
+        assert_eq!(cond.stat_references(), vec!["crit.chance"]);
+        assert_eq!(cond.context_keys(), vec!["crit.chance"]);
+
+        let expr = ValueExpr::parse("stat('crit.chance') * 2").unwrap();
+        assert_eq!(expr.evaluate(&ctx), 2.0);
+        assert_eq!(expr.to_string(), "stat(\"crit.chance\") * 2");
+        assert!(ValueExpr::parse("stat()").is_err());
+
+        // 右侧引用属性时不折叠为常量比较
+        let cond = Condition::parse("life >= stat(\"life.max\")").unwrap();
+        assert!(matches!(cond, Condition::CompareExpr { .. }), "{:?}", cond);
+        assert_eq!(cond.stat_references(), vec!["life.max"]);
+        let mut ctx = EvalContext::new().with_value("life", 10.0);
+        ctx.stats.insert("life.max".to_string(), 100.0);
+        assert!(!cond.evaluate(&ctx));
+        ctx.values.insert("life".to_string(), 100.0);
+        assert!(cond.evaluate(&ctx));
+    }
+
+    #[test]
+    fn test_tokenizer_parenthesized_conditions() {
+        let ctx = EvalContext::new().with_flag("is_moving", true).with_value("enemy_range", 3.0);
+
+        // 括号可包裹条件或算术表达式
+        let cond = Condition::parse("(is_moving == false || enemy_range > 2) && !(enemy_range + 1 < 2)").unwrap();
+        assert!(cond.evaluate(&ctx));
+        assert!(Condition::parse("has_any_tag('Tag_Spell', \"Tag_Attack\")").is_ok());
+
+        assert!(Condition::parse("is_moving = true").is_err());
+        assert!(Condition::parse("life_percent <=").is_err());
+        assert!(Condition::parse("(is_moving").is_err());
+        assert!(Condition::parse("unknown_fn(\"x\")").is_err());
+        assert!(Condition::parse("life_percent < true").is_err());
+    }
+
+    #[test]
+    fn test_value_expr_precedence() {
+        let ctx = EvalContext::new().with_value("fighting_will", 50.0).with_value("crit.chance", 0.1);
+
+        let expr = ValueExpr::parse("fighting_will * 0.002").unwrap();
+        assert!((expr.evaluate(&ctx) - 0.1).abs() < 1e-9);
+        assert_eq!(expr.variables(), vec!["fighting_will"]);
+
+        let expr = ValueExpr::parse("1 + 2 * 3 - -(crit.chance + 0.9) / 2").unwrap();
+        assert!((expr.evaluate(&ctx) - 7.5).abs() < 1e-9);
+
+        // 未提供的标识符取 0，除数为 0 时结果为 0
+        assert_eq!(ValueExpr::parse("missing / 0").unwrap().evaluate(&ctx), 0.0);
+    }
+
+    #[test]
+    fn test_value_expr_parse_errors() {
+        assert!(ValueExpr::parse("").is_err());
+        assert!(ValueExpr::parse("(1 + 2").is_err());
+        assert!(ValueExpr::parse("1 +").is_err());
+        assert!(ValueExpr::parse("1 2").is_err());
+        assert!(ValueExpr::parse("1..2").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_position_and_suggestion() {
+        let err = Condition::parse("has_tags(\"Tag_Spell\")").unwrap_err();
+        assert_eq!(err.position, 0);
+        assert_eq!(err.suggestion.as_deref(), Some("has_tag"));
+        assert!(err.expected.contains(&"mechanic_active".to_string()));
+
+        let err = Condition::parse("is_moving and life_percent < 0.5").unwrap_err();
+        assert_eq!(err.position, 10);
+        assert_eq!(err.expected, vec!["&&", "||"]);
+        assert_eq!(err.suggestion.as_deref(), Some("&&"));
+
+        let err = Condition::parse("is_moving = true").unwrap_err();
+        assert_eq!((err.position, err.suggestion.as_deref()), (10, Some("==")));
+
+        // 意外结束时位置为表达式长度
+        let err = Condition::parse("life_percent <=").unwrap_err();
+        assert_eq!(err.position, 15);
+        assert!(err.expected.contains(&"number".to_string()));
+
+        let err = Condition::parse("(is_moving && life_percent < 0.5").unwrap_err();
+        assert_eq!((err.position, err.expected.clone()), (32, vec![")".to_string()]));
+        assert_eq!(err.to_string(), "Unexpected end of expression at 32 (expected ))");
+
+        let err = Condition::parse("mechanic_stacks(\"fighting_will\") >= 1.5").unwrap_err();
+        assert_eq!((err.position, err.expected.clone()), (36, vec!["integer".to_string()]));
+        assert!(err.to_string().starts_with("Invalid stack count"));
+
+        let err = ValueExpr::parse("fighting_will * ").unwrap_err();
+        assert_eq!(err.position, 16);
+        assert_eq!(err.suggestion, None);
+        let err = ValueExpr::parse("stats(\"crit.chance\")").unwrap_err();
+        assert_eq!(err.suggestion.as_deref(), Some("stat"));
+    }
+}
@@ -3,11 +3,19 @@
 //! 实现 Tag Retention（标签记忆）机制
 //! - Phase A: Gain as Extra（额外获得）
 //! - Phase B: Conversion（伤害转化）
+//!
+//! 规则先编译为 `ConversionPlan`（排序 + 缩放比例），
+//! 相同的转化属性组合复用同一份计划。
 
+use crate::condition_ast::EvalContext;
+use crate::modifiers::{ModDB, Modifier, ModifierKind, ModifierStore};
 use crate::tags::TagRegistry;
-use crate::stats::StatPool;
 use fixedbitset::FixedBitSet;
+use lru::LruCache;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
 
 /// 伤害类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -21,7 +29,7 @@ pub enum DamageType {
 
 impl DamageType {
     /// 获取所有伤害类型（按转化优先级排序）
-    pub fn all_ordered() -> &'static [DamageType] {
+    pub const fn all_ordered() -> &'static [DamageType] {
         &[
             DamageType::Physical,
             DamageType::Lightning,
@@ -54,6 +62,7 @@ impl DamageType {
     }
 
     /// 从字符串解析
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "physical" | "phys" => Some(DamageType::Physical),
@@ -111,22 +120,223 @@ impl DamageWithTags {
     }
 }
 
+/// 转化来源优先级：技能自带 > 辅助技能 > 装备（含天赋等其他来源）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ConversionPriority {
+    Skill,
+    Support,
+    Gear,
+}
+
+impl ConversionPriority {
+    /// 按优先级从高到低
+    pub fn all_ordered() -> &'static [ConversionPriority] {
+        &[ConversionPriority::Skill, ConversionPriority::Support, ConversionPriority::Gear]
+    }
+
+    /// 根据修正来源描述判断优先级（`skill:xxx` / `support:xxx` / 其他）
+    pub fn from_source(source: &str) -> Self {
+        if source.starts_with("skill:") {
+            ConversionPriority::Skill
+        } else if source.starts_with("support:") {
+            ConversionPriority::Support
+        } else {
+            ConversionPriority::Gear
+        }
+    }
+}
+
 /// 转化规则
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ConversionRule {
     pub from: DamageType,
     pub to: DamageType,
     pub percent: f64, // 0.0 - 1.0
+    /// 来源优先级
+    pub priority: ConversionPriority,
+    /// 来源描述（用于追踪被缩放的来源）
+    pub source: String,
+}
+
+impl ConversionRule {
+    /// 创建装备优先级的转化规则
+    pub fn new(from: DamageType, to: DamageType, percent: f64) -> Self {
+        Self {
+            from,
+            to,
+            percent,
+            priority: ConversionPriority::Gear,
+            source: String::new(),
+        }
+    }
+
+    /// 设置来源（优先级由来源描述推断）
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.priority = ConversionPriority::from_source(source);
+        self.source = source.to_string();
+        self
+    }
 }
 
 /// 额外获得规则
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ExtraAsRule {
     pub from: DamageType,
     pub to: DamageType,
     pub percent: f64, // 0.0 - 1.0
 }
 
+/// 单个源类型的转化步骤
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionStep {
+    /// 源伤害类型
+    pub from: DamageType,
+    /// (目标类型, 实际转化比例)，总和超过 100% 时已按比例缩放
+    pub targets: Vec<(DamageType, f64)>,
+    /// 转化后源伤害的剩余比例
+    pub remaining: f64,
+}
+
+/// 因转化率超过 100% 被缩放的来源
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaledConversion {
+    pub from: DamageType,
+    pub to: DamageType,
+    /// 来源描述
+    pub source: String,
+    /// 原始转化率
+    pub requested: f64,
+    /// 实际生效的转化率
+    pub applied: f64,
+}
+
+/// 预编译的转化计划
+///
+/// 额外获得规则 + 按 DAG 顺序排好的转化步骤，可在多次计算间复用
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversionPlan {
+    /// 额外获得规则（Phase A）
+    pub extra_as: Vec<ExtraAsRule>,
+    /// 转化步骤（Phase B，按 DAG 顺序）
+    pub steps: Vec<ConversionStep>,
+    /// 被缩放的转化来源（用于追踪）
+    pub scaled: Vec<ScaledConversion>,
+}
+
+/// 转化计划缓存容量
+const PLAN_CACHE_SIZE: usize = 32;
+
+/// 转化计划缓存键：(属性在键表中的位置, 来源, 数值位模式)
+type PlanKey = Vec<(usize, String, u64)>;
+
+thread_local! {
+    static PLAN_CACHE: RefCell<LruCache<PlanKey, Arc<ConversionPlan>>> =
+        RefCell::new(LruCache::new(NonZeroUsize::new(PLAN_CACHE_SIZE).unwrap()));
+}
+
+impl ConversionPlan {
+    /// 编译转化计划
+    ///
+    /// 转化步骤按 `DamageType::all_ordered()` 排序。同一源类型内按来源优先级
+    /// （技能 > 辅助 > 装备）依次占用 100% 的转化容量：高优先级先全额生效，
+    /// 容量不足的那一级按比例缩放分配剩余容量，更低优先级不再生效
+    pub fn compile(extra_rules: &[ExtraAsRule], conversion_rules: &[ConversionRule]) -> Self {
+        let mut steps = Vec::new();
+        let mut scaled = Vec::new();
+
+        for &source_type in DamageType::all_ordered() {
+            let mut targets = Vec::new();
+            let mut capacity: f64 = 1.0;
+
+            for &priority in ConversionPriority::all_ordered() {
+                let tier: Vec<&ConversionRule> = conversion_rules
+                    .iter()
+                    .filter(|r| r.from == source_type && r.priority == priority && r.percent > 0.0)
+                    .collect();
+                if tier.is_empty() {
+                    continue;
+                }
+
+                let total: f64 = tier.iter().map(|r| r.percent).sum();
+                let scale = if total > capacity { capacity / total } else { 1.0 };
+
+                for rule in tier {
+                    let applied = rule.percent * scale;
+                    if scale < 1.0 {
+                        scaled.push(ScaledConversion {
+                            from: rule.from,
+                            to: rule.to,
+                            source: rule.source.clone(),
+                            requested: rule.percent,
+                            applied,
+                        });
+                    }
+                    if applied > 0.0 {
+                        targets.push((rule.to, applied));
+                    }
+                }
+                capacity = (capacity - total).max(0.0);
+            }
+
+            if targets.is_empty() {
+                continue;
+            }
+            steps.push(ConversionStep {
+                from: source_type,
+                targets,
+                remaining: capacity,
+            });
+        }
+
+        Self {
+            extra_as: extra_rules.to_vec(),
+            steps,
+            scaled,
+        }
+    }
+
+    /// 从 ModDB 编译转化计划（按修正来源区分优先级，只计入当前生效的修正）
+    pub fn from_mod_db(mod_db: &ModDB, ctx: &EvalContext, active_set: &FixedBitSet) -> Self {
+        Self::compile(
+            &extract_extra_as_rules(mod_db, ctx, active_set),
+            &extract_conversion_rules(mod_db, ctx, active_set),
+        )
+    }
+
+    /// 获取 ModDB 对应的转化计划（按相关属性子集缓存）
+    ///
+    /// 只有当前生效的 `conv.*` / `extra.*` 修正（实际值与来源）参与缓存键，
+    /// 装备其他属性变化不会使计划失效
+    pub fn cached(mod_db: &ModDB, ctx: &EvalContext, active_set: &FixedBitSet) -> Arc<Self> {
+        let key: PlanKey = CONVERSION_KEYS
+            .iter()
+            .chain(EXTRA_AS_KEYS)
+            .map(|(stat, _, _)| *stat)
+            .chain(EXTRA_AS_GROUP_KEYS.iter().map(|(stat, _, _)| *stat))
+            .enumerate()
+            .flat_map(|(idx, stat)| {
+                active_base_mods(mod_db, stat, ctx, active_set)
+                    .map(move |(m, value)| (idx, m.source.clone(), value.to_bits()))
+            })
+            .collect();
+
+        PLAN_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if let Some(plan) = cache.get(&key) {
+                return Arc::clone(plan);
+            }
+            let plan = Arc::new(Self::from_mod_db(mod_db, ctx, active_set));
+            cache.put(key, Arc::clone(&plan));
+            plan
+        })
+    }
+
+    /// 是否没有任何转化/额外获得
+    pub fn is_empty(&self) -> bool {
+        self.extra_as.is_empty() && self.steps.is_empty()
+    }
+}
+
 /// 伤害转化引擎
 pub struct ConversionEngine {
     tag_capacity: usize,
@@ -137,11 +347,7 @@ impl ConversionEngine {
         Self { tag_capacity }
     }
 
-    /// 执行完整的转化流程
-    /// 
-    /// 1. 初始化伤害池，添加原始标签
-    /// 2. Phase A: Gain as Extra（不扣除原伤害）
-    /// 3. Phase B: Conversion（扣除原伤害，按 DAG 顺序）
+    /// 执行完整的转化流程（现场编译转化计划）
     pub fn process(
         &self,
         base_damages: &HashMap<DamageType, (f64, f64)>,
@@ -149,209 +355,286 @@ impl ConversionEngine {
         conversion_rules: &[ConversionRule],
         registry: &TagRegistry,
     ) -> HashMap<DamageType, DamageWithTags> {
-        // 1. 初始化伤害池
-        let mut pool: HashMap<DamageType, DamageWithTags> = HashMap::new();
-        
-        for (&dtype, &(min, max)) in base_damages {
+        let plan = ConversionPlan::compile(extra_rules, conversion_rules);
+        self.process_plan(base_damages, &plan, registry)
+    }
+
+    /// 按预编译的转化计划执行完整流程，结果按伤害类型合并
+    pub fn process_plan(
+        &self,
+        base_damages: &HashMap<DamageType, (f64, f64)>,
+        plan: &ConversionPlan,
+        registry: &TagRegistry,
+    ) -> HashMap<DamageType, DamageWithTags> {
+        merge_packets(self.process_packets(base_damages, plan, registry))
+    }
+
+    /// 按预编译的转化计划执行完整流程，保留各伤害包独立的历史标签
+    /// 
+    /// 1. 初始化伤害包，添加原始标签
+    /// 2. Phase A: Gain as Extra（不扣除原伤害）
+    /// 3. Phase B: Conversion（扣除原伤害，按 DAG 顺序逐步传递）
+    ///
+    /// 同类型但来历不同的伤害（如原生闪电与物理转化的闪电）保持为独立的包，
+    /// 各自只吃到自身历史标签对应的增伤
+    pub fn process_packets(
+        &self,
+        base_damages: &HashMap<DamageType, (f64, f64)>,
+        plan: &ConversionPlan,
+        registry: &TagRegistry,
+    ) -> Vec<DamagePacket> {
+        // 1. 初始化伤害包（按 DAG 顺序，保证结果稳定）
+        let mut packets: Vec<DamagePacket> = Vec::new();
+
+        for &dtype in DamageType::all_ordered() {
+            let Some(&(min, max)) = base_damages.get(&dtype) else {
+                continue;
+            };
             let mut dmg = DamageWithTags::new(min, max, self.tag_capacity);
             // 添加原始伤害类型标签
             if let Some(tag_id) = registry.get_id(dtype.tag_name()) {
                 dmg.add_tag(tag_id);
             }
-            pool.insert(dtype, dmg);
+            packets.push((dtype, dmg));
         }
 
         // 2. Phase A: Gain as Extra
-        self.apply_extra_as(&mut pool, extra_rules, registry);
+        self.apply_extra_as(&mut packets, &plan.extra_as, registry);
 
         // 3. Phase B: Conversion
-        self.apply_conversion(&mut pool, conversion_rules, registry);
+        self.apply_conversion(&mut packets, &plan.steps, registry);
 
-        pool
+        packets
+    }
+
+    /// 从源伤害派生新伤害包：按比例缩放，继承历史标签并添加目标类型标签
+    fn derive(&self, source: &DamageWithTags, to: DamageType, percent: f64, registry: &TagRegistry) -> DamageWithTags {
+        let mut derived = DamageWithTags::new(source.min * percent, source.max * percent, self.tag_capacity);
+        // 继承源伤害的历史标签（Tag Retention）
+        derived.history_tags.union_with(&source.history_tags);
+        if let Some(tag_id) = registry.get_id(to.tag_name()) {
+            derived.add_tag(tag_id);
+        }
+        derived
     }
 
     /// Phase A: 额外获得
     /// 计算"额外获得"逻辑，不扣除原伤害，产出的新伤害保留原伤害标签
+    /// 额外获得只基于初始伤害，不会从其他额外获得的伤害再次派生
     fn apply_extra_as(
         &self,
-        pool: &mut HashMap<DamageType, DamageWithTags>,
+        packets: &mut Vec<DamagePacket>,
         rules: &[ExtraAsRule],
         registry: &TagRegistry,
     ) {
-        // 按目标类型收集额外伤害
-        let mut extra_damages: HashMap<DamageType, DamageWithTags> = HashMap::new();
+        let mut extra_damages = Vec::new();
 
         for rule in rules {
-            if let Some(source) = pool.get(&rule.from) {
-                if source.is_zero() {
+            for (dtype, source) in packets.iter() {
+                if *dtype != rule.from || source.is_zero() {
                     continue;
                 }
-
-                let extra_min = source.min * rule.percent;
-                let extra_max = source.max * rule.percent;
-
-                let mut extra_dmg = DamageWithTags::new(extra_min, extra_max, self.tag_capacity);
-                // 继承源伤害的历史标签
-                extra_dmg.history_tags.union_with(&source.history_tags);
-                // 添加目标类型标签
-                if let Some(tag_id) = registry.get_id(rule.to.tag_name()) {
-                    extra_dmg.add_tag(tag_id);
-                }
-
-                extra_damages
-                    .entry(rule.to)
-                    .or_insert_with(|| DamageWithTags::zero(self.tag_capacity))
-                    .merge(&extra_dmg);
+                extra_damages.push((rule.to, self.derive(source, rule.to, rule.percent, registry)));
             }
         }
 
-        // 将额外伤害合并到主池
         for (dtype, extra) in extra_damages {
-            pool.entry(dtype)
-                .or_insert_with(|| DamageWithTags::zero(self.tag_capacity))
-                .merge(&extra);
+            push_packet(packets, dtype, extra);
         }
     }
 
     /// Phase B: 伤害转化
     /// 按 DAG 顺序执行转化（Physical -> Lightning -> Cold -> Fire -> Chaos）
-    /// 转化会扣除原伤害，产出的新伤害保留历史标签
+    /// 转化会扣除原伤害，产出的新伤害保留历史标签；
+    /// 前一步转化得到的伤害会继续参与后续步骤（如 物理→闪电→冰冷），历史标签逐步累积
     fn apply_conversion(
         &self,
-        pool: &mut HashMap<DamageType, DamageWithTags>,
-        rules: &[ConversionRule],
+        packets: &mut Vec<DamagePacket>,
+        steps: &[ConversionStep],
         registry: &TagRegistry,
     ) {
-        // 按源类型分组转化规则
-        let mut rules_by_source: HashMap<DamageType, Vec<&ConversionRule>> = HashMap::new();
-        for rule in rules {
-            rules_by_source
-                .entry(rule.from)
-                .or_insert_with(Vec::new)
-                .push(rule);
-        }
+        for step in steps {
+            let mut converted = Vec::new();
 
-        // 按 DAG 顺序处理每种伤害类型
-        for &source_type in DamageType::all_ordered() {
-            let Some(source_rules) = rules_by_source.get(&source_type) else {
-                continue;
-            };
+            for (dtype, source) in packets.iter_mut() {
+                if *dtype != step.from || source.is_zero() {
+                    continue;
+                }
 
-            // 计算该类型的总转化率（上限 100%）
-            let total_percent: f64 = source_rules
-                .iter()
-                .map(|r| r.percent)
-                .sum::<f64>()
-                .min(1.0);
+                // 按计划中已缩放的比例分配转化伤害
+                for &(to, percent) in &step.targets {
+                    converted.push((to, self.derive(source, to, percent, registry)));
+                }
 
-            if total_percent == 0.0 {
-                continue;
+                // 扣除源伤害
+                source.min *= step.remaining;
+                source.max *= step.remaining;
             }
 
-            // 获取源伤害（需要 clone，因为我们要修改池）
-            let source = match pool.get(&source_type) {
-                Some(s) if !s.is_zero() => s.clone(),
-                _ => continue,
-            };
-
-            // 计算转化后的伤害分配
-            for rule in source_rules {
-                // 如果总转化率超过100%，按比例缩放
-                let actual_percent = if total_percent > 1.0 {
-                    rule.percent / total_percent
-                } else {
-                    rule.percent
-                };
-
-                let conv_min = source.min * actual_percent;
-                let conv_max = source.max * actual_percent;
-
-                let mut converted = DamageWithTags::new(conv_min, conv_max, self.tag_capacity);
-                // 继承源伤害的历史标签（Tag Retention）
-                converted.history_tags.union_with(&source.history_tags);
-                // 添加目标类型标签
-                if let Some(tag_id) = registry.get_id(rule.to.tag_name()) {
-                    converted.add_tag(tag_id);
-                }
-
-                pool.entry(rule.to)
-                    .or_insert_with(|| DamageWithTags::zero(self.tag_capacity))
-                    .merge(&converted);
+            for (dtype, dmg) in converted {
+                push_packet(packets, dtype, dmg);
             }
+        }
+    }
+}
 
-            // 扣除源伤害
-            if let Some(source_dmg) = pool.get_mut(&source_type) {
-                let remaining = 1.0 - total_percent.min(1.0);
-                source_dmg.min *= remaining;
-                source_dmg.max *= remaining;
+/// 伤害包：(伤害类型, 带历史标签的伤害)
+pub type DamagePacket = (DamageType, DamageWithTags);
+
+/// 加入伤害包，类型与历史标签完全相同的包直接合并
+fn push_packet(packets: &mut Vec<DamagePacket>, dtype: DamageType, dmg: DamageWithTags) {
+    match packets
+        .iter_mut()
+        .find(|(t, p)| *t == dtype && p.history_tags == dmg.history_tags)
+    {
+        Some((_, existing)) => existing.merge(&dmg),
+        None => packets.push((dtype, dmg)),
+    }
+}
+
+/// 按伤害类型合并伤害包（历史标签取并集）
+pub fn merge_packets(packets: impl IntoIterator<Item = DamagePacket>) -> HashMap<DamageType, DamageWithTags> {
+    let mut pool: HashMap<DamageType, DamageWithTags> = HashMap::new();
+    for (dtype, dmg) in packets {
+        match pool.get_mut(&dtype) {
+            Some(existing) => existing.merge(&dmg),
+            None => {
+                pool.insert(dtype, dmg);
             }
         }
     }
+    pool
 }
 
-/// 从属性池提取转化规则
-pub fn extract_conversion_rules(pool: &StatPool) -> Vec<ConversionRule> {
-    let mut rules = Vec::new();
-    
-    let conversions = [
-        ("conv.phys_to_fire", DamageType::Physical, DamageType::Fire),
-        ("conv.phys_to_cold", DamageType::Physical, DamageType::Cold),
-        ("conv.phys_to_lightning", DamageType::Physical, DamageType::Lightning),
-        ("conv.phys_to_chaos", DamageType::Physical, DamageType::Chaos),
-        ("conv.lightning_to_cold", DamageType::Lightning, DamageType::Cold),
-        ("conv.lightning_to_fire", DamageType::Lightning, DamageType::Fire),
-        ("conv.cold_to_fire", DamageType::Cold, DamageType::Fire),
-        ("conv.cold_to_chaos", DamageType::Cold, DamageType::Chaos),
-        ("conv.fire_to_chaos", DamageType::Fire, DamageType::Chaos),
-    ];
-    
-    for (key, from, to) in conversions {
-        let percent = pool.get_base(key);
-        if percent > 0.0 {
-            rules.push(ConversionRule {
-                from,
-                to,
-                percent: percent.min(1.0),
-            });
+/// 转化属性键表：(属性键, 源类型, 目标类型)
+const CONVERSION_KEYS: &[(&str, DamageType, DamageType)] = &[
+    ("conv.phys_to_fire", DamageType::Physical, DamageType::Fire),
+    ("conv.phys_to_cold", DamageType::Physical, DamageType::Cold),
+    ("conv.phys_to_lightning", DamageType::Physical, DamageType::Lightning),
+    ("conv.phys_to_chaos", DamageType::Physical, DamageType::Chaos),
+    ("conv.lightning_to_cold", DamageType::Lightning, DamageType::Cold),
+    ("conv.lightning_to_fire", DamageType::Lightning, DamageType::Fire),
+    ("conv.cold_to_fire", DamageType::Cold, DamageType::Fire),
+    ("conv.cold_to_chaos", DamageType::Cold, DamageType::Chaos),
+    ("conv.fire_to_chaos", DamageType::Fire, DamageType::Chaos),
+];
+
+/// 额外获得属性键表：(属性键, 源类型, 目标类型)
+const EXTRA_AS_KEYS: &[(&str, DamageType, DamageType)] = &[
+    ("extra.phys_as_fire", DamageType::Physical, DamageType::Fire),
+    ("extra.phys_as_cold", DamageType::Physical, DamageType::Cold),
+    ("extra.phys_as_lightning", DamageType::Physical, DamageType::Lightning),
+    ("extra.phys_as_chaos", DamageType::Physical, DamageType::Chaos),
+    ("extra.lightning_as_cold", DamageType::Lightning, DamageType::Cold),
+    ("extra.lightning_as_fire", DamageType::Lightning, DamageType::Fire),
+    ("extra.cold_as_fire", DamageType::Cold, DamageType::Fire),
+    ("extra.fire_as_chaos", DamageType::Fire, DamageType::Chaos),
+];
+
+/// 元素伤害类型
+const ELEMENTAL_TYPES: &[DamageType] = &[DamageType::Lightning, DamageType::Cold, DamageType::Fire];
+
+/// 额外获得组合属性键表：(属性键, 源类型组, 目标类型)
+///
+/// 词缀常以"元素伤害"/"全部伤害"为源，提取时展开为逐类型规则；
+/// `all` 组不包含目标类型自身
+const EXTRA_AS_GROUP_KEYS: &[(&str, &[DamageType], DamageType)] = &[
+    ("extra.elemental_as_chaos", ELEMENTAL_TYPES, DamageType::Chaos),
+    ("extra.all_as_fire", DamageType::all_ordered(), DamageType::Fire),
+    ("extra.all_as_cold", DamageType::all_ordered(), DamageType::Cold),
+    ("extra.all_as_lightning", DamageType::all_ordered(), DamageType::Lightning),
+    ("extra.all_as_chaos", DamageType::all_ordered(), DamageType::Chaos),
+];
+
+/// 是否为已收录的转化 / 额外获得属性键
+pub fn is_conversion_key(key: &str) -> bool {
+    CONVERSION_KEYS.iter().chain(EXTRA_AS_KEYS).any(|(stat, _, _)| *stat == key)
+        || EXTRA_AS_GROUP_KEYS.iter().any(|(stat, _, _)| *stat == key)
+}
+
+/// 展开后的全部额外获得属性键：(属性键, 源类型, 目标类型)
+fn extra_as_entries() -> impl Iterator<Item = (&'static str, DamageType, DamageType)> {
+    let direct = EXTRA_AS_KEYS.iter().copied();
+    let grouped = EXTRA_AS_GROUP_KEYS.iter().flat_map(|&(key, sources, to)| {
+        sources
+            .iter()
+            .filter(move |&&from| from != to)
+            .map(move |&from| (key, from, to))
+    });
+    direct.chain(grouped)
+}
+
+/// 追加额外获得规则（同源同目标的规则合并）
+fn push_extra_as(rules: &mut Vec<ExtraAsRule>, from: DamageType, to: DamageType, percent: f64) {
+    if percent <= 0.0 {
+        return;
+    }
+    match rules.iter_mut().find(|r| r.from == from && r.to == to) {
+        Some(rule) => rule.percent += percent,
+        None => rules.push(ExtraAsRule { from, to, percent }),
+    }
+}
+
+/// 当前上下文下生效的 Base 修正及其实际值（条件 + 标签要求 + PerStat）
+fn active_base_mods<'a>(
+    mod_db: &'a ModDB,
+    key: &str,
+    ctx: &'a EvalContext,
+    active_set: &'a FixedBitSet,
+) -> impl Iterator<Item = (&'a Modifier, f64)> + 'a {
+    mod_db
+        .get_by_kind(key, ModifierKind::Base)
+        .into_iter()
+        .filter(move |m| {
+            m.check_condition(ctx) && m.matches_context(active_set)
+        })
+        .map(move |m| (m, m.effective_value(ctx)))
+}
+
+/// 从 ModDB 提取转化规则
+///
+/// 只计入当前条件与标签要求下生效的修正（如"能量护盾全满时冰冷转火焰"），
+/// 同一来源的同类转化合并为一条
+pub fn extract_conversion_rules(
+    mod_db: &ModDB,
+    ctx: &EvalContext,
+    active_set: &FixedBitSet,
+) -> Vec<ConversionRule> {
+    let mut rules: Vec<ConversionRule> = Vec::new();
+
+    for &(key, from, to) in CONVERSION_KEYS {
+        for (m, value) in active_base_mods(mod_db, key, ctx, active_set) {
+            match rules
+                .iter_mut()
+                .find(|r| r.from == from && r.to == to && r.source == m.source)
+            {
+                Some(rule) => rule.percent += value,
+                None => rules.push(ConversionRule::new(from, to, value).with_source(&m.source)),
+            }
         }
     }
-    
+
+    rules.retain(|r| r.percent > 0.0);
     rules
 }
 
-/// 从属性池提取额外获得规则
-pub fn extract_extra_as_rules(pool: &StatPool) -> Vec<ExtraAsRule> {
+/// 从 ModDB 提取额外获得规则（只计入当前生效的修正）
+pub fn extract_extra_as_rules(
+    mod_db: &ModDB,
+    ctx: &EvalContext,
+    active_set: &FixedBitSet,
+) -> Vec<ExtraAsRule> {
     let mut rules = Vec::new();
-    
-    let extras = [
-        ("extra.phys_as_fire", DamageType::Physical, DamageType::Fire),
-        ("extra.phys_as_cold", DamageType::Physical, DamageType::Cold),
-        ("extra.phys_as_lightning", DamageType::Physical, DamageType::Lightning),
-        ("extra.phys_as_chaos", DamageType::Physical, DamageType::Chaos),
-        ("extra.lightning_as_cold", DamageType::Lightning, DamageType::Cold),
-        ("extra.lightning_as_fire", DamageType::Lightning, DamageType::Fire),
-        ("extra.cold_as_fire", DamageType::Cold, DamageType::Fire),
-        ("extra.fire_as_chaos", DamageType::Fire, DamageType::Chaos),
-    ];
-    
-    for (key, from, to) in extras {
-        let percent = pool.get_base(key);
-        if percent > 0.0 {
-            rules.push(ExtraAsRule {
-                from,
-                to,
-                percent,
-            });
-        }
+    for (key, from, to) in extra_as_entries() {
+        let percent = active_base_mods(mod_db, key, ctx, active_set).map(|(_, v)| v).sum();
+        push_extra_as(&mut rules, from, to, percent);
     }
-    
     rules
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::stats::StatPool;
 
     fn create_test_registry() -> TagRegistry {
         let mut registry = TagRegistry::new();
@@ -401,11 +684,7 @@ mod tests {
         let mut base = HashMap::new();
         base.insert(DamageType::Physical, (100.0, 100.0));
         
-        let conv_rules = vec![ConversionRule {
-            from: DamageType::Physical,
-            to: DamageType::Fire,
-            percent: 0.50, // 50% 物理转火焰
-        }];
+        let conv_rules = vec![ConversionRule::new(DamageType::Physical, DamageType::Fire, 0.50)]; // 50% 物理转火焰
         
         let result = engine.process(&base, &[], &conv_rules, &registry);
         
@@ -432,11 +711,7 @@ mod tests {
         let mut base = HashMap::new();
         base.insert(DamageType::Physical, (100.0, 100.0));
         
-        let conv_rules = vec![ConversionRule {
-            from: DamageType::Physical,
-            to: DamageType::Fire,
-            percent: 0.50,
-        }];
+        let conv_rules = vec![ConversionRule::new(DamageType::Physical, DamageType::Fire, 0.50)];
         
         let result = engine.process(&base, &[], &conv_rules, &registry);
         
@@ -466,10 +741,11 @@ mod tests {
         let mut base = HashMap::new();
         base.insert(DamageType::Lightning, (50.0, 150.0));
 
-        let mut pool = StatPool::new();
-        pool.add_base("conv.lightning_to_cold", 1.0);
-        let conv_rules = extract_conversion_rules(&pool);
-        let extra_rules = extract_extra_as_rules(&pool);
+        let mut mod_db = ModDB::new();
+        mod_db.add(Modifier::base("conv.lightning_to_cold", 1.0, "item1:affix1"));
+        let (ctx, active) = (EvalContext::new(), FixedBitSet::new());
+        let conv_rules = extract_conversion_rules(&mod_db, &ctx, &active);
+        let extra_rules = extract_extra_as_rules(&mod_db, &ctx, &active);
 
         let result = engine.process(&base, &extra_rules, &conv_rules, &registry);
 
@@ -485,5 +761,187 @@ mod tests {
         assert!(cold.history_tags.contains(tag_light as usize));
         assert!(cold.history_tags.contains(tag_cold as usize));
     }
-}
 
+    #[test]
+    fn test_plan_scales_over_capped_conversion() {
+        let plan = ConversionPlan::compile(
+            &[],
+            &[
+                ConversionRule::new(DamageType::Physical, DamageType::Fire, 0.75),
+                ConversionRule::new(DamageType::Physical, DamageType::Cold, 0.75),
+            ],
+        );
+
+        assert_eq!(plan.steps.len(), 1);
+        let step = &plan.steps[0];
+        assert_eq!(step.remaining, 0.0);
+        assert!(step.targets.iter().all(|(_, p)| (p - 0.5).abs() < 1e-9));
+
+        let registry = create_test_registry();
+        let mut base = HashMap::new();
+        base.insert(DamageType::Physical, (100.0, 100.0));
+        let result = ConversionEngine::new(64).process_plan(&base, &plan, &registry);
+        assert!((result[&DamageType::Fire].average() - 50.0).abs() < 1e-6);
+        assert!((result[&DamageType::Cold].average() - 50.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_plan_cached_by_conversion_stats() {
+        let mut a = ModDB::new();
+        a.add(Modifier::base("conv.phys_to_fire", 0.4, "item1:affix1"));
+        a.add(Modifier::base("dmg.phys.min", 10.0, "item1:base"));
+
+        let mut b = ModDB::new();
+        b.add(Modifier::base("conv.phys_to_fire", 0.4, "item1:affix1"));
+        b.add(Modifier::base("dmg.phys.min", 99.0, "item1:base"));
+
+        // 无关属性不同，复用同一份计划
+        let (ctx, active) = (EvalContext::new(), FixedBitSet::new());
+        let plan_a = ConversionPlan::cached(&a, &ctx, &active);
+        let plan_b = ConversionPlan::cached(&b, &ctx, &active);
+        assert!(Arc::ptr_eq(&plan_a, &plan_b));
+
+        b.add(Modifier::base("extra.phys_as_cold", 0.1, "item2:affix1"));
+        let plan_c = ConversionPlan::cached(&b, &ctx, &active);
+        assert!(!Arc::ptr_eq(&plan_a, &plan_c));
+        assert_eq!(plan_c.extra_as.len(), 1);
+
+        // 同样数值但来源不同（优先级可能不同），不复用
+        let mut d = ModDB::new();
+        d.add(Modifier::base("conv.phys_to_fire", 0.4, "skill:fireball"));
+        assert!(!Arc::ptr_eq(&plan_a, &ConversionPlan::cached(&d, &ctx, &active)));
+    }
+
+    #[test]
+    fn test_conversion_priority_skill_first() {
+        let mut mod_db = ModDB::new();
+        mod_db.add(Modifier::base("conv.phys_to_fire", 0.6, "skill:flame_strike"));
+        mod_db.add(Modifier::base("conv.phys_to_cold", 0.4, "support:cold_conversion"));
+        mod_db.add(Modifier::base("conv.phys_to_lightning", 0.4, "ring1:affix1"));
+
+        let plan = ConversionPlan::from_mod_db(&mod_db, &EvalContext::new(), &FixedBitSet::new());
+        let step = &plan.steps[0];
+        let percent_of = |to| step.targets.iter().find(|(t, _)| *t == to).unwrap().1;
+
+        // 技能 60% 全额生效，辅助 40% 恰好填满，装备转化被挤出
+        assert!((percent_of(DamageType::Fire) - 0.6).abs() < 1e-9);
+        assert!((percent_of(DamageType::Cold) - 0.4).abs() < 1e-9);
+        assert!(step.targets.iter().all(|(t, _)| *t != DamageType::Lightning));
+        assert!(step.remaining.abs() < 1e-9);
+
+        assert_eq!(plan.scaled.len(), 1);
+        assert_eq!(plan.scaled[0].source, "ring1:affix1");
+        assert_eq!(plan.scaled[0].applied, 0.0);
+    }
+
+    #[test]
+    fn test_conversion_priority_proportional_scaling() {
+        let mut mod_db = ModDB::new();
+        mod_db.add(Modifier::base("conv.phys_to_fire", 0.5, "skill:flame_strike"));
+        mod_db.add(Modifier::base("conv.phys_to_cold", 0.5, "ring1:affix1"));
+        mod_db.add(Modifier::base("conv.phys_to_lightning", 0.5, "amulet:affix2"));
+
+        let plan = ConversionPlan::from_mod_db(&mod_db, &EvalContext::new(), &FixedBitSet::new());
+        let step = &plan.steps[0];
+        let percent_of = |to| step.targets.iter().find(|(t, _)| *t == to).unwrap().1;
+
+        // 剩余 50% 由两条装备转化按比例平分
+        assert!((percent_of(DamageType::Fire) - 0.5).abs() < 1e-9);
+        assert!((percent_of(DamageType::Cold) - 0.25).abs() < 1e-9);
+        assert!((percent_of(DamageType::Lightning) - 0.25).abs() < 1e-9);
+
+        let mut sources: Vec<&str> = plan.scaled.iter().map(|s| s.source.as_str()).collect();
+        sources.sort();
+        assert_eq!(sources, vec!["amulet:affix2", "ring1:affix1"]);
+        assert!(plan.scaled.iter().all(|s| (s.requested - 0.5).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_extra_as_group_keys_expand_per_type() {
+        let mut mod_db = ModDB::new();
+        mod_db.add(Modifier::base("extra.elemental_as_chaos", 0.1, "item1:affix1"));
+        mod_db.add(Modifier::base("extra.all_as_fire", 0.05, "item2:affix1"));
+        mod_db.add(Modifier::base("extra.fire_as_chaos", 0.2, "item3:affix1"));
+
+        let rules = extract_extra_as_rules(&mod_db, &EvalContext::new(), &FixedBitSet::new());
+        let percent_of = |from, to| {
+            rules
+                .iter()
+                .find(|r| r.from == from && r.to == to)
+                .map(|r| r.percent)
+                .unwrap_or(0.0)
+        };
+
+        // 元素组展开为闪电/冰冷/火焰，与单类型规则合并
+        assert!((percent_of(DamageType::Lightning, DamageType::Chaos) - 0.1).abs() < 1e-9);
+        assert!((percent_of(DamageType::Cold, DamageType::Chaos) - 0.1).abs() < 1e-9);
+        assert!((percent_of(DamageType::Fire, DamageType::Chaos) - 0.3).abs() < 1e-9);
+        assert_eq!(percent_of(DamageType::Physical, DamageType::Chaos), 0.0);
+
+        // 全部组不包含目标类型自身
+        assert!((percent_of(DamageType::Physical, DamageType::Fire) - 0.05).abs() < 1e-9);
+        assert!((percent_of(DamageType::Chaos, DamageType::Fire) - 0.05).abs() < 1e-9);
+        assert_eq!(percent_of(DamageType::Fire, DamageType::Fire), 0.0);
+    }
+
+    #[test]
+    fn test_conditional_conversion_from_mod_db() {
+        let mut mod_db = ModDB::new();
+        mod_db.add(Modifier::base("conv.cold_to_fire", 1.0, "helmet:affix1").with_condition("es_full"));
+        mod_db.add(Modifier::base("extra.phys_as_fire", 0.2, "ring1:affix1").with_requirements(vec![40]));
+
+        let mut active = FixedBitSet::with_capacity(64);
+        let inactive = EvalContext::new();
+        let plan = ConversionPlan::from_mod_db(&mod_db, &inactive, &active);
+        assert!(plan.is_empty());
+
+        // 满足条件与标签要求后生效，且缓存不会返回条件不满足时的计划
+        active.insert(40);
+        let es_full = EvalContext::new().with_flag("es_full", true);
+        let plan = ConversionPlan::cached(&mod_db, &es_full, &active);
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].targets, vec![(DamageType::Fire, 1.0)]);
+        assert_eq!(plan.extra_as.len(), 1);
+    }
+
+    #[test]
+    fn test_chained_conversion_keeps_packets_separate() {
+        let registry = create_test_registry();
+        let engine = ConversionEngine::new(64);
+
+        let mut base = HashMap::new();
+        base.insert(DamageType::Physical, (100.0, 100.0));
+        base.insert(DamageType::Lightning, (40.0, 40.0));
+
+        // 装备：100% 物理转闪电；辅助：50% 闪电转冰冷
+        let plan = ConversionPlan::compile(
+            &[],
+            &[
+                ConversionRule::new(DamageType::Physical, DamageType::Lightning, 1.0),
+                ConversionRule::new(DamageType::Lightning, DamageType::Cold, 0.5),
+            ],
+        );
+        let packets = engine.process_packets(&base, &plan, &registry);
+
+        // 物理转化来的冰冷经过两步，历史标签依次累积
+        let chained = packets
+            .iter()
+            .find(|(t, d)| *t == DamageType::Cold && d.history_tags.contains(10))
+            .unwrap();
+        assert!((chained.1.average() - 50.0).abs() < 1e-6);
+        assert!(chained.1.history_tags.contains(23));
+        assert!(chained.1.history_tags.contains(22));
+
+        // 原生闪电不会带上物理标签
+        let native = packets
+            .iter()
+            .find(|(t, d)| *t == DamageType::Lightning && !d.history_tags.contains(10))
+            .unwrap();
+        assert!((native.1.average() - 20.0).abs() < 1e-6);
+
+        let merged = merge_packets(packets);
+        assert!((merged[&DamageType::Cold].average() - 70.0).abs() < 1e-6);
+        assert!((merged[&DamageType::Lightning].average() - 70.0).abs() < 1e-6);
+        assert!(merged[&DamageType::Physical].is_zero());
+    }
+}
@@ -0,0 +1,180 @@
+//! 自定义属性键模块
+//!
+//! 由数据声明新属性键，社区发现的词缀无需修改计算代码即可建模
+//!
+//! ## 核心设计
+//!
+//! 1. 自定义键由数据表 `src/data/custom_stats.json` 配置（属性键 → 修正类型、生效键、默认值）
+//! 2. 聚合时自定义键改写为规范键：`base` 直接写入生效键，`inc` / `more` 加 `mod.inc.` / `mod.more.` 前缀；
+//!    `.pct` / `.per_xxx` 后缀保留到改写后的键上，再按单位表换算、按层数/属性解析
+//! 3. 已声明的键视为已知键，严格模式校验通过
+//! 4. 所有来源均未提供的自定义键按默认值计入（默认值为 0 时不产生修正）
+
+use crate::stat_units::PERCENT_SUFFIX;
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 自定义属性表 JSON 内容（编译时内嵌）
+pub(crate) const CUSTOM_STATS_JSON: &str = include_str!("data/custom_stats.json");
+
+/// 自定义属性的修正类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomStatKind {
+    /// 基础值
+    Base,
+    /// Increased（加算）
+    Inc,
+    /// More（乘算）
+    More,
+}
+
+/// 单个自定义属性
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomStat {
+    /// 修正类型
+    pub kind: CustomStatKind,
+    /// 生效的属性键（ModDB 键，不含 `mod.inc.` / `mod.more.` 前缀）
+    pub zone: String,
+    /// 未提供时的默认值
+    #[serde(default)]
+    pub default: f64,
+    /// 改写后的规范键（加载时生成）
+    #[serde(skip)]
+    canonical: String,
+}
+
+impl CustomStat {
+    /// 改写后的规范键（如 `mod.more.dmg.cold`）
+    pub fn canonical_key(&self) -> &str {
+        &self.canonical
+    }
+}
+
+/// 自定义属性表
+#[derive(Debug, Clone, Default)]
+pub struct CustomStatTable {
+    /// 属性键 -> 自定义属性
+    stats: HashMap<String, CustomStat>,
+}
+
+impl CustomStatTable {
+    /// 从 JSON 加载自定义属性表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut stats = HashMap::new();
+        for (key, value) in raw {
+            // 跳过元数据
+            if key.starts_with('_') {
+                continue;
+            }
+
+            let mut stat: CustomStat = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse custom stat '{}': {}", key, e))?;
+            if stat.zone.is_empty() || stat.zone.starts_with("mod.") {
+                return Err(format!("Custom stat '{}' has invalid zone '{}'", key, stat.zone));
+            }
+            stat.canonical = match stat.kind {
+                CustomStatKind::Base => stat.zone.clone(),
+                CustomStatKind::Inc => format!("mod.inc.{}", stat.zone),
+                CustomStatKind::More => format!("mod.more.{}", stat.zone),
+            };
+            stats.insert(key, stat);
+        }
+
+        if let Some((key, stat)) = stats.iter().find(|(_, stat)| stats.contains_key(stat.canonical_key())) {
+            return Err(format!("Custom stat '{}' maps to another custom stat '{}'", key, stat.canonical_key()));
+        }
+
+        Ok(Self { stats })
+    }
+
+    /// 加载内嵌的默认自定义属性表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不声明自定义键）
+    pub fn load_default() -> Self {
+        load_embedded("custom_stats", Self::from_json(CUSTOM_STATS_JSON))
+    }
+
+    /// 获取共享的默认自定义属性表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_CUSTOM_STATS: OnceLock<CustomStatTable> = OnceLock::new();
+        DEFAULT_CUSTOM_STATS.get_or_init(Self::load_default)
+    }
+
+    /// 查询自定义属性
+    pub fn get(&self, key: &str) -> Option<&CustomStat> {
+        self.stats.get(key)
+    }
+
+    /// 是否为已声明的自定义键
+    pub fn contains(&self, key: &str) -> bool {
+        self.stats.contains_key(key)
+    }
+
+    /// 自定义键（可带 `.pct` / `.per_xxx` 后缀）改写为规范键: (自定义键, 改写后的键)（非自定义键返回 None）
+    pub fn rewrite<'t>(&'t self, key: &str) -> Option<(&'t str, String)> {
+        let split = if self.stats.contains_key(key) {
+            key.len()
+        } else if let Some(index) = key.find(".per_") {
+            index
+        } else {
+            key.strip_suffix(PERCENT_SUFFIX)?.len()
+        };
+        let (custom_key, stat) = self.stats.get_key_value(&key[..split])?;
+        Some((custom_key.as_str(), format!("{}{}", stat.canonical_key(), &key[split..])))
+    }
+
+    /// 全部自定义属性（属性键, 自定义属性）
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &CustomStat)> {
+        self.stats.iter().map(|(key, stat)| (key.as_str(), stat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::is_known_stat_key;
+
+    #[test]
+    fn test_canonical_keys_by_kind() {
+        let table = CustomStatTable::from_json(
+            r#"{
+                "mod.custom.a": { "kind": "base", "zone": "zone.final_damage.a" },
+                "mod.custom.b": { "kind": "inc", "zone": "dmg.fire", "default": 0.1 },
+                "mod.custom.c": { "kind": "more", "zone": "dmg.cold" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(table.get("mod.custom.a").unwrap().canonical_key(), "zone.final_damage.a");
+        assert_eq!(table.get("mod.custom.b").unwrap().canonical_key(), "mod.inc.dmg.fire");
+        assert_eq!(table.get("mod.custom.b").unwrap().default, 0.1);
+        assert_eq!(table.get("mod.custom.c").unwrap().canonical_key(), "mod.more.dmg.cold");
+
+        // 后缀保留到规范键上
+        assert_eq!(table.rewrite("mod.custom.b"), Some(("mod.custom.b", "mod.inc.dmg.fire".to_string())));
+        assert_eq!(table.rewrite("mod.custom.b.pct"), Some(("mod.custom.b", "mod.inc.dmg.fire.pct".to_string())));
+        assert_eq!(
+            table.rewrite("mod.custom.c.per_10_strength"),
+            Some(("mod.custom.c", "mod.more.dmg.cold.per_10_strength".to_string()))
+        );
+        assert_eq!(table.rewrite("mod.custom.d.pct"), None);
+        assert_eq!(table.rewrite("mod.inc.dmg.fire"), None);
+
+        let invalid = CustomStatTable::from_json(r#"{ "mod.custom.a": { "kind": "inc", "zone": "mod.inc.dmg.fire" } }"#);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_default_custom_stats_known() {
+        let table = CustomStatTable::load_default();
+        for (key, stat) in table.entries() {
+            assert!(is_known_stat_key(key), "{}", key);
+            assert!(is_known_stat_key(stat.canonical_key()), "{} -> {}", key, stat.canonical_key());
+        }
+    }
+}
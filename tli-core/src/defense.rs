@@ -0,0 +1,140 @@
+//! 防御属性换算模块
+//!
+//! 处理防御端的"额外获得"与"适用范围扩展"类词缀，在 EHP 计算前统一结算：
+//! - 额外获得：`def.gain.evasion_as_armor` = 0.3 表示获得 30% 闪避值的额外护甲
+//! - 护甲适用：`def.armor_applies.elemental` = 0.5 表示 50% 护甲对元素伤害生效
+//!
+//! ## 核心设计
+//!
+//! 1. 额外获得只读取换算前的源属性，不会链式叠加（与伤害的额外获得一致）
+//! 2. 护甲适用比例按伤害类型汇总，`elemental` 展开到火焰/冰冷/闪电
+//! 3. 所有修正按当前条件评估（如"闪避时获得额外护甲"）
+
+use crate::condition_ast::EvalContext;
+use crate::conversion::DamageType;
+use crate::modifiers::{ModDB, ModifierStore};
+use std::collections::HashMap;
+
+/// 护甲
+pub const ARMOR: &str = "def.armor";
+/// 闪避
+pub const EVASION: &str = "def.evasion";
+
+/// 防御额外获得属性键表：(属性键, 源属性, 目标属性)
+const DEFENSE_GAIN_KEYS: &[(&str, &str, &str)] = &[
+    ("def.gain.evasion_as_armor", EVASION, ARMOR),
+    ("def.gain.armor_as_evasion", ARMOR, EVASION),
+];
+
+/// 护甲适用属性键表：(属性键, 生效的伤害类型)
+const ARMOR_APPLIES_KEYS: &[(&str, &[DamageType])] = &[
+    (
+        "def.armor_applies.elemental",
+        &[DamageType::Fire, DamageType::Cold, DamageType::Lightning],
+    ),
+    ("def.armor_applies.fire", &[DamageType::Fire]),
+    ("def.armor_applies.cold", &[DamageType::Cold]),
+    ("def.armor_applies.lightning", &[DamageType::Lightning]),
+    ("def.armor_applies.chaos", &[DamageType::Chaos]),
+];
+
+/// 结算后的防御属性
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DefenseStats {
+    /// 护甲（含额外获得）
+    pub armor: f64,
+    /// 闪避（含额外获得）
+    pub evasion: f64,
+    /// 护甲对非物理伤害的生效比例（物理恒为 100%，不记录）
+    pub armor_applies: HashMap<DamageType, f64>,
+}
+
+impl DefenseStats {
+    /// 从 ModDB 结算防御属性
+    pub fn resolve(mod_db: &ModDB, ctx: &EvalContext) -> Self {
+        let source = |key: &str| mod_db.base_or_override_with_ctx(key, ctx);
+        let mut stats = Self {
+            armor: source(ARMOR),
+            evasion: source(EVASION),
+            armor_applies: HashMap::new(),
+        };
+
+        for &(key, from, to) in DEFENSE_GAIN_KEYS {
+            let percent = mod_db.sum_base_with_ctx(key, ctx);
+            if percent <= 0.0 {
+                continue;
+            }
+            let gained = source(from) * percent;
+            match to {
+                ARMOR => stats.armor += gained,
+                EVASION => stats.evasion += gained,
+                _ => {}
+            }
+        }
+
+        for &(key, types) in ARMOR_APPLIES_KEYS {
+            let percent = mod_db.sum_base_with_ctx(key, ctx);
+            if percent <= 0.0 {
+                continue;
+            }
+            for &dtype in types {
+                *stats.armor_applies.entry(dtype).or_insert(0.0) += percent;
+            }
+        }
+
+        stats
+    }
+
+    /// 护甲对指定伤害类型的减伤比例
+    ///
+    /// 简化公式：reduction = armor / (armor + 1000)，非物理伤害按生效比例折算护甲
+    pub fn armor_reduction(&self, dtype: DamageType) -> f64 {
+        let ratio = match dtype {
+            DamageType::Physical => 1.0,
+            _ => self.armor_applies.get(&dtype).copied().unwrap_or(0.0).min(1.0),
+        };
+        let armor = self.armor * ratio;
+        if armor <= 0.0 {
+            return 0.0;
+        }
+        armor / (armor + 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modifiers::Modifier;
+
+    #[test]
+    fn test_evasion_gained_as_armor() {
+        let mut db = ModDB::new();
+        db.add(Modifier::base(ARMOR, 1000.0, "chest:base"));
+        db.add(Modifier::base(EVASION, 2000.0, "boots:base"));
+        db.add(Modifier::base("def.gain.evasion_as_armor", 0.5, "talent"));
+        db.add(Modifier::base("def.gain.armor_as_evasion", 0.1, "ring1:affix1"));
+
+        let stats = DefenseStats::resolve(&db, &EvalContext::new());
+        // 只按换算前的源属性计算，不链式叠加
+        assert!((stats.armor - 2000.0).abs() < 1e-6);
+        assert!((stats.evasion - 2100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_armor_applies_to_elemental() {
+        let mut db = ModDB::new();
+        db.add(Modifier::base(ARMOR, 1000.0, "chest:base"));
+        db.add(Modifier::base("def.armor_applies.elemental", 0.5, "keystone"));
+        db.add(Modifier::base("def.armor_applies.fire", 0.2, "helmet:affix1").with_condition("is_moving"));
+
+        let stats = DefenseStats::resolve(&db, &EvalContext::new());
+        assert!((stats.armor_reduction(DamageType::Physical) - 0.5).abs() < 1e-9);
+        assert!((stats.armor_reduction(DamageType::Cold) - 500.0 / 1500.0).abs() < 1e-9);
+        assert_eq!(stats.armor_reduction(DamageType::Chaos), 0.0);
+
+        // 条件满足时火焰额外 20%
+        let moving = EvalContext::new().with_flag("is_moving", true);
+        let stats = DefenseStats::resolve(&db, &moving);
+        assert!((stats.armor_reduction(DamageType::Fire) - 700.0 / 1700.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,159 @@
+//! 本地化模块
+//!
+//! 输出中的显示文本（乘区来源、调试追踪描述）以稳定的本地化键 + 参数给出，
+//! 显示字符串集中在内嵌的语言表（`src/data/locales.json`），中英文界面均可据此渲染
+//!
+//! ## 核心设计
+//!
+//! 1. 模板中的 `{name}` 替换为同名参数，缺失的参数保留原样
+//! 2. 参数值在语言表中存在 `<参数名>.<值>` 条目时使用其翻译（如 `damage = fire` → `damage.fire`）
+//! 3. 缺失的键依次回退到默认语言与键本身
+//! 4. 输出中的 `source` / `description` 按默认语言（简体中文）渲染，保持兼容
+
+use crate::utils::load_embedded;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 默认语言
+pub const DEFAULT_LOCALE: &str = "zh-CN";
+
+/// 语言表 JSON 内容（编译时内嵌）
+pub(crate) const LOCALES_JSON: &str = include_str!("data/locales.json");
+
+/// 本地化参数（参数名 -> 值）
+pub type MessageParams = HashMap<String, String>;
+
+/// 语言表
+#[derive(Debug, Clone, Default)]
+pub struct LocaleTable {
+    /// 语言 -> 本地化键 -> 模板
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl LocaleTable {
+    /// 从 JSON 加载语言表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut locales = HashMap::new();
+        for (locale, value) in raw {
+            // 跳过元数据
+            if locale.starts_with('_') {
+                continue;
+            }
+
+            let strings: HashMap<String, String> = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse locale '{}': {}", locale, e))?;
+            locales.insert(locale, strings);
+        }
+
+        Ok(Self { locales })
+    }
+
+    /// 加载内嵌的默认语言表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：渲染结果为本地化键本身）
+    pub fn load_default() -> Self {
+        load_embedded("locales", Self::from_json(LOCALES_JSON))
+    }
+
+    /// 获取共享的默认语言表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_LOCALES: OnceLock<LocaleTable> = OnceLock::new();
+        DEFAULT_LOCALES.get_or_init(Self::load_default)
+    }
+
+    /// 已收录的语言（排序）
+    pub fn locales(&self) -> Vec<&str> {
+        let mut locales: Vec<&str> = self.locales.keys().map(String::as_str).collect();
+        locales.sort_unstable();
+        locales
+    }
+
+    /// 指定语言的完整字符串表（缺失的键以默认语言补全）
+    pub fn strings(&self, locale: &str) -> HashMap<String, String> {
+        let mut strings = self.locales.get(DEFAULT_LOCALE).cloned().unwrap_or_default();
+        if let Some(overrides) = self.locales.get(locale) {
+            strings.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        strings
+    }
+
+    /// 查询单个键的模板（回退到默认语言）
+    pub fn translate(&self, locale: &str, key: &str) -> Option<&str> {
+        self.locales
+            .get(locale)
+            .and_then(|strings| strings.get(key))
+            .or_else(|| self.locales.get(DEFAULT_LOCALE).and_then(|strings| strings.get(key)))
+            .map(String::as_str)
+    }
+
+    /// 按模板渲染本地化文本
+    pub fn render(&self, locale: &str, key: &str, params: &MessageParams) -> String {
+        let Some(template) = self.translate(locale, key) else {
+            return key.to_string();
+        };
+
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                rest = &rest[start..];
+                break;
+            };
+            let name = &rest[start + 1..start + len];
+            match params.get(name) {
+                Some(value) => {
+                    let nested = format!("{}.{}", name, value);
+                    result.push_str(self.translate(locale, &nested).unwrap_or(value));
+                }
+                None => result.push_str(&rest[start..=start + len]),
+            }
+            rest = &rest[start + len + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+/// 由键值对构建本地化参数
+pub fn message_params(pairs: &[(&str, String)]) -> MessageParams {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+}
+
+/// 按默认语言渲染本地化文本
+pub fn render_default(key: &str, params: &MessageParams) -> String {
+    LocaleTable::shared().render(DEFAULT_LOCALE, key, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_with_nested_translation() {
+        let table = LocaleTable::load_default();
+        let params = message_params(&[("source", "ring_1".to_string()), ("damage", "fire".to_string())]);
+
+        assert_eq!(table.render("zh-CN", "zone.increased", &params), "ring_1 (火焰伤害增加)");
+        assert_eq!(table.render("en", "zone.increased", &params), "ring_1 (fire increased damage)");
+
+        // 未收录的参数值原样输出，未知语言回退到默认语言
+        let params = message_params(&[("source", "x".to_string()), ("damage", "fire.spell".to_string())]);
+        assert_eq!(table.render("ja", "zone.increased", &params), "x (fire.spell伤害增加)");
+        assert_eq!(table.render("en", "zone.unknown", &params), "zone.unknown");
+    }
+
+    #[test]
+    fn test_locales_cover_same_keys() {
+        let table = LocaleTable::load_default();
+        assert_eq!(table.locales(), vec!["en", "zh-CN"]);
+        let zh = table.strings("zh-CN");
+        let en = &table.locales["en"];
+        let mut missing: Vec<&String> = zh.keys().filter(|k| !en.contains_key(*k)).collect();
+        missing.sort();
+        assert!(missing.is_empty(), "missing en strings: {:?}", missing);
+    }
+}
@@ -0,0 +1,192 @@
+//! 装备基底模块
+//!
+//! 按 `ItemData.base_type` 补全基底固有属性，调用方无需自行填写 `base_implicit_stats`
+//!
+//! ## 核心设计
+//!
+//! 1. 基底数据由数据表 `src/data/items_meta.json` 配置（来源 `supabase/seed.sql` items_meta，武器攻击时间与暴击率取自同名列）
+//! 2. 只补全装备未提供的属性键，调用方已提供的键优先
+//! 3. 基底为双手武器时装备视为双手；基底标签追加到装备标签（去重）
+//! 4. 武器基础攻击时间补全为局部攻速 `speed.attack.local`（= 1 / 攻击时间），基础暴击率补全为 `crit.chance.local`
+//! 5. 装备未提供需求时使用基底需求（等级与核心属性）
+//! 6. 未收录的基底类型保持原样
+
+use crate::types::{ItemData, ItemRequirements};
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 基底表 JSON 内容（编译时内嵌）
+pub(crate) const ITEMS_META_JSON: &str = include_str!("data/items_meta.json");
+
+/// 单个装备基底
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ItemBase {
+    /// 显示名称
+    #[serde(default)]
+    pub display_name: String,
+    /// 槽位分类（weapon_main, ring 等）
+    #[serde(default)]
+    pub slot: String,
+    /// 是否为双手武器
+    #[serde(default)]
+    pub is_two_handed: bool,
+    /// 武器基础攻击时间（秒/次）
+    #[serde(default)]
+    pub attack_time: Option<f64>,
+    /// 武器基础暴击率
+    #[serde(default)]
+    pub crit_chance: Option<f64>,
+    /// 基底固有属性
+    #[serde(default)]
+    pub implicit_stats: HashMap<String, f64>,
+    /// 基底标签
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 装备需求
+    #[serde(default)]
+    pub requirements: Option<ItemRequirements>,
+}
+
+impl ItemBase {
+    /// 基底提供的全部属性（固有属性 + 武器攻击时间/暴击率换算的局部属性）
+    pub fn base_stats(&self) -> impl Iterator<Item = (&str, f64)> {
+        let attack_speed = self
+            .attack_time
+            .filter(|time| *time > 0.0)
+            .map(|time| ("speed.attack.local", 1.0 / time));
+        let crit_chance = self.crit_chance.map(|chance| ("crit.chance.local", chance));
+        self.implicit_stats
+            .iter()
+            .map(|(key, value)| (key.as_str(), *value))
+            .chain(attack_speed)
+            .chain(crit_chance)
+    }
+}
+
+/// 装备基底表
+#[derive(Debug, Clone, Default)]
+pub struct ItemsMetaTable {
+    /// base_type -> 基底
+    bases: HashMap<String, ItemBase>,
+}
+
+impl ItemsMetaTable {
+    /// 从 JSON 加载基底表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut bases = HashMap::new();
+        for (base_type, value) in raw {
+            // 跳过元数据
+            if base_type.starts_with('_') {
+                continue;
+            }
+
+            let base: ItemBase = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse item base '{}': {}", base_type, e))?;
+            bases.insert(base_type, base);
+        }
+
+        Ok(Self { bases })
+    }
+
+    /// 加载内嵌的默认基底表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不做补全）
+    pub fn load_default() -> Self {
+        load_embedded("items_meta", Self::from_json(ITEMS_META_JSON))
+    }
+
+    /// 获取共享的默认基底表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_ITEMS_META: OnceLock<ItemsMetaTable> = OnceLock::new();
+        DEFAULT_ITEMS_META.get_or_init(Self::load_default)
+    }
+
+    /// 查询基底
+    pub fn get(&self, base_type: &str) -> Option<&ItemBase> {
+        self.bases.get(base_type)
+    }
+
+    /// 按基底补全装备（无需补全时不复制）
+    pub fn resolve<'a>(&self, item: &'a ItemData) -> Cow<'a, ItemData> {
+        let Some(base) = self.get(&item.base_type) else {
+            return Cow::Borrowed(item);
+        };
+        let complete = (item.is_two_handed || !base.is_two_handed)
+            && base.base_stats().all(|(key, _)| item.base_implicit_stats.contains_key(key))
+            && base.tags.iter().all(|tag| item.tags.contains(tag))
+            && (item.requirements.is_some() || base.requirements.is_none());
+        if complete {
+            return Cow::Borrowed(item);
+        }
+
+        let mut resolved = item.clone();
+        resolved.is_two_handed |= base.is_two_handed;
+        for (key, value) in base.base_stats() {
+            resolved.base_implicit_stats.entry(key.to_string()).or_insert(value);
+        }
+        for tag in &base.tags {
+            if !resolved.tags.contains(tag) {
+                resolved.tags.push(tag.clone());
+            }
+        }
+        if resolved.requirements.is_none() {
+            resolved.requirements = base.requirements.clone();
+        }
+        Cow::Owned(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(base_type: &str) -> ItemData {
+        serde_json::from_value(serde_json::json!({
+            "id": "item", "base_type": base_type, "slot": "weapon_main",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_fills_missing_base_stats() {
+        let table = ItemsMetaTable::load_default();
+        let sword = item("sword_2h_phys");
+        let resolved = table.resolve(&sword);
+        assert!(resolved.is_two_handed);
+        assert_eq!(resolved.base_implicit_stats["dmg.phys.max"], 80.0);
+        assert!(resolved.tags.contains(&"Tag_TwoHanded".to_string()));
+
+        // 调用方提供的属性优先，已完整的装备不复制
+        let mut custom = item("gloves_all_magic_grip");
+        custom.base_implicit_stats.insert("base.es".to_string(), 527.0);
+        let resolved = table.resolve(&custom);
+        assert_eq!(resolved.base_implicit_stats["base.es"], 527.0);
+        assert!(matches!(table.resolve(&resolved), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_attack_time_resolves_to_local_speed() {
+        let table = ItemsMetaTable::load_default();
+        let sword = item("sword_2h_phys");
+        let resolved = table.resolve(&sword);
+        assert_eq!(resolved.base_implicit_stats["speed.attack.local"], 1.0);
+        assert_eq!(resolved.base_implicit_stats["crit.chance.local"], 0.05);
+
+        let wand = item("wand_fire");
+        let resolved = table.resolve(&wand);
+        assert!((resolved.base_implicit_stats["speed.attack.local"] - 1.0 / 0.67).abs() < 1e-9);
+        assert_eq!(resolved.requirements.as_ref().map(|r| r.intelligence), Some(25));
+    }
+
+    #[test]
+    fn test_unknown_base_type_unchanged() {
+        let table = ItemsMetaTable::load_default();
+        assert!(matches!(table.resolve(&item("axe_2h")), Cow::Borrowed(_)));
+    }
+}
@@ -3,9 +3,12 @@
 //! 本模块提供完整的 DPS/EHP 计算管线，包括：
 //! - 标签系统 (UTAS)
 //! - 属性聚合
+//! - 技能等级数据
+//! - 核心属性 (力量/敏捷/智慧)
 //! - 机制系统 (祝福、球类等)
 //! - 伤害转化与标签记忆
 //! - 暴击与减伤计算
+//! - 防御属性换算
 //! - LRU 缓存优化 (悬停预览加速)
 
 use std::cell::RefCell;
@@ -14,23 +17,44 @@ use wasm_bindgen::prelude::*;
 pub mod types;
 pub mod tags;
 pub mod stats;
+pub mod attributes;
 pub mod mechanics;
 pub mod conversion;
+pub mod defense;
+pub mod skills;
 pub mod pipeline;
 pub mod calculator_cache;
 pub mod modifiers;
+pub mod more_buckets;
+pub mod stat_key;
+pub mod stat_aliases;
+pub mod stat_units;
+pub mod slots;
+pub mod items_meta;
+pub mod character;
+pub mod memories;
+pub mod multiplier_zones;
+pub mod custom_stats;
+pub mod stat_hooks;
 pub mod condition_ast;
+pub mod i18n;
 pub mod utils;
 
 pub use types::*;
 pub use tags::*;
 pub use stats::*;
+pub use attributes::*;
 pub use mechanics::*;
 pub use conversion::*;
+pub use defense::*;
+pub use skills::*;
 pub use pipeline::*;
 pub use calculator_cache::*;
 pub use modifiers::*;
+pub use more_buckets::*;
+pub use stat_key::*;
 pub use condition_ast::*;
+pub use i18n::*;
 
 // WASM 环境中使用 thread_local 维护全局缓存
 // 注意：WASM 是单线程的，所以这是安全的
@@ -48,7 +72,7 @@ pub fn init() {
 /// 主计算入口点（无缓存）
 #[wasm_bindgen]
 pub fn calculate(input_json: &str) -> Result<String, JsValue> {
-    let input: CalculatorInput = serde_json::from_str(input_json)
+    let input: CalculatorInput = utils::parse_json(input_json)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
     
     let result = pipeline::calculate_dps(&input)
@@ -64,7 +88,7 @@ pub fn calculate(input_json: &str) -> Result<String, JsValue> {
 /// 相同输入会直接返回缓存结果
 #[wasm_bindgen]
 pub fn calculate_cached(input_json: &str) -> Result<String, JsValue> {
-    let input: CalculatorInput = serde_json::from_str(input_json)
+    let input: CalculatorInput = utils::parse_json(input_json)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
     
     let result = GLOBAL_CACHE.with(|cache| {
@@ -87,10 +111,10 @@ pub fn calculate_cached(input_json: &str) -> Result<String, JsValue> {
 /// JSON 格式的差异结果
 #[wasm_bindgen]
 pub fn calculate_diff(base_json: &str, preview_json: &str) -> Result<String, JsValue> {
-    let base_input: CalculatorInput = serde_json::from_str(base_json)
+    let base_input: CalculatorInput = utils::parse_json(base_json)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse base input: {}", e)))?;
     
-    let preview_input: CalculatorInput = serde_json::from_str(preview_json)
+    let preview_input: CalculatorInput = utils::parse_json(preview_json)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse preview input: {}", e)))?;
     
     let diff = GLOBAL_CACHE.with(|cache| {
@@ -107,12 +131,147 @@ pub fn calculate_diff(base_json: &str, preview_json: &str) -> Result<String, JsV
         "crit_chance_diff": diff.crit_chance_diff,
         "base_dps": diff.base.dps_theoretical,
         "preview_dps": diff.preview.dps_theoretical,
+        "cache_info": {
+            "base": diff.base.cache_info,
+            "preview": diff.preview.cache_info,
+        },
     });
     
     serde_json::to_string(&output)
         .map_err(|e| JsValue::from_str(&format!("Failed to serialize diff: {}", e)))
 }
 
+/// 机制 DPS 贡献分析
+///
+/// 逐个关闭生效的机制（祝福、球类、资源等）重算 DPS，
+/// 返回按贡献从高到低排序的 `MechanicContribution` 数组 JSON
+#[wasm_bindgen]
+pub fn calculate_mechanic_contributions(input_json: &str) -> Result<String, JsValue> {
+    let input: CalculatorInput = utils::parse_json(input_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
+
+    let contributions = pipeline::mechanic_contributions(&input)
+        .map_err(|e| JsValue::from_str(&format!("Calculation error: {}", e)))?;
+
+    serde_json::to_string(&contributions)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// 装备贡献分析（带缓存）
+///
+/// 逐件移除装备重算，返回按 DPS 贡献从低到高排序的 `ItemContribution` 数组 JSON
+#[wasm_bindgen]
+pub fn calculate_item_contributions(input_json: &str) -> Result<String, JsValue> {
+    let input: CalculatorInput = utils::parse_json(input_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
+
+    let contributions = GLOBAL_CACHE.with(|cache| {
+        cache.borrow_mut().item_contributions(&input)
+    }).map_err(|e| JsValue::from_str(&format!("Calculation error: {}", e)))?;
+
+    serde_json::to_string(&contributions)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// 辅助技能贡献分析（带缓存）
+///
+/// 逐个移除辅助技能重算，返回按 DPS 贡献从低到高排序的 `SupportContribution` 数组 JSON
+#[wasm_bindgen]
+pub fn calculate_support_contributions(input_json: &str) -> Result<String, JsValue> {
+    let input: CalculatorInput = utils::parse_json(input_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
+
+    let contributions = GLOBAL_CACHE.with(|cache| {
+        cache.borrow_mut().support_contributions(&input)
+    }).map_err(|e| JsValue::from_str(&format!("Calculation error: {}", e)))?;
+
+    serde_json::to_string(&contributions)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// 假设分析开关矩阵
+///
+/// `toggles_json` 为 `Toggle` 数组，返回每个开关组合的 `ToggleScenario` 数组 JSON
+#[wasm_bindgen]
+pub fn calculate_toggle_matrix(input_json: &str, toggles_json: &str) -> Result<String, JsValue> {
+    let input: CalculatorInput = utils::parse_json(input_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
+
+    let toggles: Vec<Toggle> = utils::parse_json(toggles_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse toggles: {}", e)))?;
+
+    let scenarios = pipeline::evaluate_toggles(&input, &toggles)
+        .map_err(|e| JsValue::from_str(&format!("Calculation error: {}", e)))?;
+
+    serde_json::to_string(&scenarios)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// 多场景对比（带缓存）
+///
+/// `scenarios_json` 为 `ScenarioDelta` 数组，返回每个场景的 `ScenarioResult` 数组 JSON（共享基准预处理上下文）
+#[wasm_bindgen]
+pub fn calculate_scenarios(input_json: &str, scenarios_json: &str) -> Result<String, JsValue> {
+    let input: CalculatorInput = utils::parse_json(input_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
+
+    let scenarios: Vec<ScenarioDelta> = utils::parse_json(scenarios_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse scenarios: {}", e)))?;
+
+    let results = GLOBAL_CACHE.with(|cache| {
+        cache.borrow_mut().evaluate_scenarios(&input, &scenarios)
+    }).map_err(|e| JsValue::from_str(&format!("Calculation error: {}", e)))?;
+
+    serde_json::to_string(&results)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// 参数扫描
+///
+/// 上下文数值 `key` 从 `from` 到 `to` 取 `steps` 个点，返回 `SweepPoint` 数组 JSON
+#[wasm_bindgen]
+pub fn calculate_sweep(input_json: &str, key: &str, from: f64, to: f64, steps: usize) -> Result<String, JsValue> {
+    let input: CalculatorInput = utils::parse_json(input_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse input: {}", e)))?;
+
+    let curve = pipeline::sweep_value(&input, key, from, to, steps)
+        .map_err(|e| JsValue::from_str(&format!("Calculation error: {}", e)))?;
+
+    serde_json::to_string(&curve)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// 两套构筑的完整对比（带缓存）
+///
+/// 返回 `BuildComparison` JSON：总览、各乘区、防御与各伤害类型的逐项差值（B - A）
+#[wasm_bindgen]
+pub fn compare_builds(a_json: &str, b_json: &str) -> Result<String, JsValue> {
+    let a_input: CalculatorInput = utils::parse_json(a_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse build A: {}", e)))?;
+
+    let b_input: CalculatorInput = utils::parse_json(b_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse build B: {}", e)))?;
+
+    let comparison = GLOBAL_CACHE.with(|cache| {
+        cache.borrow_mut().compare_builds(&a_input, &b_input)
+    }).map_err(|e| JsValue::from_str(&format!("Calculation error: {}", e)))?;
+
+    serde_json::to_string(&comparison)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize comparison: {}", e)))
+}
+
+/// 获取指定语言的显示字符串表
+///
+/// 返回本地化键 -> 模板的 JSON，缺失的键以默认语言（zh-CN）补全；
+/// 配合输出中的 `label_key` / `message_key` 及参数渲染乘区来源与追踪描述
+#[wasm_bindgen]
+pub fn get_locale_strings(locale: &str) -> Result<String, JsValue> {
+    let strings = LocaleTable::shared().strings(locale);
+
+    serde_json::to_string(&strings)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize locale strings: {}", e)))
+}
+
 /// 获取缓存统计信息
 #[wasm_bindgen]
 pub fn get_cache_stats() -> String {
@@ -128,6 +287,39 @@ pub fn get_cache_stats() -> String {
     })
 }
 
+/// 开启或关闭计算记录（默认关闭，关闭时清空已有记录）
+///
+/// 只记录 `calculate_cached` 的输入与结果，供 `export_state` 导出
+#[wasm_bindgen]
+pub fn set_state_recording(enabled: bool) {
+    GLOBAL_CACHE.with(|cache| cache.borrow_mut().set_recording(enabled));
+}
+
+/// 导出计算器状态（用于问题反馈）
+///
+/// 返回最近 `limit` 条计算记录（输入与结果）、缓存统计、引擎与数据版本的 JSON
+#[wasm_bindgen]
+pub fn export_state(limit: usize) -> Result<String, JsValue> {
+    let state = GLOBAL_CACHE.with(|cache| cache.borrow().export_state(limit));
+
+    serde_json::to_string(&state)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
+}
+
+/// 导入计算器状态并逐条重放
+///
+/// 返回 `StateReplay` JSON：版本是否一致，以及各条记录重放与记录的理论 DPS 对比（单条失败时带错误信息）
+#[wasm_bindgen]
+pub fn import_state(state_json: &str) -> Result<String, JsValue> {
+    let state: CalculatorState = utils::parse_json(state_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse state: {}", e)))?;
+
+    let replay = GLOBAL_CACHE.with(|cache| cache.borrow_mut().import_state(&state));
+
+    serde_json::to_string(&replay)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize replay: {}", e)))
+}
+
 /// 清空计算缓存
 #[wasm_bindgen]
 pub fn clear_cache() {
@@ -139,6 +331,6 @@ pub fn clear_cache() {
 /// 获取版本信息
 #[wasm_bindgen]
 pub fn version() -> String {
-    env!("CARGO_PKG_VERSION").to_string()
+    ENGINE_VERSION.to_string()
 }
 
@@ -7,33 +7,197 @@
 //! - **祝福 (Blessing)**: 聚能祝福、坚韧祝福、灵动祝福
 //! - **球类 (Charge)**: 狂乱球、能量球、耐力球
 //! - **资源 (Resource)**: 怒火、护体等
+//! - **封印 (Sealed)**: 封印魔力，层数为封印的魔力百分比（0-100）
 //!
 //! ## 核心设计
 //!
 //! 1. 每种机制有基础效果（每层提供的属性）
 //! 2. 装备/天赋可以提供 `.per_xxx` 类型的属性，与层数相乘
 //! 3. 机制处理器负责计算总效果并应用到属性池
+//! 4. 层数按 [`StackMode`] 取值：手动层数、满层，或按获取速率 × 持续时间估算的稳态平均层数
+//! 5. 调用方未提供定义的机制回退到内嵌的内置定义（`src/data/mechanics_registry.json`）
+//! 6. 同一互斥组（`exclusive_group`）的机制同时只有一个生效：
+//!    层数最高者优先，层数相同时按机制 ID 字典序取第一个，其余视为未激活
+//! 7. 消耗型资源（配置 `cost_per_use`，如怒火）在稳态模式下按可持续比例折算层数：
+//!    满层 × min(1, 获取速率 / (每次消耗 × 技能每秒使用次数))
+//! 8. `.per_sealed_<N>` 属性按所有封印类机制的封印魔力总和（每 N%）缩放
+//! 9. 命中/击杀叠层的机制在稳态模式下按每秒命中/击杀数估算层数（由计算管线迭代求解）
+//! 10. 生效机制的 `tag_key`（如 `Mech_Blessing`）注入上下文标签
 
-use crate::types::{MechanicDefinition, MechanicState};
-use std::collections::HashMap;
+use crate::types::{MechanicDefinition, MechanicState, StackMode};
+use crate::utils::load_embedded;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// 封印魔力机制分类
+pub const SEALED_CATEGORY: &str = "sealed";
+
+/// 内置机制定义 JSON 内容（编译时内嵌）
+pub(crate) const MECHANICS_REGISTRY_JSON: &str = include_str!("data/mechanics_registry.json");
+
+/// 解析机制定义 JSON（id -> 定义，跳过 `_` 开头的元数据）
+pub fn parse_mechanic_definitions(json: &str) -> Result<HashMap<String, MechanicDefinition>, String> {
+    let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+    let mut definitions = HashMap::new();
+    for (id, value) in raw {
+        if id.starts_with('_') {
+            continue;
+        }
+        let def: MechanicDefinition = serde_json::from_value(value)
+            .map_err(|e| format!("Failed to parse mechanic '{}': {}", id, e))?;
+        definitions.insert(id, def);
+    }
+    Ok(definitions)
+}
+
+/// 获取内置机制定义（祝福、球类、战意；首次调用时解析）
+pub fn builtin_definitions() -> &'static HashMap<String, MechanicDefinition> {
+    static BUILTIN: OnceLock<HashMap<String, MechanicDefinition>> = OnceLock::new();
+    BUILTIN.get_or_init(|| load_embedded("mechanics_registry", parse_mechanic_definitions(MECHANICS_REGISTRY_JSON)))
+}
 
 /// 机制处理器
 /// 
 /// 负责管理和计算所有机制的效果
+#[derive(Debug, Clone)]
 pub struct MechanicsProcessor {
     /// 机制定义 (id -> definition)
     definitions: HashMap<String, MechanicDefinition>,
     /// 机制状态 (id -> state)
     states: HashMap<String, MechanicState>,
+    /// 层数取值模式
+    stack_mode: StackMode,
+    /// 因互斥组冲突被压制的机制 (被压制 id -> (生效 id, 互斥组))
+    suppressed: HashMap<String, (String, String)>,
+    /// 技能每秒使用次数（用于消耗型资源的可持续比例）
+    use_rate: f64,
+    /// 每秒命中数与击杀数（用于命中/击杀叠层）
+    event_rates: (f64, f64),
+    /// 强制关闭的机制（授予也不生效）
+    disabled: HashSet<String>,
 }
 
 impl MechanicsProcessor {
     /// 创建新的机制处理器
+    ///
+    /// 状态中出现但未提供定义的机制使用内置定义
     pub fn new(definitions: Vec<MechanicDefinition>, states: Vec<MechanicState>) -> Self {
-        Self {
-            definitions: definitions.into_iter().map(|d| (d.id.clone(), d)).collect(),
+        let mut definitions: HashMap<String, MechanicDefinition> =
+            definitions.into_iter().map(|d| (d.id.clone(), d)).collect();
+        for state in &states {
+            if !definitions.contains_key(&state.id) {
+                if let Some(def) = builtin_definitions().get(&state.id) {
+                    definitions.insert(state.id.clone(), def.clone());
+                }
+            }
+        }
+        let mut processor = Self {
+            definitions,
             states: states.into_iter().map(|s| (s.id.clone(), s)).collect(),
+            stack_mode: StackMode::Manual,
+            suppressed: HashMap::new(),
+            use_rate: 0.0,
+            event_rates: (0.0, 0.0),
+            disabled: HashSet::new(),
+        };
+        processor.resolve_exclusive_groups();
+        processor
+    }
+
+    /// 设置层数取值模式
+    pub fn with_stack_mode(mut self, stack_mode: StackMode) -> Self {
+        self.stack_mode = stack_mode;
+        self.resolve_exclusive_groups();
+        self
+    }
+
+    /// 设置技能每秒使用次数（消耗型资源按此估算可持续比例）
+    pub fn with_use_rate(mut self, uses_per_second: f64) -> Self {
+        self.set_use_rate(uses_per_second);
+        self
+    }
+
+    /// 更新技能每秒使用次数（聚合出速度加成后按实际速率重新估算）
+    pub fn set_use_rate(&mut self, uses_per_second: f64) {
+        self.use_rate = uses_per_second;
+        self.resolve_exclusive_groups();
+    }
+
+    /// 是否存在按使用次数消耗的资源机制
+    pub fn has_resource_costs(&self) -> bool {
+        self.definitions.values().any(|def| def.cost_per_use.is_some_and(|cost| cost > 0.0))
+    }
+
+    /// 设置每秒命中数与击杀数（命中/击杀叠层按此估算稳态层数）
+    pub fn with_event_rates(mut self, hits_per_second: f64, kills_per_second: f64) -> Self {
+        self.event_rates = (hits_per_second, kills_per_second);
+        self.resolve_exclusive_groups();
+        self
+    }
+
+    /// 强制关闭指定机制（授予、互斥组竞争均不再考虑）
+    pub fn with_disabled(mut self, mech_ids: &[String]) -> Self {
+        self.disabled = mech_ids.iter().cloned().collect();
+        self.resolve_exclusive_groups();
+        self
+    }
+
+    /// 消耗型资源的可持续比例（未配置消耗或未设置使用速率时返回 `None`）
+    pub fn resource_uptime(&self, mech_id: &str) -> Option<f64> {
+        self.definitions
+            .get(mech_id)?
+            .sustainable_uptime(self.use_rate)
+    }
+
+    /// 解析互斥组冲突
+    ///
+    /// 同组激活机制中按有效层数从高到低取胜者，层数相同时取机制 ID 字典序最小者
+    fn resolve_exclusive_groups(&mut self) {
+        self.suppressed.clear();
+        let mut groups: HashMap<&str, Vec<(&String, f64)>> = HashMap::new();
+        for (mech_id, state) in &self.states {
+            if !state.is_active || self.disabled.contains(mech_id) {
+                continue;
+            }
+            let group = self
+                .definitions
+                .get(mech_id)
+                .and_then(|def| def.exclusive_group.as_deref());
+            if let Some(group) = group {
+                groups
+                    .entry(group)
+                    .or_default()
+                    .push((mech_id, self.state_stacks(state)));
+            }
+        }
+
+        let mut suppressed = HashMap::new();
+        for (group, mut members) in groups {
+            members.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            let winner = members[0].0;
+            for (mech_id, _) in &members[1..] {
+                suppressed.insert((*mech_id).clone(), (winner.clone(), group.to_string()));
+            }
         }
+        self.suppressed = suppressed;
+    }
+
+    /// 因互斥组冲突被压制的机制：(被压制 id, 生效 id, 互斥组)，按 id 排序
+    pub fn exclusive_conflicts(&self) -> Vec<(&str, &str, &str)> {
+        let mut conflicts: Vec<_> = self
+            .suppressed
+            .iter()
+            .map(|(id, (winner, group))| (id.as_str(), winner.as_str(), group.as_str()))
+            .collect();
+        conflicts.sort();
+        conflicts
+    }
+
+    /// 机制是否实际生效（已激活、未被关闭且未被互斥组压制）
+    fn is_effective(&self, state: &MechanicState) -> bool {
+        state.is_active && !self.disabled.contains(&state.id) && !self.suppressed.contains_key(&state.id)
     }
 
     /// 创建空的机制处理器（无任何机制）
@@ -41,25 +205,99 @@ impl MechanicsProcessor {
         Self {
             definitions: HashMap::new(),
             states: HashMap::new(),
+            stack_mode: StackMode::Manual,
+            suppressed: HashMap::new(),
+            use_rate: 0.0,
+            event_rates: (0.0, 0.0),
+            disabled: HashSet::new(),
         }
     }
 
-    /// 获取机制当前层数
+    /// 获取机制当前层数（平均层数向下取整）
     /// 
     /// 如果机制未激活或不存在，返回 0
     pub fn get_stacks(&self, mech_id: &str) -> u32 {
+        self.effective_stacks(mech_id).floor() as u32
+    }
+
+    /// 获取机制按层数模式计算的有效层数（可为小数）
+    ///
+    /// 如果机制未激活或不存在，返回 0
+    pub fn effective_stacks(&self, mech_id: &str) -> f64 {
         self.states
             .get(mech_id)
-            .filter(|s| s.is_active)
-            .map(|s| s.current_stacks)
-            .unwrap_or(0)
+            .filter(|s| self.is_effective(s))
+            .map(|s| self.state_stacks(s))
+            .unwrap_or(0.0)
     }
 
-    /// 检查机制是否激活
+    /// 按层数模式解析单个状态的层数
+    fn state_stacks(&self, state: &MechanicState) -> f64 {
+        // 手动层数不超过当前上限
+        let current = state.current_stacks.min(state.max_stacks) as f64;
+        match self.stack_mode {
+            StackMode::Manual => current,
+            StackMode::Max => state.max_stacks as f64,
+            StackMode::Realistic => self
+                .definitions
+                .get(&state.id)
+                .and_then(|def| {
+                    def.sustainable_uptime(self.use_rate)
+                        .map(|uptime| state.max_stacks as f64 * uptime)
+                        .or_else(|| {
+                            let (hits, kills) = self.event_rates;
+                            def.steady_state_stacks(state.max_stacks, hits, kills)
+                        })
+                })
+                .unwrap_or(current),
+        }
+    }
+
+    /// 授予机制（如辅助技能"施法时获得聚能祝福"）
+    ///
+    /// 已有状态时激活并取较高层数；否则按定义（含内置定义）的默认上限新建激活状态
+    pub fn grant(&mut self, mech_id: &str, stacks: u32) {
+        if !self.definitions.contains_key(mech_id) {
+            if let Some(def) = builtin_definitions().get(mech_id) {
+                self.definitions.insert(mech_id.to_string(), def.clone());
+            }
+        }
+        let max_stacks = self
+            .definitions
+            .get(mech_id)
+            .map(|def| def.default_max_stacks)
+            .unwrap_or(stacks);
+        let state = self
+            .states
+            .entry(mech_id.to_string())
+            .or_insert_with(|| MechanicState {
+                id: mech_id.to_string(),
+                current_stacks: 0,
+                max_stacks,
+                is_active: false,
+            });
+        state.is_active = true;
+        state.current_stacks = state.current_stacks.max(stacks);
+        self.resolve_exclusive_groups();
+    }
+
+    /// 应用层数上限加成（如装备"聚能祝福上限 +2"）
+    ///
+    /// 上限最低为 0；当前层数与每层收益按新上限钳制
+    pub fn apply_max_stack_bonuses(&mut self, bonuses: &HashMap<String, f64>) {
+        for (mech_id, bonus) in bonuses {
+            if let Some(state) = self.states.get_mut(mech_id) {
+                state.max_stacks = (state.max_stacks as f64 + bonus).round().max(0.0) as u32;
+            }
+        }
+        self.resolve_exclusive_groups();
+    }
+
+    /// 检查机制是否激活（被互斥组压制的机制视为未激活）
     pub fn is_active(&self, mech_id: &str) -> bool {
         self.states
             .get(mech_id)
-            .map(|s| s.is_active)
+            .map(|s| self.is_effective(s))
             .unwrap_or(false)
     }
 
@@ -82,30 +320,69 @@ impl MechanicsProcessor {
     /// 如果聚能祝福有 4 层，每层 +4% 伤害：
     /// 返回 {"mod.inc.dmg.all": 0.16}
     pub fn calculate_base_effects(&self) -> HashMap<String, f64> {
-        let mut effects = HashMap::new();
+        self.calculate_base_effects_with_aura(1.0)
+    }
+
+    /// 计算所有激活机制的基础效果，受光环效果影响的机制按 `aura_effect` 缩放
+    ///
+    /// `aura_effect` 为光环效果乘数（1 + 光环效果提高）
+    ///
+    /// 同名 `mod.more.*` 在机制之间相乘，其余属性相加
+    pub fn calculate_base_effects_with_aura(&self, aura_effect: f64) -> HashMap<String, f64> {
+        self.base_effects_by_key(aura_effect)
+            .into_iter()
+            .map(|(key, values)| {
+                let total = if key.starts_with("mod.more.") {
+                    values.iter().map(|(_, value)| 1.0 + value).product::<f64>() - 1.0
+                } else {
+                    values.iter().map(|(_, value)| value).sum()
+                };
+                (key, total)
+            })
+            .collect()
+    }
+
+    /// 按属性键索引的基础效果：属性键 -> [(机制 ID, 总值)]
+    pub fn base_effects_by_key(&self, aura_effect: f64) -> HashMap<String, Vec<(String, f64)>> {
+        let mut index: HashMap<String, Vec<(String, f64)>> = HashMap::new();
+        for (mech_id, key, value) in self.base_effect_entries(aura_effect) {
+            index.entry(key).or_default().push((mech_id, value));
+        }
+        index
+    }
+
+    /// 按机制拆分的基础效果：(机制 ID, 属性键, 总值)
+    ///
+    /// 不同机制的同名属性不合并，`mod.more.*` 在各机制之间相乘而非相加；
+    /// 结果按机制 ID、属性键排序，保证聚合顺序稳定
+    pub fn base_effect_entries(&self, aura_effect: f64) -> Vec<(String, String, f64)> {
+        let mut entries = Vec::new();
 
         for (mech_id, state) in &self.states {
             // 跳过未激活或 0 层的机制
-            if !state.is_active || state.current_stacks == 0 {
+            let stacks = if self.is_effective(state) { self.state_stacks(state) } else { 0.0 };
+            if stacks <= 0.0 {
                 continue;
             }
 
             // 获取机制定义
             if let Some(def) = self.definitions.get(mech_id) {
+                let scale = if def.aura_scalable { aura_effect } else { 1.0 };
                 // 计算每层基础效果 × 层数
                 for (key, value_per_stack) in &def.base_effect_per_stack {
-                    let total_value = *value_per_stack * state.current_stacks as f64;
-                    *effects.entry(key.clone()).or_insert(0.0) += total_value;
+                    let total_value = *value_per_stack * stacks * scale;
+                    entries.push((mech_id.clone(), key.clone(), total_value));
                 }
             }
         }
 
-        effects
+        entries.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        entries
     }
 
     /// 计算带层数乘算的属性值
     /// 
-    /// 用于处理 `.per_xxx` 类型的属性
+    /// 用于处理 `.per_xxx` 类型的属性，按修正类型区分层数计算（见 [`stacked_value`]）
     /// 
     /// ## 参数
     /// 
@@ -117,39 +394,112 @@ impl MechanicsProcessor {
     /// - `Some((base_key, total_value))`: 如果机制激活且有层数
     /// - `None`: 如果机制未激活或层数为 0
     pub fn calculate_per_stack_value(&self, key: &str, value_per_stack: f64) -> Option<(String, f64)> {
+        // 封印魔力缩放键
+        if let Some((base_key, unit)) = parse_per_sealed_key(key) {
+            return self.calculate_per_sealed_value(base_key, unit, value_per_stack);
+        }
+
         // 提取机制 ID
         let mech_id = extract_mechanic_id(key)?;
         
         // 获取层数
-        let stacks = self.get_stacks(&mech_id);
-        if stacks == 0 {
+        let stacks = self.effective_stacks(&mech_id);
+        if stacks <= 0.0 {
             return None;
         }
 
-        // 计算实际值
-        let total_value = value_per_stack * stacks as f64;
-
         // 提取不带 per_xxx 后缀的基础键
         let base_key = key.replace(&format!(".per_{}", mech_id), "");
 
+        // 计算实际值
+        let total_value = stacked_value(&base_key, value_per_stack, stacks);
+
         Some((base_key, total_value))
     }
 
+    /// 封印魔力总百分比（所有生效的封印类机制层数之和，上限 100）
+    pub fn sealed_mana(&self) -> f64 {
+        let total: f64 = self
+            .states
+            .values()
+            .filter(|state| self.is_effective(state))
+            .filter(|state| {
+                self.definitions
+                    .get(&state.id)
+                    .is_some_and(|def| def.category == SEALED_CATEGORY)
+            })
+            .map(|state| self.state_stacks(state))
+            .sum();
+        total.min(100.0)
+    }
+
+    /// 按封印魔力计算 `.per_sealed_<N>` 属性：每封印 N% 魔力提供一次数值
+    fn calculate_per_sealed_value(&self, base_key: &str, unit: f64, value: f64) -> Option<(String, f64)> {
+        let times = (self.sealed_mana() / unit).floor();
+        if times <= 0.0 {
+            return None;
+        }
+        Some((base_key.to_string(), stacked_value(base_key, value, times)))
+    }
+
     /// 获取所有激活机制的层数映射
     /// 
     /// 用于注入 context_values
     pub fn get_all_stacks(&self) -> HashMap<String, f64> {
         self.states
             .iter()
-            .filter(|(_, s)| s.is_active)
-            .map(|(id, s)| (format!("{}_stacks", id), s.current_stacks as f64))
+            .filter(|(_, s)| self.is_effective(s))
+            .map(|(id, s)| (format!("{}_stacks", id), self.state_stacks(s)))
+            .collect()
+    }
+
+    /// 获取所有激活机制的层数（用于条件评估）
+    pub fn get_stack_counts(&self) -> HashMap<String, u32> {
+        self.states
+            .iter()
+            .filter(|(_, s)| self.is_effective(s))
+            .map(|(id, s)| (id.clone(), self.state_stacks(s).floor() as u32))
             .collect()
     }
 
+    /// 获取所有生效机制的标签（定义中的 `tag_key`，去重排序）
+    ///
+    /// 注入上下文标签后，"受祝福影响时" 等带标签需求的词缀自动匹配
+    pub fn active_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .states
+            .values()
+            .filter(|state| self.is_effective(state) && self.state_stacks(state) > 0.0)
+            .filter_map(|state| self.definitions.get(&state.id))
+            .filter(|def| !def.tag_key.is_empty())
+            .map(|def| def.tag_key.clone())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
     /// 获取所有机制 ID
     pub fn all_mechanic_ids(&self) -> impl Iterator<Item = &String> {
         self.definitions.keys()
     }
+
+    /// 获取所有已提供状态的机制 ID
+    pub fn state_ids(&self) -> impl Iterator<Item = &String> {
+        self.states.keys()
+    }
+}
+
+/// 按修正类型计算多层叠加后的总值
+///
+/// - `mod.more.*`：每层独立相乘，总值 = (1 + v)^层数 - 1
+/// - 其他（Inc / Base）：线性叠加，总值 = v × 层数
+pub fn stacked_value(base_key: &str, value_per_stack: f64, stacks: f64) -> f64 {
+    if base_key.starts_with("mod.more.") {
+        (1.0 + value_per_stack).powf(stacks) - 1.0
+    } else {
+        value_per_stack * stacks
+    }
 }
 
 /// 从属性键中提取机制 ID
@@ -159,16 +509,27 @@ impl MechanicsProcessor {
 /// - `"mod.inc.dmg.cold.per_focus_blessing"` -> `Some("focus_blessing")`
 /// - `"mod.inc.dmg.cold"` -> `None`
 pub fn extract_mechanic_id(key: &str) -> Option<String> {
-    if let Some(idx) = key.find(".per_") {
-        Some(key[idx + 5..].to_string())
-    } else {
-        None
-    }
+    key.find(".per_").map(|idx| key[idx + 5..].to_string())
+}
+
+/// 解析封印魔力缩放键
+///
+/// - `"mod.inc.dmg.spell.per_sealed_10"` -> `Some(("mod.inc.dmg.spell", 10.0))`（每封印 10% 魔力）
+/// - `"mod.inc.dmg.spell.per_sealed"` -> `Some(("mod.inc.dmg.spell", 1.0))`
+pub fn parse_per_sealed_key(key: &str) -> Option<(&str, f64)> {
+    let idx = key.find(".per_sealed")?;
+    let unit = match &key[idx + 11..] {
+        "" => 1.0,
+        rest => rest.strip_prefix('_')?.parse::<f64>().ok().filter(|u| *u > 0.0)?,
+    };
+    Some((&key[..idx], unit))
 }
 
 /// 检查属性键是否是 per_xxx 类型
+///
+/// `per_<数字>_<属性>` 为 PerStat 键（如 `per_10_intelligence`），不属于机制层数
 pub fn is_per_stack_stat(key: &str) -> bool {
-    key.contains(".per_")
+    extract_mechanic_id(key).is_some_and(|id| !id.starts_with(|c: char| c.is_ascii_digit()))
 }
 
 #[cfg(test)]
@@ -192,6 +553,13 @@ mod tests {
                 .into_iter()
                 .collect(),
                 description: String::new(),
+                aura_scalable: false,
+                generation_rate: None,
+                duration: None,
+                exclusive_group: None,
+                cost_per_use: None,
+                stacks_per_hit: None,
+                stacks_per_kill: None,
             },
             MechanicDefinition {
                 id: "tenacity_blessing".to_string(),
@@ -203,6 +571,13 @@ mod tests {
                     ("def.damage_taken_reduction".to_string(), 0.04),
                 ].into_iter().collect(),
                 description: String::new(),
+                aura_scalable: false,
+                generation_rate: None,
+                duration: None,
+                exclusive_group: None,
+                cost_per_use: None,
+                stacks_per_hit: None,
+                stacks_per_kill: None,
             },
             MechanicDefinition {
                 id: "agility_blessing".to_string(),
@@ -216,6 +591,13 @@ mod tests {
                     ("mod.inc.dmg.all".to_string(), 0.02),
                 ].into_iter().collect(),
                 description: String::new(),
+                aura_scalable: false,
+                generation_rate: None,
+                duration: None,
+                exclusive_group: None,
+                cost_per_use: None,
+                stacks_per_hit: None,
+                stacks_per_kill: None,
             },
             MechanicDefinition {
                 id: "fighting_will".to_string(),
@@ -228,6 +610,13 @@ mod tests {
                     ("crit.chance.spell".to_string(), 0.02),
                 ].into_iter().collect(),
                 description: "每点战意值提供2%攻击和法术暴击值".to_string(),
+                aura_scalable: false,
+                generation_rate: None,
+                duration: None,
+                exclusive_group: None,
+                cost_per_use: None,
+                stacks_per_hit: None,
+                stacks_per_kill: None,
             },
         ]
     }
@@ -404,7 +793,7 @@ mod tests {
 
     #[test]
     fn test_per_stack_more_cold() {
-        // 侵蚀旧律：每层聚能祝福 +19% 冰冷伤害（More），6 层 → 1.14 more
+        // 侵蚀旧律：每层聚能祝福额外 +19% 冰冷伤害（More），6 层逐层相乘
         let states = vec![
             MechanicState {
                 id: "focus_blessing".to_string(),
@@ -423,7 +812,7 @@ mod tests {
         assert!(result.is_some());
         let (base_key, total_value) = result.unwrap();
         assert_eq!(base_key, "mod.more.dmg.cold");
-        assert!((total_value - 1.14).abs() < 0.001); // 6 层 × 19% = 114%
+        assert!((total_value - (1.19_f64.powi(6) - 1.0)).abs() < 1e-9); // 1.19^6 - 1 ≈ 184%
     }
 
     #[test]
@@ -528,5 +917,333 @@ mod tests {
         assert!((effects.get("crit.chance.attack").copied().unwrap_or(0.0) - 0.50).abs() < 0.001);
         assert!((effects.get("crit.chance.spell").copied().unwrap_or(0.0) - 0.50).abs() < 0.001);
     }
-}
 
+    #[test]
+    fn test_stack_mode_realistic_average() {
+        // 聚能祝福每 2 秒获取 1 层、每层持续 6 秒：稳态平均 3 层
+        let mut definitions = create_test_definitions();
+        definitions[0].generation_rate = Some(0.5);
+        definitions[0].duration = Some(6.0);
+        let states = vec![
+            MechanicState {
+                id: "focus_blessing".to_string(),
+                current_stacks: 1,
+                max_stacks: 4,
+                is_active: true,
+            },
+            MechanicState {
+                id: "fighting_will".to_string(),
+                current_stacks: 25,
+                max_stacks: 100,
+                is_active: true,
+            },
+        ];
+
+        let manual = MechanicsProcessor::new(definitions.clone(), states.clone());
+        assert_eq!(manual.effective_stacks("focus_blessing"), 1.0);
+
+        let realistic = MechanicsProcessor::new(definitions.clone(), states.clone())
+            .with_stack_mode(StackMode::Realistic);
+        assert!((realistic.effective_stacks("focus_blessing") - 3.0).abs() < 1e-9);
+        // 未配置获取速率的机制回退为手动层数
+        assert_eq!(realistic.get_stacks("fighting_will"), 25);
+        let effects = realistic.calculate_base_effects();
+        assert!((effects["mod.more.dmg.all"] - 0.12).abs() < 1e-9);
+
+        // 平均层数不超过上限
+        definitions[0].duration = Some(20.0);
+        let capped = MechanicsProcessor::new(definitions.clone(), states.clone())
+            .with_stack_mode(StackMode::Realistic);
+        assert_eq!(capped.effective_stacks("focus_blessing"), 4.0);
+
+        let max = MechanicsProcessor::new(definitions, states).with_stack_mode(StackMode::Max);
+        assert_eq!(max.get_stacks("focus_blessing"), 4);
+        assert_eq!(max.get_stacks("fighting_will"), 100);
+    }
+
+    #[test]
+    fn test_max_stack_bonus_clamps_stacks() {
+        // 聚能祝福手动填 6 层，但上限只有 4 层
+        let definitions = create_test_definitions();
+        let states = vec![MechanicState {
+            id: "focus_blessing".to_string(),
+            current_stacks: 6,
+            max_stacks: 4,
+            is_active: true,
+        }];
+
+        let mut processor = MechanicsProcessor::new(definitions, states);
+        assert_eq!(processor.get_stacks("focus_blessing"), 4);
+
+        // 装备 +1 上限后可保留 5 层
+        let bonuses = [("focus_blessing".to_string(), 1.0)].into_iter().collect();
+        processor.apply_max_stack_bonuses(&bonuses);
+        assert_eq!(processor.get_stacks("focus_blessing"), 5);
+        let (_, value) = processor
+            .calculate_per_stack_value("mod.inc.dmg.cold.per_focus_blessing", 0.1)
+            .unwrap();
+        assert!((value - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_builtin_charge_definitions() {
+        // 未提供定义时使用内置狂乱球：3 层 × (4% More 伤害 + 4% 攻速)
+        let states = vec![
+            MechanicState {
+                id: "frenzy_charge".to_string(),
+                current_stacks: 3,
+                max_stacks: 3,
+                is_active: true,
+            },
+            MechanicState {
+                id: "power_charge".to_string(),
+                current_stacks: 2,
+                max_stacks: 3,
+                is_active: true,
+            },
+        ];
+
+        let processor = MechanicsProcessor::new(vec![], states);
+        assert_eq!(processor.get_definition("frenzy_charge").unwrap().category, "charge");
+        let effects = processor.calculate_base_effects();
+        assert!((effects["mod.more.dmg.all"] - 0.12).abs() < 1e-9);
+        assert!((effects["speed.attack"] - 0.12).abs() < 1e-9);
+        assert!((effects["mod.inc.crit.chance"] - 0.8).abs() < 1e-9);
+
+        // 内置表覆盖三种球类
+        for id in ["frenzy_charge", "power_charge", "endurance_charge"] {
+            assert_eq!(builtin_definitions()[id].default_max_stacks, 3);
+        }
+    }
+
+    #[test]
+    fn test_more_base_effects_multiply_across_mechanics() {
+        // 聚能祝福 4 层 × 4% More 与狂乱球 3 层 × 4% More：两机制之间相乘
+        let definitions = create_test_definitions();
+        let states = vec![
+            MechanicState {
+                id: "focus_blessing".to_string(),
+                current_stacks: 4,
+                max_stacks: 4,
+                is_active: true,
+            },
+            MechanicState {
+                id: "frenzy_charge".to_string(),
+                current_stacks: 3,
+                max_stacks: 3,
+                is_active: true,
+            },
+        ];
+
+        let processor = MechanicsProcessor::new(definitions, states);
+        let by_key = processor.base_effects_by_key(1.0);
+        assert_eq!(by_key["mod.more.dmg.all"].len(), 2);
+        let effects = processor.calculate_base_effects();
+        assert!((effects["mod.more.dmg.all"] - (1.16 * 1.12 - 1.0)).abs() < 1e-9);
+        // 速度为 Inc 类属性，相加
+        assert!((effects["speed.attack"] - 0.12).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stacked_value_by_kind() {
+        // Inc：线性叠加
+        assert!((stacked_value("mod.inc.dmg.cold", 0.1, 3.0) - 0.3).abs() < 1e-9);
+        // Base：线性叠加
+        assert!((stacked_value("crit.chance", 0.02, 3.0) - 0.06).abs() < 1e-9);
+        // More：逐层相乘
+        assert!((stacked_value("mod.more.dmg.all", 0.1, 3.0) - 0.331).abs() < 1e-9);
+        // 平均层数（小数）同样适用
+        assert!((stacked_value("mod.more.dmg.all", 0.1, 0.5) - (1.1_f64.sqrt() - 1.0)).abs() < 1e-9);
+        assert_eq!(stacked_value("mod.more.dmg.all", 0.1, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_exclusive_group_keeps_highest_stacks() {
+        let mut definitions = create_test_definitions();
+        for def in definitions.iter_mut().take(3) {
+            def.exclusive_group = Some("blessing".to_string());
+        }
+        let state = |id: &str, stacks: u32| MechanicState {
+            id: id.to_string(),
+            current_stacks: stacks,
+            max_stacks: 4,
+            is_active: true,
+        };
+        let states = vec![
+            state("focus_blessing", 2),
+            state("tenacity_blessing", 4),
+            state("agility_blessing", 4),
+        ];
+
+        // 坚韧与灵动同为 4 层，按 ID 字典序灵动生效
+        let processor = MechanicsProcessor::new(definitions.clone(), states.clone());
+        assert!(processor.is_active("agility_blessing"));
+        assert!(!processor.is_active("tenacity_blessing"));
+        assert_eq!(processor.get_stacks("focus_blessing"), 0);
+        assert_eq!(
+            processor.exclusive_conflicts(),
+            vec![
+                ("focus_blessing", "agility_blessing", "blessing"),
+                ("tenacity_blessing", "agility_blessing", "blessing"),
+            ]
+        );
+        let effects = processor.calculate_base_effects();
+        assert!(!effects.contains_key("def.damage_taken_reduction"));
+        assert!(!effects.contains_key("mod.more.dmg.all"));
+
+        // 聚能上限提高后层数最高，改由聚能生效
+        let mut processor = processor;
+        processor.apply_max_stack_bonuses(&[("focus_blessing".to_string(), 2.0)].into_iter().collect());
+        let processor = processor.with_stack_mode(StackMode::Max);
+        assert!(processor.is_active("focus_blessing"));
+        assert_eq!(processor.get_stacks("agility_blessing"), 0);
+
+        // 未分组的机制不受影响
+        let processor = MechanicsProcessor::new(definitions, vec![state("fighting_will", 3)]);
+        assert!(processor.exclusive_conflicts().is_empty());
+        assert_eq!(processor.get_stacks("fighting_will"), 3);
+    }
+
+    #[test]
+    fn test_grant_creates_active_state() {
+        let states = vec![MechanicState {
+            id: "focus_blessing".to_string(),
+            current_stacks: 3,
+            max_stacks: 4,
+            is_active: false,
+        }];
+        let mut processor = MechanicsProcessor::new(create_test_definitions(), states);
+
+        // 已有状态：激活并取较高层数
+        processor.grant("focus_blessing", 2);
+        assert!(processor.is_active("focus_blessing"));
+        assert_eq!(processor.get_stacks("focus_blessing"), 3);
+
+        // 无状态：按定义默认上限新建（内置狂乱球上限 3 层）
+        processor.grant("frenzy_charge", 5);
+        assert_eq!(processor.get_state("frenzy_charge").unwrap().max_stacks, 3);
+        assert_eq!(processor.get_stacks("frenzy_charge"), 3);
+    }
+
+    #[test]
+    fn test_disabled_mechanic_ignores_grant() {
+        let mut processor = MechanicsProcessor::new(create_test_definitions(), vec![])
+            .with_disabled(&["frenzy_charge".to_string()]);
+
+        // 被关闭的机制即使被授予也不生效
+        processor.grant("frenzy_charge", 3);
+        assert_eq!(processor.effective_stacks("frenzy_charge"), 0.0);
+        assert!(processor.active_tags().is_empty());
+        assert!(processor.get_all_stacks().is_empty());
+    }
+
+    #[test]
+    fn test_spender_resource_uptime() {
+        // 怒火：每秒获取 10 点，每次使用消耗 20 点，上限 100
+        let rage = MechanicDefinition {
+            id: "rage".to_string(),
+            category: "resource".to_string(),
+            default_max_stacks: 100,
+            generation_rate: Some(10.0),
+            cost_per_use: Some(20.0),
+            ..Default::default()
+        };
+        let states = vec![MechanicState {
+            id: "rage".to_string(),
+            current_stacks: 100,
+            max_stacks: 100,
+            is_active: true,
+        }];
+
+        // 每秒使用 1 次：可持续 50%，稳态按 50 点计算
+        let processor = MechanicsProcessor::new(vec![rage.clone()], states.clone())
+            .with_stack_mode(StackMode::Realistic)
+            .with_use_rate(1.0);
+        assert!((processor.resource_uptime("rage").unwrap() - 0.5).abs() < 1e-9);
+        assert!((processor.effective_stacks("rage") - 50.0).abs() < 1e-9);
+
+        // 获取足以支撑消耗时保持满层
+        let processor = MechanicsProcessor::new(vec![rage.clone()], states.clone())
+            .with_stack_mode(StackMode::Realistic)
+            .with_use_rate(0.25);
+        assert_eq!(processor.resource_uptime("rage"), Some(1.0));
+        assert_eq!(processor.effective_stacks("rage"), 100.0);
+
+        // 手动模式与未设置使用速率时不折算
+        let manual = MechanicsProcessor::new(vec![rage], states).with_use_rate(1.0);
+        assert_eq!(manual.effective_stacks("rage"), 100.0);
+        assert!(MechanicsProcessor::empty().resource_uptime("rage").is_none());
+    }
+
+    #[test]
+    fn test_sealed_mana_scaling() {
+        assert_eq!(
+            parse_per_sealed_key("mod.inc.dmg.spell.per_sealed_10"),
+            Some(("mod.inc.dmg.spell", 10.0))
+        );
+        assert_eq!(parse_per_sealed_key("mod.inc.dmg.spell.per_sealed"), Some(("mod.inc.dmg.spell", 1.0)));
+        assert_eq!(parse_per_sealed_key("mod.inc.dmg.spell.per_sealed_x"), None);
+        assert!(is_per_stack_stat("mod.inc.dmg.spell.per_sealed_10"));
+
+        // 两个封印来源：35% + 40%，每封印 10% 魔力 +3% 法术伤害 → 7 次
+        let sealed = |id: &str| MechanicDefinition {
+            id: id.to_string(),
+            category: SEALED_CATEGORY.to_string(),
+            default_max_stacks: 100,
+            ..Default::default()
+        };
+        let state = |id: &str, percent: u32| MechanicState {
+            id: id.to_string(),
+            current_stacks: percent,
+            max_stacks: 100,
+            is_active: true,
+        };
+        let processor = MechanicsProcessor::new(
+            vec![sealed("sealed_mana"), sealed("sealed_aura")],
+            vec![state("sealed_mana", 35), state("sealed_aura", 40)],
+        );
+        assert_eq!(processor.sealed_mana(), 75.0);
+        let (base_key, value) = processor
+            .calculate_per_stack_value("mod.inc.dmg.spell.per_sealed_10", 0.03)
+            .unwrap();
+        assert_eq!(base_key, "mod.inc.dmg.spell");
+        assert!((value - 0.21).abs() < 1e-9);
+
+        // 未封印时不生效
+        assert!(MechanicsProcessor::empty()
+            .calculate_per_stack_value("mod.inc.dmg.spell.per_sealed_10", 0.03)
+            .is_none());
+    }
+
+    #[test]
+    fn test_on_hit_stacks_from_event_rates() {
+        // 每次命中 1 层、每次击杀 2 层，持续 2 秒，上限 10 层
+        let buff = MechanicDefinition {
+            id: "onslaught".to_string(),
+            default_max_stacks: 10,
+            duration: Some(2.0),
+            stacks_per_hit: Some(1.0),
+            stacks_per_kill: Some(2.0),
+            ..Default::default()
+        };
+        assert!(buff.stacks_on_events());
+        let states = vec![MechanicState {
+            id: "onslaught".to_string(),
+            current_stacks: 0,
+            max_stacks: 10,
+            is_active: true,
+        }];
+
+        // 每秒 2 次命中、0.5 次击杀：(2 + 1) × 2 = 6 层
+        let processor = MechanicsProcessor::new(vec![buff.clone()], states.clone())
+            .with_stack_mode(StackMode::Realistic)
+            .with_event_rates(2.0, 0.5);
+        assert!((processor.effective_stacks("onslaught") - 6.0).abs() < 1e-9);
+
+        // 上限钳制
+        let processor = MechanicsProcessor::new(vec![buff], states)
+            .with_stack_mode(StackMode::Realistic)
+            .with_event_rates(10.0, 0.0);
+        assert_eq!(processor.effective_stacks("onslaught"), 10.0);
+    }
+}
@@ -0,0 +1,158 @@
+//! 英雄追忆模块
+//!
+//! 追忆装备在 `memory1`–`memory6` 槽位，聚合规则与普通装备不同
+//!
+//! ## 核心设计
+//!
+//! 1. 位置效果：追忆属性（Flag 除外）按所在槽位的倍率缩放，倍率由数据表 `src/data/memory_rules.json` 配置
+//! 2. 词缀叠加：同一词缀组在多个追忆上只保留一条（等级最高者，同级取位置缩放后数值较大者），见 `pipeline::sanitize_items`
+//! 3. 协同加成：已装备追忆数写入 `memory.count`，追忆属性可用 `.per_1_memory.count` 后缀按件数缩放
+
+use crate::stat_aliases::StatAliasTable;
+use crate::types::{ItemData, SlotType};
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 追忆规则 JSON 内容（编译时内嵌）
+pub(crate) const MEMORY_RULES_JSON: &str = include_str!("data/memory_rules.json");
+
+/// 已装备追忆数（协同加成的 PerStat 依赖属性）
+pub const MEMORY_COUNT: &str = "memory.count";
+
+/// 是否为追忆槽位
+pub fn is_memory_slot(slot: &SlotType) -> bool {
+    matches!(
+        slot,
+        SlotType::Memory1
+            | SlotType::Memory2
+            | SlotType::Memory3
+            | SlotType::Memory4
+            | SlotType::Memory5
+            | SlotType::Memory6
+    )
+}
+
+/// 单个追忆槽位的规则
+#[derive(Debug, Clone, Deserialize)]
+pub struct MemoryPosition {
+    /// 位置效果倍率
+    #[serde(default = "default_effect")]
+    pub effect: f64,
+}
+
+fn default_effect() -> f64 { 1.0 }
+
+/// 追忆规则表
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRules {
+    /// 槽位 ID -> 规则
+    positions: HashMap<String, MemoryPosition>,
+}
+
+impl MemoryRules {
+    /// 从 JSON 加载追忆规则
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut positions = HashMap::new();
+        for (slot, value) in raw {
+            // 跳过元数据
+            if slot.starts_with('_') {
+                continue;
+            }
+
+            let position: MemoryPosition = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse memory slot '{}': {}", slot, e))?;
+            if position.effect < 0.0 {
+                return Err(format!("Invalid effect for memory slot '{}': {}", slot, position.effect));
+            }
+            positions.insert(slot, position);
+        }
+
+        Ok(Self { positions })
+    }
+
+    /// 加载内嵌的默认追忆规则
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：位置效果均为 1）
+    pub fn load_default() -> Self {
+        load_embedded("memory_rules", Self::from_json(MEMORY_RULES_JSON))
+    }
+
+    /// 获取共享的默认追忆规则（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_MEMORY_RULES: OnceLock<MemoryRules> = OnceLock::new();
+        DEFAULT_MEMORY_RULES.get_or_init(Self::load_default)
+    }
+
+    /// 槽位的位置效果倍率（未登记时为 1）
+    pub fn effect(&self, slot: &SlotType) -> f64 {
+        self.positions.get(slot.id()).map_or(1.0, |position| position.effect)
+    }
+
+    /// 按位置效果缩放追忆属性（倍率为 1 时不复制）
+    ///
+    /// Flag 是开关而非数值，保持原值
+    pub fn apply_position<'a>(&self, memory: &'a ItemData) -> Cow<'a, ItemData> {
+        let effect = self.effect(&memory.slot);
+        if effect == 1.0 {
+            return Cow::Borrowed(memory);
+        }
+
+        let aliases = StatAliasTable::shared();
+        let mut scaled = memory.clone();
+        let scale = |stats: &mut HashMap<String, f64>| {
+            stats
+                .iter_mut()
+                .filter(|(key, _)| !aliases.resolve(key).unwrap_or(key).starts_with("flag."))
+                .for_each(|(_, value)| *value *= effect)
+        };
+        scale(&mut scaled.base_implicit_stats);
+        scale(&mut scaled.implicit_stats);
+        for affix in &mut scaled.affixes {
+            affix.value *= effect;
+            scale(&mut affix.stats);
+            affix.branches.iter_mut().for_each(|branch| scale(&mut branch.stats));
+        }
+        Cow::Owned(scaled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(slot: SlotType) -> ItemData {
+        let mut item: ItemData = serde_json::from_value(serde_json::json!({
+            "id": "memory", "base_type": "memory", "slot": "memory1",
+            "implicit_stats": { "mod.inc.dmg.fire": 0.1 },
+        }))
+        .unwrap();
+        item.slot = slot;
+        item
+    }
+
+    #[test]
+    fn test_position_effect_scales_stats() {
+        let rules = MemoryRules::load_default();
+        let first = memory(SlotType::Memory1);
+        assert!(matches!(rules.apply_position(&first), Cow::Borrowed(_)));
+
+        let mut last = memory(SlotType::Memory6);
+        last.implicit_stats.insert("flag.lucky".to_string(), 1.0);
+        let scaled = rules.apply_position(&last);
+        assert!((scaled.implicit_stats["mod.inc.dmg.fire"] - 0.12).abs() < 1e-9);
+        assert_eq!(scaled.implicit_stats["flag.lucky"], 1.0);
+    }
+
+    #[test]
+    fn test_memory_slots() {
+        assert!(is_memory_slot(&SlotType::Memory3));
+        assert!(!is_memory_slot(&SlotType::Ring1));
+        assert!(MemoryRules::from_json(r#"{ "memory1": { "effect": -1 } }"#).is_err());
+    }
+}
@@ -21,7 +21,11 @@
 //! ```
 
 use crate::condition_ast::{Condition, EvalContext};
-use serde::{Deserialize, Serialize};
+use crate::stat_key::StatKey;
+use crate::types::ConditionProvenance;
+use fixedbitset::FixedBitSet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use smallvec::SmallVec;
 use std::collections::HashMap;
 
 /// Modifier 类型
@@ -53,20 +57,99 @@ pub enum ModifierScope {
     Target,
 }
 
+/// More bucket 内的叠加方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketStacking {
+    /// 逐个相乘：(1 + a) * (1 + b)
+    #[default]
+    Multiplicative,
+    /// 先求和再乘：1 + a + b
+    Additive,
+}
+
+/// PerStat 倍数取整方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PerStatRounding {
+    /// 向下取整（"每 10 点"：95 点 → 9 次）
+    #[default]
+    Floor,
+    /// 不取整，按比例生效（95 点 → 9.5 次）
+    Proportional,
+}
+
 /// PerStat 配置（每 X 点属性提供效果）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PerStatConfig {
     /// 属性键
     pub stat: String,
     /// 每多少点
     pub per: f64,
+    /// 倍数取整方式
+    #[serde(default)]
+    pub rounding: PerStatRounding,
+    /// 倍数上限（如"最多 +50%"的每 1% 加成上限为 50 次），须为正数
+    #[serde(default, deserialize_with = "deserialize_max_multiplier")]
+    pub max_multiplier: Option<f64>,
+}
+
+impl PerStatConfig {
+    /// 创建配置（向下取整、无上限）
+    pub fn new(stat: &str, per: f64) -> Self {
+        Self { stat: stat.to_string(), per, rounding: PerStatRounding::Floor, max_multiplier: None }
+    }
+
+    /// 设置取整方式
+    pub fn with_rounding(mut self, rounding: PerStatRounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// 设置倍数上限（须为正数，数据来源的上限由键解析与反序列化校验）
+    pub fn with_max_multiplier(mut self, max: f64) -> Self {
+        debug_assert!(is_valid_max_multiplier(max), "倍数上限须为正数: {max}");
+        self.max_multiplier = Some(max);
+        self
+    }
+
+    /// 按属性值计算倍数（先取整后封顶）
+    pub fn multiplier(&self, stat_value: f64) -> f64 {
+        let ratio = stat_value / self.per;
+        let multiplier = match self.rounding {
+            PerStatRounding::Floor => ratio.floor(),
+            PerStatRounding::Proportional => ratio,
+        };
+        match self.max_multiplier {
+            Some(max) => multiplier.min(max),
+            None => multiplier,
+        }
+    }
+}
+
+/// 倍数上限是否有效（有限正数；零或负数会使效果恒为零或反向）
+pub fn is_valid_max_multiplier(max: f64) -> bool {
+    max.is_finite() && max > 0.0
+}
+
+/// 反序列化倍数上限并拒绝非正数
+fn deserialize_max_multiplier<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    match Option::<f64>::deserialize(deserializer)? {
+        Some(max) if !is_valid_max_multiplier(max) => {
+            Err(serde::de::Error::custom(format!("max_multiplier must be positive, got {max}")))
+        }
+        max => Ok(max),
+    }
 }
 
 /// 单个修正
+///
+/// 条件 AST 不参与序列化，只记录条件是否在计算时评估；反序列化后由 [`Modifier::rehydrate`] 按 `condition_str` 恢复
+/// （`ModDB` / `ModList` 反序列化时自动恢复）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Modifier {
-    /// 属性键（如 "dmg.fire", "crit.chance"）
-    pub key: String,
+    /// 属性键（如 "dmg.fire", "crit.chance"），驻留为 `StatKey`
+    pub key: StatKey,
     /// 修正类型
     pub kind: ModifierKind,
     /// 修正值
@@ -75,32 +158,61 @@ pub struct Modifier {
     pub source: String,
     /// Bucket ID（More 类型用于区分独立乘区）
     pub bucket_id: u32,
+    /// 同 bucket 内的叠加方式
+    #[serde(default)]
+    pub bucket_stacking: BucketStacking,
     /// 作用域
     pub scope: ModifierScope,
-    /// 条件表达式（AST）
-    #[serde(skip)]
+    /// 条件表达式（AST，计算时评估）
+    ///
+    /// 为 None 而 `condition_str` 存在时，条件已在聚合阶段评估通过，表达式仅用于溯源
+    #[serde(rename = "runtime_condition", with = "runtime_condition", default)]
     pub condition: Option<Condition>,
     /// 条件表达式字符串（用于序列化）
     pub condition_str: Option<String>,
     /// 标签要求（tag IDs）
     pub requirements: Vec<u32>,
+    /// 排除标签（tag IDs，上下文含任一标签时不生效，如"非召唤物伤害"）
+    #[serde(default)]
+    pub excluded: Vec<u32>,
+    /// 命中标签要求（tag IDs，须全部出现在伤害包的历史标签中）
+    pub hit_tags: Vec<u32>,
     /// PerStat 配置（可选）
     pub per_stat: Option<PerStatConfig>,
 }
 
+/// 条件 AST 的序列化形式：是否在计算时评估
+///
+/// 反序列化得到的占位条件始终不满足，须经 [`Modifier::rehydrate`] 恢复
+mod runtime_condition {
+    use crate::condition_ast::Condition;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(condition: &Option<Condition>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(condition.is_some())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Condition>, D::Error> {
+        Ok(bool::deserialize(deserializer)?.then_some(Condition::False))
+    }
+}
+
 impl Modifier {
     /// 创建基础值修正
     pub fn base(key: &str, value: f64, source: &str) -> Self {
         Self {
-            key: key.to_string(),
+            key: StatKey::new(key),
             kind: ModifierKind::Base,
             value,
             source: source.to_string(),
             bucket_id: 0,
+            bucket_stacking: BucketStacking::Multiplicative,
             scope: ModifierScope::Global,
             condition: None,
             condition_str: None,
             requirements: vec![],
+            excluded: vec![],
+            hit_tags: vec![],
             per_stat: None,
         }
     }
@@ -108,15 +220,18 @@ impl Modifier {
     /// 创建 Increased 修正
     pub fn inc(key: &str, value: f64, source: &str) -> Self {
         Self {
-            key: key.to_string(),
+            key: StatKey::new(key),
             kind: ModifierKind::Increased,
             value,
             source: source.to_string(),
             bucket_id: 0,
+            bucket_stacking: BucketStacking::Multiplicative,
             scope: ModifierScope::Global,
             condition: None,
             condition_str: None,
             requirements: vec![],
+            excluded: vec![],
+            hit_tags: vec![],
             per_stat: None,
         }
     }
@@ -124,15 +239,18 @@ impl Modifier {
     /// 创建 More 修正
     pub fn more(key: &str, value: f64, source: &str) -> Self {
         Self {
-            key: key.to_string(),
+            key: StatKey::new(key),
             kind: ModifierKind::More,
             value,
             source: source.to_string(),
             bucket_id: 0,
+            bucket_stacking: BucketStacking::Multiplicative,
             scope: ModifierScope::Global,
             condition: None,
             condition_str: None,
             requirements: vec![],
+            excluded: vec![],
+            hit_tags: vec![],
             per_stat: None,
         }
     }
@@ -140,15 +258,18 @@ impl Modifier {
     /// 创建带 bucket 的 More 修正
     pub fn more_with_bucket(key: &str, value: f64, bucket_id: u32, source: &str) -> Self {
         Self {
-            key: key.to_string(),
+            key: StatKey::new(key),
             kind: ModifierKind::More,
             value,
             source: source.to_string(),
             bucket_id,
+            bucket_stacking: BucketStacking::Multiplicative,
             scope: ModifierScope::Global,
             condition: None,
             condition_str: None,
             requirements: vec![],
+            excluded: vec![],
+            hit_tags: vec![],
             per_stat: None,
         }
     }
@@ -156,15 +277,18 @@ impl Modifier {
     /// 创建 Flag 修正
     pub fn flag(key: &str, source: &str) -> Self {
         Self {
-            key: key.to_string(),
+            key: StatKey::new(key),
             kind: ModifierKind::Flag,
             value: 1.0,
             source: source.to_string(),
             bucket_id: 0,
+            bucket_stacking: BucketStacking::Multiplicative,
             scope: ModifierScope::Global,
             condition: None,
             condition_str: None,
             requirements: vec![],
+            excluded: vec![],
+            hit_tags: vec![],
             per_stat: None,
         }
     }
@@ -172,26 +296,42 @@ impl Modifier {
     /// 创建 Override 修正
     pub fn override_value(key: &str, value: f64, source: &str) -> Self {
         Self {
-            key: key.to_string(),
+            key: StatKey::new(key),
             kind: ModifierKind::Override,
             value,
             source: source.to_string(),
             bucket_id: 0,
+            bucket_stacking: BucketStacking::Multiplicative,
             scope: ModifierScope::Global,
             condition: None,
             condition_str: None,
             requirements: vec![],
+            excluded: vec![],
+            hit_tags: vec![],
             per_stat: None,
         }
     }
 
     /// 设置条件（字符串形式，自动解析为 AST）
+    ///
+    /// 无法解析的条件视为始终不满足（解析错误由输入校验报告）
     pub fn with_condition(mut self, condition_str: &str) -> Self {
         self.condition_str = Some(condition_str.to_string());
-        self.condition = Condition::parse(condition_str).ok();
+        self.condition = Some(Condition::parse(condition_str).unwrap_or(Condition::False));
         self
     }
 
+    /// 按 `condition_str` 恢复反序列化后的条件 AST
+    ///
+    /// 只恢复计算时评估的条件，仅用于溯源的表达式保持不评估；
+    /// 无法解析或没有表达式（`with_condition_ast`）的条件与 [`Modifier::with_condition`] 一致视为始终不满足
+    pub fn rehydrate(&mut self) {
+        if self.condition.is_some() {
+            let parsed = self.condition_str.as_deref().and_then(|expr| Condition::parse(expr).ok());
+            self.condition = Some(parsed.unwrap_or(Condition::False));
+        }
+    }
+
     /// 设置条件（AST 形式）
     pub fn with_condition_ast(mut self, condition: Condition) -> Self {
         self.condition = Some(condition);
@@ -204,18 +344,40 @@ impl Modifier {
         self
     }
 
+    /// 设置排除标签
+    pub fn with_excluded(mut self, excluded: Vec<u32>) -> Self {
+        self.excluded = excluded;
+        self
+    }
+
+    /// 设置命中标签要求
+    pub fn with_hit_tags(mut self, hit_tags: Vec<u32>) -> Self {
+        self.hit_tags = hit_tags;
+        self
+    }
+
+    /// 设置 More bucket 及其叠加方式
+    pub fn with_bucket(mut self, bucket_id: u32, stacking: BucketStacking) -> Self {
+        self.bucket_id = bucket_id;
+        self.bucket_stacking = stacking;
+        self
+    }
+
     /// 设置作用域
     pub fn with_scope(mut self, scope: ModifierScope) -> Self {
         self.scope = scope;
         self
     }
 
-    /// 设置 PerStat 配置
+    /// 设置 PerStat 配置（向下取整、无上限）
     pub fn with_per_stat(mut self, stat: &str, per: f64) -> Self {
-        self.per_stat = Some(PerStatConfig {
-            stat: stat.to_string(),
-            per,
-        });
+        self.per_stat = Some(PerStatConfig::new(stat, per));
+        self
+    }
+
+    /// 设置完整的 PerStat 配置（取整方式与倍数上限）
+    pub fn with_per_stat_config(mut self, config: PerStatConfig) -> Self {
+        self.per_stat = Some(config);
         self
     }
 
@@ -227,6 +389,40 @@ impl Modifier {
         }
     }
 
+    /// 条件评估溯源（无条件修正返回 None）
+    ///
+    /// 仅有表达式的修正（条件已在聚合阶段评估）按表达式重新解析后溯源
+    pub fn condition_provenance(&self, ctx: &EvalContext) -> Option<ConditionProvenance> {
+        let parsed;
+        let condition = match &self.condition {
+            Some(condition) => condition,
+            None => {
+                parsed = Condition::parse(self.condition_str.as_deref()?).ok()?;
+                &parsed
+            }
+        };
+        let (passed, decided_by) = condition.explain(ctx);
+        Some(ConditionProvenance {
+            condition: self.condition_str.clone().unwrap_or_default(),
+            passed,
+            decided_by,
+        })
+    }
+
+    /// 检查标签是否匹配
+    ///
+    /// - `hit_set`: 伤害包的历史标签（已展开继承）
+    /// - `active_set`: 当前上下文标签
+    pub fn matches_tags(&self, hit_set: &FixedBitSet, active_set: &FixedBitSet) -> bool {
+        self.hit_tags.iter().all(|&id| hit_set.contains(id as usize)) && self.matches_context(active_set)
+    }
+
+    /// 检查上下文标签：标签要求全部满足，且不含任一排除标签
+    pub fn matches_context(&self, active_set: &FixedBitSet) -> bool {
+        self.requirements.iter().all(|&id| active_set.contains(id as usize))
+            && !self.excluded.iter().any(|&id| active_set.contains(id as usize))
+    }
+
     /// 计算实际生效值（考虑 PerStat）
     pub fn effective_value(&self, ctx: &EvalContext) -> f64 {
         let base_value = self.value;
@@ -234,8 +430,7 @@ impl Modifier {
         match &self.per_stat {
             Some(ps) => {
                 let stat_value = ctx.values.get(&ps.stat).copied().unwrap_or(0.0);
-                let multiplier = (stat_value / ps.per).floor();
-                base_value * multiplier
+                base_value * ps.multiplier(stat_value)
             }
             None => base_value,
         }
@@ -296,43 +491,40 @@ pub trait ModifierStore {
 
     /// 计算 More 乘积（按 bucket 分组）
     fn product_more(&self, key: &str) -> f64 {
-        let mods = self.get_by_kind(key, ModifierKind::More);
-        if mods.is_empty() {
-            return 1.0;
-        }
-
-        // 按 bucket_id 分组
-        let mut buckets: HashMap<u32, f64> = HashMap::new();
-        for m in mods {
-            let entry = buckets.entry(m.bucket_id).or_insert(1.0);
-            *entry *= 1.0 + m.value;
-        }
-
-        // 所有 bucket 相乘
-        buckets.values().product()
+        combine_more(
+            self.get_by_kind(key, ModifierKind::More)
+                .into_iter()
+                .map(|m| (m, m.value)),
+        )
     }
 
     /// 计算 More 乘积（带条件评估，按 bucket 分组）
     fn product_more_with_ctx(&self, key: &str, ctx: &EvalContext) -> f64 {
-        let mods: Vec<_> = self
-            .get_by_kind(key, ModifierKind::More)
-            .into_iter()
-            .filter(|m| m.check_condition(ctx))
-            .collect();
-
-        if mods.is_empty() {
-            return 1.0;
-        }
+        combine_more(
+            self.get_by_kind(key, ModifierKind::More)
+                .into_iter()
+                .filter(|m| m.check_condition(ctx))
+                .map(|m| (m, m.effective_value(ctx))),
+        )
+    }
 
-        // 按 bucket_id 分组
-        let mut buckets: HashMap<u32, f64> = HashMap::new();
-        for m in mods {
-            let entry = buckets.entry(m.bucket_id).or_insert(1.0);
-            *entry *= 1.0 + m.effective_value(ctx);
-        }
+    /// 计算标签匹配的 Increased 值总和
+    fn sum_inc_for_tags(&self, key: &str, hit_set: &FixedBitSet, active_set: &FixedBitSet) -> f64 {
+        self.get_by_kind(key, ModifierKind::Increased)
+            .iter()
+            .filter(|m| m.matches_tags(hit_set, active_set))
+            .map(|m| m.value)
+            .sum()
+    }
 
-        // 所有 bucket 相乘
-        buckets.values().product()
+    /// 计算标签匹配的 More 乘积（按 bucket 分组）
+    fn product_more_for_tags(&self, key: &str, hit_set: &FixedBitSet, active_set: &FixedBitSet) -> f64 {
+        combine_more(
+            self.get_by_kind(key, ModifierKind::More)
+                .into_iter()
+                .filter(|m| m.matches_tags(hit_set, active_set))
+                .map(|m| (m, m.value)),
+        )
     }
 
     /// 检查 Flag 是否存在
@@ -358,11 +550,18 @@ pub trait ModifierStore {
     fn get_override_with_ctx(&self, key: &str, ctx: &EvalContext) -> Option<f64> {
         self.get_by_kind(key, ModifierKind::Override)
             .iter()
-            .filter(|m| m.check_condition(ctx))
-            .last()
+            .rfind(|m| m.check_condition(ctx))
             .map(|m| m.value)
     }
 
+    /// 获取 Base 值，存在 Override 时以 Override 为准（带条件评估）
+    ///
+    /// 用于直接作为最终值使用的属性（抗性、命中值等）
+    fn base_or_override_with_ctx(&self, key: &str, ctx: &EvalContext) -> f64 {
+        self.get_override_with_ctx(key, ctx)
+            .unwrap_or_else(|| self.sum_base_with_ctx(key, ctx))
+    }
+
     /// 计算最终值：override > base * (1 + inc) * more
     fn calculate_final(&self, key: &str) -> f64 {
         if let Some(override_val) = self.get_override(key) {
@@ -392,6 +591,33 @@ pub trait ModifierStore {
     /// 获取所有唯一的 key
     fn keys(&self) -> Vec<String>;
 
+    /// 获取匹配通配模式的 key（按字典序，语法见 [`key_matches`]）
+    fn query_keys(&self, pattern: &str) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .keys()
+            .into_iter()
+            .filter(|k| key_matches(pattern, k))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// 按通配模式查询修正，如 `query("dmg.*")`
+    fn query(&self, pattern: &str) -> Vec<&Modifier> {
+        self.query_keys(pattern)
+            .iter()
+            .flat_map(|k| self.get(k))
+            .collect()
+    }
+
+    /// 按通配模式和类型查询修正
+    fn query_by_kind(&self, pattern: &str, kind: ModifierKind) -> Vec<&Modifier> {
+        self.query_keys(pattern)
+            .iter()
+            .flat_map(|k| self.get_by_kind(k, kind))
+            .collect()
+    }
+
     /// 获取所有修正（用于溯源）
     fn all_modifiers(&self) -> Vec<&Modifier>;
 
@@ -423,6 +649,56 @@ pub trait ModifierStore {
     }
 }
 
+/// 判断属性键是否匹配通配模式
+///
+/// 按 `.` 分段匹配：`*` 匹配恰好一段，`**` 匹配任意多段（含零段）。
+/// 例：`dmg.*` 匹配 `dmg.fire` 但不匹配 `dmg.fire.min`；`dmg.**` 两者都匹配
+pub fn key_matches(pattern: &str, key: &str) -> bool {
+    fn match_segments(pattern: &[&str], key: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => key.is_empty(),
+            Some((&"**", rest)) => (0..=key.len()).any(|i| match_segments(rest, &key[i..])),
+            Some((&seg, rest)) => match key.split_first() {
+                Some((&k, key_rest)) => (seg == "*" || seg == k) && match_segments(rest, key_rest),
+                None => false,
+            },
+        }
+    }
+
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let key: Vec<&str> = key.split('.').collect();
+    match_segments(&pattern, &key)
+}
+
+/// 单个 More 值对应的乘数
+///
+/// 负值即 "less"（如 -0.9 表示伤害只剩 10%），低于 -100% 时钳制为 0，
+/// 避免两个负乘数相乘后反而变成正值
+pub fn more_factor(value: f64) -> f64 {
+    (1.0 + value).max(0.0)
+}
+
+/// 按 bucket 合并 More 修正，输入为 (修正, 生效值)
+///
+/// 同 bucket 内按叠加方式合并，不同 bucket 之间相乘；无修正时为 1.0。
+/// 叠加型 bucket 先求和再钳制，相乘型 bucket 逐个钳制（见 [`more_factor`]）
+pub fn combine_more<'a>(mods: impl IntoIterator<Item = (&'a Modifier, f64)>) -> f64 {
+    // bucket_id -> (叠加型求和, 相乘型乘积)
+    let mut buckets: HashMap<u32, (f64, f64)> = HashMap::new();
+    for (m, value) in mods {
+        let entry = buckets.entry(m.bucket_id).or_insert((0.0, 1.0));
+        match m.bucket_stacking {
+            BucketStacking::Additive => entry.0 += value,
+            BucketStacking::Multiplicative => entry.1 *= more_factor(value),
+        }
+    }
+
+    buckets
+        .values()
+        .map(|(sum, product)| more_factor(*sum) * product)
+        .product()
+}
+
 /// 修正来源信息（用于 UI 展示）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModifierSource {
@@ -432,13 +708,42 @@ pub struct ModifierSource {
     pub bucket_id: u32,
 }
 
+/// 单个 key 的修正分桶
+///
+/// 绝大多数 key 只有 1～2 条修正，内联存储可避免逐 key 的堆分配
+pub type ModBucket = SmallVec<[Modifier; 2]>;
+
 /// ModDB - 按 key 分桶的 HashMap 存储
 ///
-/// 适合大规模数据，查询 O(1)
+/// 适合大规模数据，查询 O(1)；序列化为扁平的修正列表
 #[derive(Debug, Clone, Default)]
 pub struct ModDB {
     /// 按 key 分桶存储
-    data: HashMap<String, Vec<Modifier>>,
+    data: HashMap<StatKey, ModBucket>,
+}
+
+/// 反序列化修正列表并恢复条件 AST
+fn deserialize_rehydrated<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Modifier>, D::Error> {
+    let mut modifiers = Vec::<Modifier>::deserialize(deserializer)?;
+    modifiers.iter_mut().for_each(Modifier::rehydrate);
+    Ok(modifiers)
+}
+
+/// 按属性键排序序列化（同一属性键内保持添加顺序），同一 ModDB 的序列化结果稳定
+impl Serialize for ModDB {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut buckets: Vec<(&StatKey, &ModBucket)> = self.data.iter().collect();
+        buckets.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+        serializer.collect_seq(buckets.into_iter().flat_map(|(_, mods)| mods))
+    }
+}
+
+impl<'de> Deserialize<'de> for ModDB {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut db = ModDB::new();
+        db.add_all(deserialize_rehydrated(deserializer)?);
+        Ok(db)
+    }
 }
 
 impl ModDB {
@@ -450,16 +755,73 @@ impl ModDB {
     /// 合并另一个 ModDB
     pub fn merge(&mut self, other: &ModDB) {
         for (key, mods) in &other.data {
-            let entry = self.data.entry(key.clone()).or_default();
+            let entry = self.data.entry(*key).or_default();
             entry.extend(mods.iter().cloned());
         }
     }
 
+    /// 第二遍：将 PerStat 修正引用的属性总值写入评估上下文
+    ///
+    /// 总值为 0 时保留上下文中已有的手动输入值
+    pub fn resolve_per_stat_values(&self, ctx: &mut EvalContext) {
+        let mut stats: Vec<&str> = self
+            .data
+            .values()
+            .flatten()
+            .filter_map(|m| m.per_stat.as_ref().map(|ps| ps.stat.as_str()))
+            .collect();
+        stats.sort_unstable();
+        stats.dedup();
+
+        let totals: Vec<(String, f64)> = stats
+            .into_iter()
+            .map(|stat| (stat.to_string(), self.calculate_final_with_ctx(stat, ctx)))
+            .collect();
+
+        for (stat, total) in totals {
+            if total != 0.0 {
+                ctx.values.insert(stat, total);
+            }
+        }
+    }
+
+    /// 将条件中 `stat("...")` 引用的属性最终值写入评估上下文
+    ///
+    /// 按写入前的上下文求值：依赖 `stat(...)` 条件的修正不计入被引用属性，避免循环引用
+    pub fn resolve_stat_references(&self, ctx: &mut EvalContext) {
+        let mut stats: Vec<&str> = self
+            .data
+            .values()
+            .flatten()
+            .filter_map(|m| m.condition.as_ref())
+            .flat_map(|condition| condition.stat_references())
+            .collect();
+        if stats.is_empty() {
+            return;
+        }
+        stats.sort_unstable();
+        stats.dedup();
+
+        let totals: Vec<(String, f64)> = stats
+            .into_iter()
+            .map(|stat| (stat.to_string(), self.calculate_final_with_ctx(stat, ctx)))
+            .collect();
+        ctx.stats.extend(totals);
+    }
+
     /// 获取修正数量
     pub fn len(&self) -> usize {
         self.data.values().map(|v| v.len()).sum()
     }
 
+    /// 驻留表已满后出现的属性键（按字典序）
+    pub fn uninterned_keys(&self) -> Vec<&'static str> {
+        let mut keys: Vec<&'static str> =
+            self.data.keys().filter(|key| !key.is_interned()).map(|key| key.as_str()).collect();
+        keys.sort_unstable();
+        keys
+    }
+
     /// 是否为空
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
@@ -469,27 +831,28 @@ impl ModDB {
 impl ModifierStore for ModDB {
     fn add(&mut self, modifier: Modifier) {
         self.data
-            .entry(modifier.key.clone())
+            .entry(modifier.key)
             .or_default()
             .push(modifier);
     }
 
     fn get(&self, key: &str) -> Vec<&Modifier> {
-        self.data
-            .get(key)
+        // 未出现过的键必然没有修正，无需分配
+        StatKey::lookup(key)
+            .and_then(|k| self.data.get(&k))
             .map(|v| v.iter().collect())
             .unwrap_or_default()
     }
 
     fn get_by_kind(&self, key: &str, kind: ModifierKind) -> Vec<&Modifier> {
-        self.data
-            .get(key)
+        StatKey::lookup(key)
+            .and_then(|k| self.data.get(&k))
             .map(|v| v.iter().filter(|m| m.kind == kind).collect())
             .unwrap_or_default()
     }
 
     fn keys(&self) -> Vec<String> {
-        self.data.keys().cloned().collect()
+        self.data.keys().map(|k| k.to_string()).collect()
     }
 
     fn all_modifiers(&self) -> Vec<&Modifier> {
@@ -500,9 +863,11 @@ impl ModifierStore for ModDB {
 /// ModList - 扁平数组存储
 ///
 /// 适合临时计算和小规模数据
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct ModList {
     /// 扁平存储
+    #[serde(deserialize_with = "deserialize_rehydrated")]
     data: Vec<Modifier>,
 }
 
@@ -554,7 +919,7 @@ impl ModifierStore for ModList {
     }
 
     fn keys(&self) -> Vec<String> {
-        let mut keys: Vec<String> = self.data.iter().map(|m| m.key.clone()).collect();
+        let mut keys: Vec<String> = self.data.iter().map(|m| m.key.to_string()).collect();
         keys.sort();
         keys.dedup();
         keys
@@ -604,6 +969,69 @@ mod tests {
         assert!((more - 1.716).abs() < 0.001);
     }
 
+    #[test]
+    fn test_mod_db_additive_bucket() {
+        let mut db = ModDB::new();
+
+        // 叠加型 bucket：1 + 0.2 + 0.3 = 1.5
+        db.add(Modifier::more("dmg.all", 0.2, "光环1").with_bucket(7, BucketStacking::Additive));
+        db.add(Modifier::more("dmg.all", 0.3, "光环2").with_bucket(7, BucketStacking::Additive));
+        // 独立 bucket 相乘
+        db.add(Modifier::more("dmg.all", 0.1, "辅助").with_bucket(100, BucketStacking::Multiplicative));
+
+        let more = db.product_more("dmg.all");
+        assert!((more - 1.65).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_key_matches_wildcards() {
+        assert!(key_matches("dmg.*", "dmg.fire"));
+        assert!(!key_matches("dmg.*", "dmg.fire.min"));
+        assert!(!key_matches("dmg.*", "dmg"));
+        assert!(key_matches("dmg.**", "dmg.fire.min"));
+        assert!(key_matches("dmg.*.min", "dmg.phys.min"));
+        assert!(key_matches("crit.chance", "crit.chance"));
+        assert!(!key_matches("crit.chance", "crit.dmg"));
+    }
+
+    #[test]
+    fn test_mod_db_query() {
+        let mut db = ModDB::new();
+        db.add(Modifier::inc("dmg.fire", 0.3, "装备"));
+        db.add(Modifier::inc("dmg.all", 0.2, "天赋"));
+        db.add(Modifier::base("dmg.fire.min", 10.0, "装备"));
+        db.add(Modifier::inc("speed.cast", 0.1, "装备"));
+
+        assert_eq!(db.query_keys("dmg.*"), vec!["dmg.all", "dmg.fire"]);
+        assert_eq!(db.query("dmg.**").len(), 3);
+        assert_eq!(db.query_by_kind("dmg.**", ModifierKind::Base).len(), 1);
+
+        let mut list = ModList::new();
+        list.add(Modifier::inc("dmg.cold", 0.1, "装备"));
+        list.add(Modifier::inc("speed.attack", 0.1, "装备"));
+        assert_eq!(list.query("dmg.*").len(), 1);
+    }
+
+    #[test]
+    fn test_mod_db_less_clamped_at_zero() {
+        let mut db = ModDB::new();
+
+        // less 与 more 相乘：1.5 × 0.1 = 0.15
+        db.add(Modifier::more("dmg.min", 0.5, "辅助").with_bucket(100, BucketStacking::Multiplicative));
+        db.add(Modifier::more("dmg.min", -0.9, "最小伤害降低").with_bucket(101, BucketStacking::Multiplicative));
+        assert!((db.product_more("dmg.min") - 0.15).abs() < 0.001);
+
+        // 低于 -100% 钳制为 0，两个负乘数不会相乘为正
+        db.add(Modifier::more("dmg.all", -1.5, "诅咒"));
+        db.add(Modifier::more("dmg.all", -1.2, "诅咒2").with_bucket(5, BucketStacking::Multiplicative));
+        assert_eq!(db.product_more("dmg.all"), 0.0);
+
+        // 叠加型 bucket 先求和再钳制：1 + 0.5 - 2.0 → 0
+        db.add(Modifier::more("dmg.fire", 0.5, "光环").with_bucket(7, BucketStacking::Additive));
+        db.add(Modifier::more("dmg.fire", -2.0, "光环2").with_bucket(7, BucketStacking::Additive));
+        assert_eq!(db.product_more("dmg.fire"), 0.0);
+    }
+
     #[test]
     fn test_mod_db_flag() {
         let mut db = ModDB::new();
@@ -683,6 +1111,32 @@ mod tests {
         assert!((db1.sum_inc("dmg.cold") - 0.2).abs() < 0.001);
     }
 
+    #[test]
+    fn test_mod_db_hit_tags() {
+        let mut db = ModDB::new();
+
+        // 无标签要求
+        db.add(Modifier::inc("dmg.all", 0.1, "全伤"));
+        // 要求伤害包带火焰标签 (21)
+        db.add(Modifier::inc("dmg.fire", 0.3, "火伤").with_hit_tags(vec![21]));
+        // 要求上下文带法术标签 (110)
+        db.add(Modifier::more("dmg.spell", 0.2, "法伤").with_requirements(vec![110]));
+
+        let mut fire_packet = FixedBitSet::with_capacity(128);
+        fire_packet.insert(21);
+        let phys_packet = FixedBitSet::with_capacity(128);
+        let mut spell_ctx = FixedBitSet::with_capacity(128);
+        spell_ctx.insert(110);
+        let empty_ctx = FixedBitSet::with_capacity(128);
+
+        assert!((db.sum_inc_for_tags("dmg.all", &phys_packet, &empty_ctx) - 0.1).abs() < 0.001);
+        assert!((db.sum_inc_for_tags("dmg.fire", &fire_packet, &empty_ctx) - 0.3).abs() < 0.001);
+        assert!(db.sum_inc_for_tags("dmg.fire", &phys_packet, &empty_ctx).abs() < 0.001);
+
+        assert!((db.product_more_for_tags("dmg.spell", &phys_packet, &spell_ctx) - 1.2).abs() < 0.001);
+        assert!((db.product_more_for_tags("dmg.spell", &phys_packet, &empty_ctx) - 1.0).abs() < 0.001);
+    }
+
     #[test]
     fn test_mod_db_with_condition() {
         let mut db = ModDB::new();
@@ -712,6 +1166,10 @@ mod tests {
             .with_flag("is_moving", true)
             .with_value("fighting_will", 100.0);
         assert!((db.sum_inc_with_ctx("dmg.fire", &ctx_both) - 1.0).abs() < 0.001); // 0.2 + 0.3 + 0.5
+
+        // 无法解析的条件始终不满足
+        db.add(Modifier::inc("dmg.fire", 1.0, "拼写错误").with_condition("is_moving and fighting_will >= 50"));
+        assert!((db.sum_inc_with_ctx("dmg.fire", &ctx_both) - 1.0).abs() < 0.001);
     }
 
     #[test]
@@ -757,5 +1215,97 @@ mod tests {
             .with_mechanic("fighting_will", 100);
         assert!((db.product_more_with_ctx("dmg.cold", &ctx_both) - 1.32).abs() < 0.001); // 1.2 * 1.1
     }
-}
 
+    #[test]
+    fn test_deserialize_rehydrates_conditions() {
+        let modifier = Modifier::inc("dmg.fire", 0.3, "移动增伤").with_condition("is_moving && fighting_will >= 50");
+        let json = serde_json::to_string(&modifier).unwrap();
+        let mut restored: Modifier = serde_json::from_str(&json).unwrap();
+        // 恢复前占位条件始终不满足
+        assert!(!restored.check_condition(&EvalContext::new().with_flag("is_moving", true).with_value("fighting_will", 60.0)));
+        restored.rehydrate();
+        assert!(restored.condition.is_some());
+
+        let ctx = EvalContext::new().with_flag("is_moving", true).with_value("fighting_will", 60.0);
+        assert!(restored.check_condition(&ctx));
+        assert!(!restored.check_condition(&EvalContext::new()));
+
+        // 无法解析的条件恢复后同样始终不满足
+        let mut broken: Modifier = serde_json::from_str(&json.replace("&&", "and")).unwrap();
+        broken.rehydrate();
+        assert!(!broken.check_condition(&ctx));
+    }
+
+    #[test]
+    fn test_mod_db_round_trip() {
+        let mut db = ModDB::new();
+        db.add(Modifier::inc("dmg.fire", 0.2, "装备"));
+        db.add(Modifier::inc("dmg.fire", 0.3, "移动增伤").with_condition("is_moving == true"));
+        db.add(Modifier::more("dmg.cold", 0.2, "祝福加成").with_condition("mechanic_active(\"focus_blessing\")"));
+
+        let restored: ModDB = serde_json::from_str(&serde_json::to_string(&db).unwrap()).unwrap();
+        assert_eq!(restored.len(), 3);
+
+        let ctx = EvalContext::new().with_flag("is_moving", true).with_mechanic("focus_blessing", 3);
+        for ctx in [EvalContext::new(), ctx] {
+            assert_eq!(restored.sum_inc_with_ctx("dmg.fire", &ctx), db.sum_inc_with_ctx("dmg.fire", &ctx));
+            assert_eq!(restored.product_more_with_ctx("dmg.cold", &ctx), db.product_more_with_ctx("dmg.cold", &ctx));
+        }
+
+        // 聚合阶段已评估的条件只用于溯源，恢复后不重新评估
+        let mut provenance = ModDB::new();
+        provenance.add(Modifier { condition_str: Some("is_moving == true".to_string()), ..Modifier::inc("dmg.fire", 0.3, "移动增伤") });
+        let restored: ModDB = serde_json::from_str(&serde_json::to_string(&provenance).unwrap()).unwrap();
+        assert_eq!(restored.sum_inc_with_ctx("dmg.fire", &EvalContext::new()), 0.3);
+        assert_eq!(restored.all_modifiers()[0].condition_str.as_deref(), Some("is_moving == true"));
+
+        // 按属性键排序，序列化结果与属性键的添加顺序无关
+        let mut reordered = ModDB::new();
+        reordered.add(Modifier::more("dmg.cold", 0.2, "祝福加成").with_condition("mechanic_active(\"focus_blessing\")"));
+        reordered.add(Modifier::inc("dmg.fire", 0.2, "装备"));
+        reordered.add(Modifier::inc("dmg.fire", 0.3, "移动增伤").with_condition("is_moving == true"));
+        let json = serde_json::to_string(&db).unwrap();
+        assert_eq!(json, serde_json::to_string(&reordered).unwrap());
+        assert!(json.find("dmg.cold").unwrap() < json.find("dmg.fire").unwrap());
+
+        let list = ModList::from_vec(vec![Modifier::inc("dmg.fire", 0.3, "移动增伤").with_condition("is_moving")]);
+        let restored: ModList = serde_json::from_str(&serde_json::to_string(&list).unwrap()).unwrap();
+        assert_eq!(restored.sum_inc_with_ctx("dmg.fire", &EvalContext::new()), 0.0);
+    }
+
+    #[test]
+    fn test_per_stat_rounding_and_cap() {
+        let ctx = EvalContext::new().with_value("dexterity", 95.0);
+
+        // 默认向下取整：95 敏捷 → 9 次
+        let floor = Modifier::inc("dmg.fire", 0.01, "每点敏捷").with_per_stat("dexterity", 10.0);
+        assert!((floor.effective_value(&ctx) - 0.09).abs() < 1e-9);
+
+        // 不取整：按比例 9.5 次
+        let exact = Modifier::inc("dmg.fire", 0.01, "每点敏捷")
+            .with_per_stat_config(PerStatConfig::new("dexterity", 10.0).with_rounding(PerStatRounding::Proportional));
+        assert!((exact.effective_value(&ctx) - 0.095).abs() < 1e-9);
+
+        // 倍数上限在取整后生效
+        let capped = PerStatConfig::new("dexterity", 10.0).with_max_multiplier(5.0);
+        assert_eq!(capped.multiplier(95.0), 5.0);
+        assert_eq!(capped.multiplier(42.0), 4.0);
+        let capped = capped.with_rounding(PerStatRounding::Proportional).with_max_multiplier(4.5);
+        assert_eq!(capped.multiplier(42.0), 4.2);
+        assert_eq!(capped.multiplier(95.0), 4.5);
+
+        // 旧数据缺省字段时按向下取整、无上限反序列化
+        let config: PerStatConfig = serde_json::from_str(r#"{"stat": "dexterity", "per": 10.0}"#).unwrap();
+        assert_eq!((config.rounding, config.max_multiplier), (PerStatRounding::Floor, None));
+
+        // 按比例生效序列化为 proportional；零或负数上限拒绝反序列化
+        let config: PerStatConfig =
+            serde_json::from_str(r#"{"stat": "dexterity", "per": 10.0, "rounding": "proportional", "max_multiplier": 5}"#)
+                .unwrap();
+        assert_eq!(config, PerStatConfig::new("dexterity", 10.0).with_rounding(PerStatRounding::Proportional).with_max_multiplier(5.0));
+        for max in ["0", "-5"] {
+            let json = format!(r#"{{"stat": "dexterity", "per": 10.0, "max_multiplier": {max}}}"#);
+            assert!(serde_json::from_str::<PerStatConfig>(&json).is_err(), "{max}");
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! More 乘区 bucket 注册表
+//!
+//! 将来源分类（装备、技能、辅助技能、机制等）映射到 More 修正的 bucket，
+//! 取代散落在聚合代码中的魔法数字。
+//!
+//! ## 核心设计
+//!
+//! 1. bucket 定义由数据表 `src/data/more_buckets.json` 配置
+//! 2. 同 bucket 内按 `stacking` 合并：`additive` 先求和再乘，`multiplicative` 逐个相乘
+//! 3. 不同 bucket 之间始终相乘
+//! 4. `perInstance` 的分类（如辅助技能）每个实例独占一个 bucket
+
+use crate::modifiers::BucketStacking;
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// bucket 注册表 JSON 内容（编译时内嵌）
+pub(crate) const MORE_BUCKETS_JSON: &str = include_str!("data/more_buckets.json");
+
+/// 每个 perInstance 分类可占用的 bucket 数量
+pub const PER_INSTANCE_SPAN: u32 = 100;
+
+/// 单个 bucket 定义
+#[derive(Debug, Clone, Deserialize)]
+pub struct MoreBucket {
+    /// bucket ID（perInstance 时为起始 ID）
+    pub id: u32,
+    /// 同 bucket 内的叠加方式
+    #[serde(default)]
+    pub stacking: BucketStacking,
+    /// 是否每个来源实例独占 bucket
+    #[serde(default, rename = "perInstance")]
+    pub per_instance: bool,
+    /// 显示名称
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+/// More bucket 注册表
+#[derive(Debug, Clone, Default)]
+pub struct MoreBucketRegistry {
+    /// 来源分类 -> bucket 定义
+    buckets: HashMap<String, MoreBucket>,
+}
+
+impl MoreBucketRegistry {
+    /// 从 JSON 加载 bucket 注册表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut buckets: HashMap<String, MoreBucket> = HashMap::new();
+        for (key, value) in raw {
+            // 跳过元数据
+            if key.starts_with('_') {
+                continue;
+            }
+
+            let bucket: MoreBucket = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse bucket '{}': {}", key, e))?;
+            buckets.insert(key, bucket);
+        }
+
+        // bucket ID 区间不得重叠，否则不同分类会被错误地合并
+        let mut ranges: Vec<(u32, u32, &str)> = buckets
+            .iter()
+            .map(|(key, b)| {
+                let span = if b.per_instance { PER_INSTANCE_SPAN } else { 1 };
+                (b.id, b.id + span, key.as_str())
+            })
+            .collect();
+        ranges.sort_unstable();
+        for pair in ranges.windows(2) {
+            if pair[1].0 < pair[0].1 {
+                return Err(format!("Bucket '{}' overlaps '{}'", pair[1].2, pair[0].2));
+            }
+        }
+
+        Ok(Self { buckets })
+    }
+
+    /// 加载内嵌的默认 bucket 注册表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：所有 More 修正落入 bucket 0，逐个相乘）
+    pub fn load_default() -> Self {
+        load_embedded("more_buckets", Self::from_json(MORE_BUCKETS_JSON))
+    }
+
+    /// 获取来源分类的 bucket 定义
+    pub fn get(&self, category: &str) -> Option<&MoreBucket> {
+        self.buckets.get(category)
+    }
+
+    /// 解析来源实例对应的 bucket：(bucket ID, 叠加方式)
+    ///
+    /// 未登记的分类回退到 bucket 0（相乘）
+    pub fn resolve(&self, category: &str, instance: usize) -> (u32, BucketStacking) {
+        match self.buckets.get(category) {
+            Some(b) if b.per_instance => {
+                let offset = (instance as u32).min(PER_INSTANCE_SPAN - 1);
+                (b.id + offset, b.stacking)
+            }
+            Some(b) => (b.id, b.stacking),
+            None => (0, BucketStacking::Multiplicative),
+        }
+    }
+
+    /// 定位 bucket ID 所属的来源分类：(分类, perInstance 的实例序号（从 1 开始）)
+    pub fn locate(&self, bucket_id: u32) -> Option<(&str, Option<u32>)> {
+        self.buckets.iter().find_map(|(category, b)| {
+            if b.per_instance && (b.id..b.id + PER_INSTANCE_SPAN).contains(&bucket_id) {
+                Some((category.as_str(), Some(bucket_id - b.id + 1)))
+            } else if b.id == bucket_id {
+                Some((category.as_str(), None))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// 获取 bucket ID 的显示名称（用于乘区明细）
+    ///
+    /// perInstance 的 bucket 附带实例序号，如 `辅助技能#2`
+    pub fn display_name(&self, bucket_id: u32) -> String {
+        for b in self.buckets.values() {
+            if b.per_instance && (b.id..b.id + PER_INSTANCE_SPAN).contains(&bucket_id) {
+                return format!("{}#{}", b.display_name, bucket_id - b.id + 1);
+            }
+            if b.id == bucket_id {
+                return b.display_name.clone();
+            }
+        }
+        format!("bucket {}", bucket_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_registry_resolves_categories() {
+        let registry = MoreBucketRegistry::load_default();
+        assert_eq!(registry.resolve("item", 0).0, 0);
+        assert_eq!(registry.resolve("support", 0).0, 100);
+        assert_eq!(registry.resolve("support", 2).0, 102);
+        assert_eq!(registry.display_name(101), "辅助技能#2");
+        assert_eq!(registry.locate(101), Some(("support", Some(2))));
+        assert_eq!(registry.locate(4), Some(("global_override", None)));
+        assert_eq!(registry.locate(50), None);
+        assert_eq!(registry.resolve("unknown", 3), (0, BucketStacking::Multiplicative));
+    }
+
+    #[test]
+    fn test_overlapping_buckets_rejected() {
+        let result = MoreBucketRegistry::from_json(
+            r#"{
+                "support": { "id": 100, "perInstance": true, "displayName": "辅助" },
+                "curse": { "id": 150, "displayName": "诅咒" }
+            }"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_stacking_parsed_from_data() {
+        let registry = MoreBucketRegistry::from_json(
+            r#"{ "aura": { "id": 7, "stacking": "additive", "displayName": "光环" } }"#,
+        )
+        .unwrap();
+        assert_eq!(registry.resolve("aura", 0), (7, BucketStacking::Additive));
+    }
+}
@@ -0,0 +1,156 @@
+//! 数据驱动乘区模块
+//!
+//! 在内置乘区（增伤、More、暴击等）之外声明独立乘区，新机制无需修改计算代码
+//!
+//! ## 核心设计
+//!
+//! 1. 乘区定义由数据表 `src/data/multiplier_zones.json` 配置
+//! 2. 乘区按属性键模式（语法见 `modifiers::key_matches`）收集 ModDB 中的修正
+//! 3. 合并规则：`sum` 为 1 + Σ，`product` 为 Π(1 + v)，`max` 为 1 + max(v)
+//! 4. 全部乘区相乘后作用于每个伤害包（增伤/More 之后、暴击之前；命中与持续伤害、异常伤害包一致），并在乘区明细中单独列出
+//! 5. 乘区键不在单位表中，录入值不做单位换算
+
+use crate::modifiers::key_matches;
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 乘区定义 JSON 内容（编译时内嵌）
+pub(crate) const MULTIPLIER_ZONES_JSON: &str = include_str!("data/multiplier_zones.json");
+
+/// 乘区内修正的合并规则
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneCombine {
+    /// 1 + Σ
+    Sum,
+    /// Π(1 + v)
+    #[default]
+    Product,
+    /// 1 + max(v)
+    Max,
+}
+
+/// 单个乘区定义
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneDefinition {
+    /// 属性键模式
+    pub keys: Vec<String>,
+    /// 合并规则
+    #[serde(default)]
+    pub combine: ZoneCombine,
+    /// 乘区显示文本的本地化键
+    pub display_key: String,
+}
+
+impl ZoneDefinition {
+    /// 属性键是否属于该乘区
+    pub fn matches(&self, key: &str) -> bool {
+        self.keys.iter().any(|pattern| key_matches(pattern, key))
+    }
+
+    /// 按合并规则计算乘区倍率（无修正时为 1）
+    pub fn combine(&self, values: impl IntoIterator<Item = f64>) -> f64 {
+        let mut values = values.into_iter().peekable();
+        if values.peek().is_none() {
+            return 1.0;
+        }
+        match self.combine {
+            ZoneCombine::Sum => 1.0 + values.sum::<f64>(),
+            ZoneCombine::Product => values.map(|v| 1.0 + v).product(),
+            ZoneCombine::Max => 1.0 + values.fold(f64::NEG_INFINITY, f64::max),
+        }
+        .max(0.0)
+    }
+}
+
+/// 数据驱动乘区表
+#[derive(Debug, Clone, Default)]
+pub struct MultiplierZoneTable {
+    /// (乘区 ID, 定义)，按 ID 排序
+    zones: Vec<(String, ZoneDefinition)>,
+}
+
+impl MultiplierZoneTable {
+    /// 从 JSON 加载乘区表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut zones = Vec::new();
+        for (id, value) in raw {
+            // 跳过元数据
+            if id.starts_with('_') {
+                continue;
+            }
+
+            let zone: ZoneDefinition = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse multiplier zone '{}': {}", id, e))?;
+            if zone.keys.is_empty() {
+                return Err(format!("Multiplier zone '{}' has no keys", id));
+            }
+            zones.push((id, zone));
+        }
+        zones.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(Self { zones })
+    }
+
+    /// 加载内嵌的默认乘区表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不计算额外乘区）
+    pub fn load_default() -> Self {
+        load_embedded("multiplier_zones", Self::from_json(MULTIPLIER_ZONES_JSON))
+    }
+
+    /// 获取共享的默认乘区表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_MULTIPLIER_ZONES: OnceLock<MultiplierZoneTable> = OnceLock::new();
+        DEFAULT_MULTIPLIER_ZONES.get_or_init(Self::load_default)
+    }
+
+    /// 全部乘区（按 ID 排序）
+    pub fn zones(&self) -> impl Iterator<Item = (&str, &ZoneDefinition)> {
+        self.zones.iter().map(|(id, zone)| (id.as_str(), zone))
+    }
+
+    /// 属性键是否属于任一乘区
+    pub fn is_zone_key(&self, key: &str) -> bool {
+        self.zones.iter().any(|(_, zone)| zone.matches(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_rules() {
+        let table = MultiplierZoneTable::from_json(
+            r#"{
+                "a": { "keys": ["zone.a.*"], "combine": "sum", "display_key": "zone.a" },
+                "b": { "keys": ["zone.b"], "display_key": "zone.b" },
+                "c": { "keys": ["zone.c.*"], "combine": "max", "display_key": "zone.c" }
+            }"#,
+        )
+        .unwrap();
+        let zones: Vec<(&str, &ZoneDefinition)> = table.zones().collect();
+        assert_eq!(zones.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+
+        assert!((zones[0].1.combine([0.2, 0.3]) - 1.5).abs() < 1e-9);
+        assert!((zones[1].1.combine([0.2, 0.5]) - 1.8).abs() < 1e-9);
+        assert!((zones[2].1.combine([0.2, 0.5]) - 1.5).abs() < 1e-9);
+        assert_eq!(zones[2].1.combine([]), 1.0);
+
+        assert!(table.is_zone_key("zone.a.x"));
+        assert!(!table.is_zone_key("zone.a"));
+    }
+
+    #[test]
+    fn test_default_table() {
+        let table = MultiplierZoneTable::load_default();
+        assert!(table.is_zone_key("zone.final_damage"));
+        assert!(MultiplierZoneTable::from_json(r#"{ "x": { "keys": [], "display_key": "x" } }"#).is_err());
+    }
+}
@@ -18,18 +18,42 @@
 //! - `calculate_from_prepared()`: 从 PreparedContext 计算最终结果
 //! - `calculate_diff_incremental()`: 增量计算预览差异
 
-use crate::conversion::{
-    extract_conversion_rules, extract_extra_as_rules, ConversionEngine, DamageType, DamageWithTags,
+use crate::conversion::{ConversionEngine, ConversionPlan, DamagePacket, DamageType, DamageWithTags};
+use crate::attributes::{AttributeTable, ATTRIBUTE_KEYS, DEXTERITY, INTELLIGENCE, STRENGTH};
+use crate::condition_ast::{nearest_identifier, Condition, EvalContext, ValueExpr};
+use crate::character::CharacterTable;
+use crate::defense::DefenseStats;
+use crate::i18n::{message_params, MessageParams};
+use crate::items_meta::ItemsMetaTable;
+use crate::mechanics::{builtin_definitions, MechanicsProcessor};
+use crate::memories::{is_memory_slot, MemoryRules};
+use crate::modifiers::{
+    combine_more, more_factor, ModDB, Modifier, ModifierKind, ModifierScope, ModifierStore,
 };
-use crate::mechanics::MechanicsProcessor;
-use crate::modifiers::ModDB;
-use crate::stats::{StatAggregator, StatPool};
-use crate::tags::{ContextTags, TagRegistry};
+use crate::more_buckets::MoreBucketRegistry;
+use crate::multiplier_zones::{MultiplierZoneTable, ZoneDefinition};
+use crate::skills::SkillRegistry;
+use crate::slots::SlotRegistry;
+use crate::stat_aliases::StatAliasTable;
+use crate::stat_hooks::{StatHookTable, HOOK_SOURCE_VAR, HOOK_TRIGGER_VAR};
+use crate::stat_units::StatUnitTable;
+use crate::stats::{is_known_stat_key, is_strict_stat_key, StatAggregator, DAMAGE_MOD_SCOPES, DAMAGE_STRETCH_SCOPES, SKILL_LEVEL_KEYS};
+use crate::tags::{weapon_tags, ContextTags, TagDiagnostic, TagRegistry};
+use crate::utils::fnv1a_64;
 use crate::types::*;
+use fixedbitset::FixedBitSet;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
 use thiserror::Error;
 
+/// 单次计算的追踪条目预留容量（覆盖全部阶段，避免逐条扩容）
+const TRACE_CAPACITY: usize = 16;
+
+/// 计算引擎版本（crate 版本，写入每次计算结果）
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// 计算错误类型
 #[derive(Debug, Error)]
 pub enum CalculationError {
@@ -46,28 +70,42 @@ pub enum CalculationError {
 /// 包含聚合阶段产出的所有中间数据，可被复用于增量计算。
 #[derive(Debug, Clone)]
 pub struct PreparedContext {
-    /// 标签注册表
-    pub registry: TagRegistry,
-    /// 属性池
-    pub stat_pool: StatPool,
+    /// 标签注册表（与其他计算共享）
+    pub registry: Arc<TagRegistry>,
     /// 结构化修正存储
     pub mod_db: ModDB,
+    /// 词缀条件未满足而未生效的修正（用于来源溯源）
+    pub inactive_modifiers: Vec<Modifier>,
     /// 基础伤害（按伤害类型分组）
     pub base_damages: HashMap<DamageType, (f64, f64)>,
     /// 技能数据快照
     pub skill_snapshot: SkillSnapshot,
+    /// 由主手/副手武器推导的武器标签
+    pub weapon_tags: Vec<String>,
+    /// 生效机制的标签（如 `Mech_Blessing`）
+    pub mechanic_tags: Vec<String>,
     /// 机制状态快照（层数）
     pub mechanic_stacks: HashMap<String, f64>,
-    /// 上下文标志
-    pub context_flags: HashMap<String, bool>,
-    /// 上下文数值
-    pub context_values: HashMap<String, f64>,
-    /// 转化规则
-    pub conversion_rules: Vec<crate::conversion::ConversionRule>,
-    /// Extra-as 规则
-    pub extra_as_rules: Vec<crate::conversion::ExtraAsRule>,
+    /// 条件评估上下文（上下文标志/数值 + 技能标签 + 机制层数，不含 PerStat 总值）
+    pub condition_ctx: EvalContext,
+    /// 聚合阶段的上下文激活标签（技能、武器、辅助注入/条件、上下文派生与机制标签，不含阶段性标签）
+    pub active_tags: Vec<String>,
+    /// 预编译的转化计划（额外获得 + 转化）
+    pub conversion_plan: Arc<ConversionPlan>,
+    /// 增伤总和下限策略
+    pub increased_floor: IncreasedFloor,
     /// 调试追踪
     pub trace: Vec<TraceEntry>,
+    /// 诊断（聚合阶段产生，计算阶段的诊断在结果中追加）
+    pub warnings: Vec<Diagnostic>,
+    /// 各把武器的面板 DPS
+    pub weapon_summaries: Vec<WeaponSummary>,
+    /// 多部分技能合并计算时各部分的上下文（部分 ID、权重、上下文）；为空时按自身计算
+    pub part_contexts: Vec<(String, f64, PreparedContext)>,
+    /// 输入包含两阶段计算无法复现的模型（见 `requires_full_calculation`），`calculate_from_prepared` 拒绝计算
+    pub requires_full_calculation: bool,
+    /// 输入摘要（不含目标配置，计算阶段与目标配置合成 `input_hash`）
+    pub input_digest: u64,
 }
 
 /// 技能数据快照（用于缓存）
@@ -76,8 +114,11 @@ pub struct SkillSnapshot {
     pub id: String,
     pub is_attack: bool,
     pub base_time: f64,
+    pub cooldown: Option<f64>,
     pub effectiveness: f64,
     pub tags: Vec<String>,
+    pub phase_tags: Vec<PhaseTag>,
+    pub mana_cost: f64,
 }
 
 impl PreparedContext {
@@ -86,166 +127,762 @@ impl PreparedContext {
     /// 用于悬停预览场景：复用 base 的 PreparedContext，仅合并 preview item 产生的 modifiers
     pub fn merge_modifiers(&mut self, other_mod_db: &ModDB) {
         self.mod_db.merge(other_mod_db);
-        // 重新计算 stat_pool（从合并后的 mod_db 重建）
-        self.rebuild_stat_pool_from_mod_db();
+        for (_, _, part) in &mut self.part_contexts {
+            part.merge_modifiers(other_mod_db);
+        }
+    }
+
+    /// 构建条件评估上下文（按当前 ModDB 解析 PerStat 总值）
+    pub fn eval_context(&self) -> EvalContext {
+        let mut eval_ctx = self.condition_ctx.clone();
+        self.mod_db.resolve_per_stat_values(&mut eval_ctx);
+        self.mod_db.resolve_stat_references(&mut eval_ctx);
+        eval_ctx
+    }
+}
+
+/// 主计算函数
+///
+/// 结果按 `input.output_options` 格式化（舍入、百分比刻度）
+pub fn calculate_dps(input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
+    let mut output = calculate_raw(input)?;
+    input.output_options.apply(&mut output);
+    Ok(output)
+}
+
+/// 未格式化的完整计算（供差值分析等内部流程使用，避免舍入误差累积）
+pub(crate) fn calculate_raw(input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
+    let (resolved, evictions) = resolve_preferred_slots(input)?;
+    let mut output = calculate_unsustained(&resolved)?;
+    output.warnings.splice(0..0, evictions);
+    if let Some(model) = &input.sustain_model {
+        apply_sustain(&resolved, model, &mut output)?;
+    }
+    output.input_hash = input_hash(input);
+    Ok(output)
+}
+
+/// 计算输入指纹（16 位十六进制）
+///
+/// 对规范化序列化（JSON 对象键排序）取 FNV-1a，跨平台、跨编译版本稳定，可用于复现问题反馈中的计算
+pub fn input_hash(input: &CalculatorInput) -> String {
+    combine_input_hash(input_digest(input), &input.target_config)
+}
+
+/// 不含目标配置的输入摘要（仅替换目标配置的计算共享同一摘要）
+pub(crate) fn input_digest(input: &CalculatorInput) -> u64 {
+    let mut value = serde_json::to_value(input).unwrap_or_default();
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("target_config");
+    }
+    fnv1a_64(&serde_json::to_vec(&value).unwrap_or_default())
+}
+
+/// 输入摘要与目标配置合成输入指纹
+fn combine_input_hash(digest: u64, target_config: &TargetConfig) -> String {
+    let target = serde_json::to_value(target_config).unwrap_or_default();
+    let mut bytes = digest.to_le_bytes().to_vec();
+    bytes.extend(serde_json::to_vec(&target).unwrap_or_default());
+    format!("{:016x}", fnv1a_64(&bytes))
+}
+
+/// 按技能部分 / 命中叠层分派计算（持续 DPS 等于理论 DPS）
+fn calculate_unsustained(input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
+    if !input.active_skill.parts.is_empty() {
+        return calculate_skill_parts(input);
+    }
+    if has_event_stacking(input) {
+        return calculate_with_event_stacks(input);
+    }
+    calculate_with_rates(input, EventRates::default())
+}
+
+/// 持续输出折算
+///
+/// 增益按稳态层数重算理论 DPS（不高于理论 DPS），再乘以技能循环占比与魔力可持续比例；
+/// 技能部分合并模式下速率与魔力消耗取合并结果
+fn apply_sustain(
+    input: &CalculatorInput,
+    model: &SustainModel,
+    output: &mut CalculatorOutput,
+) -> Result<(), CalculationError> {
+    let buffed_dps = if model.realistic_buffs && input.stack_mode != StackMode::Realistic {
+        let mut realistic = input.clone();
+        realistic.stack_mode = StackMode::Realistic;
+        calculate_unsustained(&realistic)?.dps_theoretical.min(output.dps_theoretical)
+    } else {
+        output.dps_theoretical
+    };
+    let uptime = input.recent_model.as_ref().map_or(1.0, |m| m.rotation_uptime.clamp(0.0, 1.0));
+    let mana_factor = model.mana_factor(output.mana_cost, output.rate * uptime);
+
+    output.dps_sustained = buffed_dps * uptime * mana_factor;
+    output.debug_trace.push(TraceEntry::localized(
+        "Sustain",
+        "trace.sustain",
+        message_params(&[
+            ("uptime", format!("{:.1}", uptime * 100.0)),
+            ("mana", format!("{:.1}", mana_factor * 100.0)),
+        ]),
+        [
+            ("buffed_dps".to_string(), buffed_dps),
+            ("rotation_uptime".to_string(), uptime),
+            ("mana_factor".to_string(), mana_factor),
+            ("dps_sustained".to_string(), output.dps_sustained),
+        ]
+        .into_iter()
+        .collect(),
+    ));
+    Ok(())
+}
+
+/// 机制 DPS 贡献分析
+///
+/// 对每个生效的机制（含装备/技能授予的）分别关闭后重算，
+/// 按 DPS 贡献从高到低排序；层数取聚合阶段结算值（不含命中叠层迭代）
+pub fn mechanic_contributions(input: &CalculatorInput) -> Result<Vec<MechanicContribution>, CalculationError> {
+    // 槽位冲突只取舍一次，各次重算沿用同一套装备
+    let resolved = resolve_preferred_slots(input)?.0;
+    let input = resolved.as_ref();
+    let base = calculate_raw(input)?;
+    let prepared = prepare_context(input)?;
+    let mut active: Vec<(String, f64)> = prepared
+        .mechanic_stacks
+        .iter()
+        .filter_map(|(key, stacks)| Some((key.strip_suffix("_stacks")?.to_string(), *stacks)))
+        .collect();
+    active.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut contributions = Vec::with_capacity(active.len());
+    for (mechanic_id, stacks) in active {
+        let mut without_input = input.clone();
+        without_input.disabled_mechanics.push(mechanic_id.clone());
+        let without = calculate_raw(&without_input)?;
+        let dps_delta = base.dps_theoretical - without.dps_theoretical;
+        contributions.push(MechanicContribution {
+            mechanic_id,
+            stacks,
+            dps_without: without.dps_theoretical,
+            dps_delta,
+            dps_delta_percent: if without.dps_theoretical > 0.0 {
+                dps_delta / without.dps_theoretical * 100.0
+            } else {
+                0.0
+            },
+        });
     }
+    contributions.sort_by(|a, b| b.dps_delta.total_cmp(&a.dps_delta));
+    Ok(contributions)
+}
 
-    /// 从 ModDB 重建 StatPool
-    fn rebuild_stat_pool_from_mod_db(&mut self) {
-        use crate::modifiers::{ModifierKind, ModifierStore};
+/// 假设分析最多同时组合的开关数（场景数 = 2^开关数）
+pub const MAX_TOGGLES: usize = 8;
 
-        // 清空现有 StatPool
-        self.stat_pool = StatPool::new();
+/// 假设分析：开关矩阵
+///
+/// 对每个开关组合（含全不翻转的当前配置）分别计算，按组合位序返回：
+/// 第 i 个场景翻转 `toggles` 中二进制位 i 为 1 的开关。
+/// 标志取反；机制在当前生效时关闭，否则开启（无状态或 0 层时按满层）；覆盖分组启用状态取反（分组须已存在）
+pub fn evaluate_toggles(input: &CalculatorInput, toggles: &[Toggle]) -> Result<Vec<ToggleScenario>, CalculationError> {
+    // 槽位冲突只取舍一次，各次重算沿用同一套装备
+    let resolved = resolve_preferred_slots(input)?.0;
+    let input = resolved.as_ref();
+    if toggles.len() > MAX_TOGGLES {
+        return Err(CalculationError::InvalidInput(format!(
+            "too many toggles: {} (max {})",
+            toggles.len(),
+            MAX_TOGGLES
+        )));
+    }
+    if let Some(toggle) = toggles
+        .iter()
+        .filter(|t| t.kind == ToggleKind::OverrideGroup)
+        .find(|t| !input.override_groups.iter().any(|g| g.name == t.key))
+    {
+        return Err(CalculationError::InvalidInput(format!("unknown override group '{}'", toggle.key)));
+    }
+    let active_mechanics = if toggles.iter().any(|t| t.kind == ToggleKind::Mechanic) {
+        prepare_context(input)?.mechanic_stacks
+    } else {
+        HashMap::new()
+    };
 
-        // 遍历 ModDB 中的所有修正，重建 StatPool
-        for modifier in self.mod_db.all_modifiers() {
-            match modifier.kind {
-                ModifierKind::Base => {
-                    self.stat_pool.add_base(&modifier.key, modifier.value);
+    let mut scenarios = Vec::with_capacity(1 << toggles.len());
+    let mut base_dps = 0.0;
+    for mask in 0..(1usize << toggles.len()) {
+        let mut scenario_input = input.clone();
+        let flipped: Vec<Toggle> = toggles
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| mask & (1 << bit) != 0)
+            .map(|(_, toggle)| toggle.clone())
+            .collect();
+        for toggle in &flipped {
+            match toggle.kind {
+                ToggleKind::Flag => {
+                    let current = input.context_flags.get(&toggle.key).copied().unwrap_or(false);
+                    scenario_input.context_flags.insert(toggle.key.clone(), !current);
                 }
-                ModifierKind::Increased => {
-                    self.stat_pool.add_increased(&modifier.key, modifier.value);
+                ToggleKind::Mechanic => {
+                    let active = active_mechanics.contains_key(&format!("{}_stacks", toggle.key));
+                    flip_mechanic(&mut scenario_input, &toggle.key, active);
                 }
-                ModifierKind::More => {
-                    self.stat_pool.add_more(
-                        &modifier.key,
-                        modifier.value,
-                        modifier.bucket_id,
-                        &modifier.source,
-                    );
+                ToggleKind::OverrideGroup => {
+                    for group in scenario_input.override_groups.iter_mut().filter(|g| g.name == toggle.key) {
+                        group.enabled = !group.enabled;
+                    }
                 }
-                _ => {}
             }
         }
 
-        self.stat_pool.recalculate_all();
+        let output = calculate_raw(&scenario_input)?;
+        if mask == 0 {
+            base_dps = output.dps_theoretical;
+        }
+        scenarios.push(ToggleScenario {
+            flipped,
+            dps_theoretical: output.dps_theoretical,
+            dps_effective: output.dps_effective,
+            dps_delta_percent: if base_dps > 0.0 {
+                (output.dps_theoretical - base_dps) / base_dps * 100.0
+            } else {
+                0.0
+            },
+            ehp_series: output.ehp_series,
+        });
     }
+    Ok(scenarios)
 }
 
-/// 主计算函数
-pub fn calculate_dps(input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
-    let mut trace = Vec::new();
+/// 翻转机制激活状态：生效时关闭；否则解除关闭并激活（无状态或 0 层时按满层）
+fn flip_mechanic(input: &mut CalculatorInput, mech_id: &str, active: bool) {
+    if active {
+        input.disabled_mechanics.push(mech_id.to_string());
+        return;
+    }
+    input.disabled_mechanics.retain(|id| id != mech_id);
+    if let Some(state) = input.mechanic_states.iter_mut().find(|s| s.id == mech_id) {
+        state.is_active = true;
+        if state.current_stacks == 0 {
+            state.current_stacks = state.max_stacks;
+        }
+        return;
+    }
+    let max_stacks = find_mechanic_definition(input, mech_id).map_or(1, |def| def.default_max_stacks);
+    input.mechanic_states.push(MechanicState {
+        id: mech_id.to_string(),
+        current_stacks: max_stacks,
+        max_stacks,
+        is_active: true,
+    });
+}
 
-    // 0. 初始化标签注册表（实际应从数据库加载）
-    let registry = create_default_registry();
+/// 按 ID 查找机制定义（调用方定义优先，其次内置定义）
+fn find_mechanic_definition<'a>(input: &'a CalculatorInput, mech_id: &str) -> Option<&'a MechanicDefinition> {
+    input
+        .mechanic_definitions
+        .iter()
+        .find(|def| def.id == mech_id)
+        .or_else(|| builtin_definitions().get(mech_id))
+}
+
+/// 参数扫描：上下文数值从 `from` 到 `to` 等距取 `steps` 个点（含两端）的 DPS 曲线
+///
+/// `<机制ID>_stacks`（如 `fighting_will_stacks`）扫描该机制的手动层数。
+/// 参数只影响聚合之后的阶段时复用同一个 [`PreparedContext`]；
+/// 被词缀条件、派生标签或机制层数读取（在聚合阶段生效）时逐点完整重算
+pub fn sweep_value(
+    input: &CalculatorInput,
+    key: &str,
+    from: f64,
+    to: f64,
+    steps: usize,
+) -> Result<Vec<SweepPoint>, CalculationError> {
+    if steps == 0 {
+        return Err(CalculationError::InvalidInput("sweep steps must be at least 1".to_string()));
+    }
+    // 槽位冲突只取舍一次，各次重算沿用同一套装备
+    let resolved = resolve_preferred_slots(input)?.0;
+    let input = resolved.as_ref();
+    let values: Vec<f64> = (0..steps)
+        .map(|i| match steps {
+            1 => from,
+            _ => from + (to - from) * i as f64 / (steps - 1) as f64,
+        })
+        .collect();
+
+    let mechanic_id = key
+        .strip_suffix("_stacks")
+        .filter(|id| input.mechanic_states.iter().any(|s| s.id == *id) || find_mechanic_definition(input, id).is_some());
+    if mechanic_id.is_some() || sweep_affects_aggregation(input, key) {
+        return values
+            .into_iter()
+            .map(|value| {
+                let mut point_input = input.clone();
+                match mechanic_id {
+                    Some(id) => set_mechanic_stacks(&mut point_input, id, value),
+                    None => {
+                        point_input.context_values.insert(key.to_string(), value);
+                    }
+                }
+                let output = calculate_raw(&point_input)?;
+                Ok(SweepPoint {
+                    value,
+                    dps_theoretical: output.dps_theoretical,
+                    dps_effective: output.dps_effective,
+                })
+            })
+            .collect();
+    }
+
+    let prepared = prepare_context(input)?;
+    values
+        .into_iter()
+        .map(|value| {
+            let mut point_ctx = prepared.clone();
+            point_ctx.condition_ctx.values.insert(key.to_string(), value);
+            let output = calculate_from_prepared(&point_ctx, &input.target_config)?;
+            Ok(SweepPoint {
+                value,
+                dps_theoretical: output.dps_theoretical,
+                dps_effective: output.dps_effective,
+            })
+        })
+        .collect()
+}
+
+/// 输入是否需要完整计算（两阶段计算无法复现）
+///
+/// 近期事件模型、命中/击杀叠层与持续输出折算都需要以不同输入多次完整计算，
+/// 只能经 `calculate_dps` 计算；此类输入的 PreparedContext 仍可用于读取聚合结果
+pub fn requires_full_calculation(input: &CalculatorInput) -> bool {
+    input.recent_model.is_some() || input.sustain_model.is_some() || has_event_stacking(input)
+}
+
+/// 参数是否在聚合阶段生效（无法复用同一个 PreparedContext）
+///
+/// 技能部分与需要完整计算的输入（见 `requires_full_calculation`）同样需要完整重算
+fn sweep_affects_aggregation(input: &CalculatorInput, key: &str) -> bool {
+    if !input.active_skill.parts.is_empty() || requires_full_calculation(input) {
+        return true;
+    }
+    let references = |expr: &str| {
+        Condition::parse(expr).is_ok_and(|cond| cond.context_keys().contains(&key))
+    };
+    let in_affixes = input
+        .items
+        .iter()
+        .chain(input.preview_slot.as_ref().map(|p| &p.item))
+        .flat_map(|item| &item.affixes)
+        .flat_map(|affix| affix.condition.iter().chain(affix.branches.iter().map(|branch| &branch.condition)))
+        .any(|expr| references(expr));
+    in_affixes
+        || create_default_registry()
+            .derived_tags()
+            .iter()
+            .any(|(_, cond)| cond.context_keys().contains(&key))
+}
 
-    // 1. Sanitization & Slot Conflict
-    let sanitized_items = sanitize_items(&input.items, &input.preview_slot)?;
-    trace.push(TraceEntry {
-        phase: "Sanitization".to_string(),
-        description: format!("Processed {} items", sanitized_items.len()),
-        values: HashMap::new(),
-        matched_tags: vec![],
+/// 设置机制的手动层数（无状态时按定义上限新建；层数仍受上限钳制）
+fn set_mechanic_stacks(input: &mut CalculatorInput, mech_id: &str, value: f64) {
+    let stacks = value.max(0.0).round() as u32;
+    if let Some(state) = input.mechanic_states.iter_mut().find(|s| s.id == mech_id) {
+        state.current_stacks = stacks;
+        state.is_active = true;
+        return;
+    }
+    let max_stacks = find_mechanic_definition(input, mech_id).map_or(stacks, |def| def.default_max_stacks);
+    input.mechanic_states.push(MechanicState {
+        id: mech_id.to_string(),
+        current_stacks: stacks,
+        max_stacks,
+        is_active: true,
     });
+}
+
+/// 命中/击杀叠层不动点迭代的最大次数
+const MAX_EVENT_STACK_ITERATIONS: usize = 8;
+
+/// 每秒命中数与击杀数（命中/击杀叠层的输入）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct EventRates {
+    hits: f64,
+    kills: f64,
+}
+
+impl EventRates {
+    /// 由一次计算结果推导事件速率
+    fn from_output(output: &CalculatorOutput, model: Option<&RecentModel>) -> Self {
+        let uptime = model.map_or(1.0, |m| m.rotation_uptime.clamp(0.0, 1.0));
+        let kill_chance = model.map_or(0.0, |m| m.kill_chance_per_hit.clamp(0.0, 1.0));
+        let hits = output.rate.max(0.0) * output.hit_chance.clamp(0.0, 1.0) * uptime;
+        Self { hits, kills: hits * kill_chance }
+    }
+
+    fn converged(&self, other: &Self) -> bool {
+        let tolerance = 1e-6 * self.hits.abs().max(1.0);
+        (self.hits - other.hits).abs() <= tolerance && (self.kills - other.kills).abs() <= tolerance
+    }
+}
+
+/// 是否需要按命中/击杀速率迭代求解机制层数（仅稳态模式）
+fn has_event_stacking(input: &CalculatorInput) -> bool {
+    input.stack_mode == StackMode::Realistic
+        && input.mechanic_states.iter().any(|state| {
+            find_mechanic_definition(input, &state.id).is_some_and(|def| def.stacks_on_events())
+        })
+}
+
+/// 命中/击杀叠层的不动点求解
+///
+/// 层数影响速率，速率又决定层数：从 0 层开始，用上一轮的命中/击杀速率重算，
+/// 直到速率收敛或达到迭代上限（层数有上限，单调收敛）
+fn calculate_with_event_stacks(input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
+    let model = input.recent_model.as_ref();
+    let mut rates = EventRates::default();
+    let mut output = calculate_with_rates(input, rates)?;
+    let mut iterations = 1;
+    let mut converged = false;
+
+    while iterations < MAX_EVENT_STACK_ITERATIONS {
+        let next = EventRates::from_output(&output, model);
+        if next.converged(&rates) {
+            converged = true;
+            break;
+        }
+        rates = next;
+        output = calculate_with_rates(input, rates)?;
+        iterations += 1;
+    }
+
+    if !converged {
+        output.warnings.push(Diagnostic::new(
+            DiagnosticCode::StacksNotConverged,
+            message_params(&[("iterations", MAX_EVENT_STACK_ITERATIONS.to_string())]),
+        ));
+    }
+    output.debug_trace.push(TraceEntry::localized(
+        "Event Stacks",
+        "trace.event_stacks",
+        message_params(&[("iterations", iterations.to_string())]),
+        [
+            ("hits_per_second".to_string(), rates.hits),
+            ("kills_per_second".to_string(), rates.kills),
+        ]
+        .into_iter()
+        .collect(),
+    ));
+    Ok(output)
+}
+
+/// 按给定事件速率计算（含近期状态推导）
+fn calculate_with_rates(input: &CalculatorInput, rates: EventRates) -> Result<CalculatorOutput, CalculationError> {
+    match &input.recent_model {
+        Some(model) => calculate_with_recent_model(input, model, rates),
+        None => calculate_single(input, rates),
+    }
+}
+
+/// 按近期事件模型推导"近期"状态后计算（两遍）
+///
+/// 第一遍按手动标志计算速率/命中/暴击，推导出近期状态后若有变化再算一遍；
+/// 用户在 context_flags 中显式给出的状态优先于模型推导
+fn calculate_with_recent_model(
+    input: &CalculatorInput,
+    model: &RecentModel,
+    rates: EventRates,
+) -> Result<CalculatorOutput, CalculationError> {
+    let first = calculate_single(input, rates)?;
+    let probabilities = model.probabilities(first.rate, first.hit_chance, first.crit_chance);
+
+    let mut derived = input.clone();
+    for (flag, probability) in probabilities {
+        if probability >= model.threshold {
+            derived.context_flags.entry(flag.to_string()).or_insert(true);
+        }
+    }
+
+    let mut output = if derived.context_flags == input.context_flags {
+        first
+    } else {
+        calculate_single(&derived, rates)?
+    };
+    output.debug_trace.push(TraceEntry::localized(
+        "Recent",
+        "trace.recent",
+        message_params(&[
+            ("window", model.window.to_string()),
+            ("threshold", format!("{:.0}", model.threshold * 100.0)),
+        ]),
+        probabilities
+            .iter()
+            .map(|(flag, probability)| (flag.to_string(), *probability))
+            .collect(),
+    ));
+    Ok(output)
+}
+
+/// 聚合阶段的产出（`calculate_dps` 与 `prepare_context` 共用）
+struct Aggregation<'a> {
+    /// 越界值按策略钳制后的输入
+    input: Cow<'a, CalculatorInput>,
+    /// 标签注册表
+    registry: Arc<TagRegistry>,
+    /// 上下文标签（阶段性标签已出栈，含聚合后生效的机制标签）
+    context: ContextTags,
+    /// 由主手/副手武器推导的武器标签
+    weapon_tags: Vec<String>,
+    /// 结构化修正存储
+    mod_db: ModDB,
+    /// 条件评估上下文（含机制结算后的层数，不含 PerStat 总值）
+    condition_ctx: EvalContext,
+    /// 生效机制的标签
+    mechanic_tags: Vec<String>,
+    /// 机制层数（`<机制 ID>_stacks`）
+    mechanic_stacks: HashMap<String, f64>,
+    /// 条件未满足而未生效的修正
+    inactive_modifiers: Vec<Modifier>,
+    /// 各把武器的面板 DPS
+    weapon_summaries: Vec<WeaponSummary>,
+    /// 技能魔力消耗（含辅助技能倍率）
+    mana_cost: f64,
+    /// 调试追踪
+    trace: Vec<TraceEntry>,
+    /// 诊断
+    warnings: Vec<Diagnostic>,
+}
+
+/// 聚合阶段：输入校验、上下文标签、机制处理器、条件评估上下文与属性聚合
+///
+/// 两条计算路径共用，保证上下文标签、机制层数与条件评估一致；预计算路径按默认（零）事件速率调用
+fn aggregate_input(input: &CalculatorInput, rates: EventRates) -> Result<Aggregation<'_>, CalculationError> {
+    let mut trace = Vec::with_capacity(TRACE_CAPACITY);
+
+    // 0. 获取共享的标签注册表（实际应从数据库加载）
+    let registry = create_default_registry();
+
+    // 1. Sanitization & Slot Conflict（越界输入按策略钳制/拒绝）
+    let mut sanitizer = RangeSanitizer::new(input.sanitize_policy);
+    let sanitized_input = sanitize_skills(input, &mut sanitizer);
+    let input = sanitized_input.as_ref();
+    validate_input(input)?;
+    let (sanitized_items, slot_warnings) = sanitize_items(input, &mut sanitizer)?;
+    let mut warnings = sanitizer.finish()?;
+    warnings.extend(slot_warnings);
+    trace.push(TraceEntry::localized(
+        "Sanitization",
+        "trace.sanitization",
+        message_params(&[("count", sanitized_items.len().to_string())]),
+        HashMap::new(),
+    ));
 
     // 2. 建立上下文标签
+    let (support_skills, support_warnings) = validate_supports(input, &registry);
+    warnings.extend(support_warnings);
+    let equipped_weapon_tags = weapon_tags(&sanitized_items);
     let mut context = ContextTags::new(registry.clone());
     context.inject_skill_tags(&input.active_skill.tags);
-    for support in &input.support_skills {
+    context.inject_skill_tags(&equipped_weapon_tags);
+    for support in support_skills.iter() {
         context.inject_support_tags(&support.injected_tags);
     }
+    for support in support_skills.iter() {
+        context.inject_conditional_tags(&support.conditional_tags);
+    }
     context.inject_context_flags(&input.context_flags);
+    context.inject_derived_tags(&input.context_flags, &input.context_values);
 
     // 2.5 初始化机制处理器（祝福、球类等）
     let mechanics = MechanicsProcessor::new(
         input.mechanic_definitions.clone(),
         input.mechanic_states.clone(),
-    );
-    
+    )
+    .with_stack_mode(input.stack_mode)
+    .with_use_rate(base_use_rate(&input.active_skill))
+    .with_event_rates(rates.hits, rates.kills)
+    .with_disabled(&input.disabled_mechanics);
+    // 手动状态的机制标签先行注入，聚合期间的条件即可引用；授予的机制在聚合后补充
+    context.inject_skill_tags(&mechanics.active_tags());
+
     // 记录机制状态到 trace
     if !input.mechanic_states.is_empty() {
         let active_mechanics: Vec<String> = input.mechanic_states
             .iter()
-            .filter(|s| s.is_active && s.current_stacks > 0)
-            .map(|s| format!("{}({}层)", s.id, s.current_stacks))
+            .filter(|s| mechanics.effective_stacks(&s.id) > 0.0)
+            .map(|s| format!("{}×{}", s.id, mechanics.effective_stacks(&s.id)))
             .collect();
-        
+
         if !active_mechanics.is_empty() {
-            trace.push(TraceEntry {
-                phase: "Mechanics".to_string(),
-                description: format!("激活机制: {}", active_mechanics.join(", ")),
-                values: mechanics.get_all_stacks(),
-                matched_tags: vec![],
-            });
+            trace.push(TraceEntry::localized(
+                "Mechanics",
+                "trace.mechanics",
+                message_params(&[("mechanics", active_mechanics.join(", "))]),
+                mechanics.get_all_stacks(),
+            ));
         }
     }
 
     // 3. Stat Pool Aggregation（带机制处理器）
-    let mut aggregator = StatAggregator::with_mechanics(&context, &mechanics);
-    aggregator.aggregate_items(&sanitized_items);
-    aggregator.aggregate_skill(&input.active_skill);
-    aggregator.aggregate_support_skills(&input.support_skills);
-    aggregator.aggregate_overrides(&input.global_overrides);
-    
-    // 3.5 应用机制基础效果（如聚能祝福每层+4%伤害）
-    aggregator.apply_mechanic_base_effects();
-    
-    // 获取 StatPool 和 ModDB（ModDB 用于溯源，当前暂未使用）
-    let (stat_pool, _mod_db) = aggregator.finalize();
-
-    // 4. Base Calculation
-    let base_damages = calculate_base_damage(&stat_pool, &input.active_skill);
-    trace.push(TraceEntry {
-        phase: "Base Damage".to_string(),
-        description: "Calculated base damage values".to_string(),
-        values: base_damages
+    // 条件评估上下文取阶段性标签注入前的标签
+    let condition_ctx = EvalContext::from_input(input, &mechanics, &context);
+    // 阶段性标签在聚合时全部可见，避免修正被提前丢弃；各阶段再按作用域筛选
+    context.push_scope();
+    context.inject_skill_tags(&phase_tag_names(&input.active_skill.phase_tags));
+    // 3.5 聚合中结算机制基础效果、覆盖表达式与核心属性加成（见 aggregate_stats）
+    let granted_context;
+    let mut aggregator = aggregate_stats(input, &context, &mechanics, &sanitized_items, &support_skills, condition_ctx.clone());
+    let granted_tags = granted_mechanic_tags(&aggregator, &mechanics);
+    if !granted_tags.is_empty() {
+        granted_context = context_with_tags(&context, &granted_tags);
+        aggregator = aggregate_stats(
+            input,
+            &granted_context,
+            &mechanics,
+            &sanitized_items,
+            &support_skills,
+            condition_ctx.with_tags(&granted_tags),
+        );
+    }
+    // 互斥检查在授予结算之后进行，覆盖装备/技能授予的机制
+    warnings.extend(mechanic_conflict_warnings(aggregator.mechanics().unwrap_or(&mechanics)));
+
+    // 后续阶段与聚合共用同一条件评估上下文（含机制结算后的层数）
+    let condition_ctx = aggregator.eval_context().clone();
+    let mechanic_stacks = aggregator
+        .mechanics()
+        .map(|m| m.get_all_stacks())
+        .unwrap_or_default();
+    let inactive_modifiers = aggregator.inactive_modifiers().to_vec();
+    let weapon_summaries = aggregator.weapon_summaries().to_vec();
+    check_strict_stat_keys(input, aggregator.unknown_stat_keys())?;
+    warnings.extend(aliased_stat_key_diagnostics(aggregator.aliased_stat_keys()));
+    warnings.extend(unit_diagnostics(aggregator.normalized_stat_values()));
+    warnings.extend(unknown_stat_key_diagnostics(aggregator.unknown_stat_keys()));
+    warnings.extend(tag_diagnostics(input, &registry));
+    warnings.extend(condition_key_diagnostics(input, &registry));
+
+    let mechanic_tags = aggregator.mechanics().map(MechanicsProcessor::active_tags).unwrap_or_default();
+
+    // 获取 StatPool 和 ModDB（ModDB 用于标签匹配与溯源）
+    let (_, mod_db) = aggregator.finalize();
+    warnings.extend(stat_key_overflow_diagnostics(&mod_db));
+    context.pop_scope();
+    context.inject_skill_tags(&mechanic_tags);
+    let mana_cost = calculate_mana_cost(&input.active_skill, &support_skills);
+
+    Ok(Aggregation {
+        input: sanitized_input,
+        registry,
+        context,
+        weapon_tags: equipped_weapon_tags,
+        mod_db,
+        condition_ctx,
+        mechanic_tags,
+        mechanic_stacks,
+        inactive_modifiers,
+        weapon_summaries,
+        mana_cost,
+        trace,
+        warnings,
+    })
+}
+
+/// 单次计算（不含技能部分拆分、近期状态推导与叠层迭代）
+fn calculate_single(input: &CalculatorInput, rates: EventRates) -> Result<CalculatorOutput, CalculationError> {
+    let Aggregation {
+        input,
+        registry,
+        mut context,
+        weapon_tags: _,
+        mod_db,
+        condition_ctx,
+        mechanic_tags: _,
+        mechanic_stacks: _,
+        inactive_modifiers,
+        weapon_summaries,
+        mana_cost,
+        mut trace,
+        mut warnings,
+    } = aggregate_input(input, rates)?;
+    let input = input.as_ref();
+    // 后续阶段与聚合共用同一条件评估上下文（含机制结算后的层数）
+    let mut eval_ctx = condition_ctx;
+
+    // 4. Base Calculation（技能等级含装备加成）
+    mod_db.resolve_per_stat_values(&mut eval_ctx);
+    mod_db.resolve_stat_references(&mut eval_ctx);
+    let level_bonus = skill_level_bonus(&mod_db, &eval_ctx, &context);
+    let base_damages = calculate_base_damage(&mod_db, &eval_ctx, &input.active_skill, level_bonus);
+    trace.push(TraceEntry::localized(
+        "Base Damage",
+        "trace.base_damage",
+        message_params(&[
+            ("level", input.active_skill.level.to_string()),
+            ("bonus", level_bonus.to_string()),
+        ]),
+        base_damages
             .iter()
             .map(|(k, (min, max))| (k.as_key().to_string(), (*min + *max) / 2.0))
             .collect(),
-        matched_tags: vec![],
-    });
+    ));
 
     // 5. Extra & Conversion (with Tag Retention)
-    let extra_rules = extract_extra_as_rules(&stat_pool);
-    let conv_rules = extract_conversion_rules(&stat_pool);
+    let conversion_plan = context.with_phase(&input.active_skill.phase_tags, CalcPhase::Conversion, |ctx| {
+        ConversionPlan::cached(&mod_db, &eval_ctx, ctx.active_set().bits())
+    });
+    if let Some(entry) = conversion_scaling_trace(&conversion_plan) {
+        trace.push(entry);
+        warnings.push(conversion_scaling_diagnostic(&conversion_plan));
+    }
     let engine = ConversionEngine::new((registry.max_id() + 1) as usize);
-    let damage_pool = engine.process(&base_damages, &extra_rules, &conv_rules, &registry);
+    let damage_pool = engine.process_packets(&base_damages, &conversion_plan, &registry);
 
     // 6. Modification (Inc/More) - 按标签应用
-    let modified_damages = apply_modifications(&damage_pool, &stat_pool, &context);
+    let ((mut modified_damages, matched_tags), zone_tags) =
+        context.with_phase(&input.active_skill.phase_tags, CalcPhase::Modification, |ctx| {
+            let modified = apply_modifications(&damage_pool, &mod_db, &eval_ctx, ctx, input.increased_floor);
+            let zone_tags = damage_zone_tags(&modified.0, ctx);
+            (modified, zone_tags)
+        });
+    let custom_zones = custom_zones(&mod_db, &eval_ctx);
+    apply_custom_zones(&mut modified_damages, &custom_zones);
     
-    // Lucky 处理：flag.lucky 或 context_flags.lucky_damage
-    let is_lucky = stat_pool.get_base("flag.lucky") > 0.0
-        || input.context_flags.get("lucky_damage").copied().unwrap_or(false);
+    // Lucky 处理：flag.lucky（含 context_flags.lucky_damage 转换的 Flag）
+    let is_lucky = mod_db.has_flag_with_ctx("flag.lucky", &eval_ctx);
     
     let total_damage: f64 = modified_damages
         .values()
         .map(|d| expected_damage(d.min, d.max, is_lucky))
         .sum();
-    trace.push(TraceEntry {
-        phase: "Modification".to_string(),
-        description: "Applied Inc/More modifiers".to_string(),
-        values: modified_damages
-            .iter()
-            .map(|(k, v)| (k.as_key().to_string(), v.average()))
-            .collect(),
-        matched_tags: vec![],
-    });
+    trace.push(modification_trace(&modified_damages, matched_tags));
 
     // 7. Speed Layer
-    let rate_base = calculate_rate(&stat_pool, &input.active_skill);
+    let skill = &input.active_skill;
+    let rate_base = calculate_rate(&mod_db, &eval_ctx, skill.is_attack, skill.base_time, skill.cooldown, input.increased_floor);
     let mut rate = rate_base;
-    trace.push(TraceEntry {
-        phase: "Speed".to_string(),
-        description: format!("Attack/Cast base rate: {:.2}/s", rate_base),
-        values: [("rate".to_string(), rate_base)].into_iter().collect(),
-        matched_tags: vec![],
-    });
+    trace.push(TraceEntry::localized(
+        "Speed",
+        "trace.speed",
+        message_params(&[("rate", format!("{:.2}", rate_base))]),
+        [("rate".to_string(), rate_base)].into_iter().collect(),
+    ));
 
     let use_spell_burst = input.context_flags.get("use_spell_burst").copied().unwrap_or(false);
 
     if use_spell_burst {
         // 触发型迸发：遵循用户指定逻辑
-        match compute_spell_burst_charge_params(&stat_pool, &input.active_skill) {
+        match compute_spell_burst_charge_params(&mod_db, &eval_ctx, &input.active_skill, input.increased_floor) {
             Some((m, t_full, playsafe_on)) if m >= 1 => {
                 rate = m as f64 / t_full;
-                trace.push(TraceEntry {
-                    phase: "Spell Burst (triggered)".to_string(),
-                    description: format!(
-                        "Spell Burst triggered: M={} t_full={:.3}s → rate={:.2}/s",
-                        m, t_full, rate
-                    ),
-                    values: [
+                trace.push(TraceEntry::localized(
+                    "Spell Burst (triggered)",
+                    "trace.spell_burst_triggered",
+                    message_params(&[
+                        ("m", m.to_string()),
+                        ("t_full", format!("{:.3}", t_full)),
+                        ("rate", format!("{:.2}", rate)),
+                    ]),
+                    [
                         ("M".to_string(), m as f64),
                         ("t_full".to_string(), t_full),
                         ("rate_base".to_string(), rate_base),
@@ -254,35 +891,37 @@ pub fn calculate_dps(input: &CalculatorInput) -> Result<CalculatorOutput, Calcul
                     ]
                     .into_iter()
                     .collect(),
-                    matched_tags: vec![],
-                });
+                ));
             }
             _ => {
                 // M < 1 或资格不符：视为无可用迸发层，速率置 0，DPS 将为 0
                 rate = 0.0;
-                trace.push(TraceEntry {
-                    phase: "Spell Burst (triggered)".to_string(),
-                    description: "Spell Burst inactive (M < 1 or not eligible), rate=0".to_string(),
-                    values: [
+                trace.push(TraceEntry::localized(
+                    "Spell Burst (triggered)",
+                    "trace.spell_burst_inactive",
+                    MessageParams::new(),
+                    [
                         ("rate_base".to_string(), rate_base),
                         ("rate_burst".to_string(), rate),
                     ]
                     .into_iter()
                     .collect(),
-                    matched_tags: vec![],
-                });
+                ));
             }
         }
-    } else if let Some(sb) = compute_spell_burst_rate(&stat_pool, &input.active_skill, rate_base) {
+    } else if let Some(sb) = compute_spell_burst_rate(&mod_db, &eval_ctx, &input.active_skill, rate_base, input.increased_floor) {
         // 保持原逻辑（有 0.1s 层间隔、不丢伤害）
         rate = sb.rate_burst;
-        trace.push(TraceEntry {
-            phase: "Spell Burst".to_string(),
-            description: format!(
-                "Spell Burst active: M={} t_full={:.3}s t_cycle={:.3}s → rate={:.2}/s",
-                sb.m, sb.t_full, sb.t_cycle, sb.rate_burst
-            ),
-            values: [
+        trace.push(TraceEntry::localized(
+            "Spell Burst",
+            "trace.spell_burst",
+            message_params(&[
+                ("m", sb.m.to_string()),
+                ("t_full", format!("{:.3}", sb.t_full)),
+                ("t_cycle", format!("{:.3}", sb.t_cycle)),
+                ("rate", format!("{:.2}", sb.rate_burst)),
+            ]),
+            [
                 ("M".to_string(), sb.m as f64),
                 ("t_full".to_string(), sb.t_full),
                 ("t_round".to_string(), sb.t_round),
@@ -293,59 +932,64 @@ pub fn calculate_dps(input: &CalculatorInput) -> Result<CalculatorOutput, Calcul
             ]
             .into_iter()
             .collect(),
-            matched_tags: vec![],
-        });
+        ));
     }
 
     // 8. Crit & Luck
-    let (crit_chance, crit_multiplier) = calculate_crit(&stat_pool, &input.context_flags);
+    let (crit_chance, crit_multiplier) =
+        calculate_crit(&mod_db, &eval_ctx, input.active_skill.is_attack, input.increased_floor);
     let crit_factor = calculate_crit_factor(crit_chance, crit_multiplier);
     
     let hit_damage = total_damage * crit_factor;
-    trace.push(TraceEntry {
-        phase: "Critical".to_string(),
-        description: format!("Crit: {:.1}% chance, {:.1}% multi", crit_chance * 100.0, crit_multiplier * 100.0),
-        values: [
-            ("crit_chance".to_string(), crit_chance),
-            ("crit_multiplier".to_string(), crit_multiplier),
-            ("crit_factor".to_string(), crit_factor),
-        ]
-        .into_iter()
-        .collect(),
-        matched_tags: vec![],
-    });
+    trace.push(critical_trace(crit_chance, crit_multiplier, crit_factor));
 
     // 9. Mitigation (Hit Chance & Enemy DR)
-    let hit_chance = calculate_hit_chance(&stat_pool, &input.target_config);
+    let hit_chance = calculate_hit_chance(&mod_db, &eval_ctx, &input.target_config);
+    let target_db = build_target_mod_db(&input.target_config, &mod_db);
+    warnings.extend(unit_diagnostics(&normalized_target_values(&input.target_config)));
+    warnings.extend(limit_diagnostics(
+        &mod_db,
+        &target_db,
+        &eval_ctx,
+        input.active_skill.is_attack,
+        input.increased_floor,
+    ));
     let dps_theoretical = hit_damage * rate;
     let dps_effective = calculate_effective_dps(
         &modified_damages,
         rate,
         crit_factor,
         hit_chance,
-        &input.target_config,
+        &target_db,
+        &eval_ctx,
     );
 
     // 10. EHP Calculation
-    let ehp_series = calculate_ehp(&stat_pool);
+    let ehp_series = calculate_ehp(&mod_db, &eval_ctx);
 
     // 11. Build damage breakdown (带乘区明细，使用 ModDB 提供详细来源)
     let damage_breakdown = build_damage_breakdown(
         &base_damages,
         &modified_damages,
-        &stat_pool,
-        Some(&_mod_db),
+        &mod_db,
+        &inactive_modifiers,
+        &eval_ctx,
         rate,
         crit_chance,
         crit_multiplier,
         hit_chance,
-        &input.target_config,
+        &target_db,
+        &MoreBucketRegistry::load_default(),
         is_lucky,
+        input.increased_floor,
+        &zone_tags,
+        &custom_zones,
     );
 
     Ok(CalculatorOutput {
         dps_theoretical,
         dps_effective,
+        dps_sustained: dps_theoretical,
         hit_damage,
         rate,
         crit_chance,
@@ -354,20 +998,229 @@ pub fn calculate_dps(input: &CalculatorInput) -> Result<CalculatorOutput, Calcul
         ehp_series,
         damage_breakdown,
         debug_trace: trace,
+        data_version: default_data_version(),
+        engine_version: ENGINE_VERSION.to_string(),
+        input_hash: String::new(),
+        mana_cost,
+        warnings,
+        cache_info: None,
+        weapon_summaries,
     })
 }
 
+/// 多部分技能计算
+///
+/// 选中单一部分时只计算该部分；未选择时逐部分计算后按权重合并（见 `combine_skill_parts`）
+fn calculate_skill_parts(input: &CalculatorInput) -> Result<CalculatorOutput, CalculationError> {
+    let skill = &input.active_skill;
+    let selected: Vec<(usize, f64)> = match skill.selected_part {
+        Some(index) => vec![(index, 1.0)],
+        None => skill.parts.iter().enumerate().map(|(i, part)| (i, part.weight)).collect(),
+    };
+
+    let mut outputs = Vec::with_capacity(selected.len());
+    for (index, weight) in selected {
+        let output = calculate_unsustained(&with_skill_part(input, index)?)?;
+        outputs.push((skill.parts[index].id.clone(), weight, output));
+    }
+    combine_skill_parts(outputs)
+}
+
+/// 合并各技能部分的结果
+///
+/// 命中伤害、DPS 与伤害构成按权重求和；速率、暴击、命中率与各乘区按伤害占比加权平均；
+/// 魔力消耗按权重平均；EHP 与武器面板与技能部分无关，沿用第一个部分
+fn combine_skill_parts(parts: Vec<(String, f64, CalculatorOutput)>) -> Result<CalculatorOutput, CalculationError> {
+    let mut output = parts
+        .first()
+        .map(|(_, _, first)| first.clone())
+        .ok_or_else(|| CalculationError::InvalidInput("skill has no parts".to_string()))?;
+    let total_hit: f64 = parts.iter().map(|(_, weight, part)| weight * part.hit_damage).sum();
+    let total_weight: f64 = parts.iter().map(|(_, weight, _)| weight).sum();
+    let share = |weight: f64, part: &CalculatorOutput| {
+        if total_hit > 0.0 {
+            weight * part.hit_damage / total_hit
+        } else if total_weight > 0.0 {
+            weight / total_weight
+        } else {
+            0.0
+        }
+    };
+
+    output.hit_damage = 0.0;
+    output.dps_theoretical = 0.0;
+    output.dps_effective = 0.0;
+    output.dps_sustained = 0.0;
+    output.rate = 0.0;
+    output.crit_chance = 0.0;
+    output.crit_multiplier = 0.0;
+    output.hit_chance = 0.0;
+    output.mana_cost = 0.0;
+    output.damage_breakdown = DamageBreakdown::default();
+    let mut contributions = HashMap::new();
+    for (id, weight, part) in &parts {
+        let share = share(*weight, part);
+        output.hit_damage += part.hit_damage * weight;
+        output.dps_theoretical += part.dps_theoretical * weight;
+        output.dps_effective += part.dps_effective * weight;
+        output.dps_sustained += part.dps_sustained * weight;
+        output.rate += part.rate * share;
+        output.crit_chance += part.crit_chance * share;
+        output.crit_multiplier += part.crit_multiplier * share;
+        output.hit_chance += part.hit_chance * share;
+        if total_weight > 0.0 {
+            output.mana_cost += part.mana_cost * weight / total_weight;
+        }
+        accumulate_breakdown(&mut output.damage_breakdown, &part.damage_breakdown, *weight, share);
+        for warning in &part.warnings {
+            if !output.warnings.contains(warning) {
+                output.warnings.push(warning.clone());
+            }
+        }
+        contributions.insert(id.clone(), part.hit_damage * weight);
+    }
+
+    output.debug_trace.push(TraceEntry::localized(
+        "Skill Parts",
+        "trace.skill_parts",
+        message_params(&[("count", contributions.len().to_string())]),
+        contributions,
+    ));
+    Ok(output)
+}
+
+/// 将单个技能部分的伤害构成累加到合并明细
+///
+/// 伤害值按权重求和，倍率类乘区按伤害占比加权平均，来源按属性键与文本去重
+fn accumulate_breakdown(total: &mut DamageBreakdown, part: &DamageBreakdown, weight: f64, share: f64) {
+    for (dtype, value) in &part.by_type {
+        *total.by_type.entry(dtype.clone()).or_insert(0.0) += value * weight;
+    }
+    total.base_damage += part.base_damage * weight;
+    total.total_increased += part.total_increased * share;
+    total.total_more += part.total_more * share;
+    for (dtype, damage) in &part.after_conversion {
+        let entry = total.after_conversion.entry(dtype.clone()).or_default();
+        entry.damage += damage.damage * weight;
+        for tag in &damage.history_tags {
+            if !entry.history_tags.contains(tag) {
+                entry.history_tags.push(tag.clone());
+            }
+        }
+    }
+    for (dtype, range) in &part.ranges {
+        let entry = total.ranges.entry(dtype.clone()).or_default();
+        entry.min += range.min * weight;
+        entry.max += range.max * weight;
+    }
+
+    let (total_zones, zones) = (&mut total.multipliers, &part.multipliers);
+    total_zones.base_damage_zone += zones.base_damage_zone * weight;
+    total_zones.increased_zone += zones.increased_zone * share;
+    total_zones.more_zone += zones.more_zone * share;
+    total_zones.less_zone += zones.less_zone * share;
+    total_zones.crit_zone += zones.crit_zone * share;
+    total_zones.speed_zone += zones.speed_zone * share;
+    total_zones.hit_zone += zones.hit_zone * share;
+    total_zones.defense_zone += zones.defense_zone * share;
+    total_zones.resistance_zone += zones.resistance_zone * share;
+    total_zones.vulnerability_zone += zones.vulnerability_zone * share;
+    total_zones.mechanics_zone += zones.mechanics_zone * share;
+    for (zone, value) in &zones.custom_zones {
+        *total_zones.custom_zones.entry(zone.clone()).or_insert(0.0) += value * share;
+    }
+    let same_source = |a: &ZoneSource, b: &ZoneSource| {
+        a.stat_key == b.stat_key && a.source == b.source && a.value.to_bits() == b.value.to_bits()
+    };
+    for (zone, sources) in &zones.zone_sources {
+        let merged = total_zones.zone_sources.entry(zone.clone()).or_default();
+        for source in sources {
+            if !merged.iter().any(|existing| same_source(existing, source)) {
+                merged.push(source.clone());
+            }
+        }
+    }
+    for source in &zones.inactive_sources {
+        if !total_zones.inactive_sources.iter().any(|existing| same_source(existing, source)) {
+            total_zones.inactive_sources.push(source.clone());
+        }
+    }
+}
+
+/// 用指定技能部分替换主技能
+fn with_skill_part(input: &CalculatorInput, index: usize) -> Result<CalculatorInput, CalculationError> {
+    let skill = input.active_skill.resolve_part(index).ok_or_else(|| {
+        CalculationError::InvalidInput(format!("skill part index {} out of range", index))
+    })?;
+    let mut part_input = input.clone();
+    part_input.active_skill = skill;
+    Ok(part_input)
+}
+
 /// 标签注册表 JSON 内容（编译时内嵌）
 /// 
 /// 数据来源：src/data/tags_registry.json
 /// 注意：如需修改标签定义，请编辑上述 JSON 文件
 const TAGS_REGISTRY_JSON: &str = include_str!("data/tags_registry.json");
 
-/// 创建默认的标签注册表
+/// 计算引擎内嵌的全部数据表: (数据表名, JSON 内容)
+///
+/// 数据版本校验和覆盖其中全部内容，任一数据表变化都会使缓存与存档的数据版本失配
+const EMBEDDED_DATA_TABLES: &[(&str, &str)] = &[
+    ("tags_registry", TAGS_REGISTRY_JSON),
+    ("attributes_table", crate::attributes::ATTRIBUTES_TABLE_JSON),
+    ("character_table", crate::character::CHARACTER_TABLE_JSON),
+    ("custom_stats", crate::custom_stats::CUSTOM_STATS_JSON),
+    ("items_meta", crate::items_meta::ITEMS_META_JSON),
+    ("locales", crate::i18n::LOCALES_JSON),
+    ("mechanics_registry", crate::mechanics::MECHANICS_REGISTRY_JSON),
+    ("memory_rules", crate::memories::MEMORY_RULES_JSON),
+    ("more_buckets", crate::more_buckets::MORE_BUCKETS_JSON),
+    ("multiplier_zones", crate::multiplier_zones::MULTIPLIER_ZONES_JSON),
+    ("skills_registry", crate::skills::SKILLS_REGISTRY_JSON),
+    ("slots_registry", crate::slots::SLOTS_REGISTRY_JSON),
+    ("stat_aliases", crate::stat_aliases::STAT_ALIASES_JSON),
+    ("stat_hooks", crate::stat_hooks::STAT_HOOKS_JSON),
+    ("stat_units", crate::stat_units::STAT_UNITS_JSON),
+];
+
+/// 全部内嵌数据表的校验和（FNV-1a，按数据表名与内容依次计算）
+pub fn embedded_data_checksum() -> u64 {
+    static CHECKSUM: OnceLock<u64> = OnceLock::new();
+    *CHECKSUM.get_or_init(|| {
+        let mut bytes = Vec::new();
+        for (name, json) in EMBEDDED_DATA_TABLES {
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(0);
+            bytes.extend_from_slice(json.as_bytes());
+            bytes.push(0);
+        }
+        fnv1a_64(&bytes)
+    })
+}
+
+/// 计算所用的数据快照标识：标签注册表版本 + 全部内嵌数据表的校验和
+pub fn default_data_version() -> DataVersion {
+    DataVersion {
+        version: create_default_registry().version().to_string(),
+        checksum: format!("{:016x}", embedded_data_checksum()),
+    }
+}
+
+/// 获取默认的标签注册表
 /// 
 /// 从内嵌的 JSON 配置加载标签定义，实现数据与代码分离。
+/// 首次调用时解析并缓存，之后所有计算与 `PreparedContext` 共享同一份。
 /// 如果 JSON 解析失败，将回退到最小硬编码定义。
-fn create_default_registry() -> TagRegistry {
+pub fn create_default_registry() -> Arc<TagRegistry> {
+    static DEFAULT_REGISTRY: OnceLock<Arc<TagRegistry>> = OnceLock::new();
+    DEFAULT_REGISTRY
+        .get_or_init(|| Arc::new(parse_default_registry()))
+        .clone()
+}
+
+/// 解析内嵌的标签注册表 JSON
+fn parse_default_registry() -> TagRegistry {
     match TagRegistry::from_json(TAGS_REGISTRY_JSON) {
         Ok(registry) => registry,
         Err(_e) => {
@@ -431,72 +1284,96 @@ fn create_fallback_registry() -> TagRegistry {
 /// let preview_result = calculate_from_prepared(&preview_ctx)?;
 /// ```
 pub fn prepare_context(input: &CalculatorInput) -> Result<PreparedContext, CalculationError> {
-    let mut trace = Vec::new();
-
-    // 0. 初始化标签注册表
-    let registry = create_default_registry();
+    let (resolved, evictions) = resolve_preferred_slots(input)?;
+    let mut ctx = prepare_resolved_context(&resolved)?;
+    ctx.input_digest = input_digest(input);
+    ctx.warnings.splice(0..0, evictions.iter().cloned());
+    for (_, _, part) in &mut ctx.part_contexts {
+        part.warnings.splice(0..0, evictions.iter().cloned());
+    }
+    Ok(ctx)
+}
 
-    // 1. Sanitization & Slot Conflict
-    let sanitized_items = sanitize_items(&input.items, &input.preview_slot)?;
-    trace.push(TraceEntry {
-        phase: "Sanitization".to_string(),
-        description: format!("Processed {} items", sanitized_items.len()),
-        values: HashMap::new(),
-        matched_tags: vec![],
+/// 准备阶段（槽位冲突已按策略取舍）
+fn prepare_resolved_context(input: &CalculatorInput) -> Result<PreparedContext, CalculationError> {
+    // 多部分技能：选中单一部分时只准备该部分，否则逐部分准备并在计算阶段合并
+    if !input.active_skill.parts.is_empty() {
+        if let Some(index) = input.active_skill.selected_part {
+            return prepare_resolved_context(&with_skill_part(input, index)?);
+        }
+        let mut part_contexts = Vec::with_capacity(input.active_skill.parts.len());
+        for (index, part) in input.active_skill.parts.iter().enumerate() {
+            part_contexts.push((part.id.clone(), part.weight, prepare_resolved_context(&with_skill_part(input, index)?)?));
+        }
+        let mut combined = part_contexts[0].2.clone();
+        combined.part_contexts = part_contexts;
+        return Ok(combined);
+    }
+    let Aggregation {
+        input,
+        registry,
+        mut context,
+        weapon_tags,
+        mod_db,
+        condition_ctx,
+        mechanic_tags,
+        mechanic_stacks,
+        inactive_modifiers,
+        weapon_summaries,
+        mana_cost,
+        mut trace,
+        mut warnings,
+    } = aggregate_input(input, EventRates::default())?;
+    let input = input.as_ref();
+
+    // 4. Base Calculation（技能等级含装备加成）
+    let mut eval_ctx = condition_ctx.clone();
+    mod_db.resolve_per_stat_values(&mut eval_ctx);
+    mod_db.resolve_stat_references(&mut eval_ctx);
+    let level_bonus = skill_level_bonus(&mod_db, &eval_ctx, &context);
+    let base_damages = calculate_base_damage(&mod_db, &eval_ctx, &input.active_skill, level_bonus);
+
+    // 5. 编译转化计划（按转化属性子集缓存）
+    let conversion_plan = context.with_phase(&input.active_skill.phase_tags, CalcPhase::Conversion, |ctx| {
+        ConversionPlan::cached(&mod_db, &eval_ctx, ctx.active_set().bits())
     });
-
-    // 2. 建立上下文标签
-    let mut context = ContextTags::new(registry.clone());
-    context.inject_skill_tags(&input.active_skill.tags);
-    for support in &input.support_skills {
-        context.inject_support_tags(&support.injected_tags);
+    if let Some(entry) = conversion_scaling_trace(&conversion_plan) {
+        trace.push(entry);
+        warnings.push(conversion_scaling_diagnostic(&conversion_plan));
     }
-    context.inject_context_flags(&input.context_flags);
-
-    // 2.5 初始化机制处理器
-    let mechanics = MechanicsProcessor::new(
-        input.mechanic_definitions.clone(),
-        input.mechanic_states.clone(),
-    );
-
-    // 3. Stat Pool Aggregation
-    let mut aggregator = StatAggregator::with_mechanics(&context, &mechanics);
-    aggregator.aggregate_items(&sanitized_items);
-    aggregator.aggregate_skill(&input.active_skill);
-    aggregator.aggregate_support_skills(&input.support_skills);
-    aggregator.aggregate_overrides(&input.global_overrides);
-    aggregator.apply_mechanic_base_effects();
-
-    let (stat_pool, mod_db) = aggregator.finalize();
-
-    // 4. Base Calculation
-    let base_damages = calculate_base_damage(&stat_pool, &input.active_skill);
-
-    // 5. 提取转化规则
-    let extra_as_rules = extract_extra_as_rules(&stat_pool);
-    let conversion_rules = extract_conversion_rules(&stat_pool);
 
     // 创建技能快照
     let skill_snapshot = SkillSnapshot {
         id: input.active_skill.id.clone(),
         is_attack: input.active_skill.is_attack,
         base_time: input.active_skill.base_time,
+        cooldown: input.active_skill.cooldown,
         effectiveness: input.active_skill.effectiveness,
         tags: input.active_skill.tags.clone(),
+        phase_tags: input.active_skill.phase_tags.clone(),
+        mana_cost,
     };
 
     Ok(PreparedContext {
         registry,
-        stat_pool,
         mod_db,
+        inactive_modifiers,
         base_damages,
         skill_snapshot,
-        mechanic_stacks: mechanics.get_all_stacks(),
-        context_flags: input.context_flags.clone(),
-        context_values: input.context_values.clone(),
-        conversion_rules,
-        extra_as_rules,
+        weapon_tags,
+        mechanic_tags,
+        mechanic_stacks,
+        condition_ctx,
+        active_tags: context.active_tag_names(),
+        conversion_plan,
+        increased_floor: input.increased_floor,
         trace,
+        warnings,
+        weapon_summaries,
+        part_contexts: Vec::new(),
+        requires_full_calculation: requires_full_calculation(input),
+        // 由 `prepare_context` 按调用方的原始输入填写
+        input_digest: 0,
     })
 }
 
@@ -507,106 +1384,117 @@ pub fn calculate_from_prepared(
     ctx: &PreparedContext,
     target_config: &TargetConfig,
 ) -> Result<CalculatorOutput, CalculationError> {
+    if ctx.requires_full_calculation {
+        return Err(CalculationError::InvalidInput(
+            "prepared context cannot model recent events, event stacks or sustain; use calculate_dps".to_string(),
+        ));
+    }
+    if !ctx.part_contexts.is_empty() {
+        let mut outputs = Vec::with_capacity(ctx.part_contexts.len());
+        for (id, weight, part) in &ctx.part_contexts {
+            outputs.push((id.clone(), *weight, calculate_from_prepared(part, target_config)?));
+        }
+        let mut output = combine_skill_parts(outputs)?;
+        output.input_hash = combine_input_hash(ctx.input_digest, target_config);
+        return Ok(output);
+    }
     let mut trace = ctx.trace.clone();
 
     // 5. Extra & Conversion (with Tag Retention)
     let engine = ConversionEngine::new((ctx.registry.max_id() + 1) as usize);
-    let damage_pool = engine.process(
-        &ctx.base_damages,
-        &ctx.extra_as_rules,
-        &ctx.conversion_rules,
-        &ctx.registry,
-    );
+    let damage_pool = engine.process_packets(&ctx.base_damages, &ctx.conversion_plan, &ctx.registry);
 
-    // 创建临时 ContextTags 用于 apply_modifications
+    // 按聚合阶段的激活标签恢复 ContextTags，与 `calculate_dps` 一致
     let mut context = ContextTags::new(ctx.registry.clone());
-    context.inject_skill_tags(&ctx.skill_snapshot.tags);
-    context.inject_context_flags(&ctx.context_flags);
+    context.inject_skill_tags(&ctx.active_tags);
 
     // 6. Modification (Inc/More)
-    let modified_damages = apply_modifications(&damage_pool, &ctx.stat_pool, &context);
+    let eval_ctx = ctx.eval_context();
+    let ((mut modified_damages, matched_tags), zone_tags) =
+        context.with_phase(&ctx.skill_snapshot.phase_tags, CalcPhase::Modification, |tags| {
+            let modified = apply_modifications(&damage_pool, &ctx.mod_db, &eval_ctx, tags, ctx.increased_floor);
+            let zone_tags = damage_zone_tags(&modified.0, tags);
+            (modified, zone_tags)
+        });
+    let custom_zones = custom_zones(&ctx.mod_db, &eval_ctx);
+    apply_custom_zones(&mut modified_damages, &custom_zones);
 
     // Lucky 处理
-    let is_lucky = ctx.stat_pool.get_base("flag.lucky") > 0.0
-        || ctx.context_flags.get("lucky_damage").copied().unwrap_or(false);
+    let is_lucky = ctx.mod_db.has_flag_with_ctx("flag.lucky", &eval_ctx);
 
     let total_damage: f64 = modified_damages
         .values()
         .map(|d| expected_damage(d.min, d.max, is_lucky))
         .sum();
 
-    trace.push(TraceEntry {
-        phase: "Modification".to_string(),
-        description: "Applied Inc/More modifiers".to_string(),
-        values: modified_damages
-            .iter()
-            .map(|(k, v)| (k.as_key().to_string(), v.average()))
-            .collect(),
-        matched_tags: vec![],
-    });
+    trace.push(modification_trace(&modified_damages, matched_tags));
 
     // 7. Speed Layer
-    let rate = calculate_rate_from_pool(&ctx.stat_pool, &ctx.skill_snapshot);
-    trace.push(TraceEntry {
-        phase: "Speed".to_string(),
-        description: format!("Attack/Cast rate: {:.2}/s", rate),
-        values: [("rate".to_string(), rate)].into_iter().collect(),
-        matched_tags: vec![],
-    });
+    let skill = &ctx.skill_snapshot;
+    let rate = calculate_rate(&ctx.mod_db, &eval_ctx, skill.is_attack, skill.base_time, skill.cooldown, ctx.increased_floor);
+    trace.push(TraceEntry::localized(
+        "Speed",
+        "trace.speed",
+        message_params(&[("rate", format!("{:.2}", rate))]),
+        [("rate".to_string(), rate)].into_iter().collect(),
+    ));
 
     // 8. Crit & Luck
-    let (crit_chance, crit_multiplier) = calculate_crit(&ctx.stat_pool, &ctx.context_flags);
+    let (crit_chance, crit_multiplier) =
+        calculate_crit(&ctx.mod_db, &eval_ctx, ctx.skill_snapshot.is_attack, ctx.increased_floor);
     let crit_factor = calculate_crit_factor(crit_chance, crit_multiplier);
 
     let hit_damage = total_damage * crit_factor;
-    trace.push(TraceEntry {
-        phase: "Critical".to_string(),
-        description: format!(
-            "Crit: {:.1}% chance, {:.1}% multi",
-            crit_chance * 100.0,
-            crit_multiplier * 100.0
-        ),
-        values: [
-            ("crit_chance".to_string(), crit_chance),
-            ("crit_multiplier".to_string(), crit_multiplier),
-            ("crit_factor".to_string(), crit_factor),
-        ]
-        .into_iter()
-        .collect(),
-        matched_tags: vec![],
-    });
+    trace.push(critical_trace(crit_chance, crit_multiplier, crit_factor));
 
     // 9. Mitigation
-    let hit_chance = calculate_hit_chance(&ctx.stat_pool, target_config);
+    let hit_chance = calculate_hit_chance(&ctx.mod_db, &eval_ctx, target_config);
+    let target_db = build_target_mod_db(target_config, &ctx.mod_db);
+    let mut warnings = ctx.warnings.clone();
+    warnings.extend(unit_diagnostics(&normalized_target_values(target_config)));
+    warnings.extend(limit_diagnostics(
+        &ctx.mod_db,
+        &target_db,
+        &eval_ctx,
+        ctx.skill_snapshot.is_attack,
+        ctx.increased_floor,
+    ));
     let dps_theoretical = hit_damage * rate;
     let dps_effective = calculate_effective_dps(
         &modified_damages,
         rate,
         crit_factor,
         hit_chance,
-        target_config,
+        &target_db,
+        &eval_ctx,
     );
 
     // 10. EHP Calculation
-    let ehp_series = calculate_ehp(&ctx.stat_pool);
+    let ehp_series = calculate_ehp(&ctx.mod_db, &eval_ctx);
 
     // 构建输出（使用 ModDB 提供详细来源）
     let damage_breakdown = build_damage_breakdown(
         &ctx.base_damages,
         &modified_damages,
-        &ctx.stat_pool,
-        Some(&ctx.mod_db),
+        &ctx.mod_db,
+        &ctx.inactive_modifiers,
+        &eval_ctx,
         rate,
         crit_chance,
         crit_multiplier,
         hit_chance,
-        target_config,
+        &target_db,
+        &MoreBucketRegistry::load_default(),
         is_lucky,
+        ctx.increased_floor,
+        &zone_tags,
+        &custom_zones,
     );
 
     Ok(CalculatorOutput {
         dps_theoretical,
         dps_effective,
+        dps_sustained: dps_theoretical,
         hit_damage,
         rate,
         crit_chance,
@@ -615,1347 +1503,5085 @@ pub fn calculate_from_prepared(
         ehp_series,
         damage_breakdown,
         debug_trace: trace,
+        data_version: default_data_version(),
+        engine_version: ENGINE_VERSION.to_string(),
+        input_hash: combine_input_hash(ctx.input_digest, target_config),
+        mana_cost: ctx.skill_snapshot.mana_cost,
+        warnings,
+        cache_info: None,
+        weapon_summaries: ctx.weapon_summaries.clone(),
     })
 }
 
-/// 从 SkillSnapshot 计算速率（用于 PreparedContext）
-fn calculate_rate_from_pool(pool: &StatPool, skill: &SkillSnapshot) -> f64 {
-    let base_time = skill.base_time;
-    if base_time <= 0.0 {
-        return 1.0;
+/// 计算技能魔力消耗：基础消耗（等级数据优先）× 各辅助技能的魔力倍率
+fn calculate_mana_cost(skill: &SkillData, supports: &[SkillData]) -> f64 {
+    let base = skill
+        .level_data
+        .as_ref()
+        .and_then(|data| data.mana_cost)
+        .unwrap_or(skill.mana_cost) as f64;
+    supports
+        .iter()
+        .fold(base, |cost, support| cost * support.scaled_mana_multiplier())
+}
+
+/// 校验辅助技能能否辅助主技能
+///
+/// 按主技能自身标签（含继承展开）判定：须具备全部需求标签，且不含任一排除标签。
+/// 无效的辅助技能总会产生警告，仅在 `exclude_invalid_supports` 时被排除出计算
+fn validate_supports<'a>(
+    input: &'a CalculatorInput,
+    registry: &Arc<TagRegistry>,
+) -> (Cow<'a, [SkillData]>, Vec<Diagnostic>) {
+    let mut context = ContextTags::new(registry.clone());
+    context.inject_skill_tags(&input.active_skill.tags);
+    let resolve = |names: &[String]| -> Vec<u32> { names.iter().filter_map(|n| registry.get_id(n)).collect() };
+
+    let invalid: Vec<usize> = input
+        .support_skills
+        .iter()
+        .enumerate()
+        .filter(|(_, support)| {
+            let requirements = resolve(&support.support_requirements);
+            let excluded = resolve(&support.support_excluded_tags);
+            !context.matches_requirements(&requirements, &excluded)
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let warnings = invalid
+        .iter()
+        .map(|&idx| {
+            Diagnostic::new(
+                DiagnosticCode::InvalidSupport,
+                message_params(&[
+                    ("support", input.support_skills[idx].id.clone()),
+                    ("skill", input.active_skill.id.clone()),
+                    ("excluded", input.exclude_invalid_supports.to_string()),
+                ]),
+            )
+        })
+        .collect();
+
+    if invalid.is_empty() || !input.exclude_invalid_supports {
+        return (Cow::Borrowed(&input.support_skills), warnings);
     }
+    let valid = input
+        .support_skills
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !invalid.contains(idx))
+        .map(|(_, support)| support.clone())
+        .collect();
+    (Cow::Owned(valid), warnings)
+}
 
-    let base_rate = 1.0 / base_time;
-    let speed_key = if skill.is_attack {
-        "speed.attack"
+/// 阶段性标签的全部标签名
+fn phase_tag_names(phase_tags: &[PhaseTag]) -> Vec<String> {
+    phase_tags.iter().map(|t| t.tag.clone()).collect()
+}
+
+/// 技能基础每秒使用次数（聚合前的初始估算，机制效果结算前由 `skill_use_rate` 修正）
+fn base_use_rate(skill: &SkillData) -> f64 {
+    if skill.base_time > 0.0 {
+        1.0 / skill.base_time
     } else {
-        "speed.cast"
-    };
-    let speed_inc = pool.get_increased(speed_key);
-    let speed_more = pool.get_more_multiplier(speed_key);
+        0.0
+    }
+}
 
-    base_rate * (1.0 + speed_inc) * speed_more
+/// 聚合全部属性来源，并依次结算机制基础效果、全局覆盖表达式与核心属性隐性加成
+///
+/// 机制基础效果（如聚能祝福每层+4%伤害）中的消耗型资源按速度修正后的使用速率估算；
+/// 覆盖表达式引用机制层数与聚合后的属性，核心属性加成（力量→生命 等）最后应用
+fn aggregate_stats<'a>(
+    input: &CalculatorInput,
+    context: &'a ContextTags,
+    mechanics: &'a MechanicsProcessor,
+    items: &[ItemData],
+    supports: &[SkillData],
+    eval_ctx: EvalContext,
+) -> StatAggregator<'a> {
+    let mut aggregator = StatAggregator::with_mechanics(context, mechanics);
+    aggregator.set_eval_context(eval_ctx);
+    aggregator.aggregate_items(items);
+    aggregator.aggregate_item_sets(items, &input.item_sets);
+    aggregator.aggregate_skill(&input.active_skill);
+    aggregator.aggregate_support_skills(supports);
+    aggregator.aggregate_overrides(&input.global_overrides);
+    aggregator.aggregate_override_groups(&input.override_groups);
+    aggregator.aggregate_context_flags(&input.context_flags);
+    aggregator.aggregate_auras(&input.auras);
+    if let Some(character) = &input.character {
+        aggregator.aggregate_character(character, CharacterTable::shared());
+    }
+    aggregator.set_mechanic_use_rate(skill_use_rate(&aggregator, &input.active_skill, input.increased_floor));
+    aggregator.apply_mechanic_base_effects();
+    aggregator.apply_attribute_bonuses(&AttributeTable::load_default());
+    aggregator.apply_override_expressions();
+    // 默认值在所有来源（含机制效果与表达式覆盖）写入之后才能判断是否缺省
+    aggregator.apply_custom_stat_defaults();
+    aggregator
 }
 
-/// 为预览装备创建增量 ModDB
+/// 聚合期间由装备/技能授予、聚合前尚未激活的机制标签
 ///
-/// 用于悬停预览场景：只聚合 preview item 的属性，返回增量 ModDB
-pub fn prepare_item_modifiers(
-    item: &ItemData,
-    registry: &TagRegistry,
-    mechanics: Option<&MechanicsProcessor>,
-) -> ModDB {
-    let context = ContextTags::new(registry.clone());
-    let mut aggregator = if let Some(m) = mechanics {
-        StatAggregator::with_mechanics(&context, m)
+/// 非空时调用方注入这些标签后重新聚合，词缀的标签需求与条件才能看到授予的机制
+fn granted_mechanic_tags(aggregator: &StatAggregator, mechanics: &MechanicsProcessor) -> Vec<String> {
+    let initial = mechanics.active_tags();
+    aggregator
+        .mechanics()
+        .map(MechanicsProcessor::active_tags)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|tag| !initial.contains(tag))
+        .collect()
+}
+
+/// 复制上下文标签并注入额外标签（原上下文仍被聚合器借用时使用）
+fn context_with_tags(context: &ContextTags, tags: &[String]) -> ContextTags {
+    let mut granted = context.clone();
+    granted.inject_skill_tags(tags);
+    granted
+}
+
+/// 技能每秒使用次数：武器攻速或施法时间，计入已聚合的速度加成与冷却限制
+///
+/// 在机制效果结算之前求值，机制自身提供的速度不参与（避免循环依赖）
+fn skill_use_rate(aggregator: &StatAggregator, skill: &SkillData, floor: IncreasedFloor) -> f64 {
+    let weapon_speed = aggregator.local_attack_speed();
+    let base_rate = if skill.is_attack && weapon_speed > 0.0 {
+        weapon_speed
     } else {
-        StatAggregator::new(&context)
+        base_use_rate(skill)
     };
+    if base_rate <= 0.0 {
+        return 0.0;
+    }
+    speed_adjusted_rate(base_rate, aggregator.mod_db(), aggregator.eval_context(), skill.is_attack, skill.cooldown, floor)
+}
+
+/// 互斥组冲突诊断
+fn mechanic_conflict_warnings(mechanics: &MechanicsProcessor) -> Vec<Diagnostic> {
+    mechanics
+        .exclusive_conflicts()
+        .into_iter()
+        .map(|(id, winner, group)| {
+            Diagnostic::new(
+                DiagnosticCode::MechanicSuppressed,
+                message_params(&[("mechanic", id.to_string()), ("winner", winner.to_string()), ("group", group.to_string())]),
+            )
+        })
+        .collect()
+}
+
+/// 严格模式：未知的 `mod.*` / `conv.*` / `extra.*` 属性键视为输入错误（一次列出全部）
+fn check_strict_stat_keys(input: &CalculatorInput, keys: &[(String, String)]) -> Result<(), CalculationError> {
+    if !input.strict_stat_keys {
+        return Ok(());
+    }
+    let unknown: Vec<String> = keys
+        .iter()
+        .filter(|(key, _)| is_strict_stat_key(key))
+        .map(|(key, source)| format!("'{}' ({})", key, source))
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    Err(CalculationError::InvalidInput(format!("unknown stat keys: {}", unknown.join(", "))))
+}
+
+/// 属性键别名映射诊断
+fn aliased_stat_key_diagnostics(aliases: &[(String, String, String)]) -> Vec<Diagnostic> {
+    aliases
+        .iter()
+        .map(|(alias, key, source)| {
+            Diagnostic::new(
+                DiagnosticCode::StatKeyAliased,
+                message_params(&[("alias", alias.clone()), ("key", key.clone()), ("source", source.clone())]),
+            )
+        })
+        .collect()
+}
+
+/// 单位换算诊断
+fn unit_diagnostics(values: &[(String, f64, f64, String)]) -> Vec<Diagnostic> {
+    values
+        .iter()
+        .map(|(key, value, normalized, source)| {
+            Diagnostic::new(
+                DiagnosticCode::UnitNormalized,
+                message_params(&[
+                    ("key", key.clone()),
+                    ("value", value.to_string()),
+                    ("normalized", normalized.to_string()),
+                    ("source", source.clone()),
+                ]),
+            )
+        })
+        .collect()
+}
 
-    aggregator.aggregate_single_item(item);
-    let (_pool, mod_db) = aggregator.finalize();
+/// 驻留表已满后出现的属性键诊断（键不合并，仅提示计算变慢）
+fn stat_key_overflow_diagnostics(mod_db: &ModDB) -> Vec<Diagnostic> {
     mod_db
+        .uninterned_keys()
+        .into_iter()
+        .map(|key| Diagnostic::new(DiagnosticCode::StatKeyOverflow, message_params(&[("key", key.to_string())])))
+        .collect()
 }
 
-/// 1. Sanitization & Slot Conflict
-fn sanitize_items(
-    items: &[ItemData],
-    preview_slot: &Option<PreviewSlot>,
-) -> Result<Vec<ItemData>, CalculationError> {
-    let mut result: Vec<ItemData> = Vec::new();
-    let mut slots_used: HashMap<SlotType, bool> = HashMap::new();
-    let mut has_two_handed = false;
+/// 未知属性键诊断
+fn unknown_stat_key_diagnostics(keys: &[(String, String)]) -> Vec<Diagnostic> {
+    keys.iter()
+        .map(|(key, source)| {
+            Diagnostic::new(
+                DiagnosticCode::UnknownStatKey,
+                message_params(&[("key", key.clone()), ("source", source.clone())]),
+            )
+        })
+        .collect()
+}
 
-    // 如果有预览槽位，先检查是否为双手武器
-    if let Some(preview) = preview_slot {
-        if preview.item.is_two_handed {
-            has_two_handed = true;
+/// 标签名诊断：输入中引用的已弃用别名与未注册标签（按首次出现顺序去重）
+///
+/// 标签注册表由多次计算共享，诊断在每次计算中重新收集，随输出返回
+fn tag_diagnostics(input: &CalculatorInput, registry: &TagRegistry) -> Vec<Diagnostic> {
+    let mut names: Vec<&str> = Vec::new();
+    for skill in std::iter::once(&input.active_skill).chain(&input.support_skills).chain(&input.auras) {
+        names.extend(skill.tags.iter().map(String::as_str));
+        names.extend(skill.injected_tags.iter().map(String::as_str));
+        names.extend(skill.conditional_tags.iter().map(|t| t.tag.as_str()));
+        names.extend(skill.support_requirements.iter().map(String::as_str));
+        names.extend(skill.support_excluded_tags.iter().map(String::as_str));
+        names.extend(skill.phase_tags.iter().map(|t| t.tag.as_str()));
+        names.extend(skill.parts.iter().flat_map(|part| part.tags.iter().map(String::as_str)));
+    }
+    for item in &input.items {
+        names.extend(item.tags.iter().map(String::as_str));
+        for affix in &item.affixes {
+            names.extend(affix.tags.iter().map(String::as_str));
+            names.extend(affix.requirements.iter().map(String::as_str));
+            names.extend(affix.excluded_tags.iter().map(String::as_str));
         }
     }
 
-    // 处理现有装备
-    for item in items {
-        // 检查是否被预览槽位替换
-        if let Some(preview) = preview_slot {
-            if item.slot == preview.slot_type {
-                continue; // 跳过，后面会添加预览装备
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .filter(|name| seen.insert(*name))
+        .filter_map(|name| match registry.diagnose(name)? {
+            TagDiagnostic::DeprecatedAlias { alias, target } => Some(Diagnostic::new(
+                DiagnosticCode::DeprecatedTag,
+                message_params(&[("tag", alias), ("target", target)]),
+            )),
+            TagDiagnostic::UnknownTag { name } => {
+                Some(Diagnostic::new(DiagnosticCode::UnknownTag, message_params(&[("tag", name)])))
             }
-        }
+            TagDiagnostic::InvalidCondition { .. } => None,
+        })
+        .collect()
+}
 
-        // 双手武器互斥检查
-        if item.is_two_handed {
-            has_two_handed = true;
+/// 条件与表达式中未定义标识符的诊断（按来源与标识符去重）
+///
+/// 未定义的上下文键按 0 / false 计算，拼写错误（`is_movng`）会使条件静默失效。已知标识符为输入提供的上下文键、
+/// 标签注册表派生条件引用的上下文键、机制 ID 与 `<机制 ID>_stacks`；覆盖表达式与属性钩子还可引用属性键（含自定义属性键）。
+/// 检查输入条件（见 `input_conditions`）、全局覆盖与各覆盖分组、标签注册表派生条件与属性钩子的换算表达式
+fn condition_key_diagnostics(input: &CalculatorInput, registry: &TagRegistry) -> Vec<Diagnostic> {
+    let mechanic_ids: BTreeSet<&str> = builtin_definitions()
+        .keys()
+        .chain(input.mechanic_definitions.iter().map(|def| &def.id))
+        .map(String::as_str)
+        .collect();
+    let stack_keys: Vec<String> = mechanic_ids.iter().map(|id| format!("{}_stacks", id)).collect();
+    let context_keys: BTreeSet<&str> = input
+        .context_flags
+        .keys()
+        .chain(input.context_values.keys())
+        .chain(input.context_strings.keys())
+        .chain(&stack_keys)
+        .map(String::as_str)
+        .chain(registry.derived_tags().iter().flat_map(|(_, cond)| cond.context_keys()))
+        .collect();
+
+    let conditions: Vec<(String, Cow<Condition>)> = input_conditions(input)
+        .into_iter()
+        .filter_map(|(source, expr, _)| Some((source, Cow::Owned(Condition::parse(expr).ok()?))))
+        .chain(registry.derived_tags().iter().map(|(id, cond)| {
+            (format!("tags_registry:{}", registry.get_name(*id).unwrap_or_default()), Cow::Borrowed(cond))
+        }))
+        .collect();
+    let override_expressions = std::iter::once(("global_overrides", &input.global_overrides))
+        .chain(input.override_groups.iter().map(|group| (group.name.as_str(), &group.overrides)))
+        .flat_map(|(group, overrides)| {
+            overrides.iter().filter_map(move |(key, value)| match value {
+                OverrideValue::Expression(expr) => {
+                    Some((format!("{}:{}", group, key), Cow::Owned(ValueExpr::parse(expr).ok()?), &[][..]))
+                }
+                OverrideValue::Number(_) => None,
+            })
+        });
+    let hook_expressions = StatHookTable::shared().hooks().map(|(id, hook)| {
+        (format!("stat_hooks:{}", id), Cow::Borrowed(&hook.transform), &[HOOK_SOURCE_VAR, HOOK_TRIGGER_VAR][..])
+    });
+    let expressions: Vec<(String, Cow<ValueExpr>, &[&str])> = override_expressions.chain(hook_expressions).collect();
+
+    // (来源, 未定义的标识符, 拼写相近的已知标识符)
+    let mut unknown: Vec<(&str, &str, Option<String>)> = Vec::new();
+    for (source, cond) in &conditions {
+        for key in cond.context_keys() {
+            if !context_keys.contains(key) {
+                unknown.push((source, key, nearest_identifier(key, context_keys.iter().copied())));
+            }
         }
-
-        // 如果已有双手武器，忽略副手
-        if has_two_handed && item.slot == SlotType::WeaponOff {
-            continue;
+        for id in cond.mechanic_references() {
+            if !mechanic_ids.contains(id) {
+                unknown.push((source, id, nearest_identifier(id, mechanic_ids.iter().copied())));
+            }
         }
-
-        // 检查槽位冲突
-        if slots_used.contains_key(&item.slot) && !matches!(item.slot, SlotType::Ring1 | SlotType::Ring2) {
-            // 允许两个戒指槽位
-            continue;
+        for key in cond.stat_references() {
+            if !is_known_stat_key(key) {
+                unknown.push((source, key, None));
+            }
         }
-
-        slots_used.insert(item.slot, true);
-        result.push(item.clone());
     }
-
-    // 添加预览装备
-    if let Some(preview) = preview_slot {
-        // 双手武器检查
-        if preview.item.is_two_handed {
-            // 移除副手
-            result.retain(|i| i.slot != SlotType::WeaponOff);
+    for (source, expr, locals) in &expressions {
+        for key in expr.variables() {
+            if !locals.contains(&key) && !context_keys.contains(key) && !is_known_stat_key(key) {
+                unknown.push((source, key, nearest_identifier(key, context_keys.iter().chain(locals.iter()).copied())));
+            }
+        }
+        for key in expr.stat_references() {
+            if !is_known_stat_key(key) {
+                unknown.push((source, key, None));
+            }
         }
-        result.push(preview.item.clone());
     }
 
-    Ok(result)
+    let mut seen = HashSet::new();
+    unknown
+        .into_iter()
+        .filter(|(source, key, _)| seen.insert((*source, *key)))
+        .map(|(source, key, suggestion)| {
+            Diagnostic::new(
+                DiagnosticCode::UnknownConditionKey,
+                message_params(&[
+                    ("key", key.to_string()),
+                    ("source", source.to_string()),
+                    ("suggestion", suggestion.unwrap_or_else(|| "-".to_string())),
+                ]),
+            )
+        })
+        .collect()
 }
 
-/// 获取技能在指定等级的有效数据
-/// 
-/// 逻辑：
-/// - 1-20级：使用 level_data 中的具体数据
-/// - 21-30级：使用20级数据 + 每级叠乘 1.10 (默认)
-/// - 31级及以上：使用30级数据 + 每级叠乘 1.08 (默认)
-fn get_skill_effective_data(skill: &SkillData) -> (HashMap<String, f64>, f64, f64) {
-    let level = skill.level;
-    
-    // 如果有等级数据，使用它
-    if let Some(level_data) = &skill.level_data {
-        let base_damage = level_data.base_damage.clone();
-        let effectiveness = level_data.effectiveness;
-        
-        // 计算等级缩放乘数
-        let level_multiplier = calculate_level_scaling(level, &skill.scaling_rules);
-        
-        return (base_damage, effectiveness, level_multiplier);
-    }
-    
-    // 否则使用默认数据
-    let base_damage = skill.base_damage.clone();
-    let effectiveness = skill.effectiveness;
-    let level_multiplier = calculate_level_scaling(level, &skill.scaling_rules);
-    
-    (base_damage, effectiveness, level_multiplier)
+/// 非负取值范围
+const NON_NEGATIVE: (f64, f64) = (0.0, f64::INFINITY);
+
+/// 输入属性的合理取值范围（0–1 约定，比例类数值按单位表换算后比较；`mod.override.` 前缀按去掉后的键匹配）
+const STAT_RANGES: &[(&str, (f64, f64))] = &[
+    ("crit.chance", (0.0, 1.0)),
+    ("acc.chance", (0.0, 1.0)),
+    ("def.block", (0.0, 1.0)),
+    ("def.spell_block", (0.0, 1.0)),
+    ("weapon.base_speed", NON_NEGATIVE),
+];
+
+/// 属性键的合理取值范围（无约束返回 None）
+fn stat_range(key: &str) -> Option<(f64, f64)> {
+    let key = key.strip_prefix("mod.override.").unwrap_or(key);
+    STAT_RANGES.iter().find(|(stat, _)| *stat == key).map(|(_, range)| *range)
 }
 
-/// 计算技能等级缩放乘数
-/// 
-/// 默认规则：
-/// - 1-20级：无额外缩放 (multiplier = 1.0)
-/// - 21-30级：每级 +10% (叠乘)
-/// - 31级及以上：每级 +8% (叠乘)
-fn calculate_level_scaling(level: u32, rules: &[SkillScalingRule]) -> f64 {
-    if level <= 20 {
-        return 1.0;
+/// 数值是否在范围内（NaN 视为越界）
+fn in_range(value: f64, (min, max): (f64, f64)) -> bool {
+    value >= min && value <= max
+}
+
+/// 属性值是否在合理范围内（`.pct` 等按 0–100 约定录入的键换算后比较）
+fn stat_in_range(key: &str, value: f64) -> bool {
+    let (key, value) = StatUnitTable::shared().normalize(key, value).unwrap_or((key, value));
+    stat_range(key).map_or(true, |range| in_range(value, range))
+}
+
+/// 越界输入的检查与记录
+///
+/// 按 `SanitizePolicy` 钳制、拒绝或保留越界值；钳制与保留记入诊断，拒绝在 `finish` 时一次报错
+struct RangeSanitizer {
+    policy: SanitizePolicy,
+    diagnostics: Vec<Diagnostic>,
+    rejected: Vec<String>,
+}
+
+impl RangeSanitizer {
+    fn new(policy: SanitizePolicy) -> Self {
+        Self { policy, diagnostics: Vec::new(), rejected: Vec::new() }
     }
-    
-    let mut multiplier = 1.0;
-    
-    // 使用自定义规则
-    if !rules.is_empty() {
-        for rule in rules {
-            if level >= rule.level_start {
-                let end = rule.level_end.unwrap_or(u32::MAX);
-                if level <= end {
-                    let levels_in_range = (level - rule.level_start + 1).min(
-                        end.saturating_sub(rule.level_start) + 1
-                    );
-                    multiplier *= rule.multiplier_per_level.powi(levels_in_range as i32);
-                } else {
-                    // 完整应用此区间
-                    let levels_in_range = end - rule.level_start + 1;
-                    multiplier *= rule.multiplier_per_level.powi(levels_in_range as i32);
-                }
+
+    /// 检查单个数值，返回按策略处理后的值
+    fn check(&mut self, field: &str, value: f64, range: (f64, f64), source: &str) -> f64 {
+        if in_range(value, range) {
+            return value;
+        }
+        let (min, max) = range;
+        let adjusted = match self.policy {
+            SanitizePolicy::Clamp if value.is_nan() => min,
+            SanitizePolicy::Clamp => value.clamp(min, max),
+            SanitizePolicy::Keep => value,
+            SanitizePolicy::Reject => {
+                self.rejected
+                    .push(format!("{}: {} = {} (expected [{}, {}])", source, field, value, min, max));
+                return value;
             }
+        };
+        self.diagnostics.push(Diagnostic::new(
+            DiagnosticCode::ValueOutOfRange,
+            message_params(&[
+                ("field", field.to_string()),
+                ("value", value.to_string()),
+                ("min", min.to_string()),
+                ("max", max.to_string()),
+                ("adjusted", adjusted.to_string()),
+                ("source", source.to_string()),
+            ]),
+        ));
+        adjusted
+    }
+
+    /// 检查必须为正的数值：不论策略，非正值（含 NaN）一律拒绝
+    fn require_positive(&mut self, field: &str, value: f64, source: &str) {
+        if value.is_nan() || value <= 0.0 {
+            self.rejected.push(format!("{}: {} = {} (must be positive)", source, field, value));
         }
-    } else {
-        // 使用默认规则
-        // 21-30级：每级 +10%
-        if level > 20 {
-            let levels_21_30 = (level.min(30) - 20) as i32;
-            multiplier *= 1.10_f64.powi(levels_21_30);
+    }
+
+    /// 检查属性表中有范围约束的属性（钳制后的值按录入约定写回）
+    fn check_stats(&mut self, stats: &mut HashMap<String, f64>, source: &str) {
+        for (key, value) in stats.iter_mut() {
+            let (stat, divisor) = StatUnitTable::shared().percent_key(key).unwrap_or((key, 1.0));
+            let Some(range) = stat_range(stat) else {
+                continue;
+            };
+            let normalized = *value / divisor;
+            let adjusted = self.check(stat, normalized, range, source);
+            if adjusted != normalized {
+                *value = adjusted * divisor;
+            }
         }
-        // 31级及以上：每级 +8%
-        if level > 30 {
-            let levels_31_plus = (level - 30) as i32;
-            multiplier *= 1.08_f64.powi(levels_31_plus);
+    }
+
+    /// 结束检查：拒绝策略下存在越界值时报错，否则返回诊断
+    fn finish(self) -> Result<Vec<Diagnostic>, CalculationError> {
+        if self.rejected.is_empty() {
+            return Ok(self.diagnostics);
         }
+        Err(CalculationError::InvalidInput(format!("out-of-range values: {}", self.rejected.join("; "))))
     }
-    
-    multiplier
 }
 
-/// 3. 计算基础伤害
-fn calculate_base_damage(
-    pool: &StatPool,
-    skill: &SkillData,
-) -> HashMap<DamageType, (f64, f64)> {
-    let mut base = HashMap::new();
-    
-    // 获取等级有效数据
-    let (base_damage_map, effectiveness, level_multiplier) = get_skill_effective_data(skill);
+/// 技能数据是否全部在合理范围内
+fn skill_in_range(skill: &SkillData) -> bool {
+    in_range(skill.base_time, NON_NEGATIVE)
+        && skill.cooldown.map_or(true, |cd| in_range(cd, NON_NEGATIVE))
+        && in_range(skill.effectiveness, NON_NEGATIVE)
+        && skill.stats.iter().all(|(key, value)| stat_in_range(key, *value))
+}
 
-    // 从技能获取基础伤害
-    for (key, value) in &base_damage_map {
-        if key.contains("phys") {
-            let entry = base.entry(DamageType::Physical).or_insert((0.0, 0.0));
-            if key.contains("min") {
-                entry.0 += value;
-            } else if key.contains("max") {
-                entry.1 += value;
-            }
-        } else if key.contains("fire") {
-            let entry = base.entry(DamageType::Fire).or_insert((0.0, 0.0));
-            if key.contains("min") {
-                entry.0 += value;
-            } else if key.contains("max") {
-                entry.1 += value;
-            }
-        } else if key.contains("cold") {
-            let entry = base.entry(DamageType::Cold).or_insert((0.0, 0.0));
-            if key.contains("min") {
-                entry.0 += value;
-            } else if key.contains("max") {
-                entry.1 += value;
-            }
-        } else if key.contains("lightning") {
-            let entry = base.entry(DamageType::Lightning).or_insert((0.0, 0.0));
-            if key.contains("min") {
-                entry.0 += value;
-            } else if key.contains("max") {
-                entry.1 += value;
-            }
-        } else if key.contains("chaos") {
-            let entry = base.entry(DamageType::Chaos).or_insert((0.0, 0.0));
-            if key.contains("min") {
-                entry.0 += value;
-            } else if key.contains("max") {
-                entry.1 += value;
-            }
+/// 检查技能数据：冷却、伤害效用须非负，属性按范围表检查（攻击/施法时间由调用方检查）
+fn sanitize_skill(skill: &mut SkillData, sanitizer: &mut RangeSanitizer) {
+    let source = format!("skill:{}", skill.id);
+    if let Some(cooldown) = skill.cooldown {
+        skill.cooldown = Some(sanitizer.check("cooldown", cooldown, NON_NEGATIVE, &source));
+    }
+    skill.effectiveness = sanitizer.check("effectiveness", skill.effectiveness, NON_NEGATIVE, &source);
+    sanitizer.check_stats(&mut skill.stats, &source);
+}
+
+/// 检查主技能、辅助技能与光环（全部在范围内时不复制输入）
+///
+/// 主技能的攻击/施法时间决定基础速率，非正值无法换算，不论策略一律拒绝
+fn sanitize_skills<'a>(input: &'a CalculatorInput, sanitizer: &mut RangeSanitizer) -> Cow<'a, CalculatorInput> {
+    let active = &input.active_skill;
+    sanitizer.require_positive("base_time", active.base_time, &format!("skill:{}", active.id));
+    let all_in_range = std::iter::once(&input.active_skill)
+        .chain(&input.support_skills)
+        .chain(&input.auras)
+        .all(skill_in_range);
+    if all_in_range {
+        return Cow::Borrowed(input);
+    }
+    let mut sanitized = input.clone();
+    sanitize_skill(&mut sanitized.active_skill, sanitizer);
+    for skill in sanitized.support_skills.iter_mut().chain(sanitized.auras.iter_mut()) {
+        let source = format!("skill:{}", skill.id);
+        skill.base_time = sanitizer.check("base_time", skill.base_time, NON_NEGATIVE, &source);
+        sanitize_skill(skill, sanitizer);
+    }
+    Cow::Owned(sanitized)
+}
+
+/// 检查装备属性（基底、隐性、词缀）
+fn sanitize_item_stats(item: &mut ItemData, sanitizer: &mut RangeSanitizer) {
+    sanitizer.check_stats(&mut item.base_implicit_stats, &format!("{}:base", item.id));
+    sanitizer.check_stats(&mut item.implicit_stats, &format!("{}:implicit", item.id));
+    for affix in &mut item.affixes {
+        let source = format!("{}:{}", item.id, affix.id);
+        sanitizer.check_stats(&mut affix.stats, &source);
+        for branch in &mut affix.branches {
+            sanitizer.check_stats(&mut branch.stats, &source);
         }
     }
+}
 
-    // 对于攻击技能，使用武器伤害
-    if skill.is_attack {
-        let phys_min = pool.get_base("dmg.phys.min");
-        let phys_max = pool.get_base("dmg.phys.max");
-        if phys_min > 0.0 || phys_max > 0.0 {
-            let entry = base.entry(DamageType::Physical).or_insert((0.0, 0.0));
-            entry.0 += phys_min;
-            entry.1 += phys_max;
+/// 槽位冲突原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotConflict {
+    /// 槽位容量已满
+    Capacity,
+    /// 冲突组已被其他槽位占用
+    ConflictGroup,
+    /// 双手武器与副手互斥
+    TwoHanded,
+}
+
+impl SlotConflict {
+    fn as_key(self) -> &'static str {
+        match self {
+            SlotConflict::Capacity => "capacity",
+            SlotConflict::ConflictGroup => "conflict_group",
+            SlotConflict::TwoHanded => "two_handed",
         }
     }
+}
 
-    // 应用等级缩放乘数 (21级及以上的 More 乘数)
-    if level_multiplier > 1.0 {
-    for (_, (min, max)) in base.iter_mut() {
-            *min *= level_multiplier;
-            *max *= level_multiplier;
+/// 查找阻止装备 `item` 的已接受装备
+///
+/// 返回冲突原因与可选的移除方案（每个方案为 `accepted` 下标集合）：
+/// 容量冲突可任选一件同槽位装备移除，其余冲突须移除全部阻止者
+fn slot_blockers(
+    accepted: &[(usize, ItemData)],
+    item: &ItemData,
+    slot_definitions: &[SlotDefinition],
+) -> Option<(SlotConflict, Vec<Vec<usize>>)> {
+    let registry = SlotRegistry::shared();
+    let slot = registry.definition(&item.slot, slot_definitions);
+    let find = |pred: &dyn Fn(&ItemData) -> bool| -> Vec<usize> {
+        accepted.iter().enumerate().filter(|(_, (_, a))| pred(a)).map(|(i, _)| i).collect()
+    };
+
+    let same_slot = find(&|a| a.slot == item.slot);
+    if same_slot.len() as u32 >= slot.capacity {
+        return Some((SlotConflict::Capacity, same_slot.into_iter().map(|i| vec![i]).collect()));
+    }
+    if let Some(group) = &slot.conflict_group {
+        let occupied = find(&|a| {
+            a.slot != item.slot
+                && registry.definition(&a.slot, slot_definitions).conflict_group.as_ref() == Some(group)
+        });
+        if !occupied.is_empty() {
+            return Some((SlotConflict::ConflictGroup, vec![occupied]));
         }
     }
+    let two_handed = if slot.blocked_by_two_handed {
+        find(&|a| a.is_two_handed)
+    } else if item.is_two_handed {
+        find(&|a| registry.definition(&a.slot, slot_definitions).blocked_by_two_handed)
+    } else {
+        Vec::new()
+    };
+    (!two_handed.is_empty()).then(|| (SlotConflict::TwoHanded, vec![two_handed]))
+}
 
-    // 将“世事无常”一类的最小/最大伤害拉伸提前到点伤阶段
-    // 仅作用于已有的 min/max 基础伤害桶，后续 Inc/More 不再二次放大这些拉伸
-    let stretch_min_global = pool.get_more_multiplier("dmg.min");
-    let stretch_max_global = pool.get_more_multiplier("dmg.max");
-    let stretch_min_phys = pool.get_more_multiplier("dmg.phys.min");
-    let stretch_max_phys = pool.get_more_multiplier("dmg.phys.max");
+/// 槽位冲突移除记录的诊断
+fn slot_conflict_diagnostic(item: &ItemData, conflict: SlotConflict, kept: &[&ItemData]) -> Diagnostic {
+    let kept: Vec<&str> = kept.iter().map(|i| i.id.as_str()).collect();
+    Diagnostic::new(
+        DiagnosticCode::SlotConflict,
+        message_params(&[
+            ("item", item.id.clone()),
+            ("slot", item.slot.id().to_string()),
+            ("reason", conflict.as_key().to_string()),
+            ("kept", kept.join(", ")),
+        ]),
+    )
+}
 
-    for (dtype, (min, max)) in base.iter_mut() {
-        let (smin, smax) = match dtype {
-            DamageType::Physical => (
-                stretch_min_global * stretch_min_phys,
-                stretch_max_global * stretch_max_phys,
-            ),
-            _ => (stretch_min_global, stretch_max_global),
+/// 同组词缀互斥：同一装备上每个词缀组只保留一条（等级最高者，同级取数值较大者，再同则取先录入者）
+///
+/// 空词缀组不参与互斥；被移除的词缀逐条记入诊断
+fn resolve_affix_groups(item: &mut ItemData, diagnostics: &mut Vec<Diagnostic>) {
+    let mut kept: HashMap<&str, usize> = HashMap::new();
+    let mut dropped: Vec<usize> = Vec::new();
+    for (index, affix) in item.affixes.iter().enumerate() {
+        if affix.group.is_empty() {
+            continue;
+        }
+        let Some(&incumbent) = kept.get(affix.group.as_str()) else {
+            kept.insert(&affix.group, index);
+            continue;
         };
-        *min *= smin;
-        *max *= smax;
+        let current = &item.affixes[incumbent];
+        if (affix.tier, affix.value) > (current.tier, current.value) {
+            kept.insert(&affix.group, index);
+            dropped.push(incumbent);
+        } else {
+            dropped.push(index);
+        }
+    }
+    if dropped.is_empty() {
+        return;
     }
 
-    base
+    for &index in &dropped {
+        let affix = &item.affixes[index];
+        diagnostics.push(Diagnostic::new(
+            DiagnosticCode::AffixGroupConflict,
+            message_params(&[
+                ("item", item.id.clone()),
+                ("group", affix.group.clone()),
+                ("dropped", affix.id.clone()),
+                ("kept", item.affixes[kept[affix.group.as_str()]].id.clone()),
+            ]),
+        ));
+    }
+    item.affixes = std::mem::take(&mut item.affixes)
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !dropped.contains(index))
+        .map(|(_, affix)| affix)
+        .collect();
 }
 
-/// 6. 应用 Inc/More 修正（带标签匹配）
-fn apply_modifications(
-    damage_pool: &HashMap<DamageType, DamageWithTags>,
-    stat_pool: &StatPool,
-    context: &ContextTags,
-) -> HashMap<DamageType, DamageWithTags> {
-    let mut result = HashMap::new();
-    let registry = context.registry();
-
-    for (dtype, dmg) in damage_pool {
-        if dmg.is_zero() {
+/// 追忆间词缀互斥：同一词缀组在全部追忆上只保留一条（比较规则同 `resolve_affix_groups`，数值按位置效果缩放后比较，再同则取先录入的追忆）
+///
+/// 各追忆内部已先完成同组互斥；被移除的词缀逐条记入诊断
+fn resolve_memory_affix_groups(items: &mut [ItemData], diagnostics: &mut Vec<Diagnostic>) {
+    let rules = MemoryRules::shared();
+    // 词缀组 -> (装备下标, 词缀下标)
+    let mut kept: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut dropped: Vec<(usize, usize)> = Vec::new();
+    for (item_index, item) in items.iter().enumerate() {
+        if !is_memory_slot(&item.slot) {
             continue;
         }
-
-        let mut modified = dmg.clone();
-        
-        // 收集所有适用的 Inc 修正
-        let mut total_inc = 0.0;
-        
-        // 全局伤害增加
-        total_inc += stat_pool.get_increased("dmg.all");
-        
-        // 根据历史标签应用对应的 Inc
-        // Physical Inc
-        if dmg.history_tags.contains(registry.get_id("Tag_Physical").unwrap_or(0) as usize) {
-            total_inc += stat_pool.get_increased("dmg.phys");
-        }
-        
-        // Fire Inc
-        if dmg.history_tags.contains(registry.get_id("Tag_Fire").unwrap_or(0) as usize) {
-            total_inc += stat_pool.get_increased("dmg.fire");
-        }
-        
-        // Cold Inc
-        if dmg.history_tags.contains(registry.get_id("Tag_Cold").unwrap_or(0) as usize) {
-            total_inc += stat_pool.get_increased("dmg.cold");
-        }
-        
-        // Lightning Inc
-        if dmg.history_tags.contains(registry.get_id("Tag_Lightning").unwrap_or(0) as usize) {
-            total_inc += stat_pool.get_increased("dmg.lightning");
-        }
-        
-        // Chaos Inc
-        if dmg.history_tags.contains(registry.get_id("Tag_Chaos").unwrap_or(0) as usize) {
-            total_inc += stat_pool.get_increased("dmg.chaos");
-        }
-        
-        // Elemental Inc (如果有任何元素标签)
-        let has_elemental = dmg.history_tags.contains(registry.get_id("Tag_Fire").unwrap_or(0) as usize)
-            || dmg.history_tags.contains(registry.get_id("Tag_Cold").unwrap_or(0) as usize)
-            || dmg.history_tags.contains(registry.get_id("Tag_Lightning").unwrap_or(0) as usize);
-        if has_elemental {
-            total_inc += stat_pool.get_increased("dmg.elemental");
+        for (affix_index, affix) in item.affixes.iter().enumerate() {
+            if affix.group.is_empty() {
+                continue;
+            }
+            let Some(&(kept_item, kept_affix)) = kept.get(&affix.group) else {
+                kept.insert(affix.group.clone(), (item_index, affix_index));
+                continue;
+            };
+            let current = &items[kept_item].affixes[kept_affix];
+            let value = affix.value * rules.effect(&item.slot);
+            let current_value = current.value * rules.effect(&items[kept_item].slot);
+            if (affix.tier, value) > (current.tier, current_value) {
+                kept.insert(affix.group.clone(), (item_index, affix_index));
+                dropped.push((kept_item, kept_affix));
+            } else {
+                dropped.push((item_index, affix_index));
+            }
         }
+    }
+    if dropped.is_empty() {
+        return;
+    }
 
-        // 技能类型 Inc
-        if context.active_set().contains(registry.get_id("Tag_Spell").unwrap_or(0)) {
-            total_inc += stat_pool.get_increased("dmg.spell");
-        }
-        if context.active_set().contains(registry.get_id("Tag_Attack").unwrap_or(0)) {
-            total_inc += stat_pool.get_increased("dmg.attack");
-        }
-        if context.active_set().contains(registry.get_id("Tag_Melee").unwrap_or(0)) {
-            total_inc += stat_pool.get_increased("dmg.melee");
-        }
-        if context.active_set().contains(registry.get_id("Tag_AOE").unwrap_or(0)) {
-            total_inc += stat_pool.get_increased("dmg.aoe");
-        }
-        if context.active_set().contains(registry.get_id("Tag_Projectile").unwrap_or(0)) {
-            total_inc += stat_pool.get_increased("dmg.projectile");
+    for &(item_index, affix_index) in &dropped {
+        let item = &items[item_index];
+        let affix = &item.affixes[affix_index];
+        let (kept_item, kept_affix) = kept[&affix.group];
+        let kept = &items[kept_item];
+        diagnostics.push(Diagnostic::new(
+            DiagnosticCode::AffixGroupConflict,
+            message_params(&[
+                ("item", item.id.clone()),
+                ("group", affix.group.clone()),
+                ("dropped", affix.id.clone()),
+                ("kept", format!("{}:{}", kept.id, kept.affixes[kept_affix].id)),
+            ]),
+        ));
+    }
+    for (item_index, item) in items.iter_mut().enumerate() {
+        if !dropped.iter().any(|&(index, _)| index == item_index) {
+            continue;
         }
+        item.affixes = std::mem::take(&mut item.affixes)
+            .into_iter()
+            .enumerate()
+            .filter(|(affix_index, _)| !dropped.contains(&(item_index, *affix_index)))
+            .map(|(_, affix)| affix)
+            .collect();
+    }
+}
 
-        // 应用 Inc
-        let inc_multiplier = 1.0 + total_inc;
-        
-        // 收集 More 修正（支持按类型/全局/最小值/最大值拆分，并按历史标签叠加）
-        let more_all = stat_pool.get_more_multiplier("dmg.all");
-        let more_type = match dtype {
-            DamageType::Physical => stat_pool.get_more_multiplier("dmg.phys"),
-            DamageType::Fire => stat_pool.get_more_multiplier("dmg.fire"),
-            DamageType::Cold => stat_pool.get_more_multiplier("dmg.cold"),
-            DamageType::Lightning => stat_pool.get_more_multiplier("dmg.lightning"),
-            DamageType::Chaos => stat_pool.get_more_multiplier("dmg.chaos"),
-        };
-        // 法术专属 more（积聚等效果）：作为独立乘区参与
-        let more_spell = if context.active_set().contains(registry.get_id("Tag_Spell").unwrap_or(0)) {
-            stat_pool.get_more_multiplier("dmg.spell")
-        } else {
-            1.0
-        };
-        // 基于历史标签的 more（转化后仍享受源类型 more），避免与当前类型重复叠乘
-        let mut more_history = 1.0;
-        let current_tag = match dtype {
-            DamageType::Physical => registry.get_id("Tag_Physical"),
-            DamageType::Fire => registry.get_id("Tag_Fire"),
-            DamageType::Cold => registry.get_id("Tag_Cold"),
-            DamageType::Lightning => registry.get_id("Tag_Lightning"),
-            DamageType::Chaos => registry.get_id("Tag_Chaos"),
-        };
-        let apply_history = |hist: &fixedbitset::FixedBitSet, tag_id: Option<u32>, key: &str, acc: &mut f64| {
-            if let Some(id) = tag_id {
-                if hist.contains(id as usize) {
-                    *acc *= stat_pool.get_more_multiplier(key);
-                }
-            }
-        };
-        // 仅当历史标签与当前类型不同才叠乘
-        let hist = &dmg.history_tags;
-        if current_tag != registry.get_id("Tag_Lightning") {
-            apply_history(hist, registry.get_id("Tag_Lightning"), "dmg.lightning", &mut more_history);
-        }
-        if current_tag != registry.get_id("Tag_Cold") {
-            apply_history(hist, registry.get_id("Tag_Cold"), "dmg.cold", &mut more_history);
-        }
-        if current_tag != registry.get_id("Tag_Fire") {
-            apply_history(hist, registry.get_id("Tag_Fire"), "dmg.fire", &mut more_history);
-        }
-        if current_tag != registry.get_id("Tag_Physical") {
-            apply_history(hist, registry.get_id("Tag_Physical"), "dmg.phys", &mut more_history);
-        }
-        if current_tag != registry.get_id("Tag_Chaos") {
-            apply_history(hist, registry.get_id("Tag_Chaos"), "dmg.chaos", &mut more_history);
-        }
-        // 最小/最大拉伸已在基础伤害阶段应用，这里置为 1 以避免重复放大
-        let more_min_generic = 1.0;
-        let more_max_generic = 1.0;
-        let more_min_type = match dtype {
-            DamageType::Physical => 1.0,
-            _ => stat_pool.get_more_multiplier(&format!("dmg.{}.min", dtype.as_key())),
-        };
-        let more_max_type = match dtype {
-            DamageType::Physical => 1.0,
-            _ => stat_pool.get_more_multiplier(&format!("dmg.{}.max", dtype.as_key())),
-        };
-        
-        let more_multiplier_min = more_all * more_type * more_spell * more_history * more_min_generic * more_min_type;
-        let more_multiplier_max = more_all * more_type * more_spell * more_history * more_max_generic * more_max_type;
-        
-        // 应用所有修正
-        modified.min *= inc_multiplier * more_multiplier_min;
-        modified.max *= inc_multiplier * more_multiplier_max;
-        
-        result.insert(*dtype, modified);
+/// 角色所选职业（须已在角色成长表中登记）
+fn selected_class(input: &CalculatorInput) -> Result<Option<&str>, CalculationError> {
+    let Some(class) = input.character.as_ref().and_then(|c| c.class.as_deref()) else {
+        return Ok(None);
+    };
+    if CharacterTable::shared().is_registered(class) {
+        Ok(Some(class))
+    } else {
+        Err(CalculationError::InvalidInput(format!("unknown class '{}'", class)))
     }
+}
 
-    result
+/// 职业限定词缀：角色职业与 `required_class` 不符（或未选择职业）时移除并记入诊断
+fn resolve_class_restrictions(item: &mut ItemData, class: Option<&str>, diagnostics: &mut Vec<Diagnostic>) {
+    let item_id = &item.id;
+    item.affixes.retain(|affix| {
+        let Some(required) = affix.required_class.as_deref() else {
+            return true;
+        };
+        if class == Some(required) {
+            return true;
+        }
+        diagnostics.push(Diagnostic::new(
+            DiagnosticCode::ClassRestricted,
+            message_params(&[
+                ("item", item_id.clone()),
+                ("affix", affix.id.clone()),
+                ("class", required.to_string()),
+                ("current", class.unwrap_or("-").to_string()),
+            ]),
+        ));
+        false
+    });
 }
 
-/// 7. 计算攻击/施法速率
-fn calculate_rate(pool: &StatPool, skill: &SkillData) -> f64 {
-    let base_time = skill.base_time;
-    if base_time <= 0.0 {
-        return 1.0;
-    }
+/// 校验输入：全局覆盖与条件表达式
+fn validate_input(input: &CalculatorInput) -> Result<(), CalculationError> {
+    validate_overrides(input)?;
+    validate_conditions(input)
+}
 
-    let base_rate = 1.0 / base_time;
+/// 输入中的条件表达式: (来源, 表达式, 是否允许 `stat(...)`)
+///
+/// 包括词缀条件、词缀分支与辅助技能条件注入。词缀分支在聚合阶段选择，此时属性尚未聚合完成，不允许 `stat(...)`
+fn input_conditions(input: &CalculatorInput) -> Vec<(String, &String, bool)> {
+    let affix_conditions = input
+        .items
+        .iter()
+        .chain(input.preview_slot.as_ref().map(|p| &p.item))
+        .flat_map(|item| {
+            item.affixes.iter().flat_map(move |affix| {
+                let source = format!("{}:{}", item.id, affix.id);
+                let branch_source = source.clone();
+                affix
+                    .condition
+                    .iter()
+                    .map(move |expr| (source.clone(), expr, true))
+                    .chain(affix.branches.iter().map(move |branch| (branch_source.clone(), &branch.condition, false)))
+            })
+        });
+    let skill_conditions = std::iter::once(&input.active_skill)
+        .chain(&input.support_skills)
+        .chain(&input.auras)
+        .flat_map(|skill| {
+            skill
+                .conditional_tags
+                .iter()
+                .map(move |tag| (format!("{}:{}", skill.id, tag.tag), &tag.condition, true))
+        });
+    affix_conditions.chain(skill_conditions).collect()
+}
 
-    // 选择攻速还是施法速度
-    let speed_key = if skill.is_attack {
-        "speed.attack"
-    } else {
-        "speed.cast"
-    };
+/// 校验输入中的条件表达式均可解析
+///
+/// 无法解析的条件若放行会被视为不满足而静默失效，此处统一拒绝并报告出错位置、期望的词法单元与拼写提示。
+/// 词缀分支条件中的 `stat(...)` 同样拒绝（见 `input_conditions`）
+fn validate_conditions(input: &CalculatorInput) -> Result<(), CalculationError> {
+    let mut invalid: Vec<String> = input_conditions(input)
+        .into_iter()
+        .filter_map(|(source, expr, allows_stats)| {
+            let error = match Condition::parse(expr) {
+                Err(err) => err.to_string(),
+                Ok(cond) if !allows_stats && !cond.stat_references().is_empty() => {
+                    "stat() is not supported in branch conditions".to_string()
+                }
+                Ok(_) => return None,
+            };
+            Some(format!("{} \"{}\": {}", source, expr, error))
+        })
+        .collect();
+    if invalid.is_empty() {
+        return Ok(());
+    }
+    invalid.sort();
+    Err(CalculationError::InvalidInput(format!("invalid conditions: {}", invalid.join("; "))))
+}
 
-    let speed_inc = pool.get_increased(speed_key);
-    let speed_more = pool.get_more_multiplier(speed_key);
-    
-    // 武器基础攻速（如果是攻击）
-    // 默认武器攻速为 1.0，只有明确设置时才使用设置值
-    let weapon_speed = if skill.is_attack {
-        let base_speed = pool.get_base("weapon.base_speed");
-        if base_speed > 0.0 { base_speed } else { 1.0 }
-    } else {
-        1.0
-    };
+/// 校验全局覆盖：分组名称唯一，表达式（含各分组）均可解析
+fn validate_overrides(input: &CalculatorInput) -> Result<(), CalculationError> {
+    let mut names = HashSet::new();
+    if let Some(group) = input.override_groups.iter().find(|group| !names.insert(group.name.as_str())) {
+        return Err(CalculationError::InvalidInput(format!("duplicate override group '{}'", group.name)));
+    }
 
-    let rate = base_rate * weapon_speed * (1.0 + speed_inc) * speed_more;
+    let all_overrides = std::iter::once(&input.global_overrides)
+        .chain(input.override_groups.iter().map(|group| &group.overrides));
+    let mut invalid: Vec<String> = all_overrides
+        .flatten()
+        .filter_map(|(key, value)| match value {
+            OverrideValue::Expression(expr) => ValueExpr::parse(expr).err().map(|err| format!("{} = \"{}\": {}", key, expr, err)),
+            OverrideValue::Number(_) => None,
+        })
+        .collect();
+    if invalid.is_empty() {
+        return Ok(());
+    }
+    invalid.sort();
+    Err(CalculationError::InvalidInput(format!("invalid override expressions: {}", invalid.join("; "))))
+}
 
-    // 处理冷却限制
-    if let Some(cd) = skill.cooldown {
-        if cd > 0.0 {
-            let cd_rate = 1.0 / cd;
-            return rate.min(cd_rate);
+/// 核心属性的平面值与增加比例（按 `ATTRIBUTE_KEYS` 顺序）
+type AttributeSums = [(f64, f64); 3];
+
+/// 一组属性中的核心属性（别名与单位按录入规则换算）
+fn attribute_sums<'a>(stats: impl IntoIterator<Item = (&'a String, f64)>) -> AttributeSums {
+    let (aliases, units) = (StatAliasTable::shared(), StatUnitTable::shared());
+    let mut sums = [(0.0, 0.0); 3];
+    for (key, value) in stats {
+        let key = aliases.resolve(key).unwrap_or(key);
+        let (key, value) = units.normalize(key, value).unwrap_or((key, value));
+        for (sum, attribute) in sums.iter_mut().zip(ATTRIBUTE_KEYS) {
+            if key == attribute {
+                sum.0 += value;
+            } else if key.strip_prefix("mod.inc.") == Some(attribute) {
+                sum.1 += value;
+            }
         }
     }
-
-    rate
+    sums
 }
 
-struct SpellBurstInfo {
-    m: u32,
-    t_full: f64,
-    t_round: f64,
-    t_cycle: f64,
-    rate_base: f64,
-    rate_burst: f64,
-    playsafe_on: bool,
+fn add_attribute_sums(total: &mut AttributeSums, sums: &AttributeSums) {
+    for (total, sum) in total.iter_mut().zip(sums) {
+        total.0 += sum.0;
+        total.1 += sum.1;
+    }
 }
 
-fn skill_has_tag(skill: &SkillData, tag: &str) -> bool {
-    skill.tags.iter().any(|t| t == tag)
+/// 各装备提供的核心属性（无条件的基底、隐性与词缀属性）与其余来源（角色天赋、数值全局覆盖）的合计
+///
+/// 条件词缀、表达式覆盖与机制加成不计入；只累加属性键，不做完整聚合
+fn attribute_sources(input: &CalculatorInput, items: &[ItemData]) -> (Vec<AttributeSums>, AttributeSums) {
+    let per_item = items
+        .iter()
+        .map(|item| {
+            let affix_stats = item
+                .affixes
+                .iter()
+                .filter(|affix| affix.condition.is_none() && affix.branches.is_empty())
+                .flat_map(|affix| &affix.stats);
+            attribute_sums(
+                item.base_implicit_stats
+                    .iter()
+                    .chain(&item.implicit_stats)
+                    .chain(affix_stats)
+                    .map(|(key, value)| (key, *value)),
+            )
+        })
+        .collect();
+
+    let innate = input
+        .character
+        .as_ref()
+        .and_then(|character| character.class.as_deref())
+        .and_then(|class| CharacterTable::shared().innate_stats(class));
+    let overrides = std::iter::once(&input.global_overrides)
+        .chain(input.override_groups.iter().filter(|group| group.enabled).map(|group| &group.overrides))
+        .flatten()
+        .filter_map(|(key, value)| match value {
+            OverrideValue::Number(value) => Some((key, *value)),
+            OverrideValue::Expression(_) => None,
+        });
+    let others = attribute_sums(innate.into_iter().flatten().map(|(key, value)| (key, *value)).chain(overrides));
+    (per_item, others)
 }
 
-fn compute_spell_burst_charge_params(pool: &StatPool, skill: &SkillData) -> Option<(u32, f64, bool)> {
-    // 基础资格判定
-    if skill.is_attack {
-        return None;
-    }
-    if !skill_has_tag(skill, "Tag_Spell") {
-        return None;
-    }
-    if skill.cooldown.is_some() {
-        return None;
-    }
-    let blocked = ["Tag_Channeling", "Tag_Minion", "Tag_Totem", "Tag_Trap", "Tag_Brand"];
-    if blocked.iter().any(|t| skill_has_tag(skill, t)) {
-        return None;
+/// 装备需求检查：按角色等级（未配置角色时不检查等级）与核心属性总值判定，未满足的需求逐项记入诊断
+///
+/// 核心属性按其余装备、角色天赋与数值全局覆盖合计（装备自身的属性不计入自身需求）。
+/// `exclude_unusable_items` 时移除需求未满足的装备；属性总值只按全部装备判定一次，不随移除迭代
+fn check_item_requirements(input: &CalculatorInput, items: &mut Vec<ItemData>, diagnostics: &mut Vec<Diagnostic>) {
+    if items.iter().all(|item| item.requirements.is_none()) {
+        return;
     }
+    let level = input.character.as_ref().map(|c| c.level as f64);
+    let (per_item, others) = attribute_sources(input, items);
 
-    // 充能时间
-    let mut inc = pool.get_increased("speed.spell_burst_charge");
-    let mut more = pool.get_more_multiplier("speed.spell_burst_charge");
-    let playsafe_on = pool.get_base("flag.talent.playsafe") > 0.0;
-    if playsafe_on {
-        inc += pool.get_increased("speed.cast");
-        more *= pool.get_more_multiplier("speed.cast");
+    let mut unusable = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let Some(req) = &item.requirements else {
+            continue;
+        };
+        // 装备自身提供的属性不计入自身需求
+        let mut sums = others;
+        for (_, item_sums) in per_item.iter().enumerate().filter(|(other, _)| *other != index) {
+            add_attribute_sums(&mut sums, item_sums);
+        }
+        let [strength, dexterity, intelligence] = sums.map(|(flat, increased)| Some(flat * (1.0 + increased)));
+        let checks = [
+            ("level", req.level, level),
+            (STRENGTH, req.strength, strength),
+            (DEXTERITY, req.dexterity, dexterity),
+            (INTELLIGENCE, req.intelligence, intelligence),
+        ];
+        for (requirement, required, actual) in checks {
+            let Some(actual) = actual else {
+                continue;
+            };
+            if (required as f64) <= actual {
+                continue;
+            }
+            diagnostics.push(Diagnostic::new(
+                DiagnosticCode::RequirementUnmet,
+                message_params(&[
+                    ("item", item.id.clone()),
+                    ("requirement", requirement.to_string()),
+                    ("required", required.to_string()),
+                    ("actual", format!("{}", actual.floor())),
+                ]),
+            ));
+            if unusable.last() != Some(&index) {
+                unusable.push(index);
+            }
+        }
     }
-    let denom = (1.0 + inc) * more;
-    if denom <= 0.0 {
-        return None;
+    if input.exclude_unusable_items {
+        let mut index = 0;
+        items.retain(|_| {
+            index += 1;
+            !unusable.contains(&(index - 1))
+        });
     }
-    let t_full = 2.0 / denom;
+}
 
-    // 最大层数 M
-    let m_raw = pool.get_base("mechanic.spell_burst.max_stacks");
-    let m = m_raw.floor() as i32;
-    if m < 1 {
-        return None;
+/// 参与槽位分配的候选装备（按 base_type 补全）: (录入位置, 装备)
+///
+/// 候选顺序：预览装备优先（位置为 `items.len()`），其次双手武器，其余按录入顺序（被预览替换的槽位跳过）
+fn slot_candidates(input: &CalculatorInput) -> Vec<(usize, ItemData)> {
+    let items_meta = ItemsMetaTable::shared();
+    let preview = input.preview_slot.as_ref();
+    let mut candidates: Vec<(usize, ItemData)> = input
+        .items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| preview.map_or(true, |p| item.slot != p.slot_type))
+        .map(|(position, item)| (position, items_meta.resolve(item).into_owned()))
+        .collect();
+    candidates.sort_by_key(|(_, item)| !item.is_two_handed);
+    if let Some(preview) = preview {
+        candidates.insert(0, (input.items.len(), items_meta.resolve(&preview.item).into_owned()));
     }
-    let m_u = m as u32;
-
-    Some((m_u, t_full, playsafe_on))
+    candidates
 }
 
-fn compute_spell_burst_rate(pool: &StatPool, skill: &SkillData, rate_base: f64) -> Option<SpellBurstInfo> {
-    // 复用资格判定 + 充能参数
-    let (m_u, t_full, playsafe_on) = compute_spell_burst_charge_params(pool, skill)?;
-
-    // 0.1s 层间隔
-    let t_round = if m_u <= 1 { 0.0 } else { (m_u - 1) as f64 * 0.1 };
-    let t_cycle = t_full.max(t_round);
-    if t_cycle <= 0.0 {
-        return None;
-    }
-    let rate_burst = m_u as f64 / t_cycle;
+/// 冲突中的阻止者（去重，按接受顺序）
+fn conflict_blockers<'a>(accepted: &'a [(usize, ItemData)], options: &[Vec<usize>]) -> Vec<&'a ItemData> {
+    let mut indices: Vec<usize> = options.iter().flatten().copied().collect();
+    indices.sort_unstable();
+    indices.dedup();
+    indices.iter().map(|&i| &accepted[i].1).collect()
+}
 
-    Some(SpellBurstInfo {
-        m: m_u,
-        t_full,
-        t_round,
-        t_cycle,
-        rate_base,
-        rate_burst,
-        playsafe_on,
-    })
+/// 完整的无冲突装备方案
+#[derive(Debug, Clone, Default)]
+struct SlotPlan {
+    /// 接受的装备: (录入位置, 装备)
+    accepted: Vec<(usize, ItemData)>,
+    /// 被移除装备的诊断
+    evicted: Vec<Diagnostic>,
 }
 
-#[cfg(test)]
-mod spell_burst_tests {
-    use super::*;
-    use std::collections::HashMap;
-    use crate::types::{CalculatorInput, TargetConfig};
+/// `PreferHigherDps` 最多比较的完整装备方案数（达到上限后其余冲突保留已接受的装备）
+const MAX_SLOT_PLANS: usize = 64;
 
-    fn make_spell() -> SkillData {
-        SkillData {
-            id: "test_spell".to_string(),
-            skill_type: SkillType::Active,
-            damage_type: None,
-            is_attack: false,
-            level: 1,
-            base_damage: HashMap::new(),
-            base_time: 0.8,
-            cooldown: None,
-            mana_cost: 0,
-            effectiveness: 1.0,
-            tags: vec!["Tag_Spell".to_string()],
-            stats: HashMap::new(),
-            injected_tags: vec![],
-            mana_multiplier: 1.0,
-            level_data: None,
-            scaling_rules: vec![],
+/// 枚举完整的无冲突装备方案
+///
+/// 每处冲突分支为保留已接受的装备，或任选一种移除方案接受新装备；保留已接受装备的方案排在前面
+fn slot_plans(candidates: &[(usize, ItemData)], slot_definitions: &[SlotDefinition]) -> Vec<SlotPlan> {
+    let mut plans = vec![SlotPlan::default()];
+    for (position, item) in candidates {
+        let pending = plans.len();
+        let mut next = Vec::with_capacity(pending);
+        for (done, mut plan) in plans.into_iter().enumerate() {
+            let Some((conflict, options)) = slot_blockers(&plan.accepted, item, slot_definitions) else {
+                plan.accepted.push((*position, item.clone()));
+                next.push(plan);
+                continue;
+            };
+            let room = MAX_SLOT_PLANS.saturating_sub(next.len() + pending - done);
+            let trials: Vec<SlotPlan> = options
+                .iter()
+                .take(room)
+                .map(|evict| {
+                    let mut trial = plan.clone();
+                    let mut evict = evict.clone();
+                    evict.sort_unstable_by(|a, b| b.cmp(a));
+                    for index in evict {
+                        let (_, evicted) = trial.accepted.remove(index);
+                        trial.evicted.push(slot_conflict_diagnostic(&evicted, conflict, &[item]));
+                    }
+                    trial.accepted.push((*position, item.clone()));
+                    trial
+                })
+                .collect();
+            let diagnostic = slot_conflict_diagnostic(item, conflict, &conflict_blockers(&plan.accepted, &options));
+            plan.evicted.push(diagnostic);
+            next.push(plan);
+            next.extend(trials);
         }
+        plans = next;
     }
+    plans
+}
 
-    fn approx(a: f64, b: f64) -> bool {
-        (a - b).abs() < 1e-6
-    }
-
-    #[test]
-    fn spell_burst_inactive_when_m_zero() {
-        let pool = StatPool::default();
-        let skill = make_spell();
-        let info = compute_spell_burst_rate(&pool, &skill, 1.0 / skill.base_time);
-        assert!(info.is_none());
+/// 只保留方案中装备的输入（预览装备落选时取消预览）
+fn plan_input(input: &CalculatorInput, plan: &SlotPlan) -> CalculatorInput {
+    let accepted: HashSet<usize> = plan.accepted.iter().map(|(position, _)| *position).collect();
+    let mut variant = input.clone();
+    variant.items = input
+        .items
+        .iter()
+        .enumerate()
+        .filter(|(position, _)| accepted.contains(position))
+        .map(|(_, item)| item.clone())
+        .collect();
+    if !accepted.contains(&input.items.len()) {
+        variant.preview_slot = None;
     }
+    variant
+}
 
-    #[test]
-    fn spell_burst_rate_charging_bottleneck() {
-        let mut pool = StatPool::default();
-        pool.add_base("mechanic.spell_burst.max_stacks", 3.0);
-        let skill = make_spell();
-        let base_rate = 1.0 / skill.base_time;
-        let info = compute_spell_burst_rate(&pool, &skill, base_rate).expect("should activate");
-        assert_eq!(info.m, 3);
-        assert!(approx(info.t_full, 2.0));
-        assert!(approx(info.t_round, 0.2));
-        assert!(approx(info.t_cycle, 2.0));
-        assert!(approx(info.rate_burst, 1.5));
+/// 按 `PreferHigherDps` 解决槽位冲突：比较各完整装备方案的理论 DPS，取最高者（持平时保留先接受的装备）
+///
+/// 在 `calculate_raw` / `prepare_context` 入口解析一次，返回移除落选装备后的输入与移除诊断；
+/// 其后各轮计算（技能部分、叠层迭代、持续输出）沿用该输入，不再重复比较
+fn resolve_preferred_slots(
+    input: &CalculatorInput,
+) -> Result<(Cow<'_, CalculatorInput>, Vec<Diagnostic>), CalculationError> {
+    if input.slot_conflict_policy != SlotConflictPolicy::PreferHigherDps {
+        return Ok((Cow::Borrowed(input), Vec::new()));
     }
-
-    #[test]
-    fn spell_burst_rate_interval_bottleneck() {
-        let mut pool = StatPool::default();
-        pool.add_base("mechanic.spell_burst.max_stacks", 3.0);
-        pool.add_increased("speed.spell_burst_charge", 19.0); // t_full = 2 / 20 = 0.1
-        let skill = make_spell();
-        let base_rate = 1.0 / skill.base_time;
-        let info = compute_spell_burst_rate(&pool, &skill, base_rate).expect("should activate");
-        assert!(approx(info.t_full, 0.1));
-        assert!(approx(info.t_round, 0.2));
-        assert!(approx(info.t_cycle, 0.2));
-        assert!(approx(info.rate_burst, 15.0));
+    let mut plans = slot_plans(&slot_candidates(input), &input.slot_definitions);
+    if plans.iter().all(|plan| plan.evicted.is_empty()) {
+        return Ok((Cow::Borrowed(input), Vec::new()));
     }
 
-    #[test]
-    fn spell_burst_play_safe_accelerates_charge() {
-        let mut pool = StatPool::default();
-        pool.add_base("mechanic.spell_burst.max_stacks", 3.0);
-        pool.add_base("flag.talent.playsafe", 1.0);
-        pool.add_increased("speed.cast", 0.5); // +50% 施法速度
-        let skill = make_spell();
-        let base_rate = 1.0 / skill.base_time;
-        let info = compute_spell_burst_rate(&pool, &skill, base_rate).expect("should activate");
-        // t_full = 2 / 1.5 = 1.333...
-        assert!(approx(info.t_full, 1.3333333333));
-        assert!(info.rate_burst > 1.5); // faster than无 playsafe baseline(1.5)
-        assert!(info.playsafe_on);
+    let mut best = 0;
+    let mut best_dps = f64::NEG_INFINITY;
+    for (index, plan) in plans.iter().enumerate() {
+        let dps = calculate_unsustained(&plan_input(input, plan))?.dps_theoretical;
+        if dps > best_dps {
+            best = index;
+            best_dps = dps;
+        }
     }
+    let plan = plans.swap_remove(best);
+    Ok((Cow::Owned(plan_input(input, &plan)), plan.evicted))
+}
 
-    #[test]
-    fn spell_burst_blocked_by_cooldown() {
-        let mut skill = make_spell();
-        skill.cooldown = Some(1.0);
-        let pool = StatPool::default();
-        let info = compute_spell_burst_rate(&pool, &skill, 1.0 / skill.base_time);
-        assert!(info.is_none());
+/// 1. Sanitization & Slot Conflict
+///
+/// 槽位容量与冲突组按槽位注册表处理（`slot_definitions` 覆盖内置定义），
+/// 冲突按 `slot_conflict_policy` 处理，被移除的装备逐件记入诊断
+/// （`PreferHigherDps` 须先经 `resolve_preferred_slots` 按完整方案取舍）
+fn sanitize_items(
+    input: &CalculatorInput,
+    sanitizer: &mut RangeSanitizer,
+) -> Result<(Vec<ItemData>, Vec<Diagnostic>), CalculationError> {
+    let slot_definitions = &input.slot_definitions;
+    let class = selected_class(input)?;
+
+    // 未登记的自定义槽位多为拼写错误（如旧版 ID），不静默当作新槽位
+    let slot_registry = SlotRegistry::shared();
+    let unregistered: Vec<String> = input
+        .items
+        .iter()
+        .map(|item| (&item.id, &item.slot))
+        .chain(input.preview_slot.as_ref().map(|p| (&p.item.id, &p.slot_type)))
+        .filter(|(_, slot)| !slot_registry.is_registered(slot, slot_definitions))
+        .map(|(id, slot)| format!("{} ({})", id, slot.id()))
+        .collect();
+    if !unregistered.is_empty() {
+        return Err(CalculationError::InvalidInput(format!("unknown slots: {}", unregistered.join(", "))));
     }
 
-    #[test]
-    fn spell_burst_trigger_flag_zero_rate_when_m_lt_1() {
-        // use_spell_burst = true 且 M < 1 → rate = 0, DPS = 0
-        let input = CalculatorInput {
-            context_flags: HashMap::from([("use_spell_burst".to_string(), true)]),
-            context_values: HashMap::new(),
-            target_config: TargetConfig::default(),
-            items: vec![],
-            active_skill: SkillData {
-                base_time: 1.0,
-                base_damage: HashMap::from([
-                    ("dmg.cold.min".to_string(), 10.0),
-                    ("dmg.cold.max".to_string(), 20.0),
-                ]),
-                tags: vec!["Tag_Spell".to_string()],
-                ..make_spell()
-            },
-            support_skills: vec![],
-            global_overrides: HashMap::new(), // M 默认为 0
-            preview_slot: None,
-            mechanic_states: vec![],
-            mechanic_definitions: vec![],
+    // 按 base_type 补全基底属性（双手判定依赖补全结果）
+    let mut accepted: Vec<(usize, ItemData)> = Vec::with_capacity(input.items.len() + 1);
+    let mut diagnostics = Vec::new();
+    let mut errors = Vec::new();
+    for (position, item) in slot_candidates(input) {
+        let Some((conflict, options)) = slot_blockers(&accepted, &item, slot_definitions) else {
+            accepted.push((position, item));
+            continue;
         };
-
-        let result = calculate_dps(&input).expect("calc ok");
-        assert_eq!(result.rate, 0.0);
-        assert_eq!(result.dps_theoretical, 0.0);
+        let blockers = conflict_blockers(&accepted, &options);
+
+        match input.slot_conflict_policy {
+            SlotConflictPolicy::Error => {
+                let blockers: Vec<&str> = blockers.iter().map(|i| i.id.as_str()).collect();
+                errors.push(format!(
+                    "{} ({}) conflicts with {} [{}]",
+                    item.id,
+                    item.slot.id(),
+                    blockers.join(", "),
+                    conflict.as_key()
+                ));
+            }
+            // PreferHigherDps 的冲突已在入口按完整方案解决（见 `resolve_preferred_slots`），此处保留已接受的装备
+            SlotConflictPolicy::PreferPreview | SlotConflictPolicy::PreferHigherDps => {
+                diagnostics.push(slot_conflict_diagnostic(&item, conflict, &blockers));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(CalculationError::InvalidInput(format!("slot conflicts: {}", errors.join("; "))));
     }
 
-    #[test]
-    fn spell_burst_trigger_flag_uses_m_over_t_full() {
-        // use_spell_burst = true 且 M=3, t_full=2s → rate = 1.5/s
-        let input = CalculatorInput {
-            context_flags: HashMap::from([("use_spell_burst".to_string(), true)]),
-            context_values: HashMap::new(),
-            target_config: TargetConfig::default(),
-            items: vec![],
-            active_skill: SkillData {
-                base_time: 1.0, // 基础 rate = 1/s (仅用于 trace)
-                base_damage: HashMap::from([
-                    ("dmg.cold.min".to_string(), 10.0),
-                    ("dmg.cold.max".to_string(), 20.0),
-                ]),
-                tags: vec!["Tag_Spell".to_string()],
-                ..make_spell()
-            },
-            support_skills: vec![],
-            global_overrides: HashMap::from([
-                ("mechanic.spell_burst.max_stacks".to_string(), 3.0), // M = 3
-                ("speed.spell_burst_charge".to_string(), 0.0),        // t_full = 2 / 1 = 2s
-            ]),
-            preview_slot: None,
-            mechanic_states: vec![],
-            mechanic_definitions: vec![],
-        };
+    // 恢复录入顺序（预览装备在最后）
+    accepted.sort_by_key(|(position, _)| *position);
+    let mut result: Vec<ItemData> = accepted.into_iter().map(|(_, item)| item).collect();
+    for item in &mut result {
+        resolve_class_restrictions(item, class, &mut diagnostics);
+        resolve_affix_groups(item, &mut diagnostics);
+        sanitize_item_stats(item, sanitizer);
+    }
+    resolve_memory_affix_groups(&mut result, &mut diagnostics);
+    check_item_requirements(input, &mut result, &mut diagnostics);
+    Ok((result, diagnostics))
+}
 
-        let result = calculate_dps(&input).expect("calc ok");
-        // 期望 rate = 3 / 2 = 1.5
+/// 获取技能在指定等级的有效数据
+/// 
+/// 逻辑：
+/// - 1-20级：使用 level_data 中的具体数据
+/// - 21-30级：使用20级数据 + 每级叠乘 1.10 (默认)
+/// - 31级及以上：使用30级数据 + 每级叠乘 1.08 (默认)
+/// - 未提供 level_data 时按技能 ID 查内嵌等级表（见 `skills_registry.json`）
+/// - `level_bonus`：装备等提供的技能等级加成，叠加在技能自身等级上
+fn get_skill_effective_data(skill: &SkillData, level_bonus: u32) -> (HashMap<String, f64>, f64, f64) {
+    let level = skill.level + level_bonus;
+    let level_data = match &skill.level_data {
+        Some(data) => Some(data),
+        None => SkillRegistry::shared().level_data(&skill.id, level),
+    };
+    
+    // 如果有等级数据，使用它
+    if let Some(level_data) = level_data {
+        let base_damage = level_data.base_damage.clone();
+        let effectiveness = level_data.effectiveness;
+        
+        // 计算等级缩放乘数
+        let level_multiplier = calculate_level_scaling(level, &skill.scaling_rules);
+        
+        return (base_damage, effectiveness, level_multiplier);
+    }
+    
+    // 否则使用默认数据
+    let base_damage = skill.base_damage.clone();
+    let effectiveness = skill.effectiveness;
+    let level_multiplier = calculate_level_scaling(level, &skill.scaling_rules);
+    
+    (base_damage, effectiveness, level_multiplier)
+}
+
+/// 汇总装备等提供的技能等级加成
+///
+/// 带标签要求的加成（如"+1 火焰技能等级"）仅在技能拥有对应标签时生效
+fn skill_level_bonus(mod_db: &ModDB, ctx: &EvalContext, context: &ContextTags) -> u32 {
+    let registry = context.registry();
+    let total: f64 = SKILL_LEVEL_KEYS
+        .iter()
+        .filter(|(_, requirements)| {
+            requirements.iter().all(|tag| {
+                registry
+                    .get_id(tag)
+                    .is_some_and(|id| context.active_set().contains(id))
+            })
+        })
+        .map(|(key, _)| mod_db.sum_base_with_ctx(key, ctx))
+        .sum();
+    total.max(0.0).floor() as u32
+}
+
+/// 计算技能等级缩放乘数
+/// 
+/// 默认规则：
+/// - 1-20级：无额外缩放 (multiplier = 1.0)
+/// - 21-30级：每级 +10% (叠乘)
+/// - 31级及以上：每级 +8% (叠乘)
+fn calculate_level_scaling(level: u32, rules: &[SkillScalingRule]) -> f64 {
+    if level <= 20 {
+        return 1.0;
+    }
+    
+    let mut multiplier = 1.0;
+    
+    // 使用自定义规则
+    if !rules.is_empty() {
+        for rule in rules {
+            if level >= rule.level_start {
+                let end = rule.level_end.unwrap_or(u32::MAX);
+                if level <= end {
+                    let levels_in_range = (level - rule.level_start + 1).min(
+                        end.saturating_sub(rule.level_start) + 1
+                    );
+                    multiplier *= rule.multiplier_per_level.powi(levels_in_range as i32);
+                } else {
+                    // 完整应用此区间
+                    let levels_in_range = end - rule.level_start + 1;
+                    multiplier *= rule.multiplier_per_level.powi(levels_in_range as i32);
+                }
+            }
+        }
+    } else {
+        // 使用默认规则
+        // 21-30级：每级 +10%
+        if level > 20 {
+            let levels_21_30 = (level.min(30) - 20) as i32;
+            multiplier *= 1.10_f64.powi(levels_21_30);
+        }
+        // 31级及以上：每级 +8%
+        if level > 30 {
+            let levels_31_plus = (level - 30) as i32;
+            multiplier *= 1.08_f64.powi(levels_31_plus);
+        }
+    }
+    
+    multiplier
+}
+
+/// 3. 计算基础伤害
+fn calculate_base_damage(
+    mod_db: &ModDB,
+    eval_ctx: &EvalContext,
+    skill: &SkillData,
+    level_bonus: u32,
+) -> HashMap<DamageType, (f64, f64)> {
+    let mut base = HashMap::new();
+    
+    // 获取等级有效数据
+    let (base_damage_map, _effectiveness, level_multiplier) = get_skill_effective_data(skill, level_bonus);
+
+    // 从技能获取基础伤害
+    for (key, value) in &base_damage_map {
+        if key.contains("phys") {
+            let entry = base.entry(DamageType::Physical).or_insert((0.0, 0.0));
+            if key.contains("min") {
+                entry.0 += value;
+            } else if key.contains("max") {
+                entry.1 += value;
+            }
+        } else if key.contains("fire") {
+            let entry = base.entry(DamageType::Fire).or_insert((0.0, 0.0));
+            if key.contains("min") {
+                entry.0 += value;
+            } else if key.contains("max") {
+                entry.1 += value;
+            }
+        } else if key.contains("cold") {
+            let entry = base.entry(DamageType::Cold).or_insert((0.0, 0.0));
+            if key.contains("min") {
+                entry.0 += value;
+            } else if key.contains("max") {
+                entry.1 += value;
+            }
+        } else if key.contains("lightning") {
+            let entry = base.entry(DamageType::Lightning).or_insert((0.0, 0.0));
+            if key.contains("min") {
+                entry.0 += value;
+            } else if key.contains("max") {
+                entry.1 += value;
+            }
+        } else if key.contains("chaos") {
+            let entry = base.entry(DamageType::Chaos).or_insert((0.0, 0.0));
+            if key.contains("min") {
+                entry.0 += value;
+            } else if key.contains("max") {
+                entry.1 += value;
+            }
+        }
+    }
+
+    // 对于攻击技能，使用武器伤害（局部结算后的 weapon.dmg.*）与全局附加物理伤害
+    if skill.is_attack {
+        let global_phys = (
+            mod_db.sum_base_with_ctx("dmg.phys.min", eval_ctx),
+            mod_db.sum_base_with_ctx("dmg.phys.max", eval_ctx),
+        );
+        for dtype in DamageType::all_ordered() {
+            let segment = match dtype {
+                DamageType::Physical => "phys",
+                other => other.as_key(),
+            };
+            let (mut min, mut max) = (
+                mod_db.sum_base_with_ctx(&format!("weapon.dmg.{}.min", segment), eval_ctx),
+                mod_db.sum_base_with_ctx(&format!("weapon.dmg.{}.max", segment), eval_ctx),
+            );
+            if *dtype == DamageType::Physical {
+                min += global_phys.0;
+                max += global_phys.1;
+            }
+            if min > 0.0 || max > 0.0 {
+                let entry = base.entry(*dtype).or_insert((0.0, 0.0));
+                entry.0 += min;
+                entry.1 += max;
+            }
+        }
+    }
+
+    // 应用等级缩放乘数 (21级及以上的 More 乘数)
+    if level_multiplier > 1.0 {
+    for (_, (min, max)) in base.iter_mut() {
+            *min *= level_multiplier;
+            *max *= level_multiplier;
+        }
+    }
+
+    base
+}
+
+/// 6. 应用 Inc/More 修正（带标签匹配）
+///
+/// 伤害包的历史标签（展开继承后）与 ModDB 中修正的命中标签匹配，
+/// 技能类修正的标签要求与上下文标签匹配，作用域定义见 `DAMAGE_MOD_SCOPES`。
+/// 每个伤害包按自身历史标签单独修正，修正后再按伤害类型合并。
+///
+/// 同时返回标签匹配记录（每种伤害类型命中的属性键及其匹配的历史/上下文标签），供调试追踪审计
+fn apply_modifications(
+    damage_pool: &[DamagePacket],
+    mod_db: &ModDB,
+    eval_ctx: &EvalContext,
+    context: &ContextTags,
+    floor: IncreasedFloor,
+) -> (HashMap<DamageType, DamageWithTags>, Vec<String>) {
+    let mut result = HashMap::new();
+    let mut matched_tags = Vec::new();
+    let registry = context.registry();
+    let active_set = context.active_set().bits();
+
+    for (dtype, dmg) in damage_pool {
+        if dmg.is_zero() {
+            continue;
+        }
+
+        let mut modified = dmg.clone();
+
+        // 伤害包标签：历史标签 + 继承展开（如 Fire → Elemental）
+        let history_ids: Vec<u32> = dmg.history_tags.ones().map(|i| i as u32).collect();
+        let hit_set = registry.create_set_from_ids(&history_ids);
+
+        // 收集所有命中的 Inc / More 修正（每个历史类型仅计一次，避免转化后重复叠乘）
+        // More 跨属性键按 bucket 合并，叠加型 bucket 内的不同键先求和
+        let mut total_inc = 0.0;
+        let mut more_mods = Vec::new();
+        for (key, hit_tags, requirements) in DAMAGE_MOD_SCOPES {
+            let mut matched = false;
+            for m in mod_db.get_by_kind(key, ModifierKind::Increased) {
+                if m.matches_tags(&hit_set, active_set) && m.check_condition(eval_ctx) {
+                    total_inc += m.effective_value(eval_ctx);
+                    matched = true;
+                }
+            }
+            let more_count = more_mods.len();
+            more_mods.extend(
+                mod_db
+                    .get_by_kind(key, ModifierKind::More)
+                    .into_iter()
+                    .filter(|m| m.matches_tags(&hit_set, active_set) && m.check_condition(eval_ctx))
+                    .map(|m| (m, m.effective_value(eval_ctx))),
+            );
+            if matched || more_mods.len() > more_count {
+                matched_tags.push(tag_match_label(*dtype, key, hit_tags, requirements));
+            }
+        }
+        let inc_multiplier = 1.0 + floor.apply(total_inc);
+        let more_multiplier = combine_more(more_mods);
+
+        // 最小/最大拉伸：全局与各类型拉伸同样按历史标签匹配，
+        // 转化后的伤害仍享受源类型的拉伸，多个来源按 bucket 合并
+        let mut stretch_min_mods = Vec::new();
+        let mut stretch_max_mods = Vec::new();
+        for (min_key, max_key, _) in DAMAGE_STRETCH_SCOPES {
+            for (key, mods) in [(min_key, &mut stretch_min_mods), (max_key, &mut stretch_max_mods)] {
+                mods.extend(
+                    mod_db
+                        .get_by_kind(key, ModifierKind::More)
+                        .into_iter()
+                        .filter(|m| m.matches_tags(&hit_set, active_set) && m.check_condition(eval_ctx))
+                        .map(|m| (m, m.effective_value(eval_ctx))),
+                );
+            }
+        }
+        let stretch_min = combine_more(stretch_min_mods);
+        let stretch_max = combine_more(stretch_max_mods);
+
+        // 应用所有修正
+        modified.min *= inc_multiplier * more_multiplier * stretch_min;
+        modified.max *= inc_multiplier * more_multiplier * stretch_max;
+
+        // 同类型的伤害包在修正后合并
+        result
+            .entry(*dtype)
+            .or_insert_with(|| DamageWithTags::zero(modified.history_tags.len()))
+            .merge(&modified);
+    }
+
+    matched_tags.sort_unstable();
+    matched_tags.dedup();
+    (result, matched_tags)
+}
+
+/// 标签匹配记录：`<伤害类型>: <属性键> [hit: <历史标签>, context: <上下文标签>]`
+fn tag_match_label(dtype: DamageType, key: &str, hit_tags: &[&str], requirements: &[&str]) -> String {
+    let mut parts = Vec::with_capacity(2);
+    if !hit_tags.is_empty() {
+        parts.push(format!("hit: {}", hit_tags.join("+")));
+    }
+    if !requirements.is_empty() {
+        parts.push(format!("context: {}", requirements.join("+")));
+    }
+    if parts.is_empty() {
+        format!("{}: {}", dtype.as_key(), key)
+    } else {
+        format!("{}: {} [{}]", dtype.as_key(), key, parts.join(", "))
+    }
+}
+
+/// Inc/More 修正阶段的追踪条目（各类型修正后平均伤害 + 标签匹配记录）
+fn modification_trace(modified_damages: &HashMap<DamageType, DamageWithTags>, matched_tags: Vec<String>) -> TraceEntry {
+    let mut entry = TraceEntry::localized(
+        "Modification",
+        "trace.modification",
+        MessageParams::new(),
+        modified_damages
+            .iter()
+            .map(|(k, v)| (k.as_key().to_string(), v.average()))
+            .collect(),
+    );
+    entry.matched_tags = matched_tags;
+    entry
+}
+
+/// 暴击追踪条目
+fn critical_trace(crit_chance: f64, crit_multiplier: f64, crit_factor: f64) -> TraceEntry {
+    TraceEntry::localized(
+        "Critical",
+        "trace.critical",
+        message_params(&[
+            ("chance", format!("{:.1}", crit_chance * 100.0)),
+            ("multiplier", format!("{:.1}", crit_multiplier * 100.0)),
+        ]),
+        [
+            ("crit_chance".to_string(), crit_chance),
+            ("crit_multiplier".to_string(), crit_multiplier),
+            ("crit_factor".to_string(), crit_factor),
+        ]
+        .into_iter()
+        .collect(),
+    )
+}
+
+/// 转化率超过 100% 时被缩放的来源（无缩放时返回 None）
+fn conversion_scaling_trace(plan: &ConversionPlan) -> Option<TraceEntry> {
+    if plan.scaled.is_empty() {
+        return None;
+    }
+    Some(TraceEntry::localized(
+        "Conversion",
+        "trace.conversion_scaled",
+        MessageParams::new(),
+        plan.scaled
+            .iter()
+            .map(|s| {
+                let label = format!("{} ({}->{})", s.source, s.from.as_key(), s.to.as_key());
+                (label, s.applied)
+            })
+            .collect(),
+    ))
+}
+
+/// 转化率超过 100% 的诊断（列出被缩放的来源）
+fn conversion_scaling_diagnostic(plan: &ConversionPlan) -> Diagnostic {
+    let sources: Vec<String> = plan
+        .scaled
+        .iter()
+        .map(|s| format!("{} ({}->{})", s.source, s.from.as_key(), s.to.as_key()))
+        .collect();
+    Diagnostic::new(DiagnosticCode::ConversionScaled, message_params(&[("sources", sources.join(", "))]))
+}
+
+/// 武器基础攻速（次/秒），仅攻击技能且装备了提供局部攻速的武器时存在
+fn weapon_attack_rate(mod_db: &ModDB, eval_ctx: &EvalContext, is_attack: bool) -> Option<f64> {
+    if !is_attack {
+        return None;
+    }
+    let base_speed = mod_db.base_or_override_with_ctx("weapon.base_speed", eval_ctx);
+    (base_speed > 0.0).then_some(base_speed)
+}
+
+/// 7. 计算攻击/施法速率
+///
+/// 攻击技能装备武器时按武器基础攻速（1 / 武器攻击时间）计算，不使用技能的 `base_time`
+///
+/// `calculate_dps` 与 `calculate_from_prepared`（按技能快照）共用
+fn calculate_rate(
+    mod_db: &ModDB,
+    eval_ctx: &EvalContext,
+    is_attack: bool,
+    base_time: f64,
+    cooldown: Option<f64>,
+    floor: IncreasedFloor,
+) -> f64 {
+    let base_rate = match weapon_attack_rate(mod_db, eval_ctx, is_attack) {
+        Some(rate) => rate,
+        None if base_time <= 0.0 => return 1.0,
+        None => 1.0 / base_time,
+    };
+
+    speed_adjusted_rate(base_rate, mod_db, eval_ctx, is_attack, cooldown, floor)
+}
+
+/// 基础速率计入攻速/施法速度加成与冷却限制
+fn speed_adjusted_rate(
+    base_rate: f64,
+    mod_db: &ModDB,
+    eval_ctx: &EvalContext,
+    is_attack: bool,
+    cooldown: Option<f64>,
+    floor: IncreasedFloor,
+) -> f64 {
+    // 选择攻速还是施法速度
+    let speed_key = if is_attack {
+        "speed.attack"
+    } else {
+        "speed.cast"
+    };
+
+    let speed_inc = mod_db.sum_inc_with_ctx(speed_key, eval_ctx);
+    let speed_more = mod_db.product_more_with_ctx(speed_key, eval_ctx);
+
+    let rate = base_rate * (1.0 + floor.apply(speed_inc)) * speed_more;
+
+    // 处理冷却限制
+    if let Some(cd) = cooldown {
+        if cd > 0.0 {
+            let cd_rate = 1.0 / cd;
+            return rate.min(cd_rate);
+        }
+    }
+
+    rate
+}
+
+struct SpellBurstInfo {
+    m: u32,
+    t_full: f64,
+    t_round: f64,
+    t_cycle: f64,
+    rate_base: f64,
+    rate_burst: f64,
+    playsafe_on: bool,
+}
+
+fn skill_has_tag(skill: &SkillData, tag: &str) -> bool {
+    skill.tags.iter().any(|t| t == tag)
+}
+
+fn compute_spell_burst_charge_params(
+    mod_db: &ModDB,
+    eval_ctx: &EvalContext,
+    skill: &SkillData,
+    floor: IncreasedFloor,
+) -> Option<(u32, f64, bool)> {
+    // 基础资格判定
+    if skill.is_attack {
+        return None;
+    }
+    if !skill_has_tag(skill, "Tag_Spell") {
+        return None;
+    }
+    if skill.cooldown.is_some() {
+        return None;
+    }
+    let blocked = ["Tag_Channeling", "Tag_Minion", "Tag_Totem", "Tag_Trap", "Tag_Brand"];
+    if blocked.iter().any(|t| skill_has_tag(skill, t)) {
+        return None;
+    }
+
+    // 充能时间
+    let mut inc = mod_db.sum_inc_with_ctx("speed.spell_burst_charge", eval_ctx);
+    let mut more = mod_db.product_more_with_ctx("speed.spell_burst_charge", eval_ctx);
+    let playsafe_on = mod_db.has_flag_with_ctx("flag.talent.playsafe", eval_ctx);
+    if playsafe_on {
+        inc += mod_db.sum_inc_with_ctx("speed.cast", eval_ctx);
+        more *= mod_db.product_more_with_ctx("speed.cast", eval_ctx);
+    }
+    let denom = (1.0 + floor.apply(inc)) * more;
+    if denom <= 0.0 {
+        return None;
+    }
+    let t_full = 2.0 / denom;
+
+    // 最大层数 M
+    let m_raw = mod_db.base_or_override_with_ctx("mechanic.spell_burst.max_stacks", eval_ctx);
+    let m = m_raw.floor() as i32;
+    if m < 1 {
+        return None;
+    }
+    let m_u = m as u32;
+
+    Some((m_u, t_full, playsafe_on))
+}
+
+fn compute_spell_burst_rate(
+    mod_db: &ModDB,
+    eval_ctx: &EvalContext,
+    skill: &SkillData,
+    rate_base: f64,
+    floor: IncreasedFloor,
+) -> Option<SpellBurstInfo> {
+    // 复用资格判定 + 充能参数
+    let (m_u, t_full, playsafe_on) = compute_spell_burst_charge_params(mod_db, eval_ctx, skill, floor)?;
+
+    // 0.1s 层间隔
+    let t_round = if m_u <= 1 { 0.0 } else { (m_u - 1) as f64 * 0.1 };
+    let t_cycle = t_full.max(t_round);
+    if t_cycle <= 0.0 {
+        return None;
+    }
+    let rate_burst = m_u as f64 / t_cycle;
+
+    Some(SpellBurstInfo {
+        m: m_u,
+        t_full,
+        t_round,
+        t_cycle,
+        rate_base,
+        rate_burst,
+        playsafe_on,
+    })
+}
+
+#[cfg(test)]
+mod spell_burst_tests {
+    use super::*;
+    use crate::modifiers::Modifier;
+    use std::collections::HashMap;
+    use crate::types::CalculatorInput;
+
+    fn make_spell() -> SkillData {
+        SkillData {
+            id: "test_spell".to_string(),
+            base_time: 0.8,
+            tags: vec!["Tag_Spell".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn spell_burst_inactive_when_m_zero() {
+        let db = ModDB::new();
+        let skill = make_spell();
+        let info = compute_spell_burst_rate(&db, &EvalContext::default(), &skill, 1.0 / skill.base_time, IncreasedFloor::default());
+        assert!(info.is_none());
+    }
+
+    #[test]
+    fn spell_burst_rate_charging_bottleneck() {
+        let mut db = ModDB::new();
+        db.add(Modifier::base("mechanic.spell_burst.max_stacks", 3.0, "test"));
+        let skill = make_spell();
+        let base_rate = 1.0 / skill.base_time;
+        let info = compute_spell_burst_rate(&db, &EvalContext::default(), &skill, base_rate, IncreasedFloor::default()).expect("should activate");
+        assert_eq!(info.m, 3);
+        assert!(approx(info.t_full, 2.0));
+        assert!(approx(info.t_round, 0.2));
+        assert!(approx(info.t_cycle, 2.0));
+        assert!(approx(info.rate_burst, 1.5));
+    }
+
+    #[test]
+    fn spell_burst_rate_interval_bottleneck() {
+        let mut db = ModDB::new();
+        db.add(Modifier::base("mechanic.spell_burst.max_stacks", 3.0, "test"));
+        db.add(Modifier::inc("speed.spell_burst_charge", 19.0, "test")); // t_full = 2 / 20 = 0.1
+        let skill = make_spell();
+        let base_rate = 1.0 / skill.base_time;
+        let info = compute_spell_burst_rate(&db, &EvalContext::default(), &skill, base_rate, IncreasedFloor::default()).expect("should activate");
+        assert!(approx(info.t_full, 0.1));
+        assert!(approx(info.t_round, 0.2));
+        assert!(approx(info.t_cycle, 0.2));
+        assert!(approx(info.rate_burst, 15.0));
+    }
+
+    #[test]
+    fn spell_burst_play_safe_accelerates_charge() {
+        let mut db = ModDB::new();
+        db.add(Modifier::base("mechanic.spell_burst.max_stacks", 3.0, "test"));
+        db.add(Modifier::flag("flag.talent.playsafe", "test"));
+        db.add(Modifier::inc("speed.cast", 0.5, "test")); // +50% 施法速度
+        let skill = make_spell();
+        let base_rate = 1.0 / skill.base_time;
+        let info = compute_spell_burst_rate(&db, &EvalContext::default(), &skill, base_rate, IncreasedFloor::default()).expect("should activate");
+        // t_full = 2 / 1.5 = 1.333...
+        assert!(approx(info.t_full, 1.3333333333));
+        assert!(info.rate_burst > 1.5); // faster than无 playsafe baseline(1.5)
+        assert!(info.playsafe_on);
+    }
+
+    #[test]
+    fn spell_burst_blocked_by_cooldown() {
+        let mut skill = make_spell();
+        skill.cooldown = Some(1.0);
+        let db = ModDB::new();
+        let info = compute_spell_burst_rate(&db, &EvalContext::default(), &skill, 1.0 / skill.base_time, IncreasedFloor::default());
+        assert!(info.is_none());
+    }
+
+    #[test]
+    fn spell_burst_trigger_flag_zero_rate_when_m_lt_1() {
+        // use_spell_burst = true 且 M < 1 → rate = 0, DPS = 0
+        let input = CalculatorInput {
+            context_flags: HashMap::from([("use_spell_burst".to_string(), true)]),
+            active_skill: SkillData {
+                base_time: 1.0,
+                base_damage: HashMap::from([
+                    ("dmg.cold.min".to_string(), 10.0),
+                    ("dmg.cold.max".to_string(), 20.0),
+                ]),
+                tags: vec!["Tag_Spell".to_string()],
+                ..make_spell()
+            },
+            global_overrides: HashMap::new(), // M 默认为 0
+            ..Default::default()
+        };
+
+        let result = calculate_dps(&input).expect("calc ok");
+        assert_eq!(result.rate, 0.0);
+        assert_eq!(result.dps_theoretical, 0.0);
+    }
+
+    #[test]
+    fn spell_burst_trigger_flag_uses_m_over_t_full() {
+        // use_spell_burst = true 且 M=3, t_full=2s → rate = 1.5/s
+        let input = CalculatorInput {
+            context_flags: HashMap::from([("use_spell_burst".to_string(), true)]),
+            active_skill: SkillData {
+                base_time: 1.0, // 基础 rate = 1/s (仅用于 trace)
+                base_damage: HashMap::from([
+                    ("dmg.cold.min".to_string(), 10.0),
+                    ("dmg.cold.max".to_string(), 20.0),
+                ]),
+                tags: vec!["Tag_Spell".to_string()],
+                ..make_spell()
+            },
+            global_overrides: HashMap::from([
+                ("mechanic.spell_burst.max_stacks".to_string(), 3.0.into()), // M = 3
+                ("speed.spell_burst_charge".to_string(), 0.0.into()),        // t_full = 2 / 1 = 2s
+            ]),
+            ..Default::default()
+        };
+
+        let result = calculate_dps(&input).expect("calc ok");
+        // 期望 rate = 3 / 2 = 1.5
         assert!((result.rate - 1.5).abs() < 1e-6);
         // 基础均值 15，rate=1.5 → dps≈22.5（无暴击/增伤）
         assert!(result.dps_theoretical > 20.0 && result.dps_theoretical < 25.0);
     }
 
     #[test]
-    fn chain_lightning_spell_burst_full_scenario() {
-        // 场景：链闪 + 闪转冰 + 灵能乍泄 + 旧律 + 苦寒/积聚/世事无常/Beacon/PlaySafe
-        // 前提：use_spell_burst 开启；施法速度总加成为 100%（global），灵能乍泄再叠加 16%。
-        let input = CalculatorInput {
-            context_flags: HashMap::from([
-                ("lucky_damage".to_string(), false),
-                ("cannot_crit".to_string(), false),
-                ("use_spell_burst".to_string(), true),
-            ]),
-            context_values: HashMap::new(),
-            target_config: TargetConfig::default(),
-            items: vec![ItemData {
-                id: "equip_legend_116".to_string(),
-                base_type: "gloves_all_magic_grip".to_string(),
-                slot: SlotType::Gloves,
-                is_two_handed: false,
-                base_implicit_stats: HashMap::from([("base.es".to_string(), 527.0)]),
-                implicit_stats: HashMap::from([
-                    ("mod.more.dmg.cold.per_focus_blessing".to_string(), 0.19),
-                    ("mod.inc.crit.dmg.per_focus_blessing".to_string(), 0.04),
-                    ("blessing.duration".to_string(), 0.40),
-                ]),
-                affixes: vec![],
-                tags: vec!["Tag_Armor".to_string(), "Tag_Gloves".to_string(), "Tag_Cold".to_string()],
-                is_unique: true,
-                is_corrupted: true,
-            }],
-            active_skill: SkillData {
-                id: "skill_chain_lightning".to_string(),
-                skill_type: SkillType::Active,
-                damage_type: Some("lightning".to_string()),
-                is_attack: false,
-                level: 21,
-                base_damage: HashMap::from([
-                    ("dmg.lightning.min".to_string(), 95.0),
-                    ("dmg.lightning.max".to_string(), 1811.0),
-                ]),
-                base_time: 0.65,
-                cooldown: None,
-                mana_cost: 8,
-                effectiveness: 1.0,
-                tags: vec![
-                    "Tag_Spell".to_string(),
-                    "Tag_Lightning".to_string(),
-                    "Tag_Chain".to_string(),
-                    "Tag_Burst".to_string(),
-                ],
-                stats: HashMap::new(),
-                injected_tags: vec![],
-                mana_multiplier: 1.0,
-                level_data: None,
-                scaling_rules: vec![],
+    fn chain_lightning_spell_burst_full_scenario() {
+        // 场景：链闪 + 闪转冰 + 灵能乍泄 + 旧律 + 苦寒/积聚/世事无常/Beacon/PlaySafe
+        // 前提：use_spell_burst 开启；施法速度总加成为 100%（global），灵能乍泄再叠加 16%。
+        let input = CalculatorInput {
+            context_flags: HashMap::from([
+                ("lucky_damage".to_string(), false),
+                ("cannot_crit".to_string(), false),
+                ("use_spell_burst".to_string(), true),
+            ]),
+            items: vec![ItemData {
+                base_implicit_stats: HashMap::from([("base.es".to_string(), 527.0)]),
+                implicit_stats: HashMap::from([
+                    ("mod.more.dmg.cold.per_focus_blessing".to_string(), 0.19),
+                    ("mod.inc.crit.dmg.per_focus_blessing".to_string(), 0.04),
+                    ("blessing.duration".to_string(), 0.40),
+                ]),
+                tags: vec!["Tag_Armor".to_string(), "Tag_Gloves".to_string(), "Tag_Cold".to_string()],
+                is_unique: true,
+                is_corrupted: true,
+                ..ItemData::new("equip_legend_116", "gloves_all_magic_grip", SlotType::Gloves)
+            }],
+            active_skill: SkillData {
+                id: "skill_chain_lightning".to_string(),
+                damage_type: Some("lightning".to_string()),
+                level: 21,
+                base_damage: HashMap::from([
+                    ("dmg.lightning.min".to_string(), 95.0),
+                    ("dmg.lightning.max".to_string(), 1811.0),
+                ]),
+                base_time: 0.65,
+                mana_cost: 8,
+                tags: vec![
+                    "Tag_Spell".to_string(),
+                    "Tag_Lightning".to_string(),
+                    "Tag_Chain".to_string(),
+                    "Tag_Burst".to_string(),
+                ],
+                ..Default::default()
+            },
+            support_skills: vec![
+                SkillData {
+                    id: "support_lightning_to_cold".to_string(),
+                    skill_type: SkillType::Support,
+                    level: 20,
+                    base_time: 0.0,
+                    tags: vec!["Tag_Support".to_string(), "Tag_Lightning".to_string(), "Tag_Cold".to_string()],
+                    stats: HashMap::from([
+                        ("conv.lightning_to_cold".to_string(), 1.0),
+                        ("mod.more.dmg.lightning".to_string(), 0.25),
+                    ]),
+                    ..Default::default()
+                },
+                SkillData {
+                    id: "support_psychic_burst".to_string(),
+                    skill_type: SkillType::Support,
+                    level: 20,
+                    base_time: 0.0,
+                    tags: vec!["Tag_Support".to_string(), "Tag_Spell".to_string()],
+                    stats: HashMap::from([
+                        ("mod.more.dmg.all".to_string(), 0.45),
+                        ("speed.cast".to_string(), 0.16),
+                    ]),
+                    ..Default::default()
+                },
+            ],
+            global_overrides: HashMap::from([
+                // 施法速度 +100%（叠加灵能乍泄 16% 之后，PlaySafe 会把施法速度用于充能）
+                ("speed.cast".to_string(), 1.0.into()),
+                // 暴击率换算自：基础 500 法术暴击值 + 战意 100 层（每层 2%），合计 ~10%
+                ("crit.chance".to_string(), 0.10.into()),
+                // 世事无常：拉伸最小/最大伤害范围
+                ("mod.more.dmg.phys.min".to_string(), (-0.90).into()),
+                ("mod.more.dmg.phys.max".to_string(), 0.80.into()),
+                ("mod.more.dmg.min".to_string(), (-0.40).into()),
+                ("mod.more.dmg.max".to_string(), 0.40.into()),
+                // Spell Burst 层数（灯塔+2），默认上限 0 → 这里显式给 2
+                ("mechanic.spell_burst.max_stacks".to_string(), 2.0.into()),
+                // PlaySafe 开启
+                ("flag.talent.playsafe".to_string(), 1.0.into()),
+            ]),
+            mechanic_states: vec![
+                MechanicState {
+                    id: "focus_blessing".to_string(),
+                    current_stacks: 6,
+                    max_stacks: 6,
+                    is_active: true,
+                },
+                MechanicState {
+                    id: "fighting_will".to_string(),
+                    current_stacks: 100,
+                    max_stacks: 100,
+                    is_active: true,
+                },
+            ],
+            mechanic_definitions: vec![
+                MechanicDefinition {
+                    id: "focus_blessing".to_string(),
+                    display_name: "聚能祝福".to_string(),
+                    category: "blessing".to_string(),
+                    tag_key: "Mech_Blessing".to_string(),
+                    default_max_stacks: 6,
+                    base_effect_per_stack: HashMap::from([
+                        ("mod.more.dmg.all".to_string(), 0.04),
+                        ("mod.more.dmg.spell".to_string(), 0.03),
+                    ]),
+                    description: "聚能祝福每层提供额外伤害".to_string(),
+                    aura_scalable: false,
+                    generation_rate: None,
+                    duration: None,
+                    exclusive_group: None,
+                    cost_per_use: None,
+                    stacks_per_hit: None,
+                    stacks_per_kill: None,
+                },
+                MechanicDefinition {
+                    id: "fighting_will".to_string(),
+                    display_name: "战意".to_string(),
+                    category: "resource".to_string(),
+                    tag_key: "Mech_FightingWill".to_string(),
+                    default_max_stacks: 100,
+                    base_effect_per_stack: HashMap::from([
+                        ("crit.chance.rating".to_string(), 2.0),
+                    ]),
+                    description: "战意每层提供 2 点暴击值".to_string(),
+                    aura_scalable: false,
+                    generation_rate: None,
+                    duration: None,
+                    exclusive_group: None,
+                    cost_per_use: None,
+                    stacks_per_hit: None,
+                    stacks_per_kill: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let result = calculate_dps(&input).expect("calc ok");
+        println!(
+            "SpellBurst DPS={:.2}, hit={:.2}, rate={:.2}/s, crit={:.2}%/{:.2}x",
+            result.dps_theoretical,
+            result.hit_damage,
+            result.rate,
+            result.crit_chance * 100.0,
+            result.crit_multiplier
+        );
+
+        // 基本合理性校验：开启迸发后 rate 应大于基础 1/0.65 ≈ 1.54/s
+        assert!(result.rate > 1.54);
+        assert!(result.dps_theoretical > 0.0);
+    }
+}
+
+/// 8. 计算暴击
+fn calculate_crit(mod_db: &ModDB, eval_ctx: &EvalContext, is_attack: bool, floor: IncreasedFloor) -> (f64, f64) {
+    let crit_chance = uncapped_crit_chance(mod_db, eval_ctx, is_attack, floor).clamp(0.0, 1.0);
+
+    // 暴击伤害
+    let base_multi = 1.5; // 基础暴击伤害 150%
+    let crit_dmg_inc = floor.apply(mod_db.sum_inc_with_ctx("crit.dmg", eval_ctx));
+    let crit_multiplier = base_multi + crit_dmg_inc;
+
+    // 检查是否无法暴击
+    if mod_db.has_flag_with_ctx("flag.cannot_crit", eval_ctx) {
+        return (0.0, 1.0);
+    }
+
+    (crit_chance, crit_multiplier)
+}
+
+/// 截断前的暴击率：Override 优先，否则 base * (1 + inc) * more
+///
+/// 武器局部暴击率（`weapon.crit.chance`）只计入攻击技能的 base
+fn uncapped_crit_chance(mod_db: &ModDB, eval_ctx: &EvalContext, is_attack: bool, floor: IncreasedFloor) -> f64 {
+    match mod_db.get_override_with_ctx("crit.chance", eval_ctx) {
+        Some(value) => value,
+        None => {
+            let mut base = mod_db.sum_base_with_ctx("crit.chance", eval_ctx);
+            if is_attack {
+                base += mod_db.sum_base_with_ctx("weapon.crit.chance", eval_ctx);
+            }
+            let inc = floor.apply(mod_db.sum_inc_with_ctx("crit.chance", eval_ctx));
+            base * (1.0 + inc) * mod_db.product_more_with_ctx("crit.chance", eval_ctx)
+        }
+    }
+}
+
+/// 暴击率与敌人抗性触及上限的诊断
+fn limit_diagnostics(
+    mod_db: &ModDB,
+    target_db: &ModDB,
+    eval_ctx: &EvalContext,
+    is_attack: bool,
+    floor: IncreasedFloor,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let crit_chance = uncapped_crit_chance(mod_db, eval_ctx, is_attack, floor);
+    if !(0.0..=1.0).contains(&crit_chance) && !mod_db.has_flag_with_ctx("flag.cannot_crit", eval_ctx) {
+        diagnostics.push(Diagnostic::new(
+            DiagnosticCode::CritCapped,
+            message_params(&[("chance", format!("{:.1}", crit_chance * 100.0))]),
+        ));
+    }
+
+    for dtype in [DamageType::Physical, DamageType::Fire, DamageType::Cold, DamageType::Lightning, DamageType::Chaos] {
+        let resistance = uncapped_target_resistance(target_db, eval_ctx, dtype);
+        if resistance > MAX_TARGET_RESISTANCE {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticCode::ResistanceCapped,
+                message_params(&[
+                    ("damage", dtype.as_key().to_string()),
+                    ("resistance", format!("{:.1}", resistance * 100.0)),
+                    ("cap", format!("{:.0}", MAX_TARGET_RESISTANCE * 100.0)),
+                ]),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+/// 计算暴击因子
+fn calculate_crit_factor(crit_chance: f64, crit_multiplier: f64) -> f64 {
+    // 平均伤害 = (1 - crit_chance) * 1.0 + crit_chance * crit_multiplier
+    1.0 + crit_chance * (crit_multiplier - 1.0)
+}
+
+/// 计算期望伤害，支持 Lucky 机制
+/// Lucky: 取两次掷骰较高值，等价于区间 [min, max] 的期望从 0.5 提升到 2/3
+fn expected_damage(min: f64, max: f64, is_lucky: bool) -> f64 {
+    if !is_lucky || max <= min {
+        return (min + max) / 2.0;
+    }
+
+    // 期望 = min + (max - min) * 2/3
+    min + (max - min) * (2.0 / 3.0)
+}
+
+/// 9. 计算命中率
+fn calculate_hit_chance(mod_db: &ModDB, eval_ctx: &EvalContext, _target: &TargetConfig) -> f64 {
+    let base_acc = mod_db.base_or_override_with_ctx("acc.rating", eval_ctx);
+    let acc_chance = mod_db.base_or_override_with_ctx("acc.chance", eval_ctx);
+
+    // 简化的命中计算
+    if mod_db.has_flag_with_ctx("flag.always_hit", eval_ctx) {
+        1.0
+    } else if acc_chance > 0.0 {
+        acc_chance.min(1.0)
+    } else if base_acc > 0.0 {
+        // 基于命中值计算（简化公式）
+        (base_acc / (base_acc + 100.0)).min(0.95)
+    } else {
+        0.95 // 默认95%命中
+    }
+}
+
+/// 构建目标 ModDB（敌人侧修正）
+///
+/// 汇总三类来源，键均为敌人侧键（如 `res.fire`、`armor`、`increased_damage_taken`）：
+/// - 目标配置：基础抗性、护甲、通用减伤
+/// - 目标减益：诅咒、曝露、感电、地图词缀等
+/// - 玩家侧 `target.*` 属性（Target 作用域修正，去掉前缀后重新解析）
+pub fn build_target_mod_db(target: &TargetConfig, mod_db: &ModDB) -> ModDB {
+    let mut target_db = ModDB::new();
+    let to_target = |key: &str, value: f64, source: &str| {
+        StatAggregator::to_modifier(key, value, source).with_scope(ModifierScope::Target)
+    };
+
+    let units = StatUnitTable::shared();
+    for (key, value, source) in target_config_stats(target) {
+        let (key, value) = units.normalize(&key, value).unwrap_or((&key, value));
+        target_db.add(to_target(key, value, &source));
+    }
+
+    for m in mod_db.all_modifiers() {
+        if m.scope != ModifierScope::Target {
+            continue;
+        }
+        let key = m.key.as_str();
+        let key = key.strip_prefix("target.").unwrap_or(key);
+        let mut modifier = to_target(key, m.value, &m.source);
+        modifier.condition = m.condition.clone();
+        modifier.condition_str = m.condition_str.clone();
+        target_db.add(modifier);
+    }
+
+    target_db
+}
+
+/// 目标配置与目标减益提供的敌人侧属性: (属性键, 录入值, 来源)
+fn target_config_stats(target: &TargetConfig) -> Vec<(String, f64, String)> {
+    let mut stats: Vec<(String, f64, String)> = target
+        .resistances
+        .iter()
+        .map(|(dtype, value)| (format!("res.{}", dtype), *value, "target_config".to_string()))
+        .collect();
+    if target.armor > 0 {
+        stats.push(("armor".to_string(), target.armor as f64, "target_config".to_string()));
+    }
+    if target.generic_dr != 0.0 {
+        stats.push(("generic_dr".to_string(), target.generic_dr, "target_config".to_string()));
+    }
+    for debuff in &target.debuffs {
+        let source = format!("{}:{}", debuff.category, debuff.id);
+        stats.extend(debuff.stats.iter().map(|(key, value)| (key.clone(), *value, source.clone())));
+    }
+    stats
+}
+
+/// 目标配置中按 0–100 约定录入的数值: (属性键, 原值, 换算后, 来源)
+fn normalized_target_values(target: &TargetConfig) -> Vec<(String, f64, f64, String)> {
+    let units = StatUnitTable::shared();
+    target_config_stats(target)
+        .into_iter()
+        .filter_map(|(key, value, source)| {
+            let (_, normalized) = units.normalize(&key, value)?;
+            Some((key, value, normalized, source))
+        })
+        .collect()
+}
+
+/// 目标抗性相关的属性键（按伤害类型）
+///
+/// 元素类型额外受 `res.elemental` 影响，除物理外均受 `res.all` 影响
+fn target_resistance_keys(dtype: DamageType) -> Vec<String> {
+    let mut keys = vec![format!("res.{}", dtype.as_key())];
+    if matches!(dtype, DamageType::Fire | DamageType::Cold | DamageType::Lightning) {
+        keys.push("res.elemental".to_string());
+    }
+    if dtype != DamageType::Physical {
+        keys.push("res.all".to_string());
+    }
+    keys
+}
+
+/// 敌人抗性上限（超过 100% 时伤害不再为负）
+const MAX_TARGET_RESISTANCE: f64 = 1.0;
+
+/// 获取目标对指定伤害类型的最终抗性（截断到上限）
+fn target_resistance(target_db: &ModDB, eval_ctx: &EvalContext, dtype: DamageType) -> f64 {
+    uncapped_target_resistance(target_db, eval_ctx, dtype).min(MAX_TARGET_RESISTANCE)
+}
+
+/// 截断前的目标抗性
+fn uncapped_target_resistance(target_db: &ModDB, eval_ctx: &EvalContext, dtype: DamageType) -> f64 {
+    target_resistance_keys(dtype)
+        .iter()
+        .map(|key| target_db.base_or_override_with_ctx(key, eval_ctx))
+        .sum()
+}
+
+/// 计算有效 DPS（考虑目标抗性、通用减伤与易伤）
+fn calculate_effective_dps(
+    damages: &HashMap<DamageType, DamageWithTags>,
+    rate: f64,
+    crit_factor: f64,
+    hit_chance: f64,
+    target_db: &ModDB,
+    eval_ctx: &EvalContext,
+) -> f64 {
+    let generic_dr = target_db.base_or_override_with_ctx("generic_dr", eval_ctx);
+    let vulnerability = target_db.base_or_override_with_ctx("increased_damage_taken", eval_ctx);
+    let mut total = 0.0;
+
+    for (dtype, dmg) in damages {
+        let avg = dmg.average() * crit_factor;
+
+        // 获取目标抗性（含诅咒/曝露等减益）
+        let resistance = target_resistance(target_db, eval_ctx, *dtype);
+
+        // 简化的减伤计算
+        let damage_taken = avg * (1.0 - resistance) * (1.0 - generic_dr) * (1.0 + vulnerability);
+        total += damage_taken;
+    }
+
+    total * rate * hit_chance
+}
+
+/// 10. 计算 EHP
+fn calculate_ehp(mod_db: &ModDB, eval_ctx: &EvalContext) -> EhpSeries {
+    let base_life = mod_db.base_or_override_with_ctx("base.life", eval_ctx).max(1.0);
+
+    // 防御换算（额外获得护甲/闪避、护甲适用于非物理伤害）先于 EHP 结算
+    let defense = DefenseStats::resolve(mod_db, eval_ctx);
+
+    // EHP = Life / ((1 - armor_reduction) * (1 - res))
+    // 简化：armor_reduction = armor / (armor + 1000)，物理不计抗性
+    let ehp = |dtype: DamageType, res_key: Option<&str>| {
+        let res = res_key
+            .map(|key| mod_db.base_or_override_with_ctx(key, eval_ctx).min(0.75))
+            .unwrap_or(0.0);
+        let taken = (1.0 - defense.armor_reduction(dtype)) * (1.0 - res);
+        base_life / taken.max(0.01)
+    };
+
+    EhpSeries {
+        physical: ehp(DamageType::Physical, None),
+        fire: ehp(DamageType::Fire, Some("res.fire")),
+        cold: ehp(DamageType::Cold, Some("res.cold")),
+        lightning: ehp(DamageType::Lightning, Some("res.lightning")),
+        chaos: ehp(DamageType::Chaos, Some("res.chaos")),
+    }
+}
+
+/// 构建伤害明细
+/// 构建伤害分解明细，包含各乘区详情
+/// 
+/// 借鉴 ZSim 的设计，将伤害拆分为独立乘区：
+/// - 基础伤害区、增伤区、More区、暴击区、速度区、命中区、防御区、抗性区、易伤区
+#[allow(clippy::too_many_arguments)]
+fn build_damage_breakdown(
+    base_damages: &HashMap<DamageType, (f64, f64)>,
+    modified_damages: &HashMap<DamageType, DamageWithTags>,
+    mod_db: &ModDB,
+    inactive_modifiers: &[Modifier],
+    eval_ctx: &EvalContext,
+    rate: f64,
+    crit_chance: f64,
+    crit_multiplier: f64,
+    hit_chance: f64,
+    target_db: &ModDB,
+    buckets: &MoreBucketRegistry,
+    is_lucky: bool,
+    floor: IncreasedFloor,
+    zone_tags: &ZoneTagSets,
+    custom_zones: &[CustomZone],
+) -> DamageBreakdown {
+    let mut by_type = HashMap::new();
+    let mut ranges = HashMap::new();
+    let mut after_conversion = HashMap::new();
+
+    for (dtype, dmg) in modified_damages {
+        by_type.insert(dtype.as_key().to_string(), expected_damage(dmg.min, dmg.max, is_lucky));
+        ranges.insert(dtype.as_key().to_string(), DamageRange { min: dmg.min, max: dmg.max });
+        after_conversion.insert(
+            dtype.as_key().to_string(),
+            DamageWithHistory {
+                damage: expected_damage(dmg.min, dmg.max, is_lucky),
+                history_tags: dmg
+                    .history_tags
+                    .ones()
+                    .map(|i| format!("tag_{}", i))
+                    .collect(),
+            },
+        );
+    }
+
+    let base_damage: f64 = base_damages
+        .values()
+        .map(|(min, max)| (min + max) / 2.0)
+        .sum();
+
+    // 计算各乘区明细（传入 ModDB 以获取详细来源）
+    let multipliers = build_multiplier_breakdown(
+        base_damage,
+        mod_db,
+        inactive_modifiers,
+        eval_ctx,
+        rate,
+        crit_chance,
+        crit_multiplier,
+        hit_chance,
+        target_db,
+        buckets,
+        floor,
+        zone_tags,
+        custom_zones,
+    );
+
+    DamageBreakdown {
+        by_type,
+        base_damage,
+        total_increased: mod_db.sum_inc_with_ctx("dmg.all", eval_ctx),
+        total_more: mod_db.product_more_with_ctx("dmg.all", eval_ctx),
+        after_conversion,
+        multipliers,
+        ranges,
+    }
+}
+
+/// 构建乘区明细
+/// 
+/// 各乘区计算公式：
+/// - 基础伤害区: 技能基础伤害值
+/// - 增伤区: 1 + sum(所有 increased)
+/// - More区: product(所有 more)，less 钳制在 -100%
+/// - Less区: product(所有 less)，已包含在 More区 中，仅用于展示
+/// - 暴击期望区: 1 + crit_chance * crit_damage
+/// - 速度区: 攻击/施法速率
+/// - 命中区: 命中率
+/// - 防御区: level_constant / (enemy_armor + level_constant)
+/// - 抗性区: 1 - enemy_res + res_reduction + res_penetration
+/// - 易伤区: 1 + enemy_increased_damage_taken
+#[allow(clippy::too_many_arguments)]
+fn build_multiplier_breakdown(
+    base_damage: f64,
+    mod_db: &ModDB,
+    inactive_modifiers: &[Modifier],
+    eval_ctx: &EvalContext,
+    rate: f64,
+    crit_chance: f64,
+    crit_multiplier: f64,
+    hit_chance: f64,
+    target_db: &ModDB,
+    buckets: &MoreBucketRegistry,
+    floor: IncreasedFloor,
+    zone_tags: &ZoneTagSets,
+    custom_zones: &[CustomZone],
+) -> MultiplierBreakdown {
+    let mut zone_sources: HashMap<String, Vec<ZoneSource>> = HashMap::new();
+
+    // 1. 基础伤害区
+    let base_damage_zone = base_damage;
+    zone_sources.insert(
+        "base_damage".to_string(),
+        vec![ZoneSource::localized("zone.base_damage", MessageParams::new(), base_damage, "dmg.base")],
+    );
+
+    // 2. 增伤区 (收集所有 dmg.* 的 increased 来源)
+    let mut total_increased = 0.0;
+    let mut inc_sources = Vec::new();
+
+    let mut inactive_sources = Vec::new();
+
+    for m in damage_zone_mods(mod_db, ModifierKind::Increased, zone_tags) {
+        let value = m.effective_value(eval_ctx);
+        let source = damage_zone_source(m, value, buckets).with_condition(m.condition_provenance(eval_ctx));
+        // 条件未满足的修正单独列出，便于排查未生效原因
+        if source.condition.as_ref().is_some_and(|c| !c.passed) {
+            inactive_sources.push(source);
+            continue;
+        }
+        total_increased += value;
+        inc_sources.push(source);
+    }
+
+    let increased_zone = 1.0 + floor.apply(total_increased);
+    zone_sources.insert("increased".to_string(), inc_sources);
+
+    // 3. More 乘区
+    let mut more_sources = Vec::new();
+    let mut less_sources = Vec::new();
+    let mut more_mods = Vec::new();
+    let mut less_mods = Vec::new();
+
+    for m in damage_zone_mods(mod_db, ModifierKind::More, zone_tags) {
+        let value = m.effective_value(eval_ctx);
+        let source = damage_zone_source(m, value, buckets).with_condition(m.condition_provenance(eval_ctx));
+        if source.condition.as_ref().is_some_and(|c| !c.passed) {
+            inactive_sources.push(source);
+            continue;
+        }
+        // 负值 (less) 单独列出，避免大幅削减被淹没在乘积中
+        if value < 0.0 {
+            less_sources.push(source);
+            less_mods.push((m, value));
+        } else {
+            more_sources.push(source);
+        }
+        more_mods.push((m, value));
+    }
+    let more_zone = combine_more(more_mods);
+    let less_zone = combine_more(less_mods);
+
+    zone_sources.insert("more".to_string(), more_sources);
+    zone_sources.insert("less".to_string(), less_sources);
+
+    // 词缀条件在聚合阶段未满足的修正（未写入 ModDB）
+    for m in inactive_modifiers
+        .iter()
+        .filter(|m| matches!(m.kind, ModifierKind::Increased | ModifierKind::More) && is_damage_zone_key(m.key.as_str()))
+    {
+        let source = damage_zone_source(m, m.effective_value(eval_ctx), buckets);
+        inactive_sources.push(source.with_condition(m.condition_provenance(eval_ctx)));
+    }
+
+    // 4. 暴击期望区
+    // 公式: 1 + crit_chance * (crit_multiplier - 1)
+    // crit_multiplier 语义: 1.5 = 150% 总暴击伤害 (非暴击时为 100%)
+    // 例: 50% 暴击率, 150% 暴击伤害 → 1 + 0.5 * 0.5 = 1.25 倍期望伤害
+    let effective_crit_chance = crit_chance.clamp(0.0, 1.0);
+    let crit_zone = 1.0 + effective_crit_chance * (crit_multiplier - 1.0);
+    zone_sources.insert("crit".to_string(), vec![
+        ZoneSource::localized("zone.crit_chance", MessageParams::new(), crit_chance, "crit.chance"),
+        ZoneSource::localized("zone.crit_multiplier", MessageParams::new(), crit_multiplier, "crit.multiplier"),
+    ]);
+
+    // 5. 速度区
+    let speed_zone = rate;
+    zone_sources.insert(
+        "speed".to_string(),
+        vec![ZoneSource::localized("zone.speed", MessageParams::new(), rate, "rate")],
+    );
+
+    // 6. 命中区
+    let hit_zone = hit_chance;
+    zone_sources.insert(
+        "hit".to_string(),
+        vec![ZoneSource::localized("zone.hit", MessageParams::new(), hit_chance, "hit.chance")],
+    );
+
+    // 7. 防御区 (敌人护甲，含目标减益)
+    // 公式: level_constant / (enemy_armor + level_constant)
+    let level_constant = 1000.0; // 等级常数，后续可参数化
+    let enemy_armor = target_db.calculate_final_with_ctx("armor", eval_ctx).max(0.0);
+    let defense_zone = if enemy_armor > 0.0 {
+        level_constant / (enemy_armor + level_constant)
+    } else {
+        1.0
+    };
+    let mut defense_sources = vec![ZoneSource::localized(
+        "zone.enemy_armor",
+        message_params(&[("armor", enemy_armor.to_string())]),
+        defense_zone,
+        "target.armor",
+    )];
+    defense_sources.extend(target_zone_sources(target_db, eval_ctx, &["armor"]));
+    zone_sources.insert("defense".to_string(), defense_sources);
+
+    // 8. 抗性区
+    // 公式: 1 - enemy_res + res_penetration（enemy_res 已含诅咒/曝露等减益）
+    // 取平均抗性作为示例
+    let resisted_types = [DamageType::Fire, DamageType::Cold, DamageType::Lightning, DamageType::Chaos];
+    let avg_resistance = resisted_types
+        .iter()
+        .map(|dtype| target_resistance(target_db, eval_ctx, *dtype))
+        .sum::<f64>()
+        / resisted_types.len() as f64;
+    let res_penetration = mod_db.base_or_override_with_ctx("mod.penetration.res.all", eval_ctx);
+    let resistance_zone = (1.0 - avg_resistance + res_penetration).max(0.0);
+    let mut resistance_sources = vec![ZoneSource::localized(
+        "zone.avg_resistance",
+        message_params(&[("resistance", format!("{:.1}", avg_resistance * 100.0))]),
+        resistance_zone,
+        "target.resistance",
+    )];
+    resistance_sources.extend(target_zone_sources(
+        target_db,
+        eval_ctx,
+        &["res.fire", "res.cold", "res.lightning", "res.chaos", "res.elemental", "res.all"],
+    ));
+    zone_sources.insert("resistance".to_string(), resistance_sources);
+
+    // 9. 易伤区
+    let vulnerability = target_db.base_or_override_with_ctx("increased_damage_taken", eval_ctx);
+    let vulnerability_zone = 1.0 + vulnerability;
+    let mut vulnerability_sources = vec![ZoneSource::localized(
+        "zone.vulnerability",
+        MessageParams::new(),
+        vulnerability,
+        "target.increased_damage_taken",
+    )];
+    vulnerability_sources.extend(target_zone_sources(target_db, eval_ctx, &["increased_damage_taken"]));
+    zone_sources.insert("vulnerability".to_string(), vulnerability_sources);
+
+    // 10. 机制特殊区 (祝福、球类等提供的额外乘区)
+    let mechanics_more = mod_db.base_or_override_with_ctx("mechanics.more.dmg", eval_ctx);
+    let mechanics_zone = if mechanics_more > 0.0 { 1.0 + mechanics_more } else { 1.0 };
+    zone_sources.insert(
+        "mechanics".to_string(),
+        vec![ZoneSource::localized("zone.mechanics", MessageParams::new(), mechanics_more, "mechanics.more.dmg")],
+    );
+
+    // 11. 数据驱动乘区（定义见 multiplier_zones.json）
+    let mut custom_zone_values = HashMap::new();
+    for &(id, zone, zone_value, ref mods) in custom_zones {
+        let mut sources = vec![ZoneSource::localized(&zone.display_key, MessageParams::new(), zone_value, id)];
+        sources.extend(mods.iter().map(|(m, value)| {
+            ZoneSource::localized(
+                "zone.source",
+                message_params(&[("source", m.source.to_string())]),
+                *value,
+                m.key.as_str(),
+            )
+        }));
+        zone_sources.insert(id.to_string(), sources);
+        custom_zone_values.insert(id.to_string(), zone_value);
+    }
+
+    MultiplierBreakdown {
+        base_damage_zone,
+        increased_zone,
+        more_zone,
+        less_zone,
+        crit_zone,
+        speed_zone,
+        hit_zone,
+        defense_zone,
+        resistance_zone,
+        vulnerability_zone,
+        mechanics_zone,
+        custom_zones: custom_zone_values,
+        zone_sources,
+        inactive_sources,
+    }
+}
+
+/// 伤害区间拉伸键（按最小/最大值单独生效，不计入增伤区与 More 区）
+const DAMAGE_STRETCH_KEYS: &[&str] = &["dmg.min", "dmg.max"];
+
+/// 收集增伤区 / More 区的修正：所有 `dmg.*` 键中的指定类型修正（含条件未满足的，由调用方区分）
+fn damage_zone_mods<'a>(mod_db: &'a ModDB, kind: ModifierKind, zone_tags: &ZoneTagSets) -> Vec<&'a Modifier> {
+    let (hit_set, active_set) = zone_tags;
+    mod_db
+        .query_by_kind("dmg.*", kind)
+        .into_iter()
+        .filter(|m| is_damage_zone_key(m.key.as_str()) && m.matches_tags(hit_set, active_set))
+        .collect()
+}
+
+/// 乘区明细的标签集合: (各伤害包历史标签的并集（展开继承）, 修正阶段的上下文标签)
+type ZoneTagSets = (FixedBitSet, FixedBitSet);
+
+/// 收集乘区明细的标签集合：修正须命中至少一个伤害包，且满足修正阶段的上下文标签
+fn damage_zone_tags(modified_damages: &HashMap<DamageType, DamageWithTags>, context: &ContextTags) -> ZoneTagSets {
+    let history_ids: Vec<u32> = modified_damages
+        .values()
+        .flat_map(|dmg| dmg.history_tags.ones().map(|i| i as u32))
+        .collect();
+    (
+        context.registry().create_set_from_ids(&history_ids),
+        context.active_set().bits().clone(),
+    )
+}
+
+/// 数据驱动乘区（定义见 multiplier_zones.json）：(乘区 ID, 定义, 乘区倍率, [(修正, 生效值)])
+type CustomZone<'a> = (&'static str, &'static ZoneDefinition, f64, Vec<(&'a Modifier, f64)>);
+
+/// 收集数据驱动乘区（条件未满足的修正不计入）
+///
+/// ModDB 的键只遍历一次，逐键归入所属乘区；每次计算只收集一次，伤害包与乘区明细共用
+fn custom_zones<'a>(mod_db: &'a ModDB, eval_ctx: &EvalContext) -> Vec<CustomZone<'a>> {
+    let zones: Vec<(&'static str, &'static ZoneDefinition)> = MultiplierZoneTable::shared().zones().collect();
+    let mut zone_keys: Vec<Vec<String>> = vec![Vec::new(); zones.len()];
+    let mut keys = mod_db.keys();
+    keys.sort();
+    for key in keys {
+        for (index, (_, zone)) in zones.iter().enumerate() {
+            if zone.matches(&key) {
+                zone_keys[index].push(key.clone());
+            }
+        }
+    }
+    zones
+        .into_iter()
+        .zip(zone_keys)
+        .map(|((id, zone), keys)| {
+            let mods: Vec<(&Modifier, f64)> = keys
+                .iter()
+                .flat_map(|k| mod_db.get(k))
+                .filter(|m| m.condition_provenance(eval_ctx).map_or(true, |c| c.passed))
+                .map(|m| (m, m.effective_value(eval_ctx)))
+                .collect();
+            (id, zone, zone.combine(mods.iter().map(|(_, value)| *value)), mods)
+        })
+        .collect()
+}
+
+/// 数据驱动乘区作用于每个伤害包（增伤/More 之后、暴击之前；命中与持续伤害、异常伤害包一致）
+fn apply_custom_zones(damages: &mut HashMap<DamageType, DamageWithTags>, zones: &[CustomZone]) {
+    let multiplier: f64 = zones.iter().map(|(_, _, value, _)| *value).product();
+    if multiplier == 1.0 {
+        return;
+    }
+    for damage in damages.values_mut() {
+        damage.min *= multiplier;
+        damage.max *= multiplier;
+    }
+}
+
+/// 是否计入增伤区 / More 区的伤害键
+fn is_damage_zone_key(key: &str) -> bool {
+    key.starts_with("dmg.") && !DAMAGE_STRETCH_KEYS.contains(&key)
+}
+
+/// 增伤区 / More 区的来源（More 值显示为乘数形式，并附带 bucket 名称）
+fn damage_zone_source(m: &Modifier, value: f64, buckets: &MoreBucketRegistry) -> ZoneSource {
+    let damage = damage_key_label(m.key.as_str());
+    if m.kind != ModifierKind::More {
+        return ZoneSource::localized(
+            "zone.increased",
+            message_params(&[("source", m.source.to_string()), ("damage", damage)]),
+            value,
+            m.key.as_str(),
+        );
+    }
+
+    // 来源附带 bucket 名称，便于核对哪些修正相乘、哪些相加
+    let (bucket, instance) = match buckets.locate(m.bucket_id) {
+        Some((category, instance)) => (category.to_string(), instance.map(|i| format!("#{}", i))),
+        None => (format!("bucket {}", m.bucket_id), None),
+    };
+    ZoneSource::localized(
+        "zone.more",
+        message_params(&[
+            ("source", m.source.to_string()),
+            ("damage", damage),
+            ("bucket", bucket),
+            ("instance", instance.unwrap_or_default()),
+        ]),
+        more_factor(value),
+        m.key.as_str(),
+    )
+}
+
+/// 伤害键的本地化参数值（如 `dmg.fire` → `fire`，显示名称见语言表 `damage.fire`）
+fn damage_key_label(key: &str) -> String {
+    key.strip_prefix("dmg.").unwrap_or(key).to_string()
+}
+
+/// 收集目标 ModDB 中指定键的来源（用于减伤乘区溯源）
+fn target_zone_sources(target_db: &ModDB, eval_ctx: &EvalContext, keys: &[&str]) -> Vec<ZoneSource> {
+    keys.iter()
+        .flat_map(|key| {
+            target_db
+                .get_sources_with_ctx(key, eval_ctx)
+                .into_iter()
+                .map(move |src| {
+                    ZoneSource::localized(
+                        "zone.source",
+                        message_params(&[("source", src.source)]),
+                        src.value,
+                        &format!("target.{}", key),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::TagDiagnostic;
+    use crate::i18n::{render_default, LocaleTable};
+    use crate::types::MechanicDefinition;
+
+    fn create_test_input() -> CalculatorInput {
+        CalculatorInput {
+            active_skill: SkillData {
+                id: "test_fireball".to_string(),
+                damage_type: Some("fire".to_string()),
+                base_damage: [
+                    ("dmg.fire.min".to_string(), 50.0),
+                    ("dmg.fire.max".to_string(), 100.0),
+                ]
+                .into_iter()
+                .collect(),
+                base_time: 0.8,
+                mana_cost: 10,
+                tags: vec!["Tag_Spell".to_string(), "Tag_Fire".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_basic_calculation() {
+        let input = create_test_input();
+        let result = calculate_dps(&input).unwrap();
+
+        // 基础伤害 75 (平均)
+        // 速率 1.25/s
+        // 理论 DPS ≈ 75 * 1.25 * crit_factor
+        assert!(result.dps_theoretical > 0.0);
+        assert!(result.hit_damage > 0.0);
+        assert!(result.rate > 0.0);
+
+        // 结果携带所用数据快照
+        assert_eq!(result.data_version, default_data_version());
+        assert!(!result.data_version.version.is_empty());
+        assert_ne!(
+            result.data_version.checksum,
+            create_default_registry().data_version().checksum,
+            "校验和应覆盖全部内嵌数据表，而非仅标签注册表"
+        );
+
+        // 结果携带引擎版本与输入指纹（输入变化时指纹随之变化）
+        assert_eq!(result.engine_version, ENGINE_VERSION);
+        assert_eq!(result.input_hash, input_hash(&input));
+        let mut leveled = input.clone();
+        leveled.active_skill.level += 1;
+        assert_ne!(calculate_dps(&leveled).unwrap().input_hash, result.input_hash);
+
+        // 指纹与 HashMap 迭代顺序无关，覆盖词缀数值等全部输入，两阶段计算同样填写
+        let keys: Vec<String> = (0..16).map(|i| format!("value_{}", i)).collect();
+        let mut forward = input.clone();
+        forward.context_values.extend(keys.iter().map(|key| (key.clone(), 1.0)));
+        let mut reversed = input.clone();
+        reversed.context_values.extend(keys.iter().rev().map(|key| (key.clone(), 1.0)));
+        assert_eq!(input_hash(&forward), input_hash(&reversed));
+        let mut equipped = input.clone();
+        equipped.items.push(slot_test_item("ring", SlotType::Ring1, false));
+        let mut tagged = equipped.clone();
+        tagged.items[0].tags.push("Fire".to_string());
+        assert_ne!(input_hash(&tagged), input_hash(&equipped));
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert_eq!(prepared.input_hash, result.input_hash);
+        let mut target = input.target_config.clone();
+        target.level += 1;
+        assert_ne!(calculate_from_prepared(&prepare_context(&input).unwrap(), &target).unwrap().input_hash, result.input_hash);
+    }
+
+    #[test]
+    fn test_weapon_damage_adds_to_global_flat_damage() {
+        let mut input = create_test_input();
+        input.active_skill.is_attack = true;
+        input.active_skill.base_damage.clear();
+        input.active_skill.level_data = None;
+        let mut weapon = slot_test_item("test_sword", SlotType::WeaponMain, false);
+        weapon.implicit_stats = HashMap::from([("dmg.phys.min".to_string(), 50.0), ("dmg.phys.max".to_string(), 100.0)]);
+        input.items.push(weapon);
+        input.global_overrides.insert("dmg.phys.min".to_string(), 10.0.into());
+        input.global_overrides.insert("dmg.phys.max".to_string(), 20.0.into());
+
+        // 局部结算后的武器伤害与全局附加伤害累加，不互相覆盖
+        let result = calculate_dps(&input).unwrap();
+        let base = result.debug_trace.iter().find(|t| t.phase == "Base Damage").unwrap();
+        assert!((base.values["physical"] - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_increased_zone_respects_hit_tags() {
+        let base = calculate_dps(&create_test_input()).unwrap();
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 0.5.into());
+        input.global_overrides.insert("mod.inc.dmg.cold".to_string(), 0.3.into());
+
+        // 火焰技能：冰冷增伤不命中任何伤害包，不计入增伤区
+        let result = calculate_dps(&input).unwrap();
+        let multipliers = &result.damage_breakdown.multipliers;
+        assert!((multipliers.increased_zone - base.damage_breakdown.multipliers.increased_zone - 0.5).abs() < 1e-9);
+        assert!(!multipliers.zone_sources["increased"].iter().any(|s| s.stat_key == "dmg.cold"));
+    }
+
+    #[test]
+    fn test_with_increased_damage() {
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 1.0.into()); // +100% fire damage
+
+        let result = calculate_dps(&input).unwrap();
+
+        // 伤害应该翻倍
+        let base_result = calculate_dps(&create_test_input()).unwrap();
+        assert!(result.hit_damage > base_result.hit_damage * 1.5);
+    }
+
+    #[test]
+    fn test_conversion_with_tag_retention() {
+        // 测试物理转火焰，确保火焰部分也能吃到物理增伤
+        let mut input = create_test_input();
+        input.active_skill.is_attack = true;
+        input.active_skill.base_damage.clear();
+        input.active_skill.tags = vec!["Tag_Attack".to_string(), "Tag_Melee".to_string()];
+
+        // 添加武器物理伤害
+        input.items.push(ItemData {
+            base_implicit_stats: HashMap::new(), // 武器基底属性（无）
+            implicit_stats: [
+                ("dmg.phys.min".to_string(), 50.0),
+                ("dmg.phys.max".to_string(), 100.0),
+            ]
+            .into_iter()
+            .collect(),
+            ..ItemData::new("test_sword", "sword", SlotType::WeaponMain)
+        });
+
+        // 50% 物理转火焰
+        input.global_overrides.insert("conv.phys_to_fire".to_string(), 0.5.into());
+        // +100% 物理增伤
+        input.global_overrides.insert("mod.inc.dmg.phys".to_string(), 1.0.into());
+        // +100% 火焰增伤
+        input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 1.0.into());
+
+        let result = calculate_dps(&input).unwrap();
+
+        // 确保计算正常完成
+        assert!(result.dps_theoretical > 0.0);
+        
+        // 检查伤害构成
+        assert!(result.damage_breakdown.by_type.contains_key("physical"));
+        assert!(result.damage_breakdown.by_type.contains_key("fire"));
+
+        // 标签匹配记录：转化后的火焰伤害仍按物理历史标签吃到物理增伤
+        let modification = result.debug_trace.iter().find(|t| t.phase == "Modification").unwrap();
+        let matched = &modification.matched_tags;
+        assert!(matched.contains(&"fire: dmg.phys [hit: Tag_Physical]".to_string()));
+        assert!(matched.contains(&"fire: dmg.fire [hit: Tag_Fire]".to_string()));
+        assert!(matched.contains(&"physical: dmg.phys [hit: Tag_Physical]".to_string()));
+        assert!(!matched.iter().any(|m| m.starts_with("physical: dmg.fire")));
+    }
+
+    #[test]
+    fn test_phase_tags_scoped_to_modification() {
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.inc.dmg.projectile".to_string(), 0.5.into());
+
+        // 投射物标签只在修正阶段生效：投射物增伤计入
+        input.active_skill.phase_tags = vec![PhaseTag {
+            tag: "Tag_Projectile".to_string(),
+            phase: CalcPhase::Modification,
+        }];
+        let modification = calculate_dps(&input).unwrap();
+
+        // 只在转化阶段生效：投射物增伤不计入
+        input.active_skill.phase_tags[0].phase = CalcPhase::Conversion;
+        let conversion = calculate_dps(&input).unwrap();
+
+        let base = calculate_dps(&create_test_input()).unwrap();
+        assert!((modification.hit_damage / base.hit_damage - 1.5).abs() < 1e-6);
+        assert!((conversion.hit_damage - base.hit_damage).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_conversion_priority_reported_in_trace() {
+        let mut input = create_test_input();
+        input.active_skill.base_damage = [
+            ("dmg.phys.min".to_string(), 100.0),
+            ("dmg.phys.max".to_string(), 100.0),
+        ]
+        .into_iter()
+        .collect();
+
+        // 技能自带 80% 物理转火焰，全局再给 40% 物理转冰冷
+        input.active_skill.stats.insert("conv.phys_to_fire".to_string(), 0.8);
+        input.global_overrides.insert("conv.phys_to_cold".to_string(), 0.4.into());
+
+        let result = calculate_dps(&input).unwrap();
+
+        // 技能转化优先全额生效，冰冷只分到剩余的 20%
+        let fire = result.damage_breakdown.by_type["fire"];
+        let cold = result.damage_breakdown.by_type["cold"];
+        assert!((fire / cold - 4.0).abs() < 1e-6);
+
+        let entry = result.debug_trace.iter().find(|t| t.phase == "Conversion").unwrap();
+        let applied = entry.values["global_override (physical->cold)"];
+        assert!((applied - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_damage_stretch_uniform_across_types() {
+        let mut input = create_test_input();
+        input.active_skill.base_damage = [
+            ("dmg.phys.min".to_string(), 100.0),
+            ("dmg.phys.max".to_string(), 100.0),
+        ]
+        .into_iter()
+        .collect();
+        input.global_overrides.insert("conv.phys_to_cold".to_string(), 0.5.into());
+
+        // 全局最大值拉伸：天赋与辅助技能分属不同 bucket，相乘
+        input.global_overrides.insert("mod.more.dmg.max".to_string(), 0.5.into());
+        let mut support = input.active_skill.clone();
+        support.id = "test_stretch_support".to_string();
+        support.skill_type = SkillType::Support;
+        support.base_damage.clear();
+        support.stats.insert("mod.more.dmg.max".to_string(), 0.2);
+        input.support_skills.push(support);
+
+        // 物理最小值拉伸随转化保留；冰冷最小值拉伸只作用于冰冷伤害包
+        input.global_overrides.insert("mod.more.dmg.phys.min".to_string(), (-0.5).into());
+        input.global_overrides.insert("mod.more.dmg.cold.min".to_string(), 0.2.into());
+
+        let result = calculate_dps(&input).unwrap();
+        let by_type = &result.damage_breakdown.by_type;
+
+        // 物理：min 50 * 0.5 = 25，max 50 * 1.5 * 1.2 = 90
+        assert!((by_type["physical"] - 57.5).abs() < 1e-6);
+        // 冰冷：min 50 * 0.5 * 1.2 = 30，max 同上 90
+        assert!((by_type["cold"] - 60.0).abs() < 1e-6);
+
+        // 面板范围与平均值一致
+        let ranges = &result.damage_breakdown.ranges;
+        assert!((ranges["physical"].min - 25.0).abs() < 1e-6);
+        assert!((ranges["physical"].max - 90.0).abs() < 1e-6);
+        assert!((ranges["cold"].min - 30.0).abs() < 1e-6);
+        assert!((ranges["cold"].max - 90.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tag_scoped_modifiers_follow_history_tags() {
+        // 闪电 100% 转冰冷：冰冷伤害包保留闪电历史标签
+        let mut input = create_test_input();
+        input.active_skill.base_damage = HashMap::from([
+            ("dmg.lightning.min".to_string(), 100.0),
+            ("dmg.lightning.max".to_string(), 100.0),
+        ]);
+        input.active_skill.tags = vec!["Tag_Spell".to_string(), "Tag_Lightning".to_string()];
+        input.global_overrides = HashMap::from([
+            ("conv.lightning_to_cold".to_string(), 1.0.into()),
+            ("mod.inc.dmg.lightning".to_string(), 0.5.into()), // 历史标签命中
+            ("mod.inc.dmg.elemental".to_string(), 0.5.into()), // 冰冷/闪电继承元素，仅计一次
+            ("mod.inc.dmg.phys".to_string(), 1.0.into()),      // 不命中
+            ("mod.inc.dmg.attack".to_string(), 1.0.into()),    // 法术技能不满足攻击要求
+        ]);
+
+        let result = calculate_dps(&input).unwrap();
+
+        // 100 * (1 + 0.5 + 0.5) = 200
+        let cold = result.damage_breakdown.by_type.get("cold").copied().unwrap_or(0.0);
+        assert!((cold - 200.0).abs() < 0.01, "cold = {}", cold);
+    }
+
+    #[test]
+    fn test_crit_reads_conditional_mod_db() {
+        use crate::modifiers::Modifier;
+
+        let mut db = ModDB::new();
+        db.add(Modifier::base("crit.chance", 0.1, "test"));
+        db.add(Modifier::inc("crit.chance", 1.0, "test").with_condition("low_life"));
+
+        let idle = EvalContext::default();
+        let (chance, _) = calculate_crit(&db, &idle, false, IncreasedFloor::default());
+        assert!((chance - 0.1).abs() < 1e-6);
+
+        let low_life = EvalContext::from_context(
+            &HashMap::from([("low_life".to_string(), true)]),
+            &HashMap::new(),
+        );
+        let (chance, _) = calculate_crit(&db, &low_life, false, IncreasedFloor::default());
+        assert!((chance - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_affix_condition_evaluated_at_calc_time() {
+        let mut input = create_test_input();
+        let base_hit = calculate_dps(&input).unwrap().hit_damage;
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "moving_dmg".to_string(),
+                group: "dmg".to_string(),
+                value: 0.3,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+                condition: Some("is_moving && life_percent <= 0.5".to_string()),
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+
+        let idle = calculate_dps(&input).unwrap();
+        assert_eq!(idle.damage_breakdown.total_increased, 0.0);
+        // 未满足条件的修正不参与伤害计算
+        assert!((idle.hit_damage - base_hit).abs() < 1e-9, "hit = {}", idle.hit_damage);
+
+        // 未生效的修正附带决定结果的标志
+        let inactive = &idle.damage_breakdown.multipliers.inactive_sources;
+        assert_eq!(inactive.len(), 1, "{:?}", inactive);
+        assert!((inactive[0].value - 0.3).abs() < 1e-6);
+        let condition = inactive[0].condition.as_ref().unwrap();
+        assert!(!condition.passed);
+        assert_eq!(condition.decided_by.len(), 1);
+        assert_eq!(condition.decided_by[0].key, "is_moving");
+        assert_eq!(condition.decided_by[0].actual, "false");
+
+        input.context_flags.insert("is_moving".to_string(), true);
+        input.context_values.insert("life_percent".to_string(), 0.3);
+        let moving = calculate_dps(&input).unwrap();
+        assert!((moving.damage_breakdown.total_increased - 0.3).abs() < 1e-6);
+        assert!((moving.hit_damage - base_hit * 1.3).abs() < 1e-9, "hit = {}", moving.hit_damage);
+
+        let zones = &moving.damage_breakdown.multipliers;
+        assert!(zones.inactive_sources.is_empty());
+        let condition = zones.zone_sources["increased"][0].condition.as_ref().unwrap();
+        assert!(condition.passed);
+        let keys: Vec<&str> = condition.decided_by.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(keys, vec!["is_moving", "life_percent"]);
+    }
+
+    #[test]
+    fn test_affix_condition_references_aggregated_stat() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "capped_crit_dmg".to_string(),
+                group: "dmg".to_string(),
+                value: 0.5,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.5)]),
+                condition: Some("stat(\"crit.chance\") >= 1.0".to_string()),
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+        input.global_overrides = HashMap::from([("crit.chance".to_string(), 0.6.into())]);
+
+        // 暴击率未满：条件按最终属性判定为未满足
+        let uncapped = calculate_dps(&input).unwrap();
+        assert_eq!(uncapped.damage_breakdown.total_increased, 0.0);
+        let no_affix = calculate_dps(&CalculatorInput { items: Vec::new(), ..input.clone() }).unwrap();
+        assert!((uncapped.hit_damage - no_affix.hit_damage).abs() < 1e-9, "hit = {}", uncapped.hit_damage);
+        let inactive = &uncapped.damage_breakdown.multipliers.inactive_sources;
+        let condition = inactive[0].condition.as_ref().unwrap();
+        assert_eq!(condition.decided_by[0].key, "stat(\"crit.chance\")");
+        assert_eq!(condition.decided_by[0].actual, "0.6");
+
+        input.global_overrides.insert("mod.inc.crit.chance".to_string(), 1.0.into());
+        let capped = calculate_dps(&input).unwrap();
+        assert!((capped.damage_breakdown.total_increased - 0.5).abs() < 1e-9);
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.hit_damage - capped.hit_damage).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_deferred_condition_flat_damage() {
+        let mut weapon = slot_test_item("sword", SlotType::WeaponMain, false);
+        weapon.base_implicit_stats = HashMap::from([
+            ("speed.attack.local".to_string(), 1.0),
+            ("dmg.phys.min".to_string(), 100.0),
+            ("dmg.phys.max".to_string(), 100.0),
+        ]);
+        weapon.quality = 20;
+        let conditional = |id: &str, stats: &[(&str, f64)]| AffixData {
+            id: id.to_string(),
+            group: "dmg".to_string(),
+            stats: stats.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            condition: Some("stat(\"crit.chance\") >= 1.0".to_string()),
+            ..Default::default()
+        };
+        let mut ring = slot_test_item("ring", SlotType::Ring1, false);
+        ring.affixes = vec![conditional("flat_phys", &[("dmg.phys.min", 50.0), ("dmg.phys.max", 50.0)])];
+        weapon.affixes = vec![conditional("flat_fire", &[("dmg.fire.min", 30.0), ("dmg.fire.max", 30.0)])];
+
+        let mut input = create_test_input();
+        input.active_skill.is_attack = true;
+        input.items = vec![weapon.clone()];
+        input.global_overrides = HashMap::from([("crit.chance".to_string(), 0.6.into())]);
+        let unconditional = calculate_dps(&input).unwrap();
+
+        // 条件未满足：平面伤害不计入（局部属性池与 StatPool 均不写入）
+        input.items = vec![weapon, ring];
+        let uncapped = calculate_dps(&input).unwrap();
+        assert!((uncapped.hit_damage - unconditional.hit_damage).abs() < 1e-9);
+
+        // 条件满足：与无条件的同一词缀结算一致（武器局部伤害仍按所在装备的品质结算）
+        input.global_overrides.insert("mod.inc.crit.chance".to_string(), 1.0.into());
+        let capped = calculate_dps(&input).unwrap();
+        assert!(capped.hit_damage > uncapped.hit_damage);
+        let mut always = input.clone();
+        for item in &mut always.items {
+            item.affixes[0].condition = None;
+        }
+        let expected = calculate_dps(&always).unwrap();
+        assert!((capped.hit_damage - expected.hit_damage).abs() < 1e-9);
+        assert!((capped.damage_breakdown.base_damage - expected.damage_breakdown.base_damage).abs() < 1e-9);
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.hit_damage - capped.hit_damage).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_data_checksum_covers_every_embedded_table() {
+        let data_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/data");
+        let mut on_disk: Vec<String> = std::fs::read_dir(data_dir)
+            .unwrap()
+            .filter_map(|entry| {
+                let name = entry.unwrap().file_name().into_string().unwrap();
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect();
+        on_disk.sort();
+        let mut embedded: Vec<String> = EMBEDDED_DATA_TABLES.iter().map(|(name, _)| name.to_string()).collect();
+        embedded.sort();
+        assert_eq!(embedded, on_disk, "新增数据表需登记到 EMBEDDED_DATA_TABLES");
+    }
+
+    #[test]
+    fn test_unknown_tags_reported_per_calculation() {
+        let mut input = create_test_input();
+        input.active_skill.tags.push("Tag_Missing".to_string());
+        input.active_skill.phase_tags.push(PhaseTag {
+            tag: "Tag_Missing".to_string(),
+            phase: CalcPhase::Modification,
+        });
+
+        // 同一标签只报告一次，两条计算路径一致
+        for warnings in [
+            calculate_dps(&input).unwrap().warnings,
+            calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap().warnings,
+        ] {
+            let unknown: Vec<_> = warnings.iter().filter(|d| d.code == DiagnosticCode::UnknownTag).collect();
+            assert_eq!(unknown.len(), 1);
+            assert_eq!(unknown[0].message_params["tag"], "Tag_Missing");
+        }
+
+        // 诊断不残留在共享的标签注册表中
+        let clean = calculate_dps(&create_test_input()).unwrap();
+        assert!(!clean.warnings.iter().any(|d| d.code == DiagnosticCode::UnknownTag));
+    }
+
+    #[test]
+    fn test_condition_tags_match_between_paths() {
+        let mut ring = slot_test_item("ring_1", SlotType::Ring1, false);
+        ring.affixes.push(AffixData {
+            id: "sword_dmg".to_string(),
+            group: "dmg".to_string(),
+            value: 0.3,
+            stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+            condition: Some("has_tag(\"Tag_Sword\")".to_string()),
+            ..Default::default()
+        });
+        let mut input = create_test_input();
+        input.items = vec![ring];
+        let unarmed = calculate_dps(&input).unwrap();
+        assert_eq!(unarmed.damage_breakdown.total_increased, 0.0);
+
+        // 武器推导的标签同样参与条件评估，计算与预计算路径结果一致
+        input.items.push(slot_test_item("sword_1h", SlotType::WeaponMain, false));
+        let direct = calculate_dps(&input).unwrap();
+        assert!((direct.damage_breakdown.total_increased - 0.3).abs() < 1e-9);
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.hit_damage - direct.hit_damage).abs() < 1e-9);
+
+        // 命中叠层的机制：两条路径的条件评估上下文含相同的机制层数
+        input.items[0].affixes[0].condition = Some("mechanic_stacks(\"test_momentum\") >= 2 && test_momentum_stacks >= 2".to_string());
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "test_momentum".to_string(),
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), 0.05)]),
+            stacks_per_hit: Some(1.0),
+            duration: Some(2.0),
+            ..Default::default()
+        }];
+        input.mechanic_states = vec![MechanicState {
+            id: "test_momentum".to_string(),
+            current_stacks: 4,
+            max_stacks: 20,
+            is_active: true,
+        }];
+        let direct = calculate_dps(&input).unwrap();
+        assert!((direct.damage_breakdown.total_increased - 0.5).abs() < 1e-9);
+        let prepared = prepare_context(&input).unwrap();
+        assert_eq!(prepared.condition_ctx.mechanic_stacks.get("test_momentum"), Some(&4));
+        let staged = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+        assert!((staged.hit_damage - direct.hit_damage).abs() < 1e-9);
+
+        // 稳态叠层由命中速率求解，条件随求解后的层数生效；预计算路径无法复现，拒绝计算
+        input.mechanic_states[0].current_stacks = 0;
+        input.stack_mode = StackMode::Realistic;
+        let solved = calculate_dps(&input).unwrap();
+        assert!(solved.damage_breakdown.total_increased > 0.3);
+        assert!(calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).is_err());
+    }
+
+    #[test]
+    fn test_support_tags_match_between_paths() {
+        let mut input = create_test_input();
+        let base_hit = calculate_dps(&input).unwrap().hit_damage;
+        let mut support = input.active_skill.clone();
+        support.id = "support_projectile".to_string();
+        support.skill_type = SkillType::Support;
+        support.injected_tags = vec!["Tag_Projectile".to_string()];
+        support.conditional_tags = vec![ConditionalTag {
+            tag: "Tag_AOE".to_string(),
+            condition: "has_tag(\"Tag_Fire\")".to_string(),
+        }];
+        input.support_skills = vec![support];
+        input.global_overrides = HashMap::from([
+            ("mod.inc.dmg.projectile".to_string(), 1.0.into()),
+            ("mod.inc.dmg.aoe".to_string(), 0.5.into()),
+        ]);
+
+        // 辅助注入与条件注入的标签在两条路径中均参与标签匹配
+        let direct = calculate_dps(&input).unwrap();
+        assert!((direct.hit_damage - base_hit * 2.5).abs() < 1e-9, "hit = {}", direct.hit_damage);
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.hit_damage - direct.hit_damage).abs() < 1e-9, "prepared hit = {}", prepared.hit_damage);
+    }
+
+    #[test]
+    fn test_cooldown_caps_rate_in_both_paths() {
+        let mut input = create_test_input();
+        input.active_skill.cooldown = Some(4.0);
+
+        // 施法时间 0.8 秒（1.25 次/秒）受 4 秒冷却限制为 0.25 次/秒
+        let direct = calculate_dps(&input).unwrap();
+        assert!((direct.rate - 0.25).abs() < 1e-9, "rate = {}", direct.rate);
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.rate - direct.rate).abs() < 1e-9, "prepared rate = {}", prepared.rate);
+        assert!((prepared.dps_theoretical - direct.dps_theoretical).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_string_context_conditions() {
+        let mut input = create_test_input();
+        input.global_overrides = HashMap::from([("mod.inc.dmg.all".to_string(), 0.2.into())]);
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "claw_stance".to_string(),
+                group: "dmg".to_string(),
+                value: 0.3,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+                condition: Some("weapon_type == \"claw\" && stance == \"offensive\"".to_string()),
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+        input.context_strings = HashMap::from([("weapon_type".to_string(), "claw".to_string())]);
+        let neutral = calculate_dps(&input).unwrap();
+        assert!((neutral.damage_breakdown.total_increased - 0.2).abs() < 1e-9);
+
+        input.context_strings.insert("stance".to_string(), "offensive".to_string());
+        let offensive = calculate_dps(&input).unwrap();
+        assert!((offensive.damage_breakdown.total_increased - 0.5).abs() < 1e-9);
+        assert_ne!(input_hash(&input), neutral.input_hash);
+    }
+
+    #[test]
+    fn test_affix_branches_select_value_by_condition() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "full_life_more".to_string(),
+                group: "more".to_string(),
+                value: 0.15,
+                stats: HashMap::from([("mod.more.dmg.all".to_string(), 0.15)]),
+                branches: vec![AffixBranch {
+                    condition: "life_percent >= 1.0".to_string(),
+                    stats: HashMap::from([("mod.more.dmg.all".to_string(), 0.40)]),
+                }],
+                ..Default::default()
+            }],
+            ..ItemData::new("amulet_1", "amulet", SlotType::Amulet)
+        }];
+
+        // 未满血：使用默认属性
+        input.context_values.insert("life_percent".to_string(), 0.5);
+        let low = calculate_dps(&input).unwrap();
+        assert!((low.damage_breakdown.multipliers.more_zone - 1.15).abs() < 1e-9);
+        assert!(low.damage_breakdown.multipliers.zone_sources["more"][0].condition.is_none());
+
+        // 满血：命中分支，来源附带分支条件
+        input.context_values.insert("life_percent".to_string(), 1.0);
+        let full = calculate_dps(&input).unwrap();
+        assert!((full.damage_breakdown.multipliers.more_zone - 1.40).abs() < 1e-9);
+        let condition = full.damage_breakdown.multipliers.zone_sources["more"][0].condition.as_ref().unwrap();
+        assert_eq!(condition.condition, "life_percent >= 1.0");
+        assert!(condition.passed);
+
+        // 分支参与缓存键
+        let mut other = input.clone();
+        other.items[0].affixes[0].branches[0].stats.insert("mod.more.dmg.all".to_string(), 0.5);
+        assert_ne!(input_hash(&input), input_hash(&other));
+    }
+
+    #[test]
+    fn test_recent_model_derives_recently_flags() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "killed_recently_dmg".to_string(),
+                group: "dmg".to_string(),
+                value: 0.3,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+                condition: Some("recently_killed".to_string()),
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+
+        // 不击杀时近期击杀不成立
+        input.recent_model = Some(RecentModel::default());
+        let no_kills = calculate_dps(&input).unwrap();
+        assert_eq!(no_kills.damage_breakdown.total_increased, 0.0);
+        let trace = no_kills.debug_trace.iter().find(|t| t.phase == "Recent").unwrap();
+        assert!(trace.values["recently_hit"] > 0.5);
+
+        // 每次命中 50% 击杀：按速率推导出近期击杀
+        input.recent_model = Some(RecentModel {
+            kill_chance_per_hit: 0.5,
+            ..Default::default()
+        });
+        let killing = calculate_dps(&input).unwrap();
+        assert!((killing.damage_breakdown.total_increased - 0.3).abs() < 1e-6);
+
+        // 手动标志优先
+        input.context_flags.insert("recently_killed".to_string(), false);
+        let manual = calculate_dps(&input).unwrap();
+        assert_eq!(manual.damage_breakdown.total_increased, 0.0);
+    }
+
+    #[test]
+    fn test_mechanic_tag_satisfies_affix_requirement() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "blessed_dmg".to_string(),
+                group: "dmg".to_string(),
+                value: 0.3,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+                requirements: vec!["Mech_Blessing".to_string()],
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+
+        // 无祝福时词缀不生效
+        let plain = calculate_dps(&input).unwrap();
+        assert_eq!(plain.damage_breakdown.total_increased, 0.0);
+
+        // 聚能祝福 2 层（内置定义 tag_key = Mech_Blessing）：30% + 2 × 4%
+        input.mechanic_states = vec![MechanicState {
+            id: "focus_blessing".to_string(),
+            current_stacks: 2,
+            max_stacks: 4,
+            is_active: true,
+        }];
+        let blessed = calculate_dps(&input).unwrap();
+        assert!((blessed.damage_breakdown.total_increased - 0.38).abs() < 1e-6);
+
+        // 0 层不算受祝福影响
+        input.mechanic_states[0].current_stacks = 0;
+        let empty = calculate_dps(&input).unwrap();
+        assert_eq!(empty.damage_breakdown.total_increased, 0.0);
+    }
+
+    #[test]
+    fn test_affix_excluded_tags() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "non_minion_dmg".to_string(),
+                group: "dmg".to_string(),
+                value: 0.3,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+                excluded_tags: vec!["Tag_Minion".to_string()],
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+
+        // 非召唤物技能：生效
+        let own = calculate_dps(&input).unwrap();
+        assert!((own.damage_breakdown.total_increased - 0.3).abs() < 1e-6);
+
+        // 召唤物技能：被排除
+        input.active_skill.tags.push("Tag_Minion".to_string());
+        let minion = calculate_dps(&input).unwrap();
+        assert_eq!(minion.damage_breakdown.total_increased, 0.0);
+    }
+
+    fn slot_test_item(id: &str, slot: SlotType, is_two_handed: bool) -> ItemData {
+        ItemData {
+            is_two_handed,
+            ..ItemData::new(id, id, slot)
+        }
+    }
+
+    /// 槽位冲突处理后保留的装备 ID 与被移除的装备 ID
+    fn resolve_slots(input: &CalculatorInput) -> (Vec<String>, Vec<String>) {
+        let (resolved, mut diagnostics) = resolve_preferred_slots(input).unwrap();
+        let mut sanitizer = RangeSanitizer::new(SanitizePolicy::Clamp);
+        let (items, slot_diagnostics) = sanitize_items(&resolved, &mut sanitizer).unwrap();
+        diagnostics.extend(slot_diagnostics);
+        assert!(diagnostics.iter().all(|d| d.code == DiagnosticCode::SlotConflict));
+        (
+            items.into_iter().map(|i| i.id).collect(),
+            diagnostics.iter().map(|d| d.message_params["item"].clone()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_slot_conflicts_follow_slot_registry() {
+        let item = slot_test_item;
+        let pact = |n: u32| SlotType::Custom(format!("pact_spirit_{}", n));
+
+        // 内置规则：双手武器占用时副手不可用（与录入顺序无关），每个槽位容量 1
+        let mut input = create_test_input();
+        input.items = vec![
+            item("shield", SlotType::WeaponOff, false),
+            item("axe", SlotType::WeaponMain, true),
+            item("ring_a", SlotType::Ring1, false),
+            item("ring_b", SlotType::Ring2, false),
+            item("ring_c", SlotType::Ring1, false),
+            item("spirit_a", pact(1), false),
+            item("spirit_b", pact(1), false),
+            item("spirit_c", pact(2), false),
+        ];
+        let (kept, dropped) = resolve_slots(&input);
+        assert_eq!(kept, vec!["axe", "ring_a", "ring_b", "spirit_a", "spirit_c"]);
+        assert_eq!(dropped, vec!["shield", "ring_c", "spirit_b"]);
+
+        // 调用方定义的赛季槽位与契灵槽位同组：先录入者占用
+        let season = SlotType::Custom("season_relic".to_string());
+        input.slot_definitions = vec![
+            SlotDefinition { conflict_group: Some("pact".to_string()), ..SlotDefinition::fallback("season_relic") },
+            SlotDefinition { conflict_group: Some("pact".to_string()), ..SlotDefinition::fallback("pact_spirit_1") },
+        ];
+        input.items = vec![item("spirit_a", pact(1), false), item("relic", season.clone(), false)];
+        assert_eq!(resolve_slots(&input), (vec!["spirit_a".to_string()], vec!["relic".to_string()]));
+
+        // 预览装备优先，移除同组其他槽位的装备
+        input.items.pop();
+        input.preview_slot = Some(PreviewSlot { slot_type: season.clone(), item: item("relic", season, false) });
+        assert_eq!(resolve_slots(&input), (vec!["relic".to_string()], vec!["spirit_a".to_string()]));
+
+        // 未登记的槽位 ID（拼写错误、旧版 `ring_1` 形式）视为无效输入，不静默成为新槽位
+        input.preview_slot = None;
+        input.slot_definitions.clear();
+        let legacy: SlotType = serde_json::from_str(r#""ring_1""#).unwrap();
+        input.items = vec![item("legacy", legacy, false), item("typo", SlotType::Custom("ringg1".to_string()), false)];
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("legacy (ring_1), typo (ringg1)"), "{}", err);
+    }
+
+    #[test]
+    fn test_slot_conflict_policies() {
+        let mut weak = slot_test_item("weak_ring", SlotType::Ring1, false);
+        weak.implicit_stats.insert("mod.inc.dmg.all".to_string(), 0.1);
+        let mut strong = slot_test_item("strong_ring", SlotType::Ring1, false);
+        strong.implicit_stats.insert("mod.inc.dmg.all".to_string(), 0.5);
+        let mut input = create_test_input();
+        input.items = vec![weak, strong];
+
+        // 默认：先到先得，被移除的装备给出诊断
+        let result = calculate_dps(&input).unwrap();
+        let conflict = result.warnings.iter().find(|d| d.code == DiagnosticCode::SlotConflict).unwrap();
+        assert_eq!(conflict.message_params["item"], "strong_ring");
+        assert_eq!(conflict.message_params["kept"], "weak_ring");
+        assert_eq!(conflict.message_params["reason"], "capacity");
+
+        // 保留 DPS 更高的一方
+        input.slot_conflict_policy = SlotConflictPolicy::PreferHigherDps;
+        assert_eq!(resolve_slots(&input), (vec!["strong_ring".to_string()], vec!["weak_ring".to_string()]));
+        let best = calculate_dps(&input).unwrap();
+        assert!(best.dps_theoretical > result.dps_theoretical);
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert_eq!(prepared.warnings, best.warnings);
+
+        // 视为错误
+        input.slot_conflict_policy = SlotConflictPolicy::Error;
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("strong_ring (ring1) conflicts with weak_ring"), "{}", err);
+    }
+
+    #[test]
+    fn test_prefer_higher_dps_compares_complete_sets() {
+        let item = |id: &str, slot: SlotType, two_handed: bool, inc: f64| {
+            let mut item = slot_test_item(id, slot, two_handed);
+            item.implicit_stats.insert("mod.inc.dmg.all".to_string(), inc);
+            item
+        };
+        let mut input = create_test_input();
+        input.slot_conflict_policy = SlotConflictPolicy::PreferHigherDps;
+        input.items = vec![
+            item("two_hander", SlotType::WeaponMain, true, 0.5),
+            item("main", SlotType::WeaponMain, false, 0.3),
+            item("off", SlotType::WeaponOff, false, 0.3),
+        ];
+
+        // 单手 + 副手合计高于双手武器，但两件各自都不及双手武器：按完整方案比较
+        assert_eq!(
+            resolve_slots(&input),
+            (vec!["main".to_string(), "off".to_string()], vec!["two_hander".to_string()])
+        );
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.total_increased - 0.6).abs() < 1e-9);
+        let conflicts = result.warnings.iter().filter(|d| d.code == DiagnosticCode::SlotConflict).count();
+        assert_eq!(conflicts, 1);
+
+        // 取舍后的输入不再有冲突，后续计算不重复比较
+        let (resolved, _) = resolve_preferred_slots(&input).unwrap();
+        assert!(matches!(resolve_preferred_slots(&resolved).unwrap().0, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_affix_group_keeps_highest_tier() {
+        let affix = |id: &str, group: &str, tier: u32, value: f64| AffixData {
+            id: id.to_string(),
+            group: group.to_string(),
+            value,
+            stats: HashMap::from([("mod.inc.dmg.all".to_string(), value)]),
+            tier,
+            ..Default::default()
+        };
+        let mut item = slot_test_item("ring", SlotType::Ring1, false);
+        item.affixes = vec![
+            affix("dmg_t1", "inc_dmg", 1, 0.2),
+            affix("crit_t2", "inc_crit", 2, 0.1),
+            affix("dmg_t2", "inc_dmg", 2, 0.25),
+            affix("dmg_t2_low", "inc_dmg", 2, 0.21),
+            affix("free_a", "", 0, 0.05),
+            affix("free_b", "", 0, 0.05),
+        ];
+        let mut input = create_test_input();
+        input.items = vec![item];
+
+        let mut sanitizer = RangeSanitizer::new(SanitizePolicy::Clamp);
+        let (items, diagnostics) = sanitize_items(&input, &mut sanitizer).unwrap();
+        let kept: Vec<&str> = items[0].affixes.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(kept, vec!["crit_t2", "dmg_t2", "free_a", "free_b"]);
+        let dropped: Vec<(&str, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.message_params["dropped"].as_str(), d.message_params["kept"].as_str()))
+            .collect();
+        assert_eq!(dropped, vec![("dmg_t1", "dmg_t2"), ("dmg_t2_low", "dmg_t2")]);
+        assert!(diagnostics.iter().all(|d| d.code == DiagnosticCode::AffixGroupConflict));
+
+        // 计算结果同样给出诊断，且被移除的词缀不参与计算
+        let result = calculate_dps(&input).unwrap();
+        assert_eq!(result.warnings.len(), 2);
+        assert!((result.damage_breakdown.total_increased - 0.45).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_class_restricted_affixes_and_innate_bonuses() {
+        let affix = |id: &str, required_class: Option<&str>| AffixData {
+            id: id.to_string(),
+            value: 0.2,
+            stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.2)]),
+            required_class: required_class.map(str::to_string),
+            ..Default::default()
+        };
+        let mut item = slot_test_item("amulet", SlotType::Amulet, false);
+        item.affixes = vec![affix("generic", None), affix("mage_only", Some("mage")), affix("berserker_only", Some("berserker"))];
+        let mut input = create_test_input();
+        input.items = vec![item];
+
+        // 未选择职业时职业限定词缀均不生效
+        let result = calculate_dps(&input).unwrap();
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.warnings.iter().all(|d| d.code == DiagnosticCode::ClassRestricted));
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.2).abs() < 1e-9);
+
+        // 法师：保留法师词缀，并获得职业天赋（法术伤害 +10%）
+        input.character = Some(CharacterConfig { level: 1, class: Some("mage".to_string()) });
+        let result = calculate_dps(&input).unwrap();
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].message_params["affix"], "berserker_only");
+        assert_eq!(result.warnings[0].message_params["current"], "mage");
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.5).abs() < 1e-9);
+
+        input.character = Some(CharacterConfig { level: 1, class: Some("unknown_hero".to_string()) });
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("unknown_hero"), "{}", err);
+    }
+
+    #[test]
+    fn test_memory_position_effect_dedup_and_synergy() {
+        let affix = |id: &str, tier: u32, value: f64| AffixData {
+            id: id.to_string(),
+            group: "memory_dmg".to_string(),
+            value,
+            stats: HashMap::from([("mod.inc.dmg.all".to_string(), value)]),
+            tier,
+            ..Default::default()
+        };
+        let mut first = slot_test_item("memory_a", SlotType::Memory1, false);
+        first.affixes = vec![affix("dmg_t1", 1, 0.1)];
+        let mut synergy = slot_test_item("memory_b", SlotType::Memory4, false);
+        synergy.implicit_stats.insert("mod.inc.dmg.all.per_1_memory.count".to_string(), 0.05);
+        let mut last = slot_test_item("memory_c", SlotType::Memory6, false);
+        last.affixes = vec![affix("dmg_t2", 2, 0.2)];
+        let mut input = create_test_input();
+        input.items = vec![first, synergy, last];
+
+        // 同组词缀跨追忆只保留等级最高者（追忆6 位置效果 ×1.2）；协同加成按 3 件追忆计（追忆4 位置效果 ×1.1）
+        let result = calculate_dps(&input).unwrap();
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].code, DiagnosticCode::AffixGroupConflict);
+        assert_eq!(result.warnings[0].message_params["item"], "memory_a");
+        assert_eq!(result.warnings[0].message_params["kept"], "memory_c:dmg_t2");
+        let expected = 1.0 + 0.2 * 1.2 + 0.05 * 1.1 * 3.0;
+        assert!((result.damage_breakdown.multipliers.increased_zone - expected).abs() < 1e-9);
+
+        // 同级词缀按位置缩放后的数值比较：0.18 × 1.2 > 0.2
+        input.items[0].affixes = vec![affix("dmg_t2", 2, 0.2)];
+        input.items[2].affixes = vec![affix("dmg_t2", 2, 0.18)];
+        let result = calculate_dps(&input).unwrap();
+        assert_eq!(result.warnings[0].message_params["item"], "memory_a");
+        assert_eq!(result.warnings[0].message_params["kept"], "memory_c:dmg_t2");
+    }
+
+    #[test]
+    fn test_expression_overrides() {
+        let mut input = create_test_input();
+        input.context_values.insert("fighting_will".to_string(), 50.0);
+        input.global_overrides = HashMap::from([("strength".to_string(), 100.0.into())]);
+        let base = calculate_dps(&input).unwrap();
+
+        // 表达式引用聚合后的属性（力量）与上下文数值（战意层数）
+        input.global_overrides.insert(
+            "mod.inc.dmg.all".to_string(),
+            OverrideValue::Expression("strength * 0.002 + fighting_will * 0.001".to_string()),
+        );
+        let result = calculate_dps(&input).unwrap();
+        let gained = result.damage_breakdown.multipliers.increased_zone - base.damage_breakdown.multipliers.increased_zone;
+        assert!((gained - 0.25).abs() < 1e-9, "{}", gained);
+
+        // 表达式在核心属性加成之后求值（每 2 点力量 +1 生命）
+        input.global_overrides.insert(
+            "mod.inc.dmg.all".to_string(),
+            OverrideValue::Expression("base.life * 0.01".to_string()),
+        );
+        let result = calculate_dps(&input).unwrap();
+        let gained = result.damage_breakdown.multipliers.increased_zone - base.damage_breakdown.multipliers.increased_zone;
+        assert!((gained - 0.5).abs() < 1e-9, "{}", gained);
+
+        input.global_overrides.insert("crit.chance".to_string(), OverrideValue::Expression("strength *".to_string()));
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("crit.chance"), "{}", err);
+    }
+
+    #[test]
+    fn test_custom_multiplier_zone() {
+        let mut input = create_test_input();
+        let base = calculate_dps(&input).unwrap();
+        assert_eq!(base.damage_breakdown.multipliers.custom_zones["final_damage"], 1.0);
+
+        // 同一乘区内的修正相乘（product），并作用于命中伤害
+        input.global_overrides = HashMap::from([
+            ("zone.final_damage.a".to_string(), 0.5.into()),
+            ("zone.final_damage.b".to_string(), 0.2.into()),
+        ]);
+        let result = calculate_dps(&input).unwrap();
+        let multipliers = &result.damage_breakdown.multipliers;
+        assert!((multipliers.custom_zones["final_damage"] - 1.8).abs() < 1e-9);
+        assert!((result.hit_damage / base.hit_damage - 1.8).abs() < 1e-9);
+        // 作用于每个伤害包：有效 DPS 与伤害范围同样计入
+        assert!((result.dps_effective / base.dps_effective - 1.8).abs() < 1e-9);
+        let range = |output: &CalculatorOutput| output.damage_breakdown.ranges["fire"].max;
+        assert!((range(&result) / range(&base) - 1.8).abs() < 1e-9);
+
+        let sources = &multipliers.zone_sources["final_damage"];
+        assert_eq!(sources[0].label_key, "zone.final_damage");
+        assert_eq!(sources.len(), 3);
+
+        // 两阶段计算一致
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.hit_damage - result.hit_damage).abs() < 1e-9);
+
+        // 乘区键不做单位换算：超过 1 的值原样计入
+        input.global_overrides.insert("zone.final_damage.a".to_string(), 1.5.into());
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.custom_zones["final_damage"] - 3.0).abs() < 1e-9);
+
+        // 持续伤害技能同样计入
+        input.active_skill.tags.push("Tag_DOT".to_string());
+        let mut dot_base = input.clone();
+        dot_base.global_overrides.clear();
+        let ratio = calculate_dps(&input).unwrap().dps_theoretical / calculate_dps(&dot_base).unwrap().dps_theoretical;
+        assert!((ratio - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_override_groups_toggle_per_calculation() {
+        let group = |name: &str, enabled: bool, value: f64| OverrideGroup {
+            name: name.to_string(),
+            enabled,
+            overrides: HashMap::from([("mod.inc.dmg.all".to_string(), value.into())]),
+        };
+        let mut input = create_test_input();
+        input.global_overrides = HashMap::from([("mod.inc.dmg.all".to_string(), 0.1.into())]);
+        input.override_groups = vec![group("bossing buffs", true, 0.3), group("map mods", false, 0.5)];
+
+        // 启用的分组与 global_overrides 叠加，停用的分组不计入
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.4).abs() < 1e-9);
+
+        let toggle = Toggle { kind: ToggleKind::OverrideGroup, key: "map mods".to_string() };
+        let scenarios = evaluate_toggles(&input, &[toggle]).unwrap();
+        assert!((scenarios[1].dps_theoretical / scenarios[0].dps_theoretical - 1.9 / 1.4).abs() < 1e-9);
+
+        let toggle = Toggle { kind: ToggleKind::OverrideGroup, key: "map_mods".to_string() };
+        let err = evaluate_toggles(&input, &[toggle]).unwrap_err().to_string();
+        assert!(err.contains("unknown override group 'map_mods'"), "{}", err);
+
+        input.override_groups.push(group("bossing buffs", false, 0.1));
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("duplicate override group"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_affix_condition_rejected() {
+        let mut ring = slot_test_item("ring_1", SlotType::Ring1, false);
+        ring.affixes.push(AffixData {
+            id: "moving_dmg".to_string(),
+            group: "dmg".to_string(),
+            value: 0.3,
+            stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+            condition: Some("is_moving and life_percent <= 0.5".to_string()),
+            branches: vec![AffixBranch {
+                condition: "has_tags(\"Tag_Spell\")".to_string(),
+                stats: HashMap::new(),
+            }],
+            ..Default::default()
+        });
+        let mut input = create_test_input();
+        input.items = vec![ring];
+
+        // 出错位置、期望的词法单元与拼写提示一并报告
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("ring_1:moving_dmg \"is_moving and life_percent <= 0.5\": Unexpected 'and' at 10"), "{}", err);
+        assert!(err.contains("did you mean '&&'?"), "{}", err);
+        assert!(err.contains("did you mean 'has_tag'?"), "{}", err);
+        assert!(prepare_context(&input).is_err());
+
+        input.items[0].affixes[0].condition = Some("is_moving && life_percent <= 0.5".to_string());
+        input.items[0].affixes[0].branches.clear();
+        assert!(calculate_dps(&input).is_ok());
+
+        // 分支在聚合阶段选择，无法引用聚合后的属性
+        input.items[0].affixes[0].branches.push(AffixBranch {
+            condition: "stat(\"crit.chance\") >= 0.5".to_string(),
+            stats: HashMap::new(),
+        });
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("stat() is not supported in branch conditions"), "{}", err);
+        assert!(prepare_context(&input).is_err());
+    }
+
+    #[test]
+    fn test_undefined_condition_keys_reported() {
+        let condition_keys = |output: &CalculatorOutput| -> Vec<(String, String, String)> {
+            let mut keys: Vec<_> = output
+                .warnings
+                .iter()
+                .filter(|d| d.code == DiagnosticCode::UnknownConditionKey)
+                .map(|d| {
+                    let param = |name: &str| d.message_params[name].clone();
+                    (param("source"), param("key"), param("suggestion"))
+                })
+                .collect();
+            keys.sort();
+            keys
+        };
+
+        // 内嵌数据（标签注册表派生条件、属性钩子）只引用已知标识符
+        let mut input = create_test_input();
+        assert!(condition_keys(&calculate_dps(&input).unwrap()).is_empty());
+
+        let mut ring = slot_test_item("ring_1", SlotType::Ring1, false);
+        ring.affixes.push(AffixData {
+            id: "moving_dmg".to_string(),
+            stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+            condition: Some("is_movng && life_precent <= 0.5 && mechanic_active(\"focus_blesing\")".to_string()),
+            ..Default::default()
+        });
+        input.items = vec![ring];
+        input.global_overrides.insert("crit.chance".to_string(), OverrideValue::Expression("intelligense / 1000".to_string()));
+        input.override_groups.push(OverrideGroup {
+            name: "bossing".to_string(),
+            enabled: true,
+            overrides: HashMap::from([(
+                "mod.inc.dmg.all".to_string(),
+                OverrideValue::Expression("stat(\"crit.chanse\") + base.mana / 1000".to_string()),
+            )]),
+        });
+        input.context_values.insert("enemy_count".to_string(), 3.0);
+
+        let output = calculate_dps(&input).unwrap();
+        assert_eq!(
+            condition_keys(&output),
+            vec![
+                ("bossing:mod.inc.dmg.all".to_string(), "crit.chanse".to_string(), "-".to_string()),
+                ("global_overrides:crit.chance".to_string(), "intelligense".to_string(), "-".to_string()),
+                ("ring_1:moving_dmg".to_string(), "focus_blesing".to_string(), "focus_blessing".to_string()),
+                ("ring_1:moving_dmg".to_string(), "is_movng".to_string(), "is_moving".to_string()),
+                ("ring_1:moving_dmg".to_string(), "life_precent".to_string(), "life_percent".to_string()),
+            ]
+        );
+        let prepared = prepare_context(&input).unwrap();
+        assert_eq!(condition_keys(&output), condition_keys(&calculate_from_prepared(&prepared, &input.target_config).unwrap()));
+
+        // 输入提供的上下文键与机制层数变量均为已知标识符
+        input.items[0].affixes[0].condition = Some("enemy_count >= 3 && focus_blessing_stacks > 0".to_string());
+        input.global_overrides.clear();
+        input.override_groups.clear();
+        assert!(condition_keys(&calculate_dps(&input).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_item_requirements_reported_and_optionally_excluded() {
+        let mut staff = slot_test_item("staff", SlotType::WeaponMain, true);
+        staff.requirements = Some(ItemRequirements { level: 30, intelligence: 60, ..Default::default() });
+        staff.implicit_stats.insert("mod.inc.dmg.all".to_string(), 0.5);
+        let mut ring = slot_test_item("ring", SlotType::Ring1, false);
+        ring.implicit_stats.insert("intelligence".to_string(), 40.0);
+        let mut input = create_test_input();
+        input.items = vec![staff, ring];
+        input.character = Some(CharacterConfig { level: 20, class: None });
+
+        // 等级与智慧均不足：逐项诊断，默认仍参与计算
+        let result = calculate_dps(&input).unwrap();
+        let unmet: Vec<(&str, &str)> = result
+            .warnings
+            .iter()
+            .filter(|d| d.code == DiagnosticCode::RequirementUnmet)
+            .map(|d| (d.message_params["requirement"].as_str(), d.message_params["actual"].as_str()))
+            .collect();
+        assert_eq!(unmet, vec![("level", "20"), ("intelligence", "40")]);
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.5).abs() < 1e-9);
+
+        input.exclude_unusable_items = true;
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.0).abs() < 1e-9);
+
+        // 满足需求后不再诊断
+        input.character = Some(CharacterConfig { level: 30, class: None });
+        input.items[1].implicit_stats.insert("intelligence".to_string(), 60.0);
+        let result = calculate_dps(&input).unwrap();
+        assert!(result.warnings.iter().all(|d| d.code != DiagnosticCode::RequirementUnmet));
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.5).abs() < 1e-9);
+
+        // 装备自身的属性不满足自身需求
+        input.items[1].implicit_stats.insert("intelligence".to_string(), 20.0);
+        input.items[0].implicit_stats.insert("intelligence".to_string(), 40.0);
+        let result = calculate_dps(&input).unwrap();
+        let unmet: Vec<&str> = result
+            .warnings
+            .iter()
+            .filter(|d| d.code == DiagnosticCode::RequirementUnmet)
+            .map(|d| d.message_params["actual"].as_str())
+            .collect();
+        assert_eq!(unmet, vec!["20"]);
+    }
+
+    #[test]
+    fn test_weapon_restricted_modifiers() {
+        let weapon = |base_type: &str, slot: SlotType, is_two_handed: bool| ItemData {
+            id: base_type.to_string(),
+            base_type: base_type.to_string(),
+            slot,
+            is_two_handed,
+            base_implicit_stats: HashMap::new(),
+            implicit_stats: HashMap::new(),
+            affixes: vec![],
+            tags: vec![],
+            is_unique: false,
+            is_corrupted: false,
+            set_id: None,
+            quality: 0,
+            requirements: None,
+        };
+        let mut input = create_test_input();
+        input.global_overrides = HashMap::from([
+            ("mod.inc.dmg.axe".to_string(), 0.3.into()),
+            ("mod.inc.dmg.two_handed".to_string(), 0.2.into()),
+            ("mod.inc.dmg.dual_wield".to_string(), 0.1.into()),
+        ]);
+
+        // 无武器：全部不生效
+        let bare = calculate_dps(&input).unwrap();
+        assert_eq!(bare.damage_breakdown.multipliers.increased_zone, 1.0);
+
+        // 双手斧：斧 + 双手
+        input.items = vec![weapon("axe_2h", SlotType::WeaponMain, true)];
+        let axe = calculate_dps(&input).unwrap();
+        assert!((axe.damage_breakdown.multipliers.increased_zone - 1.5).abs() < 1e-6);
+
+        // 单手剑 + 副手爪：只有双持生效
+        input.items = vec![
+            weapon("sword_1h_phys", SlotType::WeaponMain, false),
+            weapon("claw_1h", SlotType::WeaponOff, false),
+        ];
+        let dual = calculate_dps(&input).unwrap();
+        assert!((dual.damage_breakdown.multipliers.increased_zone - 1.1).abs() < 1e-6);
+
+        // 两阶段计算同样保留武器标签
+        let prepared = prepare_context(&input).unwrap();
+        let staged = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+        assert!((staged.hit_damage - dual.hit_damage).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_skill_parts_selected_and_combined() {
+        let mut input = create_test_input();
+        input.active_skill.tags = vec!["Tag_Spell".to_string()];
+        input.active_skill.parts = vec![
+            SkillPart {
+                id: "hit".to_string(),
+                base_damage: HashMap::from([
+                    ("dmg.fire.min".to_string(), 100.0),
+                    ("dmg.fire.max".to_string(), 100.0),
+                ]),
+                effectiveness: None,
+                tags: vec![],
+                stats: HashMap::new(),
+                weight: 1.0,
+            },
+            SkillPart {
+                id: "explosion".to_string(),
+                base_damage: HashMap::from([
+                    ("dmg.fire.min".to_string(), 50.0),
+                    ("dmg.fire.max".to_string(), 50.0),
+                ]),
+                effectiveness: None,
+                tags: vec!["Tag_AOE".to_string()],
+                stats: HashMap::from([("mod.inc.speed.cast".to_string(), 0.5)]),
+                weight: 2.0,
+            },
+        ];
+        // 范围伤害只作用于爆炸部分
+        input.global_overrides = HashMap::from([("mod.inc.dmg.aoe".to_string(), 0.5.into())]);
+
+        input.active_skill.selected_part = Some(0);
+        let hit = calculate_dps(&input).unwrap();
+        input.active_skill.selected_part = Some(1);
+        let explosion = calculate_dps(&input).unwrap();
+        assert!((explosion.hit_damage / hit.hit_damage - 0.75).abs() < 1e-6);
+
+        // 未选择时按权重合并：1 × 命中 + 2 × 爆炸
+        input.active_skill.selected_part = None;
+        let combined = calculate_dps(&input).unwrap();
+        let expected = hit.hit_damage + 2.0 * explosion.hit_damage;
+        assert!((combined.hit_damage - expected).abs() < 1e-6);
+        assert!(combined.debug_trace.iter().any(|t| t.phase == "Skill Parts"));
+
+        // 速率按各部分伤害占比加权，伤害构成按权重求和
+        assert!(explosion.rate > hit.rate);
+        let explosion_share = 2.0 * explosion.hit_damage / expected;
+        let expected_rate = hit.rate * (1.0 - explosion_share) + explosion.rate * explosion_share;
+        assert!((combined.rate - expected_rate).abs() < 1e-6);
+        let expected_base = hit.damage_breakdown.base_damage + 2.0 * explosion.damage_breakdown.base_damage;
+        assert!((combined.damage_breakdown.base_damage - expected_base).abs() < 1e-6);
+
+        // 两阶段计算默认同样按合并模式
+        let prepared = prepare_context(&input).unwrap();
+        assert_eq!(prepared.part_contexts.len(), 2);
+        let staged = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+        assert!((staged.hit_damage - combined.hit_damage).abs() < 1e-6);
+        assert!((staged.rate - combined.rate).abs() < 1e-6);
+
+        // 持续输出按合并后的速率与魔力消耗折算
+        input.active_skill.mana_cost = 10;
+        let combined = calculate_dps(&input).unwrap();
+        let spend = combined.mana_cost * combined.rate;
+        input.sustain_model = Some(SustainModel { mana_regen: Some(spend * 0.5), ..Default::default() });
+        let sustained = calculate_dps(&input).unwrap();
+        assert!((sustained.dps_sustained - combined.dps_theoretical * 0.5).abs() < 1e-6);
+        input.sustain_model = None;
+
+        // 越界索引报错
+        input.active_skill.selected_part = Some(5);
+        assert!(calculate_dps(&input).is_err());
+    }
+
+    #[test]
+    fn test_skill_level_bonus_from_gear() {
+        let mut input = create_test_input();
+        input.active_skill.level = 20;
+        let base = calculate_dps(&input).unwrap();
+
+        // +1 全技能等级、+1 火焰技能等级生效；冰冷技能等级不生效 → 22 级
+        input.global_overrides = HashMap::from([
+            ("skill.level".to_string(), 1.0.into()),
+            ("skill.level.fire".to_string(), 1.0.into()),
+            ("skill.level.cold".to_string(), 3.0.into()),
+        ]);
+        let boosted = calculate_dps(&input).unwrap();
+        assert!((boosted.hit_damage / base.hit_damage - 1.1 * 1.1).abs() < 1e-6);
+        assert!(boosted
+            .debug_trace
+            .iter()
+            .any(|t| t.phase == "Base Damage" && t.description.contains("20 + 2")));
+    }
+
+    #[test]
+    fn test_skill_level_data_from_embedded_registry() {
+        let mut input = create_test_input();
+        input.active_skill.id = "skill_fire_burst".to_string();
+        input.active_skill.level = 10;
+        let lv10 = calculate_dps(&input).unwrap();
+        let registry = SkillRegistry::shared();
+        let expected = registry.level_data("skill_fire_burst", 10).unwrap();
+        let fire = lv10.debug_trace.iter().find(|t| t.phase == "Base Damage").unwrap().values["fire"];
+        let table_avg = (expected.base_damage["dmg.fire.min"] + expected.base_damage["dmg.fire.max"]) / 2.0;
+        assert!((fire - table_avg).abs() < 1e-6);
+
+        // 装备等级加成同样提升查表等级
+        input.global_overrides = HashMap::from([("skill.level".to_string(), 2.0.into())]);
+        let lv12 = calculate_dps(&input).unwrap();
+        assert!(lv12.hit_damage > lv10.hit_damage);
+
+        // 调用方提供的等级数据优先
+        input.active_skill.level_data = Some(expected.clone());
+        let provided = calculate_dps(&input).unwrap();
+        assert!((provided.hit_damage - lv10.hit_damage).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_invalid_support_warning_and_exclusion() {
+        let mut input = create_test_input();
+        let mut melee_support = input.active_skill.clone();
+        melee_support.id = "support_melee".to_string();
+        melee_support.skill_type = SkillType::Support;
+        melee_support.support_requirements = vec!["Tag_Melee".to_string()];
+        melee_support.stats = HashMap::from([("mod.more.dmg.all".to_string(), 0.5)]);
+        input.support_skills.push(melee_support);
+
+        // 默认只警告，辅助技能仍参与计算
+        let warned = calculate_dps(&input).unwrap();
+        assert_eq!(warned.warnings.len(), 1);
+        assert_eq!(warned.warnings[0].code, DiagnosticCode::InvalidSupport);
+        assert!(warned.warnings[0].message.contains("support_melee"));
+
+        input.exclude_invalid_supports = true;
+        let excluded = calculate_dps(&input).unwrap();
+        assert_eq!(excluded.warnings.len(), 1);
+        assert!((warned.hit_damage / excluded.hit_damage - 1.5).abs() < 1e-6);
+
+        // 主技能满足需求时无警告
+        input.active_skill.tags.push("Tag_Melee".to_string());
+        let valid = calculate_dps(&input).unwrap();
+        assert!(valid.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_support_level_scaling() {
+        let mut input = create_test_input();
+        input.active_skill.mana_cost = 10;
+        let mut support = input.active_skill.clone();
+        support.id = "support_scaling".to_string();
+        support.skill_type = SkillType::Support;
+        support.level = 1;
+        support.stats = HashMap::from([("mod.more.dmg.all".to_string(), 0.2)]);
+        support.stats_per_level = HashMap::from([("mod.more.dmg.all".to_string(), 0.01)]);
+        support.mana_multiplier = 1.2;
+        support.mana_multiplier_per_level = 0.01;
+        input.support_skills.push(support);
+
+        let lv1 = calculate_dps(&input).unwrap();
+        assert!((lv1.mana_cost - 12.0).abs() < 1e-6);
+
+        // 20 级：总伤害 +39%，魔力倍率 1.39
+        input.support_skills[0].level = 20;
+        let lv20 = calculate_dps(&input).unwrap();
+        assert!((lv20.hit_damage / lv1.hit_damage - 1.39 / 1.2).abs() < 1e-6);
+        assert!((lv20.mana_cost - 13.9).abs() < 1e-6);
+
+        // 两阶段计算保留魔力消耗
+        let prepared = prepare_context(&input).unwrap();
+        let staged = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+        assert!((staged.mana_cost - 13.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_aura_effect_scales_auras_and_blessings() {
+        let mut input = create_test_input();
+        let mut aura = input.active_skill.clone();
+        aura.id = "aura_wrath".to_string();
+        aura.skill_type = SkillType::Aura;
+        aura.stats = HashMap::from([("mod.inc.dmg.all".to_string(), 0.2)]);
+        input.auras = vec![aura];
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "focus_blessing".to_string(),
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), 0.1)]),
+            aura_scalable: true,
+            ..Default::default()
+        }];
+        input.mechanic_states = vec![MechanicState {
+            id: "focus_blessing".to_string(),
+            current_stacks: 2,
+            max_stacks: 4,
+            is_active: true,
+        }];
+
+        // 光环 20% + 祝福 2 × 10%
+        let base = calculate_dps(&input).unwrap();
+        assert!((base.damage_breakdown.multipliers.increased_zone - 1.4).abs() < 1e-6);
+
+        // +50% 光环效果：(20% + 20%) × 1.5
+        input.global_overrides = HashMap::from([("mod.inc.aura.effect".to_string(), 0.5.into())]);
+        let boosted = calculate_dps(&input).unwrap();
+        assert!((boosted.damage_breakdown.multipliers.increased_zone - 1.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_mechanic_more_effects_multiply() {
+        let mut input = create_test_input();
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "test_overload".to_string(),
+            base_effect_per_stack: HashMap::from([("mod.more.dmg.all".to_string(), 0.1)]),
+            ..Default::default()
+        }];
+        // 狂乱球使用内置定义：3 层 × 4% More
+        input.mechanic_states = vec![
+            MechanicState {
+                id: "test_overload".to_string(),
+                current_stacks: 2,
+                max_stacks: 4,
+                is_active: true,
+            },
+            MechanicState {
+                id: "frenzy_charge".to_string(),
+                current_stacks: 3,
+                max_stacks: 3,
+                is_active: true,
+            },
+        ];
+
+        // 两个机制的 More 相乘：1.2 × 1.12
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.more_zone - 1.2 * 1.12).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_mechanic_contributions() {
+        let mut input = create_test_input();
+        input.mechanic_states = vec![
+            MechanicState {
+                id: "frenzy_charge".to_string(),
+                current_stacks: 3,
+                max_stacks: 3,
+                is_active: true,
             },
-            support_skills: vec![
-                SkillData {
-                    id: "support_lightning_to_cold".to_string(),
-                    skill_type: SkillType::Support,
-                    damage_type: None,
-                    is_attack: false,
-                    level: 20,
-                    base_damage: HashMap::new(),
-                    base_time: 0.0,
-                    cooldown: None,
-                    mana_cost: 0,
-                    effectiveness: 1.0,
-                    tags: vec!["Tag_Support".to_string(), "Tag_Lightning".to_string(), "Tag_Cold".to_string()],
-                    stats: HashMap::from([
-                        ("conv.lightning_to_cold".to_string(), 1.0),
-                        ("mod.more.dmg.lightning".to_string(), 0.25),
-                    ]),
-                    injected_tags: vec![],
-                    mana_multiplier: 1.0,
-                    level_data: None,
-                    scaling_rules: vec![],
-                },
-                SkillData {
-                    id: "support_psychic_burst".to_string(),
-                    skill_type: SkillType::Support,
-                    damage_type: None,
-                    is_attack: false,
-                    level: 20,
-                    base_damage: HashMap::new(),
-                    base_time: 0.0,
-                    cooldown: None,
-                    mana_cost: 0,
-                    effectiveness: 1.0,
-                    tags: vec!["Tag_Support".to_string(), "Tag_Spell".to_string()],
-                    stats: HashMap::from([
-                        ("mod.more.dmg.all".to_string(), 0.45),
-                        ("speed.cast".to_string(), 0.16),
-                    ]),
-                    injected_tags: vec![],
-                    mana_multiplier: 1.0,
-                    level_data: None,
-                    scaling_rules: vec![],
-                },
-            ],
-            global_overrides: HashMap::from([
-                // 施法速度 +100%（叠加灵能乍泄 16% 之后，PlaySafe 会把施法速度用于充能）
-                ("speed.cast".to_string(), 1.0),
-                // 暴击率换算自：基础 500 法术暴击值 + 战意 100 层（每层 2%），合计 ~10%
-                ("crit.chance".to_string(), 0.10),
-                // 世事无常：拉伸最小/最大伤害范围
-                ("mod.more.dmg.phys.min".to_string(), -0.90),
-                ("mod.more.dmg.phys.max".to_string(), 0.80),
-                ("mod.more.dmg.min".to_string(), -0.40),
-                ("mod.more.dmg.max".to_string(), 0.40),
-                // Spell Burst 层数（灯塔+2），默认上限 0 → 这里显式给 2
-                ("mechanic.spell_burst.max_stacks".to_string(), 2.0),
-                // PlaySafe 开启
-                ("flag.talent.playsafe".to_string(), 1.0),
-            ]),
-            preview_slot: None,
-            mechanic_states: vec![
-                MechanicState {
-                    id: "focus_blessing".to_string(),
-                    current_stacks: 6,
-                    max_stacks: 6,
-                    is_active: true,
-                },
-                MechanicState {
-                    id: "fighting_will".to_string(),
-                    current_stacks: 100,
-                    max_stacks: 100,
-                    is_active: true,
-                },
-            ],
-            mechanic_definitions: vec![
-                MechanicDefinition {
-                    id: "focus_blessing".to_string(),
-                    display_name: "聚能祝福".to_string(),
-                    category: "blessing".to_string(),
-                    tag_key: "Mech_Blessing".to_string(),
-                    default_max_stacks: 6,
-                    base_effect_per_stack: HashMap::from([
-                        ("mod.more.dmg.all".to_string(), 0.04),
-                        ("mod.more.dmg.spell".to_string(), 0.03),
-                    ]),
-                    description: "聚能祝福每层提供额外伤害".to_string(),
-                },
-                MechanicDefinition {
-                    id: "fighting_will".to_string(),
-                    display_name: "战意".to_string(),
-                    category: "resource".to_string(),
-                    tag_key: "Mech_FightingWill".to_string(),
-                    default_max_stacks: 100,
-                    base_effect_per_stack: HashMap::from([
-                        ("crit.chance.rating".to_string(), 2.0),
-                    ]),
-                    description: "战意每层提供 2 点暴击值".to_string(),
-                },
-            ],
-        };
+            MechanicState {
+                id: "tenacity_blessing".to_string(),
+                current_stacks: 4,
+                max_stacks: 4,
+                is_active: true,
+            },
+        ];
+        let mut support = input.active_skill.clone();
+        support.id = "support_focus".to_string();
+        support.skill_type = SkillType::Support;
+        support.stats = HashMap::from([("grant.mechanic.focus_blessing".to_string(), 4.0)]);
+        input.support_skills = vec![support];
+
+        let contributions = mechanic_contributions(&input).unwrap();
+        let ids: Vec<&str> = contributions.iter().map(|c| c.mechanic_id.as_str()).collect();
+        assert_eq!(ids.len(), 3);
+
+        // 授予的聚能祝福也计入；防御型祝福无 DPS 贡献，排在最后
+        let focus = contributions.iter().find(|c| c.mechanic_id == "focus_blessing").unwrap();
+        assert_eq!(focus.stacks, 4.0);
+        assert!(focus.dps_delta > 0.0);
+        assert_eq!(ids[2], "tenacity_blessing");
+        assert!(contributions[2].dps_delta.abs() < 1e-9);
+
+        // 关闭后重算的 DPS 与贡献一致
+        let base = calculate_dps(&input).unwrap();
+        input.disabled_mechanics = vec!["focus_blessing".to_string()];
+        let without = calculate_dps(&input).unwrap();
+        assert!((without.dps_theoretical - focus.dps_without).abs() < 1e-6);
+        assert!((base.dps_theoretical - without.dps_theoretical - focus.dps_delta).abs() < 1e-6);
+    }
 
-        let result = calculate_dps(&input).expect("calc ok");
-        println!(
-            "SpellBurst DPS={:.2}, hit={:.2}, rate={:.2}/s, crit={:.2}%/{:.2}x",
-            result.dps_theoretical,
-            result.hit_damage,
-            result.rate,
-            result.crit_chance * 100.0,
-            result.crit_multiplier
-        );
+    #[test]
+    fn test_evaluate_toggles_matrix() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "moving_dmg".to_string(),
+                group: "dmg".to_string(),
+                value: 0.5,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.5)]),
+                condition: Some("is_moving".to_string()),
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "focus_blessing".to_string(),
+            default_max_stacks: 2,
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), 0.25)]),
+            ..Default::default()
+        }];
+
+        let toggles = vec![
+            Toggle { kind: ToggleKind::Flag, key: "is_moving".to_string() },
+            Toggle { kind: ToggleKind::Mechanic, key: "focus_blessing".to_string() },
+        ];
+        let scenarios = evaluate_toggles(&input, &toggles).unwrap();
+        assert_eq!(scenarios.len(), 4);
+        assert!(scenarios[0].flipped.is_empty());
+        assert_eq!(scenarios[0].dps_delta_percent, 0.0);
+        assert_eq!(scenarios[3].flipped, toggles);
+
+        // 移动 +50%，祝福按满层开启 2 × 25%：两者各 +50%，组合 +100%
+        assert!((scenarios[1].dps_delta_percent - 50.0).abs() < 1e-6);
+        assert!((scenarios[2].dps_delta_percent - 50.0).abs() < 1e-6);
+        assert!((scenarios[3].dps_delta_percent - 100.0).abs() < 1e-6);
+
+        let too_many = vec![toggles[0].clone(); MAX_TOGGLES + 1];
+        assert!(evaluate_toggles(&input, &too_many).is_err());
+    }
 
-        // 基本合理性校验：开启迸发后 rate 应大于基础 1/0.65 ≈ 1.54/s
-        assert!(result.rate > 1.54);
-        assert!(result.dps_theoretical > 0.0);
+    #[test]
+    fn test_sweep_value_curves() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "low_life_dmg".to_string(),
+                group: "dmg".to_string(),
+                value: 0.5,
+                stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.5)]),
+                condition: Some("life_percent <= 0.5".to_string()),
+                ..Default::default()
+            }],
+            ..ItemData::new("ring_1", "ring", SlotType::Ring1)
+        }];
+
+        // 词缀条件引用的数值：逐点重算，阈值处 DPS 跳变
+        let curve = sweep_value(&input, "life_percent", 0.0, 1.0, 5).unwrap();
+        let values: Vec<f64> = curve.iter().map(|p| p.value).collect();
+        assert_eq!(values, vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert!((curve[2].dps_theoretical / curve[3].dps_theoretical - 1.5).abs() < 1e-6);
+        assert_eq!(curve[0].dps_theoretical, curve[2].dps_theoretical);
+
+        // 无关数值：复用同一个 PreparedContext，曲线平坦且与完整计算一致
+        let base = calculate_dps(&input).unwrap();
+        let flat = sweep_value(&input, "unused_value", 0.0, 10.0, 3).unwrap();
+        assert!(flat.iter().all(|p| (p.dps_theoretical - base.dps_theoretical).abs() < 1e-6));
+
+        // 机制层数：狂乱球 0 → 3 层 More 伤害递增，超出上限按上限计算
+        let charges = sweep_value(&input, "frenzy_charge_stacks", 0.0, 4.0, 5).unwrap();
+        assert!(charges.windows(2).take(3).all(|w| w[1].dps_theoretical > w[0].dps_theoretical));
+        assert_eq!(charges[3].dps_theoretical, charges[4].dps_theoretical);
+
+        assert!(sweep_value(&input, "life_percent", 0.0, 1.0, 0).is_err());
     }
-}
 
-/// 8. 计算暴击
-fn calculate_crit(pool: &StatPool, context_flags: &HashMap<String, bool>) -> (f64, f64) {
-    // 基础暴击率
-    let base_crit = pool.get_base("crit.chance");
-    let crit_inc = pool.get_increased("crit.chance");
-    let crit_chance = (base_crit * (1.0 + crit_inc)).min(1.0).max(0.0);
+    #[test]
+    fn test_exclusive_mechanics_warning() {
+        let mut input = create_test_input();
+        let blessing = |id: &str, value: f64| MechanicDefinition {
+            id: id.to_string(),
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), value)]),
+            exclusive_group: Some("blessing".to_string()),
+            ..Default::default()
+        };
+        input.mechanic_definitions = vec![blessing("focus_blessing", 0.1), blessing("agility_blessing", 0.05)];
+        input.mechanic_states = ["focus_blessing", "agility_blessing"]
+            .iter()
+            .zip([2, 4])
+            .map(|(id, stacks)| MechanicState {
+                id: id.to_string(),
+                current_stacks: stacks,
+                max_stacks: 4,
+                is_active: true,
+            })
+            .collect();
 
-    // 暴击伤害
-    let base_multi = 1.5; // 基础暴击伤害 150%
-    let crit_dmg_inc = pool.get_increased("crit.dmg");
-    let crit_multiplier = base_multi + crit_dmg_inc;
+        // 灵动 4 层生效，聚能被压制：4 × 5%
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.2).abs() < 1e-6);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].code, DiagnosticCode::MechanicSuppressed);
+        assert!(result.warnings[0].message.contains("focus_blessing"));
+    }
 
-    // 检查是否无法暴击
-    if context_flags.get("cannot_crit").copied().unwrap_or(false) {
-        return (0.0, 1.0);
+    #[test]
+    fn test_mechanic_granted_by_support() {
+        let mut input = create_test_input();
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "focus_blessing".to_string(),
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), 0.1)]),
+            ..Default::default()
+        }];
+        let mut support = input.active_skill.clone();
+        support.id = "support_focus".to_string();
+        support.skill_type = SkillType::Support;
+        support.stats = HashMap::from([("grant.mechanic.focus_blessing".to_string(), 3.0)]);
+        input.support_skills = vec![support];
+
+        // 未手动添加状态，辅助授予 3 层：3 × 10%
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.3).abs() < 1e-6);
     }
 
-    (crit_chance, crit_multiplier)
-}
+    #[test]
+    fn test_resource_uptime_uses_speed_adjusted_rate() {
+        // 怒火：每秒获取 10 点，每次施放消耗 20 点，每点 +1% 伤害
+        let mut input = create_test_input();
+        input.stack_mode = StackMode::Realistic;
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "rage".to_string(),
+            category: "resource".to_string(),
+            default_max_stacks: 100,
+            generation_rate: Some(10.0),
+            cost_per_use: Some(20.0),
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), 0.01)]),
+            ..Default::default()
+        }];
+        input.mechanic_states = vec![MechanicState {
+            id: "rage".to_string(),
+            current_stacks: 100,
+            max_stacks: 100,
+            is_active: true,
+        }];
+
+        // 施法时间 0.8 秒：每秒 1.25 次，可持续 40%
+        let base = calculate_dps(&input).unwrap();
+        assert!((base.damage_breakdown.multipliers.increased_zone - 1.4).abs() < 1e-6);
+
+        // 施法速度 +100%：每秒 2.5 次，可持续比例减半
+        input.global_overrides = HashMap::from([("mod.inc.speed.cast".to_string(), 1.0.into())]);
+        let hasted = calculate_dps(&input).unwrap();
+        assert!((hasted.damage_breakdown.multipliers.increased_zone - 1.2).abs() < 1e-6);
+        let prepared = prepare_context(&input).unwrap();
+        let staged = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+        assert!((staged.damage_breakdown.multipliers.increased_zone - 1.2).abs() < 1e-6);
+    }
 
-/// 计算暴击因子
-fn calculate_crit_factor(crit_chance: f64, crit_multiplier: f64) -> f64 {
-    // 平均伤害 = (1 - crit_chance) * 1.0 + crit_chance * crit_multiplier
-    1.0 + crit_chance * (crit_multiplier - 1.0)
-}
+    #[test]
+    fn test_granted_mechanic_conflict_warning() {
+        let mut input = create_test_input();
+        let blessing = |id: &str, value: f64| MechanicDefinition {
+            id: id.to_string(),
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), value)]),
+            exclusive_group: Some("blessing".to_string()),
+            default_max_stacks: 4,
+            ..Default::default()
+        };
+        input.mechanic_definitions = vec![blessing("focus_blessing", 0.1), blessing("agility_blessing", 0.05)];
+        input.mechanic_states = vec![MechanicState {
+            id: "agility_blessing".to_string(),
+            current_stacks: 2,
+            max_stacks: 4,
+            is_active: true,
+        }];
+        let mut support = input.active_skill.clone();
+        support.id = "support_focus".to_string();
+        support.skill_type = SkillType::Support;
+        support.stats = HashMap::from([("grant.mechanic.focus_blessing".to_string(), 4.0)]);
+        input.support_skills = vec![support];
+
+        // 授予的聚能 4 层压制手动添加的灵动 2 层，两条路径均给出诊断
+        let result = calculate_dps(&input).unwrap();
+        let suppressed = |warnings: &[Diagnostic]| {
+            warnings
+                .iter()
+                .any(|w| w.code == DiagnosticCode::MechanicSuppressed && w.message.contains("agility_blessing"))
+        };
+        assert!(suppressed(&result.warnings));
+        assert!(suppressed(&prepare_context(&input).unwrap().warnings));
+    }
 
-/// 计算期望伤害，支持 Lucky 机制
-/// Lucky: 取两次掷骰较高值，等价于区间 [min, max] 的期望从 0.5 提升到 2/3
-fn expected_damage(min: f64, max: f64, is_lucky: bool) -> f64 {
-    if !is_lucky || max <= min {
-        return (min + max) / 2.0;
+    #[test]
+    fn test_granted_mechanic_tags_match_requirements() {
+        let mut input = create_test_input();
+        let mut support = input.active_skill.clone();
+        support.id = "support_focus".to_string();
+        support.skill_type = SkillType::Support;
+        support.stats = HashMap::from([("grant.mechanic.focus_blessing".to_string(), 1.0)]);
+        input.support_skills = vec![support];
+        let mut amulet = slot_test_item("amulet", SlotType::Amulet, false);
+        amulet.affixes = vec![AffixData {
+            id: "blessed_damage".to_string(),
+            group: "blessed".to_string(),
+            value: 0.3,
+            stats: HashMap::from([("mod.inc.dmg.all".to_string(), 0.3)]),
+            requirements: vec!["Mech_Blessing".to_string()],
+            ..Default::default()
+        }];
+        input.items = vec![amulet];
+
+        // 授予的聚能祝福 1 层（+4%）带来 Mech_Blessing 标签，"受祝福影响时" 的词缀随之生效
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.34).abs() < 1e-6);
+        let prepared = prepare_context(&input).unwrap();
+        assert!(prepared.mechanic_tags.contains(&"Mech_Blessing".to_string()));
+        let staged = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+        assert!((staged.hit_damage - result.hit_damage).abs() < 1e-6);
     }
 
-    // 期望 = min + (max - min) * 2/3
-    min + (max - min) * (2.0 / 3.0)
-}
+    #[test]
+    fn test_on_hit_stacks_solved_from_rate() {
+        let mut input = create_test_input();
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "test_momentum".to_string(),
+            base_effect_per_stack: HashMap::from([
+                ("mod.inc.dmg.all".to_string(), 0.05),
+                ("speed.attack".to_string(), 0.05),
+            ]),
+            stacks_per_hit: Some(1.0),
+            duration: Some(2.0),
+            ..Default::default()
+        }];
+        input.mechanic_states = vec![MechanicState {
+            id: "test_momentum".to_string(),
+            current_stacks: 0,
+            max_stacks: 20,
+            is_active: true,
+        }];
+
+        // 手动模式：0 层
+        let manual = calculate_dps(&input).unwrap();
+        assert_eq!(manual.damage_breakdown.total_increased, 0.0);
+
+        // 稳态模式：层数 = 命中速率 × 持续时间，速率又受层数加成，迭代收敛
+        input.stack_mode = StackMode::Realistic;
+        let solved = calculate_dps(&input).unwrap();
+        assert!(solved.warnings.is_empty());
+        let trace = solved.debug_trace.iter().find(|t| t.phase == "Event Stacks").unwrap();
+        let hits = trace.values["hits_per_second"];
+        let stacks = (hits * 2.0).min(20.0);
+        assert!(stacks > 0.0);
+        assert!((solved.damage_breakdown.total_increased - stacks * 0.05).abs() < 1e-4);
+        assert!(solved.dps_theoretical > manual.dps_theoretical);
+    }
 
-/// 9. 计算命中率
-fn calculate_hit_chance(pool: &StatPool, _target: &TargetConfig) -> f64 {
-    let base_acc = pool.get_base("acc.rating");
-    let acc_chance = pool.get_base("acc.chance");
+    #[test]
+    fn test_conditions_see_resolved_mechanic_stacks() {
+        let mut input = create_test_input();
+        let mut support = input.active_skill.clone();
+        support.id = "support_focus".to_string();
+        support.skill_type = SkillType::Support;
+        support.stats = HashMap::from([("grant.mechanic.focus_blessing".to_string(), 3.0)]);
+        input.support_skills = vec![support];
+
+        // 授予的层数进入后续阶段的条件评估上下文
+        let mut prepared = prepare_context(&input).unwrap();
+        assert_eq!(prepared.condition_ctx.mechanic_stacks.get("focus_blessing"), Some(&3));
+        let before = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+
+        let mut extra = ModDB::new();
+        extra.add(
+            crate::modifiers::Modifier::inc("dmg.all", 0.5, "test")
+                .with_condition("mechanic_stacks(\"focus_blessing\") >= 3"),
+        );
+        prepared.merge_modifiers(&extra);
+        let after = calculate_from_prepared(&prepared, &input.target_config).unwrap();
+        assert!(
+            (after.damage_breakdown.multipliers.increased_zone
+                - before.damage_breakdown.multipliers.increased_zone
+                - 0.5)
+                .abs()
+                < 1e-6
+        );
+    }
 
-    // 简化的命中计算
-    if acc_chance > 0.0 {
-        acc_chance.min(1.0)
-    } else if base_acc > 0.0 {
-        // 基于命中值计算（简化公式）
-        (base_acc / (base_acc + 100.0)).min(0.95)
-    } else {
-        0.95 // 默认95%命中
+    #[test]
+    fn test_max_stack_bonus_from_gear() {
+        let mut input = create_test_input();
+        input.mechanic_definitions = vec![MechanicDefinition {
+            id: "focus_blessing".to_string(),
+            base_effect_per_stack: HashMap::from([("mod.inc.dmg.all".to_string(), 0.1)]),
+            ..Default::default()
+        }];
+        input.mechanic_states = vec![MechanicState {
+            id: "focus_blessing".to_string(),
+            current_stacks: 6,
+            max_stacks: 4,
+            is_active: true,
+        }];
+        input.global_overrides =
+            HashMap::from([("mod.inc.dmg.all.per_focus_blessing".to_string(), 0.05.into())]);
+
+        // 手动 6 层被钳制到上限 4 层：4 × (10% + 5%)
+        let clamped = calculate_dps(&input).unwrap();
+        assert!((clamped.damage_breakdown.multipliers.increased_zone - 1.6).abs() < 1e-6);
+
+        // 聚能祝福上限 +1：5 层
+        input
+            .global_overrides
+            .insert("mod.max_stacks.focus_blessing".to_string(), 1.0.into());
+        let raised = calculate_dps(&input).unwrap();
+        assert!((raised.damage_breakdown.multipliers.increased_zone - 1.75).abs() < 1e-6);
     }
-}
 
-/// 计算有效 DPS（考虑目标抗性）
-fn calculate_effective_dps(
-    damages: &HashMap<DamageType, DamageWithTags>,
-    rate: f64,
-    crit_factor: f64,
-    hit_chance: f64,
-    target: &TargetConfig,
-) -> f64 {
-    let mut total = 0.0;
+    #[test]
+    fn test_skill_quality_with_gear_bonus() {
+        let mut input = create_test_input();
+        input.active_skill.quality = 20;
+        input.active_skill.quality_stats = HashMap::from([("mod.inc.dmg.all".to_string(), 0.01)]);
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.2).abs() < 1e-6);
 
-    for (dtype, dmg) in damages {
-        let avg = dmg.average() * crit_factor;
-        
-        // 获取目标抗性
-        let resistance = target
-            .resistances
-            .get(dtype.as_key())
-            .copied()
-            .unwrap_or(0.0);
-        
-        // 简化的减伤计算
-        let damage_taken = avg * (1.0 - resistance) * (1.0 - target.generic_dr);
-        total += damage_taken;
+        // 装备 +10% 技能品质
+        input.global_overrides = HashMap::from([("skill.quality".to_string(), 10.0.into())]);
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.damage_breakdown.multipliers.increased_zone - 1.3).abs() < 1e-6);
     }
 
-    total * rate * hit_chance
-}
+    #[test]
+    fn test_per_stat_modifier_resolved_from_aggregated_attribute() {
+        let mut input = create_test_input();
+        input.active_skill.tags = vec!["Tag_Spell".to_string()];
+        input.global_overrides = HashMap::from([
+            ("intelligence".to_string(), 200.0.into()),
+            ("mod.inc.intelligence".to_string(), 0.25.into()), // 250 智慧
+            ("mod.inc.dmg.spell.per_10_intelligence".to_string(), 0.01.into()),
+        ]);
 
-/// 10. 计算 EHP
-fn calculate_ehp(pool: &StatPool) -> EhpSeries {
-    let base_life = pool.get_base("base.life").max(1.0);
-    let armor = pool.get_base("def.armor");
-    
-    // 物理 EHP = Life / (1 - phys_reduction)
-    // 简化：phys_reduction = armor / (armor + 1000)
-    let phys_reduction = armor / (armor + 1000.0);
-    let phys_ehp = base_life / (1.0 - phys_reduction).max(0.01);
+        let result = calculate_dps(&input).unwrap();
 
-    // 元素 EHP = Life / (1 - res)
-    let fire_res = pool.get_base("res.fire").min(0.75);
-    let cold_res = pool.get_base("res.cold").min(0.75);
-    let lightning_res = pool.get_base("res.lightning").min(0.75);
-    let chaos_res = pool.get_base("res.chaos").min(0.75);
+        // 每 10 点智慧 +1% 法术伤害 → 25%
+        let increased = result.damage_breakdown.multipliers.increased_zone;
+        assert!((increased - 1.25).abs() < 1e-6, "increased = {}", increased);
 
-    EhpSeries {
-        physical: phys_ehp,
-        fire: base_life / (1.0 - fire_res).max(0.01),
-        cold: base_life / (1.0 - cold_res).max(0.01),
-        lightning: base_life / (1.0 - lightning_res).max(0.01),
-        chaos: base_life / (1.0 - chaos_res).max(0.01),
+        // 实际伤害与等值的固定增伤一致
+        input.global_overrides.remove("mod.inc.dmg.spell.per_10_intelligence");
+        input.global_overrides.insert("mod.inc.dmg.spell".to_string(), 0.25.into());
+        let flat = calculate_dps(&input).unwrap();
+        assert!((result.hit_damage - flat.hit_damage).abs() < 1e-9, "hit = {}", result.hit_damage);
     }
-}
 
-/// 构建伤害明细
-/// 构建伤害分解明细，包含各乘区详情
-/// 
-/// 借鉴 ZSim 的设计，将伤害拆分为独立乘区：
-/// - 基础伤害区、增伤区、More区、暴击区、速度区、命中区、防御区、抗性区、易伤区
-fn build_damage_breakdown(
-    base_damages: &HashMap<DamageType, (f64, f64)>,
-    modified_damages: &HashMap<DamageType, DamageWithTags>,
-    pool: &StatPool,
-    mod_db: Option<&ModDB>,
-    rate: f64,
-    crit_chance: f64,
-    crit_multiplier: f64,
-    hit_chance: f64,
-    target: &TargetConfig,
-    is_lucky: bool,
-) -> DamageBreakdown {
-    let mut by_type = HashMap::new();
-    let mut after_conversion = HashMap::new();
+    #[test]
+    fn test_per_stat_key_suffixes_from_item_affix() {
+        let mut input = create_test_input();
+        input.active_skill.tags = vec!["Tag_Spell".to_string()];
+        input.global_overrides = HashMap::from([
+            ("intelligence".to_string(), 200.0.into()),
+            ("mod.inc.intelligence".to_string(), 0.225.into()), // 245 智慧
+        ]);
+        input.items = vec![ItemData {
+            affixes: vec![AffixData {
+                id: "scaling_spell_dmg".to_string(),
+                group: "dmg".to_string(),
+                stats: HashMap::from([
+                    // 每 10 点 +1%，最多 20 次 → 20%
+                    ("mod.inc.dmg.spell.per_10_intelligence.max_20".to_string(), 0.01),
+                    // 每 100 点 +10%，按比例 → 24.5%
+                    ("mod.inc.dmg.spell.per_100_intelligence.proportional".to_string(), 0.1),
+                    // 非正数上限不生效，报告为未知属性键
+                    ("mod.inc.dmg.spell.per_1_intelligence.max_0".to_string(), 1.0),
+                ]),
+                ..Default::default()
+            }],
+            ..ItemData::new("amulet_1", "amulet", SlotType::Amulet)
+        }];
+
+        let result = calculate_dps(&input).unwrap();
+
+        let increased = result.damage_breakdown.multipliers.increased_zone;
+        assert!((increased - 1.445).abs() < 1e-6, "increased = {}", increased);
+        let base_hit = calculate_dps(&CalculatorInput { items: Vec::new(), ..input.clone() }).unwrap().hit_damage;
+        assert!((result.hit_damage - base_hit * 1.445).abs() < 1e-9, "hit = {}", result.hit_damage);
+        let unknown: Vec<_> = result.warnings.iter().filter(|d| d.code == DiagnosticCode::UnknownStatKey).collect();
+        assert_eq!(unknown.len(), 1, "{:?}", unknown);
+        assert_eq!(unknown[0].message_params["key"], "mod.inc.dmg.spell.per_1_intelligence.max_0");
+    }
+
+    #[test]
+    fn test_attribute_implicit_bonuses() {
+        let mut input = create_test_input();
+        input.global_overrides = HashMap::from([
+            ("strength".to_string(), 100.0.into()),
+            ("base.life".to_string(), 100.0.into()),
+        ]);
+
+        let result = calculate_dps(&input).unwrap();
+
+        // 100 基础生命 + 100 力量 / 2 = 150
+        assert!((result.ehp_series.physical - 150.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_override_modifier_end_to_end() {
+        let mut input = create_test_input();
+        input.global_overrides = HashMap::from([
+            ("crit.chance".to_string(), 0.05.into()),
+            ("mod.inc.crit.chance".to_string(), 1.0.into()),
+            ("mod.override.crit.chance".to_string(), 0.09.into()), // 暴击率固定为 9%
+            ("res.fire".to_string(), 0.3.into()),
+            ("mod.override.res.fire".to_string(), 0.5.into()),
+        ]);
 
-    for (dtype, dmg) in modified_damages {
-        by_type.insert(dtype.as_key().to_string(), expected_damage(dmg.min, dmg.max, is_lucky));
-        after_conversion.insert(
-            dtype.as_key().to_string(),
-            DamageWithHistory {
-                damage: expected_damage(dmg.min, dmg.max, is_lucky),
-                history_tags: dmg
-                    .history_tags
-                    .ones()
-                    .map(|i| format!("tag_{}", i))
-                    .collect(),
-            },
-        );
+        let result = calculate_dps(&input).unwrap();
+
+        assert!((result.crit_chance - 0.09).abs() < 1e-6, "crit = {}", result.crit_chance);
+        // 无生命来源时按 1 计，50% 火抗下 EHP 翻倍
+        assert!((result.ehp_series.fire - 2.0).abs() < 1e-6, "fire ehp = {}", result.ehp_series.fire);
     }
 
-    let base_damage: f64 = base_damages
-        .values()
-        .map(|(min, max)| (min + max) / 2.0)
-        .sum();
+    #[test]
+    fn test_flag_modifiers_from_items_and_context() {
+        let mut input = create_test_input();
+        input.global_overrides = HashMap::from([
+            ("crit.chance".to_string(), 0.2.into()),
+            ("flag.always_hit".to_string(), 1.0.into()),
+        ]);
+        input.context_flags.insert("cannot_crit".to_string(), true);
 
-    // 计算各乘区明细（传入 ModDB 以获取详细来源）
-    let multipliers = build_multiplier_breakdown(
-        base_damage,
-        pool,
-        mod_db,
-        rate,
-        crit_chance,
-        crit_multiplier,
-        hit_chance,
-        target,
-    );
+        let result = calculate_dps(&input).unwrap();
 
-    DamageBreakdown {
-        by_type,
-        base_damage,
-        total_increased: pool.get_increased("dmg.all"),
-        total_more: pool.get_more_multiplier("dmg.all"),
-        after_conversion,
-        multipliers,
+        assert_eq!(result.crit_chance, 0.0);
+        assert_eq!(result.hit_chance, 1.0);
     }
-}
 
-/// 构建乘区明细
-/// 
-/// 各乘区计算公式：
-/// - 基础伤害区: 技能基础伤害值
-/// - 增伤区: 1 + sum(所有 increased)
-/// - More区: product(所有 more)
-/// - 暴击期望区: 1 + crit_chance * crit_damage
-/// - 速度区: 攻击/施法速率
-/// - 命中区: 命中率
-/// - 防御区: level_constant / (enemy_armor + level_constant)
-/// - 抗性区: 1 - enemy_res + res_reduction + res_penetration
-/// - 易伤区: 1 + enemy_increased_damage_taken
-fn build_multiplier_breakdown(
-    base_damage: f64,
-    pool: &StatPool,
-    mod_db: Option<&ModDB>,
-    rate: f64,
-    crit_chance: f64,
-    crit_multiplier: f64,
-    hit_chance: f64,
-    target: &TargetConfig,
-) -> MultiplierBreakdown {
-    use crate::modifiers::{ModifierKind, ModifierStore};
+    #[test]
+    fn test_target_debuffs_feed_mitigation_zones() {
+        let mut input = create_test_input();
+        input.target_config.resistances.insert("fire".to_string(), 0.4);
+        input.target_config.debuffs = vec![
+            TargetDebuff {
+                id: "fire_exposure".to_string(),
+                category: "exposure".to_string(),
+                stats: HashMap::from([("res.fire".to_string(), -0.2)]),
+            },
+            TargetDebuff {
+                id: "shock".to_string(),
+                category: "shock".to_string(),
+                stats: HashMap::from([("increased_damage_taken".to_string(), 0.1)]),
+            },
+        ];
+        // 玩家侧诅咒：再降低 10% 全抗性
+        input.global_overrides.insert("target.res.all".to_string(), (-0.1).into());
 
-    let mut zone_sources: HashMap<String, Vec<ZoneSource>> = HashMap::new();
+        let base = calculate_dps(&create_test_input()).unwrap();
+        let result = calculate_dps(&input).unwrap();
 
-    // 1. 基础伤害区
-    let base_damage_zone = base_damage;
-    zone_sources.insert("base_damage".to_string(), vec![ZoneSource {
-        source: "技能基础".to_string(),
-        value: base_damage,
-        stat_key: "dmg.base".to_string(),
-    }]);
-
-    // 2. 增伤区 (收集所有 increased 来源)
-    let inc_keys = ["dmg.all", "dmg.phys", "dmg.fire", "dmg.cold", 
-                    "dmg.lightning", "dmg.elemental", "dmg.chaos", "dmg.spell", "dmg.attack"];
-    let inc_names = ["全伤害增加", "物理增伤", "火焰增伤", "冰冷增伤",
-                     "闪电增伤", "元素增伤", "混沌增伤", "法术增伤", "攻击增伤"];
-    
-    let mut total_increased = 0.0;
-    let mut inc_sources = Vec::new();
-    
-    for (key, name) in inc_keys.iter().zip(inc_names.iter()) {
-        let value = pool.get_increased(key);
-        if value > 0.0 {
-            total_increased += value;
-            
-            // 如果有 ModDB，获取详细来源
-            if let Some(db) = mod_db {
-                let sources = db.get_sources(key);
-                for src in sources.iter().filter(|s| s.kind == ModifierKind::Increased) {
-                    inc_sources.push(ZoneSource {
-                        source: format!("{} ({})", src.source, name),
-                        value: src.value,
-                        stat_key: key.to_string(),
-                    });
-                }
-            } else {
-                inc_sources.push(ZoneSource {
-                    source: name.to_string(),
-                    value,
-                    stat_key: key.to_string(),
-                });
-            }
-        }
+        // 火抗 0.4 - 0.2 - 0.1 = 0.1，易伤 +10%
+        let expected = base.dps_effective * (1.0 - 0.1) * 1.1;
+        assert!((result.dps_effective - expected).abs() < 1e-6, "dps = {}", result.dps_effective);
+
+        let zones = &result.damage_breakdown.multipliers;
+        assert!((zones.vulnerability_zone - 1.1).abs() < 1e-6);
+        let res_sources = &zones.zone_sources["resistance"];
+        assert!(res_sources.iter().any(|s| s.source == "exposure:fire_exposure"));
+        assert!(res_sources.iter().any(|s| s.source == "global_override" && s.stat_key == "target.res.all"));
+        assert!(zones.zone_sources["vulnerability"].iter().any(|s| s.source == "shock:shock"));
     }
-    
-    let increased_zone = 1.0 + total_increased;
-    zone_sources.insert("increased".to_string(), inc_sources);
 
-    // 3. More 乘区
-    let more_keys = ["dmg.all", "dmg.phys", "dmg.fire", "dmg.cold",
-                     "dmg.lightning", "dmg.elemental", "dmg.spell", "dmg.attack"];
-    let more_names = ["全伤害提高", "物理伤害提高", "火焰伤害提高", "冰冷伤害提高",
-                      "闪电伤害提高", "元素伤害提高", "法术伤害提高", "攻击伤害提高"];
-    
-    let mut more_zone = 1.0;
-    let mut more_sources = Vec::new();
-    
-    for (key, name) in more_keys.iter().zip(more_names.iter()) {
-        let value = pool.get_more_multiplier(key);
-        if value != 1.0 {
-            more_zone *= value;
-            
-            // 如果有 ModDB，获取详细来源
-            if let Some(db) = mod_db {
-                let sources = db.get_sources(key);
-                for src in sources.iter().filter(|s| s.kind == ModifierKind::More) {
-                    more_sources.push(ZoneSource {
-                        source: format!("{} ({})", src.source, name),
-                        value: 1.0 + src.value, // More 值显示为乘数形式
-                        stat_key: key.to_string(),
-                    });
-                }
-            } else {
-                more_sources.push(ZoneSource {
-                    source: name.to_string(),
-                    value,
-                    stat_key: key.to_string(),
-                });
-            }
-        }
+    #[test]
+    fn test_default_registry_shared() {
+        let a = create_default_registry();
+        let b = create_default_registry();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let ctx = prepare_context(&create_test_input()).unwrap();
+        assert!(Arc::ptr_eq(&ctx.registry, &a));
     }
-    
-    zone_sources.insert("more".to_string(), more_sources);
 
-    // 4. 暴击期望区
-    // 公式: 1 + crit_chance * (crit_multiplier - 1)
-    // crit_multiplier 语义: 1.5 = 150% 总暴击伤害 (非暴击时为 100%)
-    // 例: 50% 暴击率, 150% 暴击伤害 → 1 + 0.5 * 0.5 = 1.25 倍期望伤害
-    let effective_crit_chance = crit_chance.min(1.0).max(0.0);
-    let crit_zone = 1.0 + effective_crit_chance * (crit_multiplier - 1.0);
-    zone_sources.insert("crit".to_string(), vec![
-        ZoneSource {
-            source: "暴击率".to_string(),
-            value: crit_chance,
-            stat_key: "crit.chance".to_string(),
-        },
-        ZoneSource {
-            source: "暴击伤害".to_string(),
-            value: crit_multiplier,
-            stat_key: "crit.multiplier".to_string(),
-        },
-    ]);
+    #[test]
+    fn test_default_registry_conditions_parse() {
+        let registry = create_default_registry();
+        assert!(!registry
+            .diagnostics()
+            .iter()
+            .any(|d| matches!(d, TagDiagnostic::InvalidCondition { .. })));
+
+        // 敌人距离由数值上下文派生，无需前端设置标志
+        let values: HashMap<String, f64> = [("enemy_range".to_string(), 1.0)].into_iter().collect();
+        let mut context = ContextTags::new(Arc::clone(&registry));
+        context.inject_derived_tags(&HashMap::new(), &values);
+        let near = registry.get_id("Tag_State_Enemy_Near").unwrap();
+        assert!(context.active_set().contains(near));
+    }
 
-    // 5. 速度区
-    let speed_zone = rate;
-    zone_sources.insert("speed".to_string(), vec![ZoneSource {
-        source: "攻击/施法速率".to_string(),
-        value: rate,
-        stat_key: "rate".to_string(),
-    }]);
+    #[test]
+    fn test_less_sources_surfaced_separately() {
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.more.dmg.all".to_string(), (-0.6).into());
 
-    // 6. 命中区
-    let hit_zone = hit_chance;
-    zone_sources.insert("hit".to_string(), vec![ZoneSource {
-        source: "命中率".to_string(),
-        value: hit_chance,
-        stat_key: "hit.chance".to_string(),
-    }]);
+        let base = calculate_dps(&create_test_input()).unwrap();
+        let result = calculate_dps(&input).unwrap();
+        assert!((result.dps_theoretical - base.dps_theoretical * 0.4).abs() < 1e-6);
+
+        let zones = &result.damage_breakdown.multipliers;
+        assert!((zones.less_zone - 0.4).abs() < 1e-6);
+        assert!((zones.more_zone - base.damage_breakdown.multipliers.more_zone * 0.4).abs() < 1e-6);
+        let less_sources = &zones.zone_sources["less"];
+        assert_eq!(less_sources.len(), 1);
+        assert!((less_sources[0].value - 0.4).abs() < 1e-6);
+        assert!(zones.zone_sources["more"].iter().all(|s| s.value >= 1.0));
+
+        // 低于 -100% 钳制为 0
+        input.global_overrides.insert("mod.more.dmg.all".to_string(), (-1.5).into());
+        let result = calculate_dps(&input).unwrap();
+        assert_eq!(result.dps_theoretical, 0.0);
+        assert_eq!(result.damage_breakdown.multipliers.less_zone, 0.0);
+    }
 
-    // 7. 防御区 (敌人护甲)
-    // 公式: level_constant / (enemy_armor + level_constant)
-    let level_constant = 1000.0; // 等级常数，后续可参数化
-    let enemy_armor = target.armor as f64;
-    let defense_zone = if enemy_armor > 0.0 {
-        level_constant / (enemy_armor + level_constant)
-    } else {
-        1.0
-    };
-    zone_sources.insert("defense".to_string(), vec![ZoneSource {
-        source: format!("敌人护甲: {}", enemy_armor),
-        value: defense_zone,
-        stat_key: "target.armor".to_string(),
-    }]);
+    #[test]
+    fn test_breakdown_emits_locale_keys() {
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 0.3.into());
+        let result = calculate_dps(&input).unwrap();
 
-    // 8. 抗性区
-    // 公式: 1 - enemy_res + res_reduction + res_penetration
-    // 取平均抗性作为示例
-    let avg_resistance = (target.resistances.get("fire").unwrap_or(&0.0)
-        + target.resistances.get("cold").unwrap_or(&0.0)
-        + target.resistances.get("lightning").unwrap_or(&0.0)
-        + target.resistances.get("chaos").unwrap_or(&0.0)) / 4.0;
-    let res_penetration = pool.get_base("mod.penetration.res.all");
-    let resistance_zone = (1.0 - avg_resistance + res_penetration).max(0.0);
-    zone_sources.insert("resistance".to_string(), vec![ZoneSource {
-        source: format!("平均抗性: {:.1}%", avg_resistance * 100.0),
-        value: resistance_zone,
-        stat_key: "target.resistance".to_string(),
-    }]);
+        let inc = &result.damage_breakdown.multipliers.zone_sources["increased"];
+        let fire = inc.iter().find(|s| s.stat_key == "dmg.fire").unwrap();
+        assert_eq!(fire.label_key, "zone.increased");
+        assert_eq!(fire.label_params["damage"], "fire");
+        assert_eq!(fire.source, render_default(&fire.label_key, &fire.label_params));
 
-    // 9. 易伤区
-    let vulnerability = pool.get_base("target.increased_damage_taken");
-    let vulnerability_zone = 1.0 + vulnerability;
-    zone_sources.insert("vulnerability".to_string(), vec![ZoneSource {
-        source: "敌人受到伤害增加".to_string(),
-        value: vulnerability,
-        stat_key: "target.increased_damage_taken".to_string(),
-    }]);
+        let locales = LocaleTable::load_default();
+        let en = locales.render("en", &fire.label_key, &fire.label_params);
+        assert!(en.ends_with("(fire increased damage)"), "{}", en);
 
-    // 10. 机制特殊区 (祝福、球类等提供的额外乘区)
-    let mechanics_more = pool.get_base("mechanics.more.dmg");
-    let mechanics_zone = if mechanics_more > 0.0 { 1.0 + mechanics_more } else { 1.0 };
-    zone_sources.insert("mechanics".to_string(), vec![ZoneSource {
-        source: "机制加成".to_string(),
-        value: mechanics_more,
-        stat_key: "mechanics.more.dmg".to_string(),
-    }]);
+        // 追踪描述同样携带本地化键
+        assert!(result.debug_trace.iter().all(|t| !t.message_key.is_empty()));
+    }
 
-    MultiplierBreakdown {
-        base_damage_zone,
-        increased_zone,
-        more_zone,
-        crit_zone,
-        speed_zone,
-        hit_zone,
-        defense_zone,
-        resistance_zone,
-        vulnerability_zone,
-        mechanics_zone,
-        zone_sources,
+    #[test]
+    fn test_sustained_dps_limited_by_mana_and_uptime() {
+        let mut input = create_test_input();
+        input.active_skill.mana_cost = 10;
+        let burst = calculate_dps(&input).unwrap();
+        assert_eq!(burst.dps_sustained, burst.dps_theoretical);
+
+        // 魔力回复只够一半的施放
+        let spend = burst.mana_cost * burst.rate;
+        input.sustain_model = Some(SustainModel { mana_regen: Some(spend * 0.5), ..Default::default() });
+        let sustained = calculate_dps(&input).unwrap();
+        assert!((sustained.dps_theoretical - burst.dps_theoretical).abs() < 1e-9);
+        assert!((sustained.dps_sustained - burst.dps_theoretical * 0.5).abs() < 1e-6);
+
+        // 技能循环占比 50% 时魔力消耗减半，魔力不再受限
+        input.recent_model = Some(RecentModel { rotation_uptime: 0.5, ..Default::default() });
+        let rotated = calculate_dps(&input).unwrap();
+        assert!((rotated.dps_sustained - rotated.dps_theoretical * 0.5).abs() < 1e-6);
+        assert!(rotated.debug_trace.iter().any(|t| t.message_key == "trace.sustain"));
+
+        // 两阶段计算无法复现持续输出与近期事件模型，拒绝计算而非返回不一致的结果
+        assert!(requires_full_calculation(&input));
+        let prepared = prepare_context(&input).unwrap();
+        assert!(calculate_from_prepared(&prepared, &input.target_config).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::MechanicDefinition;
+    #[test]
+    fn test_weapon_local_crit_and_speed_only_affect_attacks() {
+        let mut weapon = slot_test_item("sword", SlotType::WeaponMain, false);
+        weapon.base_implicit_stats.insert("crit.chance.local".to_string(), 0.1);
+        weapon.base_implicit_stats.insert("speed.attack.local".to_string(), 2.0);
+        let mut input = create_test_input();
+        let spell_rate = calculate_dps(&input).unwrap().rate;
+        input.items = vec![weapon];
 
-    fn create_test_input() -> CalculatorInput {
-        CalculatorInput {
-            context_flags: HashMap::new(),
-            context_values: HashMap::new(),
-            target_config: TargetConfig::default(),
-            items: vec![],
-            active_skill: SkillData {
-                id: "test_fireball".to_string(),
-                skill_type: SkillType::Active,
-                damage_type: Some("fire".to_string()),
-                is_attack: false,
-                level: 1,
-                base_damage: [
-                    ("dmg.fire.min".to_string(), 50.0),
-                    ("dmg.fire.max".to_string(), 100.0),
-                ]
-                .into_iter()
-                .collect(),
-                base_time: 0.8,
-                cooldown: None,
-                mana_cost: 10,
-                effectiveness: 1.0,
-                tags: vec!["Tag_Spell".to_string(), "Tag_Fire".to_string()],
-                stats: HashMap::new(),
-                injected_tags: vec![],
-                mana_multiplier: 1.0,
-                level_data: None,
-                scaling_rules: vec![],
-            },
-            support_skills: vec![],
-            global_overrides: HashMap::new(),
-            preview_slot: None,
-            mechanic_states: vec![],
-            mechanic_definitions: vec![],
-        }
+        // 法术不受武器暴击率与攻速影响
+        let spell = calculate_dps(&input).unwrap();
+        assert_eq!(spell.crit_chance, 0.0);
+        assert!((spell.rate - spell_rate).abs() < 1e-9);
+
+        // 攻击速率取武器基础攻速，不使用技能的基础时间
+        input.active_skill.is_attack = true;
+        input.active_skill.base_time = 0.5;
+        let attack = calculate_dps(&input).unwrap();
+        assert!((attack.crit_chance - 0.1).abs() < 1e-9);
+        assert!((attack.rate - 2.0).abs() < 1e-9);
+
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.crit_chance - 0.1).abs() < 1e-9);
+        assert!((prepared.rate - 2.0).abs() < 1e-9);
     }
 
     #[test]
-    fn test_basic_calculation() {
-        let input = create_test_input();
+    fn test_embedded_tables_parse() {
+        // 内嵌数据表损坏时 `load_embedded` 在测试中直接 panic，而非静默回退为空表
+        assert!(!AttributeTable::load_default().bonuses(STRENGTH).is_empty());
+        CharacterTable::load_default();
+        crate::custom_stats::CustomStatTable::load_default();
+        ItemsMetaTable::load_default();
+        crate::i18n::LocaleTable::load_default();
+        assert!(!builtin_definitions().is_empty());
+        crate::memories::MemoryRules::load_default();
+        MoreBucketRegistry::load_default();
+        MultiplierZoneTable::load_default();
+        SkillRegistry::load_default();
+        SlotRegistry::load_default();
+        crate::stat_aliases::StatAliasTable::load_default();
+        crate::stat_hooks::StatHookTable::load_default();
+        StatUnitTable::load_default();
+        assert_eq!(EMBEDDED_DATA_TABLES.len(), 15);
+    }
+
+    #[test]
+    fn test_character_level_derives_base_life() {
+        let mut input = create_test_input();
+        let without = calculate_dps(&input).unwrap();
+        assert_eq!(without.ehp_series.physical, 1.0);
+
+        // 通用成长：60 + 12 × (50 - 1)
+        input.character = Some(CharacterConfig { level: 50, class: None });
         let result = calculate_dps(&input).unwrap();
+        assert!((result.ehp_series.physical - 648.0).abs() < 1e-9);
 
-        // 基础伤害 75 (平均)
-        // 速率 1.25/s
-        // 理论 DPS ≈ 75 * 1.25 * crit_factor
-        assert!(result.dps_theoretical > 0.0);
-        assert!(result.hit_damage > 0.0);
-        assert!(result.rate > 0.0);
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert_eq!(prepared.ehp_series.physical, result.ehp_series.physical);
+
+        // 角色等级不影响命中率与 DPS（命中率尚未按目标闪避建模）
+        for level in [1, 90] {
+            input.character = Some(CharacterConfig { level, class: None });
+            let result = calculate_dps(&input).unwrap();
+            assert_eq!(result.hit_chance, without.hit_chance);
+            assert_eq!(result.dps_theoretical, without.dps_theoretical);
+        }
     }
 
     #[test]
-    fn test_with_increased_damage() {
+    fn test_diagnostics_surface_silent_adjustments() {
         let mut input = create_test_input();
-        input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 1.0); // +100% fire damage
+        input.global_overrides.insert("fire_damage".to_string(), 1.0.into());
+        input.active_skill.stats.insert("conv.fire_to_chaos".to_string(), 0.6);
+        input.global_overrides.insert("conv.fire_to_chaos".to_string(), 0.6.into());
+        input.global_overrides.insert("crit.chance".to_string(), 1.5.into());
+        input.target_config.resistances.insert("chaos".to_string(), 1.2);
 
         let result = calculate_dps(&input).unwrap();
+        let codes: Vec<DiagnosticCode> = result.warnings.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                DiagnosticCode::UnknownStatKey,
+                DiagnosticCode::ConversionScaled,
+                DiagnosticCode::CritCapped,
+                DiagnosticCode::ResistanceCapped,
+            ]
+        );
+        assert_eq!(result.warnings[0].message_params["key"], "fire_damage");
+        assert_eq!(result.warnings[3].message_key, "diagnostic.resistance_capped");
+        assert_eq!(result.crit_chance, 1.0);
+        // 抗性截断到 100%，有效 DPS 不为负
+        assert!(result.dps_effective >= 0.0);
+
+        // 增量计算路径产出相同的诊断
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert_eq!(prepared.warnings, result.warnings);
+    }
 
-        // 伤害应该翻倍
-        let base_result = calculate_dps(&create_test_input()).unwrap();
-        assert!(result.hit_damage > base_result.hit_damage * 1.5);
+    #[test]
+    fn test_strict_stat_keys_reject_typos() {
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.inc.dmgg.fire".to_string(), 0.5.into());
+        input.active_skill.stats.insert("conv.fire_to_cold".to_string(), 0.5);
+        input.global_overrides.insert("fire_damage".to_string(), 1.0.into());
+
+        // 默认仅给出诊断
+        let lenient = calculate_dps(&input).unwrap();
+        assert_eq!(lenient.warnings.iter().filter(|d| d.code == DiagnosticCode::UnknownStatKey).count(), 3);
+
+        // 严格模式下 mod.* / conv.* 拼写错误视为错误，一次列出
+        input.strict_stat_keys = true;
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("mod.inc.dmgg.fire") && err.contains("conv.fire_to_cold"), "{}", err);
+        assert!(!err.contains("fire_damage"));
+        assert!(prepare_context(&input).is_err());
+
+        // 其余未知键仍只是诊断
+        input.global_overrides.remove("mod.inc.dmgg.fire");
+        input.active_skill.stats.remove("conv.fire_to_cold");
+        let strict = calculate_dps(&input).unwrap();
+        assert_eq!(strict.warnings.len(), 1);
     }
 
     #[test]
-    fn test_conversion_with_tag_retention() {
-        // 测试物理转火焰，确保火焰部分也能吃到物理增伤
+    fn test_stat_key_aliases_normalized_before_aggregation() {
+        let mut canonical = create_test_input();
+        canonical.global_overrides.insert("mod.inc.dmg.fire".to_string(), 0.5.into());
+        let expected = calculate_dps(&canonical).unwrap();
+
+        let mut input = create_test_input();
+        input.global_overrides.insert("FireDmg%".to_string(), 0.5.into());
+        input.strict_stat_keys = true;
+        let aliased = calculate_dps(&input).unwrap();
+
+        assert!((aliased.hit_damage - expected.hit_damage).abs() < 1e-9);
+        assert_eq!(aliased.warnings.len(), 1);
+        let diagnostic = &aliased.warnings[0];
+        assert_eq!(diagnostic.code, DiagnosticCode::StatKeyAliased);
+        assert_eq!(diagnostic.message_params["alias"], "FireDmg%");
+        assert_eq!(diagnostic.message_params["key"], "mod.inc.dmg.fire");
+        assert_eq!(diagnostic.message_params["source"], "global_override");
+    }
+
+    #[test]
+    fn test_custom_stat_keys_from_data() {
+        let mut canonical = create_test_input();
+        canonical.global_overrides.insert("mod.inc.dmg.all".to_string(), 0.3.into());
+        let expected = calculate_dps(&canonical).unwrap();
+
+        // 数据声明的自定义键按规范键生效，严格模式下视为已知键
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.custom.dmg.ailment".to_string(), 0.3.into());
+        input.strict_stat_keys = true;
+        let custom = calculate_dps(&input).unwrap();
+        assert!((custom.hit_damage - expected.hit_damage).abs() < 1e-9);
+        assert!(custom.warnings.is_empty(), "{:?}", custom.warnings);
+        let source = &custom.damage_breakdown.multipliers.zone_sources["increased"];
+        assert!(source.iter().any(|s| s.label_params["source"] == "global_override"));
+
+        // 自定义键先改写再按单位表换算：`.pct` 后缀与 `mod.inc` 一样按 0–100 约定录入
+        let mut pct = create_test_input();
+        pct.global_overrides.insert("mod.custom.dmg.ailment.pct".to_string(), 30.0.into());
+        pct.strict_stat_keys = true;
+        let pct = calculate_dps(&pct).unwrap();
+        assert!((pct.hit_damage - expected.hit_damage).abs() < 1e-9);
+        assert!(
+            pct.warnings.iter().all(|w| w.code == DiagnosticCode::UnitNormalized),
+            "{:?}",
+            pct.warnings
+        );
+
+        // `.per_` 后缀的自定义键按属性缩放
+        let mut per_stat = create_test_input();
+        per_stat.global_overrides.insert("intelligence".to_string(), 300.0.into());
+        per_stat.global_overrides.insert("mod.custom.dmg.ailment.per_10_intelligence".to_string(), 0.01.into());
+        per_stat.strict_stat_keys = true;
+        let per_stat = calculate_dps(&per_stat).unwrap();
+        assert!(per_stat.warnings.is_empty(), "{:?}", per_stat.warnings);
+        let increased = per_stat.damage_breakdown.multipliers.increased_zone;
+        assert!((increased - 1.3).abs() < 1e-6, "increased = {}", increased);
+    }
+
+    #[test]
+    fn test_stat_hook_converts_mana_to_weapon_damage() {
+        let mut weapon = slot_test_item("sword", SlotType::WeaponMain, false);
+        weapon.base_implicit_stats.insert("speed.attack.local".to_string(), 1.0);
+        weapon.base_implicit_stats.insert("dmg.phys.min".to_string(), 50.0);
+        weapon.base_implicit_stats.insert("dmg.phys.max".to_string(), 50.0);
+        weapon.implicit_stats.insert("mod.inc.dmg.phys.local".to_string(), 0.5);
         let mut input = create_test_input();
         input.active_skill.is_attack = true;
-        input.active_skill.base_damage.clear();
-        input.active_skill.tags = vec!["Tag_Attack".to_string(), "Tag_Melee".to_string()];
+        input.items = vec![weapon.clone()];
+        input.global_overrides = HashMap::from([
+            ("base.mana".to_string(), 1000.0.into()),
+            ("mod.inc.base.mana".to_string(), 0.2.into()),
+        ]);
+        let without_hook = calculate_dps(&input).unwrap();
+
+        // 10% 最大魔力（1000 × 1.2）附加为武器局部物理伤害，与武器伤害一同按局部增伤结算
+        let mut flat = input.clone();
+        flat.items[0].base_implicit_stats.insert("dmg.phys.min".to_string(), 170.0);
+        flat.items[0].base_implicit_stats.insert("dmg.phys.max".to_string(), 170.0);
+        let expected = calculate_dps(&flat).unwrap();
+
+        input.items[0].implicit_stats.insert("hook.mana_to_weapon_damage".to_string(), 0.1);
+        let hooked = calculate_dps(&input).unwrap();
+        assert!(hooked.hit_damage > without_hook.hit_damage);
+        assert!((hooked.hit_damage - expected.hit_damage).abs() < 1e-9);
+
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert!((prepared.hit_damage - hooked.hit_damage).abs() < 1e-9);
+
+        // 武器伤害不作用于法术
+        input.active_skill.is_attack = false;
+        flat.active_skill.is_attack = false;
+        let spell = calculate_dps(&input).unwrap();
+        assert!((spell.hit_damage - calculate_dps(&flat).unwrap().hit_damage).abs() < 1e-9);
+        input.items[0].implicit_stats.remove("hook.mana_to_weapon_damage");
+        assert!((spell.hit_damage - calculate_dps(&input).unwrap().hit_damage).abs() < 1e-9);
+    }
 
-        // 添加武器物理伤害
-        input.items.push(ItemData {
-            id: "test_sword".to_string(),
-            base_type: "sword".to_string(),
-            slot: SlotType::WeaponMain,
-            is_two_handed: false,
-            base_implicit_stats: HashMap::new(), // 武器基底属性（无）
-            implicit_stats: [
-                ("dmg.phys.min".to_string(), 50.0),
-                ("dmg.phys.max".to_string(), 100.0),
-            ]
-            .into_iter()
-            .collect(),
-            affixes: vec![],
-            tags: vec![],
-            is_unique: false,
-            is_corrupted: false,
-        });
+    #[test]
+    fn test_percent_values_normalized_on_ingest() {
+        let mut fraction = create_test_input();
+        fraction.global_overrides.insert("mod.inc.dmg.fire".to_string(), 0.5.into());
+        fraction.target_config.resistances.insert("fire".to_string(), 0.4);
+        let expected = calculate_dps(&fraction).unwrap();
+        assert!(expected.warnings.is_empty());
+
+        let mut percent = create_test_input();
+        percent.global_overrides.insert("mod.inc.dmg.fire.pct".to_string(), 50.0.into());
+        percent.target_config.resistances.insert("fire.pct".to_string(), 40.0);
+        let result = calculate_dps(&percent).unwrap();
+
+        assert!((result.dps_theoretical - expected.dps_theoretical).abs() < 1e-9);
+        assert!((result.dps_effective - expected.dps_effective).abs() < 1e-9);
+        let normalized: Vec<(&str, &str)> = result
+            .warnings
+            .iter()
+            .filter(|d| d.code == DiagnosticCode::UnitNormalized)
+            .map(|d| (d.message_params["key"].as_str(), d.message_params["normalized"].as_str()))
+            .collect();
+        assert_eq!(normalized, vec![("mod.inc.dmg.fire.pct", "0.5"), ("res.fire.pct", "0.4")]);
+
+        // 只按键判断单位：未加后缀的比例值不因数值大小被换算
+        let mut ratio = create_test_input();
+        ratio.global_overrides.insert("mod.inc.dmg.fire".to_string(), 1.5.into());
+        let result = calculate_dps(&ratio).unwrap();
+        assert!(result.warnings.iter().all(|d| d.code != DiagnosticCode::UnitNormalized));
+        assert!((result.damage_breakdown.multipliers.increased_zone - 2.5).abs() < 1e-9);
+    }
 
-        // 50% 物理转火焰
-        input.global_overrides.insert("conv.phys_to_fire".to_string(), 0.5);
-        // +100% 物理增伤
-        input.global_overrides.insert("mod.inc.dmg.phys".to_string(), 1.0);
-        // +100% 火焰增伤
-        input.global_overrides.insert("mod.inc.dmg.fire".to_string(), 1.0);
+    #[test]
+    fn test_out_of_range_inputs_follow_sanitize_policy() {
+        let mut input = create_test_input();
+        input.items = vec![ItemData {
+            base_implicit_stats: HashMap::from([("def.block".to_string(), 1.5)]),
+            affixes: vec![AffixData {
+                id: "crit".to_string(),
+                group: "crit".to_string(),
+                value: 150.0,
+                stats: HashMap::from([("crit.chance.pct".to_string(), 150.0)]),
+                ..Default::default()
+            }],
+            ..ItemData::new("amulet_1", "amulet", SlotType::Amulet)
+        }];
+
+        // 默认钳制：暴击率/格挡率钳制到 100%（按录入值与录入约定检查），逐项给出诊断
+        let clamped = calculate_dps(&input).unwrap();
+        let adjusted: Vec<(&str, &str, &str)> = clamped
+            .warnings
+            .iter()
+            .filter(|d| d.code == DiagnosticCode::ValueOutOfRange)
+            .map(|d| {
+                let params = &d.message_params;
+                (params["source"].as_str(), params["field"].as_str(), params["adjusted"].as_str())
+            })
+            .collect();
+        assert_eq!(adjusted.len(), 2);
+        assert!(adjusted.contains(&("amulet_1:base", "def.block", "1")));
+        assert!(adjusted.contains(&("amulet_1:crit", "crit.chance", "1")));
+        assert_eq!(clamped.crit_chance, 1.0);
+
+        // 增量计算路径产出相同的诊断
+        let prepared = calculate_from_prepared(&prepare_context(&input).unwrap(), &input.target_config).unwrap();
+        assert_eq!(prepared.warnings, clamped.warnings);
+
+        // 保留：原值参与计算，仍给出诊断
+        input.sanitize_policy = SanitizePolicy::Keep;
+        let kept = calculate_dps(&input).unwrap();
+        assert_eq!(kept.warnings.iter().filter(|d| d.code == DiagnosticCode::ValueOutOfRange).count(), 2);
+
+        // 非正的攻击/施法时间无法换算速率，不论策略一律拒绝
+        for base_time in [-0.5, 0.0] {
+            input.active_skill.base_time = base_time;
+            for policy in [SanitizePolicy::Clamp, SanitizePolicy::Keep] {
+                input.sanitize_policy = policy;
+                let err = calculate_dps(&input).unwrap_err().to_string();
+                assert!(err.contains("base_time"), "{}", err);
+            }
+        }
 
+        // 拒绝：一次列出全部越界值
+        input.sanitize_policy = SanitizePolicy::Reject;
+        let err = calculate_dps(&input).unwrap_err().to_string();
+        assert!(err.contains("base_time") && err.contains("def.block") && err.contains("crit.chance"), "{}", err);
+        assert!(prepare_context(&input).is_err());
+    }
+
+    #[test]
+    fn test_output_options_round_and_scale() {
+        let mut input = create_test_input();
+        let mut sword = slot_test_item("sword", SlotType::WeaponMain, false);
+        sword.base_implicit_stats = HashMap::from([
+            ("dmg.phys.min".to_string(), 10.333),
+            ("dmg.phys.max".to_string(), 20.777),
+            ("speed.attack.local".to_string(), 1.37),
+            ("crit.chance.local".to_string(), 0.05371),
+        ]);
+        input.items.push(sword);
+        let raw = calculate_dps(&input).unwrap();
+
+        input.output_options = OutputOptions {
+            decimal_places: Some(2),
+            rounding: RoundingMode::HalfUp,
+            percent_scale: PercentScale::Percent,
+        };
         let result = calculate_dps(&input).unwrap();
 
-        // 确保计算正常完成
-        assert!(result.dps_theoretical > 0.0);
-        
-        // 检查伤害构成
-        assert!(result.damage_breakdown.by_type.contains_key("physical"));
-        assert!(result.damage_breakdown.by_type.contains_key("fire"));
+        assert_eq!(result.dps_theoretical, (raw.dps_theoretical * 100.0).round() / 100.0);
+        assert_eq!(result.crit_chance, (raw.crit_chance * 10000.0).round() / 100.0);
+        assert_eq!(result.hit_chance, (raw.hit_chance * 10000.0).round() / 100.0);
+        // 武器面板同样舍入，局部暴击率按百分比刻度
+        let (raw_sword, sword) = (&raw.weapon_summaries[0], &result.weapon_summaries[0]);
+        assert_eq!(sword.total_dps, (raw_sword.total_dps * 100.0).round() / 100.0);
+        assert_eq!(sword.phys_range.max, 20.78);
+        assert_eq!(sword.crit_chance, 5.37);
+        // 乘区为倍数，不受百分比刻度影响
+        let zones = &result.damage_breakdown.multipliers;
+        assert_eq!(zones.crit_zone, (raw.damage_breakdown.multipliers.crit_zone * 100.0).round() / 100.0);
+
+        let options = |rounding| OutputOptions { decimal_places: Some(1), rounding, ..Default::default() };
+        assert_eq!(options(RoundingMode::HalfUp).round(0.25), 0.3);
+        assert_eq!(options(RoundingMode::HalfEven).round(0.25), 0.2);
+        assert_eq!(options(RoundingMode::Floor).round(-1.25), -1.3);
+        assert_eq!(options(RoundingMode::Ceil).round(1.21), 1.3);
+        assert_eq!(options(RoundingMode::Truncate).round(-1.29), -1.2);
+    }
+
+    #[test]
+    fn test_increased_floor_policy() {
+        let mut input = create_test_input();
+        input.global_overrides.insert("mod.inc.dmg.all".to_string(), (-1.5).into());
+
+        // 默认钳制：增伤区为 0，DPS 为 0
+        let clamped = calculate_dps(&input).unwrap();
+        assert_eq!(clamped.dps_theoretical, 0.0);
+        assert_eq!(clamped.damage_breakdown.multipliers.increased_zone, 0.0);
+
+        // 允许为负：乘数 1 - 1.5 = -0.5
+        input.increased_floor = IncreasedFloor::AllowNegative;
+        let negative = calculate_dps(&input).unwrap();
+        assert!(negative.dps_theoretical < 0.0);
+        assert!((negative.damage_breakdown.multipliers.increased_zone + 0.5).abs() < 1e-6);
+
+        // 速度与暴击使用同一策略
+        let mut db = ModDB::new();
+        db.add(Modifier::base("crit.chance", 0.5, "基础"));
+        db.add(Modifier::inc("crit.chance", -2.0, "诅咒"));
+        db.add(Modifier::inc("crit.dmg", -2.0, "诅咒"));
+        let ctx = EvalContext::default();
+        let (chance, multi) = calculate_crit(&db, &ctx, false, IncreasedFloor::ClampZero);
+        assert_eq!(chance, 0.0);
+        assert!((multi - 0.5).abs() < 1e-6);
+        let (_, multi) = calculate_crit(&db, &ctx, false, IncreasedFloor::AllowNegative);
+        assert!((multi + 0.5).abs() < 1e-6);
+
+        db.add(Modifier::inc("speed.cast", -1.5, "冰缓"));
+        let skill = &input.active_skill;
+        let rate = |floor| calculate_rate(&db, &ctx, skill.is_attack, skill.base_time, skill.cooldown, floor);
+        assert_eq!(rate(IncreasedFloor::ClampZero), 0.0);
+        assert!(rate(IncreasedFloor::AllowNegative) < 0.0);
     }
 
     #[test]
@@ -1994,13 +6620,7 @@ mod tests {
                 ("lucky_damage".to_string(), false),
                 ("cannot_crit".to_string(), false),
             ]),
-            context_values: HashMap::new(),
-            target_config: TargetConfig::default(),
             items: vec![ItemData {
-                id: "equip_legend_116".to_string(),
-                base_type: "gloves_all_magic_grip".to_string(),
-                slot: SlotType::Gloves,
-                is_two_handed: false,
                 base_implicit_stats: HashMap::from([("base.es".to_string(), 527.0)]),
                 implicit_stats: HashMap::from([
                     // 每层聚能祝福 +19% 冰冷伤害 (More)
@@ -2009,23 +6629,20 @@ mod tests {
                     ("mod.inc.crit.dmg.per_focus_blessing".to_string(), 0.04),
                     ("blessing.duration".to_string(), 0.40),
                 ]),
-                affixes: vec![],
                 tags: vec!["Tag_Armor".to_string(), "Tag_Gloves".to_string(), "Tag_Cold".to_string()],
                 is_unique: true,
                 is_corrupted: true,
+                ..ItemData::new("equip_legend_116", "gloves_all_magic_grip", SlotType::Gloves)
             }],
             active_skill: SkillData {
                 id: "skill_chain_lightning".to_string(),
-                skill_type: SkillType::Active,
                 damage_type: Some("lightning".to_string()),
-                is_attack: false,
                 level: 21,
                 base_damage: HashMap::from([
                     ("dmg.lightning.min".to_string(), 95.0),
                     ("dmg.lightning.max".to_string(), 1811.0),
                 ]),
                 base_time: 0.65,
-                cooldown: None,
                 mana_cost: 8,
                 effectiveness: 1.0, // 技能基础伤害已含效用，不再重复乘
                 tags: vec![
@@ -2034,68 +6651,45 @@ mod tests {
                     "Tag_Chain".to_string(),
                     "Tag_Burst".to_string(),
                 ],
-                stats: HashMap::new(),
-                injected_tags: vec![],
-                mana_multiplier: 1.0,
-                level_data: None,
-                scaling_rules: vec![],
+                ..Default::default()
             },
             support_skills: vec![
                 SkillData {
                     id: "support_lightning_to_cold".to_string(),
                     skill_type: SkillType::Support,
-                    damage_type: None,
-                    is_attack: false,
                     level: 20,
-                    base_damage: HashMap::new(),
                     base_time: 0.0,
-                    cooldown: None,
-                    mana_cost: 0,
-                    effectiveness: 1.0,
                     tags: vec!["Tag_Support".to_string(), "Tag_Lightning".to_string(), "Tag_Cold".to_string()],
                     stats: HashMap::from([
                         ("conv.lightning_to_cold".to_string(), 1.0), // 100% 闪电转冰冷
                         ("mod.more.dmg.lightning".to_string(), 0.25), // +25% 闪电伤害 (More)
                     ]),
-                    injected_tags: vec![],
-                    mana_multiplier: 1.0,
-                    level_data: None,
-                    scaling_rules: vec![],
+                    ..Default::default()
                 },
                 SkillData {
                     id: "support_psychic_burst".to_string(),
                     skill_type: SkillType::Support,
-                    damage_type: None,
-                    is_attack: false,
                     level: 20,
-                    base_damage: HashMap::new(),
                     base_time: 0.0,
-                    cooldown: None,
-                    mana_cost: 0,
-                    effectiveness: 1.0,
                     tags: vec!["Tag_Support".to_string(), "Tag_Spell".to_string()],
                     stats: HashMap::from([
                         ("mod.more.dmg.all".to_string(), 0.45), // +45% 伤害 (More)
                         ("speed.cast".to_string(), 0.16),       // +16% 施法速度
                     ]),
-                    injected_tags: vec![],
-                    mana_multiplier: 1.0,
-                    level_data: None,
-                    scaling_rules: vec![],
+                    ..Default::default()
                 },
             ],
             global_overrides: HashMap::from([
                 // 暴击率 10% (已换算)
                 // 换算逻辑: 基础暴击值500 × 战意加成(100×2%) = 实际暴击率
-                ("crit.chance".to_string(), 0.10),
+                ("crit.chance".to_string(), 0.10.into()),
                 // 注意: 基础暴击伤害 150% 已内置于引擎，无需额外传入
                 // 世事无常：拉伸最小/最大伤害范围
-                ("mod.more.dmg.phys.min".to_string(), -0.90), // -90% 物理最小
-                ("mod.more.dmg.phys.max".to_string(), 0.80),  // +80% 物理最大
-                ("mod.more.dmg.min".to_string(), -0.40),      // -40% 全局最小
-                ("mod.more.dmg.max".to_string(), 0.40),       // +40% 全局最大
+                ("mod.more.dmg.phys.min".to_string(), (-0.90).into()), // -90% 物理最小
+                ("mod.more.dmg.phys.max".to_string(), 0.80.into()),  // +80% 物理最大
+                ("mod.more.dmg.min".to_string(), (-0.40).into()),      // -40% 全局最小
+                ("mod.more.dmg.max".to_string(), 0.40.into()),       // +40% 全局最大
             ]),
-            preview_slot: None,
             mechanic_states: vec![
                 MechanicState { 
                     id: "focus_blessing".to_string(), 
@@ -2124,6 +6718,13 @@ mod tests {
                         ("mod.more.dmg.spell".to_string(), 0.03),
                     ]),
                     description: "聚能祝福每层提供额外伤害".to_string(),
+                    aura_scalable: false,
+                    generation_rate: None,
+                    duration: None,
+                    exclusive_group: None,
+                    cost_per_use: None,
+                    stacks_per_hit: None,
+                    stacks_per_kill: None,
                 },
                 MechanicDefinition {
                     id: "fighting_will".to_string(),
@@ -2136,8 +6737,16 @@ mod tests {
                         ("crit.chance.rating".to_string(), 2.0),
                     ]),
                     description: "战意每层提供 2 点暴击值".to_string(),
+                    aura_scalable: false,
+                    generation_rate: None,
+                    duration: None,
+                    exclusive_group: None,
+                    cost_per_use: None,
+                    stacks_per_hit: None,
+                    stacks_per_kill: None,
                 },
             ],
+            ..Default::default()
         };
 
         let result = calculate_dps(&input).expect("calc ok");
@@ -0,0 +1,124 @@
+//! 技能等级数据模块
+//!
+//! 内嵌常用主动技能的逐级数据（`src/data/skills_registry.json`），
+//! 调用方未提供 `SkillData.level_data` 时按技能 ID + 等级查表
+//!
+//! ## 核心设计
+//!
+//! 1. 数据表只覆盖 1-20 级，更高等级取 20 级数据，再由缩放规则叠乘
+//! 2. 等级缺档时取不超过请求等级的最高一档
+//! 3. 与标签注册表一致：首次使用时解析并缓存，全局共享
+
+use crate::types::SkillLevelData;
+use crate::utils::load_embedded;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+/// 等级数据表覆盖的最高等级（更高等级由缩放规则计算）
+pub const MAX_TABLE_LEVEL: u32 = 20;
+
+/// 技能等级数据 JSON 内容（编译时内嵌）
+pub(crate) const SKILLS_REGISTRY_JSON: &str = include_str!("data/skills_registry.json");
+
+/// 技能等级数据注册表
+#[derive(Debug, Clone, Default)]
+pub struct SkillRegistry {
+    /// 技能 ID -> 等级 -> 等级数据
+    skills: HashMap<String, BTreeMap<u32, SkillLevelData>>,
+}
+
+impl SkillRegistry {
+    /// 从 JSON 加载技能等级数据
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut skills = HashMap::new();
+        for (id, value) in raw {
+            // 跳过元数据
+            if id.starts_with('_') {
+                continue;
+            }
+
+            let levels: HashMap<String, SkillLevelData> = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse skill '{}': {}", id, e))?;
+            let mut table = BTreeMap::new();
+            for (level, data) in levels {
+                let level: u32 = level
+                    .parse()
+                    .map_err(|_| format!("Invalid level for '{}': {}", id, level))?;
+                table.insert(level, data);
+            }
+            skills.insert(id, table);
+        }
+
+        Ok(Self { skills })
+    }
+
+    /// 加载内嵌的默认技能等级数据
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：技能使用自身的默认数据）
+    pub fn load_default() -> Self {
+        load_embedded("skills_registry", Self::from_json(SKILLS_REGISTRY_JSON))
+    }
+
+    /// 获取共享的默认技能等级数据（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_SKILLS: OnceLock<SkillRegistry> = OnceLock::new();
+        DEFAULT_SKILLS.get_or_init(Self::load_default)
+    }
+
+    /// 查询技能在指定等级的数据
+    ///
+    /// 超过 [`MAX_TABLE_LEVEL`] 时取 20 级数据；等级缺档时取不超过请求等级的最高一档
+    pub fn level_data(&self, skill_id: &str, level: u32) -> Option<&SkillLevelData> {
+        let table = self.skills.get(skill_id)?;
+        table
+            .range(..=level.min(MAX_TABLE_LEVEL))
+            .next_back()
+            .map(|(_, data)| data)
+    }
+
+    /// 是否收录了该技能
+    pub fn contains(&self, skill_id: &str) -> bool {
+        self.skills.contains_key(skill_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_registry_levels() {
+        let registry = SkillRegistry::load_default();
+        assert!(registry.contains("skill_fire_burst"));
+
+        let lv1 = registry.level_data("skill_fire_burst", 1).unwrap();
+        let lv20 = registry.level_data("skill_fire_burst", 20).unwrap();
+        assert!(lv20.base_damage["dmg.fire.max"] > lv1.base_damage["dmg.fire.max"]);
+
+        // 超过 20 级取 20 级数据
+        let lv25 = registry.level_data("skill_fire_burst", 25).unwrap();
+        assert_eq!(lv25.base_damage, lv20.base_damage);
+        assert!(registry.level_data("skill_unknown", 1).is_none());
+    }
+
+    #[test]
+    fn test_missing_level_falls_back_to_lower() {
+        let registry = SkillRegistry::from_json(
+            r#"{
+                "_meta": {},
+                "skill_test": {
+                    "1": { "effectiveness": 1.0, "base_damage": { "dmg.fire.min": 1 } },
+                    "10": { "effectiveness": 1.5, "base_damage": { "dmg.fire.min": 10 } }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(registry.level_data("skill_test", 9).unwrap().effectiveness, 1.0);
+        assert_eq!(registry.level_data("skill_test", 12).unwrap().effectiveness, 1.5);
+        assert!(registry.level_data("skill_test", 0).is_none());
+    }
+}
@@ -0,0 +1,145 @@
+//! 槽位注册模块
+//!
+//! 槽位的容量与冲突规则由数据驱动，新增槽位（契灵、赛季槽位等）无需修改 `SlotType`
+//!
+//! ## 核心设计
+//!
+//! 1. 内置定义由数据表 `src/data/slots_registry.json` 配置（槽位 ID → 定义，条目可省略 `id`）
+//! 2. 调用方提供的 `slot_definitions` 按 ID 覆盖内置定义
+//! 3. 未登记的槽位按容量 1、无冲突组处理；计算入口拒绝未登记的 `Custom` 槽位（拼写错误不会静默成为新槽位）
+//! 4. 冲突组内同时只能有一个槽位被占用，双手武器占用时 `blocked_by_two_handed` 的槽位不可用
+
+use crate::types::{SlotDefinition, SlotType};
+use crate::utils::load_embedded;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 槽位定义 JSON 内容（编译时内嵌）
+pub(crate) const SLOTS_REGISTRY_JSON: &str = include_str!("data/slots_registry.json");
+
+/// 槽位注册表
+#[derive(Debug, Clone, Default)]
+pub struct SlotRegistry {
+    /// 槽位 ID -> 定义
+    definitions: HashMap<String, SlotDefinition>,
+}
+
+impl SlotRegistry {
+    /// 从 JSON 加载槽位注册表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut definitions = HashMap::new();
+        for (id, mut value) in raw {
+            // 跳过元数据
+            if id.starts_with('_') {
+                continue;
+            }
+
+            if let Some(object) = value.as_object_mut() {
+                object.entry("id").or_insert_with(|| id.clone().into());
+            }
+            let def: SlotDefinition = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse slot '{}': {}", id, e))?;
+            if def.id != id {
+                return Err(format!("Slot '{}' declares mismatched id '{}'", id, def.id));
+            }
+            if def.capacity == 0 {
+                return Err(format!("Invalid capacity for slot '{}': 0", id));
+            }
+            definitions.insert(id, def);
+        }
+
+        Ok(Self { definitions })
+    }
+
+    /// 加载内嵌的默认槽位注册表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：全部槽位按默认定义处理）
+    pub fn load_default() -> Self {
+        load_embedded("slots_registry", Self::from_json(SLOTS_REGISTRY_JSON))
+    }
+
+    /// 获取共享的默认槽位注册表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_SLOTS: OnceLock<SlotRegistry> = OnceLock::new();
+        DEFAULT_SLOTS.get_or_init(Self::load_default)
+    }
+
+    /// 查询槽位定义（调用方定义优先，未登记时返回默认定义）
+    pub fn definition<'a>(&'a self, slot: &SlotType, overrides: &'a [SlotDefinition]) -> Cow<'a, SlotDefinition> {
+        let id = slot.id();
+        overrides
+            .iter()
+            .find(|def| def.id == id)
+            .or_else(|| self.definitions.get(id))
+            .map_or_else(|| Cow::Owned(SlotDefinition::fallback(id)), Cow::Borrowed)
+    }
+
+    /// 槽位是否已登记（内置定义或调用方定义）
+    pub fn is_registered(&self, slot: &SlotType, overrides: &[SlotDefinition]) -> bool {
+        let id = slot.id();
+        overrides.iter().any(|def| def.id == id) || self.definitions.contains_key(id)
+    }
+
+    /// 全部内置槽位定义
+    pub fn definitions(&self) -> impl Iterator<Item = &SlotDefinition> {
+        self.definitions.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_slots_registered() {
+        let registry = SlotRegistry::load_default();
+        assert!(registry.definition(&SlotType::WeaponOff, &[]).blocked_by_two_handed);
+        assert_eq!(registry.definition(&SlotType::Ring1, &[]).capacity, 1);
+        assert_eq!(registry.definition(&SlotType::Ring2, &[]).capacity, 1);
+        assert_eq!(registry.definition(&SlotType::Custom("pact_spirit_1".to_string()), &[]).display_name, "契灵 1");
+        // 内置定义的 ID 均可解析回同一槽位
+        for def in registry.definitions() {
+            let slot: SlotType = serde_json::from_value(def.id.clone().into()).unwrap();
+            assert_eq!(slot.id(), def.id);
+        }
+    }
+
+    #[test]
+    fn test_overrides_and_fallback() {
+        let registry = SlotRegistry::load_default();
+        let overrides = vec![SlotDefinition {
+            conflict_group: Some("seasonal".to_string()),
+            ..SlotDefinition::fallback("helmet")
+        }];
+        assert_eq!(
+            registry.definition(&SlotType::Helmet, &overrides).conflict_group.as_deref(),
+            Some("seasonal")
+        );
+
+        let season = SlotType::Custom("season_relic".to_string());
+        let unknown = registry.definition(&season, &[]);
+        assert_eq!(*unknown, SlotDefinition::fallback("season_relic"));
+        assert!(!registry.is_registered(&season, &[]));
+        assert!(registry.is_registered(&season, &[SlotDefinition::fallback("season_relic")]));
+        assert!(registry.is_registered(&SlotType::Custom("pact_spirit_1".to_string()), &[]));
+    }
+
+    #[test]
+    fn test_custom_slot_serde() {
+        let slot: SlotType = serde_json::from_str(r#""weapon_main""#).unwrap();
+        assert_eq!(slot, SlotType::WeaponMain);
+        let slot: SlotType = serde_json::from_str(r#""pact_spirit_2""#).unwrap();
+        assert_eq!(slot, SlotType::Custom("pact_spirit_2".to_string()));
+        assert_eq!(serde_json::to_string(&slot).unwrap(), r#""pact_spirit_2""#);
+        assert_eq!(serde_json::to_string(&SlotType::Ring1).unwrap(), r#""ring1""#);
+    }
+
+    #[test]
+    fn test_invalid_capacity_rejected() {
+        assert!(SlotRegistry::from_json(r#"{ "helmet": { "capacity": 0 } }"#).is_err());
+    }
+}
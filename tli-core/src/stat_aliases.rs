@@ -0,0 +1,121 @@
+//! 属性键别名模块
+//!
+//! 将旧版或手工录入的属性键（如 `inc_fire_damage`、`firedmg%`）在聚合前映射为规范键
+//!
+//! ## 核心设计
+//!
+//! 1. 别名由数据表 `src/data/stat_aliases.json` 配置（别名 → 规范键）
+//! 2. 先精确匹配，再按规范化形式匹配（小写、去首尾空白、空格与连字符转下划线）
+//! 3. 规范键本身不能再是别名，避免链式映射
+//! 4. 实际发生的映射由聚合器记录，计算结果以诊断给出
+
+use crate::utils::load_embedded;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 别名表 JSON 内容（编译时内嵌）
+pub(crate) const STAT_ALIASES_JSON: &str = include_str!("data/stat_aliases.json");
+
+/// 属性键别名表
+#[derive(Debug, Clone, Default)]
+pub struct StatAliasTable {
+    /// 规范化别名 -> 规范键
+    aliases: HashMap<String, String>,
+}
+
+impl StatAliasTable {
+    /// 从 JSON 加载别名表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut aliases = HashMap::new();
+        for (alias, value) in raw {
+            // 跳过元数据
+            if alias.starts_with('_') {
+                continue;
+            }
+
+            let canonical: String = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse alias '{}': {}", alias, e))?;
+            aliases.insert(normalize(&alias), canonical);
+        }
+
+        if let Some((alias, canonical)) = aliases.iter().find(|(_, canonical)| aliases.contains_key(*canonical)) {
+            return Err(format!("Alias '{}' maps to another alias '{}'", alias, canonical));
+        }
+
+        Ok(Self { aliases })
+    }
+
+    /// 加载内嵌的默认别名表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不做映射）
+    pub fn load_default() -> Self {
+        load_embedded("stat_aliases", Self::from_json(STAT_ALIASES_JSON))
+    }
+
+    /// 获取共享的默认别名表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_ALIASES: OnceLock<StatAliasTable> = OnceLock::new();
+        DEFAULT_ALIASES.get_or_init(Self::load_default)
+    }
+
+    /// 查询别名对应的规范键（非别名返回 None）
+    pub fn resolve(&self, key: &str) -> Option<&str> {
+        if let Some(canonical) = self.aliases.get(key) {
+            return Some(canonical);
+        }
+        // 聚合热路径：已是规范化形式的键无需再分配
+        let needs_normalize = key.bytes().any(|b| b.is_ascii_uppercase() || b.is_ascii_whitespace() || b == b'-');
+        if !needs_normalize {
+            return None;
+        }
+        self.aliases.get(&normalize(key)).map(String::as_str)
+    }
+
+    /// 全部别名映射（别名, 规范键）
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases.iter().map(|(alias, canonical)| (alias.as_str(), canonical.as_str()))
+    }
+}
+
+/// 别名的规范化形式：小写、去首尾空白、空格与连字符转下划线
+fn normalize(key: &str) -> String {
+    key.trim()
+        .chars()
+        .map(|c| match c {
+            ' ' | '-' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::is_known_stat_key;
+
+    #[test]
+    fn test_resolve_normalized_aliases() {
+        let table = StatAliasTable::load_default();
+        assert_eq!(table.resolve("inc_fire_damage"), Some("mod.inc.dmg.fire"));
+        assert_eq!(table.resolve("FireDmg%"), Some("mod.inc.dmg.fire"));
+        assert_eq!(table.resolve(" Attack Speed "), Some("speed.attack"));
+        assert_eq!(table.resolve("mod.inc.dmg.fire"), None);
+    }
+
+    #[test]
+    fn test_default_aliases_target_known_keys() {
+        let table = StatAliasTable::load_default();
+        for (alias, canonical) in table.entries() {
+            assert!(is_known_stat_key(canonical), "{} -> {}", alias, canonical);
+        }
+    }
+
+    #[test]
+    fn test_chained_alias_rejected() {
+        let result = StatAliasTable::from_json(r#"{ "a": "b", "b": "mod.inc.dmg.fire" }"#);
+        assert!(result.is_err());
+    }
+}
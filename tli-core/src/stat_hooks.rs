@@ -0,0 +1,131 @@
+//! 属性钩子模块
+//!
+//! 以数据描述通用属性键无法表达的特殊暗金效果（来源属性 → 换算 → 目标属性），无需为每件装备编写代码
+//!
+//! ## 核心设计
+//!
+//! 1. 钩子由数据表 `src/data/stat_hooks.json` 配置（触发键、来源属性、换算表达式、目标属性）
+//! 2. 装备等来源提供触发键（如 `hook.mana_to_weapon_damage` = 0.1）时钩子生效，触发键数值以 `trigger` 引用
+//! 3. 聚合完成后、装备局部结算前统一求值，来源属性最终值（按条件评估上下文读取 ModDB）以 `source` 引用
+//! 4. 武器局部目标键（如 `dmg.phys.min`）写入攻击武器的局部属性，随武器伤害一同结算；其余目标写入全局属性
+//! 5. 全部钩子基于同一快照求值，钩子之间不链式触发
+
+use crate::condition_ast::ValueExpr;
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 钩子表 JSON 内容（编译时内嵌）
+pub(crate) const STAT_HOOKS_JSON: &str = include_str!("data/stat_hooks.json");
+
+/// 换算表达式中来源属性值的标识符
+pub const HOOK_SOURCE_VAR: &str = "source";
+
+/// 换算表达式中触发键数值的标识符
+pub const HOOK_TRIGGER_VAR: &str = "trigger";
+
+/// 钩子定义（JSON 形式）
+#[derive(Debug, Clone, Deserialize)]
+struct RawStatHook {
+    trigger: String,
+    source: String,
+    transform: String,
+    targets: Vec<String>,
+}
+
+/// 单个属性钩子
+#[derive(Debug, Clone)]
+pub struct StatHook {
+    /// 触发键（数值不为 0 时生效）
+    pub trigger: String,
+    /// 来源属性键
+    pub source: String,
+    /// 换算表达式
+    pub transform: ValueExpr,
+    /// 目标属性键（均写入换算结果）
+    pub targets: Vec<String>,
+}
+
+/// 属性钩子表
+#[derive(Debug, Clone, Default)]
+pub struct StatHookTable {
+    /// (钩子 ID, 钩子)，按 ID 排序
+    hooks: Vec<(String, StatHook)>,
+}
+
+impl StatHookTable {
+    /// 从 JSON 加载钩子表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut hooks = Vec::new();
+        for (id, value) in raw {
+            // 跳过元数据
+            if id.starts_with('_') {
+                continue;
+            }
+
+            let hook: RawStatHook = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse stat hook '{}': {}", id, e))?;
+            let transform = ValueExpr::parse(&hook.transform)
+                .map_err(|e| format!("Invalid transform for stat hook '{}': {}", id, e))?;
+            if hook.targets.is_empty() {
+                return Err(format!("Stat hook '{}' has no targets", id));
+            }
+            hooks.push((
+                id,
+                StatHook { trigger: hook.trigger, source: hook.source, transform, targets: hook.targets },
+            ));
+        }
+        hooks.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(Self { hooks })
+    }
+
+    /// 加载内嵌的默认钩子表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不触发钩子）
+    pub fn load_default() -> Self {
+        load_embedded("stat_hooks", Self::from_json(STAT_HOOKS_JSON))
+    }
+
+    /// 获取共享的默认钩子表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_STAT_HOOKS: OnceLock<StatHookTable> = OnceLock::new();
+        DEFAULT_STAT_HOOKS.get_or_init(Self::load_default)
+    }
+
+    /// 全部钩子（按 ID 排序）
+    pub fn hooks(&self) -> impl Iterator<Item = (&str, &StatHook)> {
+        self.hooks.iter().map(|(id, hook)| (id.as_str(), hook))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::is_known_stat_key;
+
+    #[test]
+    fn test_default_hooks_use_known_keys() {
+        let table = StatHookTable::load_default();
+        assert!(table.hooks().count() > 0);
+        for (id, hook) in table.hooks() {
+            assert!(is_known_stat_key(&hook.trigger), "{}: {}", id, hook.trigger);
+            assert!(is_known_stat_key(&hook.source), "{}: {}", id, hook.source);
+            for target in &hook.targets {
+                assert!(is_known_stat_key(target), "{}: {}", id, target);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_hooks_rejected() {
+        let bad_transform = r#"{ "h": { "trigger": "hook.h", "source": "base.mana", "transform": "source *", "targets": ["dmg.phys.min"] } }"#;
+        assert!(StatHookTable::from_json(bad_transform).is_err());
+        let no_targets = r#"{ "h": { "trigger": "hook.h", "source": "base.mana", "transform": "source", "targets": [] } }"#;
+        assert!(StatHookTable::from_json(no_targets).is_err());
+    }
+}
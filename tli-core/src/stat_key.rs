@@ -0,0 +1,221 @@
+//! 属性键驻留（interning）
+//!
+//! 将 `"dmg.fire"`、`"crit.chance"` 等字符串键映射为 `StatKey(u32)`，
+//! 使 `StatPool` / `ModDB` 的热路径只做整数哈希与比较。
+//!
+//! ## 核心设计
+//!
+//! 1. 全局驻留表：同一字符串始终得到同一 `StatKey`，进程内有效
+//! 2. 键内保存驻留后的 `&'static str`，`as_str` / `Display` / 序列化无需加锁
+//! 3. 驻留表容量有上限（[`MAX_INTERNED_KEYS`]），超出后的新键不分配编号（[`StatKey::is_interned`] 为 false），
+//!    相等与哈希改按字符串比较，不同的键不会合并；计算结果附带 `stat_key_overflow` 诊断
+//! 4. 查询使用 [`StatKey::lookup`]，未出现过的键不分配、直接视为不存在
+//! 5. 仅在 serde 边界转换为字符串
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{OnceLock, RwLock};
+
+/// 驻留表容量上限（属性键词表远小于此值，超出只可能来自异常输入）
+pub const MAX_INTERNED_KEYS: usize = 1 << 16;
+
+/// 未驻留键的编号（驻留表已满后的新键共用，相等与哈希按字符串区分）
+const UNINTERNED_ID: u32 = u32::MAX;
+
+/// 驻留后的属性键（相等与哈希只比较编号；未驻留的键比较字符串）
+#[derive(Clone, Copy)]
+pub struct StatKey {
+    id: u32,
+    name: &'static str,
+}
+
+/// 全局驻留表
+#[derive(Default)]
+struct Interner {
+    /// 字符串 -> 键
+    ids: HashMap<&'static str, StatKey>,
+    /// 驻留表已满后出现的键名（不分配编号，仅去重字符串）
+    uninterned: HashSet<&'static str>,
+}
+
+impl Interner {
+    /// 驻留字符串键；已满时返回未驻留的键
+    fn intern(&mut self, key: &str, capacity: usize) -> StatKey {
+        if let Some(found) = self.get(key) {
+            return found;
+        }
+        let name: &'static str = Box::leak(key.to_owned().into_boxed_str());
+        if self.ids.len() >= capacity {
+            self.uninterned.insert(name);
+            return StatKey { id: UNINTERNED_ID, name };
+        }
+        let id = StatKey { id: self.ids.len() as u32, name };
+        self.ids.insert(name, id);
+        id
+    }
+
+    /// 查询已出现的键（含未驻留的键）
+    fn get(&self, key: &str) -> Option<StatKey> {
+        self.ids.get(key).copied().or_else(|| {
+            self.uninterned.get(key).map(|&name| StatKey { id: UNINTERNED_ID, name })
+        })
+    }
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| RwLock::new(Interner::default()))
+}
+
+impl StatKey {
+    /// 驻留字符串键（已存在时直接返回）
+    pub fn new(key: &str) -> Self {
+        if let Some(id) = Self::lookup(key) {
+            return id;
+        }
+
+        // 获取写锁期间可能已被其他线程驻留，由 `intern` 再次检查
+        let mut table = interner().write().unwrap_or_else(|e| e.into_inner());
+        table.intern(key, MAX_INTERNED_KEYS)
+    }
+
+    /// 查询已出现的键，不存在时返回 None（不分配）
+    pub fn lookup(key: &str) -> Option<Self> {
+        let table = interner().read().unwrap_or_else(|e| e.into_inner());
+        table.get(key)
+    }
+
+    /// 获取原始字符串（不加锁）
+    pub fn as_str(self) -> &'static str {
+        self.name
+    }
+
+    /// 内部编号（未驻留的键共用 `u32::MAX`）
+    pub fn id(self) -> u32 {
+        self.id
+    }
+
+    /// 是否已驻留（驻留表已满后出现的键为 false）
+    pub fn is_interned(self) -> bool {
+        self.id != UNINTERNED_ID
+    }
+}
+
+/// 驻留表不收缩，同一字符串不会既有编号又未驻留，两种键之间恒不相等
+impl PartialEq for StatKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.is_interned(), other.is_interned()) {
+            (true, true) => self.id == other.id,
+            (false, false) => self.name == other.name,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for StatKey {}
+
+impl Hash for StatKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if self.is_interned() {
+            self.id.hash(state);
+        } else {
+            self.name.hash(state);
+        }
+    }
+}
+
+impl From<&str> for StatKey {
+    fn from(key: &str) -> Self {
+        Self::new(key)
+    }
+}
+
+impl PartialEq<str> for StatKey {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for StatKey {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for StatKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StatKey({:?})", self.as_str())
+    }
+}
+
+impl fmt::Display for StatKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for StatKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for StatKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        Ok(Self::new(&key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_roundtrip() {
+        let a = StatKey::new("dmg.fire");
+        let b = StatKey::from("dmg.fire");
+        assert_eq!(a, b);
+        assert_eq!(a.as_str(), "dmg.fire");
+        assert_ne!(a, StatKey::new("dmg.cold"));
+        assert_eq!(StatKey::lookup("dmg.fire"), Some(a));
+        assert_eq!(StatKey::lookup("test.never_interned"), None);
+    }
+
+    #[test]
+    fn test_interner_bounded() {
+        let mut table = Interner::default();
+        let a = table.intern("a", 2);
+        let b = table.intern("b", 2);
+        assert_ne!(a, b);
+        assert_eq!(table.intern("a", 2), a);
+
+        // 已满：新键不分配编号，但彼此不相等，不会合并数值
+        let c = table.intern("c", 2);
+        let d = table.intern("d", 2);
+        assert!(!c.is_interned() && !d.is_interned());
+        assert_ne!(c, d);
+        assert_ne!(c, a);
+        assert_eq!((c.as_str(), d.as_str()), ("c", "d"));
+        assert_eq!(table.intern("c", 2), c);
+        assert_eq!(table.get("d"), Some(d));
+        assert_eq!(table.ids.len(), 2);
+
+        let mut values = HashMap::new();
+        *values.entry(c).or_insert(0.0) += 1.0;
+        *values.entry(d).or_insert(0.0) += 2.0;
+        *values.entry(table.intern("c", 2)).or_insert(0.0) += 4.0;
+        assert_eq!((values[&c], values[&d]), (5.0, 2.0));
+    }
+
+    #[test]
+    fn test_serde_as_string() {
+        let key = StatKey::new("crit.chance");
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, "\"crit.chance\"");
+        let back: StatKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, key);
+    }
+}
@@ -0,0 +1,147 @@
+//! 属性单位模块
+//!
+//! 不同数据源对比例类属性使用不同约定（`0.4` 或 `40` 表示 40%），混用会产生 100 倍误差。
+//! 录入时按属性键（而非数值大小）统一换算为 0–1 约定
+//!
+//! ## 核心设计
+//!
+//! 1. 比例类键类由数据表 `src/data/stat_units.json` 声明，并注明该键类录入值的约定（`fraction` / `percent`）
+//! 2. 声明为 `percent` 的键类，录入值一律除以 100
+//! 3. 任一比例类键可加 `.pct` 后缀显式按 0–100 约定录入（如 `res.fire.pct` = 40），换算后去掉后缀
+//! 4. 键类以 `*` 结尾为前缀匹配，多个键类匹配时取最长者；`mod.override.` / `target.` 前缀按去掉后的键匹配
+//! 5. 未声明的键（伤害点数、计算得到的值等）从不换算
+
+use crate::utils::load_embedded;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 单位表 JSON 内容（编译时内嵌）
+pub(crate) const STAT_UNITS_JSON: &str = include_str!("data/stat_units.json");
+
+/// 显式按 0–100 约定录入的键后缀
+pub const PERCENT_SUFFIX: &str = ".pct";
+
+/// 比例类属性的录入约定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitConvention {
+    /// 0–1（`0.4` 表示 40%），录入值不换算
+    Fraction,
+    /// 0–100（`40` 表示 40%），录入值除以 100
+    Percent,
+}
+
+/// 单个键类的单位声明
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatUnit {
+    /// 该键类录入值的约定
+    pub unit: UnitConvention,
+}
+
+/// 属性单位表
+#[derive(Debug, Clone, Default)]
+pub struct StatUnitTable {
+    /// 键类（`*` 结尾为前缀）-> 单位声明
+    units: HashMap<String, StatUnit>,
+}
+
+impl StatUnitTable {
+    /// 从 JSON 加载单位表
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut units = HashMap::new();
+        for (pattern, value) in raw {
+            // 跳过元数据
+            if pattern.starts_with('_') {
+                continue;
+            }
+
+            let unit: StatUnit = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to parse unit '{}': {}", pattern, e))?;
+            units.insert(pattern, unit);
+        }
+
+        Ok(Self { units })
+    }
+
+    /// 加载内嵌的默认单位表
+    ///
+    /// 解析失败时见 `load_embedded`（发布构建回退为空表：不做换算）
+    pub fn load_default() -> Self {
+        load_embedded("stat_units", Self::from_json(STAT_UNITS_JSON))
+    }
+
+    /// 获取共享的默认单位表（首次调用时解析）
+    pub fn shared() -> &'static Self {
+        static DEFAULT_UNITS: OnceLock<StatUnitTable> = OnceLock::new();
+        DEFAULT_UNITS.get_or_init(Self::load_default)
+    }
+
+    /// 查询属性键所属键类的单位声明（取最长匹配）
+    pub fn unit(&self, key: &str) -> Option<&StatUnit> {
+        let key = ["mod.override.", "target."]
+            .iter()
+            .fold(key, |key, prefix| key.strip_prefix(prefix).unwrap_or(key));
+        self.units
+            .iter()
+            .filter(|(pattern, _)| match pattern.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => key == pattern.as_str(),
+            })
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, unit)| unit)
+    }
+
+    /// 按 0–100 约定录入的属性键: (规范键, 换算除数)（无需换算时返回 None）
+    ///
+    /// 只依据属性键判断：`.pct` 后缀的比例类键与声明为 `percent` 的键类除以 100
+    pub fn percent_key<'k>(&self, key: &'k str) -> Option<(&'k str, f64)> {
+        if let Some(base) = key.strip_suffix(PERCENT_SUFFIX) {
+            return self.unit(base).map(|_| (base, 100.0));
+        }
+        (self.unit(key)?.unit == UnitConvention::Percent).then_some((key, 100.0))
+    }
+
+    /// 按 0–100 约定录入的属性换算为 0–1 约定: (规范键, 换算后数值)（无需换算时返回 None）
+    pub fn normalize<'k>(&self, key: &'k str, value: f64) -> Option<(&'k str, f64)> {
+        self.percent_key(key).map(|(key, divisor)| (key, value / divisor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_by_key() {
+        let table = StatUnitTable::load_default();
+        assert_eq!(table.normalize("res.fire.pct", 40.0), Some(("res.fire", 0.4)));
+        assert_eq!(table.normalize("target.res.fire.pct", -20.0), Some(("target.res.fire", -0.2)));
+        assert_eq!(table.normalize("mod.inc.dmg.fire.pct", 25.0), Some(("mod.inc.dmg.fire", 0.25)));
+        // 数值大小不参与判断：超过 100% 的比例值原样保留
+        assert_eq!(table.normalize("res.fire", 1.2), None);
+        assert_eq!(table.normalize("crit.dmg", 1.5), None);
+        assert_eq!(table.normalize("conv.phys_to_fire", 40.0), None);
+        // 非比例类键不接受百分比后缀
+        assert_eq!(table.normalize("dmg.fire.min.pct", 40.0), None);
+    }
+
+    #[test]
+    fn test_percent_convention_key_class() {
+        let table = StatUnitTable::from_json(
+            r#"{ "res.*": { "unit": "fraction" }, "res.chaos": { "unit": "percent" } }"#,
+        )
+        .unwrap();
+        assert_eq!(table.normalize("res.chaos", 20.0), Some(("res.chaos", 0.2)));
+        assert_eq!(table.normalize("res.fire", 0.2), None);
+        assert_eq!(table.normalize("res.fire.pct", 20.0), Some(("res.fire", 0.2)));
+    }
+
+    #[test]
+    fn test_invalid_unit_rejected() {
+        assert!(StatUnitTable::from_json(r#"{ "res.*": { "unit": "permille" } }"#).is_err());
+    }
+}
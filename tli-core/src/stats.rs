@@ -8,23 +8,42 @@
 //! - `StatPool`: 旧版属性池（向后兼容）
 //! - `ModDB`: 新版结构化修正存储（用于溯源和条件评估）
 
+use crate::attributes::AttributeTable;
+use crate::character::CharacterTable;
+use crate::condition_ast::{Condition, EvalContext, ValueExpr};
+use crate::conversion::is_conversion_key;
+use crate::custom_stats::CustomStatTable;
 use crate::mechanics::{is_per_stack_stat, MechanicsProcessor};
-use crate::modifiers::{ModDB, Modifier, ModifierStore};
+use crate::memories::{is_memory_slot, MemoryRules, MEMORY_COUNT};
+use crate::modifiers::{
+    is_valid_max_multiplier, more_factor, BucketStacking, ModDB, Modifier, ModifierKind, ModifierScope, ModifierStore, PerStatConfig,
+    PerStatRounding,
+};
+use crate::more_buckets::MoreBucketRegistry;
+use crate::multiplier_zones::MultiplierZoneTable;
+use crate::stat_aliases::StatAliasTable;
+use crate::stat_hooks::{StatHookTable, HOOK_SOURCE_VAR, HOOK_TRIGGER_VAR};
+use crate::stat_key::StatKey;
+use crate::stat_units::StatUnitTable;
 use crate::tags::ContextTags;
 use crate::types::*;
+use smallvec::SmallVec;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 /// 属性池 - 聚合所有属性修正
 #[derive(Debug, Clone, Default)]
 pub struct StatPool {
     /// 基础值
-    base: HashMap<String, f64>,
+    base: HashMap<StatKey, f64>,
     /// Increased 修正（累加）
-    increased: HashMap<String, f64>,
+    increased: HashMap<StatKey, f64>,
     /// More 修正（按 bucket 分组）
-    more: HashMap<String, Vec<MoreModifier>>,
+    more: HashMap<StatKey, SmallVec<[MoreModifier; 3]>>,
+    /// Override 值（直接作为最终值）
+    overrides: HashMap<StatKey, f64>,
     /// 最终计算值缓存
-    final_values: HashMap<String, f64>,
+    final_values: HashMap<StatKey, f64>,
     /// 是否需要重新计算
     dirty: bool,
 }
@@ -45,27 +64,27 @@ impl StatPool {
 
     /// 添加基础值
     pub fn add_base(&mut self, key: &str, value: f64) {
-        *self.base.entry(key.to_string()).or_insert(0.0) += value;
+        *self.base.entry(StatKey::new(key)).or_insert(0.0) += value;
         self.dirty = true;
     }
 
     /// 设置基础值（覆盖）
     pub fn set_base(&mut self, key: &str, value: f64) {
-        self.base.insert(key.to_string(), value);
+        self.base.insert(StatKey::new(key), value);
         self.dirty = true;
     }
 
     /// 添加 Increased 修正
     pub fn add_increased(&mut self, key: &str, value: f64) {
-        *self.increased.entry(key.to_string()).or_insert(0.0) += value;
+        *self.increased.entry(StatKey::new(key)).or_insert(0.0) += value;
         self.dirty = true;
     }
 
     /// 添加 More 修正
     pub fn add_more(&mut self, key: &str, value: f64, bucket_id: u32, source: &str) {
         self.more
-            .entry(key.to_string())
-            .or_insert_with(Vec::new)
+            .entry(StatKey::new(key))
+            .or_default()
             .push(MoreModifier {
                 value,
                 bucket_id,
@@ -74,19 +93,36 @@ impl StatPool {
         self.dirty = true;
     }
 
+    /// 设置 Override 值（后设置者生效）
+    pub fn set_override(&mut self, key: &str, value: f64) {
+        self.overrides.insert(StatKey::new(key), value);
+        self.dirty = true;
+    }
+
+    /// 获取 Override 值
+    pub fn get_override(&self, key: &str) -> Option<f64> {
+        StatKey::lookup(key).and_then(|k| self.overrides.get(&k)).copied()
+    }
+
     /// 获取基础值
     pub fn get_base(&self, key: &str) -> f64 {
-        self.base.get(key).copied().unwrap_or(0.0)
+        StatKey::lookup(key)
+            .and_then(|k| self.base.get(&k))
+            .copied()
+            .unwrap_or(0.0)
     }
 
     /// 获取 Increased 总和
     pub fn get_increased(&self, key: &str) -> f64 {
-        self.increased.get(key).copied().unwrap_or(0.0)
+        StatKey::lookup(key)
+            .and_then(|k| self.increased.get(&k))
+            .copied()
+            .unwrap_or(0.0)
     }
 
     /// 获取 More 乘积
     pub fn get_more_multiplier(&self, key: &str) -> f64 {
-        let mods = match self.more.get(key) {
+        let mods = match StatKey::lookup(key).and_then(|k| self.more.get(&k)) {
             Some(m) => m,
             None => return 1.0,
         };
@@ -99,7 +135,7 @@ impl StatPool {
         let mut buckets: HashMap<u32, f64> = HashMap::new();
         for m in mods {
             let entry = buckets.entry(m.bucket_id).or_insert(1.0);
-            *entry *= 1.0 + m.value;
+            *entry *= more_factor(m.value);
         }
 
         // 所有 bucket 相乘
@@ -107,20 +143,26 @@ impl StatPool {
     }
 
     /// 计算最终值
-    /// final = base * (1 + sum(increased)) * product(1 + more)
+    /// final = override 或 base * (1 + sum(increased)) * product(1 + more)
     pub fn calculate_final(&mut self, key: &str) -> f64 {
+        let stat = StatKey::new(key);
         if !self.dirty {
-            if let Some(&cached) = self.final_values.get(key) {
+            if let Some(&cached) = self.final_values.get(&stat) {
                 return cached;
             }
         }
 
+        if let Some(override_val) = self.get_override(key) {
+            self.final_values.insert(stat, override_val);
+            return override_val;
+        }
+
         let base = self.get_base(key);
         let inc = self.get_increased(key);
         let more = self.get_more_multiplier(key);
 
         let result = base * (1.0 + inc) * more;
-        self.final_values.insert(key.to_string(), result);
+        self.final_values.insert(stat, result);
         
         result
     }
@@ -128,9 +170,14 @@ impl StatPool {
     /// 重新计算所有最终值
     pub fn recalculate_all(&mut self) {
         self.final_values.clear();
-        let keys: Vec<String> = self.base.keys().cloned().collect();
+        let keys: Vec<StatKey> = self
+            .base
+            .keys()
+            .chain(self.overrides.keys())
+            .copied()
+            .collect();
         for key in keys {
-            self.calculate_final(&key);
+            self.calculate_final(key.as_str());
         }
         self.dirty = false;
     }
@@ -138,21 +185,23 @@ impl StatPool {
     /// 合并另一个属性池
     pub fn merge(&mut self, other: &StatPool) {
         for (key, value) in &other.base {
-            self.add_base(key, *value);
+            *self.base.entry(*key).or_insert(0.0) += value;
         }
         for (key, value) in &other.increased {
-            self.add_increased(key, *value);
+            *self.increased.entry(*key).or_insert(0.0) += value;
         }
         for (key, mods) in &other.more {
-            for m in mods {
-                self.add_more(key, m.value, m.bucket_id, &m.source);
-            }
+            self.more.entry(*key).or_default().extend(mods.iter().cloned());
+        }
+        for (key, value) in &other.overrides {
+            self.overrides.insert(*key, *value);
         }
+        self.dirty = true;
     }
 
     /// 获取所有基础键
-    pub fn base_keys(&self) -> impl Iterator<Item = &String> {
-        self.base.keys()
+    pub fn base_keys(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.base.keys().map(|k| k.as_str())
     }
 }
 
@@ -162,15 +211,44 @@ impl StatPool {
 pub struct StatAggregator<'a> {
     pool: StatPool,
     context: &'a ContextTags,
-    local_pool: StatPool, // 用于武器等局部属性
+    local_pool: StatPool, // 当前装备的局部属性暂存池（武器伤害等）
     /// 每件装备的局部属性池（用于暗金装备基底+词缀合并计算）
     item_local_pools: HashMap<String, ItemLocalStats>,
-    /// 机制处理器（用于处理 .per_xxx 属性）
-    mechanics: Option<&'a MechanicsProcessor>,
+    /// 机制处理器（用于处理 .per_xxx 属性；层数上限被装备提高时持有调整后的副本）
+    mechanics: Option<Cow<'a, MechanicsProcessor>>,
     /// 结构化修正存储（新版，用于溯源）
     mod_db: ModDB,
+    /// 词缀条件评估上下文
+    eval_ctx: EvalContext,
+    /// 待第二遍解析的 PerStat 属性（StatPool 侧）: (属性键, 数值, PerStat 配置)
+    pending_per_stat: Vec<(String, f64, PerStatConfig)>,
+    /// 待层数上限结算后解析的机制层数属性
+    pending_per_stack: Vec<PendingPerStack>,
+    /// More bucket 注册表
+    buckets: MoreBucketRegistry,
+    /// 当前来源对应的 More bucket（由各 aggregate_* 方法切换）
+    more_bucket: (u32, BucketStacking),
+    /// 词缀条件未满足而未写入 ModDB 的修正（附条件表达式，用于溯源）
+    inactive_modifiers: Vec<Modifier>,
+    /// 当前词缀已评估通过的条件表达式（写入修正的 `condition_str`，仅用于溯源）
+    affix_condition: Option<String>,
+    /// 未知属性键: (属性键, 来源)
+    unknown_stat_keys: Vec<(String, String)>,
+    /// 已映射的属性键别名: (别名, 规范键, 来源)
+    aliased_stat_keys: Vec<(String, String, String)>,
+    /// 按 0–100 约定录入并已换算的数值: (属性键, 原值, 换算后, 来源)
+    normalized_stat_values: Vec<(String, f64, f64, String)>,
+    /// 各把武器的面板 DPS（按聚合顺序）
+    weapon_summaries: Vec<WeaponSummary>,
+    /// 待聚合完成后求值的全局覆盖表达式: (属性键, 表达式, 来源)
+    pending_override_expressions: Vec<(String, ValueExpr, String)>,
+    /// 已由来源提供的自定义属性键（其余按默认值计入）
+    provided_custom_stats: Vec<String>,
 }
 
+/// 待解析的机制层数属性: (属性键, 数值, 来源, More bucket, 词缀条件)
+type PendingPerStack = (String, f64, String, (u32, BucketStacking), Option<String>);
+
 /// 单件装备的局部属性
 #[derive(Debug, Clone, Default)]
 pub struct ItemLocalStats {
@@ -186,6 +264,128 @@ pub struct ItemLocalStats {
     pub armor_percent: f64,
     pub es_percent: f64,
     pub evasion_percent: f64,
+    /// 装备品质（小数，0.2 = 20%），与该装备的局部百分比加成相加
+    pub quality: f64,
+    /// 是否为武器（品质作用于武器物理伤害）
+    pub is_weapon: bool,
+    /// 该装备的其余局部属性（武器伤害、攻速、暴击率等）
+    pub pool: StatPool,
+    /// 条件引用 `stat(...)` 的局部属性: (属性键, 数值, 来源, 条件)
+    ///
+    /// 条件在计算阶段才能评估，不写入局部属性池；局部结算时按其带来的增量写入带条件的 ModDB 修正
+    pub deferred: Vec<(String, f64, String, String)>,
+}
+
+/// 局部防御类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocalDefense {
+    Armor,
+    EnergyShield,
+    Evasion,
+}
+
+/// 局部防御平面值属性键 → 防御类型（按来源写入基底值或词缀值）
+const LOCAL_DEFENSE_FLAT: &[(&str, &[LocalDefense])] = &[
+    ("def.armor", &[LocalDefense::Armor]),
+    ("base.es", &[LocalDefense::EnergyShield]),
+    ("def.evasion", &[LocalDefense::Evasion]),
+];
+
+/// "该装备 +X% 防御" 属性键 → 防御类型（混合词缀同时作用于多种防御）
+const LOCAL_DEFENSE_PERCENT: &[(&str, &[LocalDefense])] = &[
+    ("mod.inc.def.armor.local", &[LocalDefense::Armor]),
+    ("mod.inc.base.es.local", &[LocalDefense::EnergyShield]),
+    ("mod.inc.def.evasion.local", &[LocalDefense::Evasion]),
+    ("mod.inc.def.armor_es.local", &[LocalDefense::Armor, LocalDefense::EnergyShield]),
+    ("mod.inc.def.armor_evasion.local", &[LocalDefense::Armor, LocalDefense::Evasion]),
+    ("mod.inc.def.evasion_es.local", &[LocalDefense::Evasion, LocalDefense::EnergyShield]),
+];
+
+impl ItemLocalStats {
+    /// 写入一条局部属性（局部防御或局部属性池）
+    fn add_local_stat(&mut self, key: &str, value: f64) {
+        if !self.add_local_defense(key, value, false) {
+            StatAggregator::apply_stat_to_pool(&mut self.pool, key, value);
+        }
+    }
+
+    /// 是否为写入该装备局部属性的键（局部防御或局部属性）
+    fn is_local_key(&self, key: &str) -> bool {
+        LOCAL_DEFENSE_FLAT.iter().chain(LOCAL_DEFENSE_PERCENT).any(|(stat, _)| *stat == key)
+            || is_local_stat(key, self.is_weapon)
+    }
+
+    /// 该装备局部结算后写入全局的属性（双持均摊前）: (属性键, 数值)，各装备顺序一致
+    ///
+    /// - 防御 = (基底 + 词缀平面值) × (1 + 局部百分比 + 品质)
+    /// - 武器伤害 / 暴击率 / 攻速见 `local_phys_damage` / `local_flat_damage` / `local_final`
+    fn finalized_stats(&self) -> Vec<(String, f64)> {
+        let defense = |base: f64, affix: f64, percent: f64| (base + affix) * (1.0 + percent + self.quality);
+        let (phys_min, phys_max) = local_phys_damage(&self.pool, self.weapon_quality());
+        let mut stats = vec![
+            ("def.armor".to_string(), defense(self.base_armor, self.affix_armor, self.armor_percent)),
+            ("base.es".to_string(), defense(self.base_es, self.affix_es, self.es_percent)),
+            ("def.evasion".to_string(), defense(self.base_evasion, self.affix_evasion, self.evasion_percent)),
+            ("weapon.dmg.phys.min".to_string(), phys_min),
+            ("weapon.dmg.phys.max".to_string(), phys_max),
+        ];
+        for (element, _) in WEAPON_LOCAL_DAMAGE {
+            let (min, max) = local_flat_damage(&self.pool, element);
+            stats.push((format!("weapon.dmg.{}.min", element), min));
+            stats.push((format!("weapon.dmg.{}.max", element), max));
+        }
+        stats.push(("weapon.crit.chance".to_string(), local_final(&self.pool, "crit.chance.local")));
+        stats.push(("weapon.base_speed".to_string(), local_final(&self.pool, "speed.attack.local")));
+        stats
+    }
+
+    /// 作用于武器伤害的品质（非武器为 0）
+    fn weapon_quality(&self) -> f64 {
+        if self.is_weapon {
+            self.quality
+        } else {
+            0.0
+        }
+    }
+
+    /// 按映射表写入局部防御属性（`from_base` 为基底固有属性）；非局部防御键返回 false
+    fn add_local_defense(&mut self, key: &str, value: f64, from_base: bool) -> bool {
+        let lookup = |table: &[(&str, &'static [LocalDefense])]| {
+            table.iter().find(|(stat, _)| *stat == key).map(|(_, defenses)| *defenses)
+        };
+        if let Some(defenses) = lookup(LOCAL_DEFENSE_PERCENT) {
+            for defense in defenses {
+                *self.percent_mut(*defense) += value;
+            }
+            return true;
+        }
+        if let Some(defenses) = lookup(LOCAL_DEFENSE_FLAT) {
+            for defense in defenses {
+                *self.flat_mut(*defense, from_base) += value;
+            }
+            return true;
+        }
+        false
+    }
+
+    fn percent_mut(&mut self, defense: LocalDefense) -> &mut f64 {
+        match defense {
+            LocalDefense::Armor => &mut self.armor_percent,
+            LocalDefense::EnergyShield => &mut self.es_percent,
+            LocalDefense::Evasion => &mut self.evasion_percent,
+        }
+    }
+
+    fn flat_mut(&mut self, defense: LocalDefense, from_base: bool) -> &mut f64 {
+        match (defense, from_base) {
+            (LocalDefense::Armor, true) => &mut self.base_armor,
+            (LocalDefense::Armor, false) => &mut self.affix_armor,
+            (LocalDefense::EnergyShield, true) => &mut self.base_es,
+            (LocalDefense::EnergyShield, false) => &mut self.affix_es,
+            (LocalDefense::Evasion, true) => &mut self.base_evasion,
+            (LocalDefense::Evasion, false) => &mut self.affix_evasion,
+        }
+    }
 }
 
 impl<'a> StatAggregator<'a> {
@@ -198,6 +398,19 @@ impl<'a> StatAggregator<'a> {
             item_local_pools: HashMap::new(),
             mechanics: None,
             mod_db: ModDB::new(),
+            eval_ctx: EvalContext::new(),
+            pending_per_stat: Vec::new(),
+            pending_per_stack: Vec::new(),
+            buckets: MoreBucketRegistry::load_default(),
+            more_bucket: (0, BucketStacking::Multiplicative),
+            inactive_modifiers: Vec::new(),
+            affix_condition: None,
+            unknown_stat_keys: Vec::new(),
+            aliased_stat_keys: Vec::new(),
+            normalized_stat_values: Vec::new(),
+            weapon_summaries: Vec::new(),
+            pending_override_expressions: Vec::new(),
+            provided_custom_stats: Vec::new(),
         }
     }
     
@@ -208,14 +421,47 @@ impl<'a> StatAggregator<'a> {
             context,
             local_pool: StatPool::new(),
             item_local_pools: HashMap::new(),
-            mechanics: Some(mechanics),
+            mechanics: Some(Cow::Borrowed(mechanics)),
             mod_db: ModDB::new(),
+            eval_ctx: EvalContext::new(),
+            pending_per_stat: Vec::new(),
+            pending_per_stack: Vec::new(),
+            buckets: MoreBucketRegistry::load_default(),
+            more_bucket: (0, BucketStacking::Multiplicative),
+            inactive_modifiers: Vec::new(),
+            affix_condition: None,
+            unknown_stat_keys: Vec::new(),
+            aliased_stat_keys: Vec::new(),
+            normalized_stat_values: Vec::new(),
+            weapon_summaries: Vec::new(),
+            pending_override_expressions: Vec::new(),
+            provided_custom_stats: Vec::new(),
         }
     }
     
     /// 设置机制处理器
     pub fn set_mechanics(&mut self, mechanics: &'a MechanicsProcessor) {
-        self.mechanics = Some(mechanics);
+        self.mechanics = Some(Cow::Borrowed(mechanics));
+    }
+
+    /// 设置词缀条件评估上下文
+    pub fn set_eval_context(&mut self, eval_ctx: EvalContext) {
+        self.eval_ctx = eval_ctx;
+    }
+
+    /// 获取条件评估上下文（机制结算后包含最终层数）
+    pub fn eval_context(&self) -> &EvalContext {
+        &self.eval_ctx
+    }
+
+    /// 获取机制处理器（含授予与上限加成）
+    pub fn mechanics(&self) -> Option<&MechanicsProcessor> {
+        self.mechanics.as_deref()
+    }
+
+    /// 设置 More bucket 注册表
+    pub fn set_bucket_registry(&mut self, buckets: MoreBucketRegistry) {
+        self.buckets = buckets;
     }
 
     /// 获取 ModDB 引用
@@ -223,144 +469,332 @@ impl<'a> StatAggregator<'a> {
         &self.mod_db
     }
 
+    /// 切换后续 More 修正所属的 bucket（按来源分类解析）
+    fn enter_bucket(&mut self, category: &str, instance: usize) {
+        self.more_bucket = self.buckets.resolve(category, instance);
+    }
+
     /// 聚合装备属性
+    ///
+    /// 追忆按所在槽位的位置效果缩放后聚合，并写入已装备追忆数 `memory.count`（供协同加成的 per_xxx 解析）
     pub fn aggregate_items(&mut self, items: &[ItemData]) {
+        let memory_rules = MemoryRules::shared();
+        let mut memory_count = 0;
         for item in items {
-            self.aggregate_single_item(item);
+            if is_memory_slot(&item.slot) {
+                memory_count += 1;
+                self.aggregate_single_item(&memory_rules.apply_position(item));
+            } else {
+                self.aggregate_single_item(item);
+            }
+        }
+        if memory_count > 0 {
+            self.enter_bucket("item", 0);
+            self.apply_stat(MEMORY_COUNT, memory_count as f64, "memory");
         }
     }
 
     /// 聚合单个装备
     pub fn aggregate_single_item(&mut self, item: &ItemData) {
+        self.enter_bucket("item", 0);
+
         // 为每件装备创建局部属性池
-        let mut item_local = ItemLocalStats::default();
+        let mut item_local = ItemLocalStats {
+            quality: item.quality as f64 / 100.0,
+            is_weapon: matches!(item.slot, SlotType::WeaponMain | SlotType::WeaponOff),
+            ..Default::default()
+        };
+        // 该装备的局部属性先写入暂存池，聚合完成后存入该装备的局部属性
         
         // 1. 处理基底固有属性（来自 items_meta）
+        let source = format!("{}:base", item.id);
         for (key, value) in &item.base_implicit_stats {
-            match key.as_str() {
-                "def.armor" => item_local.base_armor += *value,
-                "base.es" => item_local.base_es += *value,
-                "def.evasion" => item_local.base_evasion += *value,
-                _ => {
-                    if is_local_stat(key) {
-                        self.local_pool.add_base(key, *value);
-                    } else {
-                        // 通过 apply_stat 支持 per_xxx 机制解析
-                        self.apply_stat(key, *value, &format!("{}:base", item.id));
-                    }
-                }
+            let (key, value) = self.ingest_stat(key, *value, &source);
+            let key = key.as_ref();
+            if item_local.add_local_defense(key, value, true) {
+                continue;
+            }
+            if is_local_stat(key, item_local.is_weapon) {
+                self.local_pool.add_base(key, value);
+            } else {
+                // 通过 apply_stat 支持 per_xxx 机制解析
+                self.apply_stat(key, value, &source);
             }
         }
         
         // 2. 处理暗金/传奇装备的隐性词缀
+        let source = format!("{}:implicit", item.id);
         for (key, value) in &item.implicit_stats {
-            match key.as_str() {
-                "def.armor" => item_local.affix_armor += *value,
-                "base.es" => item_local.affix_es += *value,
-                "def.evasion" => item_local.affix_evasion += *value,
-                _ => {
-                    if is_local_stat(key) {
-                        self.local_pool.add_base(key, *value);
-                    } else {
-                        // 通过 apply_stat 支持 per_xxx 机制解析
-                        self.apply_stat(key, *value, &format!("{}:implicit", item.id));
-                    }
-                }
+            let (key, value) = self.ingest_stat(key, *value, &source);
+            let key = key.as_ref();
+            if item_local.add_local_defense(key, value, false) {
+                continue;
+            }
+            if is_local_stat(key, item_local.is_weapon) {
+                self.local_pool.add_base(key, value);
+            } else {
+                // 通过 apply_stat 支持 per_xxx 机制解析
+                self.apply_stat(key, value, &source);
             }
         }
 
         // 3. 处理词缀
         for affix in &item.affixes {
             // 检查词缀条件是否满足
-            if !self.check_affix_requirements(affix) {
+            if !self.check_affix_condition(affix) {
+                self.record_inactive_affix(item, affix);
                 continue;
             }
+            // 条件分支：命中的分支条件一并记录，用于溯源
+            let (stats, branch_condition) = self.select_affix_branch(affix);
+            self.affix_condition = match (affix.condition.as_deref(), branch_condition) {
+                (Some(condition), Some(branch)) => Some(format!("({}) && ({})", condition, branch)),
+                (condition, branch) => condition.or(branch).map(str::to_string),
+            };
 
-            for (key, value) in &affix.stats {
-                // 处理该装备的局部百分比加成
-                match key.as_str() {
-                    "mod.inc.def.armor.local" => {
-                        item_local.armor_percent += *value;
-                        continue;
-                    }
-                    "mod.inc.base.es.local" => {
-                        item_local.es_percent += *value;
-                        continue;
-                    }
-                    "mod.inc.def.evasion.local" => {
-                        item_local.evasion_percent += *value;
-                        continue;
-                    }
-                    // 平面局部属性
-                    "def.armor" => {
-                        item_local.affix_armor += *value;
-                        continue;
-                    }
-                    "base.es" => {
-                        item_local.affix_es += *value;
-                        continue;
-                    }
-                    "def.evasion" => {
-                        item_local.affix_evasion += *value;
-                        continue;
-                    }
-                    _ => {}
+            let source = format!("{}:{}", item.id, affix.id);
+            let deferred = self.affix_condition_deferred();
+            for (key, value) in stats {
+                let (key, value) = self.ingest_stat(key, *value, &source);
+                let key = key.as_ref();
+                // 条件依赖最终属性的局部属性不写入局部属性池，局部结算时按条件写入增量
+                if deferred && (affix.is_local || item_local.is_local_key(key)) {
+                    let condition = self.affix_condition.clone().unwrap_or_default();
+                    item_local.deferred.push((key.to_string(), value, source.clone(), condition));
+                    continue;
+                }
+                // 该装备的局部防御（平面值与百分比加成）
+                if item_local.add_local_defense(key, value, false) {
+                    continue;
                 }
-                
-                if affix.is_local || is_local_stat(key) {
+
+                if affix.is_local || is_local_stat(key, item_local.is_weapon) {
                     // 其他局部属性（如武器物理伤害）
-                    Self::apply_stat_to_pool(&mut self.local_pool, key, *value);
+                    Self::apply_stat_to_pool(&mut self.local_pool, key, value);
                 } else {
                     // 全局属性
-                    self.apply_stat(key, *value, &format!("{}:{}", item.id, affix.id));
+                    self.apply_stat(key, value, &source);
                 }
             }
+            self.affix_condition = None;
         }
         
+        item_local.pool = std::mem::take(&mut self.local_pool);
+        if item_local.is_weapon {
+            self.weapon_summaries.push(weapon_summary(item, &item_local.pool, item_local.quality));
+        }
+
         // 保存该装备的局部属性池
         self.item_local_pools.insert(item.id.clone(), item_local);
     }
 
+    /// 各把武器的面板 DPS
+    pub fn weapon_summaries(&self) -> &[WeaponSummary] {
+        &self.weapon_summaries
+    }
+
+    /// 聚合套装加成
+    ///
+    /// 按 `set_id` 统计已装备件数，件数达到要求的各档加成均生效，来源记为 `set:<套装 ID>`
+    pub fn aggregate_item_sets(&mut self, items: &[ItemData], sets: &[ItemSetDefinition]) {
+        self.enter_bucket("item", 0);
+        for set in sets {
+            let equipped = items.iter().filter(|item| item.set_id.as_deref() == Some(set.id.as_str())).count();
+            let source = format!("set:{}", set.id);
+            for bonus in set.bonuses.iter().filter(|bonus| equipped >= bonus.pieces as usize) {
+                for (key, value) in &bonus.stats {
+                    let (key, value) = self.ingest_stat(key, *value, &source);
+                    self.apply_stat(&key, value, &source);
+                }
+            }
+        }
+    }
+
     /// 检查词缀条件是否满足
-    fn check_affix_requirements(&self, affix: &AffixData) -> bool {
-        if affix.requirements.is_empty() {
-            return true;
+    ///
+    /// 先校验标签需求，再评估条件表达式；表达式解析失败视为不满足
+    fn check_affix_condition(&self, affix: &AffixData) -> bool {
+        if !affix.requirements.is_empty() || !affix.excluded_tags.is_empty() {
+            // 将字符串需求 / 排除标签转换为 ID
+            let resolve = |names: &[String]| -> Vec<u32> {
+                names
+                    .iter()
+                    .filter_map(|name| self.context.registry().get_id(name))
+                    .collect()
+            };
+
+            if !self
+                .context
+                .matches_requirements(&resolve(&affix.requirements), &resolve(&affix.excluded_tags))
+            {
+                return false;
+            }
+        }
+
+        // 引用 `stat(...)` 的条件依赖聚合结果，延迟到计算阶段评估
+        match &affix.condition {
+            Some(expr) => Condition::parse(expr)
+                .map(|cond| !cond.stat_references().is_empty() || cond.evaluate(&self.eval_ctx))
+                .unwrap_or(false),
+            None => true,
         }
+    }
 
-        // 将字符串需求转换为 ID
-        let req_ids: Vec<u32> = affix
-            .requirements
+    /// 选择词缀生效的属性：首个条件满足的分支，均不满足时为词缀自身属性
+    ///
+    /// 返回 (属性, 命中分支的条件)；分支条件已由输入校验保证可解析且不引用 `stat(...)`（聚合阶段属性尚未完成）
+    fn select_affix_branch<'b>(&self, affix: &'b AffixData) -> (&'b HashMap<String, f64>, Option<&'b str>) {
+        affix
+            .branches
             .iter()
-            .filter_map(|name| self.context.registry().get_id(name))
-            .collect();
+            .find(|branch| Condition::parse(&branch.condition).is_ok_and(|cond| cond.evaluate(&self.eval_ctx)))
+            .map_or((&affix.stats, None), |branch| (&branch.stats, Some(branch.condition.as_str())))
+    }
 
-        self.context.matches_requirements(&req_ids)
+    /// 记录条件表达式未满足的词缀修正（标签需求不满足的词缀不记录）
+    fn record_inactive_affix(&mut self, item: &ItemData, affix: &AffixData) {
+        let Some(expr) = affix.condition.as_deref() else {
+            return;
+        };
+        let source = format!("{}:{}", item.id, affix.id);
+        let is_weapon = matches!(item.slot, SlotType::WeaponMain | SlotType::WeaponOff);
+        for (key, value) in &affix.stats {
+            let (key, value) = self.ingest_stat(key, *value, &source);
+            let key = key.as_ref();
+            if affix.is_local
+                || is_local_stat(key, is_weapon)
+                || is_per_stack_stat(key)
+                || parse_per_stat_key(key).is_some()
+            {
+                continue;
+            }
+            let mut modifier = Self::to_modifier(key, value, &source).with_condition(expr);
+            if modifier.kind == ModifierKind::More {
+                let (bucket_id, stacking) = self.more_bucket;
+                modifier = modifier.with_bucket(bucket_id, stacking);
+            }
+            self.inactive_modifiers.push(modifier);
+        }
+    }
+
+    /// 词缀条件未满足而未生效的修正
+    pub fn inactive_modifiers(&self) -> &[Modifier] {
+        &self.inactive_modifiers
+    }
+
+    /// 已映射的属性键别名（按首次出现顺序去重）: (别名, 规范键, 来源)
+    pub fn aliased_stat_keys(&self) -> &[(String, String, String)] {
+        &self.aliased_stat_keys
+    }
+
+    /// 按 0–100 约定录入并已换算的数值: (属性键, 原值, 换算后, 来源)
+    pub fn normalized_stat_values(&self) -> &[(String, f64, f64, String)] {
+        &self.normalized_stat_values
+    }
+
+    /// 录入外部数据的属性：别名映射为规范键（记录首次出现的映射），自定义键改写为规范键，
+    /// 比例类数值按单位表换算为 0–1 约定（记录每次换算）
+    fn ingest_stat<'k>(&mut self, key: &'k str, value: f64, source: &str) -> (Cow<'k, str>, f64) {
+        let key = self.resolve_stat_alias(key, source);
+        let key = match self.resolve_custom_stat(&key) {
+            Some(canonical) => Cow::Owned(canonical),
+            None => key,
+        };
+        match StatUnitTable::shared().normalize(&key, value) {
+            Some((normalized_key, normalized)) => {
+                self.normalized_stat_values
+                    .push((key.to_string(), value, normalized, source.to_string()));
+                (Cow::Owned(normalized_key.to_string()), normalized)
+            }
+            None => (key, value),
+        }
+    }
+
+    /// 自定义属性键（可带后缀）改写为规范键，并记录该自定义键已由某个来源提供（非自定义键返回 None）
+    fn resolve_custom_stat(&mut self, key: &str) -> Option<String> {
+        let (custom_key, canonical) = CustomStatTable::shared().rewrite(key)?;
+        if !self.provided_custom_stats.iter().any(|k| k == custom_key) {
+            self.provided_custom_stats.push(custom_key.to_string());
+        }
+        Some(canonical)
+    }
+
+    /// 别名映射为规范键（记录首次出现的映射）
+    fn resolve_stat_alias<'k>(&mut self, key: &'k str, source: &str) -> Cow<'k, str> {
+        match StatAliasTable::shared().resolve(key) {
+            Some(canonical) => {
+                if !self.aliased_stat_keys.iter().any(|(alias, _, _)| alias == key) {
+                    self.aliased_stat_keys
+                        .push((key.to_string(), canonical.to_string(), source.to_string()));
+                }
+                Cow::Borrowed(canonical)
+            }
+            None => Cow::Borrowed(key),
+        }
+    }
+
+    /// 未知属性键（按首次出现顺序去重）: (属性键, 来源)
+    pub fn unknown_stat_keys(&self) -> &[(String, String)] {
+        &self.unknown_stat_keys
     }
 
     /// 应用属性到池
     /// 
     /// 如果是 .per_xxx 类型的属性，会根据机制层数计算实际值
     fn apply_stat(&mut self, key: &str, value: f64, source: &str) {
+        if !is_known_stat_key(key) && !self.unknown_stat_keys.iter().any(|(k, _)| k == key) {
+            self.unknown_stat_keys.push((key.to_string(), source.to_string()));
+        }
+
+        // 未经录入的来源（表达式、默认值等）中的自定义属性键改写为规范键（见 custom_stats.json）
+        let custom = self.resolve_custom_stat(key);
+        let key = custom.as_deref().unwrap_or(key);
+
+        // 条件依赖最终属性的修正只写入 ModDB（计算阶段按条件评估），不写入不评估条件的 StatPool
+        let deferred = self.affix_condition_deferred();
+
+        // PerStat 属性：依赖第一遍聚合出的属性总值，延迟到第二遍解析
+        if let Some((base_key, config)) = parse_per_stat_key(key) {
+            let modifier = Self::to_modifier(base_key, value, source).with_per_stat_config(config.clone());
+            self.push_modifier(modifier);
+            if !deferred {
+                self.pending_per_stat.push((base_key.to_string(), value, config));
+            }
+            return;
+        }
+
         // 检查是否是 per_xxx 类型的属性
         if is_per_stack_stat(key) {
-            if let Some(mechanics) = &self.mechanics {
-                if let Some((base_key, total_value)) = mechanics.calculate_per_stack_value(key, value) {
-                    Self::apply_stat_to_pool(&mut self.pool, &base_key, total_value);
-                    self.add_to_mod_db(&base_key, total_value, source);
-                }
-                // 如果机制未激活或层数为0，跳过该属性
+            // 层数上限可能被后续来源提高，延迟到机制结算时解析（词缀条件随之保留）
+            // 如果没有机制处理器，跳过（无法计算层数）
+            if self.mechanics.is_some() {
+                self.pending_per_stack.push((
+                    key.to_string(),
+                    value,
+                    source.to_string(),
+                    self.more_bucket,
+                    self.affix_condition.clone(),
+                ));
             }
-            // 如果没有机制处理器，也跳过（无法计算层数）
         } else {
-            Self::apply_stat_to_pool(&mut self.pool, key, value);
+            if !deferred {
+                Self::apply_stat_to_pool(&mut self.pool, key, value);
+            }
             self.add_to_mod_db(key, value, source);
         }
     }
 
+    /// 当前词缀条件是否引用聚合后的属性（`stat(...)`，延迟到计算阶段评估）
+    fn affix_condition_deferred(&self) -> bool {
+        self.affix_condition.as_deref().is_some_and(references_stats)
+    }
+
     /// 应用属性到指定池（静态方法，仅更新 StatPool）
     fn apply_stat_to_pool(pool: &mut StatPool, key: &str, value: f64) {
         // 根据键名前缀判断类型
-        if key.starts_with("mod.inc.") {
+        if let Some(stripped_key) = key.strip_prefix("mod.override.") {
+            pool.set_override(stripped_key, value);
+        } else if key.starts_with("mod.inc.") {
             pool.add_increased(&key.replace("mod.inc.", ""), value);
         } else if key.starts_with("mod.more.") {
             // More 修正默认使用 bucket 0
@@ -378,7 +812,25 @@ impl<'a> StatAggregator<'a> {
 
     /// 添加到 ModDB（结构化存储）
     fn add_to_mod_db(&mut self, key: &str, value: f64, source: &str) {
-        let modifier = if key.starts_with("mod.inc.") {
+        // 值不为正的 Flag 视为未设置
+        if key.starts_with("flag.") && value <= 0.0 {
+            return;
+        }
+        let modifier = Self::to_modifier(key, value, source);
+        self.push_modifier(modifier);
+    }
+
+    /// 按键名前缀构建 Modifier
+    pub(crate) fn to_modifier(key: &str, value: f64, source: &str) -> Modifier {
+        if key.starts_with("target.") {
+            // 敌人侧效果保留原键，计算时由目标 ModDB 按前缀重新解析
+            Modifier::base(key, value, source).with_scope(ModifierScope::Target)
+        } else if let Some(stripped_key) = key.strip_prefix("mod.override.") {
+            Modifier::override_value(stripped_key, value, source)
+        } else if key.starts_with("flag.") {
+            // 布尔效果统一为 Flag
+            Modifier::flag(key, source)
+        } else if key.starts_with("mod.inc.") {
             let stripped_key = key.replace("mod.inc.", "");
             Modifier::inc(&stripped_key, value, source)
         } else if key.starts_with("mod.more.") {
@@ -392,23 +844,214 @@ impl<'a> StatAggregator<'a> {
             Modifier::inc("crit.dmg", value, source)
         } else {
             Modifier::base(key, value, source)
+        }
+    }
+
+    /// 写入 ModDB，并按伤害作用域表附加标签要求
+    ///
+    /// 所有来源（装备、技能、机制、覆盖）统一在此校验标签要求，
+    /// 不满足当前上下文标签的修正直接丢弃；More 修正归入当前来源的 bucket
+    fn push_modifier(&mut self, mut modifier: Modifier) {
+        if modifier.kind == ModifierKind::More {
+            let (bucket_id, stacking) = self.more_bucket;
+            modifier = modifier.with_bucket(bucket_id, stacking);
+        }
+        let modifier = match damage_mod_scope(modifier.key.as_str()) {
+            Some((hit_tags, requirements)) => {
+                let registry = self.context.registry();
+                let resolve = |names: &[&str]| -> Vec<u32> {
+                    names.iter().filter_map(|n| registry.get_id(n)).collect()
+                };
+                // 与修正自带的标签要求合并（去重），不覆盖
+                let mut modifier = modifier;
+                for id in resolve(hit_tags) {
+                    if !modifier.hit_tags.contains(&id) {
+                        modifier.hit_tags.push(id);
+                    }
+                }
+                for id in resolve(requirements) {
+                    if !modifier.requirements.contains(&id) {
+                        modifier.requirements.push(id);
+                    }
+                }
+                modifier
+            }
+            None => modifier,
+        };
+        if !self.context.matches_requirements(&modifier.requirements, &modifier.excluded) {
+            return;
+        }
+        // 词缀条件已在聚合阶段评估，仅保留表达式用于溯源（不再参与计算时评估）；
+        // 引用 `stat(...)` 的条件保留 AST，在计算阶段按最终属性评估
+        let modifier = match &self.affix_condition {
+            Some(expr) if references_stats(expr) => modifier.with_condition(expr),
+            Some(expr) => Modifier { condition_str: Some(expr.clone()), ..modifier },
+            None => modifier,
         };
         self.mod_db.add(modifier);
     }
     
     /// 应用机制基础效果
     /// 
-    /// 将所有激活机制的基础效果（每层提供的属性）应用到属性池
+    /// 将所有激活机制的基础效果（每层提供的属性）应用到属性池，
+    /// 标记为受光环效果影响的机制（祝福类）按光环效果乘数缩放
     pub fn apply_mechanic_base_effects(&mut self) {
+        self.apply_mechanic_grants();
+        self.apply_max_stack_bonuses();
+        self.refresh_mechanic_context();
+        self.resolve_per_stack();
+        self.enter_bucket("mechanic", 0);
+        let aura_effect = self.aura_effect_multiplier();
         if let Some(mechanics) = &self.mechanics {
-            let effects = mechanics.calculate_base_effects();
-            for (key, value) in effects {
+            // 每个机制独立写入，More 效果在机制之间相乘
+            for (mech_id, key, value) in mechanics.base_effect_entries(aura_effect) {
+                let key = self.resolve_custom_stat(&key).unwrap_or(key);
                 Self::apply_stat_to_pool(&mut self.pool, &key, value);
-                self.add_to_mod_db(&key, value, "mechanic_effect");
+                self.add_to_mod_db(&key, value, &format!("mechanic:{}", mech_id));
             }
         }
     }
     
+    /// 按聚合后的技能使用速率更新消耗型资源的可持续比例（需在机制效果结算之前调用）
+    pub fn set_mechanic_use_rate(&mut self, uses_per_second: f64) {
+        if let Some(mechanics) = self.mechanics.as_mut() {
+            if mechanics.has_resource_costs() {
+                mechanics.to_mut().set_use_rate(uses_per_second);
+            }
+        }
+    }
+
+    /// 局部结算后的武器基础攻速（双持时取平均值，未装备武器时为 0，聚合阶段即可读取）
+    pub fn local_attack_speed(&self) -> f64 {
+        self.local_total(|local| local_final(&local.pool, "speed.attack.local"))
+    }
+
+    /// 应用装备/技能授予的机制（`grant.mechanic.<机制>` = 层数），自动创建激活状态
+    fn apply_mechanic_grants(&mut self) {
+        if self.mechanics.is_none() {
+            return;
+        }
+        let pattern = format!("{}*", GRANT_MECHANIC_PREFIX);
+        let grants: Vec<(String, u32)> = self
+            .mod_db
+            .query_keys(&pattern)
+            .into_iter()
+            .filter_map(|key| {
+                let stacks = self.mod_db.sum_base_with_ctx(&key, &self.eval_ctx).round();
+                let mech_id = key.strip_prefix(GRANT_MECHANIC_PREFIX)?.to_string();
+                (stacks > 0.0).then_some((mech_id, stacks as u32))
+            })
+            .collect();
+        if grants.is_empty() {
+            return;
+        }
+        if let Some(mechanics) = self.mechanics.as_mut() {
+            let mechanics = mechanics.to_mut();
+            for (mech_id, stacks) in grants {
+                mechanics.grant(&mech_id, stacks);
+            }
+        }
+    }
+
+    /// 应用装备/天赋提供的层数上限加成（`mod.max_stacks.<机制>`）
+    fn apply_max_stack_bonuses(&mut self) {
+        let Some(mechanics) = &self.mechanics else {
+            return;
+        };
+        let bonuses: HashMap<String, f64> = mechanics
+            .state_ids()
+            .map(|id| {
+                let key = format!("{}{}", MAX_STACKS_PREFIX, id);
+                (id.clone(), self.mod_db.sum_base_with_ctx(&key, &self.eval_ctx))
+            })
+            .filter(|(_, bonus)| *bonus != 0.0)
+            .collect();
+        if bonuses.is_empty() {
+            return;
+        }
+        if let Some(mechanics) = self.mechanics.as_mut() {
+            mechanics.to_mut().apply_max_stack_bonuses(&bonuses);
+        }
+    }
+
+    /// 将授予/上限加成结算后的机制层数同步到条件评估上下文
+    fn refresh_mechanic_context(&mut self) {
+        if let Some(mechanics) = &self.mechanics {
+            for id in mechanics.state_ids() {
+                self.eval_ctx.values.remove(&format!("{}_stacks", id));
+            }
+            self.eval_ctx.values.extend(mechanics.get_all_stacks());
+            self.eval_ctx.mechanic_stacks = mechanics.get_stack_counts();
+            for tag in mechanics.active_tags() {
+                if !self.eval_ctx.tags.contains(&tag) {
+                    self.eval_ctx.tags.push(tag);
+                }
+            }
+        }
+    }
+
+    /// 按结算后的机制层数解析 `.per_<机制>` 属性
+    fn resolve_per_stack(&mut self) {
+        let pending = std::mem::take(&mut self.pending_per_stack);
+        let current_bucket = self.more_bucket;
+        for (key, value, source, bucket, condition) in pending {
+            let resolved = self
+                .mechanics
+                .as_ref()
+                .and_then(|mechanics| mechanics.calculate_per_stack_value(&key, value));
+            // 如果机制未激活或层数为0，跳过该属性
+            if let Some((base_key, total_value)) = resolved {
+                self.more_bucket = bucket;
+                self.affix_condition = condition;
+                if !self.affix_condition_deferred() {
+                    Self::apply_stat_to_pool(&mut self.pool, &base_key, total_value);
+                }
+                self.add_to_mod_db(&base_key, total_value, &source);
+            }
+        }
+        self.affix_condition = None;
+        self.more_bucket = current_bucket;
+    }
+    
+    /// 聚合上下文标志
+    ///
+    /// 将 `context_flags` 中的布尔效果转换为 Flag 修正，与装备/天赋提供的 Flag 统一查询
+    pub fn aggregate_context_flags(&mut self, flags: &HashMap<String, bool>) {
+        self.enter_bucket("context", 0);
+        for (flag, key) in CONTEXT_FLAG_ALIASES {
+            if flags.get(*flag).copied().unwrap_or(false) {
+                self.push_modifier(Modifier::flag(key, "context"));
+            }
+        }
+    }
+
+    /// 应用核心属性隐性加成
+    ///
+    /// 加成以 PerStat 形式写入，在 `finalize` 的第二遍按属性总值解析
+    pub fn apply_attribute_bonuses(&mut self, table: &AttributeTable) {
+        self.enter_bucket("attribute", 0);
+        for (key, value) in table.per_stat_entries() {
+            self.apply_stat(&key, value, "attribute");
+        }
+    }
+
+    /// 应用角色等级与职业推导的基础属性（生命、魔力、命中值）及职业天赋加成
+    pub fn aggregate_character(&mut self, character: &CharacterConfig, table: &CharacterTable) {
+        self.enter_bucket("character", 0);
+        for (key, value) in table.base_stats(character) {
+            self.apply_stat(key, value, "character");
+        }
+
+        let Some(class) = character.class.as_deref() else {
+            return;
+        };
+        let source = format!("class:{}", class);
+        for (key, value) in table.innate_stats(class).into_iter().flatten() {
+            let (key, value) = self.ingest_stat(key, *value, &source);
+            self.apply_stat(&key, value, &source);
+        }
+    }
+
     /// 获取属性池的可变引用（内部使用）
     pub fn pool_mut(&mut self) -> &mut StatPool {
         &mut self.pool
@@ -416,42 +1059,148 @@ impl<'a> StatAggregator<'a> {
 
     /// 聚合技能属性
     pub fn aggregate_skill(&mut self, skill: &SkillData) {
-        // 技能基础伤害
-        for (key, value) in &skill.base_damage {
-            self.pool.add_base(key, *value);
-            self.mod_db.add(Modifier::base(key, *value, &format!("skill:{}", skill.id)));
-        }
+        self.enter_bucket("skill", 0);
 
         // 技能自带属性
+        let source = format!("skill:{}", skill.id);
         for (key, value) in &skill.stats {
-            self.apply_stat(key, *value, &format!("skill:{}", skill.id));
+            let (key, value) = self.ingest_stat(key, *value, &source);
+            self.apply_stat(&key, value, &source);
+        }
+
+        // 技能品质：品质加成以 PerStat 形式按总品质解析，装备提供的品质同样生效
+        if skill.quality > 0 {
+            self.apply_stat(SKILL_QUALITY, skill.quality as f64, &source);
+        }
+        for (key, value) in &skill.quality_stats {
+            let (key, value) = self.ingest_stat(key, *value, &source);
+            self.apply_stat(&format!("{}.per_1_{}", key, SKILL_QUALITY), value, &source);
         }
     }
 
     /// 聚合辅助技能属性
     pub fn aggregate_support_skills(&mut self, supports: &[SkillData]) {
         for (idx, support) in supports.iter().enumerate() {
-            let bucket_id = (idx + 100) as u32; // 辅助技能使用 100+ 的 bucket
+            // 每个辅助技能独占一个 bucket（见 more_buckets.json 的 support 分类）
+            self.enter_bucket("support", idx);
             let source = format!("support:{}", support.id);
-            
-            for (key, value) in &support.stats {
-                if key.starts_with("mod.more.") {
-                    // 辅助技能的 More 使用独立 bucket
-                    let stripped_key = key.replace("mod.more.", "");
-                    self.pool.add_more(&stripped_key, *value, bucket_id, &support.id);
-                    // 同时添加到 ModDB（带 bucket）
-                    self.mod_db.add(Modifier::more_with_bucket(&stripped_key, *value, bucket_id, &source));
-                } else {
-                    self.apply_stat(key, *value, &source);
-                }
+            // 属性按辅助技能等级缩放
+            self.apply_instance_stats(&support.id, &support.scaled_stats(), 1.0, &source);
+        }
+    }
+
+    /// 聚合光环技能属性
+    ///
+    /// 属性数值乘以光环效果乘数（`aura.effect` 的增加总和），
+    /// 应在装备、天赋等可能提供光环效果的来源聚合之后调用
+    pub fn aggregate_auras(&mut self, auras: &[SkillData]) {
+        let effect = self.aura_effect_multiplier();
+        for (idx, aura) in auras.iter().enumerate() {
+            self.enter_bucket("aura", idx);
+            let source = format!("aura:{}", aura.id);
+            self.apply_instance_stats(&aura.id, &aura.scaled_stats(), effect, &source);
+        }
+    }
+
+    /// 光环效果乘数：1 + 光环效果提高（最低为 0）
+    fn aura_effect_multiplier(&self) -> f64 {
+        (1.0 + self.mod_db.sum_inc_with_ctx(AURA_EFFECT, &self.eval_ctx)).max(0.0)
+    }
+
+    /// 写入独占 bucket 的技能实例属性（辅助技能、光环），数值按 `scale` 缩放
+    fn apply_instance_stats(&mut self, owner: &str, stats: &HashMap<String, f64>, scale: f64, source: &str) {
+        let bucket_id = self.more_bucket.0;
+        for (key, value) in stats {
+            let (key, value) = self.ingest_stat(key, *value, source);
+            let key = key.as_ref();
+            let value = value * scale;
+            if key.starts_with("mod.more.") {
+                let stripped_key = key.replace("mod.more.", "");
+                self.pool.add_more(&stripped_key, value, bucket_id, owner);
+                // 同时添加到 ModDB（bucket 由 push_modifier 附加）
+                self.push_modifier(Modifier::more(&stripped_key, value, source));
+            } else {
+                self.apply_stat(key, value, source);
             }
         }
     }
 
+    /// 所有来源均未提供的自定义属性键按默认值计入（默认值为 0 的不产生修正）
+    pub fn apply_custom_stat_defaults(&mut self) {
+        let mut defaults: Vec<(&str, f64)> = CustomStatTable::shared()
+            .entries()
+            .filter(|(key, stat)| stat.default != 0.0 && !self.provided_custom_stats.iter().any(|k| k == key))
+            .map(|(key, stat)| (key, stat.default))
+            .collect();
+        defaults.sort_by(|a, b| a.0.cmp(b.0));
+        self.enter_bucket("global_override", 0);
+        for (key, value) in defaults {
+            self.apply_stat(key, value, &format!("custom_stat:{}", key));
+        }
+    }
+
     /// 聚合全局覆盖
-    pub fn aggregate_overrides(&mut self, overrides: &HashMap<String, f64>) {
+    ///
+    /// 表达式覆盖暂存，由 `apply_override_expressions` 在聚合完成后求值（无法解析的表达式忽略，由调用方预先校验）
+    pub fn aggregate_overrides(&mut self, overrides: &HashMap<String, OverrideValue>) {
+        self.aggregate_override_map(overrides, "global_override");
+    }
+
+    /// 聚合已启用的全局覆盖分组（来源记为 `override_group:<名称>`）
+    pub fn aggregate_override_groups(&mut self, groups: &[OverrideGroup]) {
+        for group in groups.iter().filter(|group| group.enabled) {
+            self.aggregate_override_map(&group.overrides, &format!("override_group:{}", group.name));
+        }
+    }
+
+    fn aggregate_override_map(&mut self, overrides: &HashMap<String, OverrideValue>, source: &str) {
+        self.enter_bucket("global_override", 0);
         for (key, value) in overrides {
-            self.apply_stat(key, *value, "global_override");
+            match value {
+                OverrideValue::Number(value) => {
+                    let (key, value) = self.ingest_stat(key, *value, source);
+                    self.apply_stat(&key, value, source);
+                }
+                OverrideValue::Expression(expr) => {
+                    if let Ok(expr) = ValueExpr::parse(expr) {
+                        self.pending_override_expressions.push((key.clone(), expr, source.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// 求值并应用全局覆盖表达式
+    ///
+    /// 须在 `apply_attribute_bonuses` 之后调用：标识符优先取条件评估上下文（机制层数、上下文数值），
+    /// 否则取当前聚合结果（含核心属性加成）的属性最终值；全部表达式基于同一快照求值，彼此不引用对方结果。
+    /// 结果是计算值而非录入值，只做别名映射，不按单位表换算
+    pub fn apply_override_expressions(&mut self) {
+        if self.pending_override_expressions.is_empty() {
+            return;
+        }
+        let expressions = std::mem::take(&mut self.pending_override_expressions);
+        let mut resolved = self.eval_ctx.clone();
+        self.mod_db.resolve_per_stat_values(&mut resolved);
+        let mut snapshot = self.eval_ctx.clone();
+        for (_, expr, _) in &expressions {
+            for key in expr.variables() {
+                if !snapshot.values.contains_key(key) {
+                    let value = self.mod_db.calculate_final_with_ctx(key, &resolved);
+                    snapshot.values.insert(key.to_string(), value);
+                }
+            }
+            for key in expr.stat_references() {
+                let value = self.mod_db.calculate_final_with_ctx(key, &resolved);
+                snapshot.stats.insert(key.to_string(), value);
+            }
+        }
+
+        self.enter_bucket("global_override", 0);
+        for (key, expr, source) in expressions {
+            let value = expr.evaluate(&snapshot);
+            let key = self.resolve_stat_alias(&key, &source).into_owned();
+            self.apply_stat(&key, value, &source);
         }
     }
 
@@ -461,60 +1210,130 @@ impl<'a> StatAggregator<'a> {
     /// 例如：玛格努斯的旧律的护甲 = 1777（基底）+ 2880~3456（暗金词缀）
     /// 如果有 "+X% 该装备护甲" 词缀，则：最终护甲 = (基底 + 词缀) * (1 + X%)
     pub fn finalize_local_stats(&mut self) {
-        // 1. 计算所有装备的局部防御属性并汇总
-        let mut total_armor: f64 = 0.0;
-        let mut total_es: f64 = 0.0;
-        let mut total_evasion: f64 = 0.0;
-        
-        for (_item_id, local_stats) in &self.item_local_pools {
-            // 合并基底和词缀属性，然后应用百分比加成
-            // final = (base + affix_flat) * (1 + percent)
-            let item_armor = (local_stats.base_armor + local_stats.affix_armor) 
-                * (1.0 + local_stats.armor_percent);
-            let item_es = (local_stats.base_es + local_stats.affix_es) 
-                * (1.0 + local_stats.es_percent);
-            let item_evasion = (local_stats.base_evasion + local_stats.affix_evasion) 
-                * (1.0 + local_stats.evasion_percent);
-            
-            total_armor += item_armor;
-            total_es += item_es;
-            total_evasion += item_evasion;
+        // 逐件结算（品质与局部加成只作用于所在装备），双持武器均摊后汇总
+        let mut totals: Vec<(String, f64)> = Vec::new();
+        let mut deferred = Vec::new();
+        for local in self.item_local_pools.values() {
+            let weight = self.local_weight(local);
+            let stats = local.finalized_stats();
+            if totals.is_empty() {
+                totals = stats.iter().map(|(key, _)| (key.clone(), 0.0)).collect();
+            }
+            for ((_, total), (_, value)) in totals.iter_mut().zip(&stats) {
+                *total += value * weight;
+            }
+
+            // 带延迟条件的局部属性：按单独加入该属性后的结算增量记录
+            for (key, value, source, condition) in &local.deferred {
+                let mut with_stat = local.clone();
+                with_stat.add_local_stat(key, *value);
+                for ((stat, before), (_, after)) in stats.iter().zip(with_stat.finalized_stats()) {
+                    if after != *before {
+                        deferred.push((stat.clone(), (after - before) * weight, source.clone(), condition.clone()));
+                    }
+                }
+            }
         }
-        
-        // 将装备提供的防御值添加到全局池
-        if total_armor > 0.0 {
-            self.pool.add_base("def.armor", total_armor);
+
+        // 局部结算结果写入全局（防御、武器伤害、武器暴击率；武器攻速仅攻击技能使用）
+        for (key, total) in totals {
+            if total > 0.0 {
+                self.add_local_base(&key, total);
+            }
         }
-        if total_es > 0.0 {
-            self.pool.add_base("base.es", total_es);
+        for (key, delta, source, condition) in deferred {
+            self.affix_condition = Some(condition);
+            self.push_modifier(Modifier::base(&key, delta, &source));
         }
-        if total_evasion > 0.0 {
-            self.pool.add_base("def.evasion", total_evasion);
+        self.affix_condition = None;
+    }
+
+    /// 各装备局部属性的合计
+    ///
+    /// 双持（两把及以上有攻速的武器）时轮流攻击，这些武器的局部属性取平均值；其余装备按原值累加
+    fn local_total(&self, value: impl Fn(&ItemLocalStats) -> f64) -> f64 {
+        self.item_local_pools.values().map(|local| value(local) * self.local_weight(local)).sum()
+    }
+
+    /// 该装备局部属性计入合计的权重（双持时攻击武器为 1 / 武器数，其余为 1）
+    fn local_weight(&self, local: &ItemLocalStats) -> f64 {
+        let attackers = self.item_local_pools.values().filter(|local| is_attack_weapon(local)).count();
+        if attackers > 1 && is_attack_weapon(local) {
+            1.0 / attackers as f64
+        } else {
+            1.0
         }
-        
-        // 2. 武器物理伤害计算
-        // final_phys = base_phys * (1 + local_inc)
-        let base_phys_min = self.local_pool.get_base("dmg.phys.min");
-        let base_phys_max = self.local_pool.get_base("dmg.phys.max");
-        let local_phys_inc = self.local_pool.get_increased("dmg.phys");
+    }
+
+    /// 写入局部结算后的装备属性（基础值与其他来源累加，StatPool 与 ModDB 保持一致）
+    fn add_local_base(&mut self, key: &str, value: f64) {
+        self.pool.add_base(key, value);
+        self.push_modifier(Modifier::base(key, value, "local"));
+    }
 
-        if base_phys_min > 0.0 || base_phys_max > 0.0 {
-            let final_phys_min = base_phys_min * (1.0 + local_phys_inc);
-            let final_phys_max = base_phys_max * (1.0 + local_phys_inc);
-            self.pool.set_base("dmg.phys.min", final_phys_min);
-            self.pool.set_base("dmg.phys.max", final_phys_max);
+    /// 第二遍：按第一遍聚合出的属性总值解析 PerStat 属性（StatPool 侧）
+    ///
+    /// ModDB 侧保留 PerStat 配置，查询时由 `EvalContext` 中的属性总值求值
+    fn resolve_per_stat(&mut self) {
+        let pending = std::mem::take(&mut self.pending_per_stat);
+
+        // 先统一取总值，避免 PerStat 属性之间互相影响
+        let totals: HashMap<String, f64> = pending
+            .iter()
+            .map(|(_, _, config)| (config.stat.clone(), self.pool.calculate_final(&config.stat)))
+            .collect();
+
+        for (key, value, config) in pending {
+            let multiplier = config.multiplier(totals[&config.stat]);
+            if multiplier > 0.0 {
+                Self::apply_stat_to_pool(&mut self.pool, &key, value * multiplier);
+            }
         }
+    }
 
-        // 3. 武器暴击率
-        let base_crit = self.local_pool.get_base("crit.chance.local");
-        if base_crit > 0.0 {
-            self.pool.add_base("crit.chance", base_crit);
+    /// 求值属性钩子（见 stat_hooks.json）：提供了触发键的钩子按来源属性最终值换算后写入目标属性
+    ///
+    /// 须在局部结算之前调用：武器局部目标（如 `dmg.phys.min`）写入各把攻击武器的局部属性池，
+    /// 与武器自身的伤害一同按局部增伤与品质结算，只作用于攻击；其余目标写入全局属性。
+    /// 属性值按条件评估上下文从 ModDB 读取（与表达式覆盖一致）；全部钩子基于同一快照求值，
+    /// 表达式中其余标识符优先取条件评估上下文，否则取属性最终值
+    fn apply_stat_hooks(&mut self) {
+        let mut resolved = self.eval_ctx.clone();
+        self.mod_db.resolve_per_stat_values(&mut resolved);
+        let final_value = |key: &str| self.mod_db.calculate_final_with_ctx(key, &resolved);
+        let mut snapshot = self.eval_ctx.clone();
+        let mut results = Vec::new();
+        for (id, hook) in StatHookTable::shared().hooks() {
+            let trigger = final_value(&hook.trigger);
+            if trigger == 0.0 {
+                continue;
+            }
+            for key in hook.transform.variables() {
+                if !snapshot.values.contains_key(key) {
+                    snapshot.values.insert(key.to_string(), final_value(key));
+                }
+            }
+            for key in hook.transform.stat_references() {
+                snapshot.stats.insert(key.to_string(), final_value(key));
+            }
+            snapshot.values.insert(HOOK_SOURCE_VAR.to_string(), final_value(&hook.source));
+            snapshot.values.insert(HOOK_TRIGGER_VAR.to_string(), trigger);
+            results.push((id, hook, hook.transform.evaluate(&snapshot)));
         }
 
-        // 4. 武器攻速（局部）
-        let local_speed = self.local_pool.get_base("speed.attack.local");
-        if local_speed > 0.0 {
-            self.pool.set_base("weapon.base_speed", local_speed);
+        self.enter_bucket("item", 0);
+        for (id, hook, value) in results {
+            let source = format!("hook:{}", id);
+            for target in &hook.targets {
+                if is_local_stat(target, true) {
+                    // 双持时各把武器均获得该伤害，局部合计取平均后即为该数值
+                    for local in self.item_local_pools.values_mut().filter(|local| is_attack_weapon(local)) {
+                        Self::apply_stat_to_pool(&mut local.pool, target, value);
+                    }
+                } else {
+                    self.apply_stat(target, value, &source);
+                }
+            }
         }
     }
 
@@ -524,112 +1343,383 @@ impl<'a> StatAggregator<'a> {
     /// - StatPool: 向后兼容的属性池
     /// - ModDB: 结构化修正存储（用于溯源和条件评估）
     pub fn finalize(mut self) -> (StatPool, ModDB) {
+        self.resolve_per_stack();
+        self.apply_stat_hooks();
         self.finalize_local_stats();
+        self.resolve_per_stat();
         self.pool.recalculate_all();
         (self.pool, self.mod_db)
     }
 
     /// 获取最终的属性池（仅 StatPool，向后兼容）
     pub fn finalize_pool_only(mut self) -> StatPool {
+        self.resolve_per_stack();
+        self.apply_stat_hooks();
         self.finalize_local_stats();
+        self.resolve_per_stat();
         self.pool.recalculate_all();
         self.pool
     }
 }
 
+/// 条件表达式是否引用聚合后的属性（`stat("...")`）
+fn references_stats(expr: &str) -> bool {
+    Condition::parse(expr).is_ok_and(|cond| !cond.stat_references().is_empty())
+}
+
+/// PerStat 键后缀：不取整，按比例生效
+pub const PER_STAT_PROPORTIONAL_SUFFIX: &str = ".proportional";
+
+/// PerStat 键后缀：倍数上限（`.max_50` 表示最多生效 50 次）
+pub const PER_STAT_MAX_PREFIX: &str = ".max_";
+
+/// 解析 PerStat 属性键
+///
+/// - `"mod.inc.dmg.spell.per_10_intelligence"` -> `Some(("mod.inc.dmg.spell", 每 10 点智力))`（向下取整、无上限）
+/// - `"mod.inc.dmg.spell.per_1_dexterity.proportional.max_50"` -> 按比例生效、最多 50 次（后缀顺序不限）
+/// - `"mod.inc.dmg.cold.per_focus_blessing"` -> `None`（机制层数键）
+/// - 倍数上限非正数时返回 `None`
+pub fn parse_per_stat_key(key: &str) -> Option<(&str, PerStatConfig)> {
+    let idx = key.find(".per_")?;
+    let (per, mut stat) = key[idx + 5..].split_once('_')?;
+    let per: f64 = per.parse().ok()?;
+    if per <= 0.0 {
+        return None;
+    }
+    let mut rounding = PerStatRounding::Floor;
+    let mut max_multiplier = None;
+    loop {
+        if let Some(rest) = stat.strip_suffix(PER_STAT_PROPORTIONAL_SUFFIX) {
+            rounding = PerStatRounding::Proportional;
+            stat = rest;
+        } else if let Some((rest, max)) = stat
+            .rsplit_once(PER_STAT_MAX_PREFIX)
+            .and_then(|(rest, max)| Some((rest, max.parse::<f64>().ok()?)))
+        {
+            if !is_valid_max_multiplier(max) {
+                return None;
+            }
+            max_multiplier.get_or_insert(max);
+            stat = rest;
+        } else {
+            break;
+        }
+    }
+    if stat.is_empty() {
+        return None;
+    }
+    let mut config = PerStatConfig::new(stat, per).with_rounding(rounding);
+    config.max_multiplier = max_multiplier;
+    Some((&key[..idx], config))
+}
+
+/// 上下文标志 → Flag 修正键
+pub const CONTEXT_FLAG_ALIASES: &[(&str, &str)] = &[
+    ("lucky_damage", "flag.lucky"),
+    ("cannot_crit", "flag.cannot_crit"),
+    ("always_hit", "flag.always_hit"),
+    ("cannot_evade", "flag.cannot_evade"),
+];
+
+/// 光环效果（`mod.inc.aura.effect` 缩放光环技能与祝福类增益的数值）
+pub const AURA_EFFECT: &str = "aura.effect";
+
+/// 机制授予前缀（`grant.mechanic.focus_blessing` = 4 表示获得 4 层聚能祝福）
+pub const GRANT_MECHANIC_PREFIX: &str = "grant.mechanic.";
+
+/// 机制层数上限加成前缀（`mod.max_stacks.focus_blessing` = 2 表示聚能祝福上限 +2）
+pub const MAX_STACKS_PREFIX: &str = "mod.max_stacks.";
+
+/// 伤害类型段（`dmg` / `res` / `weapon` / `def` / `skill` 键族共用）
+const DAMAGE_TYPE_SEGMENTS: &[&str] = &["phys", "fire", "cold", "lightning", "chaos", "elemental", "all"];
+
+/// 已知属性键族及其后续各段的词表
+///
+/// 键的每一段都须出现在所属键族的词表中（`*` 表示任意标识符，如机制 ID）；
+/// 核心属性与敌人侧键（`armor`、`generic_dr` 等）以裸键出现，词表为空
+const KNOWN_STAT_KEY_SEGMENTS: &[(&str, &[&str])] = &[
+    (
+        "dmg",
+        &[
+            "spell", "attack", "melee", "aoe", "projectile", "sword", "axe", "mace", "dagger", "claw", "wand", "staff",
+            "bow", "crossbow", "one_handed", "two_handed", "dual_wield", "dot", "min", "max", "added", "base",
+            "explosion", "local",
+        ],
+    ),
+    ("crit", &["chance", "dmg", "multiplier", "rating", "attack", "spell", "local"]),
+    ("speed", &["attack", "cast", "spell_burst_charge", "local"]),
+    (
+        "def",
+        &[
+            "armor", "evasion", "block", "spell_block", "damage_taken_reduction", "armor_applies", "gain",
+            "armor_as_evasion", "evasion_as_armor", "armor_es", "armor_evasion", "evasion_es", "local",
+        ],
+    ),
+    ("res", &[]),
+    ("skill", &["level", "quality", "attack", "spell", "melee", "projectile", "minion"]),
+    ("base", &["life", "mana", "es", "local"]),
+    ("flag", &["lucky", "cannot_crit", "always_hit", "cannot_evade", "talent", "*"]),
+    ("acc", &["chance", "rating"]),
+    ("hit", &["chance"]),
+    ("weapon", &["base_speed", "crit", "chance", "dmg", "min", "max"]),
+    ("mechanic", &["*"]),
+    ("mechanics", &["more", "dmg"]),
+    ("grant", &["mechanic", "*"]),
+    ("blessing", &["duration"]),
+    ("aura", &["effect"]),
+    ("memory", &["count"]),
+    ("strength", &[]),
+    ("dexterity", &[]),
+    ("intelligence", &[]),
+    ("armor", &[]),
+    ("generic_dr", &[]),
+    ("increased_damage_taken", &[]),
+];
+
+/// 除 `mod.inc.` / `mod.more.` / `mod.override.` 外的已知 `mod.*` 前缀（前缀之后的部分按属性键校验）
+const KNOWN_MOD_PREFIXES: &[&str] = &["mod.penetration."];
+
+/// 属性键是否为已知键（`mod.inc.` / `mod.more.` / `mod.override.` / `target.` 前缀按去掉后的键判断）
+///
+/// 自定义属性表（custom_stats.json）中声明的键、乘区表声明的乘区键与钩子表的触发键视为已知；
+/// 其余键的每一段须在所属键族的词表中，`conv.*` / `extra.*` 须为已收录的转化键（`.per_` 缩放后缀不参与判断）
+pub fn is_known_stat_key(key: &str) -> bool {
+    if CustomStatTable::shared().rewrite(key).is_some() {
+        return true;
+    }
+    let key = ["target.", "mod.inc.", "mod.more.", "mod.override."]
+        .iter()
+        .fold(key, |key, prefix| key.strip_prefix(prefix).unwrap_or(key));
+    let key = match key.find(".per_") {
+        // PerStat 键的后缀须可解析（倍数上限为正数）
+        Some(idx) if key[idx + 5..].starts_with(|c: char| c.is_ascii_digit()) && parse_per_stat_key(key).is_none() => {
+            return false;
+        }
+        Some(idx) => &key[..idx],
+        None => key,
+    };
+    if let Some(rest) = KNOWN_MOD_PREFIXES.iter().find_map(|prefix| key.strip_prefix(prefix)) {
+        return is_known_stat_key(rest);
+    }
+    if let Some(mechanic_id) = key.strip_prefix(MAX_STACKS_PREFIX) {
+        return !mechanic_id.is_empty();
+    }
+    let mut segments = key.split('.');
+    match segments.next().unwrap_or(key) {
+        "conv" | "extra" => is_conversion_key(key),
+        "zone" => MultiplierZoneTable::shared().is_zone_key(key),
+        "hook" => StatHookTable::shared().hooks().any(|(_, hook)| hook.trigger == key),
+        family => KNOWN_STAT_KEY_SEGMENTS
+            .iter()
+            .find(|(name, _)| *name == family)
+            .is_some_and(|(_, vocabulary)| {
+                let typed = matches!(family, "dmg" | "res" | "weapon" | "def" | "skill");
+                segments.all(|segment| {
+                    !segment.is_empty()
+                        && (typed && DAMAGE_TYPE_SEGMENTS.contains(&segment)
+                            || vocabulary.contains(&segment)
+                            || vocabulary.contains(&"*"))
+                })
+            }),
+    }
+}
+
+/// 严格模式下须为已知键的属性键（`mod.*` / `conv.*` / `extra.*`）
+pub fn is_strict_stat_key(key: &str) -> bool {
+    ["mod.", "conv.", "extra."].iter().any(|prefix| key.starts_with(prefix))
+}
+
+/// 技能品质（技能自身品质 + 装备的"+X% 技能品质"）
+pub const SKILL_QUALITY: &str = "skill.quality";
+
+/// 技能等级加成表
+///
+/// 属性键 → 技能标签要求（如"+1 火焰技能等级"只对带火焰标签的技能生效）
+pub const SKILL_LEVEL_KEYS: &[(&str, &[&str])] = &[
+    ("skill.level", &[]),
+    ("skill.level.attack", &["Tag_Attack"]),
+    ("skill.level.spell", &["Tag_Spell"]),
+    ("skill.level.melee", &["Tag_Melee"]),
+    ("skill.level.projectile", &["Tag_Projectile"]),
+    ("skill.level.phys", &["Tag_Physical"]),
+    ("skill.level.fire", &["Tag_Fire"]),
+    ("skill.level.cold", &["Tag_Cold"]),
+    ("skill.level.lightning", &["Tag_Lightning"]),
+    ("skill.level.chaos", &["Tag_Chaos"]),
+    ("skill.level.elemental", &["Tag_Elemental"]),
+    ("skill.level.minion", &["Tag_Minion"]),
+];
+
+/// 伤害修正作用域表
+///
+/// 属性键 → (命中标签, 技能标签要求)
+/// - 命中标签与伤害包的历史标签匹配（转化后的伤害仍享受源类型加成）
+/// - 技能标签要求与上下文标签匹配
+pub const DAMAGE_MOD_SCOPES: &[(&str, &[&str], &[&str])] = &[
+    ("dmg.all", &[], &[]),
+    ("dmg.phys", &["Tag_Physical"], &[]),
+    ("dmg.fire", &["Tag_Fire"], &[]),
+    ("dmg.cold", &["Tag_Cold"], &[]),
+    ("dmg.lightning", &["Tag_Lightning"], &[]),
+    ("dmg.chaos", &["Tag_Chaos"], &[]),
+    ("dmg.elemental", &["Tag_Elemental"], &[]),
+    ("dmg.spell", &[], &["Tag_Spell"]),
+    ("dmg.attack", &[], &["Tag_Attack"]),
+    ("dmg.melee", &[], &["Tag_Melee"]),
+    ("dmg.aoe", &[], &["Tag_AOE"]),
+    ("dmg.projectile", &[], &["Tag_Projectile"]),
+    ("dmg.sword", &[], &["Tag_Sword"]),
+    ("dmg.axe", &[], &["Tag_Axe"]),
+    ("dmg.mace", &[], &["Tag_Mace"]),
+    ("dmg.dagger", &[], &["Tag_Dagger"]),
+    ("dmg.claw", &[], &["Tag_Claw"]),
+    ("dmg.wand", &[], &["Tag_Wand"]),
+    ("dmg.staff", &[], &["Tag_Staff"]),
+    ("dmg.bow", &[], &["Tag_Bow"]),
+    ("dmg.crossbow", &[], &["Tag_Crossbow"]),
+    ("dmg.one_handed", &[], &["Tag_OneHanded"]),
+    ("dmg.two_handed", &[], &["Tag_TwoHanded"]),
+    ("dmg.dual_wield", &[], &["Tag_DualWield"]),
+];
+
+/// 伤害区间拉伸作用域表（如"世事无常"）
+///
+/// (最小值键, 最大值键, 命中标签)，More 修正按伤害包的历史标签匹配，
+/// 对所有伤害类型一视同仁
+pub const DAMAGE_STRETCH_SCOPES: &[(&str, &str, &[&str])] = &[
+    ("dmg.min", "dmg.max", &[]),
+    ("dmg.phys.min", "dmg.phys.max", &["Tag_Physical"]),
+    ("dmg.fire.min", "dmg.fire.max", &["Tag_Fire"]),
+    ("dmg.cold.min", "dmg.cold.max", &["Tag_Cold"]),
+    ("dmg.lightning.min", "dmg.lightning.max", &["Tag_Lightning"]),
+    ("dmg.chaos.min", "dmg.chaos.max", &["Tag_Chaos"]),
+];
+
+/// 查询伤害修正的作用域
+fn damage_mod_scope(key: &str) -> Option<(&'static [&'static str], &'static [&'static str])> {
+    DAMAGE_MOD_SCOPES
+        .iter()
+        .find(|(k, _, _)| *k == key)
+        .map(|(_, hit, req)| (*hit, *req))
+        .or_else(|| {
+            DAMAGE_STRETCH_SCOPES
+                .iter()
+                .find(|(min, max, _)| *min == key || *max == key)
+                .map(|(_, _, hit)| (*hit, &[][..]))
+        })
+}
+
+/// 武器局部平面伤害类型: (类型, 是否为元素伤害)
+const WEAPON_LOCAL_DAMAGE: &[(&str, bool)] = &[
+    ("fire", true),
+    ("cold", true),
+    ("lightning", true),
+    ("chaos", false),
+];
+
+/// 局部平面伤害（已应用该类型与元素的局部增伤）
+fn local_flat_damage(pool: &StatPool, element: &str) -> (f64, f64) {
+    let elemental = WEAPON_LOCAL_DAMAGE.iter().any(|(e, is_elemental)| *e == element && *is_elemental);
+    let mut inc = pool.get_increased(&format!("dmg.{}", element));
+    if elemental {
+        inc += pool.get_increased("dmg.elemental");
+    }
+    let scale = 1.0 + inc;
+    (
+        pool.get_base(&format!("dmg.{}.min", element)) * scale,
+        pool.get_base(&format!("dmg.{}.max", element)) * scale,
+    )
+}
+
+/// 局部物理伤害 = 基础物理 × (1 + 局部物理增伤 + 武器品质)
+fn local_phys_damage(pool: &StatPool, quality: f64) -> (f64, f64) {
+    let scale = 1.0 + pool.get_increased("dmg.phys") + quality;
+    (pool.get_base("dmg.phys.min") * scale, pool.get_base("dmg.phys.max") * scale)
+}
+
+/// 是否为可攻击的武器（局部攻速大于 0）
+fn is_attack_weapon(local: &ItemLocalStats) -> bool {
+    local.is_weapon && local_final(&local.pool, "speed.attack.local") > 0.0
+}
+
+/// 局部属性最终值 = 局部基础值 × (1 + 局部增加)
+fn local_final(pool: &StatPool, key: &str) -> f64 {
+    pool.get_base(key) * (1.0 + pool.get_increased(key))
+}
+
+/// 由单把武器的局部属性池计算面板 DPS
+///
+/// - 物理伤害 = 基础物理 × (1 + 局部物理增伤 + 品质)
+/// - 元素伤害 = 局部平面元素 × (1 + 局部该类型增伤 + 局部元素增伤)
+/// - 攻速 / 暴击率 = 局部基础值 × (1 + 局部增加)
+fn weapon_summary(item: &ItemData, pool: &StatPool, quality: f64) -> WeaponSummary {
+    let (min, max) = local_phys_damage(pool, quality);
+    let phys_range = DamageRange { min, max };
+    let elemental_average: f64 = WEAPON_LOCAL_DAMAGE
+        .iter()
+        .filter(|(_, elemental)| *elemental)
+        .map(|(element, _)| {
+            let (min, max) = local_flat_damage(pool, element);
+            (min + max) / 2.0
+        })
+        .sum();
+    let attack_speed = local_final(pool, "speed.attack.local");
+    let phys_dps = (phys_range.min + phys_range.max) / 2.0 * attack_speed;
+    let elemental_dps = elemental_average * attack_speed;
+
+    WeaponSummary {
+        item_id: item.id.clone(),
+        slot: item.slot.clone(),
+        phys_range,
+        elemental_average,
+        attack_speed,
+        crit_chance: local_final(pool, "crit.chance.local"),
+        phys_dps,
+        elemental_dps,
+        total_dps: phys_dps + elemental_dps,
+    }
+}
+
 /// 判断是否为局部属性
-fn is_local_stat(key: &str) -> bool {
+///
+/// 武器上的平面元素/腐蚀伤害（`dmg.<类型>.min/max`）同样为局部属性；其他装备上为全局平面伤害
+fn is_local_stat(key: &str, is_weapon: bool) -> bool {
     key.ends_with(".local") || 
     key.starts_with("dmg.phys.") && !key.contains("mod.") ||
     key == "crit.chance.local" ||
-    key == "speed.attack.local"
+    key == "speed.attack.local" ||
+    is_weapon && is_weapon_flat_damage(key)
+}
+
+/// 是否为武器局部平面元素/腐蚀伤害键（`dmg.<类型>.min/max`）
+fn is_weapon_flat_damage(key: &str) -> bool {
+    WEAPON_LOCAL_DAMAGE.iter().any(|(element, _)| {
+        key.strip_prefix("dmg.")
+            .and_then(|rest| rest.strip_prefix(element))
+            .is_some_and(|bound| bound == ".min" || bound == ".max")
+    })
 }
 
-/// 条件表达式解析器
+/// 条件表达式解析器（简易接口，解析与评估委托给 `Condition` AST）
 pub struct ConditionParser;
 
 impl ConditionParser {
-    /// 解析并评估条件表达式
-    /// 支持简单的比较: "life_percent <= 0.35", "is_moving == true"
+    /// 解析并评估条件表达式（解析失败视为不满足）
+    /// 支持的格式见 `Condition::parse`，如 "life_percent <= 0.35", "is_moving == true"
     pub fn evaluate(condition: &str, context_flags: &HashMap<String, bool>, context_values: &HashMap<String, f64>) -> bool {
-        let condition = condition.trim();
-        
-        // 尝试解析布尔条件: "key == true/false"
-        if let Some((key, expected)) = Self::parse_bool_condition(condition) {
-            return context_flags.get(&key).copied().unwrap_or(false) == expected;
-        }
-        
-        // 尝试解析数值条件: "key <= value"
-        if let Some((key, op, threshold)) = Self::parse_numeric_condition(condition) {
-            let actual = context_values.get(&key).copied().unwrap_or(0.0);
-            return match op.as_str() {
-                "<=" => actual <= threshold,
-                ">=" => actual >= threshold,
-                "<" => actual < threshold,
-                ">" => actual > threshold,
-                "==" => (actual - threshold).abs() < f64::EPSILON,
-                "!=" => (actual - threshold).abs() >= f64::EPSILON,
-                _ => false,
-            };
-        }
-        
-        // 尝试解析字符串条件: "key == 'value'"
-        if let Some((key, expected)) = Self::parse_string_condition(condition) {
-            // 字符串条件暂时用 context_flags 模拟
-            let flag_key = format!("{}_{}", key, expected);
-            return context_flags.get(&flag_key).copied().unwrap_or(false);
-        }
-        
-        // 默认为假
-        false
+        Self::evaluate_with_strings(condition, context_flags, context_values, &HashMap::new())
     }
 
-    fn parse_bool_condition(condition: &str) -> Option<(String, bool)> {
-        let parts: Vec<&str> = condition.split("==").collect();
-        if parts.len() != 2 {
-            return None;
-        }
-        
-        let key = parts[0].trim().to_string();
-        let value = parts[1].trim();
-        
-        match value {
-            "true" => Some((key, true)),
-            "false" => Some((key, false)),
-            _ => None,
-        }
-    }
-
-    fn parse_numeric_condition(condition: &str) -> Option<(String, String, f64)> {
-        let operators = ["<=", ">=", "!=", "==", "<", ">"];
-        
-        for op in operators {
-            if condition.contains(op) {
-                let parts: Vec<&str> = condition.split(op).collect();
-                if parts.len() != 2 {
-                    continue;
-                }
-                
-                let key = parts[0].trim().to_string();
-                if let Ok(value) = parts[1].trim().parse::<f64>() {
-                    return Some((key, op.to_string(), value));
-                }
-            }
-        }
-        
-        None
-    }
-
-    fn parse_string_condition(condition: &str) -> Option<(String, String)> {
-        let parts: Vec<&str> = condition.split("==").collect();
-        if parts.len() != 2 {
-            return None;
-        }
-        
-        let key = parts[0].trim().to_string();
-        let value = parts[1].trim().trim_matches('\'').trim_matches('"').to_string();
-        
-        Some((key, value))
+    /// 带字符串上下文评估条件表达式（支持 `weapon_type == "claw"`）
+    pub fn evaluate_with_strings(
+        condition: &str,
+        context_flags: &HashMap<String, bool>,
+        context_values: &HashMap<String, f64>,
+        context_strings: &HashMap<String, String>,
+    ) -> bool {
+        let mut ctx = EvalContext::from_context(context_flags, context_values);
+        ctx.strings = context_strings.clone();
+        Condition::parse(condition).is_ok_and(|cond| cond.evaluate(&ctx))
     }
 }
 
@@ -671,6 +1761,11 @@ mod tests {
         // 数值条件
         assert!(ConditionParser::evaluate("life_percent <= 0.35", &flags, &values));
         assert!(!ConditionParser::evaluate("life_percent >= 0.5", &flags, &values));
+
+        // 字符串条件按字符串上下文比较，不再拼接为标志键
+        let strings = HashMap::from([("weapon_type".to_string(), "claw".to_string())]);
+        assert!(ConditionParser::evaluate_with_strings("weapon_type == \"claw\"", &flags, &values, &strings));
+        assert!(!ConditionParser::evaluate("enemy_range == 'near'", &flags, &values));
     }
     
     #[test]
@@ -735,5 +1830,328 @@ mod tests {
         
         assert!((final_es - 490.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_mod_db_enforces_requirements() {
+        let mut registry = crate::tags::TagRegistry::new();
+        registry.register("Tag_Spell".to_string(), 1);
+        registry.register("Tag_Attack".to_string(), 2);
+        registry.precompute_expanded_sets();
+        let mut context = ContextTags::new(registry);
+        context.inject_skill_tags(&["Tag_Spell".to_string()]);
+
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.aggregate_overrides(&HashMap::from([
+            ("mod.inc.dmg.spell".to_string(), 0.3.into()),
+            ("mod.inc.dmg.attack".to_string(), 0.5.into()),
+            ("mod.inc.dmg.all".to_string(), 0.2.into()),
+        ]));
+        let (_, mod_db) = aggregator.finalize();
+
+        assert!((mod_db.sum_inc("dmg.spell") - 0.3).abs() < 1e-6);
+        assert_eq!(mod_db.sum_inc("dmg.attack"), 0.0);
+        assert!((mod_db.sum_inc("dmg.all") - 0.2).abs() < 1e-6);
+
+        // 修正自带的标签要求与作用域表合并：要求攻击标签的法术增伤不生效
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.push_modifier(Modifier::inc("dmg.spell", 0.4, "test").with_requirements(vec![2]));
+        aggregator.push_modifier(Modifier::inc("dmg.spell", 0.1, "test").with_requirements(vec![1]));
+        let (_, mod_db) = aggregator.finalize();
+        let spell = mod_db.get("dmg.spell");
+        assert_eq!(spell.len(), 1);
+        assert_eq!(spell[0].requirements, vec![1]);
+    }
+
+    #[test]
+    fn test_item_set_bonuses_by_equipped_count() {
+        let context = ContextTags::new(crate::tags::TagRegistry::new());
+        let item = |id: &str, set_id: Option<&str>| -> ItemData {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "base_type": "ring", "slot": "ring1", "set_id": set_id,
+            }))
+            .unwrap()
+        };
+        let sets = vec![ItemSetDefinition {
+            id: "ember".to_string(),
+            display_name: "余烬".to_string(),
+            bonuses: vec![
+                SetBonus { pieces: 2, stats: HashMap::from([("mod.inc.dmg.fire".to_string(), 0.2)]) },
+                SetBonus { pieces: 3, stats: HashMap::from([("mod.inc.dmg.fire".to_string(), 0.3)]) },
+            ],
+        }];
+
+        // 2 件：仅 2 件档生效，来源为 set:<套装 ID>
+        let items = vec![item("a", Some("ember")), item("b", Some("ember")), item("c", None)];
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.aggregate_items(&items);
+        aggregator.aggregate_item_sets(&items, &sets);
+        let (_, mod_db) = aggregator.finalize();
+        assert!((mod_db.sum_inc("dmg.fire") - 0.2).abs() < 1e-9);
+        assert!(mod_db.get("dmg.fire").iter().all(|m| m.source == "set:ember"));
+
+        // 3 件：各档累加
+        let items = vec![item("a", Some("ember")), item("b", Some("ember")), item("c", Some("ember"))];
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.aggregate_item_sets(&items, &sets);
+        let (_, mod_db) = aggregator.finalize();
+        assert!((mod_db.sum_inc("dmg.fire") - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_item_quality_scales_local_stats() {
+        let context = ContextTags::new(crate::tags::TagRegistry::new());
+        let item = |slot: &str, stats: serde_json::Value, quality: u32| -> ItemData {
+            serde_json::from_value(serde_json::json!({
+                "id": slot, "base_type": slot, "slot": slot, "base_implicit_stats": stats, "quality": quality,
+            }))
+            .unwrap()
+        };
+        let pool = |items: &[ItemData]| {
+            let mut aggregator = StatAggregator::new(&context);
+            aggregator.aggregate_items(items);
+            aggregator.finalize_pool_only()
+        };
+
+        let plain = pool(&[
+            item("chest", serde_json::json!({ "def.armor": 500.0 }), 0),
+            item("weapon_main", serde_json::json!({ "dmg.phys.min": 10.0, "dmg.phys.max": 20.0 }), 0),
+        ]);
+        let quality = pool(&[
+            item("chest", serde_json::json!({ "def.armor": 500.0 }), 20),
+            item("weapon_main", serde_json::json!({ "dmg.phys.min": 10.0, "dmg.phys.max": 20.0 }), 20),
+        ]);
+
+        assert!((plain.get_base("def.armor") - 500.0).abs() < 1e-9);
+        assert!((quality.get_base("def.armor") - 600.0).abs() < 1e-9);
+        assert!((quality.get_base("weapon.dmg.phys.min") - 12.0).abs() < 1e-9);
+        assert!((quality.get_base("weapon.dmg.phys.max") - 24.0).abs() < 1e-9);
+        // 非武器的品质不影响武器伤害
+        let armor_only = pool(&[
+            item("chest", serde_json::json!({ "def.armor": 500.0 }), 20),
+            item("weapon_main", serde_json::json!({ "dmg.phys.min": 10.0, "dmg.phys.max": 20.0 }), 0),
+        ]);
+        assert!((armor_only.get_base("weapon.dmg.phys.min") - 10.0).abs() < 1e-9);
+        // 双持时品质逐把计算，不累加到另一把武器上
+        let dual = pool(&[
+            item("weapon_main", serde_json::json!({ "dmg.phys.min": 10.0, "dmg.phys.max": 20.0 }), 20),
+            item("weapon_off", serde_json::json!({ "dmg.phys.min": 10.0, "dmg.phys.max": 20.0 }), 0),
+        ]);
+        assert!((dual.get_base("weapon.dmg.phys.min") - 22.0).abs() < 1e-9);
+        assert!((dual.get_base("weapon.dmg.phys.max") - 44.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_weapon_summaries_use_own_local_stats() {
+        let context = ContextTags::new(crate::tags::TagRegistry::new());
+        let items: Vec<ItemData> = serde_json::from_value(serde_json::json!([
+            {
+                "id": "sword", "base_type": "sword", "slot": "weapon_main", "quality": 20,
+                "base_implicit_stats": { "dmg.phys.min": 10.0, "dmg.phys.max": 20.0, "speed.attack.local": 1.5 },
+                "affixes": [{
+                    "id": "heavy", "group": "local_phys", "value": 0.5, "is_local": true,
+                    "stats": { "mod.inc.dmg.phys": 0.5 },
+                }],
+            },
+            {
+                "id": "dagger", "base_type": "dagger", "slot": "weapon_off",
+                "base_implicit_stats": { "speed.attack.local": 1.0, "crit.chance.local": 0.07 },
+                "affixes": [{
+                    "id": "fire", "group": "local_fire", "value": 5.0, "is_local": true,
+                    "stats": { "dmg.fire.min": 5.0, "dmg.fire.max": 15.0 },
+                }],
+            },
+            { "id": "chest", "base_type": "chest", "slot": "chest", "base_implicit_stats": { "def.armor": 100.0 } },
+        ]))
+        .unwrap();
+
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.aggregate_items(&items);
+        let summaries = aggregator.weapon_summaries();
+        assert_eq!(summaries.len(), 2);
+
+        // 主手：(10~20) × (1 + 50% + 20% 品质) × 1.5 次/秒，副手的伤害不计入
+        let sword = &summaries[0];
+        assert_eq!(sword.item_id, "sword");
+        assert!((sword.phys_range.min - 17.0).abs() < 1e-9);
+        assert!((sword.phys_dps - 38.25).abs() < 1e-9);
+        assert_eq!(sword.elemental_dps, 0.0);
+
+        let dagger = &summaries[1];
+        assert_eq!(dagger.slot, SlotType::WeaponOff);
+        assert!((dagger.elemental_dps - 10.0).abs() < 1e-9);
+        assert!((dagger.crit_chance - 0.07).abs() < 1e-9);
+        assert!((dagger.total_dps - 10.0).abs() < 1e-9);
+
+        // 双持轮流攻击：攻速、暴击率与伤害取两把武器的平均值，而非相加
+        assert!((aggregator.local_attack_speed() - 1.25).abs() < 1e-9);
+        let pool = aggregator.finalize_pool_only();
+        assert!((pool.get_base("weapon.base_speed") - 1.25).abs() < 1e-9);
+        assert!((pool.get_base("weapon.crit.chance") - 0.035).abs() < 1e-9);
+        assert!((pool.get_base("weapon.dmg.phys.min") - 8.5).abs() < 1e-9);
+        assert!((pool.get_base("weapon.dmg.fire.min") - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_weapon_local_elemental_damage() {
+        let context = ContextTags::new(crate::tags::TagRegistry::new());
+        let items: Vec<ItemData> = serde_json::from_value(serde_json::json!([
+            {
+                "id": "staff", "base_type": "staff", "slot": "weapon_main",
+                "base_implicit_stats": { "dmg.lightning.min": 2.0, "dmg.lightning.max": 4.0 },
+                "affixes": [
+                    {
+                        "id": "flat", "group": "local_flat", "value": 10.0, "is_local": true,
+                        "stats": { "dmg.fire.min": 10.0, "dmg.fire.max": 20.0, "dmg.chaos.min": 4.0, "dmg.chaos.max": 8.0 },
+                    },
+                    {
+                        "id": "inc", "group": "local_inc", "value": 0.5, "is_local": true,
+                        "stats": { "mod.inc.dmg.elemental": 0.5, "mod.inc.dmg.fire": 0.25 },
+                    },
+                ],
+            },
+            { "id": "ring", "base_type": "ring", "slot": "ring1", "implicit_stats": { "dmg.cold.min": 3.0, "dmg.cold.max": 6.0 } },
+        ]))
+        .unwrap();
+
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.aggregate_items(&items);
+        let pool = aggregator.finalize_pool_only();
+
+        // 火焰享受元素与火焰局部增伤，腐蚀不受元素增伤影响
+        assert!((pool.get_base("weapon.dmg.fire.min") - 17.5).abs() < 1e-9);
+        assert!((pool.get_base("weapon.dmg.fire.max") - 35.0).abs() < 1e-9);
+        assert!((pool.get_base("weapon.dmg.chaos.max") - 8.0).abs() < 1e-9);
+        // 武器基底的平面元素伤害（未标记 is_local）同样为局部属性
+        assert!((pool.get_base("weapon.dmg.lightning.min") - 3.0).abs() < 1e-9);
+        assert_eq!(pool.get_base("dmg.lightning.min"), 0.0);
+        // 非武器的全局附加伤害不计入武器伤害
+        assert_eq!(pool.get_base("weapon.dmg.cold.min"), 0.0);
+        assert!((pool.get_base("dmg.cold.min") - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_hybrid_local_defense_percent() {
+        let context = ContextTags::new(crate::tags::TagRegistry::new());
+        let item: ItemData = serde_json::from_value(serde_json::json!({
+            "id": "chest", "base_type": "chest", "slot": "chest",
+            "base_implicit_stats": { "def.armor": 400.0, "base.es": 100.0, "def.evasion": 200.0 },
+            "affixes": [{
+                "id": "hybrid", "group": "local_def", "value": 0.5,
+                "stats": { "mod.inc.def.armor_es.local": 0.5, "mod.inc.def.evasion.local": 0.1 },
+            }],
+        }))
+        .unwrap();
+
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.aggregate_items(&[item]);
+        let pool = aggregator.finalize_pool_only();
+
+        // 混合词缀同时提高该装备的护甲与护盾，不影响闪避
+        assert!((pool.get_base("def.armor") - 600.0).abs() < 1e-9);
+        assert!((pool.get_base("base.es") - 150.0).abs() < 1e-9);
+        assert!((pool.get_base("def.evasion") - 220.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_per_stat_key() {
+        assert_eq!(
+            parse_per_stat_key("mod.inc.dmg.spell.per_10_intelligence"),
+            Some(("mod.inc.dmg.spell", PerStatConfig::new("intelligence", 10.0)))
+        );
+        let proportional = PerStatConfig::new("dexterity", 1.0).with_rounding(PerStatRounding::Proportional);
+        for key in [
+            "mod.inc.dmg.spell.per_1_dexterity.proportional.max_50",
+            "mod.inc.dmg.spell.per_1_dexterity.max_50.proportional",
+        ] {
+            assert_eq!(
+                parse_per_stat_key(key),
+                Some(("mod.inc.dmg.spell", proportional.clone().with_max_multiplier(50.0))),
+                "{key}"
+            );
+        }
+        assert_eq!(
+            parse_per_stat_key("base.life.per_2_strength.max_12.5"),
+            Some(("base.life", PerStatConfig::new("strength", 2.0).with_max_multiplier(12.5)))
+        );
+        // 非正数上限不可解析，同时视为未知属性键
+        for key in ["mod.inc.dmg.spell.per_1_dexterity.max_0", "mod.inc.dmg.spell.per_1_dexterity.max_-5"] {
+            assert_eq!(parse_per_stat_key(key), None, "{key}");
+            assert!(!is_known_stat_key(key), "{key}");
+        }
+        assert!(is_known_stat_key("mod.inc.dmg.spell.per_1_dexterity.proportional.max_50"));
+        assert_eq!(parse_per_stat_key("mod.inc.dmg.cold.per_focus_blessing"), None);
+        assert_eq!(parse_per_stat_key("mod.inc.dmg.cold"), None);
+    }
+
+    #[test]
+    fn test_known_stat_keys() {
+        for key in [
+            "mod.inc.dmg.fire",
+            "mod.inc.dmg.dot",
+            "skill.level.fire",
+            "target.res.fire",
+            "strength",
+            "base.life.per_2_strength",
+            "conv.phys_to_fire",
+            "dmg.fire.added.min",
+            "weapon.dmg.cold.max",
+            "mod.penetration.res.all",
+            "mod.max_stacks.focus_blessing",
+            "grant.mechanic.focus_blessing",
+            "zone.final_damage.custom",
+            "hook.mana_to_weapon_damage",
+            "mod.custom.dmg.ailment.per_10_strength",
+        ] {
+            assert!(is_known_stat_key(key), "{}", key);
+        }
+        for key in [
+            "mod.inc.dmgg.fire",
+            "fire_damage",
+            "target.foo",
+            "mod.incc.dmg.fire",
+            "conv.fire_to_cold",
+            "mod.inc.dmg.fiire",
+            "crit.chanse",
+            "res.fire.extra",
+            "strength.bonus",
+            "zone.unknown",
+            "hook.unknown",
+            "mod.penetration.res.fier",
+        ] {
+            assert!(!is_known_stat_key(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_custom_stat_provided_by_expression() {
+        let context = ContextTags::new(crate::tags::TagRegistry::new());
+        let mut aggregator = StatAggregator::new(&context);
+        aggregator.aggregate_overrides(&HashMap::from([
+            ("strength".to_string(), 100.0.into()),
+            (
+                "mod.custom.dmg.ailment".to_string(),
+                OverrideValue::Expression("strength * 0.001".to_string()),
+            ),
+        ]));
+        assert!(aggregator.provided_custom_stats.is_empty());
+
+        // 仅由表达式提供的自定义键同样视为已提供（不再叠加默认值）
+        aggregator.apply_override_expressions();
+        assert_eq!(aggregator.provided_custom_stats, vec!["mod.custom.dmg.ailment".to_string()]);
+        let (_, mod_db) = aggregator.finalize();
+        assert!((mod_db.sum_inc("dmg.all") - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stat_pool_override() {
+        let mut pool = StatPool::new();
+        pool.add_base("crit.chance", 0.05);
+        pool.add_increased("crit.chance", 1.0);
+        pool.set_override("crit.chance", 0.09);
+
+        assert!((pool.calculate_final("crit.chance") - 0.09).abs() < 1e-6);
+        // 基础值保持不变
+        assert!((pool.get_base("crit.chance") - 0.05).abs() < 1e-6);
+    }
 }
 
@@ -5,9 +5,14 @@
 //! - 继承展开
 //! - BitSet 集合运算
 
+use crate::condition_ast::{Condition, EvalContext};
+use crate::types::{CalcPhase, ConditionalTag, DataVersion, ItemData, PhaseTag, SlotType};
+use crate::utils::fnv1a_64;
 use fixedbitset::FixedBitSet;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 
 /// 标签注册表
 #[derive(Debug, Clone, Default)]
@@ -16,12 +21,47 @@ pub struct TagRegistry {
     name_to_id: HashMap<String, u32>,
     /// ID 到标签名的映射
     id_to_name: HashMap<u32, String>,
+    /// 别名到 ID 的映射（数据更新中改名的旧标签名）
+    aliases: HashMap<String, u32>,
     /// 标签继承关系（ID -> 父级 ID 列表）
     inheritance: HashMap<u32, Vec<u32>>,
     /// 预计算的展开集（包含自身和所有祖先）
     expanded_sets: HashMap<u32, FixedBitSet>,
+    /// 上下文派生规则（标签 ID, 条件），由上下文数值/标志自动激活
+    derived: Vec<(u32, Condition)>,
     /// 最大标签 ID
     max_id: u32,
+    /// 数据版本（`_meta.version`）
+    version: String,
+    /// 源数据校验和
+    checksum: u64,
+    /// 加载数据时产生的诊断信息（别名目标缺失、派生条件无法解析）
+    diagnostics: Vec<TagDiagnostic>,
+}
+
+/// 标签查询诊断
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagDiagnostic {
+    /// 使用了已弃用的别名（仍解析到新标签）
+    DeprecatedAlias { alias: String, target: String },
+    /// 未注册的标签名
+    UnknownTag { name: String },
+    /// 标签的派生条件无法解析
+    InvalidCondition { tag: String, error: String },
+}
+
+impl fmt::Display for TagDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagDiagnostic::DeprecatedAlias { alias, target } => {
+                write!(f, "tag '{}' is deprecated, use '{}'", alias, target)
+            }
+            TagDiagnostic::UnknownTag { name } => write!(f, "unknown tag '{}'", name),
+            TagDiagnostic::InvalidCondition { tag, error } => {
+                write!(f, "invalid condition on tag '{}': {}", tag, error)
+            }
+        }
+    }
 }
 
 /// 标签定义（用于从 JSON 加载）
@@ -51,6 +91,13 @@ impl TagRegistry {
             .map_err(|e| format!("Failed to parse JSON: {}", e))?;
         
         let mut registry = Self::new();
+        registry.checksum = fnv1a_64(json.as_bytes());
+        registry.version = raw
+            .get("_meta")
+            .and_then(|meta| meta.get("version"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
         let mut parent_refs: HashMap<String, Vec<String>> = HashMap::new();
         
         // 第一遍：注册所有标签
@@ -65,15 +112,30 @@ impl TagRegistry {
             
             registry.register(key.clone(), def.id);
             parent_refs.insert(key.clone(), def.parents);
+
+            if let Some(condition) = &def.condition {
+                match Condition::parse(condition) {
+                    Ok(condition) => registry.set_condition(def.id, condition),
+                    Err(error) => registry.diagnostics.push(TagDiagnostic::InvalidCondition { tag: key.clone(), error: error.to_string() }),
+                }
+            }
+        }
+
+        // 别名表：旧标签名 -> 新标签名
+        if let Some(aliases) = raw.get("_aliases") {
+            let aliases: HashMap<String, String> = serde_json::from_value(aliases.clone())
+                .map_err(|e| format!("Failed to parse tag aliases: {}", e))?;
+            for (alias, target) in aliases {
+                registry.register_alias(alias, &target);
+            }
         }
         
-        // 第二遍：建立继承关系
+        // 第二遍：建立继承关系（父级引用同样可使用别名）
         for (tag_name, parents) in parent_refs {
             if let Some(&tag_id) = registry.name_to_id.get(&tag_name) {
                 let parent_ids: Vec<u32> = parents
                     .iter()
-                    .filter_map(|p| registry.name_to_id.get(p))
-                    .copied()
+                    .filter_map(|p| registry.get_id(p))
                     .collect();
                 registry.inheritance.insert(tag_id, parent_ids);
             }
@@ -92,6 +154,27 @@ impl TagRegistry {
         self.max_id = self.max_id.max(id);
     }
 
+    /// 注册别名（目标标签须已注册，否则记录诊断）
+    pub fn register_alias(&mut self, alias: String, target: &str) {
+        match self.name_to_id.get(target) {
+            Some(&id) => {
+                self.aliases.insert(alias, id);
+            }
+            None => self.diagnostics.push(TagDiagnostic::UnknownTag { name: target.to_string() }),
+        }
+    }
+
+    /// 设置标签的上下文派生条件
+    pub fn set_condition(&mut self, tag_id: u32, condition: Condition) {
+        self.derived.retain(|(id, _)| *id != tag_id);
+        self.derived.push((tag_id, condition));
+    }
+
+    /// 上下文派生规则
+    pub fn derived_tags(&self) -> &[(u32, Condition)] {
+        &self.derived
+    }
+
     /// 设置标签继承关系
     pub fn set_parents(&mut self, tag_id: u32, parent_ids: Vec<u32>) {
         self.inheritance.insert(tag_id, parent_ids);
@@ -124,9 +207,30 @@ impl TagRegistry {
         result
     }
 
-    /// 获取标签 ID
+    /// 获取标签 ID（别名解析到新标签；未注册的标签名返回 None）
     pub fn get_id(&self, name: &str) -> Option<u32> {
-        self.name_to_id.get(name).copied()
+        self.name_to_id.get(name).or_else(|| self.aliases.get(name)).copied()
+    }
+
+    /// 诊断标签名：已弃用的别名或未注册的标签名
+    ///
+    /// 注册表由多次计算共享，查询本身不记录状态，由调用方按需收集
+    pub fn diagnose(&self, name: &str) -> Option<TagDiagnostic> {
+        if self.name_to_id.contains_key(name) {
+            return None;
+        }
+        match self.aliases.get(name) {
+            Some(&id) => Some(TagDiagnostic::DeprecatedAlias {
+                alias: name.to_string(),
+                target: self.get_name(id).unwrap_or_default().to_string(),
+            }),
+            None => Some(TagDiagnostic::UnknownTag { name: name.to_string() }),
+        }
+    }
+
+    /// 加载数据时产生的诊断信息
+    pub fn diagnostics(&self) -> &[TagDiagnostic] {
+        &self.diagnostics
     }
 
     /// 获取标签名
@@ -185,6 +289,24 @@ impl TagRegistry {
     pub fn max_id(&self) -> u32 {
         self.max_id
     }
+
+    /// 数据版本（手动构建的注册表为空）
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// 源数据校验和（手动构建的注册表为 0）
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// 数据快照标识（写入计算结果）
+    pub fn data_version(&self) -> DataVersion {
+        DataVersion {
+            version: self.version.clone(),
+            checksum: format!("{:016x}", self.checksum),
+        }
+    }
 }
 
 /// 标签集合操作
@@ -227,7 +349,7 @@ impl TagSet {
 
     /// 检查是否包含任一指定标签
     pub fn contains_any(&self, tags: &FixedBitSet) -> bool {
-        !self.bits.intersection(tags).next().is_none()
+        self.bits.intersection(tags).next().is_some()
     }
 
     /// 合并另一个集合
@@ -264,20 +386,76 @@ impl Clone for TagSet {
     }
 }
 
+/// 武器基底类型前缀 → 武器类别标签
+///
+/// 基底类型按 `_` 分隔的首段匹配，如 `sword_1h_phys` → `Tag_Sword`
+pub const WEAPON_CLASS_TAGS: &[(&str, &str)] = &[
+    ("sword", "Tag_Sword"),
+    ("axe", "Tag_Axe"),
+    ("mace", "Tag_Mace"),
+    ("dagger", "Tag_Dagger"),
+    ("claw", "Tag_Claw"),
+    ("wand", "Tag_Wand"),
+    ("staff", "Tag_Staff"),
+    ("bow", "Tag_Bow"),
+    ("crossbow", "Tag_Crossbow"),
+];
+
+/// 根据基底类型查询武器类别标签
+pub fn weapon_class_tag(base_type: &str) -> Option<&'static str> {
+    let prefix = base_type.split('_').next().unwrap_or(base_type);
+    WEAPON_CLASS_TAGS
+        .iter()
+        .find(|(p, _)| *p == prefix)
+        .map(|(_, tag)| *tag)
+}
+
+/// 根据已装备的武器推导武器标签
+///
+/// - 主手武器：类别标签 + 单手/双手标签
+/// - 副手同样是武器时：双持标签
+pub fn weapon_tags(items: &[ItemData]) -> Vec<String> {
+    let Some(main) = items.iter().find(|i| i.slot == SlotType::WeaponMain) else {
+        return Vec::new();
+    };
+    let Some(class) = weapon_class_tag(&main.base_type) else {
+        return Vec::new();
+    };
+
+    let mut tags = vec![class.to_string()];
+    if main.is_two_handed {
+        tags.push("Tag_TwoHanded".to_string());
+    } else {
+        tags.push("Tag_OneHanded".to_string());
+        let dual_wield = items
+            .iter()
+            .any(|i| i.slot == SlotType::WeaponOff && weapon_class_tag(&i.base_type).is_some());
+        if dual_wield {
+            tags.push("Tag_DualWield".to_string());
+        }
+    }
+    tags
+}
+
 /// 上下文标签管理器
+#[derive(Clone)]
 pub struct ContextTags {
     /// 当前活动的标签集合
     active: TagSet,
-    /// 标签注册表引用
-    registry: TagRegistry,
+    /// 作用域栈（进入作用域前的标签集合，退出时恢复）
+    scopes: Vec<FixedBitSet>,
+    /// 标签注册表（共享引用）
+    registry: Arc<TagRegistry>,
 }
 
 impl ContextTags {
     /// 创建新的上下文标签管理器
-    pub fn new(registry: TagRegistry) -> Self {
+    pub fn new(registry: impl Into<Arc<TagRegistry>>) -> Self {
+        let registry = registry.into();
         let capacity = (registry.max_id() + 1) as usize;
         Self {
             active: TagSet::empty(capacity),
+            scopes: Vec::new(),
             registry,
         }
     }
@@ -300,6 +478,71 @@ impl ContextTags {
         self.inject_skill_tags(injected_tags);
     }
 
+    /// 当前激活标签的名称（含继承展开与已注入的标签）
+    pub fn active_tag_names(&self) -> Vec<String> {
+        self.active
+            .iter()
+            .filter_map(|id| self.registry.get_name(id).map(str::to_string))
+            .collect()
+    }
+
+    /// 注入辅助技能的条件标签
+    ///
+    /// 条件对当前技能标签集合（含继承展开与已注入的标签）评估，
+    /// 应在所有无条件标签注入之后调用；条件解析失败视为不满足
+    pub fn inject_conditional_tags(&mut self, conditional_tags: &[ConditionalTag]) {
+        if conditional_tags.is_empty() {
+            return;
+        }
+        let eval_ctx = EvalContext::new().with_tags(&self.active_tag_names());
+
+        let granted: Vec<String> = conditional_tags
+            .iter()
+            .filter(|t| {
+                Condition::parse(&t.condition)
+                    .map(|cond| cond.evaluate(&eval_ctx))
+                    .unwrap_or(false)
+            })
+            .map(|t| t.tag.clone())
+            .collect();
+        self.inject_skill_tags(&granted);
+    }
+
+    /// 进入临时作用域：之后注入的标签在 [`pop_scope`](Self::pop_scope) 时撤销
+    pub fn push_scope(&mut self) {
+        self.scopes.push(self.active.bits().clone());
+    }
+
+    /// 退出临时作用域，恢复进入前的标签集合（没有作用域时返回 false）
+    pub fn pop_scope(&mut self) -> bool {
+        match self.scopes.pop() {
+            Some(bits) => {
+                self.active = TagSet::from_bitset(bits);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 当前作用域深度
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// 在临时作用域中注入指定阶段的标签并执行闭包，返回后标签自动撤销
+    pub fn with_phase<R>(&mut self, phase_tags: &[PhaseTag], phase: CalcPhase, f: impl FnOnce(&Self) -> R) -> R {
+        self.push_scope();
+        let tags: Vec<String> = phase_tags
+            .iter()
+            .filter(|t| t.phase == phase)
+            .map(|t| t.tag.clone())
+            .collect();
+        self.inject_skill_tags(&tags);
+        let result = f(self);
+        self.pop_scope();
+        result
+    }
+
     /// 根据上下文标志注入状态标签
     pub fn inject_context_flags(&mut self, flags: &HashMap<String, bool>) {
         // 状态标签映射
@@ -328,8 +571,33 @@ impl ContextTags {
         }
     }
 
+    /// 根据上下文数值/标志派生状态标签
+    ///
+    /// 按注册表中标签的 `condition`（如 `enemy_range <= 2`）评估，
+    /// 只有条件引用的键全部由上下文提供时才参与判定，未提供的状态不会被推断
+    pub fn inject_derived_tags(&mut self, flags: &HashMap<String, bool>, values: &HashMap<String, f64>) {
+        let eval_ctx = EvalContext::from_context(flags, values);
+        let registry = Arc::clone(&self.registry);
+        for (id, condition) in registry.derived_tags() {
+            let keys = condition.context_keys();
+            let provided = !keys.is_empty()
+                && keys.iter().all(|k| flags.contains_key(*k) || values.contains_key(*k));
+            if provided && condition.evaluate(&eval_ctx) {
+                self.active.insert(*id);
+                if let Some(expanded) = registry.get_expanded_set(*id) {
+                    self.active.union_with_bits(expanded);
+                }
+            }
+        }
+    }
+
     /// 检查修正是否满足条件
-    pub fn matches_requirements(&self, requirements: &[u32]) -> bool {
+    ///
+    /// 需求标签须全部激活，排除标签（如"非双持时"的双持标签）不能有任一激活
+    pub fn matches_requirements(&self, requirements: &[u32], excluded: &[u32]) -> bool {
+        if excluded.iter().any(|&id| self.active.contains(id)) {
+            return false;
+        }
         if requirements.is_empty() {
             return true;
         }
@@ -413,11 +681,133 @@ mod tests {
         ctx.inject_skill_tags(&["Tag_Fire".to_string()]);
         
         // Fire 技能应该满足 Elemental 需求
-        assert!(ctx.matches_requirements(&[20])); // Elemental
+        assert!(ctx.matches_requirements(&[20], &[])); // Elemental
         // 也应该满足 Damage 需求
-        assert!(ctx.matches_requirements(&[1])); // Damage
+        assert!(ctx.matches_requirements(&[1], &[])); // Damage
         // 但不应该满足 Physical 需求
-        assert!(!ctx.matches_requirements(&[10])); // Physical
+        assert!(!ctx.matches_requirements(&[10], &[])); // Physical
+        // 排除标签：非物理 满足，非元素 不满足（继承展开后同样排除）
+        assert!(ctx.matches_requirements(&[], &[10]));
+        assert!(!ctx.matches_requirements(&[1], &[20]));
+    }
+
+    #[test]
+    fn test_scoped_phase_tags() {
+        let registry = create_test_registry();
+        let mut ctx = ContextTags::new(registry);
+        ctx.inject_skill_tags(&["Tag_Physical".to_string()]);
+
+        let phase_tags = [PhaseTag {
+            tag: "Tag_Fire".to_string(),
+            phase: CalcPhase::Conversion,
+        }];
+
+        // 转化阶段可见 Fire（及其继承的 Elemental），修正阶段不可见
+        let in_conversion = ctx.with_phase(&phase_tags, CalcPhase::Conversion, |c| c.matches_requirements(&[20], &[]));
+        let in_modification = ctx.with_phase(&phase_tags, CalcPhase::Modification, |c| c.matches_requirements(&[20], &[]));
+        assert!(in_conversion);
+        assert!(!in_modification);
+
+        // 作用域退出后恢复原标签
+        assert_eq!(ctx.scope_depth(), 0);
+        assert!(!ctx.active_set().contains(21));
+        assert!(ctx.active_set().contains(10));
+        assert!(!ctx.pop_scope());
     }
-}
 
+    #[test]
+    fn test_conditional_tag_injection() {
+        let mut registry = create_test_registry();
+        registry.register("Tag_Cold".to_string(), 22);
+        registry.set_parents(22, vec![20]);
+        registry.precompute_expanded_sets();
+        let registry = Arc::new(registry);
+
+        let conversion = [ConditionalTag {
+            tag: "Tag_Cold".to_string(),
+            condition: "has_tag(\"Tag_Fire\")".to_string(),
+        }];
+
+        let mut fire = ContextTags::new(Arc::clone(&registry));
+        fire.inject_skill_tags(&["Tag_Fire".to_string()]);
+        fire.inject_conditional_tags(&conversion);
+        assert!(fire.active_set().contains(22));
+
+        let mut phys = ContextTags::new(Arc::clone(&registry));
+        phys.inject_skill_tags(&["Tag_Physical".to_string()]);
+        phys.inject_conditional_tags(&conversion);
+        assert!(!phys.active_set().contains(22));
+    }
+
+    #[test]
+    fn test_alias_and_diagnostics() {
+        let json = r#"{
+            "_aliases": { "Tag_Burning": "Tag_Fire" },
+            "Tag_Damage": { "id": 1, "category": "Identity", "parents": [], "displayName": "伤害" },
+            "Tag_Fire": { "id": 21, "category": "Identity", "parents": ["Tag_Damage"], "displayName": "火焰" },
+            "Tag_Ignite": { "id": 40, "category": "Identity", "parents": ["Tag_Burning"], "displayName": "点燃" }
+        }"#;
+        let registry = TagRegistry::from_json(json).unwrap();
+
+        // 别名解析到同一 ID，父级引用别名同样生效
+        assert_eq!(registry.get_id("Tag_Burning"), Some(21));
+        assert!(registry.get_expanded_set(40).unwrap().contains(21));
+
+        assert_eq!(registry.get_id("Tag_Missing"), None);
+        assert_eq!(
+            registry.diagnose("Tag_Burning"),
+            Some(TagDiagnostic::DeprecatedAlias {
+                alias: "Tag_Burning".to_string(),
+                target: "Tag_Fire".to_string(),
+            })
+        );
+        assert_eq!(
+            registry.diagnose("Tag_Missing"),
+            Some(TagDiagnostic::UnknownTag { name: "Tag_Missing".to_string() })
+        );
+        assert_eq!(registry.diagnose("Tag_Fire"), None);
+        // 查询不改变共享注册表的状态
+        assert!(registry.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_registry_version_and_checksum() {
+        let json = r#"{ "_meta": { "version": "1.2.0" }, "Tag_Fire": { "id": 21, "category": "Identity", "displayName": "火焰" } }"#;
+        let a = TagRegistry::from_json(json).unwrap();
+        let b = TagRegistry::from_json(json).unwrap();
+        assert_eq!(a.version(), "1.2.0");
+        assert_eq!(a.checksum(), b.checksum());
+        assert_eq!(a.data_version().checksum.len(), 16);
+
+        // 数据变化后校验和随之变化
+        let changed = TagRegistry::from_json(&json.replace("火焰", "火")).unwrap();
+        assert_ne!(a.checksum(), changed.checksum());
+    }
+
+    #[test]
+    fn test_derived_tags_from_context_values() {
+        let json = r#"{
+            "Tag_State_Low_Life": { "id": 700, "category": "State", "displayName": "低血", "condition": "life_percent <= 0.35" },
+            "Tag_State_Enemy_Near": { "id": 727, "category": "State", "displayName": "近距离", "condition": "enemy_range <= 2" },
+            "Tag_State_Enemy_Far": { "id": 728, "category": "State", "displayName": "远距离", "condition": "enemy_range > 2" }
+        }"#;
+        let registry = Arc::new(TagRegistry::from_json(json).unwrap());
+
+        let values: HashMap<String, f64> = [("enemy_range".to_string(), 1.5)].into_iter().collect();
+        let mut ctx = ContextTags::new(Arc::clone(&registry));
+        ctx.inject_derived_tags(&HashMap::new(), &values);
+
+        assert!(ctx.active_set().contains(727));
+        assert!(!ctx.active_set().contains(728));
+        // 未提供 life_percent，不推断低血状态
+        assert!(!ctx.active_set().contains(700));
+    }
+
+    #[test]
+    fn test_weapon_class_tag_from_base_type() {
+        assert_eq!(weapon_class_tag("sword_1h_phys"), Some("Tag_Sword"));
+        assert_eq!(weapon_class_tag("crossbow_2h"), Some("Tag_Crossbow"));
+        assert_eq!(weapon_class_tag("claw"), Some("Tag_Claw"));
+        assert_eq!(weapon_class_tag("shield_str"), None);
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! 使用 ts-rs 导出 TypeScript 类型绑定
 
+use crate::i18n::{render_default, MessageParams};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use ts_rs::TS;
@@ -11,7 +12,7 @@ use ts_rs::TS;
 // ============================================================
 
 /// 计算器主输入结构
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../bindings/")]
 pub struct CalculatorInput {
     /// 动态上下文标志 (如 "is_moving": true, "enemy_shocked": true)
@@ -37,8 +38,10 @@ pub struct CalculatorInput {
     pub support_skills: Vec<SkillData>,
     
     /// 全局属性覆盖 (天赋盘/手动输入)
+    ///
+    /// 值可为数值，或引用其他属性的表达式（如 `"crit.chance": "fighting_will * 0.002"`）
     #[serde(default)]
-    pub global_overrides: HashMap<String, f64>,
+    pub global_overrides: HashMap<String, OverrideValue>,
     
     /// 预览槽位 (用于 Diff 计算)
     #[serde(default)]
@@ -51,6 +54,487 @@ pub struct CalculatorInput {
     /// 机制定义（从数据库预加载）
     #[serde(default)]
     pub mechanic_definitions: Vec<MechanicDefinition>,
+
+    /// 增伤总和低于 -100% 时的处理策略
+    #[serde(default)]
+    pub increased_floor: IncreasedFloor,
+    
+    /// 光环技能（属性受光环效果加成缩放）
+    #[serde(default)]
+    pub auras: Vec<SkillData>,
+    
+    /// 是否将无法辅助主技能的辅助技能排除出聚合（否则仅给出警告）
+    #[serde(default)]
+    pub exclude_invalid_supports: bool,
+
+    /// 机制层数取值模式
+    #[serde(default)]
+    pub stack_mode: StackMode,
+
+    /// 近期事件模型（未设置时"近期命中/暴击/击杀"完全由 context_flags 手动指定）
+    #[serde(default)]
+    pub recent_model: Option<RecentModel>,
+
+    /// 强制关闭的机制 ID（即使由装备/技能授予也不生效）
+    #[serde(default)]
+    pub disabled_mechanics: Vec<String>,
+
+    /// 输出数值格式（小数位、舍入方式、百分比刻度）
+    #[serde(default)]
+    pub output_options: OutputOptions,
+
+    /// 持续输出模型（未设置时持续 DPS 等于理论 DPS）
+    #[serde(default)]
+    pub sustain_model: Option<SustainModel>,
+
+    /// 严格模式：未知的 `mod.*` / `conv.*` / `extra.*` 属性键视为错误（否则仅给出诊断）
+    #[serde(default)]
+    pub strict_stat_keys: bool,
+
+    /// 越界输入（负的攻击时间、超过 100% 的格挡率/暴击率等）的处理策略
+    #[serde(default)]
+    pub sanitize_policy: SanitizePolicy,
+
+    /// 槽位定义（覆盖或新增内置槽位，如赛季槽位）
+    #[serde(default)]
+    pub slot_definitions: Vec<SlotDefinition>,
+
+    /// 槽位冲突的处理策略（被移除的装备记入诊断）
+    #[serde(default)]
+    pub slot_conflict_policy: SlotConflictPolicy,
+
+    /// 套装定义（按装备的 `set_id` 统计件数）
+    #[serde(default)]
+    pub item_sets: Vec<ItemSetDefinition>,
+
+    /// 角色配置（按等级与职业推导基础生命/魔力，未提供时不计入）
+    #[serde(default)]
+    pub character: Option<CharacterConfig>,
+
+    /// 是否将需求未满足的装备排除出聚合（否则仅给出诊断）
+    #[serde(default)]
+    pub exclude_unusable_items: bool,
+
+    /// 命名的全局覆盖分组（配置预设，按 `enabled` 逐次开关，启用的分组与 `global_overrides` 叠加）
+    #[serde(default)]
+    pub override_groups: Vec<OverrideGroup>,
+
+    /// 字符串上下文 (如 "weapon_type": "claw", "stance": "offensive")，用于 `key == "value"` 条件
+    #[serde(default)]
+    pub context_strings: HashMap<String, String>,
+}
+
+/// 全局属性覆盖值
+///
+/// 表达式按 `condition_ast::ValueExpr` 语法解析，标识符取机制层数、上下文数值或聚合后的属性最终值；
+/// 全部表达式基于同一聚合快照求值，彼此不引用对方结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(untagged)]
+pub enum OverrideValue {
+    /// 固定数值
+    Number(f64),
+    /// 数值表达式
+    Expression(String),
+}
+
+impl From<f64> for OverrideValue {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+/// 命名的全局覆盖分组（如 "bossing buffs"、"map mods"）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct OverrideGroup {
+    /// 分组名称（须唯一）
+    pub name: String,
+    /// 是否启用（默认启用）
+    #[serde(default = "default_group_enabled")]
+    pub enabled: bool,
+    /// 分组内的属性覆盖（语法同 `global_overrides`）
+    #[serde(default)]
+    pub overrides: HashMap<String, OverrideValue>,
+}
+
+fn default_group_enabled() -> bool { true }
+
+/// 角色配置
+///
+/// 基础生命与魔力按 `character_table.json` 的职业成长推导
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct CharacterConfig {
+    /// 角色等级
+    #[serde(default = "default_character_level")]
+    pub level: u32,
+    /// 职业 ID（berserker / mage / ranger，须在角色成长表中登记；未指定时使用通用成长）
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+fn default_character_level() -> u32 { 1 }
+
+/// 近期事件模型
+///
+/// 按技能使用速率、命中率、暴击率推导 `recently_hit` / `recently_crit` / `recently_killed`：
+/// 事件按泊松过程发生，窗口内至少发生一次的概率 = 1 - e^(-每秒事件数 × 窗口)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct RecentModel {
+    /// "近期"窗口（秒）
+    #[serde(default = "default_recent_window")]
+    pub window: f64,
+
+    /// 技能循环中实际使用该技能的时间占比（0-1）
+    #[serde(default = "default_rotation_uptime")]
+    pub rotation_uptime: f64,
+
+    /// 每次命中击杀敌人的概率
+    #[serde(default)]
+    pub kill_chance_per_hit: f64,
+
+    /// 概率达到该阈值时视为状态成立
+    #[serde(default = "default_recent_threshold")]
+    pub threshold: f64,
+}
+
+fn default_recent_window() -> f64 { 4.0 }
+fn default_rotation_uptime() -> f64 { 1.0 }
+fn default_recent_threshold() -> f64 { 0.5 }
+
+impl Default for RecentModel {
+    fn default() -> Self {
+        Self {
+            window: default_recent_window(),
+            rotation_uptime: default_rotation_uptime(),
+            kill_chance_per_hit: 0.0,
+            threshold: default_recent_threshold(),
+        }
+    }
+}
+
+impl RecentModel {
+    /// 窗口内至少发生一次事件的概率
+    pub fn event_probability(&self, events_per_second: f64) -> f64 {
+        if events_per_second <= 0.0 || self.window <= 0.0 {
+            return 0.0;
+        }
+        1.0 - (-events_per_second * self.window).exp()
+    }
+
+    /// 推导近期状态概率：(上下文标志, 概率)
+    pub fn probabilities(&self, rate: f64, hit_chance: f64, crit_chance: f64) -> [(&'static str, f64); 3] {
+        let hits = rate.max(0.0) * hit_chance.clamp(0.0, 1.0) * self.rotation_uptime.clamp(0.0, 1.0);
+        [
+            ("recently_hit", self.event_probability(hits)),
+            ("recently_crit", self.event_probability(hits * crit_chance.clamp(0.0, 1.0))),
+            ("recently_killed", self.event_probability(hits * self.kill_chance_per_hit.clamp(0.0, 1.0))),
+        ]
+    }
+}
+
+/// 机制层数取值模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum StackMode {
+    /// 使用 `MechanicState.current_stacks`
+    #[default]
+    Manual,
+    /// 按当前上限满层计算
+    Max,
+    /// 按获取速率与持续时间估算稳态平均层数（未配置时回退为手动层数）
+    Realistic,
+}
+
+/// 增伤总和下限策略
+///
+/// 作用于伤害、速度、暴击的 `1 + sum(increased)` 计算
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum IncreasedFloor {
+    /// 钳制在 -100%，乘数最低为 0
+    #[default]
+    ClampZero,
+    /// 允许为负（乘数可为负值，用于调试极端配置）
+    AllowNegative,
+}
+
+impl IncreasedFloor {
+    /// 按策略处理增伤总和
+    pub fn apply(self, total_inc: f64) -> f64 {
+        match self {
+            IncreasedFloor::ClampZero => total_inc.max(-1.0),
+            IncreasedFloor::AllowNegative => total_inc,
+        }
+    }
+}
+
+/// 越界输入处理策略
+///
+/// 作用于装备属性与技能数据（主技能、辅助、光环），每次处理均记入诊断
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum SanitizePolicy {
+    /// 钳制到合理范围
+    #[default]
+    Clamp,
+    /// 视为输入错误
+    Reject,
+    /// 保留原值，仅给出诊断
+    Keep,
+}
+
+/// 槽位冲突处理策略
+///
+/// 冲突包括槽位容量已满、冲突组已被其他槽位占用、双手武器与副手互斥。
+/// 预览装备总是替换其所在槽位的装备，该替换不视为冲突
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum SlotConflictPolicy {
+    /// 预览装备优先，其次双手武器，其余按录入顺序先到先得
+    #[default]
+    PreferPreview,
+    /// 存在冲突时视为输入错误
+    Error,
+    /// 保留理论 DPS 更高的一方（按上述顺序逐件比较）
+    PreferHigherDps,
+}
+
+/// 持续输出模型
+///
+/// 持续 DPS = 理论 DPS（增益按稳态层数）× 技能循环占比 × 魔力可持续比例。
+/// 冷却已在速率计算中限制；技能循环占比取 `recent_model.rotation_uptime`（未设置为 1）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct SustainModel {
+    /// 每秒可用于该技能的魔力回复（未设置时不计魔力限制）
+    #[serde(default)]
+    pub mana_regen: Option<f64>,
+
+    /// 增益是否按稳态平均层数计算（等价于 `StackMode::Realistic`）
+    #[serde(default = "default_realistic_buffs")]
+    pub realistic_buffs: bool,
+}
+
+fn default_realistic_buffs() -> bool { true }
+
+impl Default for SustainModel {
+    fn default() -> Self {
+        Self {
+            mana_regen: None,
+            realistic_buffs: default_realistic_buffs(),
+        }
+    }
+}
+
+impl SustainModel {
+    /// 魔力可持续比例（0-1）
+    ///
+    /// 比例 = 魔力回复 / (单次消耗 × 每秒使用次数)，不超过 1；未设置回复或无消耗时为 1
+    pub fn mana_factor(&self, mana_cost: f64, uses_per_second: f64) -> f64 {
+        let Some(regen) = self.mana_regen else {
+            return 1.0;
+        };
+        let spend = mana_cost * uses_per_second;
+        if spend <= 0.0 {
+            return 1.0;
+        }
+        (regen.max(0.0) / spend).min(1.0)
+    }
+}
+
+/// 输出数值格式
+///
+/// 统一作用于 `CalculatorOutput` 的全部数值：先按百分比刻度换算比例类字段，再按小数位舍入。
+/// 默认不舍入、比例类字段为 0–1，与未设置时的输出一致
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct OutputOptions {
+    /// 保留小数位（None 为不舍入）
+    #[serde(default)]
+    pub decimal_places: Option<u32>,
+    /// 舍入方式
+    #[serde(default)]
+    pub rounding: RoundingMode,
+    /// 比例类字段（暴击率、暴击伤害、命中率、增伤总和及其来源）的刻度
+    #[serde(default)]
+    pub percent_scale: PercentScale,
+}
+
+/// 舍入方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// 四舍五入（0.5 远离零）
+    #[default]
+    HalfUp,
+    /// 银行家舍入（0.5 取偶）
+    HalfEven,
+    /// 向下取整
+    Floor,
+    /// 向上取整
+    Ceil,
+    /// 向零截断
+    Truncate,
+}
+
+/// 比例类字段刻度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum PercentScale {
+    /// 0–1（如暴击率 0.25）
+    #[default]
+    Fraction,
+    /// 0–100（如暴击率 25.0）
+    Percent,
+}
+
+/// 以比例表示、受 `PercentScale` 影响的乘区来源
+const PERCENT_ZONE_SOURCES: [&str; 3] = ["increased", "crit", "hit"];
+
+impl OutputOptions {
+    /// 按小数位与舍入方式处理单个数值
+    pub fn round(&self, value: f64) -> f64 {
+        let Some(places) = self.decimal_places else {
+            return value;
+        };
+        if !value.is_finite() {
+            return value;
+        }
+
+        let factor = 10f64.powi(places.min(15) as i32);
+        let scaled = value * factor;
+        let rounded = match self.rounding {
+            RoundingMode::HalfUp => scaled.round(),
+            RoundingMode::HalfEven => scaled.round_ties_even(),
+            RoundingMode::Floor => scaled.floor(),
+            RoundingMode::Ceil => scaled.ceil(),
+            RoundingMode::Truncate => scaled.trunc(),
+        };
+        rounded / factor
+    }
+
+    /// 按百分比刻度换算比例值（0–1）
+    pub fn percent(&self, fraction: f64) -> f64 {
+        match self.percent_scale {
+            PercentScale::Fraction => fraction,
+            PercentScale::Percent => fraction * 100.0,
+        }
+    }
+
+    /// 将格式应用到计算结果
+    pub fn apply(&self, output: &mut CalculatorOutput) {
+        if *self == Self::default() {
+            return;
+        }
+
+        output.crit_chance = self.percent(output.crit_chance);
+        output.crit_multiplier = self.percent(output.crit_multiplier);
+        output.hit_chance = self.percent(output.hit_chance);
+        for weapon in &mut output.weapon_summaries {
+            weapon.crit_chance = self.percent(weapon.crit_chance);
+        }
+        output.damage_breakdown.total_increased = self.percent(output.damage_breakdown.total_increased);
+        for zone in PERCENT_ZONE_SOURCES {
+            if let Some(sources) = output.damage_breakdown.multipliers.zone_sources.get_mut(zone) {
+                for source in sources {
+                    source.value = self.percent(source.value);
+                }
+            }
+        }
+        for source in &mut output.damage_breakdown.multipliers.inactive_sources {
+            if source.label_key == "zone.increased" {
+                source.value = self.percent(source.value);
+            }
+        }
+
+        if self.decimal_places.is_none() {
+            return;
+        }
+
+        for value in [
+            &mut output.dps_theoretical,
+            &mut output.dps_effective,
+            &mut output.dps_sustained,
+            &mut output.hit_damage,
+            &mut output.rate,
+            &mut output.crit_chance,
+            &mut output.crit_multiplier,
+            &mut output.hit_chance,
+            &mut output.mana_cost,
+            &mut output.ehp_series.physical,
+            &mut output.ehp_series.fire,
+            &mut output.ehp_series.cold,
+            &mut output.ehp_series.lightning,
+            &mut output.ehp_series.chaos,
+        ] {
+            *value = self.round(*value);
+        }
+
+        for weapon in &mut output.weapon_summaries {
+            for value in [
+                &mut weapon.phys_range.min,
+                &mut weapon.phys_range.max,
+                &mut weapon.elemental_average,
+                &mut weapon.attack_speed,
+                &mut weapon.crit_chance,
+                &mut weapon.phys_dps,
+                &mut weapon.elemental_dps,
+                &mut weapon.total_dps,
+            ] {
+                *value = self.round(*value);
+            }
+        }
+
+        let breakdown = &mut output.damage_breakdown;
+        for value in breakdown.by_type.values_mut() {
+            *value = self.round(*value);
+        }
+        breakdown.base_damage = self.round(breakdown.base_damage);
+        breakdown.total_increased = self.round(breakdown.total_increased);
+        breakdown.total_more = self.round(breakdown.total_more);
+        for damage in breakdown.after_conversion.values_mut() {
+            damage.damage = self.round(damage.damage);
+        }
+        for range in breakdown.ranges.values_mut() {
+            range.min = self.round(range.min);
+            range.max = self.round(range.max);
+        }
+
+        let zones = &mut breakdown.multipliers;
+        for value in [
+            &mut zones.base_damage_zone,
+            &mut zones.increased_zone,
+            &mut zones.more_zone,
+            &mut zones.less_zone,
+            &mut zones.crit_zone,
+            &mut zones.speed_zone,
+            &mut zones.hit_zone,
+            &mut zones.defense_zone,
+            &mut zones.resistance_zone,
+            &mut zones.vulnerability_zone,
+            &mut zones.mechanics_zone,
+        ] {
+            *value = self.round(*value);
+        }
+        for source in zones.zone_sources.values_mut().flatten().chain(&mut zones.inactive_sources) {
+            source.value = self.round(source.value);
+        }
+
+        for entry in &mut output.debug_trace {
+            for value in entry.values.values_mut() {
+                *value = self.round(*value);
+            }
+        }
+    }
 }
 
 /// 预览槽位
@@ -88,6 +572,28 @@ pub struct TargetConfig {
     /// 闪避值
     #[serde(default)]
     pub evasion: u32,
+    
+    /// 敌人身上的减益（诅咒、曝露、感电、地图词缀等）
+    #[serde(default)]
+    pub debuffs: Vec<TargetDebuff>,
+}
+
+/// 目标减益
+///
+/// 属性键为敌人侧键（不带 `target.` 前缀），如 `{"res.fire": -0.2}` 表示火焰抗性 -20%
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct TargetDebuff {
+    /// 减益 ID
+    pub id: String,
+    
+    /// 减益分类 (curse, exposure, shock, map_mod)
+    #[serde(default)]
+    pub category: String,
+    
+    /// 属性效果
+    #[serde(default)]
+    pub stats: HashMap<String, f64>,
 }
 
 fn default_level() -> u32 { 100 }
@@ -101,12 +607,16 @@ impl Default for TargetConfig {
             generic_dr: 0.0,
             armor: 0,
             evasion: 0,
+            debuffs: Vec::new(),
         }
     }
 }
 
 /// 槽位类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+///
+/// 已知槽位为固定变体；其余槽位（契灵、赛季槽位等）按 ID 录入为 `Custom`，
+/// 容量与冲突规则由槽位注册表（`SlotRegistry`）给出，未登记的 `Custom` 槽位在计算时视为无效输入
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../bindings/")]
 #[serde(rename_all = "snake_case")]
 pub enum SlotType {
@@ -126,6 +636,77 @@ pub enum SlotType {
     Memory4,
     Memory5,
     Memory6,
+    /// 数据驱动的槽位 ID（如 `pact_spirit_1`）
+    #[serde(untagged)]
+    Custom(String),
+}
+
+impl SlotType {
+    /// 槽位 ID（与序列化形式一致）
+    pub fn id(&self) -> &str {
+        match self {
+            Self::WeaponMain => "weapon_main",
+            Self::WeaponOff => "weapon_off",
+            Self::Helmet => "helmet",
+            Self::Chest => "chest",
+            Self::Gloves => "gloves",
+            Self::Boots => "boots",
+            Self::Amulet => "amulet",
+            Self::Ring1 => "ring1",
+            Self::Ring2 => "ring2",
+            Self::Belt => "belt",
+            Self::Memory1 => "memory1",
+            Self::Memory2 => "memory2",
+            Self::Memory3 => "memory3",
+            Self::Memory4 => "memory4",
+            Self::Memory5 => "memory5",
+            Self::Memory6 => "memory6",
+            Self::Custom(id) => id,
+        }
+    }
+}
+
+/// 槽位定义
+///
+/// 内置定义见 `src/data/slots_registry.json`，调用方可通过 `CalculatorInput.slot_definitions` 覆盖或新增
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct SlotDefinition {
+    /// 槽位 ID（已知槽位与 `SlotType` 的序列化形式一致）
+    pub id: String,
+
+    /// 显示名称
+    #[serde(default)]
+    pub display_name: String,
+
+    /// 单个槽位可装备的件数
+    #[serde(default = "default_slot_capacity")]
+    pub capacity: u32,
+
+    /// 冲突组：同组槽位同时只能有一个被占用（先录入者优先，预览装备优先于现有装备）
+    #[serde(default)]
+    pub conflict_group: Option<String>,
+
+    /// 装备双手武器时不可用（如副手）
+    #[serde(default)]
+    pub blocked_by_two_handed: bool,
+}
+
+fn default_slot_capacity() -> u32 {
+    1
+}
+
+impl SlotDefinition {
+    /// 未登记槽位的默认定义（容量 1、无冲突组）
+    pub fn fallback(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            display_name: String::new(),
+            capacity: default_slot_capacity(),
+            conflict_group: None,
+            blocked_by_two_handed: false,
+        }
+    }
 }
 
 // ============================================================
@@ -150,7 +731,7 @@ pub struct ItemData {
     pub is_two_handed: bool,
     
     /// 基底固有属性（来自 items_meta，如护甲基底值 1777）
-    /// 这是装备基底本身的属性，与暗金词缀分开
+    /// 这是装备基底本身的属性，与暗金词缀分开；未提供的键按 `base_type` 从内嵌基底表补全
     #[serde(default)]
     pub base_implicit_stats: HashMap<String, f64>,
     
@@ -174,10 +755,88 @@ pub struct ItemData {
     /// 是否为侵蚀状态
     #[serde(default)]
     pub is_corrupted: bool,
+
+    /// 所属套装 ID（关联 `CalculatorInput.item_sets`）
+    #[serde(default)]
+    pub set_id: Option<String>,
+
+    /// 装备品质（%），每 1% 品质提高 1% 该装备的护甲/护盾/闪避（武器为物理伤害）
+    #[serde(default)]
+    pub quality: u32,
+
+    /// 装备需求（未提供时按 `base_type` 从基底表补全）
+    #[serde(default)]
+    pub requirements: Option<ItemRequirements>,
+}
+
+impl ItemData {
+    /// 创建装备（其余字段取反序列化时的默认值）
+    pub fn new(id: &str, base_type: &str, slot: SlotType) -> Self {
+        Self {
+            id: id.to_string(),
+            base_type: base_type.to_string(),
+            slot,
+            is_two_handed: false,
+            base_implicit_stats: HashMap::new(),
+            implicit_stats: HashMap::new(),
+            affixes: Vec::new(),
+            tags: Vec::new(),
+            is_unique: false,
+            is_corrupted: false,
+            set_id: None,
+            quality: 0,
+            requirements: None,
+        }
+    }
+}
+
+/// 装备需求（角色等级与核心属性）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ItemRequirements {
+    /// 需求等级
+    #[serde(default)]
+    pub level: u32,
+    /// 力量需求
+    #[serde(default)]
+    pub strength: u32,
+    /// 敏捷需求
+    #[serde(default)]
+    pub dexterity: u32,
+    /// 智慧需求
+    #[serde(default)]
+    pub intelligence: u32,
+}
+
+/// 套装定义
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ItemSetDefinition {
+    /// 套装 ID
+    pub id: String,
+
+    /// 显示名称
+    #[serde(default)]
+    pub display_name: String,
+
+    /// 各档套装加成（已装备件数达到要求的各档均生效）
+    #[serde(default)]
+    pub bonuses: Vec<SetBonus>,
+}
+
+/// 套装加成档位
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct SetBonus {
+    /// 所需件数
+    pub pieces: u32,
+
+    /// 加成属性
+    pub stats: HashMap<String, f64>,
 }
 
 /// 词缀数据
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../bindings/")]
 pub struct AffixData {
     /// 词缀 ID
@@ -200,15 +859,79 @@ pub struct AffixData {
     #[serde(default)]
     pub requirements: Vec<String>,
     
+    /// 排除标签（上下文含任一标签时不生效，如"非双持时"）
+    #[serde(default)]
+    pub excluded_tags: Vec<String>,
+    
+    /// 生效条件表达式（如 `is_moving && life_percent <= 0.35`）
+    #[serde(default)]
+    pub condition: Option<String>,
+    
     /// 是否为局部属性
     #[serde(default)]
     pub is_local: bool,
+
+    /// 词缀等级（数值越大越高；同组冲突时保留等级最高者）
+    #[serde(default)]
+    pub tier: u32,
+
+    /// 限定职业（如英雄专属词缀，角色职业不符时不生效）
+    #[serde(default)]
+    pub required_class: Option<String>,
+
+    /// 条件分支（如"满血时 40% 更多伤害，否则 15%"）
+    ///
+    /// 聚合时按顺序取首个条件满足的分支替换 `stats`，均不满足时使用 `stats`
+    #[serde(default)]
+    pub branches: Vec<AffixBranch>,
+}
+
+/// 词缀条件分支
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct AffixBranch {
+    /// 分支条件表达式（聚合阶段评估；引用 `stat(...)` 的条件在输入校验时拒绝）
+    pub condition: String,
+
+    /// 条件满足时的属性效果
+    pub stats: HashMap<String, f64>,
 }
 
 // ============================================================
 // 技能数据
 // ============================================================
 
+/// 计算阶段（用于阶段性标签）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../bindings/")]
+pub enum CalcPhase {
+    /// 额外获得与转化
+    Conversion,
+    /// Inc/More 修正
+    Modification,
+}
+
+/// 仅在某一计算阶段生效的标签
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct PhaseTag {
+    /// 标签名
+    pub tag: String,
+    /// 生效阶段
+    pub phase: CalcPhase,
+}
+
+/// 条件注入标签
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ConditionalTag {
+    /// 注入的标签
+    pub tag: String,
+    /// 注入条件，对技能标签集合评估（如 `has_tag("Tag_Lightning")`）
+    pub condition: String,
+}
+
 /// 技能数据
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../bindings/")]
@@ -235,7 +958,7 @@ pub struct SkillData {
     #[serde(default)]
     pub base_damage: HashMap<String, f64>,
     
-    /// 基础时间（秒）
+    /// 基础时间（秒；攻击技能装备武器时由武器攻击时间取代）
     #[serde(default = "default_base_time")]
     pub base_time: f64,
     
@@ -263,10 +986,50 @@ pub struct SkillData {
     #[serde(default)]
     pub injected_tags: Vec<String>,
     
-    /// 辅助技能：魔力倍率
+    /// 辅助技能：条件注入的标签（如"闪电转冰冷"仅在技能造成闪电伤害时注入冰冷标签）
+    #[serde(default)]
+    pub conditional_tags: Vec<ConditionalTag>,
+    
+    /// 辅助技能：主技能须具备的全部标签（如近战辅助要求 Tag_Melee）
+    #[serde(default)]
+    pub support_requirements: Vec<String>,
+    
+    /// 辅助技能：主技能不能具备的标签
+    #[serde(default)]
+    pub support_excluded_tags: Vec<String>,
+    
+    /// 仅在特定计算阶段生效的标签（技能各部分标签不同时使用）
+    #[serde(default)]
+    pub phase_tags: Vec<PhaseTag>,
+    
+    /// 技能品质（%）
+    #[serde(default)]
+    pub quality: u32,
+    
+    /// 每 1% 品质提供的属性（总品质含装备的"+X% 技能品质"）
+    #[serde(default)]
+    pub quality_stats: HashMap<String, f64>,
+    
+    /// 技能部分（如初始命中 / 爆炸、光束 / 爆发），各部分基础伤害与标签不同
+    #[serde(default)]
+    pub parts: Vec<SkillPart>,
+    
+    /// 选中的技能部分索引，None 表示按权重合并所有部分
+    #[serde(default)]
+    pub selected_part: Option<usize>,
+    
+    /// 辅助技能：魔力倍率（1 级）
     #[serde(default = "default_mana_multiplier")]
     pub mana_multiplier: f64,
     
+    /// 辅助技能：每级魔力倍率变化（按 等级 - 1 线性增长）
+    #[serde(default)]
+    pub mana_multiplier_per_level: f64,
+    
+    /// 辅助技能：每级额外属性（在 1 级 `stats` 基础上按 等级 - 1 线性增长，如每级 +1% 总伤害）
+    #[serde(default)]
+    pub stats_per_level: HashMap<String, f64>,
+    
     /// 技能等级数据 (1-20级详细数据，可选)
     #[serde(default)]
     pub level_data: Option<SkillLevelData>,
@@ -276,6 +1039,126 @@ pub struct SkillData {
     pub scaling_rules: Vec<SkillScalingRule>,
 }
 
+impl Default for SkillData {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            skill_type: SkillType::Active,
+            damage_type: None,
+            is_attack: false,
+            level: default_skill_level(),
+            base_damage: HashMap::new(),
+            base_time: default_base_time(),
+            cooldown: None,
+            mana_cost: 0,
+            effectiveness: default_effectiveness(),
+            tags: Vec::new(),
+            stats: HashMap::new(),
+            injected_tags: Vec::new(),
+            conditional_tags: Vec::new(),
+            support_requirements: Vec::new(),
+            support_excluded_tags: Vec::new(),
+            phase_tags: Vec::new(),
+            quality: 0,
+            quality_stats: HashMap::new(),
+            parts: Vec::new(),
+            selected_part: None,
+            mana_multiplier: default_mana_multiplier(),
+            mana_multiplier_per_level: 0.0,
+            stats_per_level: HashMap::new(),
+            level_data: None,
+            scaling_rules: Vec::new(),
+        }
+    }
+}
+
+impl SkillData {
+    /// 按等级缩放后的属性：`stats + stats_per_level × (等级 - 1)`
+    pub fn scaled_stats(&self) -> HashMap<String, f64> {
+        let steps = self.level.saturating_sub(1) as f64;
+        let mut stats = self.stats.clone();
+        for (key, per_level) in &self.stats_per_level {
+            *stats.entry(key.clone()).or_insert(0.0) += per_level * steps;
+        }
+        stats
+    }
+
+    /// 按等级缩放后的魔力倍率
+    pub fn scaled_mana_multiplier(&self) -> f64 {
+        let steps = self.level.saturating_sub(1) as f64;
+        self.mana_multiplier + self.mana_multiplier_per_level * steps
+    }
+
+    /// 展开指定技能部分，得到可直接计算的单一技能
+    ///
+    /// 部分的基础伤害与伤害倍率覆盖技能（含等级数据）的对应值，
+    /// 标签追加到技能标签，属性与技能属性叠加；索引越界时返回 None
+    pub fn resolve_part(&self, index: usize) -> Option<SkillData> {
+        let part = self.parts.get(index)?;
+        let mut skill = self.clone();
+        skill.parts = Vec::new();
+        skill.selected_part = None;
+
+        if !part.base_damage.is_empty() {
+            skill.base_damage = part.base_damage.clone();
+            // 部分的基础伤害优先于等级数据（含内嵌等级表）
+            let effectiveness = skill.effectiveness;
+            let level_data = skill.level_data.get_or_insert_with(|| SkillLevelData {
+                effectiveness,
+                base_damage: HashMap::new(),
+                mana_cost: None,
+                base_time: None,
+                extra_effects: HashMap::new(),
+                stats: HashMap::new(),
+            });
+            level_data.base_damage = part.base_damage.clone();
+        }
+        if let Some(effectiveness) = part.effectiveness {
+            skill.effectiveness = effectiveness;
+            if let Some(level_data) = &mut skill.level_data {
+                level_data.effectiveness = effectiveness;
+            }
+        }
+        for tag in &part.tags {
+            if !skill.tags.contains(tag) {
+                skill.tags.push(tag.clone());
+            }
+        }
+        for (key, value) in &part.stats {
+            *skill.stats.entry(key.clone()).or_insert(0.0) += value;
+        }
+        Some(skill)
+    }
+}
+
+/// 技能部分
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct SkillPart {
+    /// 部分 ID（如 "hit"、"explosion"）
+    pub id: String,
+    
+    /// 基础伤害（为空时沿用技能本身的基础伤害）
+    #[serde(default)]
+    pub base_damage: HashMap<String, f64>,
+    
+    /// 伤害倍率（覆盖技能的 Damage Effectiveness）
+    #[serde(default)]
+    pub effectiveness: Option<f64>,
+    
+    /// 该部分追加的标签（如爆炸部分的 Tag_AOE）
+    #[serde(default)]
+    pub tags: Vec<String>,
+    
+    /// 该部分额外属性
+    #[serde(default)]
+    pub stats: HashMap<String, f64>,
+    
+    /// 合并计算时的权重（如每次施放触发的次数）
+    #[serde(default = "default_part_weight")]
+    pub weight: f64,
+}
+
 /// 技能等级数据
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../bindings/")]
@@ -326,6 +1209,7 @@ fn default_skill_level() -> u32 { 1 }
 fn default_base_time() -> f64 { 1.0 }
 fn default_effectiveness() -> f64 { 1.0 }
 fn default_mana_multiplier() -> f64 { 1.0 }
+fn default_part_weight() -> f64 { 1.0 }
 
 /// 技能类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
@@ -408,6 +1292,34 @@ pub struct MechanicDefinition {
     /// 描述
     #[serde(default)]
     pub description: String,
+    
+    /// 每层效果是否受光环效果加成（祝福类增益）
+    #[serde(default)]
+    pub aura_scalable: bool,
+
+    /// 层数获取速率（层/秒），用于估算稳态平均层数
+    #[serde(default)]
+    pub generation_rate: Option<f64>,
+
+    /// 每层持续时间（秒）
+    #[serde(default)]
+    pub duration: Option<f64>,
+
+    /// 互斥组：同组机制同时只有一个生效（冲突处理见 `MechanicsProcessor`）
+    #[serde(default)]
+    pub exclusive_group: Option<String>,
+
+    /// 资源型机制每次使用技能的消耗量（如怒火），与 `generation_rate` 一起估算可持续比例
+    #[serde(default)]
+    pub cost_per_use: Option<f64>,
+
+    /// 每次命中获得的层数（稳态层数按计算出的命中速率估算）
+    #[serde(default)]
+    pub stacks_per_hit: Option<f64>,
+
+    /// 每次击杀获得的层数
+    #[serde(default)]
+    pub stacks_per_kill: Option<f64>,
 }
 
 impl Default for MechanicDefinition {
@@ -420,7 +1332,63 @@ impl Default for MechanicDefinition {
             default_max_stacks: 4,
             base_effect_per_stack: HashMap::new(),
             description: String::new(),
+            aura_scalable: false,
+            generation_rate: None,
+            duration: None,
+            exclusive_group: None,
+            cost_per_use: None,
+            stacks_per_hit: None,
+            stacks_per_kill: None,
+        }
+    }
+}
+
+impl MechanicDefinition {
+    /// 稳态平均层数
+    ///
+    /// 每层独立计时：平均层数 = 获取速率 × 持续时间，不超过 `max_stacks`；
+    /// 未配置获取速率或持续时间时返回 `None`
+    pub fn average_stacks(&self, max_stacks: u32) -> Option<f64> {
+        self.steady_state_stacks(max_stacks, 0.0, 0.0)
+    }
+
+    /// 含命中/击杀叠层的稳态平均层数
+    ///
+    /// 总获取速率 = 获取速率 + 每次命中层数 × 每秒命中 + 每次击杀层数 × 每秒击杀；
+    /// 未配置持续时间或任何获取方式时返回 `None`
+    pub fn steady_state_stacks(
+        &self,
+        max_stacks: u32,
+        hits_per_second: f64,
+        kills_per_second: f64,
+    ) -> Option<f64> {
+        let duration = self.duration.filter(|d| *d > 0.0)?;
+        let generation = self.generation_rate.filter(|r| *r > 0.0);
+        if generation.is_none() && self.stacks_per_hit.is_none() && self.stacks_per_kill.is_none() {
+            return None;
+        }
+        let rate = generation.unwrap_or(0.0)
+            + self.stacks_per_hit.unwrap_or(0.0) * hits_per_second.max(0.0)
+            + self.stacks_per_kill.unwrap_or(0.0) * kills_per_second.max(0.0);
+        Some((rate * duration).clamp(0.0, max_stacks as f64))
+    }
+
+    /// 是否按命中/击杀叠层
+    pub fn stacks_on_events(&self) -> bool {
+        self.stacks_per_hit.is_some() || self.stacks_per_kill.is_some()
+    }
+
+    /// 消耗型资源的可持续比例（0-1）
+    ///
+    /// 比例 = 获取速率 / (每次消耗 × 每秒使用次数)，不超过 1；
+    /// 未配置消耗或使用速率为 0 时返回 `None`
+    pub fn sustainable_uptime(&self, uses_per_second: f64) -> Option<f64> {
+        let cost = self.cost_per_use.filter(|c| *c > 0.0)?;
+        if uses_per_second <= 0.0 {
+            return None;
         }
+        let generation = self.generation_rate.unwrap_or(0.0).max(0.0);
+        Some((generation / (cost * uses_per_second)).min(1.0))
     }
 }
 
@@ -437,6 +1405,10 @@ pub struct CalculatorOutput {
     
     /// 有效 DPS (考虑命中、抗性等)
     pub dps_effective: f64,
+
+    /// 持续 DPS（受魔力、技能循环占比与增益稳态层数限制，见 `SustainModel`）
+    #[serde(default)]
+    pub dps_sustained: f64,
     
     /// 单次命中伤害
     pub hit_damage: f64,
@@ -462,6 +1434,188 @@ pub struct CalculatorOutput {
     /// 调试追踪（标签匹配溯源）
     #[serde(default)]
     pub debug_trace: Vec<TraceEntry>,
+    
+    /// 计算所用的数据快照（标签注册表版本与校验和）
+    #[serde(default)]
+    pub data_version: DataVersion,
+
+    /// 计算引擎版本（crate 版本）
+    #[serde(default)]
+    pub engine_version: String,
+
+    /// 计算输入指纹（16 位十六进制，跨平台稳定，见 `pipeline::input_hash`）
+    #[serde(default)]
+    pub input_hash: String,
+    
+    /// 技能魔力消耗（含辅助技能魔力倍率）
+    #[serde(default)]
+    pub mana_cost: f64,
+    
+    /// 诊断（非致命问题，如辅助技能无法辅助主技能、未知属性键、数值触及上限）
+    #[serde(default)]
+    pub warnings: Vec<Diagnostic>,
+
+    /// 缓存溯源（仅经 `CachedCalculator` 返回的结果）
+    #[serde(default)]
+    pub cache_info: Option<CacheInfo>,
+
+    /// 各把已装备武器的面板 DPS（与主技能无关，用于比较武器）
+    #[serde(default)]
+    pub weapon_summaries: Vec<WeaponSummary>,
+}
+
+/// 武器面板 DPS
+///
+/// 仅由该武器自身的局部属性（基底、隐性词缀、词缀、品质）计算，不含全局加成与技能倍率
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct WeaponSummary {
+    /// 装备 ID
+    pub item_id: String,
+    /// 装备槽位
+    pub slot: SlotType,
+    /// 物理伤害范围（已应用局部增伤与品质）
+    pub phys_range: DamageRange,
+    /// 元素伤害平均值（火焰 + 冰冷 + 闪电）
+    pub elemental_average: f64,
+    /// 攻击速度（次/秒）
+    pub attack_speed: f64,
+    /// 局部暴击率
+    pub crit_chance: f64,
+    /// 物理 DPS
+    pub phys_dps: f64,
+    /// 元素 DPS
+    pub elemental_dps: f64,
+    /// 总 DPS（物理 + 元素）
+    pub total_dps: f64,
+}
+
+/// 缓存溯源
+///
+/// 标明结果来自哪一层缓存，用于排查预览结果过期问题
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct CacheInfo {
+    /// 结果缓存是否命中
+    pub result_hit: bool,
+    /// PreparedContext 缓存是否命中（未经过上下文缓存时为 false）
+    pub context_hit: bool,
+    /// 复用的缓存层（`result` / `context`）
+    pub reused_layers: Vec<String>,
+    /// 输入缓存键指纹（16 位十六进制）
+    pub cache_key: String,
+}
+
+/// 诊断类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticCode {
+    /// 辅助技能无法辅助主技能
+    InvalidSupport,
+    /// 机制被互斥组中的其他机制压制
+    MechanicSuppressed,
+    /// 命中/击杀叠层迭代未收敛
+    StacksNotConverged,
+    /// 输入值超出合理范围
+    ValueOutOfRange,
+    /// 装备因槽位冲突被移除
+    SlotConflict,
+    /// 同一装备上的同组词缀被移除
+    AffixGroupConflict,
+    /// 词缀限定的职业与角色职业不符，已移除
+    ClassRestricted,
+    /// 装备的等级/属性需求未满足
+    RequirementUnmet,
+    /// 属性键别名已映射为规范键
+    StatKeyAliased,
+    /// 未知属性键（写入后不参与任何计算）
+    UnknownStatKey,
+    /// 比例类数值按 0–100 约定录入，已换算为 0–1
+    UnitNormalized,
+    /// 转化率超过 100%，低优先级转化被缩放
+    ConversionScaled,
+    /// 暴击率超出 0–100%，已截断
+    CritCapped,
+    /// 敌人抗性超过上限，已截断
+    ResistanceCapped,
+    /// 标签名为已弃用的别名（仍解析到新标签）
+    DeprecatedTag,
+    /// 未注册的标签名（不参与标签匹配）
+    UnknownTag,
+    /// 条件或表达式引用了未定义的标识符（按 0 / false 计算）
+    UnknownConditionKey,
+    /// 属性键驻留表已满，新键按字符串比较（结果不变，查询变慢）
+    StatKeyOverflow,
+}
+
+impl DiagnosticCode {
+    /// 类别键（与序列化名称一致）
+    pub fn as_key(&self) -> &'static str {
+        match self {
+            DiagnosticCode::InvalidSupport => "invalid_support",
+            DiagnosticCode::MechanicSuppressed => "mechanic_suppressed",
+            DiagnosticCode::StacksNotConverged => "stacks_not_converged",
+            DiagnosticCode::ValueOutOfRange => "value_out_of_range",
+            DiagnosticCode::SlotConflict => "slot_conflict",
+            DiagnosticCode::AffixGroupConflict => "affix_group_conflict",
+            DiagnosticCode::ClassRestricted => "class_restricted",
+            DiagnosticCode::RequirementUnmet => "requirement_unmet",
+            DiagnosticCode::StatKeyAliased => "stat_key_aliased",
+            DiagnosticCode::UnknownStatKey => "unknown_stat_key",
+            DiagnosticCode::UnitNormalized => "unit_normalized",
+            DiagnosticCode::ConversionScaled => "conversion_scaled",
+            DiagnosticCode::CritCapped => "crit_capped",
+            DiagnosticCode::ResistanceCapped => "resistance_capped",
+            DiagnosticCode::DeprecatedTag => "deprecated_tag",
+            DiagnosticCode::UnknownTag => "unknown_tag",
+            DiagnosticCode::UnknownConditionKey => "unknown_condition_key",
+            DiagnosticCode::StatKeyOverflow => "stat_key_overflow",
+        }
+    }
+}
+
+/// 计算诊断
+///
+/// 计算仍可完成但结果可能与预期不符的问题，`message` 按默认语言渲染
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct Diagnostic {
+    /// 诊断类别
+    pub code: DiagnosticCode,
+    /// 描述
+    pub message: String,
+    /// 描述的本地化键（见 `src/data/locales.json`）
+    #[serde(default)]
+    pub message_key: String,
+    /// 本地化参数
+    #[serde(default)]
+    pub message_params: HashMap<String, String>,
+}
+
+impl Diagnostic {
+    /// 按类别构建诊断（本地化键为 `diagnostic.<类别>`）
+    pub fn new(code: DiagnosticCode, message_params: MessageParams) -> Self {
+        let message_key = format!("diagnostic.{}", code.as_key());
+        Self {
+            code,
+            message: render_default(&message_key, &message_params),
+            message_key,
+            message_params,
+        }
+    }
+}
+
+/// 数据快照标识
+///
+/// 用于将计算结果与具体的数据版本对应，数据更新后可据此识别过期缓存
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct DataVersion {
+    /// 标签注册表版本（`_meta.version`）
+    pub version: String,
+    /// 标签注册表内容校验和（FNV-1a，16 位十六进制）
+    pub checksum: String,
 }
 
 /// EHP 系列
@@ -494,8 +1648,13 @@ pub struct MultiplierBreakdown {
     pub increased_zone: f64,
     
     /// More 乘区 (product of more multipliers)
-    /// 各 bucket 的 more 效果相乘
+    /// 各 bucket 的 more 效果相乘（含 less）
     pub more_zone: f64,
+
+    /// Less 乘区 (product of negative more multipliers)
+    /// 已包含在 more_zone 中，单独列出便于发现大幅削减
+    #[serde(default)]
+    pub less_zone: f64,
     
     /// 暴击期望区 (1 + crit_chance × crit_damage)
     pub crit_zone: f64,
@@ -519,21 +1678,64 @@ pub struct MultiplierBreakdown {
     /// 机制特殊区 (祝福、球类等机制提供的额外乘区)
     pub mechanics_zone: f64,
     
+    /// 数据驱动乘区（乘区 ID → 倍率，定义见 multiplier_zones.json）
+    #[serde(default)]
+    pub custom_zones: HashMap<String, f64>,
+    
     /// 各乘区的详细来源追踪
     #[serde(default)]
     pub zone_sources: HashMap<String, Vec<ZoneSource>>,
+
+    /// 因条件未满足而未生效的增伤/More 来源（值为满足条件时的贡献，附条件溯源）
+    #[serde(default)]
+    pub inactive_sources: Vec<ZoneSource>,
 }
 
 /// 乘区来源详情
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../bindings/")]
 pub struct ZoneSource {
-    /// 来源名称 (装备名、技能名等)
+    /// 来源显示文本（按默认语言渲染）
     pub source: String,
     /// 贡献值
     pub value: f64,
     /// 属性键
     pub stat_key: String,
+    /// 来源文本的本地化键（见 `src/data/locales.json`）
+    #[serde(default)]
+    pub label_key: String,
+    /// 本地化参数
+    #[serde(default)]
+    pub label_params: HashMap<String, String>,
+    /// 条件评估溯源（仅条件修正）
+    #[serde(default)]
+    pub condition: Option<ConditionProvenance>,
+}
+
+/// 条件评估溯源
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ConditionProvenance {
+    /// 条件表达式
+    pub condition: String,
+    /// 是否满足
+    pub passed: bool,
+    /// 决定结果的原子条件
+    pub decided_by: Vec<ConditionFactor>,
+}
+
+/// 原子条件的评估结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ConditionFactor {
+    /// 上下文键（标志、数值、标签或机制 ID）
+    pub key: String,
+    /// 实际值
+    pub actual: String,
+    /// 期望（如 `== true`、`>= 50`）
+    pub expected: String,
+    /// 是否满足
+    pub passed: bool,
 }
 
 impl Default for ZoneSource {
@@ -542,8 +1744,31 @@ impl Default for ZoneSource {
             source: String::new(),
             value: 0.0,
             stat_key: String::new(),
+            label_key: String::new(),
+            label_params: HashMap::new(),
+            condition: None,
+        }
+    }
+}
+
+impl ZoneSource {
+    /// 按本地化键构建来源（`source` 按默认语言渲染）
+    pub fn localized(label_key: &str, label_params: MessageParams, value: f64, stat_key: &str) -> Self {
+        Self {
+            source: render_default(label_key, &label_params),
+            value,
+            stat_key: stat_key.to_string(),
+            label_key: label_key.to_string(),
+            label_params,
+            condition: None,
         }
     }
+
+    /// 附加条件评估溯源
+    pub fn with_condition(mut self, condition: Option<ConditionProvenance>) -> Self {
+        self.condition = condition;
+        self
+    }
 }
 
 /// 伤害构成明细
@@ -569,6 +1794,20 @@ pub struct DamageBreakdown {
     /// 提供各计算阶段的详细乘区分解
     #[serde(default)]
     pub multipliers: MultiplierBreakdown,
+
+    /// 各伤害类型的单次命中伤害范围（已应用 Inc/More，不含暴击，与游戏面板一致）
+    #[serde(default)]
+    pub ranges: HashMap<String, DamageRange>,
+}
+
+/// 伤害范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct DamageRange {
+    /// 最小伤害
+    pub min: f64,
+    /// 最大伤害
+    pub max: f64,
 }
 
 /// 带历史标签的伤害
@@ -581,6 +1820,285 @@ pub struct DamageWithHistory {
     pub history_tags: Vec<String>,
 }
 
+/// 单个机制的 DPS 贡献（关闭该机制后重算的边际差值）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct MechanicContribution {
+    /// 机制 ID
+    pub mechanic_id: String,
+    /// 当前生效层数
+    pub stacks: f64,
+    /// 关闭该机制后的理论 DPS
+    pub dps_without: f64,
+    /// DPS 贡献（当前 DPS - 关闭后 DPS）
+    pub dps_delta: f64,
+    /// DPS 贡献百分比（相对关闭后 DPS，如 12.5 表示 +12.5%）
+    pub dps_delta_percent: f64,
+}
+
+/// 单件装备的贡献（移除该装备后重算的边际差值）
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ItemContribution {
+    /// 装备 ID
+    pub item_id: String,
+    /// 装备槽位
+    pub slot: SlotType,
+    /// 移除该装备后的理论 DPS
+    pub dps_without: f64,
+    /// DPS 贡献（当前 DPS - 移除后 DPS）
+    pub dps_delta: f64,
+    /// DPS 贡献百分比（相对移除后 DPS，如 12.5 表示 +12.5%）
+    pub dps_delta_percent: f64,
+    /// 各元素 EHP 贡献（当前 EHP - 移除后 EHP）
+    pub ehp_delta: EhpSeries,
+}
+
+/// 单个辅助技能的贡献（移除该辅助后重算的边际差值）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct SupportContribution {
+    /// 辅助技能 ID
+    pub support_id: String,
+    /// 按等级缩放后的魔力倍率
+    pub mana_multiplier: f64,
+    /// 移除该辅助后的理论 DPS
+    pub dps_without: f64,
+    /// DPS 贡献（当前 DPS - 移除后 DPS）
+    pub dps_delta: f64,
+    /// DPS 贡献百分比（相对移除后 DPS，如 12.5 表示 +12.5%）
+    pub dps_delta_percent: f64,
+    /// 魔力消耗增量（当前消耗 - 移除后消耗）
+    pub mana_cost_delta: f64,
+}
+
+/// 单项数值对比（B 相对 A）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct StatDelta {
+    /// 数值键（如 `dps_theoretical`、`crit_zone`、`fire`）
+    pub key: String,
+    /// 构筑 A 的值
+    pub a: f64,
+    /// 构筑 B 的值
+    pub b: f64,
+    /// 差值（B - A）
+    pub delta: f64,
+    /// 差值百分比（相对 A，如 12.5 表示 +12.5%；A 为 0 时为 0）
+    pub delta_percent: f64,
+}
+
+impl StatDelta {
+    /// 由两个值构建对比项
+    pub fn new(key: &str, a: f64, b: f64) -> Self {
+        Self {
+            key: key.to_string(),
+            a,
+            b,
+            delta: b - a,
+            delta_percent: if a != 0.0 { (b - a) / a.abs() * 100.0 } else { 0.0 },
+        }
+    }
+}
+
+/// 两套构筑的完整对比
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct BuildComparison {
+    /// 总览（DPS、单次伤害、速率、暴击、命中、魔力消耗）
+    pub summary: Vec<StatDelta>,
+    /// 各伤害乘区
+    pub zones: Vec<StatDelta>,
+    /// 防御（各元素 EHP）
+    pub defenses: Vec<StatDelta>,
+    /// 各伤害类型的最终伤害（按类型名排序）
+    pub damage_types: Vec<StatDelta>,
+}
+
+impl BuildComparison {
+    /// 对比两次计算结果
+    pub fn from_outputs(a: &CalculatorOutput, b: &CalculatorOutput) -> Self {
+        let summary = vec![
+            StatDelta::new("dps_theoretical", a.dps_theoretical, b.dps_theoretical),
+            StatDelta::new("dps_effective", a.dps_effective, b.dps_effective),
+            StatDelta::new("dps_sustained", a.dps_sustained, b.dps_sustained),
+            StatDelta::new("hit_damage", a.hit_damage, b.hit_damage),
+            StatDelta::new("rate", a.rate, b.rate),
+            StatDelta::new("crit_chance", a.crit_chance, b.crit_chance),
+            StatDelta::new("crit_multiplier", a.crit_multiplier, b.crit_multiplier),
+            StatDelta::new("hit_chance", a.hit_chance, b.hit_chance),
+            StatDelta::new("mana_cost", a.mana_cost, b.mana_cost),
+        ];
+
+        let (ma, mb) = (&a.damage_breakdown.multipliers, &b.damage_breakdown.multipliers);
+        let zones = vec![
+            StatDelta::new("base_damage_zone", ma.base_damage_zone, mb.base_damage_zone),
+            StatDelta::new("increased_zone", ma.increased_zone, mb.increased_zone),
+            StatDelta::new("more_zone", ma.more_zone, mb.more_zone),
+            StatDelta::new("less_zone", ma.less_zone, mb.less_zone),
+            StatDelta::new("crit_zone", ma.crit_zone, mb.crit_zone),
+            StatDelta::new("speed_zone", ma.speed_zone, mb.speed_zone),
+            StatDelta::new("hit_zone", ma.hit_zone, mb.hit_zone),
+            StatDelta::new("defense_zone", ma.defense_zone, mb.defense_zone),
+            StatDelta::new("resistance_zone", ma.resistance_zone, mb.resistance_zone),
+            StatDelta::new("vulnerability_zone", ma.vulnerability_zone, mb.vulnerability_zone),
+            StatDelta::new("mechanics_zone", ma.mechanics_zone, mb.mechanics_zone),
+        ];
+
+        let (ea, eb) = (&a.ehp_series, &b.ehp_series);
+        let defenses = vec![
+            StatDelta::new("physical", ea.physical, eb.physical),
+            StatDelta::new("fire", ea.fire, eb.fire),
+            StatDelta::new("cold", ea.cold, eb.cold),
+            StatDelta::new("lightning", ea.lightning, eb.lightning),
+            StatDelta::new("chaos", ea.chaos, eb.chaos),
+        ];
+
+        let (ta, tb) = (&a.damage_breakdown.by_type, &b.damage_breakdown.by_type);
+        let mut types: Vec<&String> = ta.keys().chain(tb.keys()).collect();
+        types.sort();
+        types.dedup();
+        let damage_types = types
+            .into_iter()
+            .map(|t| StatDelta::new(t, ta.get(t).copied().unwrap_or(0.0), tb.get(t).copied().unwrap_or(0.0)))
+            .collect();
+
+        Self {
+            summary,
+            zones,
+            defenses,
+            damage_types,
+        }
+    }
+}
+
+/// 假设分析开关类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum ToggleKind {
+    /// 上下文标志（`context_flags`，未设置视为 false）
+    Flag,
+    /// 机制激活状态（生效 ↔ 关闭）
+    Mechanic,
+    /// 全局覆盖分组（启用 ↔ 停用）
+    OverrideGroup,
+}
+
+/// 假设分析开关
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct Toggle {
+    /// 开关类型
+    pub kind: ToggleKind,
+    /// 标志名、机制 ID 或覆盖分组名称
+    pub key: String,
+}
+
+/// 假设分析的单个场景结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ToggleScenario {
+    /// 本场景相对当前配置翻转的开关（空表示当前配置）
+    pub flipped: Vec<Toggle>,
+    /// 理论 DPS
+    pub dps_theoretical: f64,
+    /// 有效 DPS
+    pub dps_effective: f64,
+    /// 相对当前配置的理论 DPS 变化百分比（如 12.5 表示 +12.5%）
+    pub dps_delta_percent: f64,
+    /// 各元素 EHP
+    pub ehp_series: EhpSeries,
+}
+
+/// 多场景对比的单个场景差异（相对基准输入）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ScenarioDelta {
+    /// 场景名称
+    pub name: String,
+    /// 覆盖的上下文标志
+    #[serde(default)]
+    pub context_flags: HashMap<String, bool>,
+    /// 覆盖的上下文数值
+    #[serde(default)]
+    pub context_values: HashMap<String, f64>,
+    /// 替换的目标配置（如 Boss / 普通怪预设）
+    #[serde(default)]
+    pub target_config: Option<TargetConfig>,
+    /// 覆盖的机制层数（机制 ID → 层数，0 表示关闭）
+    #[serde(default)]
+    pub mechanic_stacks: HashMap<String, u32>,
+}
+
+impl ScenarioDelta {
+    /// 是否只替换目标配置（可直接复用基准的预处理上下文）
+    pub fn is_target_only(&self) -> bool {
+        self.context_flags.is_empty() && self.context_values.is_empty() && self.mechanic_stacks.is_empty()
+    }
+
+    /// 将差异应用到基准输入
+    pub fn apply(&self, base: &CalculatorInput) -> CalculatorInput {
+        let mut input = base.clone();
+        input.context_flags.extend(self.context_flags.iter().map(|(k, v)| (k.clone(), *v)));
+        input.context_values.extend(self.context_values.iter().map(|(k, v)| (k.clone(), *v)));
+        if let Some(target) = &self.target_config {
+            input.target_config = target.clone();
+        }
+        for (mech_id, &stacks) in &self.mechanic_stacks {
+            if stacks > 0 {
+                input.disabled_mechanics.retain(|id| id != mech_id);
+            }
+            match input.mechanic_states.iter_mut().find(|s| &s.id == mech_id) {
+                Some(state) => {
+                    state.current_stacks = stacks;
+                    state.max_stacks = state.max_stacks.max(stacks);
+                    state.is_active = stacks > 0;
+                }
+                None => input.mechanic_states.push(MechanicState {
+                    id: mech_id.clone(),
+                    current_stacks: stacks,
+                    max_stacks: stacks,
+                    is_active: stacks > 0,
+                }),
+            }
+        }
+        input
+    }
+}
+
+/// 多场景对比的单个场景结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct ScenarioResult {
+    /// 场景名称
+    pub name: String,
+    /// 理论 DPS
+    pub dps_theoretical: f64,
+    /// 有效 DPS
+    pub dps_effective: f64,
+    /// 单次命中期望伤害
+    pub hit_damage: f64,
+    /// 相对基准输入的理论 DPS 变化百分比（如 12.5 表示 +12.5%）
+    pub dps_delta_percent: f64,
+    /// 各元素 EHP
+    pub ehp_series: EhpSeries,
+    /// 是否复用了基准输入的预处理上下文（仅替换目标配置的场景）
+    pub context_reused: bool,
+}
+
+/// 参数扫描曲线上的一个点
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../bindings/")]
+pub struct SweepPoint {
+    /// 扫描参数取值
+    pub value: f64,
+    /// 理论 DPS
+    pub dps_theoretical: f64,
+    /// 有效 DPS
+    pub dps_effective: f64,
+}
+
 /// 调试追踪条目
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../bindings/")]
@@ -592,9 +2110,29 @@ pub struct TraceEntry {
     /// 相关数值
     #[serde(default)]
     pub values: HashMap<String, f64>,
-    /// 匹配的标签
+    /// 匹配的标签（Modification 阶段为 `<伤害类型>: <属性键> [hit: <历史标签>, context: <上下文标签>]` 形式的匹配记录）
     #[serde(default)]
     pub matched_tags: Vec<String>,
+    /// 描述的本地化键（见 `src/data/locales.json`）
+    #[serde(default)]
+    pub message_key: String,
+    /// 本地化参数
+    #[serde(default)]
+    pub message_params: HashMap<String, String>,
+}
+
+impl TraceEntry {
+    /// 按本地化键构建追踪条目（`description` 按默认语言渲染）
+    pub fn localized(phase: &str, message_key: &str, message_params: MessageParams, values: HashMap<String, f64>) -> Self {
+        Self {
+            phase: phase.to_string(),
+            description: render_default(message_key, &message_params),
+            values,
+            matched_tags: vec![],
+            message_key: message_key.to_string(),
+            message_params,
+        }
+    }
 }
 
 // ============================================================
@@ -1,5 +1,6 @@
 //! 工具函数模块
 
+use serde::de::DeserializeOwned;
 use wasm_bindgen::prelude::*;
 
 /// 设置 panic hook（用于调试）
@@ -8,6 +9,52 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+/// FNV-1a 64 位哈希（跨平台、跨编译版本稳定，用于数据校验和）
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &b| (hash ^ b as u64).wrapping_mul(PRIME))
+}
+
+/// 解析 JSON，错误信息带出错字段的路径
+///
+/// 前端动态拼装输入时，`items[2].affixes[0].value: invalid type: string "12", expected f64`
+/// 比单纯的行列号更易定位；根级错误（如 JSON 语法错误）不带路径
+pub fn parse_json<T: DeserializeOwned>(json: &str) -> Result<T, String> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        if path == "." {
+            e.into_inner().to_string()
+        } else {
+            format!("{}: {}", path, e.into_inner())
+        }
+    })?;
+    deserializer.end().map_err(|e| e.to_string())?;
+    Ok(value)
+}
+
+/// 解析编译时内嵌的数据表
+///
+/// 内嵌数据表随库发布，解析失败属于构建缺陷：调试构建与测试中直接 panic；
+/// 发布构建输出错误并回退为空表，避免整个计算引擎不可用
+pub(crate) fn load_embedded<T: Default>(name: &str, parsed: Result<T, String>) -> T {
+    match parsed {
+        Ok(table) => table,
+        Err(e) => {
+            let message = format!("embedded data table '{}' failed to parse: {}", name, e);
+            if cfg!(debug_assertions) {
+                panic!("{}", message);
+            }
+            #[cfg(target_arch = "wasm32")]
+            error(&message);
+            #[cfg(not(target_arch = "wasm32"))]
+            eprintln!("{}", message);
+            T::default()
+        }
+    }
+}
+
 /// 浮点数近似比较
 pub fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
     (a - b).abs() < epsilon
@@ -64,6 +111,13 @@ macro_rules! console_log {
 mod tests {
     use super::*;
 
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "embedded data table 'broken' failed to parse")]
+    fn test_load_embedded_panics_in_debug() {
+        load_embedded::<Vec<u32>>("broken", Err("expected value".to_string()));
+    }
+
     #[test]
     fn test_approx_eq() {
         assert!(approx_eq(1.0, 1.0000001, 0.001));
@@ -77,6 +131,29 @@ mod tests {
         assert_eq!(format_number(1500000.0), "1.50M");
     }
 
+    #[test]
+    fn test_parse_json_reports_field_path() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Affix {
+            #[allow(dead_code)]
+            value: f64,
+        }
+        #[derive(Debug, serde::Deserialize)]
+        struct Item {
+            #[allow(dead_code)]
+            affixes: Vec<Affix>,
+        }
+
+        let err = parse_json::<Vec<Item>>(r#"[{ "affixes": [] }, { "affixes": [{ "value": 1 }, { "value": "12" }] }]"#)
+            .unwrap_err();
+        assert!(err.starts_with("[1].affixes[1].value: invalid type"), "{}", err);
+
+        // 根级语法错误不带路径，尾随内容同样报错
+        assert!(!parse_json::<Vec<Item>>("[").unwrap_err().starts_with('.'));
+        assert!(parse_json::<Vec<Item>>("[] []").is_err());
+        assert!(parse_json::<Vec<Item>>("[]").unwrap().is_empty());
+    }
+
     #[test]
     fn test_clamp() {
         assert_eq!(clamp(5.0, 0.0, 10.0), 5.0);
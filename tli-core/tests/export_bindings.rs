@@ -19,15 +19,56 @@ fn export_bindings() {
     CalculatorInput::export().unwrap();
     CalculatorOutput::export().unwrap();
     TargetConfig::export().unwrap();
+    TargetDebuff::export().unwrap();
     ItemData::export().unwrap();
+    ItemRequirements::export().unwrap();
+    ItemSetDefinition::export().unwrap();
+    CharacterConfig::export().unwrap();
+    OverrideValue::export().unwrap();
+    OverrideGroup::export().unwrap();
+    SetBonus::export().unwrap();
     SkillData::export().unwrap();
+    SkillPart::export().unwrap();
+    ConditionalTag::export().unwrap();
+    PhaseTag::export().unwrap();
+    CalcPhase::export().unwrap();
     AffixData::export().unwrap();
+    AffixBranch::export().unwrap();
     SlotType::export().unwrap();
+    SlotDefinition::export().unwrap();
+    SlotConflictPolicy::export().unwrap();
     SkillType::export().unwrap();
+    IncreasedFloor::export().unwrap();
+    StackMode::export().unwrap();
+    SanitizePolicy::export().unwrap();
+    RecentModel::export().unwrap();
+    SustainModel::export().unwrap();
+    OutputOptions::export().unwrap();
+    RoundingMode::export().unwrap();
+    PercentScale::export().unwrap();
     EhpSeries::export().unwrap();
     DamageBreakdown::export().unwrap();
     DamageWithHistory::export().unwrap();
+    DamageRange::export().unwrap();
     TraceEntry::export().unwrap();
+    ConditionProvenance::export().unwrap();
+    ConditionFactor::export().unwrap();
+    MechanicContribution::export().unwrap();
+    ItemContribution::export().unwrap();
+    StatDelta::export().unwrap();
+    BuildComparison::export().unwrap();
+    SupportContribution::export().unwrap();
+    ToggleKind::export().unwrap();
+    Toggle::export().unwrap();
+    ToggleScenario::export().unwrap();
+    ScenarioDelta::export().unwrap();
+    ScenarioResult::export().unwrap();
+    SweepPoint::export().unwrap();
+    DataVersion::export().unwrap();
+    CacheInfo::export().unwrap();
+    WeaponSummary::export().unwrap();
+    DiagnosticCode::export().unwrap();
+    Diagnostic::export().unwrap();
     PreviewSlot::export().unwrap();
 
     println!("TypeScript bindings exported to ../bindings/");